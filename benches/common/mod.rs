@@ -0,0 +1,133 @@
+//! Lock types shared by the benchmark targets.
+//!
+//! Every lock is driven through the [`Lock`] trait. To cover a new lock
+//! type, implement [`Subject`] for it and add it to [`for_each_subject!`].
+
+use std::sync::MutexGuard;
+use std::sync::PoisonError;
+
+use mutex::cohort::CohortLock;
+use mutex::hybrid::HybridMutex;
+use mutex::mutex::Lock;
+use mutex::mutex::RawSpinLock;
+use mutex::padded::PaddedSpinLock;
+use mutex::padded::SplitSpinLock;
+
+/// A lock the benchmarks can construct generically.
+pub trait Subject<T>: Lock<T> + Sync {
+    const NAME: &'static str;
+
+    fn new(value: T) -> Self;
+}
+
+impl<T: Send> Subject<T> for RawSpinLock<T> {
+    const NAME: &'static str = "RawSpinLock";
+
+    fn new(value: T) -> Self {
+        RawSpinLock::new(value)
+    }
+}
+
+impl<T: Send> Subject<T> for PaddedSpinLock<T> {
+    const NAME: &'static str = "PaddedSpinLock";
+
+    fn new(value: T) -> Self {
+        PaddedSpinLock::new(RawSpinLock::new(value))
+    }
+}
+
+impl<T: Send> Subject<T> for SplitSpinLock<T> {
+    const NAME: &'static str = "SplitSpinLock";
+
+    fn new(value: T) -> Self {
+        SplitSpinLock::new(value)
+    }
+}
+
+impl<T: Send> Subject<T> for HybridMutex<T> {
+    const NAME: &'static str = "HybridMutex";
+
+    fn new(value: T) -> Self {
+        HybridMutex::new(value)
+    }
+}
+
+impl<T: Send> Subject<T> for CohortLock<T> {
+    const NAME: &'static str = "CohortLock";
+
+    fn new(value: T) -> Self {
+        CohortLock::new(value)
+    }
+}
+
+pub struct StdMutex<T>(std::sync::Mutex<T>);
+
+impl<T> Lock<T> for StdMutex<T> {
+    type Guard<'a>
+        = MutexGuard<'a, T>
+    where
+        T: 'a;
+
+    fn lock(&self) -> Self::Guard<'_> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn try_lock(&self) -> Option<Self::Guard<'_>> {
+        self.0.try_lock().ok()
+    }
+}
+
+impl<T: Send> Subject<T> for StdMutex<T> {
+    const NAME: &'static str = "std::sync::Mutex";
+
+    fn new(value: T) -> Self {
+        StdMutex(std::sync::Mutex::new(value))
+    }
+}
+
+pub struct SpinMutex<T>(spin::Mutex<T>);
+
+impl<T> Lock<T> for SpinMutex<T> {
+    type Guard<'a>
+        = spin::MutexGuard<'a, T>
+    where
+        T: 'a;
+
+    fn lock(&self) -> Self::Guard<'_> {
+        self.0.lock()
+    }
+
+    fn try_lock(&self) -> Option<Self::Guard<'_>> {
+        self.0.try_lock()
+    }
+}
+
+impl<T: Send> Subject<T> for SpinMutex<T> {
+    const NAME: &'static str = "spin::Mutex";
+
+    fn new(value: T) -> Self {
+        SpinMutex(spin::Mutex::new(value))
+    }
+}
+
+/// Calls `$f::<L>(args...)` for every general-purpose lock under comparison.
+macro_rules! for_each_subject {
+    ($f:ident($($arg:expr),*)) => {
+        $f::<mutex::mutex::RawSpinLock<u64>>($($arg),*);
+        $f::<mutex::padded::PaddedSpinLock<u64>>($($arg),*);
+        $f::<mutex::hybrid::HybridMutex<u64>>($($arg),*);
+        $f::<mutex::cohort::CohortLock<u64>>($($arg),*);
+        $f::<$crate::common::StdMutex<u64>>($($arg),*);
+        $f::<$crate::common::SpinMutex<u64>>($($arg),*);
+    };
+}
+
+pub(crate) use for_each_subject;
+
+/// Pins the current thread to a core picked by `index`, if the platform
+/// supports it, so repeated runs schedule the same way.
+pub fn pin_to_core(index: usize) {
+    if let Some(cores) = core_affinity::get_core_ids().filter(|cores| !cores.is_empty()) {
+        core_affinity::set_for_current(cores[index % cores.len()]);
+    }
+}
@@ -0,0 +1,337 @@
+//! Lock microbenchmarks: `cargo bench --bench lock`.
+//!
+//! The locks under comparison are listed in the `common` module. Worker
+//! threads are pinned to cores when the platform allows it, so runs on the
+//! same machine are comparable across PRs.
+
+use std::collections::VecDeque;
+use std::hint::black_box;
+use std::sync::Barrier;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::thread;
+use std::time::Duration;
+use std::time::Instant;
+
+use criterion::BenchmarkId;
+use criterion::Criterion;
+use criterion::Throughput;
+use criterion::criterion_group;
+use criterion::criterion_main;
+use mutex::combining::FlatCombiningLock;
+use mutex::mutex::RawSpinLock;
+use mutex::mutex::enable_raw_atomics;
+use mutex::padded::PaddedSpinLock;
+use mutex::padded::SplitSpinLock;
+use mutex::raw::STRONG_CAS;
+use mutex::relax::Backoff;
+use mutex::relax::Relax;
+use mutex::relax::Spin;
+use mutex::rwlock::RwSpinLock;
+
+use crate::common::Subject;
+use crate::common::for_each_subject;
+use crate::common::pin_to_core;
+
+mod common;
+
+const THREADS: [usize; 3] = [2, 4, 8];
+
+/// Runs `op(thread_index)` `iters` times on each of `threads` pinned threads
+/// and returns the wall time from the common start to the last finish.
+fn run_threads(threads: usize, iters: u64, op: impl Fn(usize) + Sync) -> Duration {
+    let barrier = Barrier::new(threads + 1);
+    thread::scope(|s| {
+        for index in 0..threads {
+            let (barrier, op) = (&barrier, &op);
+            s.spawn(move || {
+                pin_to_core(index);
+                barrier.wait();
+                for _ in 0..iters {
+                    op(index);
+                }
+            });
+        }
+        barrier.wait();
+        let start = Instant::now();
+        // Leaving the scope joins every worker.
+        start
+    })
+    .elapsed()
+}
+
+/// The cost of the `raw_atomics_enabled()` branch: the same lock before and
+/// after raw atomics are switched on. Runs first, since the switch is one-way.
+fn gate(c: &mut Criterion) {
+    let mut group = c.benchmark_group("gate");
+    let lock = RawSpinLock::new(0u64);
+    group.bench_function("permissive", |b| b.iter(|| *black_box(&lock).lock() += 1));
+    enable_raw_atomics();
+    group.bench_function("atomic", |b| b.iter(|| *black_box(&lock).lock() += 1));
+    group.finish();
+}
+
+fn uncontended_one<L: Subject<u64>>(c: &mut Criterion) {
+    let lock = L::new(0);
+    c.benchmark_group("uncontended")
+        .bench_function(L::NAME, |b| b.iter(|| *black_box(&lock).lock() += 1));
+}
+
+fn uncontended(c: &mut Criterion) {
+    enable_raw_atomics();
+    for_each_subject!(uncontended_one(c));
+}
+
+fn contended_one<L: Subject<u64>>(c: &mut Criterion) {
+    let mut group = c.benchmark_group("contended");
+    for threads in THREADS {
+        group.throughput(Throughput::Elements(threads as u64));
+        group.bench_with_input(
+            BenchmarkId::new(L::NAME, threads),
+            &threads,
+            |b, &threads| {
+                let lock = L::new(0);
+                b.iter_custom(|iters| run_threads(threads, iters, |_| *lock.lock() += 1));
+            },
+        );
+    }
+    group.finish();
+}
+
+fn contended(c: &mut Criterion) {
+    enable_raw_atomics();
+    for_each_subject!(contended_one(c));
+}
+
+/// One lock per thread, packed into an array: only false sharing contends.
+fn sharded_one<L: Subject<u64>>(c: &mut Criterion) {
+    let mut group = c.benchmark_group("sharded");
+    for threads in THREADS {
+        group.throughput(Throughput::Elements(threads as u64));
+        group.bench_with_input(
+            BenchmarkId::new(L::NAME, threads),
+            &threads,
+            |b, &threads| {
+                let shards: Vec<L> = (0..threads).map(|_| L::new(0)).collect();
+                b.iter_custom(|iters| {
+                    run_threads(threads, iters, |index| *shards[index].lock() += 1)
+                });
+            },
+        );
+    }
+    group.finish();
+}
+
+fn sharded(c: &mut Criterion) {
+    enable_raw_atomics();
+    sharded_one::<RawSpinLock<u64>>(c);
+    sharded_one::<PaddedSpinLock<u64>>(c);
+}
+
+/// A 256-byte payload rewritten under contention, where waiters polling a
+/// lock word on the same line slow the owner down. Also the scenario to run
+/// with and without `--features prefetch`, which shortens the miss a waiter
+/// takes on the payload right after acquiring.
+fn large_payload_one<L: Subject<[u64; 32]>>(c: &mut Criterion) {
+    let mut group = c.benchmark_group("large_payload");
+    for threads in THREADS {
+        group.throughput(Throughput::Elements(threads as u64));
+        group.bench_with_input(
+            BenchmarkId::new(L::NAME, threads),
+            &threads,
+            |b, &threads| {
+                let lock = L::new([0; 32]);
+                b.iter_custom(|iters| {
+                    run_threads(threads, iters, |_| {
+                        for word in lock.lock().iter_mut() {
+                            *word = black_box(*word + 1);
+                        }
+                    })
+                });
+            },
+        );
+    }
+    group.finish();
+}
+
+fn large_payload(c: &mut Criterion) {
+    enable_raw_atomics();
+    large_payload_one::<RawSpinLock<[u64; 32]>>(c);
+    large_payload_one::<SplitSpinLock<[u64; 32]>>(c);
+}
+
+/// A shared counter and a shared queue, each behind one hot lock: a
+/// `RawSpinLock` against a `FlatCombiningLock` running the same operations.
+fn combining(c: &mut Criterion) {
+    enable_raw_atomics();
+    let mut group = c.benchmark_group("combining");
+    for threads in THREADS {
+        group.throughput(Throughput::Elements(threads as u64));
+        group.bench_with_input(
+            BenchmarkId::new("counter/RawSpinLock", threads),
+            &threads,
+            |b, &threads| {
+                let lock = RawSpinLock::new(0u64);
+                b.iter_custom(|iters| run_threads(threads, iters, |_| *lock.lock() += 1));
+            },
+        );
+        group.bench_with_input(
+            BenchmarkId::new("counter/FlatCombiningLock", threads),
+            &threads,
+            |b, &threads| {
+                let lock = FlatCombiningLock::<u64>::new(0);
+                b.iter_custom(|iters| run_threads(threads, iters, |_| lock.apply(|n| *n += 1)));
+            },
+        );
+        // Every thread pushes and pops, so the queue stays short.
+        group.bench_with_input(
+            BenchmarkId::new("queue/RawSpinLock", threads),
+            &threads,
+            |b, &threads| {
+                let lock = RawSpinLock::new(VecDeque::with_capacity(2 * threads));
+                b.iter_custom(|iters| {
+                    run_threads(threads, iters, |index| {
+                        lock.lock().push_back(index);
+                        black_box(lock.lock().pop_front());
+                    })
+                });
+            },
+        );
+        group.bench_with_input(
+            BenchmarkId::new("queue/FlatCombiningLock", threads),
+            &threads,
+            |b, &threads| {
+                let lock = FlatCombiningLock::<_>::new(VecDeque::with_capacity(2 * threads));
+                b.iter_custom(|iters| {
+                    run_threads(threads, iters, |index| {
+                        lock.apply(|queue| queue.push_back(index));
+                        black_box(lock.apply(VecDeque::pop_front));
+                    })
+                });
+            },
+        );
+    }
+    group.finish();
+}
+
+/// Contended acquisitions through the retry CAS, labelled with its
+/// strength. Run once as is and once with `--features strong-cas` to see
+/// which form suits a target; on LL/SC cores the weak form can fail
+/// spuriously on every cache-line bounce.
+fn cas(c: &mut Criterion) {
+    enable_raw_atomics();
+    let strength = if STRONG_CAS { "strong" } else { "weak" };
+    let mut group = c.benchmark_group("cas");
+    for threads in THREADS {
+        group.throughput(Throughput::Elements(threads as u64));
+        group.bench_with_input(
+            BenchmarkId::new(format!("RawSpinLock/{strength}"), threads),
+            &threads,
+            |b, &threads| {
+                let lock = RawSpinLock::new(0u64);
+                b.iter_custom(|iters| run_threads(threads, iters, |_| *lock.lock() += 1));
+            },
+        );
+        group.bench_with_input(
+            BenchmarkId::new(format!("RwSpinLock/{strength}"), threads),
+            &threads,
+            |b, &threads| {
+                let lock = RwSpinLock::new(0u64);
+                b.iter_custom(|iters| {
+                    run_threads(threads, iters, |index| {
+                        if index % 2 == 0 {
+                            *lock.write() += 1;
+                        } else {
+                            black_box(*lock.read());
+                        }
+                    })
+                });
+            },
+        );
+    }
+    group.finish();
+}
+
+/// A read-mostly `RwSpinLock`: every thread reads, and one in 64
+/// acquisitions on thread 0 writes. Readers enter with a single increment,
+/// so this shows how they scale once they no longer fail each other's CAS.
+fn read_heavy(c: &mut Criterion) {
+    enable_raw_atomics();
+    let mut group = c.benchmark_group("read_heavy");
+    for threads in THREADS {
+        group.throughput(Throughput::Elements(threads as u64));
+        group.bench_with_input(
+            BenchmarkId::new("RwSpinLock", threads),
+            &threads,
+            |b, &threads| {
+                let lock = RwSpinLock::new(0u64);
+                b.iter_custom(|iters| {
+                    let writes = AtomicU64::new(0);
+                    run_threads(threads, iters, |index| {
+                        if index == 0 && writes.fetch_add(1, Ordering::Relaxed) % 64 == 0 {
+                            *lock.write() += 1;
+                        } else {
+                            black_box(*lock.read());
+                        }
+                    })
+                });
+            },
+        );
+    }
+    group.finish();
+}
+
+/// Two threads passing a turn back and forth, each waiting for the other
+/// through a `Backoff` with strategy `R` on the word holding the turn:
+/// long waits past the cap, as when a lock is held for a while.
+fn handoff_with<R: Relax>(iters: u64) -> Duration {
+    let turn = AtomicUsize::new(0);
+    run_threads(2, iters, |index| {
+        let mut backoff = Backoff::<R>::with_relax();
+        while turn.load(Ordering::Acquire) != index {
+            backoff.wait_on(&turn);
+        }
+        // Stay a while, so the other side backs off past the cap.
+        for _ in 0..256 {
+            std::hint::spin_loop();
+        }
+        turn.store(1 - index, Ordering::Release);
+    })
+}
+
+/// How the capped waits compare. On x86_64, `Umwait` sleeps in `umwait`
+/// where the CPU has WAITPKG, which shows here as throughput; measure power
+/// alongside with `perf stat -e power/energy-pkg/`. Without WAITPKG it
+/// falls back to the pause loop, and its label says so.
+fn relax(c: &mut Criterion) {
+    let mut group = c.benchmark_group("relax");
+    group.throughput(Throughput::Elements(2));
+    group.bench_function("Spin", |b| b.iter_custom(handoff_with::<Spin>));
+    #[cfg(target_arch = "x86_64")]
+    {
+        use mutex::relax::Umwait;
+
+        let name = if Umwait::is_supported() {
+            "Umwait"
+        } else {
+            "Umwait/fallback"
+        };
+        group.bench_function(name, |b| b.iter_custom(handoff_with::<Umwait>));
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    gate,
+    uncontended,
+    contended,
+    sharded,
+    large_payload,
+    combining,
+    cas,
+    read_heavy,
+    relax
+);
+criterion_main!(benches);
@@ -0,0 +1,246 @@
+//! Thread-scaling sweep: `cargo bench --bench scaling -- [options]`.
+//!
+//! For every lock in the `common` module and every thread count from 1 to
+//! the number of CPUs, hammers one lock for a fixed time and prints a row of
+//! threads, lock type, throughput and the 99th percentile of acquisition
+//! spins. A first row measures `RawSpinLock` in permissive mode (before raw
+//! atomics are enabled) on one thread, as the floor the other rows are
+//! compared against.
+//!
+//! Acquisitions go through `try_lock` in a loop so the failed attempts can be
+//! counted; that is what "spins" means here.
+//!
+//! Options:
+//!
+//! - `--duration <seconds>`: time per row, default 1.
+//! - `--critical-section <n>`: increments done while holding the lock,
+//!   default 1.
+//! - `--format csv|json`: output format, default `csv`.
+//!
+//! `cargo test` runs the sweep as a smoke test with a very short duration
+//! and two threads, and checks that every row made progress and that no
+//! increment was lost.
+
+use std::hint::black_box;
+use std::process;
+use std::sync::Barrier;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::thread;
+use std::time::Duration;
+use std::time::Instant;
+
+use mutex::mutex::RawSpinLock;
+use mutex::mutex::enable_raw_atomics;
+
+use crate::common::Subject;
+use crate::common::for_each_subject;
+use crate::common::pin_to_core;
+
+mod common;
+
+/// Spin counts at or above this land in the last histogram bucket.
+const SPIN_BUCKETS: usize = 1024;
+
+#[derive(Clone, Copy)]
+enum Format {
+    Csv,
+    Json,
+}
+
+struct Config {
+    duration: Duration,
+    critical_section: u64,
+    format: Format,
+    max_threads: usize,
+    smoke: bool,
+}
+
+struct Row {
+    threads: usize,
+    lock: String,
+    ops_per_sec: f64,
+    p99_spins: usize,
+}
+
+fn usage(message: &str) -> ! {
+    eprintln!("scaling: {message}");
+    eprintln!("usage: scaling [--duration <seconds>] [--critical-section <n>] [--format csv|json]");
+    process::exit(2);
+}
+
+fn parse_args() -> Config {
+    let cpus = thread::available_parallelism().map_or(1, |n| n.get());
+    let mut args = std::env::args().skip(1);
+    let mut duration = None;
+    let mut critical_section = 1;
+    let mut format = Format::Csv;
+    let mut bench = false;
+    let mut unknown = None;
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .unwrap_or_else(|| usage(&format!("{arg} needs a value")))
+        };
+        match arg.as_str() {
+            // Passed by `cargo bench`; its absence means `cargo test`.
+            "--bench" => bench = true,
+            "--duration" => {
+                let seconds = value()
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|s| s.is_finite() && *s > 0.0)
+                    .unwrap_or_else(|| usage("--duration must be a positive number"));
+                duration = Some(Duration::from_secs_f64(seconds));
+            }
+            "--critical-section" => {
+                critical_section = value()
+                    .parse()
+                    .unwrap_or_else(|_| usage("--critical-section must be an integer"));
+            }
+            "--format" => {
+                format = match value().as_str() {
+                    "csv" => Format::Csv,
+                    "json" => Format::Json,
+                    _ => usage("--format must be csv or json"),
+                };
+            }
+            _ => unknown = unknown.or(Some(arg)),
+        }
+    }
+    // Under `cargo test` the libtest flags and filters get passed through too.
+    if let (true, Some(arg)) = (bench, unknown) {
+        usage(&format!("unknown argument {arg}"));
+    }
+    let smoke = !bench && duration.is_none();
+    Config {
+        duration: duration.unwrap_or(if smoke {
+            Duration::from_millis(20)
+        } else {
+            Duration::from_secs(1)
+        }),
+        critical_section,
+        format,
+        max_threads: if smoke { 2 } else { cpus },
+        smoke,
+    }
+}
+
+/// Runs `threads` pinned workers against one `L` for `config.duration`.
+fn measure<L: Subject<u64>>(config: &Config, threads: usize, name: &str) -> Row {
+    let lock = L::new(0);
+    let stop = AtomicBool::new(false);
+    let barrier = Barrier::new(threads + 1);
+    let (elapsed, ops, histogram) = thread::scope(|s| {
+        let workers: Vec<_> = (0..threads)
+            .map(|index| {
+                let (lock, stop, barrier) = (&lock, &stop, &barrier);
+                s.spawn(move || {
+                    pin_to_core(index);
+                    let mut ops = 0u64;
+                    let mut histogram = vec![0u64; SPIN_BUCKETS];
+                    barrier.wait();
+                    while !stop.load(Ordering::Relaxed) {
+                        let mut spins = 0;
+                        let mut guard = loop {
+                            if let Some(guard) = lock.try_lock() {
+                                break guard;
+                            }
+                            spins += 1;
+                            std::hint::spin_loop();
+                        };
+                        for _ in 0..config.critical_section {
+                            *guard = black_box(*guard + 1);
+                        }
+                        drop(guard);
+                        histogram[spins.min(SPIN_BUCKETS - 1)] += 1;
+                        ops += 1;
+                    }
+                    (ops, histogram)
+                })
+            })
+            .collect();
+        barrier.wait();
+        let start = Instant::now();
+        thread::sleep(config.duration);
+        stop.store(true, Ordering::Relaxed);
+        let mut ops = 0;
+        let mut histogram = vec![0u64; SPIN_BUCKETS];
+        for worker in workers {
+            let (worker_ops, worker_histogram) = worker.join().unwrap();
+            ops += worker_ops;
+            for (total, count) in histogram.iter_mut().zip(worker_histogram) {
+                *total += count;
+            }
+        }
+        (start.elapsed(), ops, histogram)
+    });
+    if config.smoke {
+        assert!(ops > 0, "{name} on {threads} threads made no progress");
+        assert_eq!(
+            *lock.lock(),
+            ops * config.critical_section,
+            "{name} on {threads} threads lost increments"
+        );
+    }
+    Row {
+        threads,
+        lock: name.to_string(),
+        ops_per_sec: ops as f64 / elapsed.as_secs_f64(),
+        p99_spins: percentile(&histogram, ops, 0.99),
+    }
+}
+
+fn percentile(histogram: &[u64], total: u64, fraction: f64) -> usize {
+    let target = (total as f64 * fraction).ceil() as u64;
+    let mut seen = 0;
+    for (spins, count) in histogram.iter().enumerate() {
+        seen += count;
+        if seen >= target {
+            return spins;
+        }
+    }
+    histogram.len() - 1
+}
+
+fn sweep<L: Subject<u64>>(config: &Config, rows: &mut Vec<Row>) {
+    for threads in 1..=config.max_threads {
+        rows.push(measure::<L>(config, threads, L::NAME));
+    }
+}
+
+fn main() {
+    let config = parse_args();
+    let mut rows = Vec::new();
+    // Permissive locks provide no exclusion, so the baseline is one thread.
+    rows.push(measure::<RawSpinLock<u64>>(
+        &config,
+        1,
+        "RawSpinLock (permissive)",
+    ));
+    enable_raw_atomics();
+    for_each_subject!(sweep(&config, &mut rows));
+
+    match config.format {
+        Format::Csv => {
+            println!("threads,lock,ops_per_sec,p99_spins");
+            for row in &rows {
+                println!(
+                    "{},{},{:.0},{}",
+                    row.threads, row.lock, row.ops_per_sec, row.p99_spins
+                );
+            }
+        }
+        Format::Json => {
+            println!("[");
+            for (i, row) in rows.iter().enumerate() {
+                let comma = if i + 1 < rows.len() { "," } else { "" };
+                println!(
+                    "  {{\"threads\": {}, \"lock\": \"{}\", \"ops_per_sec\": {:.0}, \"p99_spins\": {}}}{comma}",
+                    row.threads, row.lock, row.ops_per_sec, row.p99_spins
+                );
+            }
+            println!("]");
+        }
+    }
+}
@@ -0,0 +1,106 @@
+// A fixed-capacity buffer shared by several producers and consumers.
+//
+// The crate has no semaphore or condition variable yet, so a full or empty
+// buffer is waited out by polling with `Backoff`.
+
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::thread;
+
+use mutex::RawSpinLock;
+use mutex::relax::Backoff;
+
+const PRODUCERS: usize = 3;
+const CONSUMERS: usize = 3;
+const CAPACITY: usize = 8;
+/// Items made by each producer.
+const ITEMS: usize = 100_000;
+const TOTAL: usize = PRODUCERS * ITEMS;
+
+struct Ring {
+    items: [usize; CAPACITY],
+    head: usize,
+    len: usize,
+}
+
+impl Ring {
+    const fn new() -> Self {
+        Self {
+            items: [0; CAPACITY],
+            head: 0,
+            len: 0,
+        }
+    }
+
+    fn push(&mut self, item: usize) -> bool {
+        if self.len == CAPACITY {
+            return false;
+        }
+        self.items[(self.head + self.len) % CAPACITY] = item;
+        self.len += 1;
+        true
+    }
+
+    fn pop(&mut self) -> Option<usize> {
+        if self.len == 0 {
+            return None;
+        }
+        let item = self.items[self.head];
+        self.head = (self.head + 1) % CAPACITY;
+        self.len -= 1;
+        Some(item)
+    }
+}
+
+static BUFFER: RawSpinLock<Ring> = RawSpinLock::new(Ring::new());
+static CONSUMED: AtomicUsize = AtomicUsize::new(0);
+
+fn produce(producer: usize) {
+    for item in producer * ITEMS..(producer + 1) * ITEMS {
+        let mut backoff = Backoff::new();
+        // The guard is a temporary, so the lock is released before waiting.
+        while !BUFFER.lock().push(item) {
+            backoff.spin();
+        }
+    }
+}
+
+/// Returns the sum of the items this consumer took.
+fn consume(seen: &[AtomicBool]) -> usize {
+    let mut sum = 0;
+    let mut backoff = Backoff::new();
+    while CONSUMED.load(Ordering::Relaxed) < TOTAL {
+        let Some(item) = BUFFER.lock().pop() else {
+            backoff.spin();
+            continue;
+        };
+        backoff = Backoff::new();
+        assert!(
+            !seen[item].swap(true, Ordering::Relaxed),
+            "{item} consumed twice"
+        );
+        CONSUMED.fetch_add(1, Ordering::Relaxed);
+        sum += item;
+    }
+    sum
+}
+
+fn main() {
+    mutex::enable_raw_atomics();
+
+    let seen: Vec<AtomicBool> = (0..TOTAL).map(|_| AtomicBool::new(false)).collect();
+    let sum: usize = thread::scope(|scope| {
+        for producer in 0..PRODUCERS {
+            scope.spawn(move || produce(producer));
+        }
+        let consumers: Vec<_> = (0..CONSUMERS)
+            .map(|_| scope.spawn(|| consume(&seen)))
+            .collect();
+        consumers.into_iter().map(|c| c.join().unwrap()).sum()
+    });
+
+    assert!(seen.iter().all(|item| item.load(Ordering::Relaxed)));
+    assert_eq!(sum, TOTAL * (TOTAL - 1) / 2);
+    println!("{TOTAL} items through a buffer of {CAPACITY}, each consumed once");
+}
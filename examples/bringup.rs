@@ -0,0 +1,151 @@
+// Mirrors how a kernel brings the crate up: the boot CPU fills static locks
+// while atomic instructions are still off limits, enables raw atomics, and
+// then starts secondary CPUs, here threads, that contend on the same locks.
+
+use std::cell::Cell;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::thread;
+
+use mutex::AtomicsEnabled;
+use mutex::RawSpinLock;
+use mutex::RwSpinLock;
+use mutex::preempt::PreemptionControl;
+use mutex::racy::RacyCell;
+
+const SECONDARY_CPUS: usize = 3;
+/// Fewer for the reduced run under `cargo test`.
+const TASKS: usize = if cfg!(test) { 3_000 } else { 30_000 };
+const FRAMES_PER_REGION: usize = 16_384;
+
+#[derive(Clone, Copy)]
+struct Region {
+    base: usize,
+    free_frames: usize,
+}
+
+struct BootInfo {
+    cpus: usize,
+    command_line: &'static str,
+}
+
+mutex::spin_static! {
+    /// Filled through `no_lock`, as the earliest boot code would.
+    static MEMORY_MAP: RawSpinLock<[Region; 4]> = [Region {
+        base: 0,
+        free_frames: 0,
+    }; 4];
+    static RUN_QUEUE: RawSpinLock<Vec<usize>> = Vec::new();
+    static BOOT_INFO: RwSpinLock<BootInfo> = BootInfo {
+        cpus: 0,
+        command_line: "",
+    };
+}
+
+/// How far the boot CPU got, kept without a lock as only it runs while the
+/// stage changes.
+static BOOT_STAGE: RacyCell<&str> = RacyCell::new("reset");
+
+/// Counts calls of the preemption hook, which real acquisitions make once
+/// raw atomics are enabled.
+struct CountingPreemption;
+
+static PREEMPT_DISABLES: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    static PREEMPT_DEPTH: Cell<usize> = const { Cell::new(0) };
+}
+
+impl PreemptionControl for CountingPreemption {
+    fn disable() {
+        PREEMPT_DISABLES.fetch_add(1, Ordering::Relaxed);
+        PREEMPT_DEPTH.set(PREEMPT_DEPTH.get() + 1);
+    }
+
+    fn enable() {
+        PREEMPT_DEPTH.set(PREEMPT_DEPTH.get() - 1);
+    }
+}
+
+fn boot_cpu() {
+    // SAFETY: only the boot CPU is running, and no other guard exists.
+    let mut map = unsafe { MEMORY_MAP.no_lock() };
+    for (i, region) in map.iter_mut().enumerate() {
+        *region = Region {
+            base: i << 32,
+            free_frames: FRAMES_PER_REGION,
+        };
+    }
+    drop(map);
+    BOOT_STAGE.write("memory map");
+
+    mutex::preempt::set_preemption_control::<CountingPreemption>().expect("hook registered twice");
+
+    RUN_QUEUE.lock().extend(0..TASKS);
+    *BOOT_INFO.write() = BootInfo {
+        cpus: 1 + SECONDARY_CPUS,
+        command_line: "console=ttyS0",
+    };
+
+    // Guards before enabling do not hold the lock, so they skip the hook.
+    assert_eq!(PREEMPT_DISABLES.load(Ordering::Relaxed), 0);
+    BOOT_STAGE.write("boot info");
+}
+
+/// Returns the sum of the tasks this CPU ran. Secondary CPUs only start
+/// once raw atomics are enabled, so they take the token and lock without
+/// checking the gate.
+fn secondary_cpu(enabled: AtomicsEnabled) -> usize {
+    let info = BOOT_INFO.read_with(enabled);
+    assert_eq!(info.cpus, 1 + SECONDARY_CPUS);
+    assert_eq!(info.command_line, "console=ttyS0");
+    drop(info);
+    // SAFETY: the stage is no longer written once secondary CPUs start;
+    // `BOOT_STAGE.read()` would panic now that raw atomics are enabled.
+    assert_eq!(unsafe { *BOOT_STAGE.get() }, "boot info");
+
+    let mut sum = 0;
+    while let Some(task) = RUN_QUEUE.lock_with(enabled).pop() {
+        let mut map = MEMORY_MAP.lock_with(enabled);
+        let region = &mut map[task % 4];
+        assert!(region.base == (task % 4) << 32, "memory map corrupted");
+        region.free_frames -= 1;
+        drop(map);
+        sum += task;
+    }
+    assert_eq!(PREEMPT_DEPTH.get(), 0, "preemption left disabled");
+    sum
+}
+
+fn main() {
+    boot_cpu();
+
+    // Paging and caches would be set up here, after which atomic
+    // instructions are allowed.
+    let enabled = mutex::enable_raw_atomics();
+
+    let sum: usize = thread::scope(|scope| {
+        let cpus: Vec<_> = (0..SECONDARY_CPUS)
+            .map(|_| scope.spawn(move || secondary_cpu(enabled)))
+            .collect();
+        cpus.into_iter().map(|cpu| cpu.join().unwrap()).sum()
+    });
+
+    assert_eq!(sum, TASKS * (TASKS - 1) / 2);
+    let free: usize = MEMORY_MAP.lock().iter().map(|r| r.free_frames).sum();
+    assert_eq!(free, 4 * FRAMES_PER_REGION - TASKS);
+    println!(
+        "{SECONDARY_CPUS} secondary CPUs ran {TASKS} tasks, {} preemption hook calls",
+        PREEMPT_DISABLES.load(Ordering::Relaxed)
+    );
+}
+
+/// The bring-up end to end, in its reduced form.
+#[cfg(test)]
+#[test]
+fn secondary_cpus_run_every_task_after_the_enable() {
+    main();
+    assert!(RUN_QUEUE.lock().is_empty());
+    // Each task takes both locks for real.
+    assert!(PREEMPT_DISABLES.load(Ordering::Relaxed) >= 2 * TASKS);
+}
@@ -0,0 +1,48 @@
+// Decodes a raw lock word, such as one read out of a memory dump:
+// `cargo run --example decode_state -- rw 0x8000000000000002`.
+
+use std::env;
+use std::process;
+
+use mutex::state::MutexState;
+use mutex::state::RwState;
+
+fn usage() -> ! {
+    eprintln!("usage: decode_state mutex|rw <value>, in hex with 0x or in decimal");
+    process::exit(2);
+}
+
+fn parse(value: &str) -> Option<usize> {
+    match value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+    {
+        Some(hex) => usize::from_str_radix(&hex.replace('_', ""), 16).ok(),
+        None => value.replace('_', "").parse().ok(),
+    }
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let [kind, value] = args.as_slice() else {
+        usage();
+    };
+    let Some(raw) = parse(value) else {
+        usage();
+    };
+    match kind.as_str() {
+        "mutex" => {
+            let Ok(raw) = u8::try_from(raw) else {
+                eprintln!("a RawSpinLock word is a single byte");
+                process::exit(2);
+            };
+            let state = MutexState::decode(raw);
+            println!("{state}\n{state:?}");
+        }
+        "rw" => {
+            let state = RwState::decode(raw);
+            println!("{state}\n{state:?}");
+        }
+        _ => usage(),
+    }
+}
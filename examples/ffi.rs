@@ -0,0 +1,121 @@
+// Shares a structure between Rust and C-style code: "C" threads reach the
+// lock only through the `extern "C"` entry points, called through function
+// pointers as C would, while Rust threads use ordinary guards on the same
+// memory.
+//
+// `cargo run --example ffi --features ffi`
+
+use std::mem::MaybeUninit;
+use std::thread;
+
+use mutex::RawSpinLock;
+use mutex::ffi::RawSpinLockFfi;
+
+const THREADS: usize = 2;
+const ROUNDS: usize = 50_000;
+
+#[repr(C)]
+struct Counters {
+    a: u64,
+    b: u64,
+}
+
+/// The C declarations, `struct rawspinlock` and a `struct shared` that
+/// embeds it. Rust shares the same memory as a `RawSpinLock<Counters>`.
+#[repr(C)]
+struct CRawSpinLock {
+    locked: u8,
+}
+
+#[repr(C)]
+struct CShared {
+    lock: CRawSpinLock,
+    data: Counters,
+}
+
+const _: () = assert!(size_of::<CRawSpinLock>() == mutex::ffi::RAWSPINLOCK_SIZE);
+const _: () = assert!(align_of::<CRawSpinLock>() == mutex::ffi::RAWSPINLOCK_ALIGN);
+
+/// The entry points as C sees them.
+struct CApi {
+    init: unsafe extern "C" fn(*mut RawSpinLockFfi),
+    lock: unsafe extern "C" fn(*mut RawSpinLockFfi),
+    try_lock: unsafe extern "C" fn(*mut RawSpinLockFfi) -> bool,
+    unlock: unsafe extern "C" fn(*mut RawSpinLockFfi),
+}
+
+const C: CApi = CApi {
+    init: mutex::ffi::rawspinlock_init,
+    lock: mutex::ffi::rawspinlock_lock,
+    try_lock: mutex::ffi::rawspinlock_try_lock,
+    unlock: mutex::ffi::rawspinlock_unlock,
+};
+
+/// What a C function taking `struct shared *` does.
+///
+/// # Safety
+///
+/// `shared` must point to an initialized `struct shared`.
+unsafe fn c_increment(shared: *mut CShared) {
+    unsafe {
+        let lock = (&raw mut (*shared).lock).cast();
+        if !(C.try_lock)(lock) {
+            (C.lock)(lock);
+        }
+        (*shared).data.a += 1;
+        (*shared).data.b += 1;
+        (C.unlock)(lock);
+    }
+}
+
+struct SharedPtr(*mut CShared);
+
+// SAFETY: only dereferenced through the lock.
+unsafe impl Send for SharedPtr {}
+unsafe impl Sync for SharedPtr {}
+
+fn main() {
+    if size_of::<CShared>() != size_of::<RawSpinLock<Counters>>() {
+        println!("the debugging features change the lock header, so C cannot share it");
+        return;
+    }
+
+    let shared = Box::into_raw(Box::new(MaybeUninit::<CShared>::uninit())).cast::<CShared>();
+    // C initializes the header and its own data.
+    unsafe {
+        (C.init)((&raw mut (*shared).lock).cast());
+        (&raw mut (*shared).data).write(Counters { a: 0, b: 0 });
+    }
+
+    // C code may run before the gate opens, on the boot core only.
+    unsafe { c_increment(shared) };
+    mutex::enable_raw_atomics();
+
+    let ptr = SharedPtr(shared);
+    let ptr = &ptr;
+    thread::scope(|scope| {
+        for _ in 0..THREADS {
+            scope.spawn(move || {
+                for _ in 0..ROUNDS {
+                    // SAFETY: initialized above, and never freed.
+                    unsafe { c_increment(ptr.0) };
+                }
+            });
+            scope.spawn(move || {
+                // SAFETY: the layout matches, as checked above.
+                let lock = unsafe { RawSpinLock::<Counters>::from_raw(ptr.0.cast()) };
+                for _ in 0..ROUNDS {
+                    let mut counters = lock.lock();
+                    counters.a += 1;
+                    counters.b += 1;
+                }
+            });
+        }
+    });
+
+    // SAFETY: the threads are done.
+    let counters = unsafe { RawSpinLock::<Counters>::from_raw(shared.cast()) }.lock();
+    let expected = (2 * THREADS * ROUNDS + 1) as u64;
+    assert_eq!((counters.a, counters.b), (expected, expected));
+    println!("{} increments from C and Rust, none lost", counters.a);
+}
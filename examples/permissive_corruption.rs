@@ -0,0 +1,109 @@
+// Shows why permissive mode is for a single core only: threads move units
+// between the two halves of a pair whose sum must stay 100, first before
+// raw atomics are enabled and then after.
+//
+// The halves are atomics accessed with relaxed loads and stores, and only
+// through shared references, so the race in the first phase breaks the
+// invariant without being undefined behaviour.
+
+use std::process;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::thread;
+
+use mutex::RawSpinLock;
+
+const THREADS: usize = 4;
+const TRANSFERS: usize = 50_000;
+const TOTAL: usize = 100;
+
+struct Pair {
+    a: AtomicUsize,
+    b: AtomicUsize,
+}
+
+impl Pair {
+    const fn new() -> Self {
+        Self {
+            a: AtomicUsize::new(TOTAL),
+            b: AtomicUsize::new(0),
+        }
+    }
+
+    fn reset(&self) {
+        self.a.store(TOTAL, Ordering::Relaxed);
+        self.b.store(0, Ordering::Relaxed);
+    }
+
+    /// Checks the invariant the lock is meant to protect.
+    fn validate(&self) -> bool {
+        let a = self.a.load(Ordering::Relaxed);
+        let b = self.b.load(Ordering::Relaxed);
+        a.checked_add(b) == Some(TOTAL)
+    }
+
+    /// Moves one unit from the fuller half to the other, in two steps that
+    /// are only safe under the lock.
+    fn transfer(&self) {
+        let (from, to) = if self.a.load(Ordering::Relaxed) > self.b.load(Ordering::Relaxed) {
+            (&self.a, &self.b)
+        } else {
+            (&self.b, &self.a)
+        };
+        let taken = from.load(Ordering::Relaxed).wrapping_sub(1);
+        from.store(taken, Ordering::Relaxed);
+        // Give other threads a chance to see the half-done transfer.
+        thread::yield_now();
+        let given = to.load(Ordering::Relaxed).wrapping_add(1);
+        to.store(given, Ordering::Relaxed);
+    }
+}
+
+static PAIR: RawSpinLock<Pair> = RawSpinLock::new(Pair::new());
+
+/// Runs the workload and returns how many invariant violations a checker
+/// saw while holding the lock, plus one if the final state is broken.
+fn run() -> usize {
+    let done = AtomicBool::new(false);
+    let violations = thread::scope(|scope| {
+        let workers: Vec<_> = (0..THREADS)
+            .map(|_| {
+                scope.spawn(|| {
+                    for _ in 0..TRANSFERS {
+                        PAIR.lock().transfer();
+                    }
+                })
+            })
+            .collect();
+        let checker = scope.spawn(|| {
+            let mut violations = 0;
+            while !done.load(Ordering::Relaxed) {
+                if !PAIR.lock().validate() {
+                    violations += 1;
+                }
+                thread::yield_now();
+            }
+            violations
+        });
+        for worker in workers {
+            worker.join().unwrap();
+        }
+        done.store(true, Ordering::Relaxed);
+        checker.join().unwrap()
+    });
+    violations + usize::from(!PAIR.lock().validate())
+}
+
+fn main() {
+    let before = run();
+    println!("before enable_raw_atomics: {before} violations (expected, the lock does nothing)");
+
+    mutex::enable_raw_atomics();
+    PAIR.lock().reset();
+    let after = run();
+    println!("after enable_raw_atomics: {after} violations");
+    if after != 0 {
+        process::exit(1);
+    }
+}
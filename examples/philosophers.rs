@@ -0,0 +1,694 @@
+// バイナリセマフォを実現する
+
+use std::process;
+use std::sync::Barrier;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::thread;
+use std::time::Duration;
+use std::time::Instant;
+
+use mutex::Lock;
+use mutex::RawSpinLock;
+use mutex::biased::BiasedSpinLock;
+use mutex::cohort::CohortLock;
+use mutex::hybrid::HybridMutex;
+use mutex::padded::SplitSpinLock;
+use mutex::relax::Backoff;
+use mutex::stress::StressReport;
+use mutex::stress::run_philosophers;
+
+const LOCKS: [&str; 5] = ["spin", "biased", "cohort", "hybrid", "split"];
+const STRATEGIES: [&str; 6] = [
+    "asymmetric",
+    "hierarchy",
+    "arbitrator",
+    "chandy-misra",
+    "naive",
+    "trylock",
+];
+
+/// How long `--force-deadlock` waits before declaring the deadlock it
+/// caused, when the deadlock detector does not report it first.
+const DEADLOCK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The seed and longest delay `--stress` runs with under the `chaos`
+/// feature, for `cargo stress-chaos`.
+#[cfg(feature = "chaos")]
+const CHAOS: (u64, usize) = (0x5eed, 256);
+
+const USAGE: &str = "\
+usage: philosophers [--philosophers N] [--iterations M] [--eat-millis T]
+                    [--lock spin|biased|cohort|hybrid|split]
+                    [--strategy asymmetric|hierarchy|arbitrator|chandy-misra|naive|trylock]
+                    [--force-deadlock]
+       philosophers --stress [--philosophers N] [--iterations M]";
+
+static ATOMIC_USIZE: AtomicUsize = AtomicUsize::new(0);
+/// The number of philosophers, for the deadlock handler.
+static PHILOSOPHERS: AtomicUsize = AtomicUsize::new(0);
+
+struct Config {
+    philosophers: usize,
+    iterations: usize,
+    eat: Duration,
+    lock: String,
+    strategy: String,
+    force_deadlock: bool,
+    /// Runs `mutex::stress` against every lock type instead.
+    stress: bool,
+}
+
+/// How philosophers avoid deadlocking on their forks.
+trait Strategy: Sync {
+    /// The order in which philosopher `i` of `n` picks up its forks, `i` on
+    /// the left and `(i + 1) % n` on the right.
+    fn order(&self, i: usize, n: usize) -> (usize, usize) {
+        (i, (i + 1) % n)
+    }
+
+    /// Picks up both forks of philosopher `i` in that order and holds them
+    /// while `eat` runs on their counts.
+    fn dine<L: Lock<usize>>(
+        &self,
+        _i: usize,
+        first: &L,
+        second: &L,
+        eat: impl FnOnce(&mut usize, &mut usize),
+    ) {
+        let mut first = first.lock();
+        let mut second = second.lock();
+        eat(&mut first, &mut second);
+    }
+
+    /// Called once philosopher `i` has eaten its last meal.
+    fn finish(&self, _i: usize) {}
+
+    /// Anything the strategy counted, for the end-of-run report.
+    fn summary(&self) -> Option<String> {
+        None
+    }
+}
+
+/// The first philosopher picks up the right fork first, everyone else the
+/// left one.
+struct Asymmetric;
+
+impl Strategy for Asymmetric {
+    fn order(&self, i: usize, n: usize) -> (usize, usize) {
+        let (left, right) = (i, (i + 1) % n);
+        if i == 0 { (right, left) } else { (left, right) }
+    }
+}
+
+/// Everyone picks up the lower-numbered fork first, so forks are always
+/// taken in one global order.
+struct Hierarchy;
+
+impl Strategy for Hierarchy {
+    fn order(&self, i: usize, n: usize) -> (usize, usize) {
+        let (left, right) = (i, (i + 1) % n);
+        (left.min(right), left.max(right))
+    }
+}
+
+/// Forks are only picked up while holding the waiter, who is released
+/// before eating.
+struct Arbitrator(RawSpinLock<()>);
+
+impl Strategy for Arbitrator {
+    fn dine<L: Lock<usize>>(
+        &self,
+        _: usize,
+        first: &L,
+        second: &L,
+        eat: impl FnOnce(&mut usize, &mut usize),
+    ) {
+        let waiter = self.0.lock();
+        let mut first = first.lock();
+        let mut second = second.lock();
+        drop(waiter);
+        eat(&mut first, &mut second);
+    }
+}
+
+/// Everyone picks up the left fork first, which can deadlock. With
+/// `--force-deadlock`, everyone waits at the barrier holding the left fork
+/// before the first meal, so it does.
+struct Naive {
+    barrier: Option<Barrier>,
+    first_meal: Vec<AtomicBool>,
+}
+
+impl Naive {
+    fn new(config: &Config) -> Self {
+        Self {
+            barrier: config
+                .force_deadlock
+                .then(|| Barrier::new(config.philosophers)),
+            first_meal: (0..config.philosophers)
+                .map(|_| AtomicBool::new(true))
+                .collect(),
+        }
+    }
+}
+
+impl Strategy for Naive {
+    fn dine<L: Lock<usize>>(
+        &self,
+        i: usize,
+        first: &L,
+        second: &L,
+        eat: impl FnOnce(&mut usize, &mut usize),
+    ) {
+        let mut first = first.lock();
+        if let Some(barrier) = &self.barrier {
+            if self.first_meal[i].swap(false, Ordering::Relaxed) {
+                barrier.wait();
+            }
+        }
+        let mut second = second.lock();
+        eat(&mut first, &mut second);
+    }
+}
+
+/// Philosophers take the first fork, then only try the second; on failure
+/// they put the first one back and retry.
+///
+/// Retrying at once can livelock: neighbours that start in step keep
+/// taking their first forks, failing on the second and releasing together.
+/// Backing off for a growing while between attempts breaks the symmetry.
+struct TryLock {
+    retries: AtomicUsize,
+}
+
+impl Strategy for TryLock {
+    fn dine<L: Lock<usize>>(
+        &self,
+        _: usize,
+        first: &L,
+        second: &L,
+        eat: impl FnOnce(&mut usize, &mut usize),
+    ) {
+        let mut backoff = Backoff::new();
+        loop {
+            let mut first = first.lock();
+            if let Some(mut second) = second.try_lock() {
+                eat(&mut first, &mut second);
+                return;
+            }
+            drop(first);
+            self.retries.fetch_add(1, Ordering::Relaxed);
+            backoff.spin();
+        }
+    }
+
+    fn summary(&self) -> Option<String> {
+        Some(format!(
+            "{} retries after a failed try_lock",
+            self.retries.load(Ordering::Relaxed)
+        ))
+    }
+}
+
+/// A fork's state under Chandy–Misra.
+struct Fork {
+    owner: usize,
+    dirty: bool,
+    /// The philosopher who does not own the fork has asked for it.
+    requested: bool,
+}
+
+/// Philosophers pass forks on request: a fork is handed over once its
+/// owner has eaten with it, so it is dirty, and a neighbour asks for it.
+/// The `L` forks are still locked while eating, but never contended.
+struct ChandyMisra {
+    forks: Vec<RawSpinLock<Fork>>,
+    finished: AtomicUsize,
+}
+
+impl ChandyMisra {
+    fn new(n: usize) -> Self {
+        Self {
+            // Each fork starts dirty with the lower-numbered of the two
+            // philosophers sharing it, which keeps the precedence graph
+            // acyclic.
+            forks: (0..n)
+                .map(|f| {
+                    RawSpinLock::new(Fork {
+                        owner: f.min((f + n - 1) % n),
+                        dirty: true,
+                        requested: false,
+                    })
+                })
+                .collect(),
+            finished: AtomicUsize::new(0),
+        }
+    }
+
+    /// Philosopher `i`'s forks, each with the neighbour sharing it.
+    fn forks_of(&self, i: usize) -> [(usize, usize); 2] {
+        let n = self.forks.len();
+        [(i, (i + n - 1) % n), ((i + 1) % n, (i + 1) % n)]
+    }
+
+    /// Hands every requested fork philosopher `i` owns, if dirty, to the
+    /// neighbour who asked. Returns how many forks `i` still owns.
+    fn answer_requests(&self, i: usize) -> usize {
+        let mut owned = 0;
+        for (fork, neighbour) in self.forks_of(i) {
+            let mut fork = self.forks[fork].lock();
+            if fork.owner != i {
+                continue;
+            }
+            if fork.requested && fork.dirty {
+                fork.owner = neighbour;
+                fork.dirty = false;
+                fork.requested = false;
+            } else {
+                owned += 1;
+            }
+        }
+        owned
+    }
+}
+
+impl Strategy for ChandyMisra {
+    fn dine<L: Lock<usize>>(
+        &self,
+        i: usize,
+        first: &L,
+        second: &L,
+        eat: impl FnOnce(&mut usize, &mut usize),
+    ) {
+        while self.answer_requests(i) < 2 {
+            for (fork, _) in self.forks_of(i) {
+                let mut fork = self.forks[fork].lock();
+                if fork.owner != i {
+                    fork.requested = true;
+                }
+            }
+            thread::yield_now();
+        }
+        {
+            let mut first = first.lock();
+            let mut second = second.lock();
+            eat(&mut first, &mut second);
+        }
+        for (fork, _) in self.forks_of(i) {
+            self.forks[fork].lock().dirty = true;
+        }
+        self.answer_requests(i);
+    }
+
+    fn finish(&self, i: usize) {
+        // Neighbours may still need this philosopher's forks.
+        self.finished.fetch_add(1, Ordering::Relaxed);
+        while self.finished.load(Ordering::Relaxed) < self.forks.len() {
+            self.answer_requests(i);
+            thread::yield_now();
+        }
+    }
+}
+
+impl Config {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut config = Config {
+            philosophers: 5,
+            iterations: 1000,
+            eat: Duration::from_millis(1),
+            lock: "spin".into(),
+            strategy: "asymmetric".into(),
+            force_deadlock: false,
+            stress: false,
+        };
+        while let Some(flag) = args.next() {
+            if flag == "--force-deadlock" {
+                config.force_deadlock = true;
+                continue;
+            }
+            if flag == "--stress" {
+                config.stress = true;
+                continue;
+            }
+            let value = args.next().ok_or(format!("{flag} needs a value"))?;
+            let number = || {
+                value
+                    .parse::<u64>()
+                    .map_err(|_| format!("invalid value for {flag}: {value}"))
+            };
+            match flag.as_str() {
+                "--philosophers" => config.philosophers = number()? as usize,
+                "--iterations" => config.iterations = number()? as usize,
+                "--eat-millis" => config.eat = Duration::from_millis(number()?),
+                "--lock" if LOCKS.contains(&value.as_str()) => config.lock = value,
+                "--lock" => return Err(format!("unknown lock type: {value}")),
+                "--strategy" if STRATEGIES.contains(&value.as_str()) => config.strategy = value,
+                "--strategy" => return Err(format!("unknown strategy: {value}")),
+                _ => return Err(format!("unknown argument: {flag}")),
+            }
+        }
+        if config.philosophers < 2 {
+            return Err("--philosophers must be at least 2".into());
+        }
+        if config.force_deadlock && config.strategy != "naive" {
+            return Err("--force-deadlock needs --strategy naive".into());
+        }
+        Ok(config)
+    }
+}
+
+struct Philosopher<'a, L>(usize, &'a L, &'a L);
+
+impl<'a, L: Lock<usize>> Philosopher<'a, L> {
+    fn new(i: usize, first: &'a L, second: &'a L) -> Self {
+        Philosopher(i, first, second)
+    }
+
+    fn eat(&self, strategy: &impl Strategy, eat: Duration) {
+        strategy.dine(self.0, self.1, self.2, |first, second| {
+            *first += 1;
+            *second += 1;
+            println!("philosopher {} eating...", self.0 + 1);
+            thread::sleep(eat);
+            println!("philosopher {} finished eating.", self.0 + 1);
+        });
+    }
+}
+
+/// What a run counted, to compare with what it should have.
+struct Outcome {
+    meals: usize,
+    elapsed: Duration,
+    /// Uses recorded in the forks' counts, under their locks.
+    fork_uses: usize,
+    /// `ATOMIC_USIZE`, incremented between meals without a lock.
+    increments: usize,
+    strategy: Option<String>,
+}
+
+impl Outcome {
+    /// Prints one PASS or FAIL line per counter. Returns whether both
+    /// counters are exact.
+    fn report(&self) -> bool {
+        let rate = self.meals as f64 / self.elapsed.as_secs_f64();
+        println!(
+            "{} meals in {:.3} s ({rate:.0} meals/s)",
+            self.meals,
+            self.elapsed.as_secs_f64()
+        );
+        if let Some(summary) = &self.strategy {
+            println!("{summary}");
+        }
+        let forks = check("fork uses (locked)", 2 * self.meals, self.fork_uses);
+        let atomic = check("ATOMIC_USIZE (atomic)", 1000 * self.meals, self.increments);
+        forks && atomic
+    }
+}
+
+fn check(name: &str, expected: usize, observed: usize) -> bool {
+    let pass = expected == observed;
+    print!(
+        "{} {name}: expected {expected}, observed {observed}",
+        if pass { "PASS" } else { "FAIL" }
+    );
+    if observed < expected {
+        print!(", lost {} updates", expected - observed);
+    }
+    println!();
+    pass
+}
+
+fn run<L: Lock<usize> + Sync>(config: &Config, new: impl Fn() -> L) -> Outcome {
+    match config.strategy.as_str() {
+        "asymmetric" => feast(config, new, &Asymmetric),
+        "hierarchy" => feast(config, new, &Hierarchy),
+        "arbitrator" => feast(config, new, &Arbitrator(RawSpinLock::new(()))),
+        "chandy-misra" => feast(config, new, &ChandyMisra::new(config.philosophers)),
+        "naive" => feast(config, new, &Naive::new(config)),
+        "trylock" => feast(
+            config,
+            new,
+            &TryLock {
+                retries: AtomicUsize::new(0),
+            },
+        ),
+        _ => unreachable!("checked by `Config::parse`"),
+    }
+}
+
+fn feast<L: Lock<usize> + Sync>(
+    config: &Config,
+    new: impl Fn() -> L,
+    strategy: &impl Strategy,
+) -> Outcome {
+    let forks: Vec<L> = (0..config.philosophers).map(|_| new()).collect();
+    let philosophers: Vec<_> = (0..config.philosophers)
+        .map(|i| {
+            let (first, second) = strategy.order(i, forks.len());
+            Philosopher::new(i, &forks[first], &forks[second])
+        })
+        .collect();
+
+    let start = Instant::now();
+    let meals: Vec<usize> = thread::scope(|scope| {
+        let handles: Vec<_> = philosophers
+            .iter()
+            .map(|p| {
+                scope.spawn(move || {
+                    let mut meals = 0;
+                    for _ in 0..config.iterations {
+                        p.eat(strategy, config.eat);
+                        meals += 1;
+                        for _ in 0..1000 {
+                            ATOMIC_USIZE.fetch_add(1, Ordering::SeqCst);
+                        }
+                    }
+                    strategy.finish(p.0);
+                    meals
+                })
+            })
+            .collect();
+        handles.into_iter().map(|h| h.join().unwrap()).collect()
+    });
+    if config.iterations > 0 {
+        for (i, &meals) in meals.iter().enumerate() {
+            assert!(meals > 0, "philosopher {} never ate", i + 1);
+        }
+    }
+    Outcome {
+        meals: meals.iter().sum(),
+        elapsed: start.elapsed(),
+        fork_uses: forks.iter().map(|fork| *fork.lock()).sum(),
+        increments: ATOMIC_USIZE.load(Ordering::SeqCst),
+        strategy: strategy.summary(),
+    }
+}
+
+fn main() {
+    let config = match Config::parse(std::env::args().skip(1)) {
+        Ok(config) => config,
+        Err(message) => {
+            eprintln!("{message}\n{USAGE}");
+            process::exit(2);
+        }
+    };
+
+    mutex::enable_raw_atomics();
+    if config.stress {
+        stress(&config);
+    }
+    println!(
+        "--- With raw atomics enabled, {} locks, {} strategy ---",
+        config.lock, config.strategy
+    );
+    if config.force_deadlock {
+        expect_deadlock(&config);
+    }
+
+    if !run_with_lock(&config).report() {
+        process::exit(1);
+    }
+}
+
+/// Runs the demo on forks of the lock type `config` names.
+fn run_with_lock(config: &Config) -> Outcome {
+    match config.lock.as_str() {
+        "spin" => run(config, || RawSpinLock::new(0)),
+        "biased" => run(config, || BiasedSpinLock::new(0)),
+        "cohort" => run(config, || CohortLock::<usize>::new(0)),
+        "hybrid" => run(config, || HybridMutex::new(0)),
+        "split" => run(config, || SplitSpinLock::new(0)),
+        _ => unreachable!("checked by `Config::parse`"),
+    }
+}
+
+/// Runs the `mutex::stress` harness against every lock type, printing one
+/// line per lock, and exits.
+fn stress(config: &Config) -> ! {
+    let (n, iterations) = (config.philosophers, config.iterations);
+    #[cfg(feature = "chaos")]
+    {
+        let (seed, max_delay_spins) = CHAOS;
+        println!("chaos: seed {seed:#x}, delays of up to {max_delay_spins} spins");
+        mutex::chaos::configure(seed, max_delay_spins);
+    }
+    let reports: [(&str, StressReport); 5] = [
+        (
+            "spin",
+            run_philosophers(n, iterations, || RawSpinLock::new(())),
+        ),
+        (
+            "biased",
+            run_philosophers(n, iterations, || BiasedSpinLock::new(())),
+        ),
+        (
+            "cohort",
+            run_philosophers(n, iterations, || CohortLock::<()>::new(())),
+        ),
+        (
+            "hybrid",
+            run_philosophers(n, iterations, || HybridMutex::new(())),
+        ),
+        (
+            "split",
+            run_philosophers(n, iterations, || SplitSpinLock::new(())),
+        ),
+    ];
+    let mut passed = true;
+    for (lock, report) in &reports {
+        println!(
+            "{} {lock}: {} acquisitions in {:.3} s, {} violations{}",
+            if report.passed() { "PASS" } else { "FAIL" },
+            report.total_acquisitions(),
+            report.elapsed.as_secs_f64(),
+            report.violations,
+            if report.timed_out { ", timed out" } else { "" }
+        );
+        passed &= report.passed();
+    }
+    process::exit(if passed { 0 } else { 1 });
+}
+
+/// Arranges for the deadlock `--force-deadlock` causes to end the demo:
+/// through the detector's report where it can see the forks, or else after
+/// [`DEADLOCK_TIMEOUT`].
+fn expect_deadlock(config: &Config) {
+    PHILOSOPHERS.store(config.philosophers, Ordering::Relaxed);
+    #[cfg(feature = "deadlock-detection")]
+    mutex::deadlock::set_deadlock_handler(report_deadlock).expect("handler registered twice");
+
+    thread::spawn(|| {
+        thread::sleep(DEADLOCK_TIMEOUT);
+        println!("deadlocked as expected, no report after {DEADLOCK_TIMEOUT:?}");
+        process::exit(0);
+    });
+}
+
+#[cfg(feature = "deadlock-detection")]
+fn report_deadlock(report: &mutex::deadlock::DeadlockReport) {
+    println!("{report}");
+    let philosophers = PHILOSOPHERS.load(Ordering::Relaxed);
+    if report.edges().len() == philosophers {
+        println!("all {philosophers} philosophers are in the cycle");
+        process::exit(0);
+    }
+    println!("expected all {philosophers} philosophers in the cycle");
+    process::exit(1);
+}
+
+/// Reduced runs of the demo, for `cargo test`.
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+
+    use super::*;
+
+    /// Far longer than a reduced run takes, short enough to fail a stuck
+    /// one.
+    const TIMEOUT: Duration = Duration::from_secs(30);
+
+    fn config(args: &[&str]) -> Config {
+        Config::parse(args.iter().map(|arg| arg.to_string())).unwrap()
+    }
+
+    #[test]
+    fn hierarchy_finishes_on_every_lock_within_a_timeout() {
+        mutex::enable_raw_atomics();
+        for lock in LOCKS {
+            let config = config(&[
+                "--strategy",
+                "hierarchy",
+                "--lock",
+                lock,
+                "--iterations",
+                "20",
+                "--eat-millis",
+                "0",
+            ]);
+            let (done, finished) = mpsc::channel();
+            // Detached, so a deadlocked run fails the test instead of
+            // hanging it.
+            thread::spawn(move || done.send(run_with_lock(&config)).unwrap());
+            let outcome = finished
+                .recv_timeout(TIMEOUT)
+                .unwrap_or_else(|_| panic!("hierarchy on {lock} forks did not finish"));
+            assert_eq!(outcome.meals, 5 * 20, "{lock}");
+            assert_eq!(outcome.fork_uses, 2 * outcome.meals, "{lock}");
+        }
+    }
+
+    #[cfg(feature = "deadlock-detection")]
+    #[test]
+    fn a_forced_deadlock_is_reported_with_every_philosopher() {
+        use std::sync::Mutex;
+
+        use mutex::deadlock::DeadlockReport;
+
+        /// The first report, as the handler saw it.
+        static REPORT: Mutex<Option<(DeadlockReport, String)>> = Mutex::new(None);
+
+        fn record(report: &DeadlockReport) {
+            REPORT
+                .lock()
+                .unwrap()
+                .get_or_insert_with(|| (*report, report.to_string()));
+        }
+
+        const N: usize = 3;
+        mutex::enable_raw_atomics();
+        mutex::deadlock::set_deadlock_handler(record).unwrap();
+        let config = config(&[
+            "--strategy",
+            "naive",
+            "--force-deadlock",
+            "--philosophers",
+            "3",
+            "--eat-millis",
+            "0",
+        ]);
+        // Never finishes; the test ends with its philosophers still stuck.
+        thread::spawn(move || run_with_lock(&config));
+
+        let start = Instant::now();
+        let (report, text) = loop {
+            if let Some(found) = REPORT.lock().unwrap().take() {
+                break found;
+            }
+            assert!(start.elapsed() < TIMEOUT, "no deadlock report");
+            thread::sleep(Duration::from_millis(10));
+        };
+        let edges = report.edges();
+        assert_eq!(edges.len(), N, "{text}");
+        for (i, edge) in edges.iter().enumerate() {
+            // Each philosopher waits for the next one's left fork.
+            assert_eq!(edge.holder, edges[(i + 1) % N].waiter, "{text}");
+            assert!(text.contains(&format!("{:#x} waits", edge.waiter.get())));
+        }
+        let mut locks: Vec<_> = edges.iter().map(|edge| edge.lock).collect();
+        locks.sort_unstable();
+        locks.dedup();
+        assert_eq!(locks.len(), N, "{text}");
+    }
+}
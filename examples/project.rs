@@ -0,0 +1,52 @@
+// Locks one field of a device's state at a time through the accessors
+// `#[derive(LockProject)]` generates: `cargo run --example project
+// --features derive`.
+
+use std::thread;
+
+use mutex::RawSpinLock;
+use mutex::RwSpinLock;
+use mutex::project::LockProject;
+
+#[derive(LockProject)]
+struct DeviceState {
+    interrupts: u64,
+    last_status: u8,
+    /// Only written at probe time, so it gets no accessor.
+    #[lock_project(skip)]
+    #[allow(dead_code)]
+    model: &'static str,
+}
+
+#[derive(LockProject)]
+struct RoutingTable {
+    routes: [u16; 4],
+}
+
+static DEVICE: RawSpinLock<DeviceState> = RawSpinLock::new(DeviceState {
+    interrupts: 0,
+    last_status: 0,
+    model: "uart16550",
+});
+static ROUTES: RwSpinLock<RoutingTable> = RwSpinLock::new(RoutingTable { routes: [0; 4] });
+
+fn main() {
+    mutex::enable_raw_atomics();
+    thread::scope(|s| {
+        for cpu in 0..4u8 {
+            s.spawn(move || {
+                for _ in 0..1000 {
+                    *DEVICE.lock_interrupts() += 1;
+                }
+                *DEVICE.lock_last_status() = cpu;
+                ROUTES.write_routes()[usize::from(cpu)] = u16::from(cpu) * 10;
+            });
+        }
+    });
+    // One accessor guard at a time: two guards from the same lock in one
+    // expression would deadlock.
+    let interrupts = *DEVICE.lock_interrupts();
+    let last_status = *DEVICE.lock_last_status();
+    let routes = *ROUTES.read_routes();
+    println!("{interrupts} interrupts, last status {last_status}, routes {routes:?}");
+}
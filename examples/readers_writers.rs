@@ -0,0 +1,118 @@
+// A configuration read by many workers and replaced now and then by a
+// writer, on an `RwSpinLock`.
+
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::thread;
+use std::time::Duration;
+
+use mutex::RwSpinLock;
+
+const READERS: usize = 4;
+/// How long the demo runs; one second for the reduced run under `cargo test`.
+const SECONDS: u64 = if cfg!(test) { 1 } else { 3 };
+/// How often the writer replaces the configuration.
+const PERIOD: Duration = Duration::from_millis(100);
+const GENERATIONS: usize = (SECONDS * 1000 / PERIOD.as_millis() as u64) as usize + 1;
+
+/// Every field is derived from `generation`, so a reader that sees fields
+/// from two generations has seen a torn write.
+#[derive(PartialEq)]
+struct Config {
+    generation: usize,
+    limits: [usize; 4],
+    checksum: usize,
+}
+
+impl Config {
+    const fn new(generation: usize) -> Self {
+        let limits = [generation, 2 * generation, 3 * generation, 4 * generation];
+        Self {
+            generation,
+            limits,
+            checksum: 10 * generation,
+        }
+    }
+
+    fn is_consistent(&self) -> bool {
+        *self == Config::new(self.generation)
+    }
+}
+
+static CONFIG: RwSpinLock<Config> = RwSpinLock::new(Config::new(0));
+static STOP: AtomicBool = AtomicBool::new(false);
+static READS: AtomicUsize = AtomicUsize::new(0);
+/// Reads that found the writer holding the lock and had to wait.
+static WAITED: AtomicUsize = AtomicUsize::new(0);
+
+/// Returns how often this reader saw each generation.
+fn read_loop() -> Vec<usize> {
+    let mut seen = vec![0; GENERATIONS];
+    while !STOP.load(Ordering::Relaxed) {
+        let config = match CONFIG.try_read() {
+            Some(config) => config,
+            None => {
+                WAITED.fetch_add(1, Ordering::Relaxed);
+                CONFIG.read()
+            }
+        };
+        assert!(config.is_consistent(), "torn configuration");
+        seen[config.generation] += 1;
+        drop(config);
+        READS.fetch_add(1, Ordering::Relaxed);
+    }
+    seen
+}
+
+fn write_loop() {
+    for generation in 1..GENERATIONS {
+        thread::sleep(PERIOD);
+        *CONFIG.write() = Config::new(generation);
+        assert_eq!(CONFIG.read().generation, generation);
+    }
+}
+
+fn main() {
+    mutex::enable_raw_atomics();
+
+    let seen = thread::scope(|scope| {
+        let readers: Vec<_> = (0..READERS).map(|_| scope.spawn(read_loop)).collect();
+        scope.spawn(write_loop);
+
+        let mut last = 0;
+        for second in 1..=SECONDS {
+            thread::sleep(Duration::from_secs(1));
+            let reads = READS.load(Ordering::Relaxed);
+            println!("second {second}: {} reads", reads - last);
+            last = reads;
+        }
+        STOP.store(true, Ordering::Relaxed);
+
+        let mut seen = vec![0; GENERATIONS];
+        for reader in readers {
+            for (total, count) in seen.iter_mut().zip(reader.join().unwrap()) {
+                *total += count;
+            }
+        }
+        seen
+    });
+
+    for (generation, count) in seen.iter().enumerate() {
+        println!("generation {generation}: observed {count} times");
+    }
+    println!(
+        "{} reads, {} waited for the writer, none torn",
+        READS.load(Ordering::Relaxed),
+        WAITED.load(Ordering::Relaxed)
+    );
+}
+
+/// The demo end to end, in its reduced form.
+#[cfg(test)]
+#[test]
+fn readers_never_see_a_torn_configuration() {
+    main();
+    assert_eq!(CONFIG.read().generation, GENERATIONS - 1);
+    assert!(READS.load(Ordering::Relaxed) > 0);
+}
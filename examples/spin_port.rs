@@ -0,0 +1,121 @@
+// A text console in the style of hobby kernels written against the `spin`
+// crate, ported by changing only the `spin::` imports to `mutex::spin::`.
+// The console writes into an in-memory screen instead of VGA memory.
+//
+// `cargo run --example spin_port --features spin-compat`
+
+use core::fmt;
+use core::fmt::Write;
+use std::thread;
+
+use mutex::spin::Mutex;
+use mutex::spin::MutexGuard;
+use mutex::spin::Once;
+use mutex::spin::RwLock;
+
+const BUFFER_HEIGHT: usize = 25;
+const BUFFER_WIDTH: usize = 80;
+
+pub struct Writer {
+    column_position: usize,
+    buffer: [[u8; BUFFER_WIDTH]; BUFFER_HEIGHT],
+}
+
+impl Writer {
+    pub fn write_byte(&mut self, byte: u8) {
+        match byte {
+            b'\n' => self.new_line(),
+            byte => {
+                if self.column_position >= BUFFER_WIDTH {
+                    self.new_line();
+                }
+                self.buffer[BUFFER_HEIGHT - 1][self.column_position] = byte;
+                self.column_position += 1;
+            }
+        }
+    }
+
+    fn new_line(&mut self) {
+        self.buffer.copy_within(1.., 0);
+        self.buffer[BUFFER_HEIGHT - 1] = [b' '; BUFFER_WIDTH];
+        self.column_position = 0;
+    }
+
+    fn line(&self, row: usize) -> &str {
+        core::str::from_utf8(&self.buffer[row]).unwrap().trim_end()
+    }
+}
+
+impl fmt::Write for Writer {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for byte in s.bytes() {
+            self.write_byte(byte);
+        }
+        Ok(())
+    }
+}
+
+pub static WRITER: Mutex<Writer> = Mutex::new(Writer {
+    column_position: 0,
+    buffer: [[b' '; BUFFER_WIDTH]; BUFFER_HEIGHT],
+});
+
+struct KernelConfig {
+    name: &'static str,
+    cpus: usize,
+}
+
+static CONFIG: Once<KernelConfig> = Once::new();
+static HANDLERS: RwLock<Vec<&'static str>> = RwLock::new(Vec::new());
+
+macro_rules! kprintln {
+    ($($arg:tt)*) => {
+        writeln!(WRITER.lock(), $($arg)*).unwrap()
+    };
+}
+
+fn config() -> &'static KernelConfig {
+    CONFIG.call_once(|| KernelConfig {
+        name: "toy",
+        cpus: 4,
+    })
+}
+
+/// Called from a panic handler, where the lock may be held already.
+fn emergency_writer() -> MutexGuard<'static, Writer> {
+    if WRITER.is_locked() {
+        // SAFETY: the holder will never run again.
+        unsafe { WRITER.force_unlock() };
+    }
+    WRITER.lock()
+}
+
+fn main() {
+    mutex::enable_raw_atomics();
+
+    kprintln!("{} kernel booting", config().name);
+    HANDLERS.write().push("timer");
+
+    thread::scope(|scope| {
+        for cpu in 1..config().cpus {
+            scope.spawn(move || {
+                assert_eq!(HANDLERS.read().len(), 1);
+                kprintln!("cpu {cpu} online");
+            });
+        }
+    });
+
+    assert!(CONFIG.is_completed());
+    assert_eq!(HANDLERS.reader_count(), 0);
+    writeln!(emergency_writer(), "all cpus online").unwrap();
+
+    let writer = WRITER.lock();
+    let lines: Vec<_> = (0..BUFFER_HEIGHT)
+        .map(|row| writer.line(row))
+        .filter(|line| !line.is_empty())
+        .collect();
+    assert_eq!(lines.len(), config().cpus + 1);
+    for line in lines {
+        println!("{line}");
+    }
+}
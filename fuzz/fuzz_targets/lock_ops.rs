@@ -0,0 +1,11 @@
+//! Runs the input as a sequence of lock operations: `cargo fuzz run lock_ops`.
+//! The format and the checks are described in `../lock_ops.rs`.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+#[path = "../lock_ops.rs"]
+mod lock_ops;
+
+fuzz_target!(|data: &[u8]| lock_ops::run(data));
@@ -0,0 +1,330 @@
+//! The interpreter behind the `lock_ops` fuzz target, shared with
+//! `tests/fuzz_corpus.rs`, which replays the seed corpus under `cargo test`.
+//!
+//! The first byte picks the number of threads, 1 to 4. While raw atomics are
+//! still disabled, the remaining bytes run on one thread until an `ENABLE`
+//! byte, which drops every guard and enables them; the bytes after it are
+//! dealt round-robin to the threads. Raw atomics cannot be disabled again,
+//! so once an input has enabled them, later inputs go straight to the
+//! threads.
+//!
+//! Each thread keeps a shadow model of what it holds and only issues
+//! operations the crate allows: blocking acquisitions go in a fixed order,
+//! so the threads cannot deadlock, nothing is acquired twice by one thread,
+//! and `force_unlock` only releases a lock whose guard this thread leaked.
+//! With `checked-guards`, a mutex whose guard was leaked before raw atomics
+//! were enabled stays borrowed for good, so it is not locked again until
+//! they are: that would be misuse, and the crate rightly panics.
+//! Shared counters then check that no lock ever has two exclusive holders
+//! and that readers never meet a writer, and every payload is checked for
+//! torn writes. Any panic is a finding.
+
+use std::hint::black_box;
+use std::mem;
+use std::sync::LazyLock;
+use std::sync::Mutex;
+#[cfg(feature = "checked-guards")]
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::mpsc;
+use std::sync::mpsc::Receiver;
+use std::sync::mpsc::Sender;
+use std::thread;
+
+use mutex::mutex::RawSpinLock;
+use mutex::mutex::RawSpinLockGuard;
+use mutex::mutex::RwSpinLock;
+use mutex::mutex::RwSpinLockReadGuard;
+use mutex::mutex::RwSpinLockWriteGuard;
+use mutex::mutex::enable_raw_atomics;
+use mutex::mutex::raw_atomics_enabled;
+
+const MUTEXES: usize = 2;
+/// Position of the rw lock in the acquisition order, after the mutexes.
+const RW: usize = MUTEXES;
+/// Read guards one thread holds at most.
+const MAX_READS: usize = 2;
+
+// The low nibble of a byte is the operation; for mutex operations, the next
+// bit picks the mutex.
+const LOCK: u8 = 0;
+const TRY_LOCK: u8 = 1;
+const UNLOCK: u8 = 2;
+const LEAK: u8 = 3;
+const FORCE_UNLOCK: u8 = 4;
+const READ: u8 = 5;
+const TRY_READ: u8 = 6;
+const RELEASE_READ: u8 = 7;
+const WRITE: u8 = 8;
+const TRY_WRITE: u8 = 9;
+const RELEASE_WRITE: u8 = 10;
+const MUTATE: u8 = 11;
+pub const ENABLE: u8 = 12;
+
+/// Every word is written together, so differing words mean a torn write.
+type Payload = [u64; 4];
+
+fn check(payload: &Payload) {
+    assert!(
+        payload.iter().all(|&word| word == payload[0]),
+        "torn payload {payload:?}"
+    );
+}
+
+fn mutate(payload: &mut Payload) {
+    for word in payload.iter_mut() {
+        *word = black_box(*word + 1);
+    }
+}
+
+struct Shared {
+    mutexes: [RawSpinLock<Payload>; MUTEXES],
+    rw: RwSpinLock<Payload>,
+    /// Exclusive holders of each mutex, leaked guards included.
+    holders: [AtomicUsize; MUTEXES],
+    readers: AtomicUsize,
+    writers: AtomicUsize,
+}
+
+/// What one thread holds.
+struct Model<'a> {
+    shared: &'a Shared,
+    guards: [Option<RawSpinLockGuard<'a, Payload>>; MUTEXES],
+    leaked: [bool; MUTEXES],
+    reads: Vec<RwSpinLockReadGuard<'a, Payload>>,
+    write: Option<RwSpinLockWriteGuard<'a, Payload>>,
+}
+
+impl<'a> Model<'a> {
+    fn new(shared: &'a Shared) -> Self {
+        Self {
+            shared,
+            guards: [const { None }; MUTEXES],
+            leaked: [false; MUTEXES],
+            reads: Vec::new(),
+            write: None,
+        }
+    }
+
+    fn holds(&self, lock: usize) -> bool {
+        if lock == RW {
+            !self.reads.is_empty() || self.write.is_some()
+        } else {
+            self.guards[lock].is_some() || self.leaked[lock]
+        }
+    }
+
+    /// Blocking acquisitions are only made in increasing lock order.
+    fn may_block_on(&self, lock: usize) -> bool {
+        #[cfg(feature = "checked-guards")]
+        if lock < MUTEXES && !raw_atomics_enabled() && LEAKED_BORROWED[lock].load(Ordering::Relaxed)
+        {
+            return false;
+        }
+        (lock..=RW).all(|held| !self.holds(held))
+    }
+
+    fn acquired(&mut self, lock: usize, guard: RawSpinLockGuard<'a, Payload>) {
+        let holders = self.shared.holders[lock].fetch_add(1, Ordering::Relaxed);
+        assert_eq!(holders, 0, "mutex {lock} has two holders");
+        check(&guard);
+        self.guards[lock] = Some(guard);
+    }
+
+    fn released(&self, lock: usize) {
+        self.shared.holders[lock].fetch_sub(1, Ordering::Relaxed);
+    }
+
+    fn read_acquired(&mut self, guard: RwSpinLockReadGuard<'a, Payload>) {
+        self.shared.readers.fetch_add(1, Ordering::Relaxed);
+        assert_eq!(
+            self.shared.writers.load(Ordering::Relaxed),
+            0,
+            "reader alongside a writer"
+        );
+        check(&guard);
+        self.reads.push(guard);
+    }
+
+    fn write_acquired(&mut self, guard: RwSpinLockWriteGuard<'a, Payload>) {
+        let writers = self.shared.writers.fetch_add(1, Ordering::Relaxed);
+        assert_eq!(writers, 0, "two writers");
+        assert_eq!(
+            self.shared.readers.load(Ordering::Relaxed),
+            0,
+            "writer alongside a reader"
+        );
+        check(&guard);
+        self.write = Some(guard);
+    }
+
+    fn step(&mut self, byte: u8) {
+        let shared = self.shared;
+        let lock = usize::from(byte >> 4) % MUTEXES;
+        match byte & 0xf {
+            LOCK if self.may_block_on(lock) => {
+                let guard = shared.mutexes[lock].lock();
+                self.acquired(lock, guard);
+            }
+            TRY_LOCK if !self.holds(lock) => {
+                if let Some(guard) = shared.mutexes[lock].try_lock() {
+                    self.acquired(lock, guard);
+                }
+            }
+            UNLOCK => {
+                if let Some(guard) = self.guards[lock].take() {
+                    self.released(lock);
+                    drop(guard);
+                }
+            }
+            LEAK => {
+                if let Some(guard) = self.guards[lock].take() {
+                    mem::forget(guard);
+                    self.leaked[lock] = true;
+                    #[cfg(feature = "checked-guards")]
+                    if !raw_atomics_enabled() {
+                        LEAKED_BORROWED[lock].store(true, Ordering::Relaxed);
+                    }
+                }
+            }
+            FORCE_UNLOCK if self.leaked[lock] => {
+                self.leaked[lock] = false;
+                self.released(lock);
+                // SAFETY: this thread leaked the only guard, which is gone.
+                unsafe { shared.mutexes[lock].force_unlock() };
+            }
+            READ if self.may_block_on(RW) => {
+                let guard = shared.rw.read();
+                self.read_acquired(guard);
+            }
+            TRY_READ if self.write.is_none() && self.reads.len() < MAX_READS => {
+                if let Some(guard) = shared.rw.try_read() {
+                    self.read_acquired(guard);
+                }
+            }
+            RELEASE_READ => {
+                if let Some(guard) = self.reads.pop() {
+                    shared.readers.fetch_sub(1, Ordering::Relaxed);
+                    drop(guard);
+                }
+            }
+            WRITE if self.may_block_on(RW) => {
+                let guard = shared.rw.write();
+                self.write_acquired(guard);
+            }
+            TRY_WRITE if !self.holds(RW) => {
+                if let Some(guard) = shared.rw.try_write() {
+                    self.write_acquired(guard);
+                }
+            }
+            RELEASE_WRITE => {
+                if let Some(guard) = self.write.take() {
+                    shared.writers.fetch_sub(1, Ordering::Relaxed);
+                    drop(guard);
+                }
+            }
+            MUTATE => {
+                for guard in self.guards.iter_mut().flatten() {
+                    mutate(guard);
+                }
+                if let Some(guard) = &mut self.write {
+                    mutate(guard);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Drops every guard, and force-unlocks the leaked ones.
+    fn release_all(&mut self) {
+        for lock in 0..MUTEXES {
+            self.step(UNLOCK | (lock as u8) << 4);
+            self.step(FORCE_UNLOCK | (lock as u8) << 4);
+        }
+        while !self.reads.is_empty() {
+            self.step(RELEASE_READ);
+        }
+        self.step(RELEASE_WRITE);
+    }
+}
+
+/// Worker threads, kept for the whole run: threads spawned per input are
+/// still being torn down when the next input starts, which the leak checker
+/// reports.
+static POOL: LazyLock<Mutex<Vec<Worker>>> = LazyLock::new(|| Mutex::new(Vec::new()));
+
+static SHARED: Shared = Shared {
+    mutexes: [const { RawSpinLock::new([0; 4]) }; MUTEXES],
+    rw: RwSpinLock::new([0; 4]),
+    holders: [const { AtomicUsize::new(0) }; MUTEXES],
+    readers: AtomicUsize::new(0),
+    writers: AtomicUsize::new(0),
+};
+
+/// Mutexes whose guard was leaked before raw atomics were enabled, which
+/// `checked-guards` keeps borrowed across inputs until they are.
+#[cfg(feature = "checked-guards")]
+static LEAKED_BORROWED: [AtomicBool; MUTEXES] = [const { AtomicBool::new(false) }; MUTEXES];
+
+struct Worker {
+    ops: Sender<Vec<u8>>,
+    done: Receiver<()>,
+}
+
+impl Worker {
+    fn spawn() -> Self {
+        let (ops, ops_rx) = mpsc::channel::<Vec<u8>>();
+        let (done_tx, done) = mpsc::channel();
+        thread::spawn(move || {
+            for ops in ops_rx {
+                let mut model = Model::new(&SHARED);
+                for byte in ops {
+                    model.step(byte);
+                }
+                model.release_all();
+                done_tx.send(()).unwrap();
+            }
+        });
+        Self { ops, done }
+    }
+}
+
+/// Runs one input; see the `lock_ops` target for its format.
+pub fn run(data: &[u8]) {
+    let Some((&threads, mut ops)) = data.split_first() else {
+        return;
+    };
+    let threads = usize::from(threads % 4) + 1;
+
+    if !raw_atomics_enabled() {
+        // Permissive mode: one thread, which the model keeps from aliasing
+        // its own guards.
+        let mut model = Model::new(&SHARED);
+        while let Some((&byte, rest)) = ops.split_first() {
+            ops = rest;
+            if byte & 0xf == ENABLE {
+                model.release_all();
+                enable_raw_atomics();
+                break;
+            }
+            model.step(byte);
+        }
+        model.release_all();
+        if !raw_atomics_enabled() {
+            return;
+        }
+    }
+
+    let mut pool = POOL.lock().unwrap();
+    while pool.len() < threads {
+        pool.push(Worker::spawn());
+    }
+    for (thread, worker) in pool[..threads].iter().enumerate() {
+        let ops = ops.iter().skip(thread).step_by(threads).copied().collect();
+        worker.ops.send(ops).unwrap();
+    }
+    for worker in &pool[..threads] {
+        worker.done.recv().unwrap();
+    }
+}
@@ -0,0 +1,248 @@
+//! `#[derive(LockProject)]` for the `mutex` crate. Use it through `mutex`'s
+//! `derive` feature, as `mutex::project::LockProject`, which documents what
+//! it generates.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::format_ident;
+use quote::quote;
+use syn::Data;
+use syn::DeriveInput;
+use syn::Fields;
+use syn::Ident;
+use syn::Type;
+use syn::Visibility;
+use syn::parse_macro_input;
+
+/// Generates per-field accessors on `RawSpinLock<Self>` and
+/// `RwSpinLock<Self>`.
+///
+/// For each field `foo: Foo`, `lock.lock_foo()` on a `RawSpinLock` returns
+/// a `MappedRawSpinLockGuard` for just `foo`, and `read_foo()` and
+/// `write_foo()` on an `RwSpinLock` return mapped read and write guards.
+/// Fields marked `#[lock_project(skip)]` get none.
+///
+/// The accessors live in extension traits with the fields' visibility:
+/// `{Struct}LockProject` and `{Struct}RwLockProject` for fields as visible
+/// as the struct, and the same names with `Crate`, `Super`, `Private` or
+/// `In{Path}` appended for fields that are less visible, so a private field
+/// is only reachable where it could be read directly. Bring the traits into
+/// scope to call the accessors.
+#[proc_macro_derive(LockProject, attributes(lock_project))]
+pub fn derive_lock_project(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+struct Field<'a> {
+    ident: &'a Ident,
+    ty: &'a Type,
+}
+
+/// The fields sharing one visibility, which get one pair of traits.
+struct Group<'a> {
+    vis: Visibility,
+    /// Appended to the trait names; empty for the struct's own visibility.
+    suffix: String,
+    fields: Vec<Field<'a>>,
+}
+
+fn expand(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => return Err(not_named(input)),
+        },
+        _ => return Err(not_named(input)),
+    };
+    let mut groups: Vec<Group<'_>> = Vec::new();
+    for field in fields {
+        if skipped(field)? {
+            continue;
+        }
+        let vis = trait_visibility(&input.vis, &field.vis);
+        let key = quote!(#vis).to_string();
+        let field = Field {
+            ident: field.ident.as_ref().expect("named field"),
+            ty: &field.ty,
+        };
+        match groups.iter_mut().find(|group| {
+            let vis = &group.vis;
+            quote!(#vis).to_string() == key
+        }) {
+            Some(group) => group.fields.push(field),
+            None => {
+                let struct_vis = &input.vis;
+                let suffix = if quote!(#struct_vis).to_string() == key {
+                    String::new()
+                } else {
+                    suffix(&vis)
+                };
+                groups.push(Group {
+                    vis,
+                    suffix,
+                    fields: vec![field],
+                });
+            }
+        }
+    }
+    Ok(groups
+        .iter()
+        .map(|group| expand_group(input, group))
+        .collect())
+}
+
+fn not_named(input: &DeriveInput) -> syn::Error {
+    syn::Error::new_spanned(&input.ident, "LockProject needs a struct with named fields")
+}
+
+fn skipped(field: &syn::Field) -> syn::Result<bool> {
+    let mut skip = false;
+    for attr in &field.attrs {
+        if !attr.path().is_ident("lock_project") {
+            continue;
+        }
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("skip") {
+                skip = true;
+                Ok(())
+            } else {
+                Err(meta.error("expected `skip`"))
+            }
+        })?;
+    }
+    Ok(skip)
+}
+
+/// The visibility of a field's accessors: the field's own, but no wider
+/// than the struct's, so the traits never expose a type where it cannot be
+/// named.
+fn trait_visibility(struct_vis: &Visibility, field_vis: &Visibility) -> Visibility {
+    match (struct_vis, field_vis) {
+        (_, Visibility::Public(_)) | (Visibility::Inherited, _) => struct_vis.clone(),
+        _ => field_vis.clone(),
+    }
+}
+
+fn suffix(vis: &Visibility) -> String {
+    match vis {
+        Visibility::Public(_) => "Pub".to_owned(),
+        Visibility::Inherited => "Private".to_owned(),
+        Visibility::Restricted(restricted) => {
+            let path = &restricted.path;
+            if restricted.in_token.is_none() {
+                if path.is_ident("crate") {
+                    return "Crate".to_owned();
+                }
+                if path.is_ident("super") {
+                    return "Super".to_owned();
+                }
+                return "Private".to_owned();
+            }
+            let mut suffix = "In".to_owned();
+            // `pub(in crate::dev_state)` becomes `InCrateDevState`.
+            for segment in &path.segments {
+                for part in segment.ident.to_string().split('_') {
+                    let mut chars = part.chars();
+                    if let Some(first) = chars.next() {
+                        suffix.extend(first.to_uppercase());
+                        suffix.extend(chars);
+                    }
+                }
+            }
+            suffix
+        }
+    }
+}
+
+fn expand_group(input: &DeriveInput, group: &Group<'_>) -> TokenStream2 {
+    let name = &input.ident;
+    let vis = &group.vis;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let lock_trait = format_ident!("{}LockProject{}", name, group.suffix);
+    let rw_trait = format_ident!("{}RwLockProject{}", name, group.suffix);
+    let lock_doc = format!("Per-field `RawSpinLock` accessors for `{name}`.");
+    let rw_doc = format!("Per-field `RwSpinLock` accessors for `{name}`.");
+
+    let mut lock_sigs = Vec::new();
+    let mut lock_fns = Vec::new();
+    let mut rw_sigs = Vec::new();
+    let mut rw_fns = Vec::new();
+    for Field { ident, ty } in &group.fields {
+        let lock = format_ident!("lock_{}", ident);
+        let read = format_ident!("read_{}", ident);
+        let write = format_ident!("write_{}", ident);
+        let lock_doc = format!("Locks the lock and returns a guard for `{ident}`.");
+        let read_doc = format!("Read-locks the lock and returns a guard for `{ident}`.");
+        let write_doc = format!("Write-locks the lock and returns a guard for `{ident}`.");
+        let lock_ret = quote! {
+            ::mutex::project::MappedRawSpinLockGuard<'_, #name #ty_generics, #ty>
+        };
+        let read_ret = quote! {
+            ::mutex::project::MappedRwSpinLockReadGuard<'_, #name #ty_generics, #ty>
+        };
+        let write_ret = quote! {
+            ::mutex::project::MappedRwSpinLockWriteGuard<'_, #name #ty_generics, #ty>
+        };
+        lock_sigs.push(quote! {
+            #[doc = #lock_doc]
+            fn #lock(&self) -> #lock_ret;
+        });
+        lock_fns.push(quote! {
+            #[track_caller]
+            fn #lock(&self) -> #lock_ret {
+                ::mutex::RawSpinLockGuard::map(::mutex::RawSpinLock::lock(self), |data| {
+                    &mut data.#ident
+                })
+            }
+        });
+        rw_sigs.push(quote! {
+            #[doc = #read_doc]
+            fn #read(&self) -> #read_ret;
+
+            #[doc = #write_doc]
+            fn #write(&self) -> #write_ret;
+        });
+        rw_fns.push(quote! {
+            #[track_caller]
+            fn #read(&self) -> #read_ret {
+                ::mutex::RwSpinLockReadGuard::map(::mutex::RwSpinLock::read(self), |data| {
+                    &data.#ident
+                })
+            }
+
+            #[track_caller]
+            fn #write(&self) -> #write_ret {
+                ::mutex::RwSpinLockWriteGuard::map(::mutex::RwSpinLock::write(self), |data| {
+                    &mut data.#ident
+                })
+            }
+        });
+    }
+
+    quote! {
+        #[doc = #lock_doc]
+        #vis trait #lock_trait #impl_generics #where_clause {
+            #(#lock_sigs)*
+        }
+
+        impl #impl_generics #lock_trait #ty_generics
+            for ::mutex::RawSpinLock<#name #ty_generics> #where_clause
+        {
+            #(#lock_fns)*
+        }
+
+        #[doc = #rw_doc]
+        #vis trait #rw_trait #impl_generics #where_clause {
+            #(#rw_sigs)*
+        }
+
+        impl #impl_generics #rw_trait #ty_generics
+            for ::mutex::RwSpinLock<#name #ty_generics> #where_clause
+        {
+            #(#rw_fns)*
+        }
+    }
+}
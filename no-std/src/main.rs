@@ -0,0 +1,99 @@
+//! A bare-metal consumer of the crate, linked without std: `cargo
+//! build-no-std`. It only has to build, so that std leaking into the
+//! library's `no_std` build, through formatting or thread APIs, fails at
+//! link time rather than on a real target. The plain build has no global
+//! allocator, so the library pulling in `alloc` without the `alloc` feature
+//! fails as well; `cargo build-no-std-alloc` adds one and the `alloc` APIs.
+
+#![no_std]
+#![no_main]
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+use core::hint::spin_loop;
+use core::panic::PanicInfo;
+
+use mutex::RawSpinLock;
+use mutex::RwSpinLock;
+
+static BOOT_PARAMS: RawSpinLock<[u8; 16]> = RawSpinLock::new([0; 16]);
+static COUNTER: RawSpinLock<u64> = RawSpinLock::new(0);
+static TABLE: RwSpinLock<[u32; 8]> = RwSpinLock::new([0; 8]);
+
+#[unsafe(no_mangle)]
+extern "C" fn _start() -> ! {
+    // SAFETY: only the boot CPU runs, and no other guard exists.
+    unsafe { BOOT_PARAMS.no_lock() }.fill(0xaa);
+    *COUNTER.lock() += 1;
+    TABLE.write()[0] = 1;
+
+    mutex::enable_raw_atomics();
+
+    *COUNTER.lock() += 1;
+    if let Some(mut counter) = COUNTER.try_lock() {
+        *counter += u64::from(BOOT_PARAMS.lock()[0]);
+    }
+    let first = TABLE.read()[0];
+    TABLE.write()[1] = first + 1;
+    #[cfg(feature = "alloc")]
+    heap::run();
+
+    loop {
+        spin_loop();
+    }
+}
+
+#[panic_handler]
+fn panic(_: &PanicInfo) -> ! {
+    loop {
+        spin_loop();
+    }
+}
+
+#[cfg(feature = "alloc")]
+mod heap {
+    use alloc::sync::Arc;
+    use core::alloc::Layout;
+    use core::ptr;
+
+    use mutex::RawSpinLock;
+    use mutex::allocator::LockedAllocator;
+    use mutex::allocator::RawAllocator;
+    use mutex::arc_swap::SpinArcSwap;
+
+    /// Hands out memory from a static array and never frees it.
+    struct Bump {
+        next: usize,
+    }
+
+    static mut HEAP: [u8; 4096] = [0; 4096];
+
+    // SAFETY: blocks are aligned, inside `HEAP` and never reused.
+    unsafe impl RawAllocator for Bump {
+        fn alloc(&mut self, layout: Layout) -> *mut u8 {
+            let base = (&raw mut HEAP).cast::<u8>();
+            let start =
+                (base as usize + self.next).next_multiple_of(layout.align()) - base as usize;
+            let Some(end) = start.checked_add(layout.size()).filter(|&end| end <= 4096) else {
+                return ptr::null_mut();
+            };
+            self.next = end;
+            base.wrapping_add(start)
+        }
+
+        unsafe fn dealloc(&mut self, _: *mut u8, _: Layout) {}
+    }
+
+    #[global_allocator]
+    static ALLOCATOR: LockedAllocator<Bump> = LockedAllocator::new(Bump { next: 0 });
+
+    pub(crate) fn run() {
+        let config = SpinArcSwap::new(Arc::new(1u32));
+        let old = config.swap(Arc::new(2));
+        let (left, right) = RawSpinLock::new(*old + *config.load()).split_arc();
+        *left.lock() += 1;
+        drop(left);
+        let _ = right.into_inner();
+    }
+}
@@ -0,0 +1,133 @@
+//! Traits for code that reads or writes data without caring how it is
+//! locked.
+//!
+//! A helper that only needs `&T` can take `impl ReadAccess<T>`, and one
+//! that needs `&mut T` an `impl WriteAccess<T>`. Every guard in the crate
+//! implements them for its data, as do `&T` and `&mut T`, so the same
+//! helper works under a [`RawSpinLockGuard`], an [`RwSpinLockReadGuard`], a
+//! mapped guard or no lock at all. Read-only guards only implement
+//! [`ReadAccess`].
+//!
+//! Unlike `Deref`, the traits are implemented only here, for the crate's
+//! own guards, so `impl ReadAccess<T>` does not also admit a `Box` or an
+//! `Arc` whose contents nobody is holding a lock on. [`snapshot`], [`swap`],
+//! [`replace`] and [`take`] are written against them.
+//!
+//! [`RawSpinLockGuard`]: crate::mutex::RawSpinLockGuard
+//! [`RwSpinLockReadGuard`]: crate::rwlock::RwSpinLockReadGuard
+
+/// Shared access to a `T`, from a guard or a reference.
+pub trait ReadAccess<T: ?Sized> {
+    fn access(&self) -> &T;
+}
+
+/// Exclusive access to a `T`, from a guard or a mutable reference.
+pub trait WriteAccess<T: ?Sized>: ReadAccess<T> {
+    fn access_mut(&mut self) -> &mut T;
+}
+
+impl<T: ?Sized> ReadAccess<T> for &T {
+    fn access(&self) -> &T {
+        self
+    }
+}
+
+impl<T: ?Sized> ReadAccess<T> for &mut T {
+    fn access(&self) -> &T {
+        self
+    }
+}
+
+impl<T: ?Sized> WriteAccess<T> for &mut T {
+    fn access_mut(&mut self) -> &mut T {
+        self
+    }
+}
+
+/// Implements the traits for guards through their `Deref` and `DerefMut`.
+macro_rules! access {
+    (read: $($(#[$attr:meta])* [$($generics:tt)*] $guard:ty => $target:ty;)*) => {
+        $(
+            $(#[$attr])*
+            impl<$($generics)*> ReadAccess<$target> for $guard {
+                fn access(&self) -> &$target {
+                    self
+                }
+            }
+        )*
+    };
+    (write: $($(#[$attr:meta])* [$($generics:tt)*] $guard:ty => $target:ty;)*) => {
+        $(
+            access!(read: $(#[$attr])* [$($generics)*] $guard => $target;);
+
+            $(#[$attr])*
+            impl<$($generics)*> WriteAccess<$target> for $guard {
+                fn access_mut(&mut self) -> &mut $target {
+                    self
+                }
+            }
+        )*
+    };
+}
+
+access!(read:
+    #[cfg(feature = "std")]
+    [T] crate::compat::RwLockReadGuard<'_, T> => T;
+    #[cfg(feature = "testing")]
+    [T] crate::mock::MockLockReadGuard<'_, T> => T;
+    [T] crate::mutex::RawSpinLockReadOnlyGuard<'_, T> => T;
+    [T] crate::rwlock::RwSpinLockReadGuard<'_, T> => T;
+    [T, U: ?Sized] crate::project::MappedRwSpinLockReadGuard<'_, T, U> => U;
+    [T] crate::versioned::VersionedReadGuard<'_, T> => T;
+);
+
+access!(write:
+    [T] crate::biased::BiasedSpinLockGuard<'_, T> => T;
+    #[cfg(feature = "cortex-m")]
+    [T] crate::ceiling::CeilingLockGuard<'_, T> => T;
+    [T, const NODES: usize] crate::cohort::CohortLockGuard<'_, T, NODES> => T;
+    #[cfg(feature = "std")]
+    [T] crate::compat::MutexGuard<'_, T> => T;
+    #[cfg(feature = "std")]
+    [T] crate::compat::RwLockWriteGuard<'_, T> => T;
+    [T] crate::hybrid::HybridMutexGuard<'_, T> => T;
+    [T] crate::irq::RawSpinLockIrqGuard<'_, T> => T;
+    #[cfg(feature = "testing")]
+    [T] crate::mock::MockLockGuard<'_, T> => T;
+    [T] crate::mutex::HandoffGuard<'_, T> => T;
+    [T] crate::mutex::RawSpinLockGuard<'_, T> => T;
+    [T] crate::padded::SplitSpinLockGuard<'_, T> => T;
+    [T, U: ?Sized] crate::project::MappedRawSpinLockGuard<'_, T, U> => U;
+    [T, U: ?Sized] crate::project::MappedRwSpinLockWriteGuard<'_, T, U> => U;
+    [T] crate::rwlock::RwSpinLockWriteGuard<'_, T> => T;
+    [T] crate::signal::RawSpinLockSignalGuard<'_, T> => T;
+    #[cfg(any(feature = "portable-atomic", target_has_atomic = "64"))]
+    [T: crate::small::Packable] crate::small::SmallSpinLockGuard<'_, T> => T;
+    #[cfg(feature = "alloc")]
+    [T] crate::spin_ptr::SpinPtrGuard<'_, T> => Option<alloc::boxed::Box<T>>;
+    [T: ?Sized] crate::spin_ref::SpinRefGuard<'_, T> => T;
+    #[cfg(feature = "alloc")]
+    [T: ?Sized] crate::sync::MappedSpinLockGuard<'_, T> => T;
+    [T] crate::transaction::TxWriteGuard<'_, T> => T;
+    [T] crate::versioned::VersionedGuard<'_, T> => T;
+);
+
+/// Clones the data behind the guard, for use after releasing it.
+pub fn snapshot<T: Clone>(guard: &impl ReadAccess<T>) -> T {
+    guard.access().clone()
+}
+
+/// Swaps the data behind two guards, which may be of different kinds.
+pub fn swap<T>(a: &mut impl WriteAccess<T>, b: &mut impl WriteAccess<T>) {
+    core::mem::swap(a.access_mut(), b.access_mut());
+}
+
+/// Puts `value` behind the guard and returns what was there.
+pub fn replace<T>(guard: &mut impl WriteAccess<T>, value: T) -> T {
+    core::mem::replace(guard.access_mut(), value)
+}
+
+/// Takes the data from behind the guard, leaving the default.
+pub fn take<T: Default>(guard: &mut impl WriteAccess<T>) -> T {
+    core::mem::take(guard.access_mut())
+}
@@ -0,0 +1,88 @@
+use core::alloc::GlobalAlloc;
+use core::alloc::Layout;
+use core::ptr;
+
+use crate::mutex::RawSpinLock;
+use crate::mutex::RawSpinLockGuard;
+
+/// An allocator that needs exclusive access to hand out memory, such as a
+/// bump or linked-list heap.
+///
+/// # Safety
+///
+/// Implementations must uphold the same contract as [`GlobalAlloc`]: returned
+/// blocks must satisfy the requested layout and stay valid until deallocated.
+/// The methods run with the [`LockedAllocator`] lock held, so they must not
+/// allocate through the global allocator or panic.
+pub unsafe trait RawAllocator {
+    fn alloc(&mut self, layout: Layout) -> *mut u8;
+
+    /// # Safety
+    ///
+    /// `ptr` must have been returned by [`alloc`](Self::alloc) on this
+    /// allocator with the same `layout`.
+    unsafe fn dealloc(&mut self, ptr: *mut u8, layout: Layout);
+
+    /// Resizes a block, by default by allocating a new one and copying.
+    ///
+    /// # Safety
+    ///
+    /// Same requirements as [`GlobalAlloc::realloc`].
+    unsafe fn realloc(&mut self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        // SAFETY: the caller guarantees `new_size` forms a valid layout with `layout.align()`.
+        let new_layout = unsafe { Layout::from_size_align_unchecked(new_size, layout.align()) };
+        let new_ptr = self.alloc(new_layout);
+        if !new_ptr.is_null() {
+            // SAFETY: both blocks are valid for the smaller of the two sizes and
+            // cannot overlap because the old block is still allocated.
+            unsafe {
+                ptr::copy_nonoverlapping(ptr, new_ptr, layout.size().min(new_size));
+                self.dealloc(ptr, layout);
+            }
+        }
+        new_ptr
+    }
+}
+
+/// Wraps a [`RawAllocator`] in a [`RawSpinLock`] so it can be used as the
+/// `#[global_allocator]`.
+///
+/// Like every lock in this crate it works before [`enable_raw_atomics`]
+/// during single-core bring-up.
+///
+/// [`enable_raw_atomics`]: crate::raw::enable_raw_atomics
+pub struct LockedAllocator<A> {
+    inner: RawSpinLock<A>,
+}
+
+impl<A> LockedAllocator<A> {
+    pub const fn new(allocator: A) -> Self {
+        Self {
+            inner: RawSpinLock::new(allocator),
+        }
+    }
+
+    /// Locks the inner allocator, e.g. to add heap regions at runtime.
+    ///
+    /// Allocating through the global allocator while holding this guard will
+    /// deadlock once raw atomics are enabled.
+    pub fn lock(&self) -> RawSpinLockGuard<'_, A> {
+        self.inner.lock()
+    }
+}
+
+unsafe impl<A: RawAllocator> GlobalAlloc for LockedAllocator<A> {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        self.inner.lock().alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        // SAFETY: forwarded from the caller.
+        unsafe { self.inner.lock().dealloc(ptr, layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        // SAFETY: forwarded from the caller.
+        unsafe { self.inner.lock().realloc(ptr, layout, new_size) }
+    }
+}
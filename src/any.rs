@@ -0,0 +1,97 @@
+//! Locking whichever of a set of locks is free.
+//!
+//! [`try_lock_any`] makes one [`try_lock`](Lock::try_lock) pass over a
+//! slice of locks, such as the per-queue [`RawSpinLock`]s of a work-stealing
+//! scheduler, and returns the index and guard of the first it gets.
+//! [`lock_any`] repeats such passes, backing off in between, until one
+//! succeeds. Any [`Lock`] will do, so a `MockLock`, from the `testing`
+//! feature, can script the contention in a test.
+//!
+//! A pass starts at a rotation index and wraps around, so callers that
+//! start at different indices do not all contend for the first lock, and
+//! no lock is favoured just for its place in the slice. The `_from`
+//! variants take the index from the caller, who can advance it after each
+//! call to go round the set. The others start at an index derived from the
+//! calling context's [`OwnerId`](crate::owner::OwnerId), which spreads
+//! contexts over the set but keeps each one at the same start.
+//!
+//! Before raw atomics are enabled every `RawSpinLock::try_lock` succeeds,
+//! so the lock at the rotation index is returned.
+//!
+//! [`RawSpinLock`]: crate::mutex::RawSpinLock
+
+use crate::mutex::Lock;
+use crate::relax::Backoff;
+
+/// Tries each lock once, from the calling context's index, and returns the
+/// index and guard of the first one acquired, or `None` if all were held.
+#[track_caller]
+pub fn try_lock_any<'a, T: ?Sized, L: Lock<T>>(
+    locks: &'a [&'a L],
+) -> Option<(usize, L::Guard<'a>)> {
+    try_lock_any_from(locks, context_start())
+}
+
+/// Like [`try_lock_any`], starting at `start`, taken modulo the number of
+/// locks.
+#[track_caller]
+pub fn try_lock_any_from<'a, T: ?Sized, L: Lock<T>>(
+    locks: &'a [&'a L],
+    start: usize,
+) -> Option<(usize, L::Guard<'a>)> {
+    let len = locks.len();
+    if len == 0 {
+        return None;
+    }
+    let start = start % len;
+    // A loop rather than `find_map`, whose closure would not pass the
+    // caller's location on to `try_lock`.
+    for index in (start..len).chain(0..start) {
+        if let Some(guard) = locks[index].try_lock() {
+            return Some((index, guard));
+        }
+    }
+    None
+}
+
+/// Spins until one of the locks is acquired, from the calling context's
+/// index, and returns its index and guard.
+///
+/// # Panics
+///
+/// If `locks` is empty.
+#[track_caller]
+pub fn lock_any<'a, T: ?Sized, L: Lock<T>>(locks: &'a [&'a L]) -> (usize, L::Guard<'a>) {
+    lock_any_from(locks, context_start())
+}
+
+/// Like [`lock_any`], starting every pass at `start`, taken modulo the
+/// number of locks.
+///
+/// # Panics
+///
+/// If `locks` is empty.
+#[track_caller]
+pub fn lock_any_from<'a, T: ?Sized, L: Lock<T>>(
+    locks: &'a [&'a L],
+    start: usize,
+) -> (usize, L::Guard<'a>) {
+    assert!(!locks.is_empty(), "lock_any needs at least one lock");
+    let mut backoff = Backoff::new();
+    loop {
+        if let Some(acquired) = try_lock_any_from(locks, start) {
+            return acquired;
+        }
+        backoff.spin();
+    }
+}
+
+fn context_start() -> usize {
+    // Owner ids are often addresses of thread-locals, which differ only in
+    // a few middle bits; the splitmix64 output function mixes those into
+    // the low bits the rotation is taken from.
+    let mut z = crate::owner::current().map_or(0, |owner| owner.get()) as u64;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    (z ^ (z >> 31)) as usize
+}
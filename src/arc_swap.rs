@@ -0,0 +1,74 @@
+//! A shared `Arc` that can be replaced while readers hold it.
+//!
+//! [`SpinArcSwap`] suits read-mostly configuration: readers
+//! [`load`](SpinArcSwap::load) a clone of the current `Arc` and keep using
+//! it for as long as they like, while a writer [`store`](SpinArcSwap::store)s
+//! a new one. The race to avoid is a swap dropping the old `Arc` between a
+//! reader fetching the pointer and bumping its count. Here the count is
+//! bumped under a [`RawSpinLock`] that the swap also takes, so the pointer a
+//! reader clones is owned by the cell for the whole bump. The lock is held
+//! only for that increment, or for the pointer exchange, and the old value
+//! is dropped after it is released.
+//!
+//! Unlike a grace-period scheme nothing is deferred: the last holder of an
+//! old `Arc` frees it, and every reader pays for an atomic increment and
+//! decrement.
+
+use alloc::sync::Arc;
+#[cfg(not(feature = "tiny"))]
+use core::fmt;
+
+use crate::mutex::RawSpinLock;
+
+pub struct SpinArcSwap<T> {
+    current: RawSpinLock<Arc<T>>,
+}
+
+impl<T> SpinArcSwap<T> {
+    pub const fn new(value: Arc<T>) -> Self {
+        Self {
+            current: RawSpinLock::new(value),
+        }
+    }
+
+    /// Returns the current value.
+    pub fn load(&self) -> Arc<T> {
+        Arc::clone(&self.current.lock())
+    }
+
+    /// Replaces the current value; the old one is dropped once its last
+    /// reader lets go of it.
+    pub fn store(&self, value: Arc<T>) {
+        drop(self.swap(value));
+    }
+
+    /// Replaces the current value and returns the old one.
+    pub fn swap(&self, value: Arc<T>) -> Arc<T> {
+        // The guard is released at the end of the statement, before the
+        // caller can drop the old value.
+        core::mem::replace(&mut *self.current.lock(), value)
+    }
+
+    pub fn into_inner(self) -> Arc<T> {
+        self.current.into_inner()
+    }
+}
+
+impl<T: Default> Default for SpinArcSwap<T> {
+    fn default() -> Self {
+        Self::new(Arc::default())
+    }
+}
+
+impl<T> From<Arc<T>> for SpinArcSwap<T> {
+    fn from(value: Arc<T>) -> Self {
+        Self::new(value)
+    }
+}
+
+#[cfg(not(feature = "tiny"))]
+impl<T: fmt::Debug> fmt::Debug for SpinArcSwap<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("SpinArcSwap").field(&self.load()).finish()
+    }
+}
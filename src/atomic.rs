@@ -0,0 +1,231 @@
+//! Atomic types and the spin-loop hint used throughout the crate.
+//!
+//! With the `portable-atomic` feature these come from the `portable-atomic`
+//! crate, for targets without native compare-and-swap (thumbv6m, some
+//! RISC-V). Pick one of its backends, such as `critical-section` or
+//! `unsafe-assume-single-core`, in the final binary.
+//!
+//! Under `cfg(loom)` or `cfg(shuttle)` they come from that model checker
+//! instead, so that downstream tests explore the schedules of the locks' own
+//! atomics: loom exhaustively, including weak orderings, and shuttle
+//! randomly, treating every ordering as `SeqCst` but scaling to larger
+//! scenarios. Global settings made during bring-up use the [`global`] types,
+//! which stay plain atomics because a `static` model atomic would outlive
+//! the execution that created it. For the same reason debugging features
+//! that keep global state, such as `deadlock-detection`, `lockdep`,
+//! `registry` or `watchdog`, do not work under either, and neither do
+//! `StdParker` and `OsParker`, which block the real thread; a parker built
+//! on the checker's own `park` and `unpark` does, as in `tests/loom_park.rs`.
+//! The crate's own models are in `tests/loom.rs` and `tests/shuttle.rs`.
+//!
+//! Loom atomics cannot be created in a `const fn`, so under loom each one is
+//! created on first use, which loom sees as a plain write: create a lock
+//! inside the model and use it once before sharing it with other model
+//! threads.
+
+#[cfg(all(loom, shuttle))]
+compile_error!("`cfg(loom)` and `cfg(shuttle)` cannot be used together");
+
+#[cfg(not(any(loom, shuttle, feature = "portable-atomic")))]
+pub(crate) use core::sync::atomic::AtomicBool;
+#[cfg(not(any(loom, shuttle, feature = "portable-atomic")))]
+pub(crate) use core::sync::atomic::AtomicPtr;
+#[cfg(not(any(loom, shuttle, feature = "portable-atomic")))]
+pub(crate) use core::sync::atomic::AtomicU8;
+#[cfg(all(
+    not(any(loom, shuttle, feature = "portable-atomic")),
+    target_has_atomic = "64"
+))]
+pub(crate) use core::sync::atomic::AtomicU64;
+#[cfg(not(any(loom, shuttle, feature = "portable-atomic")))]
+pub(crate) use core::sync::atomic::AtomicUsize;
+#[cfg(not(feature = "portable-atomic"))]
+pub(crate) use core::sync::atomic::Ordering;
+
+#[cfg(all(not(any(loom, shuttle)), feature = "portable-atomic"))]
+pub(crate) use portable_atomic::AtomicBool;
+#[cfg(all(not(any(loom, shuttle)), feature = "portable-atomic"))]
+pub(crate) use portable_atomic::AtomicPtr;
+#[cfg(all(not(any(loom, shuttle)), feature = "portable-atomic"))]
+pub(crate) use portable_atomic::AtomicU8;
+#[cfg(all(not(any(loom, shuttle)), feature = "portable-atomic"))]
+pub(crate) use portable_atomic::AtomicU64;
+#[cfg(all(not(any(loom, shuttle)), feature = "portable-atomic"))]
+pub(crate) use portable_atomic::AtomicUsize;
+#[cfg(feature = "portable-atomic")]
+pub(crate) use portable_atomic::Ordering;
+
+#[cfg(any(loom, shuttle))]
+pub(crate) use model::AtomicBool;
+#[cfg(any(loom, shuttle))]
+pub(crate) use model::AtomicPtr;
+#[cfg(any(loom, shuttle))]
+pub(crate) use model::AtomicU8;
+#[cfg(any(loom, shuttle))]
+pub(crate) use model::AtomicU64;
+#[cfg(any(loom, shuttle))]
+pub(crate) use model::AtomicUsize;
+
+#[cfg(not(any(loom, shuttle)))]
+pub(crate) use core::hint::spin_loop;
+#[cfg(loom)]
+pub(crate) use loom::hint::spin_loop;
+#[cfg(shuttle)]
+pub(crate) use shuttle::hint::spin_loop;
+
+/// Atomics for global settings made during bring-up, which the model
+/// checkers do not model.
+pub(crate) mod global {
+    #[cfg(not(any(loom, shuttle)))]
+    pub(crate) use super::AtomicBool;
+    #[cfg(not(any(loom, shuttle)))]
+    pub(crate) use super::AtomicU8;
+    #[cfg(not(any(loom, shuttle)))]
+    pub(crate) use super::AtomicUsize;
+
+    #[cfg(any(loom, shuttle))]
+    pub(crate) use core::sync::atomic::AtomicBool;
+    #[cfg(any(loom, shuttle))]
+    pub(crate) use core::sync::atomic::AtomicU8;
+    #[cfg(any(loom, shuttle))]
+    pub(crate) use core::sync::atomic::AtomicUsize;
+}
+
+#[cfg(loom)]
+mod model {
+    use core::cell::UnsafeCell;
+    use core::ops::Deref;
+
+    macro_rules! lazy_atomic {
+        ($name:ident, $value:ty) => {
+            /// A loom atomic created on first use, so that `new` can be
+            /// `const`.
+            pub(crate) struct $name {
+                initial: $value,
+                atomic: UnsafeCell<Option<loom::sync::atomic::$name>>,
+            }
+
+            // SAFETY: loom runs one thread at a time, so creating the atomic
+            // on first use cannot race.
+            unsafe impl Sync for $name {}
+
+            // Not every type needs every method.
+            #[allow(dead_code)]
+            impl $name {
+                pub(crate) const fn new(value: $value) -> Self {
+                    Self {
+                        initial: value,
+                        atomic: UnsafeCell::new(None),
+                    }
+                }
+
+                pub(crate) fn into_inner(self) -> $value {
+                    match self.atomic.into_inner() {
+                        Some(atomic) => atomic.into_inner(),
+                        None => self.initial,
+                    }
+                }
+
+                /// An address identifying the atomic; not for dereferencing.
+                pub(crate) fn as_ptr(&self) -> *mut $value {
+                    core::ptr::from_ref(self).cast_mut().cast()
+                }
+            }
+
+            impl Deref for $name {
+                type Target = loom::sync::atomic::$name;
+
+                fn deref(&self) -> &Self::Target {
+                    // SAFETY: see the `Sync` impl; the atomic is never
+                    // replaced once created.
+                    unsafe {
+                        (*self.atomic.get())
+                            .get_or_insert_with(|| loom::sync::atomic::$name::new(self.initial))
+                    }
+                }
+            }
+        };
+    }
+
+    lazy_atomic!(AtomicBool, bool);
+    lazy_atomic!(AtomicU8, u8);
+    lazy_atomic!(AtomicU64, u64);
+    lazy_atomic!(AtomicUsize, usize);
+
+    /// A loom atomic pointer created on first use, like the other types here.
+    pub(crate) struct AtomicPtr<T> {
+        initial: *mut T,
+        atomic: UnsafeCell<Option<loom::sync::atomic::AtomicPtr<T>>>,
+    }
+
+    // SAFETY: as for the other types here.
+    unsafe impl<T> Sync for AtomicPtr<T> {}
+    // SAFETY: the pointer is only handed out through the atomic.
+    unsafe impl<T> Send for AtomicPtr<T> {}
+
+    impl<T> AtomicPtr<T> {
+        pub(crate) const fn new(value: *mut T) -> Self {
+            Self {
+                initial: value,
+                atomic: UnsafeCell::new(None),
+            }
+        }
+    }
+
+    impl<T> Deref for AtomicPtr<T> {
+        type Target = loom::sync::atomic::AtomicPtr<T>;
+
+        fn deref(&self) -> &Self::Target {
+            // SAFETY: as for the other types here.
+            unsafe {
+                (*self.atomic.get())
+                    .get_or_insert_with(|| loom::sync::atomic::AtomicPtr::new(self.initial))
+            }
+        }
+    }
+}
+
+#[cfg(shuttle)]
+mod model {
+    use core::ops::Deref;
+
+    macro_rules! wrapped_atomic {
+        ($name:ident, $value:ty) => {
+            /// A shuttle atomic, with the methods shuttle does not provide.
+            pub(crate) struct $name(shuttle::sync::atomic::$name);
+
+            // Not every type needs every method.
+            #[allow(dead_code)]
+            impl $name {
+                #[track_caller]
+                pub(crate) const fn new(value: $value) -> Self {
+                    Self(shuttle::sync::atomic::$name::new(value))
+                }
+
+                pub(crate) fn into_inner(self) -> $value {
+                    self.0.into_inner()
+                }
+
+                /// An address identifying the atomic; not for dereferencing.
+                pub(crate) fn as_ptr(&self) -> *mut $value {
+                    core::ptr::from_ref(self).cast_mut().cast()
+                }
+            }
+
+            impl Deref for $name {
+                type Target = shuttle::sync::atomic::$name;
+
+                fn deref(&self) -> &Self::Target {
+                    &self.0
+                }
+            }
+        };
+    }
+
+    wrapped_atomic!(AtomicBool, bool);
+    wrapped_atomic!(AtomicU8, u8);
+    wrapped_atomic!(AtomicU64, u64);
+    wrapped_atomic!(AtomicUsize, usize);
+
+    pub(crate) type AtomicPtr<T> = shuttle::sync::atomic::AtomicPtr<T>;
+}
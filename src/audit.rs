@@ -0,0 +1,71 @@
+//! Which locks were used before raw atomics were enabled.
+//!
+//! With the `audit` feature, every [`RawSpinLock`] counts the guards it
+//! hands out in permissive mode, and a global counter adds them all up, so
+//! the claim that only a few known locks are touched during bring-up can be
+//! checked afterwards. [`report`] lists the registered locks, as created
+//! with [`registered_static!`], that were acquired before the switch;
+//! locks without a name only show up in [`pre_atomic_total`].
+//!
+//! The counts cannot change once raw atomics are enabled, and
+//! [`enable_raw_atomics`] records the total at that point, so reading them
+//! late still shows the boot phase.
+//!
+//! [`RawSpinLock`]: crate::mutex::RawSpinLock
+//! [`registered_static!`]: crate::registered_static
+//! [`enable_raw_atomics`]: crate::raw::enable_raw_atomics
+
+use core::fmt;
+
+use crate::atomic::AtomicUsize;
+use crate::atomic::Ordering;
+use crate::raw::raw_atomics_enabled;
+use crate::registry;
+
+/// Permissive-mode acquisitions of all locks so far.
+static PRE_ATOMIC: AtomicUsize = AtomicUsize::new(0);
+/// `PRE_ATOMIC` when raw atomics were enabled.
+static AT_ENABLE: AtomicUsize = AtomicUsize::new(0);
+
+/// Counts a permissive-mode acquisition in `counter` and the total. Plain
+/// loads and stores, as permissive mode runs on a single core.
+#[inline(always)]
+pub(crate) fn count(counter: &AtomicUsize) {
+    counter.store(counter.load(Ordering::Relaxed) + 1, Ordering::Relaxed);
+    PRE_ATOMIC.store(PRE_ATOMIC.load(Ordering::Relaxed) + 1, Ordering::Relaxed);
+}
+
+/// Records the total; called by `enable_raw_atomics` just before the gate
+/// opens.
+pub(crate) fn seal() {
+    AT_ENABLE.store(PRE_ATOMIC.load(Ordering::Relaxed), Ordering::Relaxed);
+}
+
+/// How many guards all locks handed out before raw atomics were enabled,
+/// or so far while they are not.
+pub fn pre_atomic_total() -> usize {
+    if raw_atomics_enabled() {
+        AT_ENABLE.load(Ordering::Relaxed)
+    } else {
+        PRE_ATOMIC.load(Ordering::Relaxed)
+    }
+}
+
+/// Writes one line for every registered lock acquired before raw atomics
+/// were enabled, with its count, followed by the total over all locks.
+///
+/// Registered locks are only listed once they have been locked, which is
+/// never the case for one missing here.
+pub fn report(writer: &mut dyn fmt::Write) -> fmt::Result {
+    registry::for_each(|name, lock, snapshot| {
+        if snapshot.pre_atomic == 0 {
+            return Ok(());
+        }
+        writeln!(
+            writer,
+            "{name} ({lock:p}): {} before raw atomics",
+            snapshot.pre_atomic
+        )
+    })?;
+    writeln!(writer, "total: {}", pre_atomic_total())
+}
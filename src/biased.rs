@@ -0,0 +1,329 @@
+//! A spinlock biased towards one dominant owner.
+//!
+//! While a [`BiasedSpinLock`] is biased, its owner enters and leaves the
+//! critical section with plain loads and compiler fences only: no atomic
+//! read-modify-write and no lock-word traffic. Any other context must first
+//! revoke the bias. It raises a revocation request and waits until the owner
+//! acknowledges it, which the owner does at its next acquire or release
+//! (never inside the critical section). From then on the lock behaves like a
+//! [`RawSpinLock`] for everyone, until somebody calls
+//! [`claim_bias`](BiasedSpinLock::claim_bias).
+//!
+//! Revocation waits for the owner to lock or unlock. If the owner might not
+//! touch the lock again (it is idle, or has exited) while holding the bias,
+//! it should give the bias up first with
+//! [`revoke_bias`](BiasedSpinLock::revoke_bias), outside the critical
+//! section.
+//!
+//! Owners are identified through [`owner::current`]; on builds without an
+//! owner id the lock is never biased.
+
+use core::cell::UnsafeCell;
+use core::ops::Deref;
+use core::ops::DerefMut;
+use core::panic::RefUnwindSafe;
+use core::panic::UnwindSafe;
+use core::sync::atomic::compiler_fence;
+
+use crate::atomic::AtomicBool;
+use crate::atomic::AtomicUsize;
+use crate::atomic::Ordering;
+use crate::mutex::Lock;
+use crate::mutex::RawSpinLock;
+use crate::mutex::RawSpinLockGuard;
+use crate::owner;
+use crate::owner::OwnerId;
+use crate::preempt;
+use crate::raw::raw_atomics_enabled;
+use crate::relax::Backoff;
+
+/// `bias` value while the lock is not biased.
+const UNBIASED: usize = 0;
+
+pub struct BiasedSpinLock<T: ?Sized> {
+    /// Id of the owner the lock is biased towards, or `UNBIASED`. Only the
+    /// owner clears it (acknowledging a revocation), and it is only set
+    /// while holding `word`.
+    bias: AtomicUsize,
+    revoke: AtomicBool,
+    /// Whether the owner's biased guard is alive. Only the owner touches it.
+    entered: AtomicBool,
+    word: RawSpinLock<()>,
+    data: UnsafeCell<T>,
+}
+
+pub struct BiasedSpinLockGuard<'a, T> {
+    lock: &'a BiasedSpinLock<T>,
+    /// `None` for the owner's fast path and for permissive guards.
+    _word: Option<RawSpinLockGuard<'a, ()>>,
+    biased: bool,
+}
+
+unsafe impl<T: ?Sized + Send> Send for BiasedSpinLock<T> {}
+unsafe impl<T: ?Sized + Send> Sync for BiasedSpinLock<T> {}
+
+// Unwind safe like `std::sync::Mutex`, but without poisoning; see
+// `RawSpinLock`.
+impl<T: ?Sized> UnwindSafe for BiasedSpinLock<T> {}
+impl<T: ?Sized> RefUnwindSafe for BiasedSpinLock<T> {}
+
+const _: () = {
+    crate::mutex::assert_unwind_safe::<BiasedSpinLock<core::cell::Cell<u8>>>();
+    crate::mutex::assert_unwind_safe::<BiasedSpinLockGuard<'_, &mut u8>>();
+};
+
+impl<T> BiasedSpinLock<T> {
+    /// Creates an unbiased lock.
+    pub const fn new(data: T) -> Self {
+        Self::with_bias(data, None)
+    }
+
+    /// Creates a lock biased towards `owner`, if given.
+    pub const fn with_bias(data: T, owner: Option<OwnerId>) -> Self {
+        Self {
+            bias: AtomicUsize::new(match owner {
+                Some(owner) => owner.get(),
+                None => UNBIASED,
+            }),
+            revoke: AtomicBool::new(false),
+            entered: AtomicBool::new(false),
+            word: RawSpinLock::new(()),
+            data: UnsafeCell::new(data),
+        }
+    }
+
+    /// Returns the owner the lock is currently biased towards.
+    pub fn bias(&self) -> Option<OwnerId> {
+        core::num::NonZeroUsize::new(self.bias.load(Ordering::Relaxed)).map(OwnerId::new)
+    }
+
+    #[track_caller]
+    pub fn lock(&self) -> BiasedSpinLockGuard<'_, T> {
+        if !raw_atomics_enabled() {
+            return self.guard(None, false);
+        }
+        let me = owner::current().map_or(UNBIASED, OwnerId::get);
+        loop {
+            let bias = self.bias.load(Ordering::Acquire);
+            if bias != UNBIASED && bias == me {
+                if self.enter_biased() {
+                    return self.guard(None, true);
+                }
+                continue;
+            }
+            if bias != UNBIASED {
+                self.request_revocation();
+            }
+            let word = self.word.lock();
+            // A `claim_bias` may have slipped in before we got the word.
+            if self.bias.load(Ordering::Relaxed) == UNBIASED {
+                return self.guard(Some(word), false);
+            }
+        }
+    }
+
+    /// Attempts to acquire the lock without spinning.
+    ///
+    /// Returns `None` if the lock is held, or if it is biased towards
+    /// another owner, since revoking the bias means waiting for that owner.
+    /// Before raw atomics are enabled this always succeeds, mirroring
+    /// [`lock`](Self::lock).
+    pub fn try_lock(&self) -> Option<BiasedSpinLockGuard<'_, T>> {
+        if !raw_atomics_enabled() {
+            return Some(self.guard(None, false));
+        }
+        let me = owner::current().map_or(UNBIASED, OwnerId::get);
+        match self.bias.load(Ordering::Acquire) {
+            UNBIASED => {}
+            // The owner's own guard holds the lock.
+            bias if bias == me && self.entered.load(Ordering::Relaxed) => return None,
+            // A failed biased entry has dropped the bias; try the word.
+            bias if bias == me => {
+                if self.enter_biased() {
+                    return Some(self.guard(None, true));
+                }
+            }
+            _ => return None,
+        }
+        let word = self.word.try_lock()?;
+        (self.bias.load(Ordering::Relaxed) == UNBIASED).then(|| self.guard(Some(word), false))
+    }
+
+    /// The owner's acquire. Returns `false` if a revocation was pending; it
+    /// is acknowledged and the caller must take the unbiased path.
+    ///
+    /// # Panics
+    ///
+    /// If the owner's biased guard is still alive, rather than hand out a
+    /// second `&mut T`.
+    #[inline]
+    #[track_caller]
+    fn enter_biased(&self) -> bool {
+        if self.entered.load(Ordering::Relaxed) {
+            recursive_lock();
+        }
+        if self.revoke.load(Ordering::Relaxed) {
+            self.acknowledge_revocation();
+            return false;
+        }
+        // Keep the critical section after the check; a revoker does not
+        // touch the data until we acknowledge, so no hardware fence is
+        // needed.
+        compiler_fence(Ordering::Acquire);
+        preempt::disable();
+        self.entered.store(true, Ordering::Relaxed);
+        true
+    }
+
+    /// The owner's release.
+    #[inline]
+    fn leave_biased(&self) {
+        self.entered.store(false, Ordering::Relaxed);
+        compiler_fence(Ordering::Release);
+        if self.revoke.load(Ordering::Relaxed) {
+            self.acknowledge_revocation();
+        }
+        preempt::enable();
+    }
+
+    #[cold]
+    fn acknowledge_revocation(&self) {
+        // Release publishes the owner's critical sections to the revoker.
+        self.bias.store(UNBIASED, Ordering::Release);
+    }
+
+    /// Asks the owner to give up the bias and waits until it has.
+    #[cold]
+    fn request_revocation(&self) {
+        let mut backoff = Backoff::new();
+        while self.bias.load(Ordering::Acquire) != UNBIASED {
+            // Re-raised on every round: the owner may have acknowledged a
+            // request, claimed the bias back and cleared the flag before we
+            // saw the lock unbiased.
+            self.revoke.store(true, Ordering::Relaxed);
+            backoff.spin();
+        }
+    }
+
+    /// Biases the lock towards the current context, revoking any existing
+    /// bias first. Returns `false` if the current context has no owner id.
+    pub fn claim_bias(&self) -> bool {
+        let Some(me) = owner::current() else {
+            return false;
+        };
+        if !raw_atomics_enabled() {
+            self.bias.store(me.get(), Ordering::Relaxed);
+            return true;
+        }
+        loop {
+            let bias = self.bias.load(Ordering::Acquire);
+            if bias == me.get() {
+                return true;
+            }
+            if bias != UNBIASED {
+                self.request_revocation();
+            }
+            let _word = self.word.lock();
+            if self.bias.load(Ordering::Relaxed) == UNBIASED {
+                self.revoke.store(false, Ordering::Relaxed);
+                // Release orders the cleared request before the new bias for
+                // the next revoker.
+                self.bias.store(me.get(), Ordering::Release);
+                return true;
+            }
+        }
+    }
+
+    /// Removes the bias, waiting for the owner to acknowledge if the caller
+    /// is not the owner itself.
+    ///
+    /// # Panics
+    ///
+    /// If the caller is the owner and its biased guard is still alive: the
+    /// guard does not hold the lock word, so dropping the bias under it
+    /// would let another context in.
+    #[track_caller]
+    pub fn revoke_bias(&self) {
+        let bias = self.bias.load(Ordering::Acquire);
+        if bias == UNBIASED {
+            return;
+        }
+        if !raw_atomics_enabled() || owner::current().is_some_and(|me| me.get() == bias) {
+            // Biased guards only exist once raw atomics are enabled.
+            if self.entered.load(Ordering::Relaxed) {
+                panic!("revoke_bias called while the owner's biased guard is alive");
+            }
+            self.bias.store(UNBIASED, Ordering::Release);
+        } else {
+            self.request_revocation();
+        }
+    }
+
+    fn guard<'a>(
+        &'a self,
+        word: Option<RawSpinLockGuard<'a, ()>>,
+        biased: bool,
+    ) -> BiasedSpinLockGuard<'a, T> {
+        BiasedSpinLockGuard {
+            lock: self,
+            _word: word,
+            biased,
+        }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        self.data.get_mut()
+    }
+}
+
+/// The owner locked again while its biased guard is alive, which would
+/// alias the data instead of spinning on itself like other spinlocks.
+#[cold]
+#[track_caller]
+fn recursive_lock() -> ! {
+    panic!(
+        "recursive lock acquisition at {}",
+        core::panic::Location::caller()
+    );
+}
+
+impl<T> Drop for BiasedSpinLockGuard<'_, T> {
+    fn drop(&mut self) {
+        // An unbiased guard is released when `_word` is dropped after this.
+        if self.biased {
+            self.lock.leave_biased();
+        }
+    }
+}
+
+impl<T> Deref for BiasedSpinLockGuard<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &Self::Target {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<T> DerefMut for BiasedSpinLockGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+impl<T> Lock<T> for BiasedSpinLock<T> {
+    type Guard<'a>
+        = BiasedSpinLockGuard<'a, T>
+    where
+        T: 'a;
+
+    fn lock(&self) -> Self::Guard<'_> {
+        BiasedSpinLock::lock(self)
+    }
+
+    fn try_lock(&self) -> Option<Self::Guard<'_>> {
+        BiasedSpinLock::try_lock(self)
+    }
+}
@@ -0,0 +1,152 @@
+//! Creating a [`RawSpinLock`] with several construction options at once.
+//!
+//! [`RawSpinLock::builder`] returns a [`SpinLockBuilder`] whose setters
+//! chain, and whose `build` methods take the data. Every method is a
+//! `const fn`, so a builder chain can initialise a `static`, and
+//! [`registered_static!`] and [`spin_static!`] expand to one. A builder
+//! with nothing set builds the same lock as [`RawSpinLock::new`].
+//!
+//! Options that only exist with a feature, such as the lock level or the
+//! lockdep class, can be set either way and only take effect with it, like
+//! the constructors they stand in for.
+//!
+//! [`registered_static!`]: crate::registered_static
+//! [`spin_static!`]: crate::spin_static
+
+#[cfg(not(feature = "tiny"))]
+use core::fmt;
+use core::marker::PhantomData;
+
+use crate::lockdep::LockClass;
+use crate::mutex::RawSpinLock;
+use crate::padded::CachePadded;
+use crate::padded::PaddedSpinLock;
+
+/// Construction options for a [`RawSpinLock<T>`].
+pub struct SpinLockBuilder<T> {
+    name: Option<&'static str>,
+    level: u32,
+    class: Option<&'static LockClass>,
+    locked: bool,
+    #[cfg(feature = "watchdog")]
+    spin_threshold: Option<usize>,
+    _data: PhantomData<fn(T) -> RawSpinLock<T>>,
+}
+
+impl<T> SpinLockBuilder<T> {
+    pub const fn new() -> Self {
+        Self {
+            name: None,
+            level: 0,
+            class: None,
+            locked: false,
+            #[cfg(feature = "watchdog")]
+            spin_threshold: None,
+            _data: PhantomData,
+        }
+    }
+
+    /// Lists the lock in the `registry` under `name`. Only
+    /// [`build_static`](Self::build_static) registers it, since the lock
+    /// must not move once the registry points at it; `build` ignores the
+    /// name.
+    pub const fn name(mut self, name: &'static str) -> Self {
+        self.name = Some(name);
+        self
+    }
+
+    /// Puts the lock at `level` in the lock order, as
+    /// [`RawSpinLock::with_level`] does.
+    pub const fn level(mut self, level: u32) -> Self {
+        self.level = level;
+        self
+    }
+
+    /// Places the lock in a lockdep class, as [`RawSpinLock::in_class`]
+    /// does.
+    pub const fn class(mut self, class: &'static LockClass) -> Self {
+        self.class = Some(class);
+        self
+    }
+
+    /// Creates the lock held, as [`RawSpinLock::new_locked`] does.
+    pub const fn locked(mut self) -> Self {
+        self.locked = true;
+        self
+    }
+
+    /// Overrides the watchdog spin threshold, as
+    /// [`RawSpinLock::set_spin_threshold`] does once the lock exists.
+    #[cfg(feature = "watchdog")]
+    pub const fn spin_threshold(mut self, spins: Option<usize>) -> Self {
+        self.spin_threshold = spins;
+        self
+    }
+
+    /// Creates the lock around `data`, without registering it.
+    pub const fn build(self, data: T) -> RawSpinLock<T> {
+        let mut lock = RawSpinLock::with_level(data, self.level);
+        if let Some(class) = self.class {
+            lock = lock.in_class(class);
+        }
+        if self.locked {
+            lock = lock.initially_locked();
+        }
+        #[cfg(feature = "watchdog")]
+        {
+            lock = lock.with_spin_threshold(self.spin_threshold);
+        }
+        lock
+    }
+
+    /// Creates the lock on a cache line of its own; see
+    /// [`PaddedSpinLock`]. Like `build`, it does not register the lock.
+    pub const fn build_padded(self, data: T) -> PaddedSpinLock<T> {
+        CachePadded::new(self.build(data))
+    }
+
+    /// Creates the lock and, with a [`name`](Self::name) set, registers it
+    /// like [`RawSpinLock::registered`].
+    ///
+    /// # Safety
+    ///
+    /// With a name set, the lock must not be moved or dropped after it is
+    /// first locked, which a `static` guarantees.
+    pub const unsafe fn build_static(self, data: T) -> RawSpinLock<T> {
+        let name = self.name;
+        let lock = self.build(data);
+        match name {
+            // SAFETY: guaranteed by the caller.
+            Some(name) => unsafe { lock.named(name) },
+            None => lock,
+        }
+    }
+}
+
+impl<T> Default for SpinLockBuilder<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Clone for SpinLockBuilder<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for SpinLockBuilder<T> {}
+
+#[cfg(not(feature = "tiny"))]
+impl<T> fmt::Debug for SpinLockBuilder<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut d = f.debug_struct("SpinLockBuilder");
+        d.field("name", &self.name)
+            .field("level", &self.level)
+            .field("class", &self.class.map(LockClass::name))
+            .field("locked", &self.locked);
+        #[cfg(feature = "watchdog")]
+        d.field("spin_threshold", &self.spin_threshold);
+        d.finish()
+    }
+}
@@ -0,0 +1,202 @@
+//! Sharing an `embedded-hal` bus between drivers.
+//!
+//! Drivers take their I2C or SPI bus by value, so several devices on one
+//! bus each need a handle that behaves as if it owned it. With the bus in a
+//! [`RawSpinLock`], [`SpinDevice`] is such a handle: it implements `I2c`,
+//! and `SpiDevice` for a bus that already is one, by holding the lock for
+//! each whole transaction, so the transactions of two drivers never
+//! interleave, on one core or several. [`SpinSpiDevice`] does the same for
+//! an `SpiBus` shared by devices with a chip-select pin each, and only
+//! asserts the pin while it holds the lock.
+//!
+//! Before raw atomics are enabled the lock excludes nobody. While a single
+//! core calls its drivers from thread context that changes nothing, as one
+//! transaction ends before the next starts, but a driver called from an
+//! interrupt handler can break into the middle of one. Keep such drivers
+//! quiet until the gate opens. Afterwards the caveat of
+//! [`irq`](crate::irq) applies instead: a handler that interrupts a
+//! transaction on its own core spins forever waiting for the bus, so lock
+//! the bus through [`lock_irqsave`](RawSpinLock::lock_irqsave) around the
+//! thread-context transactions, or leave the bus to one context per core.
+
+use core::fmt;
+
+use embedded_hal::delay::DelayNs;
+use embedded_hal::digital::OutputPin;
+use embedded_hal::i2c;
+use embedded_hal::i2c::I2c;
+use embedded_hal::spi;
+use embedded_hal::spi::SpiBus;
+use embedded_hal::spi::SpiDevice;
+
+use crate::mutex::RawSpinLock;
+
+/// One driver's handle to a bus shared through a [`RawSpinLock`].
+///
+/// It is only a reference to the lock, so every driver on the bus gets a
+/// copy.
+pub struct SpinDevice<'a, BUS> {
+    bus: &'a RawSpinLock<BUS>,
+}
+
+impl<'a, BUS> SpinDevice<'a, BUS> {
+    pub const fn new(bus: &'a RawSpinLock<BUS>) -> Self {
+        Self { bus }
+    }
+}
+
+impl<BUS> Clone for SpinDevice<'_, BUS> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<BUS> Copy for SpinDevice<'_, BUS> {}
+
+#[cfg(not(feature = "tiny"))]
+impl<BUS> fmt::Debug for SpinDevice<'_, BUS> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("SpinDevice")
+            .field(&core::ptr::from_ref(self.bus))
+            .finish()
+    }
+}
+
+impl<BUS: i2c::ErrorType> i2c::ErrorType for SpinDevice<'_, BUS> {
+    type Error = BUS::Error;
+}
+
+impl<A: i2c::AddressMode, BUS: I2c<A>> I2c<A> for SpinDevice<'_, BUS> {
+    #[track_caller]
+    fn read(&mut self, address: A, read: &mut [u8]) -> Result<(), Self::Error> {
+        self.bus.lock().read(address, read)
+    }
+
+    #[track_caller]
+    fn write(&mut self, address: A, write: &[u8]) -> Result<(), Self::Error> {
+        self.bus.lock().write(address, write)
+    }
+
+    #[track_caller]
+    fn write_read(&mut self, address: A, write: &[u8], read: &mut [u8]) -> Result<(), Self::Error> {
+        self.bus.lock().write_read(address, write, read)
+    }
+
+    #[track_caller]
+    fn transaction(
+        &mut self,
+        address: A,
+        operations: &mut [i2c::Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        self.bus.lock().transaction(address, operations)
+    }
+}
+
+impl<BUS: spi::ErrorType> spi::ErrorType for SpinDevice<'_, BUS> {
+    type Error = BUS::Error;
+}
+
+/// For a bus that selects its devices itself, such as one behind an
+/// `ExclusiveDevice`.
+impl<Word: Copy + 'static, BUS: SpiDevice<Word>> SpiDevice<Word> for SpinDevice<'_, BUS> {
+    #[track_caller]
+    fn transaction(
+        &mut self,
+        operations: &mut [spi::Operation<'_, Word>],
+    ) -> Result<(), Self::Error> {
+        self.bus.lock().transaction(operations)
+    }
+}
+
+/// An error from a [`SpinSpiDevice`]: from the bus, or from driving its
+/// chip-select pin.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum DeviceError<BUS, CS> {
+    Spi(BUS),
+    Cs(CS),
+}
+
+impl<BUS: spi::Error, CS: fmt::Debug> spi::Error for DeviceError<BUS, CS> {
+    fn kind(&self) -> spi::ErrorKind {
+        match self {
+            Self::Spi(error) => error.kind(),
+            Self::Cs(_) => spi::ErrorKind::ChipSelectFault,
+        }
+    }
+}
+
+/// A device with its own chip-select pin on an `SpiBus` shared through a
+/// [`RawSpinLock`].
+///
+/// A transaction locks the bus, asserts the pin, runs the operations,
+/// flushes the bus and deasserts the pin before the lock is released.
+/// `DelayNs` operations flush the bus and then wait on `delay`.
+pub struct SpinSpiDevice<'a, BUS, CS, D> {
+    bus: &'a RawSpinLock<BUS>,
+    cs: CS,
+    delay: D,
+}
+
+impl<'a, BUS, CS: OutputPin, D> SpinSpiDevice<'a, BUS, CS, D> {
+    /// Creates the device and deasserts `cs`, driving it high.
+    pub fn new(bus: &'a RawSpinLock<BUS>, mut cs: CS, delay: D) -> Result<Self, CS::Error> {
+        cs.set_high()?;
+        Ok(Self { bus, cs, delay })
+    }
+
+    pub fn into_parts(self) -> (CS, D) {
+        (self.cs, self.delay)
+    }
+}
+
+#[cfg(not(feature = "tiny"))]
+impl<BUS, CS, D> fmt::Debug for SpinSpiDevice<'_, BUS, CS, D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SpinSpiDevice")
+            .field("bus", &core::ptr::from_ref(self.bus))
+            .finish_non_exhaustive()
+    }
+}
+
+impl<BUS: spi::ErrorType, CS: OutputPin, D> spi::ErrorType for SpinSpiDevice<'_, BUS, CS, D> {
+    type Error = DeviceError<BUS::Error, CS::Error>;
+}
+
+impl<Word, BUS, CS, D> SpiDevice<Word> for SpinSpiDevice<'_, BUS, CS, D>
+where
+    Word: Copy + 'static,
+    BUS: SpiBus<Word>,
+    CS: OutputPin,
+    D: DelayNs,
+{
+    #[track_caller]
+    fn transaction(
+        &mut self,
+        operations: &mut [spi::Operation<'_, Word>],
+    ) -> Result<(), Self::Error> {
+        let mut bus = self.bus.lock();
+        self.cs.set_low().map_err(DeviceError::Cs)?;
+        let result = operations
+            .iter_mut()
+            .try_for_each(|operation| match operation {
+                spi::Operation::Read(words) => bus.read(words),
+                spi::Operation::Write(words) => bus.write(words),
+                spi::Operation::Transfer(read, write) => bus.transfer(read, write),
+                spi::Operation::TransferInPlace(words) => bus.transfer_in_place(words),
+                spi::Operation::DelayNs(ns) => {
+                    bus.flush()?;
+                    self.delay.delay_ns(*ns);
+                    Ok(())
+                }
+            });
+        // Flush and deselect even after a failed operation, so the next
+        // device finds the bus idle.
+        let flushed = bus.flush();
+        let deselected = self.cs.set_high();
+        drop(bus);
+        result.map_err(DeviceError::Spi)?;
+        flushed.map_err(DeviceError::Spi)?;
+        deselected.map_err(DeviceError::Cs)
+    }
+}
@@ -0,0 +1,37 @@
+//! Cache maintenance for lock memory written before raw atomics.
+//!
+//! Before the MMU and caches are set up, permissive-mode locks are written
+//! with plain stores that may land in an uncached or stale alias of the
+//! memory the atomics will use later. A [`CacheMaintenance`] hook registered
+//! with [`set_cache_maintenance`] is called on every lock in the registry
+//! by [`enable_raw_atomics`] before the gate opens, and on single locks by
+//! [`RawSpinLock::prepare_for_atomics`], so the first atomic access sees
+//! what the plain stores wrote. Without a hook neither does anything.
+//!
+//! [`enable_raw_atomics`]: crate::raw::enable_raw_atomics
+//! [`RawSpinLock::prepare_for_atomics`]: crate::mutex::RawSpinLock::prepare_for_atomics
+
+use crate::hook::HookCell;
+use crate::hook::SetHookError;
+
+/// Kernel hook cleaning and invalidating the data cache lines covering
+/// `len` bytes from `addr`.
+pub trait CacheMaintenance {
+    fn clean_invalidate(addr: usize, len: usize);
+}
+
+static CACHE_MAINTENANCE: HookCell<fn(usize, usize)> = HookCell::new();
+
+/// Registers the cache-maintenance hook. Call once during bring-up, before
+/// [`enable_raw_atomics`](crate::raw::enable_raw_atomics).
+pub fn set_cache_maintenance<C: CacheMaintenance>() -> Result<(), SetHookError> {
+    CACHE_MAINTENANCE.set(C::clean_invalidate)
+}
+
+/// Cleans and invalidates `len` bytes from `addr`, if a hook is registered.
+#[inline]
+pub(crate) fn clean_invalidate(addr: usize, len: usize) {
+    if let Some(clean_invalidate) = CACHE_MAINTENANCE.get() {
+        clean_invalidate(addr, len);
+    }
+}
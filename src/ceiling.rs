@@ -0,0 +1,203 @@
+//! Priority-ceiling locking on Cortex-M.
+//!
+//! On ARMv7-M and ARMv8-M Mainline, writing BASEPRI masks every interrupt
+//! at or below a priority while leaving more urgent ones enabled. A
+//! [`CeilingLock`] raises BASEPRI to its ceiling before taking its
+//! [`RawSpinLock`], and its guard releases the lock and then restores the
+//! previous BASEPRI. Raising only ever masks more, so nested guards unwind
+//! correctly as long as they are dropped in reverse order. On a single core
+//! the spinlock is never contended, since everything that could contend is
+//! masked; on multi-core parts it excludes the other cores.
+//!
+//! `CEILING` is the raw BASEPRI value, with the priority already shifted
+//! into the implemented high bits, so on a part with 3 priority bits
+//! priority 2 is `2 << 5`. Lower values mask more, and 0 would mask nothing,
+//! so it is rejected at compile time. Every context that takes the lock must
+//! run at a priority the ceiling masks: a handler above the ceiling that
+//! takes it spins forever against the context it interrupted.
+//!
+//! The feature assumes a bare-metal ARM target is M-profile Mainline;
+//! there BASEPRI is accessed directly. Everywhere else a [`BasepriControl`]
+//! hook can be registered with [`set_basepri_control`] to stand in for the
+//! register, for instance to test on the host. Without one a `CeilingLock`
+//! is a plain [`RawSpinLock`].
+
+use core::mem::ManuallyDrop;
+use core::ops::Deref;
+use core::ops::DerefMut;
+
+#[cfg(not(all(target_arch = "arm", target_os = "none")))]
+use crate::hook::HookCell;
+use crate::hook::SetHookError;
+use crate::mutex::RawSpinLock;
+use crate::mutex::RawSpinLockGuard;
+
+/// Platform hook standing in for BASEPRI where there is none.
+pub trait BasepriControl {
+    /// Returns the current BASEPRI value.
+    fn read() -> u8;
+
+    /// Sets BASEPRI to `value`.
+    fn write(value: u8);
+}
+
+#[cfg(not(all(target_arch = "arm", target_os = "none")))]
+#[derive(Clone, Copy)]
+struct Hooks {
+    read: fn() -> u8,
+    write: fn(u8),
+}
+
+#[cfg(not(all(target_arch = "arm", target_os = "none")))]
+static BASEPRI_CONTROL: HookCell<Hooks> = HookCell::new();
+
+/// Registers the BASEPRI hook. Call once during bring-up.
+///
+/// On bare-metal ARM the real register is always used and the hook is
+/// ignored.
+pub fn set_basepri_control<B: BasepriControl>() -> Result<(), SetHookError> {
+    #[cfg(not(all(target_arch = "arm", target_os = "none")))]
+    return BASEPRI_CONTROL.set(Hooks {
+        read: B::read,
+        write: B::write,
+    });
+    #[cfg(all(target_arch = "arm", target_os = "none"))]
+    Ok(())
+}
+
+/// Raises BASEPRI to `ceiling` unless it already masks as much, and returns
+/// the previous value.
+#[cfg(all(target_arch = "arm", target_os = "none"))]
+#[inline]
+fn raise(ceiling: u8) -> u8 {
+    let previous: u32;
+    // SAFETY: reading BASEPRI and raising it through BASEPRI_MAX only mask
+    // interrupts. Without `nomem` the writes also keep the protected
+    // accesses from moving outside the masked region.
+    unsafe {
+        core::arch::asm!("mrs {}, BASEPRI", out(reg) previous, options(nomem, nostack, preserves_flags));
+        core::arch::asm!("msr BASEPRI_MAX, {}", in(reg) u32::from(ceiling), options(nostack, preserves_flags));
+    }
+    previous as u8
+}
+
+#[cfg(all(target_arch = "arm", target_os = "none"))]
+#[inline]
+fn restore(previous: u8) {
+    // SAFETY: `previous` was read from BASEPRI by the matching `raise`.
+    unsafe {
+        core::arch::asm!("msr BASEPRI, {}", in(reg) u32::from(previous), options(nostack, preserves_flags));
+    }
+}
+
+#[cfg(not(all(target_arch = "arm", target_os = "none")))]
+#[inline]
+fn raise(ceiling: u8) -> u8 {
+    let Some(hooks) = BASEPRI_CONTROL.get() else {
+        return 0;
+    };
+    let previous = (hooks.read)();
+    // What BASEPRI_MAX does in hardware.
+    if previous == 0 || ceiling < previous {
+        (hooks.write)(ceiling);
+    }
+    previous
+}
+
+#[cfg(not(all(target_arch = "arm", target_os = "none")))]
+#[inline]
+fn restore(previous: u8) {
+    if let Some(hooks) = BASEPRI_CONTROL.get() {
+        (hooks.write)(previous);
+    }
+}
+
+/// A [`RawSpinLock`] taken with BASEPRI raised to `CEILING`.
+pub struct CeilingLock<T, const CEILING: u8> {
+    inner: RawSpinLock<T>,
+}
+
+/// Guard returned by [`CeilingLock::lock`].
+///
+/// Dropping it releases the lock first and then restores the BASEPRI value
+/// it replaced.
+pub struct CeilingLockGuard<'a, T> {
+    guard: ManuallyDrop<RawSpinLockGuard<'a, T>>,
+    previous: u8,
+}
+
+impl<T, const CEILING: u8> CeilingLock<T, CEILING> {
+    pub const fn new(data: T) -> Self {
+        const { assert!(CEILING != 0, "a ceiling of 0 masks no interrupts") };
+        Self {
+            inner: RawSpinLock::new(data),
+        }
+    }
+
+    /// Raises BASEPRI to the ceiling, then acquires the lock.
+    pub fn lock(&self) -> CeilingLockGuard<'_, T> {
+        let previous = raise(CEILING);
+        CeilingLockGuard {
+            guard: ManuallyDrop::new(self.inner.lock()),
+            previous,
+        }
+    }
+
+    /// Like [`lock`](Self::lock), but fails instead of spinning, in which
+    /// case BASEPRI is restored immediately.
+    pub fn try_lock(&self) -> Option<CeilingLockGuard<'_, T>> {
+        let previous = raise(CEILING);
+        match self.inner.try_lock() {
+            Some(guard) => Some(CeilingLockGuard {
+                guard: ManuallyDrop::new(guard),
+                previous,
+            }),
+            None => {
+                restore(previous);
+                None
+            }
+        }
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        self.inner.get_mut()
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner.into_inner()
+    }
+}
+
+impl<T: Default, const CEILING: u8> Default for CeilingLock<T, CEILING> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T> Drop for CeilingLockGuard<'_, T> {
+    fn drop(&mut self) {
+        // SAFETY: the inner guard is dropped exactly once, here.
+        unsafe { ManuallyDrop::drop(&mut self.guard) };
+        restore(self.previous);
+    }
+}
+
+impl<T> Deref for CeilingLockGuard<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &Self::Target {
+        &self.guard
+    }
+}
+
+impl<T> DerefMut for CeilingLockGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.guard
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl<T: defmt::Format> defmt::Format for CeilingLockGuard<'_, T> {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        defmt::Format::format(&**self, f)
+    }
+}
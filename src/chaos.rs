@@ -0,0 +1,74 @@
+//! Random delays around lock transitions, to widen race windows in tests.
+//!
+//! Critical sections in this crate are short, so a test exercising code
+//! built on its locks rarely sees two threads meet at a lock, and races in
+//! that code stay hidden. With the `chaos` feature, after [`configure`] the
+//! atomic lock paths spin for a random number of rounds, up to a maximum:
+//!
+//! - after the CAS or increment that takes a lock, before the guard is
+//!   handed out;
+//! - before the store that releases it;
+//! - on every round of an [`RwSpinLock`] writer waiting for readers to
+//!   drain.
+//!
+//! This covers [`RawSpinLock`], [`RwSpinLock`], the raw word API and
+//! everything built on them. The delays come from one global generator,
+//! seeded by [`configure`], so a run with the same seed draws the same
+//! sequence of delays. Which thread gets which delay still depends on how
+//! the threads are scheduled, so a seed that found a race makes it likely
+//! to show again rather than certain. Until [`configure`] is called, or
+//! with a maximum of 0, nothing is delayed.
+//!
+//! The feature is for tests only. It cannot be enabled in a build without
+//! debug assertions, which rules out release builds of downstream code
+//! that turned it on by accident: enable it for the test run, as in
+//! `cargo test --features mutex/chaos`.
+//!
+//! [`RawSpinLock`]: crate::mutex::RawSpinLock
+//! [`RwSpinLock`]: crate::rwlock::RwSpinLock
+
+use crate::atomic::Ordering;
+use crate::atomic::global::AtomicUsize;
+
+#[cfg(not(debug_assertions))]
+compile_error!("the `chaos` feature is for tests only and needs debug assertions");
+
+/// The largest delay, in spins; 0 disables chaos.
+static MAX_DELAY: AtomicUsize = AtomicUsize::new(0);
+/// The generator's state, advanced once per delay.
+static STATE: AtomicUsize = AtomicUsize::new(0);
+
+/// Starts delaying lock transitions by up to `max_delay_spins` spins each,
+/// drawn from a generator seeded with `seed`. Calling it again restarts
+/// the sequence; a maximum of 0 turns the delays off.
+pub fn configure(seed: u64, max_delay_spins: usize) {
+    STATE.store(seed as usize, Ordering::Relaxed);
+    MAX_DELAY.store(max_delay_spins, Ordering::Relaxed);
+}
+
+/// Spins for the next delay, if chaos is configured. Only called on paths
+/// that already use read-modify-write atomics.
+#[inline(always)]
+pub(crate) fn delay() {
+    let max = MAX_DELAY.load(Ordering::Relaxed);
+    if max != 0 {
+        spin(max);
+    }
+}
+
+#[cold]
+#[inline(never)]
+fn spin(max: usize) {
+    const GOLDEN: u64 = 0x9e37_79b9_7f4a_7c15;
+    let state = STATE.fetch_add(GOLDEN as usize, Ordering::Relaxed) as u64;
+    // The splitmix64 output function, so consecutive states give unrelated
+    // delays.
+    let mut z = state.wrapping_add(GOLDEN);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^= z >> 31;
+    let spins = (z % (max as u64).saturating_add(1)) as usize;
+    for _ in 0..spins {
+        crate::atomic::spin_loop();
+    }
+}
@@ -0,0 +1,46 @@
+//! A monotonic time source for measurements inside the locks.
+//!
+//! Nothing is timed until a [`Clock`] is registered with [`set_clock`], so
+//! builds that never register one pay only for checking that none is. On
+//! `std`, [`StdClock`] is available for registering.
+
+use crate::hook::HookCell;
+use crate::hook::SetHookError;
+
+/// Kernel hook reading a monotonic counter, such as the cycle counter.
+///
+/// The unit is up to the implementation; durations derived from it are
+/// reported in the same unit.
+pub trait Clock {
+    fn now() -> u64;
+}
+
+static CLOCK: HookCell<fn() -> u64> = HookCell::new();
+
+/// Registers the clock. Call once during bring-up.
+pub fn set_clock<C: Clock>() -> Result<(), SetHookError> {
+    CLOCK.set(C::now)
+}
+
+/// Returns the current time, or `None` if no clock is registered.
+#[cfg_attr(
+    not(any(feature = "hold-time", feature = "metrics", feature = "panic-hook")),
+    allow(dead_code)
+)]
+#[inline(always)]
+pub(crate) fn now() -> Option<u64> {
+    CLOCK.get().map(|now| now())
+}
+
+/// Nanoseconds from [`std::time::Instant`], counted from the first reading.
+#[cfg(feature = "std")]
+pub struct StdClock;
+
+#[cfg(feature = "std")]
+impl Clock for StdClock {
+    fn now() -> u64 {
+        static EPOCH: std::sync::OnceLock<std::time::Instant> = std::sync::OnceLock::new();
+        let epoch = *EPOCH.get_or_init(std::time::Instant::now);
+        u64::try_from(epoch.elapsed().as_nanos()).unwrap_or(u64::MAX)
+    }
+}
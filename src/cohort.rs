@@ -0,0 +1,247 @@
+//! A NUMA-aware cohort lock.
+//!
+//! [`CohortLock`] pairs a global ticket lock with one ticket lock per NUMA
+//! node (cohort). A thread first takes its node's local lock, then the
+//! global one. On release, if another thread of the same node is already
+//! queued on the local lock, the global lock is passed to it along with the
+//! local one, so ownership and the protected data's cache lines stay on one
+//! socket. After [`MAX_LOCAL_HANDOFFS`] consecutive local handoffs the global
+//! lock is released anyway, so other nodes are not starved.
+//!
+//! Nodes come from the [`Topology`](crate::topology::Topology) hook.
+
+use core::cell::UnsafeCell;
+use core::ops::Deref;
+use core::ops::DerefMut;
+use core::panic::RefUnwindSafe;
+use core::panic::UnwindSafe;
+
+use crate::atomic::AtomicBool;
+use crate::atomic::AtomicUsize;
+use crate::atomic::Ordering;
+use crate::mutex::Lock;
+use crate::padded::CachePadded;
+use crate::preempt;
+use crate::raw::raw_atomics_enabled;
+use crate::relax;
+use crate::relax::Backoff;
+use crate::topology;
+
+/// Consecutive handoffs within one cohort before the global lock is
+/// released to the other nodes.
+pub const MAX_LOCAL_HANDOFFS: usize = 64;
+
+struct TicketLock {
+    next: AtomicUsize,
+    serving: AtomicUsize,
+}
+
+impl TicketLock {
+    const fn new() -> Self {
+        Self {
+            next: AtomicUsize::new(0),
+            serving: AtomicUsize::new(0),
+        }
+    }
+
+    fn lock(&self) {
+        let ticket = self.next.fetch_add(1, Ordering::Relaxed);
+        let mut backoff = Backoff::new();
+        while self.serving.load(Ordering::Acquire) != ticket {
+            backoff.wait();
+        }
+    }
+
+    fn try_lock(&self) -> bool {
+        // The previous holder released through `serving`, so that is the
+        // load that must acquire; the exchange on `next` only claims the
+        // ticket.
+        let serving = self.serving.load(Ordering::Acquire);
+        self.next
+            .compare_exchange(serving, serving + 1, Ordering::Relaxed, Ordering::Relaxed)
+            .is_ok()
+    }
+
+    /// Only meaningful while holding the lock.
+    fn has_waiters(&self) -> bool {
+        // `serving` only changes under the lock, so only `next` can race.
+        self.next.load(Ordering::Relaxed) != self.serving.load(Ordering::Relaxed) + 1
+    }
+
+    fn unlock(&self) {
+        // Only the holder writes `serving`, so a load and store suffice.
+        let serving = self.serving.load(Ordering::Relaxed);
+        self.serving.store(serving + 1, Ordering::Release);
+        relax::notify();
+    }
+}
+
+struct Cohort {
+    local: TicketLock,
+    // Both fields are only accessed by the holder of `local`.
+    owns_global: AtomicBool,
+    handoffs: AtomicUsize,
+}
+
+impl Cohort {
+    const fn new() -> Self {
+        Self {
+            local: TicketLock::new(),
+            owns_global: AtomicBool::new(false),
+            handoffs: AtomicUsize::new(0),
+        }
+    }
+}
+
+/// A spinlock that prefers handing ownership to waiters on the same NUMA
+/// node.
+///
+/// `NODES` is the number of cohorts; node ids reported by the
+/// [`Topology`](crate::topology::Topology) hook are folded into that range.
+/// Like [`RawSpinLock`](crate::mutex::RawSpinLock), it is permissive until
+/// raw atomics are enabled.
+pub struct CohortLock<T: ?Sized, const NODES: usize = 2> {
+    global: CachePadded<TicketLock>,
+    cohorts: [CachePadded<Cohort>; NODES],
+    data: UnsafeCell<T>,
+}
+
+pub struct CohortLockGuard<'a, T, const NODES: usize = 2> {
+    lock: &'a CohortLock<T, NODES>,
+    node: usize,
+    unlock_on_drop: bool,
+}
+
+unsafe impl<T: ?Sized + Send, const NODES: usize> Send for CohortLock<T, NODES> {}
+unsafe impl<T: ?Sized + Send, const NODES: usize> Sync for CohortLock<T, NODES> {}
+
+// Unwind safe like `std::sync::Mutex`, but without poisoning; see
+// `RawSpinLock`.
+impl<T: ?Sized, const NODES: usize> UnwindSafe for CohortLock<T, NODES> {}
+impl<T: ?Sized, const NODES: usize> RefUnwindSafe for CohortLock<T, NODES> {}
+
+const _: () = {
+    crate::mutex::assert_unwind_safe::<CohortLock<core::cell::Cell<u8>>>();
+    crate::mutex::assert_unwind_safe::<CohortLockGuard<'_, &mut u8>>();
+};
+
+impl<T, const NODES: usize> CohortLock<T, NODES> {
+    pub const fn new(data: T) -> Self {
+        assert!(NODES > 0, "a cohort lock needs at least one node");
+        Self {
+            global: CachePadded::new(TicketLock::new()),
+            cohorts: [const { CachePadded::new(Cohort::new()) }; NODES],
+            data: UnsafeCell::new(data),
+        }
+    }
+
+    fn current_node() -> usize {
+        topology::current_node() % NODES
+    }
+
+    pub fn lock(&self) -> CohortLockGuard<'_, T, NODES> {
+        let node = Self::current_node();
+        let unlock_on_drop = raw_atomics_enabled();
+        if unlock_on_drop {
+            let cohort = &self.cohorts[node];
+            cohort.local.lock();
+            if !cohort.owns_global.load(Ordering::Relaxed) {
+                self.global.lock();
+                cohort.owns_global.store(true, Ordering::Relaxed);
+            }
+            preempt::disable();
+        }
+        CohortLockGuard {
+            lock: self,
+            node,
+            unlock_on_drop,
+        }
+    }
+
+    /// Attempts to acquire the lock without spinning.
+    ///
+    /// Returns `None` if the lock is currently held. Before raw atomics are
+    /// enabled this always succeeds, mirroring [`lock`](Self::lock).
+    pub fn try_lock(&self) -> Option<CohortLockGuard<'_, T, NODES>> {
+        let node = Self::current_node();
+        let unlock_on_drop = raw_atomics_enabled();
+        if unlock_on_drop {
+            let cohort = &self.cohorts[node];
+            if !cohort.local.try_lock() {
+                return None;
+            }
+            if !cohort.owns_global.load(Ordering::Relaxed) {
+                if !self.global.try_lock() {
+                    cohort.local.unlock();
+                    return None;
+                }
+                cohort.owns_global.store(true, Ordering::Relaxed);
+            }
+            preempt::disable();
+        }
+        Some(CohortLockGuard {
+            lock: self,
+            node,
+            unlock_on_drop,
+        })
+    }
+
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        self.data.get_mut()
+    }
+
+    fn unlock(&self, node: usize) {
+        let cohort = &self.cohorts[node];
+        let handoffs = cohort.handoffs.load(Ordering::Relaxed);
+        if handoffs < MAX_LOCAL_HANDOFFS && cohort.local.has_waiters() {
+            // The queued local waiter inherits the global lock.
+            cohort.handoffs.store(handoffs + 1, Ordering::Relaxed);
+        } else {
+            cohort.handoffs.store(0, Ordering::Relaxed);
+            cohort.owns_global.store(false, Ordering::Relaxed);
+            self.global.unlock();
+        }
+        cohort.local.unlock();
+    }
+}
+
+impl<T, const NODES: usize> Drop for CohortLockGuard<'_, T, NODES> {
+    fn drop(&mut self) {
+        if self.unlock_on_drop {
+            self.lock.unlock(self.node);
+            preempt::enable();
+        }
+    }
+}
+
+impl<T, const NODES: usize> Deref for CohortLockGuard<'_, T, NODES> {
+    type Target = T;
+    fn deref(&self) -> &Self::Target {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<T, const NODES: usize> DerefMut for CohortLockGuard<'_, T, NODES> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+impl<T, const NODES: usize> Lock<T> for CohortLock<T, NODES> {
+    type Guard<'a>
+        = CohortLockGuard<'a, T, NODES>
+    where
+        T: 'a;
+
+    fn lock(&self) -> Self::Guard<'_> {
+        CohortLock::lock(self)
+    }
+
+    fn try_lock(&self) -> Option<Self::Guard<'_>> {
+        CohortLock::try_lock(self)
+    }
+}
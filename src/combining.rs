@@ -0,0 +1,294 @@
+//! A flat-combining lock.
+//!
+//! [`FlatCombiningLock::apply`] runs an operation on the protected value.
+//! An uncontended caller takes the lock and runs it directly. A caller that
+//! finds the lock held instead publishes the operation in one of `SLOTS`
+//! slots and waits; whoever holds the lock runs every published operation
+//! before releasing it, so a burst of callers is served in one pass without
+//! the lock or the value's cache lines changing hands between them. Waiters
+//! keep trying the lock, and the one that gets it combines, so no operation
+//! waits on a holder that is gone.
+//!
+//! Operations and their results live on the waiting caller's stack; the
+//! slots only point at them, so nothing is allocated. When every slot is
+//! taken, callers fall back to waiting for the lock.
+//!
+//! An operation that panics while another thread combines it is caught
+//! there with the `std` feature and resumed on the caller that published
+//! it, so the combiner carries on. Without `std` the panic unwinds the
+//! combiner, and the publisher panics as well once it sees the slot marked.
+//! As with [`RawSpinLock`], nothing is poisoned.
+
+use core::cell::UnsafeCell;
+use core::panic::RefUnwindSafe;
+use core::panic::UnwindSafe;
+
+use crate::atomic::AtomicU8;
+use crate::atomic::Ordering;
+use crate::mutex::RawSpinLock;
+use crate::padded::CachePadded;
+use crate::relax;
+use crate::relax::Backoff;
+
+/// The default number of publication slots.
+pub const DEFAULT_SLOTS: usize = 8;
+
+const FREE: u8 = 0;
+/// Claimed by a publisher that is still filling it in.
+const CLAIMED: u8 = 1;
+const PENDING: u8 = 2;
+/// Taken by a combiner.
+const RUNNING: u8 = 3;
+const DONE: u8 = 4;
+const PANICKED: u8 = 5;
+
+/// An operation published by a waiting caller.
+struct Request<T> {
+    /// Points at the caller's [`Pending`].
+    pending: *mut (),
+    run: unsafe fn(*mut (), &mut T),
+}
+
+impl<T> Request<T> {
+    fn new<F: FnOnce(&mut T) -> R, R>(pending: &mut Pending<F, R>) -> Self {
+        Self {
+            pending: (pending as *mut Pending<F, R>).cast(),
+            run: run::<T, F, R>,
+        }
+    }
+}
+
+impl<T> Clone for Request<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Request<T> {}
+
+struct Slot<T> {
+    state: AtomicU8,
+    // Written by the publisher between claiming the slot and marking it
+    // pending, read by the combiner while it is pending.
+    request: UnsafeCell<Option<Request<T>>>,
+    // Written by the combiner before marking the slot panicked, taken by
+    // the publisher afterwards.
+    #[cfg(feature = "std")]
+    panic: UnsafeCell<Option<std::boxed::Box<dyn core::any::Any + Send>>>,
+}
+
+impl<T> Slot<T> {
+    const fn new() -> Self {
+        Self {
+            state: AtomicU8::new(FREE),
+            request: UnsafeCell::new(None),
+            #[cfg(feature = "std")]
+            panic: UnsafeCell::new(None),
+        }
+    }
+}
+
+/// The caller's side of a published operation.
+struct Pending<F, R> {
+    op: Option<F>,
+    result: Option<R>,
+}
+
+/// Runs the operation behind a [`Request`].
+///
+/// # Safety
+///
+/// `pending` must point at a live `Pending<F, R>` whose operation has not
+/// run yet.
+unsafe fn run<T, F: FnOnce(&mut T) -> R, R>(pending: *mut (), data: &mut T) {
+    // SAFETY: the caller guarantees `pending` is live and of this type.
+    let pending = unsafe { &mut *pending.cast::<Pending<F, R>>() };
+    let op = pending.op.take().expect("operation combined twice");
+    pending.result = Some(op(data));
+}
+
+/// Marks a slot panicked if the operation it holds unwinds the combiner.
+struct Completion<'a, T> {
+    slot: &'a Slot<T>,
+}
+
+impl<T> Drop for Completion<'_, T> {
+    fn drop(&mut self) {
+        self.slot.state.store(PANICKED, Ordering::Release);
+    }
+}
+
+/// Withdraws a published operation if its publisher unwinds while waiting,
+/// which can only happen when it combines another operation that panics.
+struct Publication<'a, T> {
+    slot: &'a Slot<T>,
+}
+
+impl<T> Drop for Publication<'_, T> {
+    fn drop(&mut self) {
+        let withdrawn = self
+            .slot
+            .state
+            .compare_exchange(PENDING, FREE, Ordering::Relaxed, Ordering::Relaxed)
+            .is_ok();
+        if !withdrawn {
+            // A combiner is running it and still uses the caller's stack.
+            while !matches!(self.slot.state.load(Ordering::Acquire), DONE | PANICKED) {
+                relax::spin();
+            }
+            self.slot.state.store(FREE, Ordering::Release);
+        }
+    }
+}
+
+/// A lock whose holder also runs the operations of callers waiting for it.
+///
+/// Like [`RawSpinLock`], which it is built on, it is permissive until raw
+/// atomics are enabled, and every caller then runs its operation directly.
+pub struct FlatCombiningLock<T, const SLOTS: usize = DEFAULT_SLOTS> {
+    inner: RawSpinLock<T>,
+    slots: [CachePadded<Slot<T>>; SLOTS],
+}
+
+// SAFETY: slots are only accessed under the protocol above, and published
+// operations and their results are required to be `Send`.
+unsafe impl<T: Send, const SLOTS: usize> Send for FlatCombiningLock<T, SLOTS> {}
+unsafe impl<T: Send, const SLOTS: usize> Sync for FlatCombiningLock<T, SLOTS> {}
+
+// Unwind safe like `std::sync::Mutex`, but without poisoning; see
+// `RawSpinLock`.
+impl<T, const SLOTS: usize> UnwindSafe for FlatCombiningLock<T, SLOTS> {}
+impl<T, const SLOTS: usize> RefUnwindSafe for FlatCombiningLock<T, SLOTS> {}
+
+impl<T, const SLOTS: usize> FlatCombiningLock<T, SLOTS> {
+    pub const fn new(data: T) -> Self {
+        Self {
+            inner: RawSpinLock::new(data),
+            slots: [const { CachePadded::new(Slot::new()) }; SLOTS],
+        }
+    }
+
+    /// Runs `op` on the protected value, on this thread if the lock is free
+    /// and otherwise possibly on the thread holding it, which is why `op`
+    /// and its result must be `Send`.
+    pub fn apply<R: Send>(&self, op: impl FnOnce(&mut T) -> R + Send) -> R {
+        if let Some(mut guard) = self.inner.try_lock() {
+            let result = op(&mut guard);
+            self.combine(&mut guard);
+            return result;
+        }
+        let Some(slot) = self.claim() else {
+            let mut guard = self.inner.lock();
+            let result = op(&mut guard);
+            self.combine(&mut guard);
+            return result;
+        };
+        let mut pending = Pending {
+            op: Some(op),
+            result: None,
+        };
+        // SAFETY: the slot is claimed, so only this thread accesses it.
+        unsafe {
+            *slot.request.get() = Some(Request::new(&mut pending));
+        }
+        slot.state.store(PENDING, Ordering::Release);
+        let publication = Publication { slot };
+
+        let mut backoff = Backoff::new();
+        let state = loop {
+            let state = slot.state.load(Ordering::Acquire);
+            if state == DONE || state == PANICKED {
+                break state;
+            }
+            match self.inner.try_lock() {
+                // Runs this slot too.
+                Some(mut guard) => self.combine(&mut guard),
+                None => backoff.wait(),
+            }
+        };
+        core::mem::forget(publication);
+        // SAFETY: a completed slot is back in this thread's hands until it
+        // is freed.
+        #[cfg(feature = "std")]
+        let panic = unsafe { (*slot.panic.get()).take() };
+        slot.state.store(FREE, Ordering::Release);
+        if state == PANICKED {
+            #[cfg(feature = "std")]
+            if let Some(panic) = panic {
+                std::panic::resume_unwind(panic);
+            }
+            panic!("an operation combined by another thread panicked");
+        }
+        pending
+            .result
+            .take()
+            .expect("combined operation left no result")
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner.into_inner()
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        self.inner.get_mut()
+    }
+
+    fn claim(&self) -> Option<&Slot<T>> {
+        self.slots.iter().map(|slot| &**slot).find(|slot| {
+            slot.state
+                .compare_exchange(FREE, CLAIMED, Ordering::Acquire, Ordering::Relaxed)
+                .is_ok()
+        })
+    }
+
+    /// Runs every pending operation. Only called with the lock held, which
+    /// `data` stands for.
+    fn combine(&self, data: &mut T) {
+        let mut combined = false;
+        for slot in &self.slots {
+            // The load keeps idle slots' lines shared between passes.
+            let taken = slot.state.load(Ordering::Relaxed) == PENDING
+                && slot
+                    .state
+                    .compare_exchange(PENDING, RUNNING, Ordering::Acquire, Ordering::Relaxed)
+                    .is_ok();
+            if !taken {
+                continue;
+            }
+            combined = true;
+            // SAFETY: a pending request is complete, and taking the slot
+            // gave this thread sole access to it.
+            let request =
+                unsafe { (*slot.request.get()).take() }.expect("pending slot without a request");
+            let completion = Completion { slot };
+            // SAFETY: the publisher waits, keeping `pending` alive, until
+            // the slot is marked complete, and the operation has not run.
+            #[cfg(feature = "std")]
+            let run = || unsafe { (request.run)(request.pending, data) };
+            #[cfg(feature = "std")]
+            if let Err(panic) = std::panic::catch_unwind(core::panic::AssertUnwindSafe(run)) {
+                // SAFETY: the slot is still this thread's.
+                unsafe { *slot.panic.get() = Some(panic) };
+                // Marks it panicked.
+                drop(completion);
+                continue;
+            }
+            // SAFETY: as above.
+            #[cfg(not(feature = "std"))]
+            unsafe {
+                (request.run)(request.pending, data)
+            };
+            core::mem::forget(completion);
+            slot.state.store(DONE, Ordering::Release);
+        }
+        if combined {
+            relax::notify();
+        }
+    }
+}
+
+impl<T: Default, const SLOTS: usize> Default for FlatCombiningLock<T, SLOTS> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
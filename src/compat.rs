@@ -0,0 +1,397 @@
+//! Drop-in replacements for `std::sync::Mutex` and `std::sync::RwLock`.
+//!
+//! Code written against std switches by changing only its imports:
+//! [`Mutex`] and [`RwLock`] have std's methods and signatures, and the
+//! result and error types are std's own, re-exported here. Underneath they
+//! are a [`RawSpinLock`] and an [`RwSpinLock`], so they are permissive
+//! until [`enable_raw_atomics`](crate::raw::enable_raw_atomics) like every
+//! lock in the crate, and they spin rather than block.
+//!
+//! As in std, a guard dropped while its thread panics poisons the lock, and
+//! later acquisitions report that through [`PoisonError`], which still
+//! hands out the guard. Read guards never poison. Unlike std, `T` must be
+//! `Sized`.
+//!
+//! std's error types convert into the crate's
+//! [`TryLockError`](crate::error::TryLockError), and back where std has a
+//! matching variant.
+
+// The signatures are std's, and guards grow with the debugging features.
+#![allow(clippy::result_large_err)]
+
+use core::fmt;
+use core::ops::Deref;
+use core::ops::DerefMut;
+pub use std::sync::LockResult;
+pub use std::sync::PoisonError;
+pub use std::sync::TryLockError;
+pub use std::sync::TryLockResult;
+use std::thread;
+
+use crate::atomic::AtomicBool;
+use crate::atomic::Ordering;
+use crate::error;
+use crate::mutex::RawSpinLock;
+use crate::mutex::RawSpinLockGuard;
+use crate::rwlock::RwSpinLock;
+use crate::rwlock::RwSpinLockReadGuard;
+use crate::rwlock::RwSpinLockWriteGuard;
+
+/// Whether a panic left the data behind a lock half-updated. Set and read
+/// with plain stores and loads, so it works before raw atomics are enabled.
+struct Flag(AtomicBool);
+
+impl Flag {
+    const fn new() -> Self {
+        Self(AtomicBool::new(false))
+    }
+
+    fn get(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    fn clear(&self) {
+        self.0.store(false, Ordering::Relaxed);
+    }
+
+    /// Poisons if the thread started panicking while the guard was held;
+    /// `panicking` is `thread::panicking()` sampled at acquisition.
+    fn done(&self, panicking: bool) {
+        if !panicking && thread::panicking() {
+            self.0.store(true, Ordering::Relaxed);
+        }
+    }
+
+    fn map<G>(&self, guard: G) -> LockResult<G> {
+        if self.get() {
+            Err(PoisonError::new(guard))
+        } else {
+            Ok(guard)
+        }
+    }
+}
+
+fn map_try<G>(result: Option<LockResult<G>>) -> TryLockResult<G> {
+    match result {
+        Some(Ok(guard)) => Ok(guard),
+        Some(Err(poisoned)) => Err(TryLockError::Poisoned(poisoned)),
+        None => Err(TryLockError::WouldBlock),
+    }
+}
+
+/// Drops the guard a poisoned result carries.
+impl<G> From<TryLockError<G>> for error::TryLockError {
+    fn from(err: TryLockError<G>) -> Self {
+        match err {
+            TryLockError::Poisoned(_) => Self::Poisoned,
+            TryLockError::WouldBlock => Self::WouldBlock,
+        }
+    }
+}
+
+/// Drops the guard the error carries.
+impl<G> From<PoisonError<G>> for error::TryLockError {
+    fn from(_: PoisonError<G>) -> Self {
+        Self::Poisoned
+    }
+}
+
+/// Every failure to acquire becomes `WouldBlock`. `Poisoned` is given back,
+/// as std's variant needs a guard to carry.
+impl<G> TryFrom<error::TryLockError> for TryLockError<G> {
+    type Error = error::TryLockError;
+
+    fn try_from(err: error::TryLockError) -> Result<Self, Self::Error> {
+        match err {
+            error::TryLockError::Poisoned => Err(err),
+            _ => Ok(Self::WouldBlock),
+        }
+    }
+}
+
+/// A `std::sync::Mutex` work-alike over [`RawSpinLock`].
+pub struct Mutex<T> {
+    poison: Flag,
+    inner: RawSpinLock<T>,
+}
+
+/// The guard of a [`Mutex`]; poisons it if dropped during a panic.
+pub struct MutexGuard<'a, T> {
+    lock: &'a Mutex<T>,
+    panicking: bool,
+    // Dropped after `drop` has recorded any poisoning.
+    guard: RawSpinLockGuard<'a, T>,
+}
+
+impl<T> Mutex<T> {
+    pub const fn new(t: T) -> Self {
+        Self {
+            poison: Flag::new(),
+            inner: RawSpinLock::new(t),
+        }
+    }
+
+    #[track_caller]
+    pub fn lock(&self) -> LockResult<MutexGuard<'_, T>> {
+        let guard = self.inner.lock();
+        self.poison.map(self.guard(guard))
+    }
+
+    #[track_caller]
+    pub fn try_lock(&self) -> TryLockResult<MutexGuard<'_, T>> {
+        map_try(
+            self.inner
+                .try_lock()
+                .map(|guard| self.poison.map(self.guard(guard))),
+        )
+    }
+
+    fn guard<'a>(&'a self, guard: RawSpinLockGuard<'a, T>) -> MutexGuard<'a, T> {
+        MutexGuard {
+            lock: self,
+            panicking: thread::panicking(),
+            guard,
+        }
+    }
+
+    pub fn is_poisoned(&self) -> bool {
+        self.poison.get()
+    }
+
+    pub fn clear_poison(&self) {
+        self.poison.clear();
+    }
+
+    pub fn into_inner(self) -> LockResult<T> {
+        let poisoned = self.poison.get();
+        let data = self.inner.into_inner();
+        if poisoned {
+            Err(PoisonError::new(data))
+        } else {
+            Ok(data)
+        }
+    }
+
+    pub fn get_mut(&mut self) -> LockResult<&mut T> {
+        self.poison.map(self.inner.get_mut())
+    }
+}
+
+impl<T: Default> Default for Mutex<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T> From<T> for Mutex<T> {
+    fn from(t: T) -> Self {
+        Self::new(t)
+    }
+}
+
+/// Formats like std: the value without blocking, or `<locked>`.
+impl<T: fmt::Debug> fmt::Debug for Mutex<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut d = f.debug_struct("Mutex");
+        match self.inner.try_lock() {
+            Some(guard) => d.field("data", &&*guard),
+            None => d.field("data", &format_args!("<locked>")),
+        };
+        d.field("poisoned", &self.is_poisoned());
+        d.finish_non_exhaustive()
+    }
+}
+
+impl<T> Drop for MutexGuard<'_, T> {
+    fn drop(&mut self) {
+        self.lock.poison.done(self.panicking);
+    }
+}
+
+impl<T> Deref for MutexGuard<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        &self.guard
+    }
+}
+
+impl<T> DerefMut for MutexGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.guard
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for MutexGuard<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for MutexGuard<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&**self, f)
+    }
+}
+
+/// A `std::sync::RwLock` work-alike over [`RwSpinLock`].
+pub struct RwLock<T> {
+    poison: Flag,
+    inner: RwSpinLock<T>,
+}
+
+/// The read guard of an [`RwLock`]; never poisons it.
+pub struct RwLockReadGuard<'a, T> {
+    guard: RwSpinLockReadGuard<'a, T>,
+}
+
+/// The write guard of an [`RwLock`]; poisons it if dropped during a panic.
+pub struct RwLockWriteGuard<'a, T> {
+    lock: &'a RwLock<T>,
+    panicking: bool,
+    // Dropped after `drop` has recorded any poisoning.
+    guard: RwSpinLockWriteGuard<'a, T>,
+}
+
+impl<T> RwLock<T> {
+    pub const fn new(t: T) -> Self {
+        Self {
+            poison: Flag::new(),
+            inner: RwSpinLock::new(t),
+        }
+    }
+
+    #[track_caller]
+    pub fn read(&self) -> LockResult<RwLockReadGuard<'_, T>> {
+        let guard = self.inner.read();
+        self.poison.map(RwLockReadGuard { guard })
+    }
+
+    #[track_caller]
+    pub fn try_read(&self) -> TryLockResult<RwLockReadGuard<'_, T>> {
+        map_try(
+            self.inner
+                .try_read()
+                .map(|guard| self.poison.map(RwLockReadGuard { guard })),
+        )
+    }
+
+    #[track_caller]
+    pub fn write(&self) -> LockResult<RwLockWriteGuard<'_, T>> {
+        let guard = self.inner.write();
+        self.poison.map(self.write_guard(guard))
+    }
+
+    #[track_caller]
+    pub fn try_write(&self) -> TryLockResult<RwLockWriteGuard<'_, T>> {
+        map_try(
+            self.inner
+                .try_write()
+                .map(|guard| self.poison.map(self.write_guard(guard))),
+        )
+    }
+
+    fn write_guard<'a>(&'a self, guard: RwSpinLockWriteGuard<'a, T>) -> RwLockWriteGuard<'a, T> {
+        RwLockWriteGuard {
+            lock: self,
+            panicking: thread::panicking(),
+            guard,
+        }
+    }
+
+    pub fn is_poisoned(&self) -> bool {
+        self.poison.get()
+    }
+
+    pub fn clear_poison(&self) {
+        self.poison.clear();
+    }
+
+    pub fn into_inner(self) -> LockResult<T> {
+        let poisoned = self.poison.get();
+        let data = self.inner.into_inner();
+        if poisoned {
+            Err(PoisonError::new(data))
+        } else {
+            Ok(data)
+        }
+    }
+
+    pub fn get_mut(&mut self) -> LockResult<&mut T> {
+        self.poison.map(self.inner.get_mut())
+    }
+}
+
+impl<T: Default> Default for RwLock<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T> From<T> for RwLock<T> {
+    fn from(t: T) -> Self {
+        Self::new(t)
+    }
+}
+
+/// Formats like std: the value without blocking, or `<locked>`.
+impl<T: fmt::Debug> fmt::Debug for RwLock<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut d = f.debug_struct("RwLock");
+        match self.inner.try_read() {
+            Some(guard) => d.field("data", &&*guard),
+            None => d.field("data", &format_args!("<locked>")),
+        };
+        d.field("poisoned", &self.is_poisoned());
+        d.finish_non_exhaustive()
+    }
+}
+
+impl<T> Deref for RwLockReadGuard<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        &self.guard
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for RwLockReadGuard<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for RwLockReadGuard<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&**self, f)
+    }
+}
+
+impl<T> Drop for RwLockWriteGuard<'_, T> {
+    fn drop(&mut self) {
+        self.lock.poison.done(self.panicking);
+    }
+}
+
+impl<T> Deref for RwLockWriteGuard<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        &self.guard
+    }
+}
+
+impl<T> DerefMut for RwLockWriteGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.guard
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for RwLockWriteGuard<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for RwLockWriteGuard<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&**self, f)
+    }
+}
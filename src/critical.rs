@@ -0,0 +1,99 @@
+//! The program's `critical-section` implementation, backed by a
+//! [`RawSpinLock`].
+//!
+//! With the `critical-section-impl` feature this crate provides
+//! `critical_section::Impl` for the whole program, so `critical_section::with`
+//! and everything built on it, such as `embassy_sync`'s
+//! `CriticalSectionRawMutex`, excludes other cores as well as interrupts. An
+//! outermost acquisition masks local interrupts through the
+//! [`InterruptControl`](crate::irq::InterruptControl) hook and then takes one
+//! crate-level `RawSpinLock`; a nested one on the same context finds itself
+//! already inside and takes nothing. The restore state is a `bool` saying
+//! which of the two it was, so the feature selects `critical-section`'s
+//! `restore-state-bool`, and nothing else in the program may select another
+//! restore state or provide a second implementation.
+//!
+//! Nesting is recognised by the [`OwnerId`](crate::owner::OwnerId) of the
+//! current context, so on more than one core an
+//! [`OwnerIdProvider`](crate::owner::OwnerIdProvider) must tell the cores
+//! apart; without one every context counts as the same, which is only right
+//! on a single core. With `std` each thread has an id already. An interrupt
+//! handler shares its core's id, which is fine, as it cannot run while its
+//! core is inside. Before raw atomics are enabled the lock excludes nobody,
+//! like any other, and the interrupt masking is all there is.
+//!
+//! Providing the implementation rules out consuming it underneath the lock:
+//! the atomics must not themselves be built on `critical-section`, as they
+//! are when `portable-atomic`'s own `critical-section` feature is enabled.
+
+use core::cell::UnsafeCell;
+
+use critical_section::RawRestoreState;
+
+use crate::atomic::Ordering;
+use crate::atomic::global::AtomicUsize;
+use crate::irq;
+use crate::mutex::RawSpinLock;
+use crate::mutex::RawSpinLockGuard;
+
+/// The implementation registered with `critical_section::set_impl!`.
+pub struct SpinCriticalSection;
+
+critical_section::set_impl!(SpinCriticalSection);
+
+static LOCK: RawSpinLock<()> = RawSpinLock::new(());
+
+/// The id of the context inside the critical section, or 0. Only ever
+/// compared with the reader's own id, which only the reader stores.
+static OWNER: AtomicUsize = AtomicUsize::new(0);
+
+/// What the outermost acquisition has to undo.
+struct Held {
+    guard: RawSpinLockGuard<'static, ()>,
+    flags: usize,
+}
+
+struct HeldCell(UnsafeCell<Option<Held>>);
+
+// SAFETY: only the context inside the critical section touches the cell,
+// with its interrupts masked.
+unsafe impl Sync for HeldCell {}
+
+static HELD: HeldCell = HeldCell(UnsafeCell::new(None));
+
+fn current() -> usize {
+    crate::owner::current().map_or(usize::MAX, |owner| owner.get())
+}
+
+// SAFETY: the outermost acquisition masks local interrupts and holds
+// `LOCK` until the matching release, which excludes every other core once
+// raw atomics are enabled; nested acquisitions happen inside it.
+unsafe impl critical_section::Impl for SpinCriticalSection {
+    unsafe fn acquire() -> RawRestoreState {
+        let me = current();
+        // Without masking: if this context is inside, nothing can interrupt
+        // it, and an interrupt handler leaves the owner as it found it.
+        if OWNER.load(Ordering::Relaxed) == me {
+            return false;
+        }
+        let flags = irq::save_and_disable();
+        let guard = LOCK.lock();
+        OWNER.store(me, Ordering::Relaxed);
+        // SAFETY: this context is inside, with interrupts masked.
+        unsafe { *HELD.0.get() = Some(Held { guard, flags }) };
+        true
+    }
+
+    unsafe fn release(outermost: RawRestoreState) {
+        if !outermost {
+            return;
+        }
+        // SAFETY: as in `acquire`; the caller is still inside.
+        let Some(held) = (unsafe { (*HELD.0.get()).take() }) else {
+            return;
+        };
+        OWNER.store(0, Ordering::Relaxed);
+        drop(held.guard);
+        irq::restore(held.flags);
+    }
+}
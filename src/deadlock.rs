@@ -0,0 +1,356 @@
+//! Wait-for-graph deadlock detection for [`RawSpinLock`].
+//!
+//! With the `deadlock-detection` feature, every context records in a shared
+//! fixed-size table which locks it holds and which lock it is spinning on.
+//! A waiter that has spun for a while walks the wait-for graph from its own
+//! lock: who holds it, what that holder waits on, and so on. If the walk
+//! comes back to the waiter, and the same cycle is still there on the next
+//! check, the deadlock is reported through the handler registered with
+//! [`set_deadlock_handler`], or by panicking if there is none. Panicking
+//! unwinds the waiter's guards, so the other participants can continue.
+//!
+//! Acquiring and releasing a lock costs one store into the caller's own slot;
+//! the graph walk only runs in the contended path. Bookkeeping is
+//! best-effort: contexts beyond [`MAX_CONTEXTS`], and locks beyond
+//! [`MAX_HELD`] per context, are not tracked, and waiters that have blocked
+//! in a [`Parker`](crate::park::Parker) no longer check. Contexts are told
+//! apart through [`owner::current`]. With `track-location`, each edge of a
+//! report also says where the holder acquired its lock.
+//!
+//! [`RawSpinLock`]: crate::mutex::RawSpinLock
+
+use core::fmt;
+#[cfg(feature = "track-location")]
+use core::panic::Location;
+
+#[cfg(feature = "track-location")]
+use crate::atomic::AtomicPtr;
+use crate::atomic::AtomicUsize;
+use crate::atomic::Ordering;
+use crate::hook::HookCell;
+use crate::hook::SetHookError;
+use crate::owner;
+use crate::owner::OwnerId;
+
+/// Contexts that can be tracked at the same time.
+pub const MAX_CONTEXTS: usize = 64;
+/// Locks tracked per context.
+pub const MAX_HELD: usize = 8;
+/// Longest cycle that can be reported.
+pub const MAX_CYCLE: usize = 16;
+
+/// Spins between two walks of the wait-for graph.
+pub(crate) const CHECK_INTERVAL: usize = 1 << 12;
+
+struct Slot {
+    owner: AtomicUsize,
+    waiting_on: AtomicUsize,
+    held: [AtomicUsize; MAX_HELD],
+    /// Where each entry of `held` was acquired.
+    #[cfg(feature = "track-location")]
+    held_at: [AtomicPtr<Location<'static>>; MAX_HELD],
+}
+
+impl Slot {
+    const fn new() -> Self {
+        Self {
+            owner: AtomicUsize::new(0),
+            waiting_on: AtomicUsize::new(0),
+            held: [const { AtomicUsize::new(0) }; MAX_HELD],
+            #[cfg(feature = "track-location")]
+            held_at: [const { AtomicPtr::new(core::ptr::null_mut()) }; MAX_HELD],
+        }
+    }
+
+    fn position(&self, lock: usize) -> Option<usize> {
+        self.held
+            .iter()
+            .position(|held| held.load(Ordering::Relaxed) == lock)
+    }
+}
+
+static SLOTS: [Slot; MAX_CONTEXTS] = [const { Slot::new() }; MAX_CONTEXTS];
+
+#[cfg(feature = "std")]
+mod lease {
+    use core::cell::Cell;
+
+    use super::SLOTS;
+    use crate::atomic::Ordering;
+
+    /// Gives the slot back when the thread exits.
+    pub(super) struct Lease(pub(super) Cell<Option<usize>>);
+
+    impl Drop for Lease {
+        fn drop(&mut self) {
+            if let Some(index) = self.0.get() {
+                let slot = &SLOTS[index];
+                slot.waiting_on.store(0, Ordering::Relaxed);
+                for held in &slot.held {
+                    held.store(0, Ordering::Relaxed);
+                }
+                slot.owner.store(0, Ordering::Release);
+            }
+        }
+    }
+
+    std::thread_local!(pub(super) static LEASE: Lease = const { Lease(Cell::new(None)) });
+}
+
+fn find_slot(me: usize) -> Option<usize> {
+    if let Some(index) = SLOTS
+        .iter()
+        .position(|slot| slot.owner.load(Ordering::Relaxed) == me)
+    {
+        return Some(index);
+    }
+    SLOTS.iter().position(|slot| {
+        slot.owner
+            .compare_exchange(0, me, Ordering::Acquire, Ordering::Relaxed)
+            .is_ok()
+    })
+}
+
+fn my_slot() -> Option<&'static Slot> {
+    let me = owner::current()?.get();
+    #[cfg(feature = "std")]
+    let index = lease::LEASE
+        .try_with(|lease| {
+            if lease.0.get().is_none() {
+                lease.0.set(find_slot(me));
+            }
+            lease.0.get()
+        })
+        .ok()
+        .flatten();
+    #[cfg(not(feature = "std"))]
+    let index = find_slot(me);
+    index.map(|index| &SLOTS[index])
+}
+
+/// Records that the current context now holds `lock`, acquired at the
+/// caller's location.
+#[cfg_attr(feature = "track-location", track_caller)]
+pub(crate) fn acquired(lock: usize) {
+    let Some((slot, index)) = entry(0) else {
+        return;
+    };
+    #[cfg(feature = "track-location")]
+    {
+        let location: *const Location<'static> = Location::caller();
+        slot.held_at[index].store(location.cast_mut(), Ordering::Relaxed);
+    }
+    slot.held[index].store(lock, Ordering::Relaxed);
+}
+
+/// Records that the current context is about to release `lock`.
+pub(crate) fn released(lock: usize) {
+    if let Some((slot, index)) = entry(lock) {
+        slot.held[index].store(0, Ordering::Relaxed);
+    }
+}
+
+/// The current context's slot and the index of `lock` in its `held`.
+fn entry(lock: usize) -> Option<(&'static Slot, usize)> {
+    let slot = my_slot()?;
+    Some((slot, slot.position(lock)?))
+}
+
+/// Marks the current context as spinning on a lock until dropped, and runs
+/// the periodic checks.
+pub(crate) struct Waiting {
+    slot: Option<&'static Slot>,
+    lock: usize,
+    suspected: Option<DeadlockReport>,
+    #[cfg(feature = "usdt")]
+    site: crate::usdt::Site,
+}
+
+impl Waiting {
+    pub(crate) fn new(lock: usize, #[cfg(feature = "usdt")] site: crate::usdt::Site) -> Self {
+        let slot = my_slot();
+        if let Some(slot) = slot {
+            slot.waiting_on.store(lock, Ordering::Relaxed);
+        }
+        Self {
+            slot,
+            lock,
+            suspected: None,
+            #[cfg(feature = "usdt")]
+            site,
+        }
+    }
+
+    /// Walks the wait-for graph, reporting a cycle seen on two consecutive
+    /// checks. Transient cycles built from stale entries fade in between.
+    #[cold]
+    pub(crate) fn check(&mut self) {
+        let Some(slot) = self.slot else {
+            return;
+        };
+        let found = find_cycle(slot, self.lock);
+        let report = match found {
+            Some(report) if self.suspected == found => report,
+            _ => {
+                self.suspected = found;
+                return;
+            }
+        };
+        self.suspected = None;
+        // Not waiting while the handler runs, in case it panics.
+        slot.waiting_on.store(0, Ordering::Relaxed);
+        #[cfg(feature = "usdt")]
+        crate::usdt::deadlock(self.lock, self.site, report.edges().len());
+        match HANDLER.get() {
+            Some(handler) => handler(&report),
+            None => panic!("{report}"),
+        }
+        slot.waiting_on.store(self.lock, Ordering::Relaxed);
+    }
+}
+
+impl Drop for Waiting {
+    fn drop(&mut self) {
+        if let Some(slot) = self.slot {
+            slot.waiting_on.store(0, Ordering::Relaxed);
+        }
+    }
+}
+
+fn find_cycle(me: &Slot, lock: usize) -> Option<DeadlockReport> {
+    let mut report = DeadlockReport {
+        edges: [Edge::EMPTY; MAX_CYCLE],
+        len: 0,
+    };
+    let mut waiter = me;
+    let mut lock = lock;
+    loop {
+        #[cfg_attr(not(feature = "track-location"), allow(unused_variables))]
+        let (holder, index) = SLOTS.iter().find_map(|slot| {
+            if slot.owner.load(Ordering::Relaxed) == 0 {
+                return None;
+            }
+            Some((slot, slot.position(lock)?))
+        })?;
+        report.edges[report.len] = Edge {
+            waiter: id(waiter)?,
+            lock,
+            holder: id(holder)?,
+            // SAFETY: the pointer is null or comes from a `&'static Location`.
+            #[cfg(feature = "track-location")]
+            acquired_at: unsafe { holder.held_at[index].load(Ordering::Relaxed).as_ref() },
+        };
+        report.len += 1;
+        if core::ptr::eq(holder, me) {
+            return Some(report);
+        }
+        if report.len == MAX_CYCLE {
+            return None;
+        }
+        match holder.waiting_on.load(Ordering::Relaxed) {
+            0 => return None,
+            next => {
+                waiter = holder;
+                lock = next;
+            }
+        }
+    }
+}
+
+fn id(slot: &Slot) -> Option<OwnerId> {
+    core::num::NonZeroUsize::new(slot.owner.load(Ordering::Relaxed)).map(OwnerId::new)
+}
+
+/// One step of a deadlock cycle: `waiter` spins on `lock`, which `holder`
+/// holds.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Edge {
+    pub waiter: OwnerId,
+    /// Address of the lock.
+    pub lock: usize,
+    pub holder: OwnerId,
+    /// Where `holder` acquired the lock, if known.
+    #[cfg(feature = "track-location")]
+    pub acquired_at: Option<&'static Location<'static>>,
+}
+
+impl Edge {
+    const EMPTY: Edge = Edge {
+        waiter: OwnerId::new(core::num::NonZeroUsize::MIN),
+        lock: 0,
+        holder: OwnerId::new(core::num::NonZeroUsize::MIN),
+        #[cfg(feature = "track-location")]
+        acquired_at: None,
+    };
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for Edge {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        defmt::write!(
+            f,
+            "Edge {{ waiter: {}, lock: {:#x}, holder: {}",
+            self.waiter,
+            self.lock,
+            self.holder
+        );
+        #[cfg(feature = "track-location")]
+        if let Some(acquired_at) = self.acquired_at {
+            defmt::write!(
+                f,
+                ", acquired_at: {}:{}",
+                acquired_at.file(),
+                acquired_at.line()
+            );
+        }
+        defmt::write!(f, " }}")
+    }
+}
+
+/// A detected cycle, starting at the context that found it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DeadlockReport {
+    edges: [Edge; MAX_CYCLE],
+    len: usize,
+}
+
+impl DeadlockReport {
+    pub fn edges(&self) -> &[Edge] {
+        &self.edges[..self.len]
+    }
+}
+
+impl fmt::Display for DeadlockReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("deadlock detected:")?;
+        for edge in self.edges() {
+            write!(
+                f,
+                "\n  {:#x} waits for lock {:#x} held by {:#x}",
+                edge.waiter.get(),
+                edge.lock,
+                edge.holder.get()
+            )?;
+            #[cfg(feature = "track-location")]
+            if let Some(acquired_at) = edge.acquired_at {
+                write!(f, " since {acquired_at}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for DeadlockReport {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        defmt::write!(f, "deadlock detected: {}", self.edges())
+    }
+}
+
+static HANDLER: HookCell<fn(&DeadlockReport)> = HookCell::new();
+
+/// Registers a handler called instead of panicking when a deadlock is
+/// found. If it returns, the waiter keeps spinning.
+pub fn set_deadlock_handler(handler: fn(&DeadlockReport)) -> Result<(), SetHookError> {
+    HANDLER.set(handler)
+}
@@ -0,0 +1,82 @@
+//! A ping-pong pair of buffers for one producer and one consumer.
+//!
+//! A [`DoubleBuffer`] lets a producer, such as a sensor interrupt handler,
+//! fill the back buffer while a consumer reads the front one, and swap the
+//! two at frame boundaries. Each buffer has its own [`RawSpinLock`]; an
+//! atomic index says which one is in front.
+//!
+//! The reader locks the buffer the index names and then checks the index
+//! again, retrying if a publish moved it in between, so it only ever sees
+//! published frames. [`publish`](DoubleBuffer::publish) flips the index and
+//! then spins until the reader lets go of the old front, for as long as the
+//! reader is still on the previous frame. Writes mostly find the back buffer
+//! free, but one can still wait out a reader that locked it just before a
+//! publish and has yet to see the new index.
+//!
+//! The contract is one writer and one reader. More of either stays memory
+//! safe, since every access is under a lock, but frames from two writers
+//! interleave and two readers serialize.
+
+use crate::atomic::AtomicUsize;
+use crate::atomic::Ordering;
+use crate::mutex::RawSpinLock;
+
+/// Two buffers, one being read while the other is written.
+pub struct DoubleBuffer<T> {
+    buffers: [RawSpinLock<T>; 2],
+    /// The index of the front buffer.
+    front: AtomicUsize,
+}
+
+impl<T> DoubleBuffer<T> {
+    /// Creates a buffer pair with `front` readable and `back` to be written.
+    pub const fn new(front: T, back: T) -> Self {
+        Self {
+            buffers: [RawSpinLock::new(front), RawSpinLock::new(back)],
+            front: AtomicUsize::new(0),
+        }
+    }
+
+    /// Runs `f` on the back buffer.
+    pub fn write<R>(&self, f: impl FnOnce(&mut T) -> R) -> R {
+        // Only the writer moves the index.
+        let back = 1 - self.front.load(Ordering::Relaxed);
+        f(&mut self.buffers[back].lock())
+    }
+
+    /// Makes the back buffer the front one, waiting until the reader is done
+    /// with the old front, which becomes the next back buffer.
+    pub fn publish(&self) {
+        let front = self.front.load(Ordering::Relaxed);
+        self.front.store(1 - front, Ordering::Release);
+        // A reader that locks the old front from here on sees the new index
+        // and moves on; one that already holds it is waited for.
+        drop(self.buffers[front].lock());
+    }
+
+    /// Runs `f` on the front buffer, the frame published last.
+    pub fn read<R>(&self, f: impl FnOnce(&T) -> R) -> R {
+        loop {
+            let front = self.front.load(Ordering::Acquire);
+            let buffer = self.buffers[front].lock();
+            if self.front.load(Ordering::Acquire) == front {
+                return f(&buffer);
+            }
+        }
+    }
+
+    /// Returns the front and back buffers.
+    pub fn into_inner(self) -> (T, T) {
+        let [first, second] = self.buffers.map(RawSpinLock::into_inner);
+        match self.front.into_inner() {
+            0 => (first, second),
+            _ => (second, first),
+        }
+    }
+}
+
+impl<T: Default> Default for DoubleBuffer<T> {
+    fn default() -> Self {
+        Self::new(T::default(), T::default())
+    }
+}
@@ -0,0 +1,101 @@
+//! Hardware lock elision using Intel RTM.
+//!
+//! With the `hle` feature on a build with `target_feature = "rtm"`,
+//! [`RawSpinLock::lock`](crate::mutex::RawSpinLock::lock) first runs the
+//! critical section as a hardware transaction that only reads the lock word.
+//! Conflicting accesses abort the transaction and, after a few attempts, the
+//! lock is taken for real. The RTM intrinsics are unstable, hence the inline
+//! assembly.
+
+use core::arch::asm;
+
+use crate::atomic::AtomicU8;
+use crate::atomic::AtomicUsize;
+use crate::atomic::Ordering;
+
+const XBEGIN_STARTED: u32 = !0;
+const XABORT_EXPLICIT: u32 = 1 << 0;
+const XABORT_RETRY: u32 = 1 << 1;
+const XABORT_LOCK_BUSY: u8 = 0xff;
+
+/// Transactions attempted per `lock()` call before falling back to spinning.
+const MAX_ATTEMPTS: usize = 3;
+
+static ATTEMPTS: AtomicUsize = AtomicUsize::new(0);
+static ABORTS: AtomicUsize = AtomicUsize::new(0);
+
+/// Process-wide elision counters, to tell whether elision is paying off.
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(not(feature = "tiny"), derive(Debug))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ElisionStats {
+    /// Transactions started.
+    pub attempts: usize,
+    /// Transactions that aborted, including those aborted because the lock
+    /// was held for real.
+    pub aborts: usize,
+}
+
+pub fn stats() -> ElisionStats {
+    ElisionStats {
+        attempts: ATTEMPTS.load(Ordering::Relaxed),
+        aborts: ABORTS.load(Ordering::Relaxed),
+    }
+}
+
+/// Tries to enter the critical section transactionally.
+///
+/// Returns `true` inside a running transaction that has the lock word in its
+/// read set; the caller must later call [`end`].
+#[inline]
+pub(crate) fn try_elide(locked: &AtomicU8) -> bool {
+    for _ in 0..MAX_ATTEMPTS {
+        ATTEMPTS.fetch_add(1, Ordering::Relaxed);
+        let status = xbegin();
+        if status == XBEGIN_STARTED {
+            if locked.load(Ordering::Relaxed) == 0 {
+                return true;
+            }
+            xabort_lock_busy();
+        }
+        // Execution resumes here with the abort status after a rollback.
+        ABORTS.fetch_add(1, Ordering::Relaxed);
+        let busy = status & XABORT_EXPLICIT != 0 && status >> 24 == XABORT_LOCK_BUSY as u32;
+        if busy || status & XABORT_RETRY == 0 {
+            break;
+        }
+    }
+    false
+}
+
+/// Commits the transaction started by a successful [`try_elide`].
+#[inline]
+pub(crate) fn end() {
+    // SAFETY: only called by a guard whose `try_elide` returned `true`, so a
+    // transaction is active.
+    unsafe { asm!("xend", options(nostack)) };
+}
+
+#[inline(always)]
+fn xbegin() -> u32 {
+    let status: u32;
+    // SAFETY: on abort the processor restores the register state from this
+    // point and resumes at the label with the status in EAX, mirroring the
+    // `_xbegin` intrinsic.
+    unsafe {
+        asm!(
+            "xbegin 2f",
+            "2:",
+            inout("eax") XBEGIN_STARTED => status,
+            options(nostack),
+        );
+    }
+    status
+}
+
+#[inline(always)]
+fn xabort_lock_busy() {
+    // SAFETY: only executed inside a transaction, where it rolls back to the
+    // matching `xbegin`.
+    unsafe { asm!("xabort {}", const XABORT_LOCK_BUSY, options(nostack)) };
+}
@@ -0,0 +1,49 @@
+//! A raw mutex for `embassy_sync` backed by [`RawSpinLock`].
+//!
+//! `embassy_sync`'s blocking `Mutex` and the channel types built on it are
+//! generic over its [`RawMutex`] trait. [`SpinRawMutex`] implements it
+//! with a `RawSpinLock<()>`, so they synchronise across cores without the
+//! interrupt masking of `CriticalSectionRawMutex`, and follow the
+//! raw-atomics gate and preemption hook like any other lock here.
+//!
+//! `RawMutex` allows an implementation to be reentrant on one core, but
+//! this one is not: locking it again inside its own closure spins forever,
+//! or panics with the `owner-tracking` feature. The interrupt caveat of
+//! [`irq`](crate::irq) applies as well, since an interrupt handler that
+//! takes a mutex its core already holds never gets it.
+
+use embassy_sync::blocking_mutex::raw::RawMutex;
+
+use crate::mutex::RawSpinLock;
+
+pub struct SpinRawMutex {
+    lock: RawSpinLock<()>,
+}
+
+impl SpinRawMutex {
+    pub const fn new() -> Self {
+        Self {
+            lock: RawSpinLock::new(()),
+        }
+    }
+}
+
+impl Default for SpinRawMutex {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// SAFETY: `f` runs with the spinlock held, which excludes every other
+// core and thread once raw atomics are enabled, and before that only one
+// core runs at all.
+unsafe impl RawMutex for SpinRawMutex {
+    #[allow(clippy::declare_interior_mutable_const)]
+    const INIT: Self = Self::new();
+
+    #[track_caller]
+    fn lock<R>(&self, f: impl FnOnce() -> R) -> R {
+        let _guard = self.lock.lock();
+        f()
+    }
+}
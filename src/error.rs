@@ -0,0 +1,98 @@
+//! Errors shared by the fallible lock APIs.
+//!
+//! [`TryLockError`] says why an acquisition did not happen. The locks'
+//! `try_*_result` methods return it, and the plain `try_*` methods are
+//! wrappers returning `Option` for callers that only care whether they got
+//! the guard. The `*_checked` acquisitions refuse to hand out a guard that
+//! excludes nobody and return [`GateDisabled`] instead. With the `std`
+//! feature, [`compat`](crate::compat) converts between [`TryLockError`] and
+//! `std::sync::TryLockError`.
+
+#[cfg(not(feature = "tiny"))]
+use core::fmt;
+use core::panic::Location;
+
+/// Why a lock was not acquired.
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(not(feature = "tiny"), derive(Debug))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum TryLockError {
+    /// The lock is held, and acquiring it would have meant waiting.
+    WouldBlock,
+    /// A holder panicked and left the data possibly half-updated.
+    Poisoned,
+    /// The lock stayed held for the whole spin budget.
+    TimedOut {
+        /// How many times the caller spun before giving up.
+        spins: usize,
+    },
+    /// Raw atomics are not enabled yet, and the caller asked for a guard
+    /// that excludes other cores.
+    GateDisabled,
+}
+
+#[cfg(not(feature = "tiny"))]
+impl fmt::Display for TryLockError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::WouldBlock => f.write_str("lock is held"),
+            Self::Poisoned => f.write_str("lock is poisoned"),
+            Self::TimedOut { spins } => write!(f, "lock still held after {spins} spins"),
+            Self::GateDisabled => f.write_str("raw atomics are not enabled"),
+        }
+    }
+}
+
+#[cfg(not(feature = "tiny"))]
+impl core::error::Error for TryLockError {}
+
+impl From<GateDisabled> for TryLockError {
+    fn from(_: GateDisabled) -> Self {
+        Self::GateDisabled
+    }
+}
+
+/// Error returned by the `*_checked` acquisitions before raw atomics are
+/// enabled, when the guard would not exclude anybody.
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(not(feature = "tiny"), derive(Debug))]
+pub struct GateDisabled {
+    location: &'static Location<'static>,
+}
+
+impl GateDisabled {
+    #[track_caller]
+    pub(crate) fn new() -> Self {
+        Self {
+            location: Location::caller(),
+        }
+    }
+
+    /// Where the lock was to be acquired.
+    pub fn location(&self) -> &'static Location<'static> {
+        self.location
+    }
+}
+
+#[cfg(not(feature = "tiny"))]
+impl fmt::Display for GateDisabled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "raw atomics are not enabled at {}", self.location)
+    }
+}
+
+#[cfg(not(feature = "tiny"))]
+impl core::error::Error for GateDisabled {}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for GateDisabled {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        defmt::write!(
+            f,
+            "raw atomics are not enabled at {}:{}",
+            self.location.file(),
+            self.location.line()
+        );
+    }
+}
@@ -0,0 +1,198 @@
+//! A rendezvous where two callers swap values.
+//!
+//! An [`Exchanger`] suits two threads that trade buffers at a
+//! synchronisation point: each arrives with a value and leaves with the
+//! other's. It is a state byte next to a single slot. The first caller
+//! claims the empty slot, parks its value there and spins; the second
+//! finds it waiting, takes the parked value, leaves its own in its place
+//! and lets the first one go, which takes it out and empties the slot.
+//!
+//! More than two callers are paired two at a time, in no particular order:
+//! while a pair is using the slot, others spin until it is empty again, so
+//! every value goes to exactly one other caller and an odd one out keeps
+//! waiting for the next arrival. Before raw atomics are enabled the state
+//! moves with plain stores, so the partner can only be an interrupt handler
+//! on the same core, which has to use
+//! [`try_exchange`](Exchanger::try_exchange) as it cannot wait for the
+//! caller it interrupted.
+
+use core::cell::UnsafeCell;
+#[cfg(not(feature = "tiny"))]
+use core::fmt;
+use core::mem::MaybeUninit;
+
+use crate::atomic::AtomicU8;
+use crate::atomic::Ordering;
+use crate::raw::raw_atomics_enabled;
+use crate::relax;
+use crate::relax::Backoff;
+
+const EMPTY: u8 = 0;
+/// Claimed by the first caller, which is writing its value, or taking it
+/// back after giving up.
+const WRITING: u8 = 1;
+/// The first caller's value is parked, waiting for a partner.
+const WAITING: u8 = 2;
+/// Claimed by the partner, which is swapping the values.
+const SWAPPING: u8 = 3;
+/// The partner's value is in the slot for the first caller to take.
+const DONE: u8 = 4;
+
+/// A slot where pairs of callers swap values.
+///
+/// The slot only holds a value while an exchange is under way, so nothing
+/// is left in it to drop once every call has returned.
+pub struct Exchanger<T> {
+    state: AtomicU8,
+    slot: UnsafeCell<MaybeUninit<T>>,
+}
+
+// SAFETY: values move between the two callers of an exchange, and the
+// state machine gives each of them exclusive access to the slot in turn.
+unsafe impl<T: Send> Send for Exchanger<T> {}
+unsafe impl<T: Send> Sync for Exchanger<T> {}
+
+impl<T> Exchanger<T> {
+    pub const fn new() -> Self {
+        Self {
+            state: AtomicU8::new(EMPTY),
+            slot: UnsafeCell::new(MaybeUninit::uninit()),
+        }
+    }
+
+    /// Waits for a partner and returns its value in return for `value`.
+    pub fn exchange(&self, value: T) -> T {
+        match self.exchange_inner(value, None) {
+            Ok(value) => value,
+            Err(_) => unreachable!("exchange without a budget gave up"),
+        }
+    }
+
+    /// Swaps `value` with a caller already waiting, or gives it back if
+    /// nobody is, without spinning.
+    pub fn try_exchange(&self, value: T) -> Result<T, T> {
+        if self.transition(WAITING, SWAPPING) {
+            // SAFETY: `SWAPPING` gives this call exclusive access to the
+            // parked value.
+            Ok(unsafe { self.swap(value) })
+        } else {
+            Err(value)
+        }
+    }
+
+    /// Like [`exchange`](Self::exchange), but gives `value` back once it
+    /// has spun `spins` times without a partner.
+    ///
+    /// A partner that claimed the parked value just as the budget ran out
+    /// still completes the exchange, so nothing is lost either way.
+    pub fn exchange_for(&self, value: T, spins: usize) -> Result<T, T> {
+        self.exchange_inner(value, Some(spins))
+    }
+
+    fn exchange_inner(&self, value: T, budget: Option<usize>) -> Result<T, T> {
+        let mut backoff = Backoff::new();
+        let mut spins = 0;
+        let out_of_budget = |spins| budget.is_some_and(|budget| spins >= budget);
+        loop {
+            match self.state.load(Ordering::Relaxed) {
+                WAITING if self.transition(WAITING, SWAPPING) => {
+                    // SAFETY: as in `try_exchange`.
+                    return Ok(unsafe { self.swap(value) });
+                }
+                EMPTY if self.transition(EMPTY, WRITING) => break,
+                _ => {}
+            }
+            if out_of_budget(spins) {
+                return Err(value);
+            }
+            spins += 1;
+            backoff.wait_on(&self.state);
+        }
+        // SAFETY: `WRITING` gives this call exclusive access to the slot.
+        unsafe { (*self.slot.get()).write(value) };
+        self.state.store(WAITING, Ordering::Release);
+        relax::notify();
+        backoff.reset();
+        loop {
+            if self.state.load(Ordering::Acquire) == DONE {
+                // SAFETY: the partner wrote its value before publishing
+                // `DONE`, and only this call waits for it.
+                return Ok(unsafe { self.take() });
+            }
+            // Withdrawing fails once a partner has claimed the value, and
+            // then `DONE` follows shortly.
+            if out_of_budget(spins) && self.transition(WAITING, WRITING) {
+                // SAFETY: `WRITING` gives this call its own value back.
+                return Err(unsafe { self.take() });
+            }
+            spins += 1;
+            backoff.wait_on(&self.state);
+        }
+    }
+
+    /// Moves the state from `from` to `to`, with a compare-exchange once
+    /// raw atomics are enabled and plain stores before.
+    fn transition(&self, from: u8, to: u8) -> bool {
+        if raw_atomics_enabled() {
+            self.state
+                .compare_exchange(from, to, Ordering::Acquire, Ordering::Relaxed)
+                .is_ok()
+        } else {
+            if self.state.load(Ordering::Acquire) != from {
+                return false;
+            }
+            self.state.store(to, Ordering::Relaxed);
+            true
+        }
+    }
+
+    /// # Safety
+    ///
+    /// The caller must have moved the state from `WAITING` to `SWAPPING`.
+    unsafe fn swap(&self, value: T) -> T {
+        let slot = self.slot.get();
+        // SAFETY: the parked value was published with `WAITING`, and the
+        // caller has exclusive access until it publishes `DONE`.
+        let parked = unsafe { (*slot).assume_init_read() };
+        unsafe { (*slot).write(value) };
+        self.state.store(DONE, Ordering::Release);
+        relax::notify();
+        parked
+    }
+
+    /// Takes the value out of the slot and empties it.
+    ///
+    /// # Safety
+    ///
+    /// The slot must hold a value that only the caller may take.
+    unsafe fn take(&self) -> T {
+        // SAFETY: guaranteed by the caller.
+        let value = unsafe { (*self.slot.get()).assume_init_read() };
+        self.state.store(EMPTY, Ordering::Release);
+        relax::notify();
+        value
+    }
+
+    /// Whether a caller is parked waiting for a partner.
+    pub fn is_waiting(&self) -> bool {
+        self.state.load(Ordering::Relaxed) == WAITING
+    }
+}
+
+impl<T> Default for Exchanger<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(not(feature = "tiny"))]
+impl<T> fmt::Debug for Exchanger<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = match self.state.load(Ordering::Relaxed) {
+            EMPTY => "empty",
+            WAITING => "waiting",
+            _ => "exchanging",
+        };
+        f.debug_struct("Exchanger").field("state", &state).finish()
+    }
+}
@@ -0,0 +1,61 @@
+//! Forced `try_*` failures, for testing the code that handles them.
+//!
+//! In unit tests a lock is nearly always free, so the paths that handle a
+//! failed [`try_lock`] never run. With the `fault-injection` feature,
+//! [`fail_next_try_locks`] makes the next calls to [`RawSpinLock::try_lock`],
+//! [`RwSpinLock::try_read`] and [`RwSpinLock::try_write`] on any lock return
+//! `None` without touching the lock, so such paths can be driven
+//! deterministically. Locks of other types are not affected, except where
+//! they are built on a [`RawSpinLock`].
+//!
+//! The count is global: tests that inject failures should not run alongside
+//! tests that expect `try_*` to succeed.
+//!
+//! [`try_lock`]: crate::mutex::RawSpinLock::try_lock
+//! [`RawSpinLock`]: crate::mutex::RawSpinLock
+//! [`RawSpinLock::try_lock`]: crate::mutex::RawSpinLock::try_lock
+//! [`RwSpinLock::try_read`]: crate::rwlock::RwSpinLock::try_read
+//! [`RwSpinLock::try_write`]: crate::rwlock::RwSpinLock::try_write
+
+use crate::atomic::AtomicUsize;
+use crate::atomic::Ordering;
+use crate::raw::raw_atomics_enabled;
+
+static FAIL_NEXT: AtomicUsize = AtomicUsize::new(0);
+
+/// Makes the next `count` `try_*` calls fail, replacing any count still
+/// pending. `0` stops injecting failures.
+pub fn fail_next_try_locks(count: usize) {
+    FAIL_NEXT.store(count, Ordering::Relaxed);
+}
+
+/// Returns how many failures are still to be injected.
+pub fn pending_failures() -> usize {
+    FAIL_NEXT.load(Ordering::Relaxed)
+}
+
+/// Consumes one pending failure. Returns whether the caller must fail.
+#[inline(always)]
+pub(crate) fn inject() -> bool {
+    let mut count = FAIL_NEXT.load(Ordering::Relaxed);
+    if !raw_atomics_enabled() {
+        // Single-core bring-up: nothing else can touch the count.
+        if count == 0 {
+            return false;
+        }
+        FAIL_NEXT.store(count - 1, Ordering::Relaxed);
+        return true;
+    }
+    while count != 0 {
+        match FAIL_NEXT.compare_exchange_weak(
+            count,
+            count - 1,
+            Ordering::Relaxed,
+            Ordering::Relaxed,
+        ) {
+            Ok(_) => return true,
+            Err(current) => count = current,
+        }
+    }
+    false
+}
@@ -0,0 +1,149 @@
+//! C entry points for locks shared with C code.
+//!
+//! C declares the lock header as
+//!
+//! > `struct rawspinlock { uint8_t locked; };`
+//!
+//! which has the layout of [`RawSpinLockFfi`], and puts it first in the
+//! structure it protects. Since [`RawSpinLock`] is `#[repr(C)]` with the
+//! lock word first, a C `struct { struct rawspinlock lock; T data; }` is
+//! laid out as `RawSpinLock<T>` for a `#[repr(C)]` `T`, so Rust reaches
+//! the same structure through
+//! [`from_raw`](RawSpinLock::from_raw) and ordinary guards, while C passes
+//! `&shared->lock` to [`rawspinlock_lock`] and [`rawspinlock_unlock`].
+//!
+//! The entry points honour the raw-atomics gate: before
+//! [`enable_raw_atomics`] they only mark the lock word with plain stores,
+//! as Rust guards do. The debugging features add fields to the header, so
+//! the C declaration above only matches builds without them; the size is
+//! pinned below for those builds.
+//!
+//! # Boot handoff
+//!
+//! Assembly that releases a lock Rust acquired, such as a stub Rust jumps
+//! to while holding a lock, needs only the lock word: a byte at
+//! [`LOCK_WORD_OFFSET`] in every `RawSpinLock<T>`, whatever `T` and the
+//! features. The contract is
+//!
+//! - Rust acquires with [`lock`](RawSpinLock::lock) after raw atomics are
+//!   enabled, and gives up the guard with `mem::forget` before the jump,
+//!   which leaves the preemption hook's `disable` in effect;
+//! - assembly stores [`LOCK_WORD_UNLOCKED`] to the word with release
+//!   semantics (`stlrb` on AArch64, a plain `mov` on x86_64), and on
+//!   AArch64 and ARMv7 follows it with `dsb ishst; sev` to wake waiters;
+//! - no sleeping-wait hook is registered with [`park`](crate::park), since
+//!   waking parked waiters takes a call back into Rust.
+//!
+//! [`raw_release`] is that sequence written in Rust, and
+//! [`lock_from_word`] turns the address assembly holds back into a lock.
+//!
+//! [`enable_raw_atomics`]: crate::raw::enable_raw_atomics
+
+use crate::mutex::RawSpinLock;
+
+/// The lock header C code embeds, without any data.
+pub type RawSpinLockFfi = RawSpinLock<()>;
+
+/// `sizeof(struct rawspinlock)`.
+pub const RAWSPINLOCK_SIZE: usize = 1;
+/// `_Alignof(struct rawspinlock)`.
+pub const RAWSPINLOCK_ALIGN: usize = 1;
+
+#[cfg(not(any(
+    loom,
+    shuttle,
+    feature = "audit",
+    feature = "checked-guards",
+    feature = "lock-ordering",
+    feature = "lockdep",
+    feature = "no-lock-check",
+    feature = "owner-tracking",
+    feature = "registry",
+    feature = "stats",
+    feature = "track-location",
+    feature = "validate-placement",
+    feature = "waiter-count",
+    feature = "watchdog"
+)))]
+const _: () = assert!(
+    size_of::<RawSpinLockFfi>() == RAWSPINLOCK_SIZE
+        && align_of::<RawSpinLockFfi>() == RAWSPINLOCK_ALIGN
+);
+
+/// Offset of the lock word from the start of any `RawSpinLock<T>`.
+pub const LOCK_WORD_OFFSET: usize = 0;
+/// The lock word of a free lock.
+pub const LOCK_WORD_UNLOCKED: u8 = 0;
+
+/// The lock whose lock word is at `word`.
+///
+/// # Safety
+///
+/// `word` must point to the lock word of a `RawSpinLock<T>`, under the
+/// same requirements as [`RawSpinLock::from_raw`].
+pub unsafe fn lock_from_word<'a, T>(word: *const u8) -> &'a RawSpinLock<T> {
+    // SAFETY: guaranteed by the caller.
+    unsafe { RawSpinLock::from_raw(word.wrapping_sub(LOCK_WORD_OFFSET).cast()) }
+}
+
+/// Releases the lock whose lock word is at `word` exactly as the boot
+/// handoff contract asks of assembly.
+///
+/// # Safety
+///
+/// `word` must be valid as for [`lock_from_word`], and its lock must be
+/// held by an acquisition whose guard was forgotten, under the contract in
+/// the module docs.
+pub unsafe fn raw_release(word: *mut u8) {
+    // SAFETY: guaranteed by the caller.
+    unsafe { lock_from_word::<()>(word) }.release_word();
+}
+
+/// Initializes an unlocked header at `ptr`.
+///
+/// # Safety
+///
+/// As for [`RawSpinLock::init_in_place`].
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn rawspinlock_init(ptr: *mut RawSpinLockFfi) {
+    // SAFETY: guaranteed by the caller.
+    unsafe { RawSpinLock::init_in_place(ptr, ()) };
+}
+
+/// Acquires the lock at `ptr`, spinning until it is free.
+///
+/// # Safety
+///
+/// `ptr` must point to an initialized header that stays valid until the
+/// matching [`rawspinlock_unlock`].
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn rawspinlock_lock(ptr: *mut RawSpinLockFfi) {
+    // SAFETY: guaranteed by the caller.
+    unsafe { RawSpinLock::from_raw(ptr) }.lock_unguarded();
+}
+
+/// Tries to acquire the lock at `ptr` without spinning, returning whether
+/// it did.
+///
+/// # Safety
+///
+/// As for [`rawspinlock_lock`].
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn rawspinlock_try_lock(ptr: *mut RawSpinLockFfi) -> bool {
+    // SAFETY: guaranteed by the caller.
+    unsafe { RawSpinLock::from_raw(ptr) }.try_lock_unguarded()
+}
+
+/// Releases the lock at `ptr`.
+///
+/// # Safety
+///
+/// The caller must hold the lock through [`rawspinlock_lock`] or
+/// [`rawspinlock_try_lock`], and raw atomics must not have been enabled
+/// in between, since the release cannot tell whether the acquisition
+/// disabled preemption.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn rawspinlock_unlock(ptr: *mut RawSpinLockFfi) {
+    // SAFETY: `ptr` is valid, and the rest is guaranteed by the caller.
+    unsafe { RawSpinLock::from_raw(ptr).unlock_unguarded() };
+}
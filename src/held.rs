@@ -0,0 +1,106 @@
+//! The locks each thread holds, for reporting when it panics.
+//!
+//! With the `panic-hook` feature, every [`RawSpinLock`] acquired once raw
+//! atomics are enabled is recorded in a thread-local list of [`MAX_HELD`]
+//! entries until its guard is dropped; deeper nesting is not tracked.
+//! [`dump`] writes the current thread's list, and [`install_panic_hook`]
+//! writes it to standard error whenever a thread panics. Locks are named
+//! after their registry name or lockdep class where those features are
+//! enabled, and hold times are given once a [`Clock`] is registered.
+//!
+//! Nothing here takes a lock: the list is only touched by its own thread.
+//!
+//! [`RawSpinLock`]: crate::mutex::RawSpinLock
+//! [`Clock`]: crate::clock::Clock
+
+use core::cell::Cell;
+use core::fmt;
+use core::panic::Location;
+use std::io::Write;
+
+/// Locks tracked per thread.
+pub const MAX_HELD: usize = 16;
+
+#[derive(Clone, Copy)]
+struct Entry {
+    lock: usize,
+    name: Option<&'static str>,
+    location: &'static Location<'static>,
+    /// Clock reading when the lock was acquired.
+    since: Option<u64>,
+}
+
+std::thread_local!(static HELD: [Cell<Option<Entry>>; MAX_HELD] = const {
+    [const { Cell::new(None) }; MAX_HELD]
+});
+
+/// Records that the current thread holds `lock`. Returns whether it was
+/// recorded, and so must be removed again with [`release`].
+#[track_caller]
+pub(crate) fn acquire(lock: usize, name: Option<&'static str>) -> bool {
+    let entry = Entry {
+        lock,
+        name,
+        location: Location::caller(),
+        since: crate::clock::now(),
+    };
+    HELD.try_with(|held| {
+        let free = held.iter().find(|slot| slot.get().is_none());
+        free.map(|free| free.set(Some(entry))).is_some()
+    })
+    .unwrap_or(false)
+}
+
+/// Forgets the entry [`acquire`] recorded for `lock`.
+pub(crate) fn release(lock: usize) {
+    let _ = HELD.try_with(|held| {
+        let entry = held
+            .iter()
+            .find(|slot| slot.get().is_some_and(|entry| entry.lock == lock));
+        if let Some(entry) = entry {
+            entry.set(None);
+        }
+    });
+}
+
+/// Writes one line for every lock the current thread holds.
+pub fn dump(writer: &mut dyn fmt::Write) -> fmt::Result {
+    let now = crate::clock::now();
+    HELD.try_with(|held| {
+        for entry in held.iter().filter_map(Cell::get) {
+            write!(
+                writer,
+                "{} ({:#x}) acquired at {}",
+                entry.name.unwrap_or("<unnamed>"),
+                entry.lock,
+                entry.location
+            )?;
+            if let (Some(since), Some(now)) = (entry.since, now) {
+                write!(writer, ", held for {} ticks", now.saturating_sub(since))?;
+            }
+            writer.write_char('\n')?;
+        }
+        Ok(())
+    })
+    .unwrap_or(Ok(()))
+}
+
+/// Installs a panic hook that writes the panicking thread's held locks to
+/// standard error, then runs the hook that was installed before.
+pub fn install_panic_hook() {
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(std::boxed::Box::new(move |info| {
+        let mut held = std::string::String::new();
+        if dump(&mut held).is_ok() && !held.is_empty() {
+            let thread = std::thread::current();
+            let mut stderr = std::io::stderr().lock();
+            let _ = writeln!(
+                stderr,
+                "thread '{}' panicked holding:",
+                thread.name().unwrap_or("<unnamed>")
+            );
+            let _ = stderr.write_all(held.as_bytes());
+        }
+        previous(info);
+    }));
+}
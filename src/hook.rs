@@ -0,0 +1,79 @@
+//! Once-settable global hooks registered during bring-up.
+
+use core::cell::UnsafeCell;
+#[cfg(not(feature = "tiny"))]
+use core::fmt;
+use core::mem::MaybeUninit;
+
+use crate::atomic::Ordering;
+use crate::atomic::global::AtomicU8;
+use crate::raw::raw_atomics_enabled;
+
+/// Error returned when registering a hook that is already registered.
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(not(feature = "tiny"), derive(Debug))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SetHookError;
+
+#[cfg(not(feature = "tiny"))]
+impl fmt::Display for SetHookError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("hook is already registered")
+    }
+}
+
+#[cfg(not(feature = "tiny"))]
+impl core::error::Error for SetHookError {}
+
+const UNSET: u8 = 0;
+const SETTING: u8 = 1;
+const SET: u8 = 2;
+
+/// Storage for a hook that can be registered once and read from any core.
+///
+/// Before raw atomics are enabled registration uses plain loads and stores,
+/// relying on the single-core bring-up invariant like the rest of the crate.
+pub(crate) struct HookCell<T> {
+    state: AtomicU8,
+    value: UnsafeCell<MaybeUninit<T>>,
+}
+
+// SAFETY: the value is written once before `state` is published as `SET` and
+// only read afterwards.
+unsafe impl<T: Copy + Send + Sync> Sync for HookCell<T> {}
+
+impl<T: Copy> HookCell<T> {
+    pub(crate) const fn new() -> Self {
+        Self {
+            state: AtomicU8::new(UNSET),
+            value: UnsafeCell::new(MaybeUninit::uninit()),
+        }
+    }
+
+    pub(crate) fn set(&self, value: T) -> Result<(), SetHookError> {
+        if raw_atomics_enabled() {
+            self.state
+                .compare_exchange(UNSET, SETTING, Ordering::Acquire, Ordering::Relaxed)
+                .map_err(|_| SetHookError)?;
+        } else {
+            if self.state.load(Ordering::Relaxed) != UNSET {
+                return Err(SetHookError);
+            }
+            self.state.store(SETTING, Ordering::Relaxed);
+        }
+        // SAFETY: the `SETTING` state gives this call exclusive access to the slot.
+        unsafe { (*self.value.get()).write(value) };
+        self.state.store(SET, Ordering::Release);
+        Ok(())
+    }
+
+    #[inline(always)]
+    pub(crate) fn get(&self) -> Option<T> {
+        if self.state.load(Ordering::Acquire) == SET {
+            // SAFETY: `SET` is only published after the value was written.
+            Some(unsafe { (*self.value.get()).assume_init() })
+        } else {
+            None
+        }
+    }
+}
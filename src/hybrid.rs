@@ -0,0 +1,220 @@
+//! A mutex for code shared between the kernel and its hosted test harness.
+//!
+//! Without `std`, [`HybridMutex`] is the gate-aware [`RawSpinLock`]. With
+//! `std` it is permissive like every other lock until
+//! [`enable_raw_atomics`](crate::raw::enable_raw_atomics) has run, so early
+//! single-threaded init still works, and afterwards it blocks on a
+//! `std::sync::Mutex`. Either way callers see the same [`HybridMutexGuard`].
+//!
+//! As with `RawSpinLock`, the outermost guard taken before the enable marks
+//! the lock, and a locker after the enable waits for that guard to drop
+//! even though it never held the std mutex.
+//!
+//! [`RawSpinLock`]: crate::mutex::RawSpinLock
+
+use core::ops::Deref;
+use core::ops::DerefMut;
+
+#[cfg(feature = "std")]
+use crate::atomic::Ordering;
+use crate::mutex::Lock;
+#[cfg(not(feature = "std"))]
+use crate::mutex::RawSpinLock;
+#[cfg(not(feature = "std"))]
+use crate::mutex::RawSpinLockGuard;
+
+#[cfg(not(feature = "std"))]
+pub struct HybridMutex<T> {
+    inner: RawSpinLock<T>,
+}
+
+#[cfg(feature = "std")]
+pub struct HybridMutex<T> {
+    // The std mutex only provides exclusion; the data lives beside it so the
+    // permissive path can reach it without going through the mutex.
+    mutex: std::sync::Mutex<()>,
+    // Set while the outermost guard taken before raw atomics were enabled is
+    // alive; nested permissive guards leave it to that one.
+    marked: crate::atomic::AtomicBool,
+    data: core::cell::UnsafeCell<T>,
+}
+
+#[cfg(feature = "std")]
+unsafe impl<T: Send> Send for HybridMutex<T> {}
+#[cfg(feature = "std")]
+unsafe impl<T: Send> Sync for HybridMutex<T> {}
+// The data sits outside the std mutex, so this has to opt back in. As with
+// `RawSpinLock` there is no poisoning: `lock` ignores the std mutex's.
+#[cfg(feature = "std")]
+impl<T> core::panic::UnwindSafe for HybridMutex<T> {}
+#[cfg(feature = "std")]
+impl<T> core::panic::RefUnwindSafe for HybridMutex<T> {}
+
+const _: () = {
+    crate::mutex::assert_unwind_safe::<HybridMutex<core::cell::Cell<u8>>>();
+    crate::mutex::assert_unwind_safe::<HybridMutexGuard<'_, &mut u8>>();
+};
+
+pub struct HybridMutexGuard<'a, T> {
+    #[cfg(not(feature = "std"))]
+    inner: RawSpinLockGuard<'a, T>,
+    #[cfg(feature = "std")]
+    lock: &'a HybridMutex<T>,
+    // `None` for the permissive guards handed out before atomics are enabled.
+    #[cfg(feature = "std")]
+    _held: Option<std::sync::MutexGuard<'a, ()>>,
+    #[cfg(feature = "std")]
+    marked: bool,
+}
+
+#[cfg(not(feature = "std"))]
+impl<T> HybridMutex<T> {
+    pub const fn new(data: T) -> Self {
+        Self {
+            inner: RawSpinLock::new(data),
+        }
+    }
+
+    pub fn lock(&self) -> HybridMutexGuard<'_, T> {
+        HybridMutexGuard {
+            inner: self.inner.lock(),
+        }
+    }
+
+    pub fn try_lock(&self) -> Option<HybridMutexGuard<'_, T>> {
+        self.inner
+            .try_lock()
+            .map(|inner| HybridMutexGuard { inner })
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner.into_inner()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T> HybridMutex<T> {
+    pub const fn new(data: T) -> Self {
+        Self {
+            mutex: std::sync::Mutex::new(()),
+            marked: crate::atomic::AtomicBool::new(false),
+            data: core::cell::UnsafeCell::new(data),
+        }
+    }
+
+    pub fn lock(&self) -> HybridMutexGuard<'_, T> {
+        if !crate::raw::raw_atomics_enabled() {
+            return self.permissive();
+        }
+        let held = self
+            .mutex
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        // Holding the std mutex, so later lockers queue behind this one.
+        let mut backoff = crate::relax::Backoff::new();
+        while self.marked.load(Ordering::Acquire) {
+            backoff.wait();
+        }
+        HybridMutexGuard {
+            lock: self,
+            _held: Some(held),
+            marked: false,
+        }
+    }
+
+    pub fn try_lock(&self) -> Option<HybridMutexGuard<'_, T>> {
+        if !crate::raw::raw_atomics_enabled() {
+            return Some(self.permissive());
+        }
+        let held = match self.mutex.try_lock() {
+            Ok(held) => held,
+            Err(std::sync::TryLockError::Poisoned(poisoned)) => poisoned.into_inner(),
+            Err(std::sync::TryLockError::WouldBlock) => return None,
+        };
+        if self.marked.load(Ordering::Acquire) {
+            return None;
+        }
+        Some(HybridMutexGuard {
+            lock: self,
+            _held: Some(held),
+            marked: false,
+        })
+    }
+
+    /// A guard that excludes nobody, marking the lock if no outer guard
+    /// has. Plain loads and stores, as permissive mode runs on one core.
+    fn permissive(&self) -> HybridMutexGuard<'_, T> {
+        let marked = !self.marked.load(Ordering::Relaxed);
+        if marked {
+            self.marked.store(true, Ordering::Relaxed);
+        }
+        HybridMutexGuard {
+            lock: self,
+            _held: None,
+            marked,
+        }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T> Drop for HybridMutexGuard<'_, T> {
+    fn drop(&mut self) {
+        // A release, should raw atomics have been enabled since the mark.
+        if self.marked {
+            self.lock.marked.store(false, Ordering::Release);
+            crate::relax::notify();
+        }
+    }
+}
+
+impl<T> Deref for HybridMutexGuard<'_, T> {
+    type Target = T;
+
+    #[cfg(not(feature = "std"))]
+    fn deref(&self) -> &Self::Target {
+        &self.inner
+    }
+
+    #[cfg(feature = "std")]
+    fn deref(&self) -> &Self::Target {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<T> DerefMut for HybridMutexGuard<'_, T> {
+    #[cfg(not(feature = "std"))]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.inner
+    }
+
+    #[cfg(feature = "std")]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl<T: defmt::Format> defmt::Format for HybridMutexGuard<'_, T> {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        defmt::Format::format(&**self, f)
+    }
+}
+
+impl<T> Lock<T> for HybridMutex<T> {
+    type Guard<'a>
+        = HybridMutexGuard<'a, T>
+    where
+        T: 'a;
+
+    fn lock(&self) -> Self::Guard<'_> {
+        HybridMutex::lock(self)
+    }
+
+    fn try_lock(&self) -> Option<Self::Guard<'_>> {
+        HybridMutex::try_lock(self)
+    }
+}
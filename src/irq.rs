@@ -0,0 +1,154 @@
+//! Interrupt-safe locking.
+//!
+//! Taking a lock in thread context and then again from an interrupt handler
+//! on the same core deadlocks. [`RawSpinLock::lock_irqsave`] avoids that by
+//! disabling local interrupts through the registered [`InterruptControl`]
+//! hook before acquiring, and restoring them after releasing.
+//!
+//! That keeps interrupts masked for as long as the lock stays contended.
+//! [`RawSpinLock::lock_irqsave_spin_enabled`] only masks them around each
+//! acquisition attempt, like Linux's lock-break variant, and waits with the
+//! saved state restored in between, so pending interrupts are serviced.
+
+use core::mem::ManuallyDrop;
+use core::ops::Deref;
+use core::ops::DerefMut;
+
+use crate::hook::HookCell;
+use crate::hook::SetHookError;
+use crate::mutex::RawSpinLock;
+use crate::mutex::RawSpinLockGuard;
+use crate::raw::raw_atomics_enabled;
+
+/// Platform hook that masks and unmasks local interrupts.
+pub trait InterruptControl {
+    /// Disables interrupts on the current core and returns the previous state.
+    fn save_and_disable() -> usize;
+
+    /// Restores a state returned by [`save_and_disable`](Self::save_and_disable).
+    fn restore(flags: usize);
+}
+
+#[derive(Clone, Copy)]
+struct Hooks {
+    save_and_disable: fn() -> usize,
+    restore: fn(usize),
+}
+
+static INTERRUPT_CONTROL: HookCell<Hooks> = HookCell::new();
+
+/// Registers the interrupt-control hook. Call once during bring-up.
+///
+/// Until a hook is registered the irqsave APIs behave like their plain
+/// counterparts.
+pub fn set_interrupt_control<I: InterruptControl>() -> Result<(), SetHookError> {
+    INTERRUPT_CONTROL.set(Hooks {
+        save_and_disable: I::save_and_disable,
+        restore: I::restore,
+    })
+}
+
+#[inline]
+pub(crate) fn save_and_disable() -> usize {
+    INTERRUPT_CONTROL
+        .get()
+        .map_or(0, |hooks| (hooks.save_and_disable)())
+}
+
+#[inline]
+pub(crate) fn restore(flags: usize) {
+    if let Some(hooks) = INTERRUPT_CONTROL.get() {
+        (hooks.restore)(flags);
+    }
+}
+
+/// Guard returned by [`RawSpinLock::lock_irqsave`].
+///
+/// Dropping it releases the lock first and then restores the saved
+/// interrupt state.
+pub struct RawSpinLockIrqGuard<'a, T> {
+    guard: ManuallyDrop<RawSpinLockGuard<'a, T>>,
+    flags: usize,
+}
+
+impl<T> RawSpinLock<T> {
+    /// Disables local interrupts, then acquires the lock.
+    pub fn lock_irqsave(&self) -> RawSpinLockIrqGuard<'_, T> {
+        let flags = save_and_disable();
+        RawSpinLockIrqGuard {
+            guard: ManuallyDrop::new(self.lock()),
+            flags,
+        }
+    }
+
+    /// Like [`lock_irqsave`](Self::lock_irqsave), but restores the saved
+    /// interrupt state while the lock is contended.
+    ///
+    /// Each attempt disables interrupts and tries the lock once; if that
+    /// fails, it restores the state and waits as
+    /// [`wait_unlocked`](Self::wait_unlocked) does before the next one. The
+    /// guard keeps the state saved by the successful attempt, so it is
+    /// restored exactly once, on drop. Before raw atomics are enabled nothing
+    /// contends, so this is `lock_irqsave`.
+    #[track_caller]
+    pub fn lock_irqsave_spin_enabled(&self) -> RawSpinLockIrqGuard<'_, T> {
+        if !raw_atomics_enabled() {
+            return self.lock_irqsave();
+        }
+        loop {
+            let flags = save_and_disable();
+            if let Some(guard) = self.try_lock() {
+                return RawSpinLockIrqGuard {
+                    guard: ManuallyDrop::new(guard),
+                    flags,
+                };
+            }
+            restore(flags);
+            self.wait_unlocked();
+        }
+    }
+
+    /// Like [`lock_irqsave`](Self::lock_irqsave), but fails instead of
+    /// spinning, in which case the interrupt state is restored immediately.
+    pub fn try_lock_irqsave(&self) -> Option<RawSpinLockIrqGuard<'_, T>> {
+        let flags = save_and_disable();
+        match self.try_lock() {
+            Some(guard) => Some(RawSpinLockIrqGuard {
+                guard: ManuallyDrop::new(guard),
+                flags,
+            }),
+            None => {
+                restore(flags);
+                None
+            }
+        }
+    }
+}
+
+impl<T> Drop for RawSpinLockIrqGuard<'_, T> {
+    fn drop(&mut self) {
+        // SAFETY: the inner guard is dropped exactly once, here.
+        unsafe { ManuallyDrop::drop(&mut self.guard) };
+        restore(self.flags);
+    }
+}
+
+impl<T> Deref for RawSpinLockIrqGuard<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &Self::Target {
+        &self.guard
+    }
+}
+
+impl<T> DerefMut for RawSpinLockIrqGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.guard
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl<T: defmt::Format> defmt::Format for RawSpinLockIrqGuard<'_, T> {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        defmt::Format::format(&**self, f)
+    }
+}
@@ -0,0 +1,211 @@
+//! Spinlocks for kernel bring-up whose atomic operations can be switched on
+//! once the platform allows it.
+//!
+//! The crate is `no_std` unless the default `std` feature is enabled. The
+//! APIs that allocate are behind the `alloc` feature, which `std` implies,
+//! so the locks themselves build without an allocator.
+//!
+//! The core API is re-exported here and in [`prelude`]: [`RawSpinLock`] and
+//! [`RwSpinLock`] with their guards, the [`Lock`] and [`ReadWriteLock`]
+//! traits, and [`enable_raw_atomics`], [`raw_atomics_enabled`] and the
+//! [`AtomicsEnabled`] token.
+//! Everything else is reached through its module. `examples/philosophers.rs`
+//! runs the dining philosophers on `RawSpinLock`s:
+//! `cargo run --example philosophers`.
+//!
+//! # Stable surface
+//!
+//! Downstream code can rely on these staying as they are:
+//!
+//! - [`RawSpinLock`], [`RawSpinLockGuard`] and [`Lock`] in [`mutex`];
+//! - [`RwSpinLock`], its guards and [`ReadWriteLock`] in [`rwlock`];
+//! - [`enable_raw_atomics`] and [`raw_atomics_enabled`] in [`raw`];
+//! - [`LateInitSpinLock`](once::LateInitSpinLock) in [`once`];
+//! - [`prelude`] and the re-exports at the crate root.
+//!
+//! The other locks, `stats`, [`sync`], and the debugging features with
+//! their hooks may still change between releases. Names that moved keep
+//! their old paths, such as `mutex::RwSpinLock` and
+//! `mutex::enable_raw_atomics`.
+//!
+//! # ThreadSanitizer
+//!
+//! Once raw atomics are enabled, every lock here synchronises through
+//! acquire and release operations on standard atomics, which ThreadSanitizer
+//! models, so no annotations are needed: runs under `-Zsanitizer=thread` are
+//! expected to be clean with any combination of features except `hle`, since
+//! hardware transactions are not modelled. Permissive mode does not
+//! synchronise at all, so a lock shared between threads before
+//! [`enable_raw_atomics`] is a data race. Data written through
+//! [`no_lock`] or in permissive mode is published to threads started after
+//! enabling by the thread start itself. `cargo +nightly tsan` runs
+//! `tests/tsan.rs`, which covers each step of that, and the lost-update
+//! suite under ThreadSanitizer.
+//!
+//! # Miri
+//!
+//! The unsafe paths, such as the guards' `Deref` impls, [`no_lock`],
+//! [`init_in_place`] and [`from_raw`], are expected to run clean under Miri,
+//! both on one thread and with threads locking after [`enable_raw_atomics`].
+//! The same caveat about permissive mode applies, and `hle` cannot run at
+//! all, since Miri does not execute inline assembly. The benchmarks do not
+//! run under Miri either, as they pin threads to cores, and
+//! `examples/permissive_corruption.rs` races on purpose. `tests/miri.rs` and
+//! `tests/miri_bringup.rs` cover these paths in runs small enough for
+//! `cargo +nightly miri test`.
+//!
+//! # Code size
+//!
+//! The `Debug`, `Display` and `Error` impls and the formatted panic messages
+//! pull `core::fmt` into a firmware image as soon as anything formats them,
+//! such as a panic handler that prints its message. The `tiny` feature
+//! leaves all of them out: panics in lock code carry fixed messages, and the
+//! caller's location only through the `PanicInfo`. `defmt::Format` impls
+//! stay, as they do not use `core::fmt`, and so does the `Debug` impl of
+//! `bus::DeviceError`, which `embedded-hal` requires. Since `std` and the
+//! diagnostics features format their reports, enabling any of them with
+//! `tiny` fails to compile. `cargo build-no-std-tiny` links the bare-metal
+//! consumer in `no-std/` with it.
+//!
+//! [`enable_raw_atomics`]: crate::raw::enable_raw_atomics
+//! [`no_lock`]: crate::mutex::RawSpinLock::no_lock
+//! [`init_in_place`]: crate::mutex::RawSpinLock::init_in_place
+//! [`from_raw`]: crate::mutex::RawSpinLock::from_raw
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(all(
+    feature = "tiny",
+    any(
+        feature = "std",
+        feature = "audit",
+        feature = "checked-guards",
+        feature = "deadlock-detection",
+        feature = "lock-ordering",
+        feature = "lockdep",
+        feature = "log",
+        feature = "no-lock-check",
+        feature = "owner-tracking",
+        feature = "reader-tracking",
+        feature = "registry",
+        feature = "track-location",
+        feature = "tracing",
+        feature = "validate-placement",
+        feature = "watchdog",
+    )
+))]
+compile_error!("the `tiny` feature cannot be combined with `std` or the diagnostics features");
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+pub mod access;
+pub mod allocator;
+pub mod any;
+#[cfg(feature = "alloc")]
+pub mod arc_swap;
+mod atomic;
+#[cfg(feature = "audit")]
+pub mod audit;
+pub mod biased;
+pub mod builder;
+#[cfg(feature = "embedded-hal")]
+pub mod bus;
+pub mod cache;
+#[cfg(feature = "cortex-m")]
+pub mod ceiling;
+#[cfg(feature = "chaos")]
+pub mod chaos;
+pub mod clock;
+pub mod cohort;
+pub mod combining;
+#[cfg(feature = "std")]
+pub mod compat;
+#[cfg(feature = "critical-section-impl")]
+pub mod critical;
+#[cfg(feature = "deadlock-detection")]
+pub mod deadlock;
+pub mod double_buffer;
+#[cfg(all(feature = "hle", target_arch = "x86_64", target_feature = "rtm"))]
+pub mod elision;
+#[cfg(feature = "embassy")]
+pub mod embassy;
+pub mod error;
+pub mod exchanger;
+#[cfg(feature = "fault-injection")]
+pub mod fault;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "panic-hook")]
+pub mod held;
+pub mod hook;
+pub mod hybrid;
+pub mod irq;
+pub mod list;
+pub mod lockdep;
+#[cfg(feature = "log")]
+pub mod logger;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+#[cfg(feature = "testing")]
+pub mod mock;
+pub mod mutex;
+pub mod once;
+pub mod oneshot;
+#[cfg(feature = "lock-ordering")]
+pub mod ordering;
+pub mod owner;
+pub mod padded;
+#[cfg(feature = "panic-policy")]
+pub mod panic_policy;
+pub mod park;
+pub mod placement;
+pub mod preempt;
+pub mod prelude;
+pub mod probe;
+pub mod project;
+pub mod racy;
+pub mod raw;
+#[cfg(feature = "reader-tracking")]
+pub mod readers;
+#[cfg(feature = "registry")]
+pub mod registry;
+pub mod relax;
+pub mod rwlock;
+#[cfg(all(feature = "async", not(any(loom, shuttle))))]
+pub mod rwlock_async;
+pub mod signal;
+#[cfg(any(feature = "portable-atomic", target_has_atomic = "64"))]
+pub mod small;
+#[cfg(feature = "spin-compat")]
+pub mod spin;
+#[cfg(feature = "alloc")]
+pub mod spin_ptr;
+pub mod spin_ref;
+pub mod state;
+#[cfg(feature = "static-key")]
+pub mod static_key;
+#[cfg(feature = "stats")]
+pub mod stats;
+#[cfg(feature = "std")]
+pub mod stress;
+pub mod sync;
+pub mod topology;
+pub mod transaction;
+#[cfg(feature = "usdt")]
+pub mod usdt;
+pub mod versioned;
+pub mod wait;
+#[cfg(feature = "watchdog")]
+pub mod watchdog;
+
+pub use mutex::Lock;
+pub use mutex::RawSpinLock;
+pub use mutex::RawSpinLockGuard;
+pub use raw::AtomicsEnabled;
+pub use raw::enable_raw_atomics;
+pub use raw::raw_atomics_enabled;
+pub use rwlock::ReadWriteLock;
+pub use rwlock::RwSpinLock;
+pub use rwlock::RwSpinLockReadGuard;
+pub use rwlock::RwSpinLockWriteGuard;
@@ -0,0 +1,421 @@
+//! An intrusive doubly-linked list behind a spinlock.
+//!
+//! [`SpinList`] links [`ListNode`]s that callers own, embedded in their own
+//! structs or on the stack of a waiting thread, so linking never allocates.
+//! [`SpinList::lock`] returns a [`SpinListGuard`] through which the list is
+//! edited and walked while its [`RawSpinLock`] is held.
+//!
+//! A node is linked through a `Pin`, so it stays put, and dropping a linked
+//! node unlinks it first, taking its list's lock; dropping a linked node
+//! while holding that list's guard therefore deadlocks. A node's lifetime
+//! parameter is the borrow of the lists it may join, which keeps them alive
+//! while it is.
+//!
+//! Nodes taken off with [`pop_front`](SpinListGuard::pop_front),
+//! [`pop_back`](SpinListGuard::pop_back) or
+//! [`Cursor::remove_current`] stay assigned to their list until their owner
+//! drops them or calls [`unlink`](ListNode::unlink), so that the reference
+//! the guard hands out cannot outlive the node.
+
+use core::cell::UnsafeCell;
+use core::marker::PhantomData;
+use core::marker::PhantomPinned;
+use core::ops::Deref;
+use core::pin::Pin;
+use core::ptr;
+use core::ptr::NonNull;
+
+use crate::atomic::AtomicPtr;
+use crate::atomic::Ordering;
+use crate::mutex::RawSpinLock;
+use crate::mutex::RawSpinLockGuard;
+use crate::raw::raw_atomics_enabled;
+
+/// A node as the list stores it, with its lifetime erased.
+type NodePtr<T> = NonNull<ListNode<'static, T>>;
+
+struct Links<T> {
+    prev: Option<NodePtr<T>>,
+    next: Option<NodePtr<T>>,
+    linked: bool,
+}
+
+/// A list entry carrying a `T`, owned by the caller.
+pub struct ListNode<'l, T> {
+    /// The list whose lock guards `links`, or null.
+    list: AtomicPtr<()>,
+    links: UnsafeCell<Links<T>>,
+    value: T,
+    /// Invariant, so the node cannot join a list borrowed for less time
+    /// than the node lives.
+    _lists: PhantomData<fn(&'l ()) -> &'l ()>,
+    _pin: PhantomPinned,
+}
+
+// SAFETY: `links` is only accessed under the lock of the list in `list`,
+// and other threads only get shared references to `value`.
+unsafe impl<T: Send> Send for ListNode<'_, T> {}
+unsafe impl<T: Sync> Sync for ListNode<'_, T> {}
+
+impl<'l, T> ListNode<'l, T> {
+    pub const fn new(value: T) -> Self {
+        Self {
+            list: AtomicPtr::new(ptr::null_mut()),
+            links: UnsafeCell::new(Links {
+                prev: None,
+                next: None,
+                linked: false,
+            }),
+            value,
+            _lists: PhantomData,
+            _pin: PhantomPinned,
+        }
+    }
+
+    /// Takes the node off its list, waiting for the list's lock. Returns
+    /// whether it was linked, as opposed to unassigned or already popped.
+    pub fn unlink(self: Pin<&Self>) -> bool {
+        loop {
+            let list = self.list.load(Ordering::Acquire);
+            if list.is_null() {
+                return false;
+            }
+            // SAFETY: only lists borrowed for `'l` are stored in `list`, so
+            // it is still alive.
+            let list: &SpinList<T> = unsafe { &*list.cast() };
+            let mut guard = list.lock();
+            if self.list.load(Ordering::Relaxed) == guard.id() {
+                return guard.remove(self);
+            }
+            // Moved by `append` while we waited.
+        }
+    }
+
+    fn ptr(&self) -> NodePtr<T> {
+        NonNull::from(self).cast()
+    }
+}
+
+impl<T> Deref for ListNode<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &Self::Target {
+        &self.value
+    }
+}
+
+impl<T> Drop for ListNode<'_, T> {
+    fn drop(&mut self) {
+        // SAFETY: a node is never moved once linked, so it is pinned until
+        // here.
+        unsafe { Pin::new_unchecked(&*self) }.unlink();
+    }
+}
+
+struct Chain<T> {
+    head: Option<NodePtr<T>>,
+    tail: Option<NodePtr<T>>,
+    len: usize,
+}
+
+// SAFETY: the chain only hands out shared references to the nodes' values.
+unsafe impl<T: Sync> Send for Chain<T> {}
+
+impl<T> Chain<T> {
+    /// # Safety
+    ///
+    /// `node` must be alive and, as with every node passed here, must have
+    /// this chain's list in its `list`.
+    unsafe fn links<'a>(node: NodePtr<T>) -> &'a mut Links<T> {
+        // SAFETY: forwarded from the caller; the list's lock is held.
+        unsafe { &mut *node.as_ref().links.get() }
+    }
+
+    /// Links an unlinked `node` between the neighbours `prev` and `next`.
+    ///
+    /// # Safety
+    ///
+    /// As for [`links`](Self::links), for all three.
+    unsafe fn insert(
+        &mut self,
+        node: NodePtr<T>,
+        prev: Option<NodePtr<T>>,
+        next: Option<NodePtr<T>>,
+    ) {
+        // SAFETY: forwarded from the caller.
+        unsafe {
+            *Self::links(node) = Links {
+                prev,
+                next,
+                linked: true,
+            };
+            match prev {
+                Some(prev) => Self::links(prev).next = Some(node),
+                None => self.head = Some(node),
+            }
+            match next {
+                Some(next) => Self::links(next).prev = Some(node),
+                None => self.tail = Some(node),
+            }
+        }
+        self.len += 1;
+    }
+
+    /// # Safety
+    ///
+    /// As for [`links`](Self::links), and `node` must be linked.
+    unsafe fn remove(&mut self, node: NodePtr<T>) {
+        // SAFETY: forwarded from the caller.
+        unsafe {
+            let links = Self::links(node);
+            let (prev, next) = (links.prev.take(), links.next.take());
+            links.linked = false;
+            match prev {
+                Some(prev) => Self::links(prev).next = next,
+                None => self.head = next,
+            }
+            match next {
+                Some(next) => Self::links(next).prev = prev,
+                None => self.tail = prev,
+            }
+        }
+        self.len -= 1;
+    }
+}
+
+/// An intrusive list of [`ListNode`]s, locked as a whole.
+///
+/// Like [`RawSpinLock`], which it is built on, it is permissive until raw
+/// atomics are enabled.
+pub struct SpinList<T> {
+    chain: RawSpinLock<Chain<T>>,
+}
+
+impl<T> SpinList<T> {
+    pub const fn new() -> Self {
+        Self {
+            chain: RawSpinLock::new(Chain {
+                head: None,
+                tail: None,
+                len: 0,
+            }),
+        }
+    }
+
+    pub fn lock(&self) -> SpinListGuard<'_, T> {
+        SpinListGuard {
+            list: self,
+            chain: self.chain.lock(),
+        }
+    }
+}
+
+impl<T> Default for SpinList<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A locked [`SpinList`].
+pub struct SpinListGuard<'l, T> {
+    list: &'l SpinList<T>,
+    chain: RawSpinLockGuard<'l, Chain<T>>,
+}
+
+impl<'l, T> SpinListGuard<'l, T> {
+    fn id(&self) -> *mut () {
+        ptr::from_ref(self.list).cast_mut().cast()
+    }
+
+    /// Assigns `node` to this list, which must be free to link it.
+    fn claim(&self, node: &ListNode<'l, T>) -> NodePtr<T> {
+        let me = self.id();
+        let current = if raw_atomics_enabled() {
+            node.list
+                .compare_exchange(ptr::null_mut(), me, Ordering::Relaxed, Ordering::Relaxed)
+                .err()
+        } else {
+            let current = node.list.load(Ordering::Relaxed);
+            if current.is_null() {
+                node.list.store(me, Ordering::Relaxed);
+            }
+            Some(current).filter(|current| !current.is_null())
+        };
+        if let Some(current) = current {
+            assert!(current == me, "node is assigned to another list");
+            // SAFETY: the node is assigned to this list, whose lock is held.
+            let linked = unsafe { Chain::links(node.ptr()) }.linked;
+            assert!(!linked, "node is already linked");
+        }
+        node.ptr()
+    }
+
+    pub fn push_front(&mut self, node: Pin<&ListNode<'l, T>>) {
+        let node = self.claim(&node);
+        let head = self.chain.head;
+        // SAFETY: `node` and the head are assigned to this list, and linked
+        // nodes are alive until they unlink themselves under its lock.
+        unsafe { self.chain.insert(node, None, head) };
+    }
+
+    pub fn push_back(&mut self, node: Pin<&ListNode<'l, T>>) {
+        let node = self.claim(&node);
+        let tail = self.chain.tail;
+        // SAFETY: as in `push_front`.
+        unsafe { self.chain.insert(node, tail, None) };
+    }
+
+    /// Unlinks the first node and returns its value. The node stays
+    /// assigned to this list, see the [module docs](self).
+    pub fn pop_front(&mut self) -> Option<&T> {
+        let head = self.chain.head?;
+        // SAFETY: as in `push_front`. The node's drop waits for this guard,
+        // which the returned reference borrows.
+        unsafe {
+            self.chain.remove(head);
+            Some(&head.as_ref().value)
+        }
+    }
+
+    /// Like [`pop_front`](Self::pop_front), from the other end.
+    pub fn pop_back(&mut self) -> Option<&T> {
+        let tail = self.chain.tail?;
+        // SAFETY: as in `pop_front`.
+        unsafe {
+            self.chain.remove(tail);
+            Some(&tail.as_ref().value)
+        }
+    }
+
+    /// Unlinks `node` if it is on this list and releases it from the list
+    /// either way, so it can join another one. Returns whether it was
+    /// linked.
+    pub fn remove(&mut self, node: Pin<&ListNode<'_, T>>) -> bool {
+        // Only this list's lock holder assigns nodes to it.
+        if node.list.load(Ordering::Relaxed) != self.id() {
+            return false;
+        }
+        // SAFETY: the node is assigned to this list, whose lock is held.
+        let linked = unsafe { Chain::links(node.ptr()) }.linked;
+        if linked {
+            // SAFETY: as in `push_front`.
+            unsafe { self.chain.remove(node.ptr()) };
+        }
+        // Release, so an owner that sees the node unassigned and frees it
+        // does so after the writes above.
+        node.list.store(ptr::null_mut(), Ordering::Release);
+        linked
+    }
+
+    /// Moves every node of `other` to the back of this list. Takes time
+    /// linear in the length of `other`, to reassign its nodes.
+    pub fn append(&mut self, other: &mut SpinListGuard<'l, T>) {
+        if ptr::eq(self.list, other.list) {
+            return;
+        }
+        let Some(first) = other.chain.head else {
+            return;
+        };
+        let me = self.id();
+        for node in other.iter_ptrs() {
+            // SAFETY: as in `push_front`, for `other`.
+            unsafe { node.as_ref() }.list.store(me, Ordering::Relaxed);
+        }
+        // SAFETY: the nodes are alive as above and now assigned to this
+        // list, both locks being held.
+        unsafe {
+            Chain::links(first).prev = self.chain.tail;
+            match self.chain.tail {
+                Some(tail) => Chain::links(tail).next = Some(first),
+                None => self.chain.head = Some(first),
+            }
+        }
+        self.chain.tail = other.chain.tail.take();
+        self.chain.len += other.chain.len;
+        other.chain.head = None;
+        other.chain.len = 0;
+    }
+
+    pub fn len(&self) -> usize {
+        self.chain.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chain.head.is_none()
+    }
+
+    /// Iterates over the linked values from front to back.
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter {
+            next: self.chain.head,
+            _guard: PhantomData,
+        }
+    }
+
+    /// Returns a cursor at the front node, for walking the list while
+    /// removing nodes.
+    pub fn cursor_front(&mut self) -> Cursor<'_, 'l, T> {
+        Cursor {
+            current: self.chain.head,
+            guard: self,
+        }
+    }
+
+    fn iter_ptrs(&self) -> impl Iterator<Item = NodePtr<T>> + '_ {
+        // SAFETY: as in `push_front`.
+        core::iter::successors(self.chain.head, |&node| unsafe { Chain::links(node) }.next)
+    }
+}
+
+/// Iterator returned by [`SpinListGuard::iter`].
+pub struct Iter<'a, T> {
+    next: Option<NodePtr<T>>,
+    _guard: PhantomData<&'a T>,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.next?;
+        // SAFETY: the guard is borrowed for `'a`, so linked nodes stay
+        // alive and unchanged.
+        let node = unsafe { node.as_ref() };
+        self.next = unsafe { Chain::links(node.ptr()) }.next;
+        Some(&node.value)
+    }
+}
+
+/// A position in a locked [`SpinList`], from [`SpinListGuard::cursor_front`].
+pub struct Cursor<'g, 'l, T> {
+    guard: &'g mut SpinListGuard<'l, T>,
+    current: Option<NodePtr<T>>,
+}
+
+impl<'g, T> Cursor<'g, '_, T> {
+    /// Returns the value at the cursor, or `None` past the end.
+    pub fn current(&self) -> Option<&T> {
+        // SAFETY: as in `SpinListGuard::push_front`.
+        self.current.map(|node| unsafe { &node.as_ref().value })
+    }
+
+    pub fn move_next(&mut self) {
+        if let Some(node) = self.current {
+            // SAFETY: as in `SpinListGuard::push_front`.
+            self.current = unsafe { Chain::links(node) }.next;
+        }
+    }
+
+    /// Unlinks the node at the cursor, moves to the next one and returns
+    /// the removed value, which stays borrowed from the guard like the one
+    /// [`SpinListGuard::pop_front`] returns.
+    pub fn remove_current(&mut self) -> Option<&'g T> {
+        let node = self.current?;
+        // SAFETY: as in `SpinListGuard::pop_front`; the guard is borrowed
+        // for `'g`.
+        unsafe {
+            self.current = Chain::links(node).next;
+            self.guard.chain.remove(node);
+            Some(&node.as_ref().value)
+        }
+    }
+}
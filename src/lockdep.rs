@@ -0,0 +1,515 @@
+//! Lock ordering learned at run time, in the style of Linux's lockdep.
+//!
+//! Every [`RawSpinLock`] placed in a [`LockClass`] with
+//! [`RawSpinLock::in_class`] belongs to that class; [`lock_class!`] declares
+//! one class per call site, and [`registered_static!`] gives each static a
+//! class of its own name. With the `lockdep` feature, acquiring a lock of
+//! class B while holding one of class A records the edge "A before B" in a
+//! global table. When an acquisition would add an edge that closes a cycle,
+//! the inversion is reported once per pair of classes, before spinning and
+//! whether or not the two orders ever race: through the handler registered
+//! with `set_lockdep_handler`, or by panicking if there is none. The
+//! report names where both the current and the conflicting earlier order
+//! were taken.
+//!
+//! All memory is static: at most [`MAX_CLASSES`] classes and [`MAX_EDGES`]
+//! recorded edges; classes beyond that are not tracked, and edges beyond
+//! that are still checked but reported without locations. Each context
+//! keeps the locks it holds in [`MAX_DEPTH`] entries, found the same way as
+//! for lock-order levels: thread-local on `std`, otherwise one of
+//! [`MAX_CONTEXTS`] slots keyed by [`owner::current`]. [`try_lock`] cannot
+//! deadlock and adds no edges, but the lock it takes counts as held. Nested
+//! locks of the same class are not checked. Tracking starts once raw
+//! atomics are enabled.
+//!
+//! [`RawSpinLock`]: crate::mutex::RawSpinLock
+//! [`RawSpinLock::in_class`]: crate::mutex::RawSpinLock::in_class
+//! [`lock_class!`]: crate::lock_class
+//! [`registered_static!`]: crate::registered_static
+//! [`try_lock`]: crate::mutex::RawSpinLock::try_lock
+//! [`owner::current`]: crate::owner::current
+
+#[cfg(feature = "lockdep")]
+use crate::atomic::AtomicUsize;
+
+#[cfg(feature = "lockdep")]
+pub use tracking::Acquisition;
+#[cfg(feature = "lockdep")]
+pub use tracking::Dependency;
+#[cfg(feature = "lockdep")]
+pub use tracking::LockdepReport;
+#[cfg(feature = "lockdep")]
+pub(crate) use tracking::acquire;
+#[cfg(feature = "lockdep")]
+pub(crate) use tracking::check;
+#[cfg(feature = "lockdep")]
+pub(crate) use tracking::release;
+#[cfg(feature = "lockdep")]
+pub use tracking::set_lockdep_handler;
+
+/// Classes that can be tracked.
+pub const MAX_CLASSES: usize = 256;
+/// Edges whose locations are recorded.
+pub const MAX_EDGES: usize = 1024;
+/// Locks tracked per context.
+pub const MAX_DEPTH: usize = 16;
+/// Contexts that can be tracked at the same time without `std`.
+pub const MAX_CONTEXTS: usize = 64;
+/// Longest earlier order a report can show.
+pub const MAX_PATH: usize = 16;
+
+/// The class a lock's ordering is learned for; declare one per lock
+/// declaration site, usually with [`lock_class!`](crate::lock_class).
+pub struct LockClass {
+    name: &'static str,
+    /// Index into the class table plus one, 0 before first use, or
+    /// `UNTRACKED`.
+    #[cfg(feature = "lockdep")]
+    index: AtomicUsize,
+}
+
+impl LockClass {
+    pub const fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            #[cfg(feature = "lockdep")]
+            index: AtomicUsize::new(0),
+        }
+    }
+
+    pub const fn name(&self) -> &'static str {
+        self.name
+    }
+}
+
+/// Declares a [`LockClass`] for this call site and evaluates to a
+/// `&'static` reference to it. Without a name, the class is named after
+/// the file and line.
+#[macro_export]
+macro_rules! lock_class {
+    () => {
+        $crate::lock_class!(::core::concat!(::core::file!(), ":", ::core::line!()))
+    };
+    ($name:expr) => {{
+        static CLASS: $crate::lockdep::LockClass = $crate::lockdep::LockClass::new($name);
+        &CLASS
+    }};
+}
+
+#[cfg(feature = "lockdep")]
+mod tracking {
+    use core::cell::Cell;
+    use core::fmt;
+    use core::panic::Location;
+    use core::ptr;
+
+    use crate::atomic::AtomicPtr;
+    use crate::atomic::AtomicUsize;
+    use crate::atomic::Ordering;
+    use crate::hook::HookCell;
+    use crate::hook::SetHookError;
+
+    use super::LockClass;
+    use super::MAX_CLASSES;
+    #[cfg(not(feature = "std"))]
+    use super::MAX_CONTEXTS;
+    use super::MAX_DEPTH;
+    use super::MAX_EDGES;
+    use super::MAX_PATH;
+
+    const UNTRACKED: usize = usize::MAX;
+    const WORDS: usize = MAX_CLASSES / usize::BITS as usize;
+
+    /// A set of classes.
+    type Bitmap = [AtomicUsize; WORDS];
+
+    fn contains(set: &Bitmap, class: usize) -> bool {
+        set[class / usize::BITS as usize].load(Ordering::Acquire) & bit(class) != 0
+    }
+
+    /// Adds `class` to `set`, returning whether it was already there.
+    fn insert(set: &Bitmap, class: usize) -> bool {
+        set[class / usize::BITS as usize].fetch_or(bit(class), Ordering::AcqRel) & bit(class) != 0
+    }
+
+    fn bit(class: usize) -> usize {
+        1 << (class % usize::BITS as usize)
+    }
+
+    static CLASSES: [AtomicPtr<LockClass>; MAX_CLASSES] =
+        [const { AtomicPtr::new(ptr::null_mut()) }; MAX_CLASSES];
+    static NEXT_CLASS: AtomicUsize = AtomicUsize::new(0);
+
+    /// `AFTER[a]` holds every class seen acquired while `a` was held.
+    static AFTER: [Bitmap; MAX_CLASSES] =
+        [const { [const { AtomicUsize::new(0) }; WORDS] }; MAX_CLASSES];
+    /// `REPORTED[b]` holds every `a` for which "`b` before `a`" was reported.
+    static REPORTED: [Bitmap; MAX_CLASSES] =
+        [const { [const { AtomicUsize::new(0) }; WORDS] }; MAX_CLASSES];
+
+    /// Where an edge of `AFTER` was first seen.
+    struct EdgeSlot {
+        /// Classes plus one; 0 while the slot is being filled.
+        from: AtomicUsize,
+        to: AtomicUsize,
+        from_at: AtomicPtr<Location<'static>>,
+        to_at: AtomicPtr<Location<'static>>,
+    }
+
+    static EDGES: [EdgeSlot; MAX_EDGES] = [const {
+        EdgeSlot {
+            from: AtomicUsize::new(0),
+            to: AtomicUsize::new(0),
+            from_at: AtomicPtr::new(ptr::null_mut()),
+            to_at: AtomicPtr::new(ptr::null_mut()),
+        }
+    }; MAX_EDGES];
+    static NEXT_EDGE: AtomicUsize = AtomicUsize::new(0);
+
+    impl LockClass {
+        /// Returns the class's table index, assigning one on first use.
+        fn index(&'static self) -> Option<usize> {
+            match self.index.load(Ordering::Acquire) {
+                0 => self.assign(),
+                UNTRACKED => None,
+                index => Some(index - 1),
+            }
+        }
+
+        #[cold]
+        fn assign(&'static self) -> Option<usize> {
+            let index = NEXT_CLASS.fetch_add(1, Ordering::Relaxed);
+            let assigned = if index < MAX_CLASSES {
+                CLASSES[index].store(ptr::from_ref(self).cast_mut(), Ordering::Release);
+                index + 1
+            } else {
+                UNTRACKED
+            };
+            // Losing the race wastes a slot; the winner's index is the one used.
+            match self
+                .index
+                .compare_exchange(0, assigned, Ordering::AcqRel, Ordering::Acquire)
+            {
+                Ok(_) if assigned == UNTRACKED => None,
+                Ok(_) => Some(index),
+                Err(UNTRACKED) => None,
+                Err(index) => Some(index - 1),
+            }
+        }
+    }
+
+    fn class_name(class: usize) -> &'static str {
+        // SAFETY: non-null entries come from `&'static LockClass`.
+        unsafe { CLASSES[class].load(Ordering::Acquire).as_ref() }.map_or("?", |class| class.name)
+    }
+
+    #[derive(Clone, Copy)]
+    struct Entry {
+        lock: usize,
+        class: usize,
+        location: &'static Location<'static>,
+    }
+
+    /// The locks one context holds. Only ever touched by that context.
+    struct Held {
+        entries: [Cell<Option<Entry>>; MAX_DEPTH],
+    }
+
+    impl Held {
+        const fn new() -> Self {
+            Self {
+                entries: [const { Cell::new(None) }; MAX_DEPTH],
+            }
+        }
+    }
+
+    #[cfg(feature = "std")]
+    fn with_held(f: impl FnOnce(&Held)) {
+        std::thread_local!(static HELD: Held = const { Held::new() });
+        let _ = HELD.try_with(f);
+    }
+
+    #[cfg(not(feature = "std"))]
+    fn with_held(f: impl FnOnce(&Held)) {
+        struct Slot {
+            owner: AtomicUsize,
+            held: Held,
+        }
+
+        // SAFETY: `held` is only accessed by the context whose id is in `owner`.
+        unsafe impl Sync for Slot {}
+
+        static SLOTS: [Slot; MAX_CONTEXTS] = [const {
+            Slot {
+                owner: AtomicUsize::new(0),
+                held: Held::new(),
+            }
+        }; MAX_CONTEXTS];
+
+        let Some(me) = crate::owner::current() else {
+            return;
+        };
+        let me = me.get();
+        let slot = SLOTS
+            .iter()
+            .find(|slot| slot.owner.load(Ordering::Relaxed) == me)
+            .or_else(|| {
+                SLOTS.iter().find(|slot| {
+                    slot.owner
+                        .compare_exchange(0, me, Ordering::Acquire, Ordering::Relaxed)
+                        .is_ok()
+                })
+            });
+        if let Some(slot) = slot {
+            f(&slot.held);
+        }
+    }
+
+    /// Learns the edges from every held class to `class`, reporting any that
+    /// closes a cycle.
+    #[track_caller]
+    pub(crate) fn check(class: &'static LockClass) {
+        let Some(to) = class.index() else {
+            return;
+        };
+        let location = Location::caller();
+        let mut inversion = None;
+        with_held(|held| {
+            for entry in held.entries.iter().filter_map(Cell::get) {
+                if entry.class == to || contains(&AFTER[entry.class], to) {
+                    continue;
+                }
+                match find_path(to, entry.class) {
+                    Some(path) => {
+                        if inversion.is_none() && !insert(&REPORTED[to], entry.class) {
+                            inversion = Some(LockdepReport {
+                                acquiring: Acquisition {
+                                    class: class.name,
+                                    location: Some(location),
+                                },
+                                holding: Acquisition {
+                                    class: class_name(entry.class),
+                                    location: Some(entry.location),
+                                },
+                                path,
+                            });
+                        }
+                    }
+                    None => learn(entry, to, location),
+                }
+            }
+        });
+        // Reported outside `with_held`, in case the handler takes locks.
+        if let Some(report) = inversion {
+            match HANDLER.get() {
+                Some(handler) => handler(&report),
+                None => panic!("{report}"),
+            }
+        }
+    }
+
+    fn learn(from: Entry, to: usize, to_at: &'static Location<'static>) {
+        let slot = NEXT_EDGE.fetch_add(1, Ordering::Relaxed);
+        if let Some(slot) = EDGES.get(slot) {
+            slot.from_at
+                .store(ptr::from_ref(from.location).cast_mut(), Ordering::Relaxed);
+            slot.to_at
+                .store(ptr::from_ref(to_at).cast_mut(), Ordering::Relaxed);
+            slot.to.store(to + 1, Ordering::Relaxed);
+            slot.from.store(from.class + 1, Ordering::Release);
+        }
+        insert(&AFTER[from.class], to);
+    }
+
+    /// Finds a recorded chain of edges from `from` to `to`, breadth first so
+    /// the shortest one is reported.
+    fn find_path(from: usize, to: usize) -> Option<Path> {
+        const NONE: u16 = u16::MAX;
+        let mut parent = [NONE; MAX_CLASSES];
+        let mut queue = [0u16; MAX_CLASSES];
+        let (mut head, mut tail) = (0, 1);
+        queue[0] = from as u16;
+        parent[from] = from as u16;
+        while head < tail {
+            let class = usize::from(queue[head]);
+            head += 1;
+            for next in 0..MAX_CLASSES {
+                if parent[next] != NONE || !contains(&AFTER[class], next) {
+                    continue;
+                }
+                parent[next] = class as u16;
+                if next == to {
+                    return Some(Path::walk(&parent, from, to));
+                }
+                queue[tail] = next as u16;
+                tail += 1;
+            }
+        }
+        None
+    }
+
+    /// Records that the current context holds `lock`. Returns whether it was
+    /// recorded, and so must be removed again with [`release`].
+    #[track_caller]
+    pub(crate) fn acquire(lock: usize, class: &'static LockClass) -> bool {
+        let Some(class) = class.index() else {
+            return false;
+        };
+        let location = Location::caller();
+        let mut recorded = false;
+        with_held(|held| {
+            if let Some(free) = held.entries.iter().find(|entry| entry.get().is_none()) {
+                free.set(Some(Entry {
+                    lock,
+                    class,
+                    location,
+                }));
+                recorded = true;
+            }
+        });
+        recorded
+    }
+
+    /// Forgets the entry [`acquire`] recorded for `lock`.
+    pub(crate) fn release(lock: usize) {
+        with_held(|held| {
+            let entry = held
+                .entries
+                .iter()
+                .find(|entry| entry.get().is_some_and(|entry| entry.lock == lock));
+            if let Some(entry) = entry {
+                entry.set(None);
+            }
+        });
+    }
+
+    /// One lock acquisition within a report.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub struct Acquisition {
+        pub class: &'static str,
+        /// Where the lock was acquired, if recorded.
+        pub location: Option<&'static Location<'static>>,
+    }
+
+    impl fmt::Display for Acquisition {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self.location {
+                Some(location) => write!(f, "{} (at {location})", self.class),
+                None => f.write_str(self.class),
+            }
+        }
+    }
+
+    /// An earlier order: `held` was held while `acquired` was acquired.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub struct Dependency {
+        pub held: Acquisition,
+        pub acquired: Acquisition,
+    }
+
+    impl Dependency {
+        const EMPTY: Dependency = Dependency {
+            held: Acquisition {
+                class: "",
+                location: None,
+            },
+            acquired: Acquisition {
+                class: "",
+                location: None,
+            },
+        };
+
+        fn recorded(from: usize, to: usize) -> Self {
+            let slot = EDGES.iter().find(|slot| {
+                slot.from.load(Ordering::Acquire) == from + 1
+                    && slot.to.load(Ordering::Relaxed) == to + 1
+            });
+            // SAFETY: the pointers are null or come from `&'static Location`s.
+            let location =
+                |at: &AtomicPtr<Location<'static>>| unsafe { at.load(Ordering::Relaxed).as_ref() };
+            Self {
+                held: Acquisition {
+                    class: class_name(from),
+                    location: slot.and_then(|slot| location(&slot.from_at)),
+                },
+                acquired: Acquisition {
+                    class: class_name(to),
+                    location: slot.and_then(|slot| location(&slot.to_at)),
+                },
+            }
+        }
+    }
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    struct Path {
+        edges: [Dependency; MAX_PATH],
+        len: usize,
+    }
+
+    impl Path {
+        /// Builds the path from `from` to `to` out of `find_path`'s parents.
+        /// Chains longer than [`MAX_PATH`] keep their first edges.
+        fn walk(parent: &[u16; MAX_CLASSES], from: usize, to: usize) -> Self {
+            let mut classes = [0usize; MAX_CLASSES];
+            let mut len = 0;
+            let mut class = to;
+            while class != from {
+                classes[len] = class;
+                len += 1;
+                class = usize::from(parent[class]);
+            }
+            classes[len] = from;
+            let mut path = Path {
+                edges: [Dependency::EMPTY; MAX_PATH],
+                len: len.min(MAX_PATH),
+            };
+            for (i, edge) in path.edges[..path.len].iter_mut().enumerate() {
+                *edge = Dependency::recorded(classes[len - i], classes[len - i - 1]);
+            }
+            path
+        }
+    }
+
+    /// A lock acquisition that inverts an order seen earlier.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub struct LockdepReport {
+        /// The lock being acquired.
+        pub acquiring: Acquisition,
+        /// The held lock it must not be acquired after.
+        pub holding: Acquisition,
+        path: Path,
+    }
+
+    impl LockdepReport {
+        /// The earlier order, from the class being acquired to the class held.
+        pub fn earlier(&self) -> &[Dependency] {
+            &self.path.edges[..self.path.len]
+        }
+    }
+
+    impl fmt::Display for LockdepReport {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(
+                f,
+                "lock order inversion: acquiring {} while holding {}, after:",
+                self.acquiring, self.holding
+            )?;
+            for edge in self.earlier() {
+                write!(
+                    f,
+                    "\n  {} held while acquiring {}",
+                    edge.held, edge.acquired
+                )?;
+            }
+            Ok(())
+        }
+    }
+
+    static HANDLER: HookCell<fn(&LockdepReport)> = HookCell::new();
+
+    /// Registers a handler called instead of panicking when an inversion is
+    /// found. If it returns, the acquisition goes ahead.
+    pub fn set_lockdep_handler(handler: fn(&LockdepReport)) -> Result<(), SetHookError> {
+        HANDLER.set(handler)
+    }
+}
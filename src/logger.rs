@@ -0,0 +1,78 @@
+use core::fmt;
+
+use log::LevelFilter;
+use log::Log;
+use log::Metadata;
+use log::Record;
+use log::SetLoggerError;
+
+use crate::atomic::AtomicUsize;
+use crate::atomic::Ordering;
+use crate::mutex::RawSpinLock;
+
+/// A [`log::Log`] implementation writing each record to `W` under a
+/// [`RawSpinLock`], so lines from different cores never interleave.
+///
+/// A record logged while the writer is already locked (for example from an
+/// interrupt that preempted another log call on the same core) is dropped
+/// instead of deadlocking, and counted in [`lost_messages`](Self::lost_messages).
+pub struct SpinLogger<W> {
+    writer: RawSpinLock<W>,
+    max_level: LevelFilter,
+    lost: AtomicUsize,
+}
+
+impl<W: fmt::Write + Send> SpinLogger<W> {
+    pub const fn new(writer: W) -> Self {
+        Self {
+            writer: RawSpinLock::new(writer),
+            max_level: LevelFilter::Trace,
+            lost: AtomicUsize::new(0),
+        }
+    }
+
+    /// Sets the most verbose level this logger accepts.
+    pub const fn with_max_level(mut self, level: LevelFilter) -> Self {
+        self.max_level = level;
+        self
+    }
+
+    /// Installs `self` as the global logger and applies its level filter.
+    pub fn set_as_logger(&'static self) -> Result<(), SetLoggerError> {
+        log::set_logger(self)?;
+        log::set_max_level(self.max_level);
+        Ok(())
+    }
+
+    /// Returns how many records were dropped because the writer was busy.
+    pub fn lost_messages(&self) -> usize {
+        self.lost.load(Ordering::Relaxed)
+    }
+}
+
+impl<W: fmt::Write + Send> Log for SpinLogger<W> {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        metadata.level() <= self.max_level
+    }
+
+    fn log(&self, record: &Record<'_>) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        // `try_lock` only fails once raw atomics are enabled, so the counter
+        // is never touched with RMW instructions before that.
+        let Some(mut writer) = self.writer.try_lock() else {
+            self.lost.fetch_add(1, Ordering::Relaxed);
+            return;
+        };
+        let _ = writeln!(
+            writer,
+            "[{}] {}: {}",
+            record.level(),
+            record.target(),
+            record.args()
+        );
+    }
+
+    fn flush(&self) {}
+}
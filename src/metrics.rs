@@ -0,0 +1,99 @@
+//! Pushing lock events into a user-defined metrics sink.
+//!
+//! With the `metrics` feature, every [`RawSpinLock`] acquisition through
+//! `lock` or `try_lock` is reported to the [`MetricsSink`] registered with
+//! [`set_metrics_sink`]: [`on_acquire`](MetricsSink::on_acquire) and then
+//! [`on_release`](MetricsSink::on_release), both called right after the
+//! lock is released so the sink never runs inside the critical section.
+//! Hold times need a [`Clock`](crate::clock::Clock); without one they are
+//! reported as 0. Nothing is recorded while no sink is registered.
+//!
+//! [`RawSpinLock`]: crate::mutex::RawSpinLock
+
+use crate::clock;
+use crate::hook::HookCell;
+use crate::hook::SetHookError;
+
+/// Identifies the lock an event is about.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(not(feature = "tiny"), derive(Debug))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct LockId(usize);
+
+impl LockId {
+    /// Address of the lock.
+    pub const fn addr(&self) -> usize {
+        self.0
+    }
+}
+
+/// Receives lock events, for example to feed a metrics registry.
+pub trait MetricsSink: Sync {
+    /// The lock was acquired, after `spins` spins if it was `contended`.
+    fn on_acquire(&self, lock: &LockId, contended: bool, spins: u32);
+
+    /// The lock was released after being held for `held_ns`, in units of
+    /// the registered clock (nanoseconds with
+    /// [`StdClock`](crate::clock::StdClock)).
+    fn on_release(&self, lock: &LockId, held_ns: u64);
+}
+
+static SINK: HookCell<&'static dyn MetricsSink> = HookCell::new();
+
+/// Registers the metrics sink. Call once during bring-up.
+pub fn set_metrics_sink(sink: &'static dyn MetricsSink) -> Result<(), SetHookError> {
+    SINK.set(sink)
+}
+
+/// An acquisition to report once the lock is released.
+pub(crate) struct Pending {
+    sink: &'static dyn MetricsSink,
+    contention: Option<usize>,
+    acquired_at: Option<u64>,
+}
+
+impl Pending {
+    /// Starts tracking an acquisition, if a sink is registered.
+    #[inline]
+    pub(crate) fn start(contention: Option<usize>) -> Option<Self> {
+        let sink = SINK.get()?;
+        Some(Self {
+            sink,
+            contention,
+            acquired_at: clock::now(),
+        })
+    }
+
+    /// Ends the hold; called just before the lock is released.
+    #[inline]
+    pub(crate) fn stop(self) -> Released {
+        let held = match (self.acquired_at, clock::now()) {
+            (Some(start), Some(end)) => end.saturating_sub(start),
+            _ => 0,
+        };
+        Released {
+            pending: self,
+            held,
+        }
+    }
+}
+
+/// A finished hold, reported once the lock is free again.
+pub(crate) struct Released {
+    pending: Pending,
+    held: u64,
+}
+
+impl Released {
+    #[cold]
+    pub(crate) fn report(self, lock: usize) {
+        let lock = LockId(lock);
+        let spins = self.pending.contention.unwrap_or(0);
+        self.pending.sink.on_acquire(
+            &lock,
+            self.pending.contention.is_some(),
+            u32::try_from(spins).unwrap_or(u32::MAX),
+        );
+        self.pending.sink.on_release(&lock, self.held);
+    }
+}
@@ -0,0 +1,432 @@
+//! A scripted [`Lock`] and [`ReadWriteLock`] for unit tests.
+//!
+//! Code generic over the lock traits handles a failed `try_lock`, or a lock
+//! that takes a while, on paths a real lock almost never takes in a test.
+//! A [`MockLock`] excludes like a reader-writer lock, but can be told to:
+//!
+//! - fail the next few `try_*` calls, with [`fail_next_try_locks`];
+//! - hold back one acquisition until the test lets it through, with
+//!   [`delay_acquisition`] and [`release_delayed`];
+//! - record every call, with where it was made, for [`history`] and the
+//!   `assert_*` methods.
+//!
+//! Failures are scripted per lock, unlike the global count of
+//! [`fault`](crate::fault), so tests using mocks can run in parallel.
+//!
+//! [`fail_next_try_locks`]: MockLock::fail_next_try_locks
+//! [`delay_acquisition`]: MockLock::delay_acquisition
+//! [`release_delayed`]: MockLock::release_delayed
+//! [`history`]: MockLock::history
+
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::ops::Deref;
+use core::ops::DerefMut;
+use core::panic::Location;
+use std::sync::Condvar;
+use std::sync::Mutex;
+use std::sync::MutexGuard;
+use std::sync::PoisonError;
+use std::vec::Vec;
+
+use crate::mutex::Lock;
+use crate::rwlock::ReadWriteLock;
+
+/// The trait method a [`MockLock`] was called through.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Call {
+    Lock,
+    TryLock,
+    Read,
+    TryRead,
+    Write,
+    TryWrite,
+}
+
+impl Call {
+    fn is_try(self) -> bool {
+        matches!(self, Self::TryLock | Self::TryRead | Self::TryWrite)
+    }
+
+    fn is_shared(self) -> bool {
+        matches!(self, Self::Read | Self::TryRead)
+    }
+}
+
+/// One entry of a [`MockLock`]'s history.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Event {
+    /// `call`, made at `at`, acquired the lock.
+    Acquired {
+        call: Call,
+        at: &'static Location<'static>,
+    },
+    /// `call`, a `try_*` made at `at`, failed.
+    Failed {
+        call: Call,
+        at: &'static Location<'static>,
+    },
+    /// The guard `call` at `at` acquired was dropped.
+    Released {
+        call: Call,
+        at: &'static Location<'static>,
+    },
+}
+
+struct State {
+    readers: usize,
+    writer: bool,
+    fail_next: usize,
+    /// Acquisition attempts so far, all calls counted.
+    attempts: usize,
+    /// The attempt to hold back, once `attempts` reaches it.
+    delay_at: Option<usize>,
+    /// Whether the held-back attempt is waiting for `release_delayed`.
+    delaying: bool,
+    history: Vec<Event>,
+}
+
+impl State {
+    fn is_free(&self, call: Call) -> bool {
+        !self.writer && (call.is_shared() || self.readers == 0)
+    }
+}
+
+/// A lock whose contention is scripted by the test; see the
+/// [module docs](self).
+pub struct MockLock<T> {
+    state: Mutex<State>,
+    changed: Condvar,
+    data: UnsafeCell<T>,
+}
+
+// SAFETY: as for `RwSpinLock`: the lock hands out `&T` to several readers
+// or `&mut T` to one writer, tracked under `state`.
+unsafe impl<T: Send> Send for MockLock<T> {}
+unsafe impl<T: Send + Sync> Sync for MockLock<T> {}
+
+impl<T> MockLock<T> {
+    pub fn new(value: T) -> Self {
+        Self {
+            state: Mutex::new(State {
+                readers: 0,
+                writer: false,
+                fail_next: 0,
+                attempts: 0,
+                delay_at: None,
+                delaying: false,
+                history: Vec::new(),
+            }),
+            changed: Condvar::new(),
+            data: UnsafeCell::new(value),
+        }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+
+    /// Makes the next `count` `try_*` calls fail whether or not the lock is
+    /// free, replacing any count still pending.
+    pub fn fail_next_try_locks(&self, count: usize) {
+        self.state().fail_next = count;
+    }
+
+    /// Holds back the `nth` acquisition attempt from now, counting from 1
+    /// over every call: a blocking call waits in the lock until
+    /// [`release_delayed`](Self::release_delayed), and a `try_*` fails.
+    /// Replaces any delay still pending; 0 cancels it.
+    pub fn delay_acquisition(&self, nth: usize) {
+        let mut state = self.state();
+        let at = state.attempts + nth;
+        state.delay_at = (nth != 0).then_some(at);
+    }
+
+    /// Lets the held-back acquisition through, or cancels the delay if it
+    /// has not been reached.
+    pub fn release_delayed(&self) {
+        let mut state = self.state();
+        state.delay_at = None;
+        state.delaying = false;
+        self.changed.notify_all();
+    }
+
+    /// Whether an acquisition is waiting for
+    /// [`release_delayed`](Self::release_delayed).
+    pub fn is_delaying(&self) -> bool {
+        self.state().delaying
+    }
+
+    /// Whether any guard is alive.
+    pub fn is_held(&self) -> bool {
+        let state = self.state();
+        state.writer || state.readers != 0
+    }
+
+    /// Every call so far, in order.
+    pub fn history(&self) -> Vec<Event> {
+        self.state().history.clone()
+    }
+
+    /// Forgets the history, for asserting on what follows.
+    pub fn clear_history(&self) {
+        self.state().history.clear();
+    }
+
+    /// How many calls acquired the lock.
+    pub fn acquisitions(&self) -> usize {
+        self.count(|event| matches!(event, Event::Acquired { .. }))
+    }
+
+    /// How many `try_*` calls failed.
+    pub fn failures(&self) -> usize {
+        self.count(|event| matches!(event, Event::Failed { .. }))
+    }
+
+    /// Panics unless the lock was acquired exactly `n` times.
+    #[track_caller]
+    pub fn assert_acquired_times(&self, n: usize) {
+        let acquisitions = self.acquisitions();
+        assert_eq!(
+            acquisitions, n,
+            "MockLock acquired {acquisitions} times, expected {n}"
+        );
+    }
+
+    /// Panics unless `n` `try_*` calls failed.
+    #[track_caller]
+    pub fn assert_failed_times(&self, n: usize) {
+        let failures = self.failures();
+        assert_eq!(
+            failures, n,
+            "MockLock failed {failures} try_* calls, expected {n}"
+        );
+    }
+
+    /// Panics if a guard is alive.
+    #[track_caller]
+    pub fn assert_not_held(&self) {
+        assert!(!self.is_held(), "MockLock is still held");
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn count(&self, f: impl Fn(&Event) -> bool) -> usize {
+        self.state().history.iter().filter(|event| f(event)).count()
+    }
+
+    /// Runs the script for one attempt, then acquires, waiting unless the
+    /// call is a `try_*`. Returns whether it acquired.
+    fn acquire(&self, call: Call, at: &'static Location<'static>) -> bool {
+        let mut state = self.state();
+        state.attempts += 1;
+        let delayed = state.delay_at == Some(state.attempts);
+        if call.is_try() {
+            let scripted = if state.fail_next != 0 {
+                state.fail_next -= 1;
+                true
+            } else {
+                delayed
+            };
+            if delayed {
+                state.delay_at = None;
+            }
+            if scripted || !state.is_free(call) {
+                state.history.push(Event::Failed { call, at });
+                return false;
+            }
+        } else {
+            if delayed {
+                state.delaying = true;
+                while state.delaying {
+                    state = self
+                        .changed
+                        .wait(state)
+                        .unwrap_or_else(PoisonError::into_inner);
+                }
+            }
+            while !state.is_free(call) {
+                state = self
+                    .changed
+                    .wait(state)
+                    .unwrap_or_else(PoisonError::into_inner);
+            }
+        }
+        if call.is_shared() {
+            state.readers += 1;
+        } else {
+            state.writer = true;
+        }
+        state.history.push(Event::Acquired { call, at });
+        true
+    }
+
+    fn release(&self, call: Call, at: &'static Location<'static>) {
+        let mut state = self.state();
+        if call.is_shared() {
+            state.readers -= 1;
+        } else {
+            state.writer = false;
+        }
+        state.history.push(Event::Released { call, at });
+        self.changed.notify_all();
+    }
+
+    #[track_caller]
+    fn write_guard(&self, call: Call) -> Option<MockLockGuard<'_, T>> {
+        let at = Location::caller();
+        self.acquire(call, at).then(|| MockLockGuard {
+            lock: self,
+            call,
+            at,
+        })
+    }
+
+    #[track_caller]
+    fn read_guard(&self, call: Call) -> Option<MockLockReadGuard<'_, T>> {
+        let at = Location::caller();
+        self.acquire(call, at).then(|| MockLockReadGuard {
+            lock: self,
+            call,
+            at,
+        })
+    }
+}
+
+impl<T: Default> Default for MockLock<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T> fmt::Debug for MockLock<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.state();
+        f.debug_struct("MockLock")
+            .field("readers", &state.readers)
+            .field("writer", &state.writer)
+            .field("events", &state.history.len())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Exclusive guard of a [`MockLock`]; records its release on drop.
+pub struct MockLockGuard<'a, T> {
+    lock: &'a MockLock<T>,
+    call: Call,
+    at: &'static Location<'static>,
+}
+
+impl<T> Deref for MockLockGuard<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        // SAFETY: the lock is marked held by a writer until this is dropped.
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<T> DerefMut for MockLockGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        // SAFETY: as in `deref`.
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+impl<T> Drop for MockLockGuard<'_, T> {
+    fn drop(&mut self) {
+        self.lock.release(self.call, self.at);
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for MockLockGuard<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+/// Shared guard of a [`MockLock`]; records its release on drop.
+pub struct MockLockReadGuard<'a, T> {
+    lock: &'a MockLock<T>,
+    call: Call,
+    at: &'static Location<'static>,
+}
+
+impl<T> Deref for MockLockReadGuard<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        // SAFETY: the lock counts this reader until it is dropped, and no
+        // writer is let in meanwhile.
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<T> Drop for MockLockReadGuard<'_, T> {
+    fn drop(&mut self) {
+        self.lock.release(self.call, self.at);
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for MockLockReadGuard<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<T> Lock<T> for MockLock<T> {
+    type Guard<'a>
+        = MockLockGuard<'a, T>
+    where
+        T: 'a;
+
+    #[track_caller]
+    fn lock(&self) -> Self::Guard<'_> {
+        match self.write_guard(Call::Lock) {
+            Some(guard) => guard,
+            None => unreachable!(),
+        }
+    }
+
+    #[track_caller]
+    fn try_lock(&self) -> Option<Self::Guard<'_>> {
+        self.write_guard(Call::TryLock)
+    }
+}
+
+impl<T> ReadWriteLock<T> for MockLock<T> {
+    type ReadGuard<'a>
+        = MockLockReadGuard<'a, T>
+    where
+        T: 'a;
+    type WriteGuard<'a>
+        = MockLockGuard<'a, T>
+    where
+        T: 'a;
+
+    #[track_caller]
+    fn read(&self) -> Self::ReadGuard<'_> {
+        match self.read_guard(Call::Read) {
+            Some(guard) => guard,
+            None => unreachable!(),
+        }
+    }
+
+    #[track_caller]
+    fn try_read(&self) -> Option<Self::ReadGuard<'_>> {
+        self.read_guard(Call::TryRead)
+    }
+
+    #[track_caller]
+    fn write(&self) -> Self::WriteGuard<'_> {
+        match self.write_guard(Call::Write) {
+            Some(guard) => guard,
+            None => unreachable!(),
+        }
+    }
+
+    #[track_caller]
+    fn try_write(&self) -> Option<Self::WriteGuard<'_>> {
+        self.write_guard(Call::TryWrite)
+    }
+}
@@ -1,170 +1,977 @@
-use std::cell::SyncUnsafeCell;
-use std::cell::UnsafeCell;
-use std::ops::Deref;
-use std::ops::DerefMut;
-use std::sync::atomic::AtomicBool;
-use std::sync::atomic::AtomicUsize;
-use std::sync::atomic::Ordering;
+#[cfg(feature = "alloc")]
+use alloc::sync::Arc;
+use core::cell::UnsafeCell;
+#[cfg(not(feature = "tiny"))]
+use core::fmt;
+use core::marker::PhantomData;
+use core::mem::MaybeUninit;
+use core::ops::Deref;
+use core::ops::DerefMut;
+#[cfg(feature = "track-location")]
+use core::panic::Location;
+use core::panic::RefUnwindSafe;
+use core::panic::UnwindSafe;
+#[cfg(feature = "track-location")]
+use core::ptr;
 
-static RAW_ATOMICS_ENABLED: SyncUnsafeCell<bool> = SyncUnsafeCell::new(false);
+#[cfg(feature = "panic-hook")]
+pub use crate::held::install_panic_hook;
+// Kept at their old paths after moving to their own modules.
+#[doc(no_inline)]
+pub use crate::raw::enable_raw_atomics;
+#[doc(no_inline)]
+pub use crate::raw::raw_atomics_enabled;
+#[doc(no_inline)]
+pub use crate::rwlock::ReadWriteLock;
+#[doc(no_inline)]
+pub use crate::rwlock::RwSpinLock;
+#[doc(no_inline)]
+pub use crate::rwlock::RwSpinLockReadGuard;
+#[doc(no_inline)]
+pub use crate::rwlock::RwSpinLockWriteGuard;
+#[cfg(feature = "fairness")]
+#[doc(no_inline)]
+pub use crate::stats::FAIRNESS_SLOTS;
+#[cfg(feature = "stats")]
+#[doc(no_inline)]
+pub use crate::stats::LockStats;
+#[cfg(feature = "alloc")]
+#[doc(no_inline)]
+pub use crate::sync::BiLockHalf;
+#[cfg(feature = "alloc")]
+#[doc(no_inline)]
+pub use crate::sync::ReuniteError;
+#[cfg(feature = "zeroize")]
+#[doc(no_inline)]
+pub use crate::sync::ZeroizingSpinLock;
 
-#[inline]
-pub fn raw_atomics_enabled() -> bool {
-    // SAFETY: `RAW_ATOMICS_ENABLED` is only written during single-std bring-up,
-    // and reads are allowed as a simple flag check after that point.
-    unsafe { *RAW_ATOMICS_ENABLED.get() }
-}
-
-/// Enables raw atomic operations globally for this crate.
-///
-/// # Invariants
-/// - Call only after paging/caches/memory attributes are enabled. If called too early,
-///   subsequent lock/atomic operations will execute atomic RMW instructions while the
-///   platform still forbids them, which can trap or lead to unpredictable memory behavior.
-/// - Call before secondary stds start and before any concurrent lock usage. If called
-///   concurrently with readers, some operations may remain non-atomic while others become
-///   atomic, leading to data races, lost updates, or aliasing UB.
-/// - This function is intentionally unsynchronized and must only run during single-std
-///   bring-up to avoid races with `raw_atomics_enabled()` readers.
-#[inline]
-pub fn enable_raw_atomics() {
-    // SAFETY: callers must uphold the bring-up sequencing and single-std invariants above.
-    unsafe {
-        *RAW_ATOMICS_ENABLED.get() = true;
-    }
-}
-
-#[inline]
-fn disable_raw_atomics() {
-    // SAFETY: used only in tests to restore state.
-    unsafe {
-        *RAW_ATOMICS_ENABLED.get() = false;
-    }
-}
-
-#[inline(always)]
-fn lock_atomic(locked: &AtomicBool) {
-    while locked
-        .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
-        .is_err()
-    {
-        std::hint::spin_loop();
-    }
-}
-
-#[inline(always)]
-fn unlock_atomic(locked: &AtomicBool) {
-    locked.store(false, Ordering::Release);
-}
+#[cfg(any(feature = "checked-guards", feature = "validate-placement"))]
+use crate::atomic::AtomicBool;
+#[cfg(feature = "track-location")]
+use crate::atomic::AtomicPtr;
+use crate::atomic::AtomicU8;
+#[cfg(any(
+    feature = "audit",
+    feature = "no-lock-check",
+    feature = "owner-tracking",
+    feature = "waiter-count",
+    feature = "watchdog"
+))]
+use crate::atomic::AtomicUsize;
+use crate::atomic::Ordering;
+use crate::builder::SpinLockBuilder;
+use crate::error::GateDisabled;
+use crate::error::TryLockError;
+#[cfg(feature = "stats")]
+use crate::padded::CachePadded;
+use crate::preempt;
+use crate::project::MappedRawSpinLockGuard;
+use crate::project::SubLock;
+use crate::raw::AtomicsEnabled;
+use crate::raw::LOCKED;
+#[cfg(feature = "registry")]
+use crate::raw::PARKED;
+use crate::raw::UNLOCKED;
+use crate::raw::lock_atomic;
+#[cfg(feature = "track-location")]
+use crate::raw::record_location;
+use crate::raw::try_lock_atomic;
+use crate::raw::unlock_atomic;
+use crate::relax::Backoff;
+#[cfg(feature = "stats")]
+use crate::stats::Counters;
+#[cfg(feature = "alloc")]
+use crate::sync::MappedSpinLockGuard;
 
-const WRITE_FLAG: usize = 1 << (usize::BITS - 1);
+/// Set in `RawSpinLock::bypass` once `no_lock` has been called; the other
+/// bits, but for `HELD_FROM_NEW`, count live `no_lock` guards.
+#[cfg(feature = "no-lock-check")]
+const NO_LOCK_USED: usize = 1 << (usize::BITS - 1);
+/// Set in `RawSpinLock::bypass` while the hold a lock was created with is
+/// still in place, which `no_lock` guards do not overlap with.
+#[cfg(feature = "no-lock-check")]
+const HELD_FROM_NEW: usize = 1 << (usize::BITS - 2);
+/// The flag bits of `RawSpinLock::bypass`.
+#[cfg(feature = "no-lock-check")]
+const BYPASS_FLAGS: usize = NO_LOCK_USED | HELD_FROM_NEW;
 
-#[inline(always)]
-fn rw_read_lock_atomic(state: &AtomicUsize) {
-    loop {
-        let current_state = state.load(Ordering::Relaxed);
-        if current_state & WRITE_FLAG != 0 {
-            std::hint::spin_loop();
-            continue;
-        }
+/// A mutual exclusion primitive from this crate.
+///
+/// Code generic over `L: Lock<T>` can swap one lock type for another without
+/// touching call sites. The guard is a generic associated type, so the trait
+/// is not object safe; use it as a bound rather than as `dyn Lock<T>`.
+pub trait Lock<T: ?Sized> {
+    type Guard<'a>: DerefMut<Target = T>
+    where
+        Self: 'a;
 
-        let next_state = match current_state.checked_add(1) {
-            Some(next) => next,
-            None => {
-                std::hint::spin_loop();
-                continue;
-            }
-        };
+    fn lock(&self) -> Self::Guard<'_>;
 
-        if next_state & WRITE_FLAG != 0 {
-            std::hint::spin_loop();
-            continue;
-        }
+    fn try_lock(&self) -> Option<Self::Guard<'_>>;
 
-        if state
-            .compare_exchange_weak(
-                current_state,
-                next_state,
-                Ordering::Acquire,
-                Ordering::Relaxed,
-            )
-            .is_ok()
-        {
-            break;
-        }
-        std::hint::spin_loop();
+    /// Like [`try_lock`](Self::try_lock), but says why it failed.
+    #[track_caller]
+    fn try_lock_result(&self) -> Result<Self::Guard<'_>, TryLockError> {
+        self.try_lock().ok_or(TryLockError::WouldBlock)
     }
 }
 
-#[inline(always)]
-fn rw_read_unlock_atomic(state: &AtomicUsize) {
-    state.fetch_sub(1, Ordering::Release);
+/// A spinlock whose acquisition is gated on [`raw_atomics_enabled`].
+///
+/// Before raw atomics are enabled a guard does not exclude anybody, but it
+/// still marks the lock word held and clears it again, with plain stores, so
+/// registry dumps and `is_locked` see locks taken during bring-up. A guard
+/// that outlives [`enable_raw_atomics`] keeps the lock held until it is
+/// dropped: acquisitions after enabling wait for it, and on the core
+/// holding it they spin forever.
+///
+/// The layout is `#[repr(C)]`: the lock word comes first, followed by the
+/// data, so a lock can be placed in memory shared with another process or
+/// with firmware as long as both sides agree on the layout of `T`. See
+/// [`init_in_place`](Self::init_in_place) and [`from_raw`](Self::from_raw).
+/// With the debugging features (`audit`, `checked-guards`,
+/// `lock-ordering`, `lockdep`, `no-lock-check`, `owner-tracking`,
+/// `registry`, `stats`, `track-location`, `validate-placement`,
+/// `waiter-count`, `watchdog`),
+/// extra fields sit between
+/// the two, so both sides must agree on those features as well.
+///
+/// Like `std::sync::Mutex`, the lock is `UnwindSafe` and `RefUnwindSafe`
+/// whatever `T` is, since the lock is where the unwind boundary sits. There
+/// is no poisoning, though: if a thread panics while holding a guard, the
+/// next holder sees the data as it was left, so invariants that a panic can
+/// break have to be restored or checked by the caller. With the
+/// `panic-policy` feature a handler can halt instead; see the
+/// `panic_policy` module.
+#[repr(C)]
+pub struct RawSpinLock<T: ?Sized> {
+    locked: AtomicU8,
+    /// Id of the current holder, or 0 if unknown or unlocked.
+    #[cfg(feature = "owner-tracking")]
+    owner: AtomicUsize,
+    /// Where the lock was last acquired, or null.
+    #[cfg(feature = "track-location")]
+    last_acquired_at: AtomicPtr<Location<'static>>,
+    /// On its own line, so bumping the counters does not touch the line
+    /// waiters spin on.
+    #[cfg(feature = "stats")]
+    stats: CachePadded<Counters>,
+    /// Per-lock watchdog threshold, or `watchdog::INHERIT`.
+    #[cfg(feature = "watchdog")]
+    spin_threshold: AtomicUsize,
+    /// Contexts in the contended path.
+    #[cfg(feature = "waiter-count")]
+    waiters: AtomicUsize,
+    /// Live `no_lock` guards, plus `NO_LOCK_USED`.
+    #[cfg(feature = "no-lock-check")]
+    bypass: AtomicUsize,
+    /// Whether a guard handed out before raw atomics were enabled is alive.
+    #[cfg(feature = "checked-guards")]
+    borrowed: AtomicBool,
+    /// Position in the lock order, or 0 if unordered.
+    #[cfg(feature = "lock-ordering")]
+    level: u32,
+    #[cfg(feature = "lockdep")]
+    class: Option<&'static crate::lockdep::LockClass>,
+    #[cfg(feature = "registry")]
+    node: crate::registry::Node,
+    /// Acquisitions before raw atomics were enabled.
+    #[cfg(feature = "audit")]
+    pre_atomic: AtomicUsize,
+    /// Whether the `placement` hook has approved the lock's address.
+    #[cfg(feature = "validate-placement")]
+    placed: AtomicBool,
+    data: UnsafeCell<T>,
 }
 
-#[inline(always)]
-fn rw_write_lock_atomic(state: &AtomicUsize) {
-    loop {
-        let current_state = state.load(Ordering::Relaxed);
-        if current_state & WRITE_FLAG != 0 {
-            std::hint::spin_loop();
-            continue;
-        }
+// Without the debugging features the lock adds a single byte to the data.
+#[cfg(not(any(
+    loom,
+    shuttle,
+    feature = "audit",
+    feature = "checked-guards",
+    feature = "lock-ordering",
+    feature = "lockdep",
+    feature = "no-lock-check",
+    feature = "owner-tracking",
+    feature = "registry",
+    feature = "stats",
+    feature = "track-location",
+    feature = "validate-placement",
+    feature = "waiter-count",
+    feature = "watchdog"
+)))]
+const _: () = assert!(size_of::<RawSpinLock<()>>() == 1);
 
-        if state
-            .compare_exchange_weak(
-                current_state,
-                current_state | WRITE_FLAG,
-                Ordering::Acquire,
-                Ordering::Relaxed,
-            )
-            .is_ok()
-        {
-            while state.load(Ordering::Relaxed) & !WRITE_FLAG != 0 {
-                std::hint::spin_loop();
-            }
-            break;
-        }
-        std::hint::spin_loop();
-    }
+/// How a guard came to be, and so whether it excludes anybody.
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(not(feature = "tiny"), derive(Debug))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum GuardKind {
+    /// Acquired with atomics, so it holds the lock.
+    Atomic,
+    /// Handed out before raw atomics were enabled; it excludes nobody.
+    PreAtomic,
+    /// Issued by [`RawSpinLock::no_lock`], which skips the lock entirely.
+    Bypass,
 }
 
-#[inline(always)]
-fn rw_write_unlock_atomic(state: &AtomicUsize) {
-    state.fetch_and(!WRITE_FLAG, Ordering::Release);
+/// Holds a [`RawSpinLock`] until dropped.
+///
+/// With debug assertions, dropping the guard in a different context than
+/// the one that acquired it, as told apart by [`owner::current`], panics
+/// naming both and where the lock was acquired. Moving a guard into another
+/// thread is almost always a mistake; deliberate handoffs opt out with
+/// [`allow_foreign_unlock`](Self::allow_foreign_unlock).
+///
+/// With the `checked-guards` feature, a guard handed out before raw atomics
+/// are enabled marks its lock as borrowed until it is dropped, much like a
+/// `RefCell`: locking the lock again meanwhile panics instead of aliasing
+/// the data mutably, and `try_lock` fails.
+///
+/// [`owner::current`]: crate::owner::current
+pub struct RawSpinLockGuard<'a, T> {
+    lock: &'a RawSpinLock<T>,
+    unlock_on_drop: bool,
+    /// Marked the lock word held with a plain store before raw atomics
+    /// were enabled, and clears it on drop.
+    recorded: bool,
+    /// The acquiring context and location, checked against the releasing
+    /// context.
+    #[cfg(debug_assertions)]
+    acquirer: Option<(
+        crate::owner::OwnerId,
+        &'static core::panic::Location<'static>,
+    )>,
+    #[cfg(all(feature = "hle", target_arch = "x86_64", target_feature = "rtm"))]
+    elided: bool,
+    /// When the lock was acquired, if a clock is registered.
+    #[cfg(feature = "hold-time")]
+    acquired_at: Option<u64>,
+    /// Entered while the guard is alive; closed when it is dropped.
+    #[cfg(feature = "tracing")]
+    span: tracing::Span,
+    /// Recorded as held for lock ordering.
+    #[cfg(feature = "lock-ordering")]
+    ordered: bool,
+    /// Recorded as held by `lockdep`.
+    #[cfg(feature = "lockdep")]
+    lockdep: bool,
+    /// Recorded in the thread's held-lock list.
+    #[cfg(feature = "panic-hook")]
+    held: bool,
+    /// Reported to the metrics sink once the lock is released.
+    #[cfg(feature = "metrics")]
+    metrics: Option<crate::metrics::Pending>,
+    /// Issued by `no_lock`. With `no-lock-check`, also counted in
+    /// `RawSpinLock::bypass`.
+    bypass: bool,
+    /// Marked in `RawSpinLock::borrowed`.
+    #[cfg(feature = "checked-guards")]
+    borrowed: bool,
 }
 
-pub struct RawSpinLock<T: ?Sized> {
-    locked: AtomicBool,
-    data: UnsafeCell<T>,
+/// A read-only guard from [`RawSpinLock::no_lock_read`], which does not
+/// hold the lock.
+pub struct RawSpinLockReadOnlyGuard<'a, T> {
+    lock: &'a RawSpinLock<T>,
+    /// Shares `T` with any other readers, so moving the guard needs
+    /// `T: Sync`.
+    _marker: PhantomData<&'a T>,
 }
 
-pub struct RawSpinLockGuard<'a, T> {
+/// A guard from [`RawSpinLock::lock_for_handoff`], meant to be released
+/// on another core or thread.
+///
+/// It only holds the lock word: nothing about it is tied to the context
+/// that took it, so it is `Send` whenever `T` is, and dropping it or calling
+/// [`complete`](Self::complete) anywhere releases the lock.
+#[must_use = "dropping the guard releases the lock immediately"]
+pub struct HandoffGuard<'a, T> {
     lock: &'a RawSpinLock<T>,
-    unlock_on_drop: bool,
+    /// Took the lock word atomically, and releases it on drop.
+    holds_lock: bool,
+    /// Marked the lock word held with a plain store before raw atomics
+    /// were enabled, and clears it on drop.
+    recorded: bool,
+    /// Hands out `&mut T` wherever it goes, so moving the guard needs
+    /// `T: Send` and sharing it `T: Sync`.
+    _marker: PhantomData<&'a mut T>,
 }
 
 unsafe impl<T: ?Sized + Send> Send for RawSpinLock<T> {}
 unsafe impl<T: ?Sized + Send> Sync for RawSpinLock<T> {}
 
+impl<T: ?Sized> UnwindSafe for RawSpinLock<T> {}
+impl<T: ?Sized> RefUnwindSafe for RawSpinLock<T> {}
+// The tracing span and metrics a guard may carry are only diagnostics.
+impl<T> UnwindSafe for RawSpinLockGuard<'_, T> {}
+impl<T> RefUnwindSafe for RawSpinLockGuard<'_, T> {}
+
+/// Fails to compile unless `T` can cross `catch_unwind`, by value or by
+/// reference.
+pub(crate) const fn assert_unwind_safe<T: ?Sized + UnwindSafe + RefUnwindSafe>() {}
+
+// Even for data that is neither, such as `Cell` and `&mut`.
+const _: () = {
+    assert_unwind_safe::<RawSpinLock<core::cell::Cell<u8>>>();
+    assert_unwind_safe::<RawSpinLockGuard<'_, &mut u8>>();
+    assert_unwind_safe::<RwSpinLock<core::cell::Cell<u8>>>();
+    assert_unwind_safe::<RwSpinLockReadGuard<'_, &mut u8>>();
+    assert_unwind_safe::<RwSpinLockWriteGuard<'_, &mut u8>>();
+};
+
 impl<T> RawSpinLock<T> {
     pub const fn new(data: T) -> Self {
+        Self::with_level(data, 0)
+    }
+
+    /// Creates a lock that is already held, by nobody in particular.
+    ///
+    /// This is for boot handoffs: the boot core creates the lock held,
+    /// secondary cores wait in [`lock`](Self::lock), and once setup is done
+    /// the boot core lets exactly one of them in with
+    /// [`force_unlock`](Self::force_unlock), from code that never had a
+    /// guard. From then on the lock behaves like any other. In a `static`,
+    /// the boot core fills in the data through its own `lock()` guards
+    /// before raw atomics are enabled, or with
+    /// [`no_lock`](Self::no_lock) afterwards. With `no-lock-check`, that
+    /// initial hold is nobody's, so neither `no_lock` nor lockers already
+    /// waiting count as an overlap with it; `force_unlock` panics instead if
+    /// a `no_lock` guard is still alive when it ends the hold.
+    ///
+    /// The lock word starts out held, so `is_locked` reports it held right
+    /// away. Guards handed out before raw atomics are enabled leave the word
+    /// as it is, so the lock is still held once they are, and only then do
+    /// `lock` and `try_lock` wait for it or fail.
+    pub const fn new_locked(data: T) -> Self {
+        Self::new(data).initially_locked()
+    }
+
+    /// Marks a lock that nothing has used yet as held, for `new_locked` and
+    /// [`SpinLockBuilder::locked`].
+    pub(crate) const fn initially_locked(mut self) -> Self {
+        self.locked = AtomicU8::new(LOCKED);
+        #[cfg(feature = "no-lock-check")]
+        {
+            self.bypass = AtomicUsize::new(HELD_FROM_NEW);
+        }
+        self
+    }
+
+    /// Sets the spin threshold of a lock that nothing has used yet, for
+    /// [`SpinLockBuilder::spin_threshold`].
+    #[cfg(feature = "watchdog")]
+    pub(crate) const fn with_spin_threshold(mut self, spins: Option<usize>) -> Self {
+        self.spin_threshold = AtomicUsize::new(crate::watchdog::per_lock(spins));
+        self
+    }
+
+    /// Returns a [`SpinLockBuilder`] for a lock with more than one option
+    /// set. Building without setting any gives the same lock as
+    /// [`new`](Self::new).
+    pub const fn builder() -> SpinLockBuilder<T> {
+        SpinLockBuilder::new()
+    }
+
+    /// Creates `N` locks holding copies of `data`, for tables of locks in a
+    /// `static`. For data that is not `Copy`, use
+    /// `[const { RawSpinLock::new(data) }; N]` with an inline constant, or
+    /// [`spin_static!`](crate::spin_static).
+    pub const fn new_array<const N: usize>(data: T) -> [Self; N]
+    where
+        T: Copy,
+    {
+        let mut array = MaybeUninit::<[Self; N]>::uninit();
+        let first = array.as_mut_ptr().cast::<Self>();
+        let mut i = 0;
+        while i < N {
+            // SAFETY: `i < N`, so this is within the array.
+            unsafe { first.add(i).write(Self::new(data)) };
+            i += 1;
+        }
+        // SAFETY: every element was written above.
+        unsafe { array.assume_init() }
+    }
+
+    /// Creates a lock at `level` in the lock order; level 0 is unordered.
+    ///
+    /// The level is only checked with the `lock-ordering` feature, see the
+    /// `ordering` module; otherwise this is the same as [`new`](Self::new).
+    #[cfg_attr(not(feature = "lock-ordering"), allow(unused_variables))]
+    pub const fn with_level(data: T, level: u32) -> Self {
         Self {
-            locked: AtomicBool::new(false),
+            locked: AtomicU8::new(UNLOCKED),
+            #[cfg(feature = "owner-tracking")]
+            owner: AtomicUsize::new(0),
+            #[cfg(feature = "track-location")]
+            last_acquired_at: AtomicPtr::new(ptr::null_mut()),
+            #[cfg(feature = "stats")]
+            stats: CachePadded::new(Counters::new()),
+            #[cfg(feature = "watchdog")]
+            spin_threshold: AtomicUsize::new(crate::watchdog::INHERIT),
+            #[cfg(feature = "waiter-count")]
+            waiters: AtomicUsize::new(0),
+            #[cfg(feature = "no-lock-check")]
+            bypass: AtomicUsize::new(0),
+            #[cfg(feature = "checked-guards")]
+            borrowed: AtomicBool::new(false),
+            #[cfg(feature = "lock-ordering")]
+            level,
+            #[cfg(feature = "lockdep")]
+            class: None,
+            #[cfg(feature = "registry")]
+            node: crate::registry::Node::unlisted(Self::snapshot),
+            #[cfg(feature = "audit")]
+            pre_atomic: AtomicUsize::new(0),
+            #[cfg(feature = "validate-placement")]
+            placed: AtomicBool::new(false),
             data: UnsafeCell::new(data),
         }
     }
 
+    /// Places the lock in `class`, whose ordering the `lockdep` feature
+    /// learns; see the [`lockdep`](crate::lockdep) module. Usually called
+    /// with [`lock_class!`](crate::lock_class).
+    #[cfg_attr(not(feature = "lockdep"), allow(unused_variables, unused_mut))]
+    pub const fn in_class(mut self, class: &'static crate::lockdep::LockClass) -> Self {
+        #[cfg(feature = "lockdep")]
+        {
+            self.class = Some(class);
+        }
+        self
+    }
+
+    /// Where the lock goes when two locks are taken together: by level, then
+    /// by address.
+    fn order_key(&self) -> (u32, usize) {
+        #[cfg(feature = "lock-ordering")]
+        let level = self.level;
+        #[cfg(not(feature = "lock-ordering"))]
+        let level = 0;
+        (level, core::ptr::from_ref(self).addr())
+    }
+
+    /// The registry name, or else the lockdep class name, if any.
+    #[cfg(any(feature = "panic-hook", feature = "panic-policy", feature = "usdt"))]
+    fn name(&self) -> Option<&'static str> {
+        #[cfg(feature = "registry")]
+        if !self.node.name().is_empty() {
+            return Some(self.node.name());
+        }
+        #[cfg(feature = "lockdep")]
+        if let Some(class) = self.class {
+            return Some(class.name());
+        }
+        None
+    }
+
+    /// Creates a lock that the `registry` module lists under `name` once it
+    /// has been locked.
+    ///
+    /// Without the `registry` feature this is the same as
+    /// [`new`](Self::new). Prefer [`registered_static!`](crate::registered_static),
+    /// which declares the lock as a `static`.
+    ///
+    /// # Safety
+    ///
+    /// The lock must not be moved or dropped after it is first locked, since
+    /// the registry keeps pointing at it.
+    pub const unsafe fn registered(name: &'static str, data: T) -> Self {
+        // SAFETY: guaranteed by the caller.
+        unsafe { Self::new(data).named(name) }
+    }
+
+    /// Names a lock that nothing has used yet in the registry, for
+    /// `registered` and [`SpinLockBuilder::build_static`].
+    ///
+    /// # Safety
+    ///
+    /// As for [`registered`](Self::registered).
+    #[cfg_attr(not(feature = "registry"), allow(unused_variables, unused_mut))]
+    pub(crate) const unsafe fn named(mut self, name: &'static str) -> Self {
+        #[cfg(feature = "registry")]
+        {
+            self.node = crate::registry::Node::pending(name, size_of::<Self>(), Self::snapshot);
+        }
+        self
+    }
+
+    /// # Safety
+    ///
+    /// `lock` must point to a live `RawSpinLock<T>`.
+    #[cfg(feature = "registry")]
+    unsafe fn snapshot(lock: *const ()) -> crate::registry::Snapshot {
+        // SAFETY: guaranteed by the caller.
+        let lock = unsafe { &*lock.cast::<Self>() };
+        let state = lock.locked.load(Ordering::Relaxed);
+        crate::registry::Snapshot {
+            held: state != UNLOCKED,
+            parked: state == PARKED,
+            #[cfg(feature = "owner-tracking")]
+            owner: lock.owner(),
+            #[cfg(feature = "track-location")]
+            location: lock.last_acquired_at(),
+            #[cfg(feature = "stats")]
+            stats: lock.stats(),
+            #[cfg(feature = "audit")]
+            pre_atomic: lock.pre_atomic.load(Ordering::Relaxed),
+        }
+    }
+
+    #[cfg(feature = "registry")]
+    #[inline(always)]
+    fn register(&self) {
+        // SAFETY: only locks from `registered` are pending, and its caller
+        // promised the lock stays put.
+        unsafe { self.node.register(core::ptr::from_ref(self).cast()) };
+    }
+
+    #[track_caller]
     pub fn lock(&self) -> RawSpinLockGuard<'_, T> {
-        let unlock_on_drop = raw_atomics_enabled();
-        if unlock_on_drop {
-            lock_atomic(&self.locked);
+        match AtomicsEnabled::get() {
+            Some(enabled) => self.lock_with(enabled),
+            None => self.lock_permissive(),
+        }
+    }
+
+    /// Like [`lock`](Self::lock), for callers that hold the
+    /// [`AtomicsEnabled`] token: the gate is not checked, and the guard
+    /// always holds the lock.
+    #[track_caller]
+    pub fn lock_with(&self, _: AtomicsEnabled) -> RawSpinLockGuard<'_, T> {
+        self.before_lock();
+        #[cfg(feature = "validate-placement")]
+        self.validate_placement();
+        #[cfg(feature = "no-lock-check")]
+        self.check_no_bypass();
+        #[cfg(feature = "lockdep")]
+        if let Some(class) = self.class {
+            crate::lockdep::check(class);
+        }
+        #[cfg(feature = "owner-tracking")]
+        let me = crate::owner::current();
+        #[cfg(feature = "owner-tracking")]
+        if me.is_some() && self.owner() == me {
+            self.recursive_lock();
+        }
+        #[cfg(all(feature = "hle", target_arch = "x86_64", target_feature = "rtm"))]
+        if crate::elision::try_elide(&self.locked) {
+            preempt::disable();
+            let mut guard = self.guard(false);
+            guard.elided = true;
+            return guard;
+        }
+        #[cfg(feature = "usdt")]
+        let site = crate::usdt::Site::here(self.name().unwrap_or(""));
+        let contention = lock_atomic(
+            &self.locked,
+            self.data.get().cast(),
+            #[cfg(feature = "owner-tracking")]
+            &self.owner,
+            #[cfg(feature = "waiter-count")]
+            &self.waiters,
+            #[cfg(feature = "watchdog")]
+            self.watched(),
+            #[cfg(feature = "usdt")]
+            site,
+        );
+        // First, so waiters asking whether the owner runs see it as
+        // early as possible.
+        #[cfg(feature = "owner-tracking")]
+        self.set_owner(me);
+        #[cfg(feature = "usdt")]
+        crate::usdt::acquired(self.locked.as_ptr() as usize, site, contention.unwrap_or(0));
+        #[cfg(feature = "tracing")]
+        if let Some(spins) = contention {
+            tracing::trace!(
+                lock = ?self.locked.as_ptr(),
+                location = %core::panic::Location::caller(),
+                spins,
+                "acquired contended lock"
+            );
+        }
+        #[cfg(feature = "deadlock-detection")]
+        crate::deadlock::acquired(self.locked.as_ptr() as usize);
+        #[cfg(feature = "track-location")]
+        record_location(&self.last_acquired_at);
+        #[cfg(feature = "stats")]
+        self.stats.record(contention);
+        preempt::disable();
+        self.acquired_guard(true, contention)
+    }
+
+    /// Locks for a release in another context, such as a request submitted
+    /// on one core and completed on another.
+    ///
+    /// The [`HandoffGuard`] can be sent anywhere and released there. Since
+    /// the acquiring context may be long gone by then, nothing is recorded
+    /// against it: the preemption hook is not called, the lock records no
+    /// owner for `owner-tracking`, so recursion is not caught and waiters
+    /// never stop spinning early on the holder's behalf, and the
+    /// per-context bookkeeping of `lock-ordering`, `lockdep`,
+    /// `deadlock-detection` and `panic-hook` does not see it. The lock has
+    /// no poisoning and no fairness to change. Before raw atomics are
+    /// enabled it marks the lock word like [`lock`](Self::lock) and excludes
+    /// nobody.
+    #[track_caller]
+    pub fn lock_for_handoff(&self) -> HandoffGuard<'_, T> {
+        self.before_lock();
+        // The same gate check as `lock`.
+        let holds_lock = AtomicsEnabled::get().is_some();
+        if holds_lock {
+            #[cfg(feature = "validate-placement")]
+            self.validate_placement();
+            #[cfg(feature = "no-lock-check")]
+            self.check_no_bypass();
+            #[cfg(feature = "usdt")]
+            let site = crate::usdt::Site::here(self.name().unwrap_or(""));
+            let contention = lock_atomic(
+                &self.locked,
+                self.data.get().cast(),
+                #[cfg(feature = "owner-tracking")]
+                &self.owner,
+                #[cfg(feature = "waiter-count")]
+                &self.waiters,
+                #[cfg(feature = "watchdog")]
+                self.watched(),
+                #[cfg(feature = "usdt")]
+                site,
+            );
+            #[cfg(feature = "usdt")]
+            crate::usdt::acquired(self.locked.as_ptr() as usize, site, contention.unwrap_or(0));
+            #[cfg(feature = "track-location")]
+            record_location(&self.last_acquired_at);
+            #[cfg(feature = "stats")]
+            self.stats.record(contention);
+            #[cfg(not(any(feature = "stats", feature = "usdt")))]
+            let _ = contention;
+        }
+        #[cfg(feature = "audit")]
+        if !holds_lock {
+            crate::audit::count(&self.pre_atomic);
+        }
+        HandoffGuard {
+            lock: self,
+            holds_lock,
+            recorded: !holds_lock && self.record(),
+            _marker: PhantomData,
         }
+    }
+
+    /// The permissive-mode half of [`lock`](Self::lock).
+    #[track_caller]
+    #[inline(always)]
+    fn lock_permissive(&self) -> RawSpinLockGuard<'_, T> {
+        self.before_lock();
+        #[cfg(feature = "checked-guards")]
+        if !self.borrow() {
+            self.reentrant_borrow();
+        }
+        #[cfg(feature = "stats")]
+        self.stats.record(None);
+        self.acquired_guard(false, None)
+    }
+
+    /// Checks shared by both halves of [`lock`](Self::lock).
+    #[track_caller]
+    #[inline(always)]
+    fn before_lock(&self) {
+        #[cfg(feature = "registry")]
+        self.register();
+        #[cfg(feature = "lock-ordering")]
+        crate::ordering::check(self.level);
+    }
+
+    #[inline(always)]
+    fn guard(&self, unlock_on_drop: bool) -> RawSpinLockGuard<'_, T> {
         RawSpinLockGuard {
             lock: self,
             unlock_on_drop,
+            recorded: false,
+            #[cfg(debug_assertions)]
+            acquirer: None,
+            #[cfg(all(feature = "hle", target_arch = "x86_64", target_feature = "rtm"))]
+            elided: false,
+            #[cfg(feature = "hold-time")]
+            acquired_at: None,
+            #[cfg(feature = "tracing")]
+            span: tracing::Span::none(),
+            #[cfg(feature = "lock-ordering")]
+            ordered: false,
+            #[cfg(feature = "lockdep")]
+            lockdep: false,
+            #[cfg(feature = "panic-hook")]
+            held: false,
+            #[cfg(feature = "metrics")]
+            metrics: None,
+            bypass: false,
+            #[cfg(feature = "checked-guards")]
+            borrowed: false,
         }
     }
 
+    /// The guard for a `lock` or `try_lock`, whose hold time is measured,
+    /// traced and reported, and which counts as held for lock ordering.
+    /// `contention` is the number of spins if the lock was contended.
+    #[track_caller]
+    #[cfg_attr(not(feature = "metrics"), allow(unused_variables))]
+    #[inline(always)]
+    fn acquired_guard(
+        &self,
+        unlock_on_drop: bool,
+        contention: Option<usize>,
+    ) -> RawSpinLockGuard<'_, T> {
+        let mut guard = self.guard(unlock_on_drop);
+        guard.recorded = !unlock_on_drop && self.record();
+        #[cfg(feature = "audit")]
+        if !unlock_on_drop {
+            crate::audit::count(&self.pre_atomic);
+        }
+        #[cfg(debug_assertions)]
+        if unlock_on_drop {
+            let location = core::panic::Location::caller();
+            guard.acquirer = crate::owner::current().map(|me| (me, location));
+        }
+        #[cfg(feature = "checked-guards")]
+        {
+            guard.borrowed = !unlock_on_drop;
+        }
+        #[cfg(feature = "metrics")]
+        {
+            guard.metrics = crate::metrics::Pending::start(contention);
+        }
+        #[cfg(feature = "lock-ordering")]
+        {
+            guard.ordered = crate::ordering::acquire(self.locked.as_ptr() as usize, self.level);
+        }
+        #[cfg(feature = "lockdep")]
+        if let (true, Some(class)) = (unlock_on_drop, self.class) {
+            guard.lockdep = crate::lockdep::acquire(self.locked.as_ptr() as usize, class);
+        }
+        #[cfg(feature = "panic-hook")]
+        if unlock_on_drop {
+            guard.held = crate::held::acquire(self.locked.as_ptr() as usize, self.name());
+        }
+        #[cfg(feature = "hold-time")]
+        {
+            guard.acquired_at = crate::clock::now();
+        }
+        #[cfg(feature = "tracing")]
+        {
+            guard.span = tracing::trace_span!(
+                "lock held",
+                lock = ?self.locked.as_ptr(),
+                location = %core::panic::Location::caller()
+            );
+            // Entered through the dispatcher rather than `Span::entered`,
+            // which would make the guard `!Send`.
+            guard
+                .span
+                .with_subscriber(|(id, dispatch)| dispatch.enter(id));
+        }
+        guard
+    }
+
+    /// Asks the [`placement`](crate::placement) hook about the lock once,
+    /// before its first atomic acquisition.
+    #[cfg(feature = "validate-placement")]
+    #[track_caller]
+    #[inline(always)]
+    fn validate_placement(&self) {
+        if !self.placed.load(Ordering::Relaxed)
+            && crate::placement::check(self.locked.as_ptr() as usize)
+        {
+            self.placed.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// Marks a free lock word held for a permissive-mode guard, returning
+    /// whether it was free. Plain loads and stores, as permissive mode runs
+    /// on a single core; a word already marked belongs to an outer guard.
+    #[inline(always)]
+    fn record(&self) -> bool {
+        let free = self.locked.load(Ordering::Relaxed) == UNLOCKED;
+        if free {
+            self.locked.store(LOCKED, Ordering::Relaxed);
+        }
+        free
+    }
+
+    /// Clears a lock word marked by [`record`](Self::record): with a plain
+    /// store while the gate is still closed, and with a real release once
+    /// it is open, since other cores may be waiting on the word by then.
+    #[inline(always)]
+    fn clear_recorded(&self) {
+        if raw_atomics_enabled() {
+            unlock_atomic(&self.locked);
+        } else {
+            self.locked.store(UNLOCKED, Ordering::Relaxed);
+        }
+    }
+
+    /// Like [`lock`](Self::lock), but fails instead of handing out a guard
+    /// that excludes nobody before raw atomics are enabled, for code that
+    /// must never run in permissive mode.
+    #[track_caller]
+    pub fn lock_checked(&self) -> Result<RawSpinLockGuard<'_, T>, GateDisabled> {
+        if !raw_atomics_enabled() {
+            return Err(GateDisabled::new());
+        }
+        Ok(self.lock())
+    }
+
+    /// Like [`try_lock_result`](Self::try_lock_result), but fails with
+    /// [`TryLockError::GateDisabled`] before raw atomics are enabled.
+    #[track_caller]
+    pub fn try_lock_checked(&self) -> Result<RawSpinLockGuard<'_, T>, TryLockError> {
+        if !raw_atomics_enabled() {
+            return Err(GateDisabled::new().into());
+        }
+        self.try_lock_result()
+    }
+
+    /// Attempts to acquire the lock without spinning.
+    ///
+    /// Returns `None` if the lock is currently held. Before raw atomics are
+    /// enabled this always succeeds, mirroring [`lock`](Self::lock), except
+    /// with the `checked-guards` feature while another guard is alive.
+    ///
+    /// Async-signal-safe: see [`signal`](crate::signal). That does not hold
+    /// with the `tracing` feature, since subscribers run inline.
+    #[track_caller]
+    pub fn try_lock(&self) -> Option<RawSpinLockGuard<'_, T>> {
+        self.try_lock_result().ok()
+    }
+
+    /// Like [`try_lock`](Self::try_lock), but returns
+    /// [`TryLockError::WouldBlock`] where that returns `None`.
+    #[track_caller]
+    pub fn try_lock_result(&self) -> Result<RawSpinLockGuard<'_, T>, TryLockError> {
+        #[cfg(feature = "registry")]
+        self.register();
+        #[cfg(feature = "fault-injection")]
+        if crate::fault::inject() {
+            return Err(TryLockError::WouldBlock);
+        }
+        let unlock_on_drop = raw_atomics_enabled();
+        if unlock_on_drop {
+            #[cfg(feature = "validate-placement")]
+            self.validate_placement();
+            #[cfg(feature = "no-lock-check")]
+            self.check_no_bypass();
+            if !try_lock_atomic(&self.locked) {
+                #[cfg(feature = "tracing")]
+                tracing::trace!(
+                    lock = ?self.locked.as_ptr(),
+                    location = %core::panic::Location::caller(),
+                    "try_lock failed"
+                );
+                return Err(TryLockError::WouldBlock);
+            }
+            #[cfg(feature = "usdt")]
+            crate::usdt::acquired(
+                self.locked.as_ptr() as usize,
+                crate::usdt::Site::here(self.name().unwrap_or("")),
+                0,
+            );
+            #[cfg(feature = "deadlock-detection")]
+            crate::deadlock::acquired(self.locked.as_ptr() as usize);
+            #[cfg(feature = "owner-tracking")]
+            self.set_owner(crate::owner::current());
+            #[cfg(feature = "track-location")]
+            record_location(&self.last_acquired_at);
+            preempt::disable();
+        }
+        #[cfg(feature = "checked-guards")]
+        if !unlock_on_drop && !self.borrow() {
+            return Err(TryLockError::WouldBlock);
+        }
+        #[cfg(feature = "stats")]
+        self.stats.record(None);
+        Ok(self.acquired_guard(unlock_on_drop, None))
+    }
+
+    /// Locks once the data satisfies `condition`, and returns the guard
+    /// with it still holding.
+    ///
+    /// Each attempt takes the lock and checks `condition`; if it fails, the
+    /// lock is released and the caller backs off before the next attempt,
+    /// so whoever is to make the condition true can get in. This polls: to
+    /// sleep until a waker signals instead, use a
+    /// [`WaitQueue`](crate::wait::WaitQueue). Before raw atomics are enabled
+    /// only an interrupt handler can change the data, so a condition that
+    /// waits for another core never comes true.
+    #[track_caller]
+    pub fn lock_when(&self, mut condition: impl FnMut(&T) -> bool) -> RawSpinLockGuard<'_, T> {
+        let mut backoff = Backoff::new();
+        loop {
+            let guard = self.lock();
+            if condition(&guard) {
+                return guard;
+            }
+            drop(guard);
+            backoff.spin();
+        }
+    }
+
+    /// Like [`lock_when`](Self::lock_when), but makes one attempt, through
+    /// [`try_lock`](Self::try_lock), and returns `None` if the lock is held
+    /// or `condition` fails.
+    #[track_caller]
+    pub fn try_lock_when(
+        &self,
+        condition: impl FnOnce(&T) -> bool,
+    ) -> Option<RawSpinLockGuard<'_, T>> {
+        self.try_lock().filter(|guard| condition(guard))
+    }
+
+    /// Like [`lock_when`](Self::lock_when), but makes at most `attempts`
+    /// attempts, each through [`try_lock`](Self::try_lock), and then fails
+    /// with [`TryLockError::TimedOut`].
+    #[track_caller]
+    pub fn lock_when_for(
+        &self,
+        attempts: usize,
+        mut condition: impl FnMut(&T) -> bool,
+    ) -> Result<RawSpinLockGuard<'_, T>, TryLockError> {
+        let mut backoff = Backoff::new();
+        for _ in 0..attempts {
+            if let Some(guard) = self.try_lock_when(&mut condition) {
+                return Ok(guard);
+            }
+            backoff.spin();
+        }
+        Err(TryLockError::TimedOut { spins: attempts })
+    }
+
+    /// Consumes the lock and returns the protected value.
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+
+    /// Returns a mutable reference to the data; no locking is needed since
+    /// the borrow is exclusive.
+    pub fn get_mut(&mut self) -> &mut T {
+        self.data.get_mut()
+    }
+
+    /// A lasting handle to the part of the data `project` picks, such as a
+    /// field, for code that should reach nothing else. It takes this lock
+    /// for every acquisition; see [`SubLock`].
+    pub fn project<U: ?Sized>(&self, project: fn(&mut T) -> &mut U) -> SubLock<'_, T, U> {
+        SubLock::new(self, project)
+    }
+
+    /// Splits the lock into two owned halves sharing the same data.
+    ///
+    /// Each half can be sent to a different thread and locked independently;
+    /// see [`BiLockHalf`] for how to get the value back out.
+    #[cfg(feature = "alloc")]
+    pub fn split_arc(self) -> (BiLockHalf<T>, BiLockHalf<T>) {
+        BiLockHalf::pair(Arc::new(self))
+    }
+
     /// Returns a guard without acquiring the lock or modifying lock state.
     ///
     /// # Safety
@@ -173,26 +980,770 @@ impl<T> RawSpinLock<T> {
     /// value concurrently (including via [`lock`](Self::lock)), and that no
     /// other guard exists that could produce references to the same `T`.
     /// Breaking these requirements can cause data races or aliasing UB.
+    ///
+    /// With the `no-lock-check` feature, calling this while the lock is held,
+    /// or acquiring the lock while a guard from this is alive, panics. The
+    /// bookkeeping uses plain loads and stores, so this is best-effort: an
+    /// overlap that starts in the same instant on two cores can go unseen.
+    ///
+    /// With the `checked-guards` feature, calling this before raw atomics are
+    /// enabled while another guard for the lock is alive panics as well.
+    #[cfg_attr(
+        any(feature = "checked-guards", feature = "no-lock-check"),
+        track_caller
+    )]
     pub unsafe fn no_lock(&self) -> RawSpinLockGuard<'_, T> {
-        RawSpinLockGuard {
+        // Before the guard exists, whose drop would undo bookkeeping that
+        // was never done.
+        #[cfg(feature = "no-lock-check")]
+        if self.held_by_someone() {
+            self.no_lock_overlap(true);
+        }
+        #[cfg(feature = "checked-guards")]
+        let borrowed = !raw_atomics_enabled();
+        #[cfg(feature = "checked-guards")]
+        if borrowed && !self.borrow() {
+            self.reentrant_borrow();
+        }
+        let mut guard = self.guard(false);
+        guard.bypass = true;
+        #[cfg(feature = "checked-guards")]
+        {
+            guard.borrowed = borrowed;
+        }
+        #[cfg(feature = "no-lock-check")]
+        {
+            // Plain stores, which also work before raw atomics are enabled.
+            let bypass = self.bypass.load(Ordering::Relaxed);
+            self.bypass
+                .store((bypass + 1) | NO_LOCK_USED, Ordering::Relaxed);
+        }
+        guard
+    }
+
+    /// Returns a guard that only reads the data, without acquiring the lock
+    /// or modifying lock state.
+    ///
+    /// # Safety
+    ///
+    /// Nothing may write the protected value while the guard is alive: no
+    /// other CPU/thread may hold or acquire the lock, and no other guard may
+    /// exist except read-only ones. Those can be on other CPUs/threads only
+    /// if `T` is `Sync`.
+    ///
+    /// With the `no-lock-check` feature, this is checked like
+    /// [`no_lock`](Self::no_lock), and with `checked-guards`, calling it
+    /// before raw atomics are enabled while a mutable guard is alive panics.
+    #[cfg_attr(
+        any(feature = "checked-guards", feature = "no-lock-check"),
+        track_caller
+    )]
+    pub unsafe fn no_lock_read(&self) -> RawSpinLockReadOnlyGuard<'_, T> {
+        #[cfg(feature = "checked-guards")]
+        if !raw_atomics_enabled() && self.borrowed.load(Ordering::Relaxed) {
+            self.reentrant_borrow();
+        }
+        #[cfg(feature = "no-lock-check")]
+        {
+            if self.held_by_someone() {
+                self.no_lock_overlap(true);
+            }
+            let bypass = self.bypass.load(Ordering::Relaxed);
+            self.bypass
+                .store((bypass + 1) | NO_LOCK_USED, Ordering::Relaxed);
+        }
+        RawSpinLockReadOnlyGuard {
             lock: self,
-            unlock_on_drop: false,
+            _marker: PhantomData,
         }
     }
+
+    /// Marks the lock as borrowed by a guard from before raw atomics were
+    /// enabled. Returns `false` if it already was.
+    #[cfg(feature = "checked-guards")]
+    #[inline(always)]
+    fn borrow(&self) -> bool {
+        // Plain loads and stores, as permissive mode runs on a single core.
+        if self.borrowed.load(Ordering::Relaxed) {
+            return false;
+        }
+        self.borrowed.store(true, Ordering::Relaxed);
+        true
+    }
+
+    #[cfg(feature = "checked-guards")]
+    #[cold]
+    #[track_caller]
+    fn reentrant_borrow(&self) -> ! {
+        panic!(
+            "lock {:#x} acquired at {} while another guard for it is alive, before raw atomics are enabled",
+            self.locked.as_ptr() as usize,
+            core::panic::Location::caller()
+        );
+    }
+
+    /// Returns whether [`no_lock`](Self::no_lock) or
+    /// [`no_lock_read`](Self::no_lock_read) was ever called on this lock.
+    #[cfg(feature = "no-lock-check")]
+    pub fn no_lock_used(&self) -> bool {
+        self.bypass.load(Ordering::Relaxed) & NO_LOCK_USED != 0
+    }
+
+    #[cfg(feature = "no-lock-check")]
+    #[track_caller]
+    #[inline(always)]
+    fn check_no_bypass(&self) {
+        let bypass = self.bypass.load(Ordering::Relaxed);
+        // Waiting out the initial hold overlaps with nothing; `force_unlock`
+        // checks once it ends.
+        if bypass & HELD_FROM_NEW == 0 && bypass & !BYPASS_FLAGS != 0 {
+            self.no_lock_overlap(false);
+        }
+    }
+
+    /// `force_unlock` ended a lock's initial hold while a `no_lock` guard
+    /// is alive, letting a waiter in alongside it.
+    #[cfg(feature = "no-lock-check")]
+    #[cold]
+    #[track_caller]
+    fn unlocked_under_no_lock(&self) -> ! {
+        panic!(
+            "force_unlock on lock {:#x} at {} while a no_lock guard is alive",
+            self.locked.as_ptr() as usize,
+            core::panic::Location::caller()
+        );
+    }
+
+    /// Whether the lock is held by a guard or a context, rather than only
+    /// by the hold it was created with.
+    #[cfg(feature = "no-lock-check")]
+    fn held_by_someone(&self) -> bool {
+        self.locked.load(Ordering::Relaxed) != UNLOCKED
+            && self.bypass.load(Ordering::Relaxed) & HELD_FROM_NEW == 0
+    }
+
+    /// A `no_lock` guard and a real acquisition overlap; `held` says which
+    /// came first.
+    #[cfg(feature = "no-lock-check")]
+    #[cold]
+    #[track_caller]
+    fn no_lock_overlap(&self, held: bool) -> ! {
+        let lock = self.locked.as_ptr() as usize;
+        if held {
+            panic!(
+                "no_lock on lock {lock:#x} at {} while it is held",
+                core::panic::Location::caller()
+            );
+        }
+        panic!(
+            "lock {lock:#x} acquired at {} while a no_lock guard is alive",
+            core::panic::Location::caller()
+        );
+    }
+
+    /// Releases the lock without a guard.
+    ///
+    /// This is the only way to recover a lock whose holder can no longer
+    /// release it, such as a peer process that died inside its critical
+    /// section. Nothing here detects that situation automatically.
+    ///
+    /// # Safety
+    ///
+    /// The caller must ensure the current holder will never touch the data
+    /// or drop its guard afterwards; otherwise two parties end up inside the
+    /// critical section at once, or a later guard drop releases somebody
+    /// else's acquisition.
+    #[cfg_attr(feature = "no-lock-check", track_caller)]
+    pub unsafe fn force_unlock(&self) {
+        #[cfg(feature = "no-lock-check")]
+        {
+            let bypass = self.bypass.load(Ordering::Relaxed);
+            if bypass & HELD_FROM_NEW != 0 {
+                if bypass & !BYPASS_FLAGS != 0 {
+                    self.unlocked_under_no_lock();
+                }
+                self.bypass
+                    .store(bypass & !HELD_FROM_NEW, Ordering::Relaxed);
+            }
+        }
+        #[cfg(feature = "owner-tracking")]
+        if raw_atomics_enabled() {
+            self.set_owner(None);
+        }
+        self.clear_recorded();
+    }
+
+    /// Runs the [`CacheMaintenance`](crate::cache::CacheMaintenance) hook
+    /// over this lock, for locks outside the registry that were used before
+    /// the caches were set up. Call it before enabling raw atomics; without
+    /// a hook it does nothing.
+    ///
+    /// With the `registry` feature, registered locks that have been locked
+    /// are covered by [`enable_raw_atomics`] already.
+    pub fn prepare_for_atomics(&self) {
+        crate::cache::clean_invalidate(core::ptr::from_ref(self) as usize, size_of::<Self>());
+    }
+
+    /// Decodes the lock word; see [`state`](crate::state). Only a snapshot.
+    pub fn state(&self) -> crate::state::MutexState {
+        crate::state::MutexState::decode(self.locked.load(Ordering::Relaxed))
+    }
+
+    /// Whether the lock is held, under `spin::Mutex`'s name. Only a
+    /// snapshot. Guards from before raw atomics were enabled count too.
+    #[cfg(feature = "spin-compat")]
+    pub fn is_locked(&self) -> bool {
+        self.locked.load(Ordering::Relaxed) != UNLOCKED
+    }
+
+    /// Returns the id of the context holding the lock, for debugging.
+    ///
+    /// `None` if the lock is free, or if the holder has no
+    /// [`OwnerId`](crate::owner::OwnerId). The value is only a snapshot and
+    /// may be stale by the time it is returned. Permissive-mode and elided
+    /// acquisitions are not recorded.
+    ///
+    /// The owner also lets [`lock`](Self::lock) panic with "recursive lock
+    /// acquisition" when the caller already holds the lock, instead of
+    /// spinning forever.
+    #[cfg(feature = "owner-tracking")]
+    pub fn owner(&self) -> Option<crate::owner::OwnerId> {
+        core::num::NonZeroUsize::new(self.owner.load(Ordering::Relaxed))
+            .map(crate::owner::OwnerId::new)
+    }
+
+    /// Spins until the lock is free, without taking it, for quiescence
+    /// points such as suspend that only need nobody to be mid-update.
+    ///
+    /// This is a momentary observation: another context may take the lock
+    /// right after it returns. Returning synchronizes with the last unlock,
+    /// so its writes are visible. The `watchdog` feature watches the wait
+    /// like a contended [`lock`](Self::lock). Before raw atomics are
+    /// enabled it returns at once, since the only core running cannot be
+    /// waiting for itself.
+    #[track_caller]
+    pub fn wait_unlocked(&self) {
+        let _ = self.wait_unlocked_inner(None);
+    }
+
+    /// Like [`wait_unlocked`](Self::wait_unlocked), but gives up with
+    /// [`TryLockError::TimedOut`] once the lock has stayed held for `spins`
+    /// spins.
+    #[track_caller]
+    pub fn wait_unlocked_for(&self, spins: usize) -> Result<(), TryLockError> {
+        self.wait_unlocked_inner(Some(spins))
+            .map_err(|spins| TryLockError::TimedOut { spins })
+    }
+
+    #[track_caller]
+    fn wait_unlocked_inner(&self, budget: Option<usize>) -> Result<(), usize> {
+        let idle = || self.locked.load(Ordering::Acquire) == UNLOCKED;
+        if !raw_atomics_enabled() || idle() {
+            return Ok(());
+        }
+        crate::raw::wait_until(
+            idle,
+            budget,
+            #[cfg(feature = "watchdog")]
+            self.locked.as_ptr().addr(),
+            #[cfg(feature = "watchdog")]
+            self.watched(),
+        )
+    }
+
+    #[cfg(feature = "watchdog")]
+    fn watched(&self) -> crate::watchdog::Watched<'_> {
+        crate::watchdog::Watched {
+            spin_threshold: Some(&self.spin_threshold),
+            #[cfg(feature = "track-location")]
+            last_acquired_at: &self.last_acquired_at,
+        }
+    }
+
+    /// Overrides the [`watchdog`](crate::watchdog) spin threshold for this
+    /// lock; `Some(0)` disables it, `None` goes back to the global one.
+    #[cfg(feature = "watchdog")]
+    pub fn set_spin_threshold(&self, spins: Option<usize>) {
+        self.spin_threshold
+            .store(crate::watchdog::per_lock(spins), Ordering::Relaxed);
+    }
+
+    /// Returns how many contexts are waiting for the lock.
+    ///
+    /// A racy hint: waiters may arrive or give up right after the read.
+    /// Waiters are counted from the first failed attempt until they get the
+    /// lock, including while blocked in a [`Parker`](crate::park::Parker);
+    /// the uncontended path does not touch the count.
+    #[cfg(feature = "waiter-count")]
+    pub fn waiters(&self) -> usize {
+        self.waiters.load(Ordering::Relaxed)
+    }
+
+    /// Returns whether anyone is waiting for the lock, for example to decide
+    /// between doing more work under the guard and releasing early. As racy
+    /// as [`waiters`](Self::waiters).
+    #[cfg(feature = "waiter-count")]
+    pub fn is_contended(&self) -> bool {
+        self.waiters() != 0
+    }
+
+    /// Returns the lock's counters.
+    ///
+    /// Elided acquisitions are not counted. The counters are read without
+    /// synchronizing with the holder, so a snapshot taken while the lock is
+    /// in use may be slightly behind.
+    #[cfg(feature = "stats")]
+    pub fn stats(&self) -> LockStats {
+        self.stats.snapshot()
+    }
+
+    /// Returns the lock's acquisitions per owner, with owner ids hashed into
+    /// [`FAIRNESS_SLOTS`] slots, so two owners may share one. Acquisitions
+    /// without an owner id are not counted.
+    #[cfg(feature = "fairness")]
+    pub fn fairness_histogram(&self) -> [u64; FAIRNESS_SLOTS] {
+        self.stats.fairness_histogram()
+    }
+
+    /// Returns the Gini coefficient of the [`fairness_histogram`] over its
+    /// non-empty slots: 0 when every owner seen got as many acquisitions as
+    /// the others, approaching 1 as a single owner gets all of them.
+    ///
+    /// [`fairness_histogram`]: Self::fairness_histogram
+    #[cfg(feature = "fairness")]
+    pub fn fairness_imbalance(&self) -> f64 {
+        let histogram = self.fairness_histogram();
+        let seen = || histogram.iter().copied().filter(|&count| count != 0);
+        let owners = seen().count() as u64;
+        if owners < 2 {
+            return 0.0;
+        }
+        let total: u64 = seen().sum();
+        let differences: u64 = seen()
+            .map(|a| seen().map(|b| a.abs_diff(b)).sum::<u64>())
+            .sum();
+        differences as f64 / (2 * owners * total) as f64
+    }
+
+    /// Zeroes the lock's counters. An acquisition in progress may still
+    /// count towards the old values.
+    #[cfg(feature = "stats")]
+    pub fn reset_stats(&self) {
+        self.stats.reset();
+    }
+
+    #[cfg(feature = "owner-tracking")]
+    #[inline]
+    fn set_owner(&self, owner: Option<crate::owner::OwnerId>) {
+        let owner = owner.map_or(0, crate::owner::OwnerId::get);
+        self.owner.store(owner, Ordering::Relaxed);
+    }
+
+    /// Returns where the lock was last acquired through `lock` or
+    /// `try_lock`, for debugging.
+    ///
+    /// The location is kept after the guard is dropped, so on a free lock
+    /// it names the previous holder.
+    #[cfg(feature = "track-location")]
+    pub fn last_acquired_at(&self) -> Option<&'static Location<'static>> {
+        // SAFETY: the pointer is null or comes from a `&'static Location`.
+        unsafe { self.last_acquired_at.load(Ordering::Relaxed).as_ref() }
+    }
+
+    /// Calls `f` with the data if the lock is free, holding it on the bare
+    /// word meanwhile: formatting is not an acquisition, so it uses up no
+    /// injected faults and leaves no stats, traces, events or location.
+    /// Before raw atomics are enabled a marked word means a live guard,
+    /// whose data is not aliased either.
+    #[cfg(any(not(feature = "tiny"), feature = "defmt"))]
+    fn peek<R>(&self, f: impl FnOnce(Option<&T>) -> R) -> R {
+        /// Releases the word even if `f` panics.
+        struct Release<'a>(&'a AtomicU8);
+
+        impl Drop for Release<'_> {
+            fn drop(&mut self) {
+                unlock_atomic(self.0);
+                preempt::enable();
+            }
+        }
+
+        if !raw_atomics_enabled() {
+            if self.locked.load(Ordering::Relaxed) != UNLOCKED {
+                return f(None);
+            }
+            // SAFETY: no guard is alive, and none can be taken while `f`
+            // runs on this context.
+            return f(Some(unsafe { &*self.data.get() }));
+        }
+        if !try_lock_atomic(&self.locked) {
+            return f(None);
+        }
+        preempt::disable();
+        let _release = Release(&self.locked);
+        // SAFETY: the compare-exchange made us the holder until `_release`
+        // is dropped.
+        f(Some(unsafe { &*self.data.get() }))
+    }
+
+    /// The current context already holds the lock, so spinning would never
+    /// end.
+    #[cfg(feature = "owner-tracking")]
+    #[cold]
+    #[track_caller]
+    fn recursive_lock(&self) -> ! {
+        match self.last_acquired_at() {
+            Some(first) => panic!(
+                "recursive lock acquisition at {}, already acquired at {first}",
+                Location::caller()
+            ),
+            None => panic!("recursive lock acquisition at {}", Location::caller()),
+        }
+    }
+
+    /// Returns the size and alignment of `RawSpinLock<T>`, for sizing shared
+    /// memory regions.
+    pub const fn size_and_align() -> (usize, usize) {
+        (size_of::<Self>(), align_of::<Self>())
+    }
+
+    /// Initializes an unlocked lock holding `value` at `ptr` without first
+    /// constructing it on the stack.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be valid for writes and aligned to
+    /// [`size_and_align`](Self::size_and_align)`().1`, and no other party may
+    /// access the memory until this returns. Any previous contents are
+    /// overwritten without being dropped.
+    pub unsafe fn init_in_place(ptr: *mut RawSpinLock<T>, value: T) {
+        // SAFETY: the caller guarantees `ptr` is valid and aligned, so the
+        // field projections are in bounds.
+        unsafe {
+            (&raw mut (*ptr).locked).write(AtomicU8::new(UNLOCKED));
+            #[cfg(feature = "owner-tracking")]
+            (&raw mut (*ptr).owner).write(AtomicUsize::new(0));
+            #[cfg(feature = "track-location")]
+            (&raw mut (*ptr).last_acquired_at).write(AtomicPtr::new(ptr::null_mut()));
+            #[cfg(feature = "stats")]
+            (&raw mut (*ptr).stats).write(CachePadded::new(Counters::new()));
+            #[cfg(feature = "watchdog")]
+            (&raw mut (*ptr).spin_threshold).write(AtomicUsize::new(crate::watchdog::INHERIT));
+            #[cfg(feature = "waiter-count")]
+            (&raw mut (*ptr).waiters).write(AtomicUsize::new(0));
+            #[cfg(feature = "no-lock-check")]
+            (&raw mut (*ptr).bypass).write(AtomicUsize::new(0));
+            #[cfg(feature = "checked-guards")]
+            (&raw mut (*ptr).borrowed).write(AtomicBool::new(false));
+            #[cfg(feature = "lock-ordering")]
+            (&raw mut (*ptr).level).write(0);
+            #[cfg(feature = "lockdep")]
+            (&raw mut (*ptr).class).write(None);
+            #[cfg(feature = "registry")]
+            (&raw mut (*ptr).node).write(crate::registry::Node::unlisted(Self::snapshot));
+            #[cfg(feature = "audit")]
+            (&raw mut (*ptr).pre_atomic).write(AtomicUsize::new(0));
+            #[cfg(feature = "validate-placement")]
+            (&raw mut (*ptr).placed).write(AtomicBool::new(false));
+            (&raw mut (*ptr).data).write(UnsafeCell::new(value));
+        }
+    }
+
+    /// Borrows a lock that lives in externally managed memory, such as a
+    /// region shared with another process.
+    ///
+    /// There is no robustness against a peer dying while it holds the lock;
+    /// the survivor will spin forever unless it calls
+    /// [`force_unlock`](Self::force_unlock). Both sides must also have raw
+    /// atomics enabled, since a permissive-mode peer only marks the lock
+    /// word with plain stores, which exclude nobody.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must point to a lock initialized with
+    /// [`init_in_place`](Self::init_in_place) (or an equivalent peer), that
+    /// stays valid for `'a`. Every party mapping the memory must agree on the
+    /// layout of `T` — same definition, same compiler layout, and no
+    /// pointers into address-space-specific memory.
+    pub unsafe fn from_raw<'a>(ptr: *const RawSpinLock<T>) -> &'a RawSpinLock<T> {
+        // SAFETY: validity and lifetime are guaranteed by the caller.
+        unsafe { &*ptr }
+    }
+}
+
+/// Guardless acquisitions for the C entry points in [`ffi`](crate::ffi).
+///
+/// The guard is forgotten on acquisition and a bare one dropped on
+/// release, so the lock word, the gate, the preemption hook, owner
+/// tracking and `checked-guards` behave as for a guard. Lock ordering,
+/// lockdep, the panic hook's held list, metrics and tracing follow the
+/// guard that was forgotten, so they never see the release.
+#[cfg(feature = "ffi")]
+impl RawSpinLock<()> {
+    #[track_caller]
+    pub(crate) fn lock_unguarded(&self) {
+        core::mem::forget(self.lock());
+    }
+
+    #[track_caller]
+    pub(crate) fn try_lock_unguarded(&self) -> bool {
+        self.try_lock().map(core::mem::forget).is_some()
+    }
+
+    /// # Safety
+    ///
+    /// The caller must hold the lock through
+    /// [`lock_unguarded`](Self::lock_unguarded) or
+    /// [`try_lock_unguarded`](Self::try_lock_unguarded), and raw atomics
+    /// must not have been enabled since.
+    pub(crate) unsafe fn unlock_unguarded(&self) {
+        let unlock_on_drop = raw_atomics_enabled();
+        let mut guard = self.guard(unlock_on_drop);
+        guard.recorded = !unlock_on_drop;
+        #[cfg(feature = "checked-guards")]
+        {
+            guard.borrowed = !unlock_on_drop;
+        }
+        drop(guard);
+    }
+
+    /// The release that the boot handoff contract in [`ffi`](crate::ffi)
+    /// asks of assembly code.
+    pub(crate) fn release_word(&self) {
+        self.locked.store(UNLOCKED, Ordering::Release);
+        crate::relax::notify();
+    }
 }
 
-#[cfg(test)]
+// The lock word sits at `ffi::LOCK_WORD_OFFSET` whatever the data, which
+// assembly relies on.
+#[cfg(feature = "ffi")]
+const _: () = {
+    use crate::ffi::LOCK_WORD_OFFSET;
+    use core::mem::offset_of;
+    assert!(offset_of!(RawSpinLock<()>, locked) == LOCK_WORD_OFFSET);
+    assert!(offset_of!(RawSpinLock<u8>, locked) == LOCK_WORD_OFFSET);
+    assert!(offset_of!(RawSpinLock<u128>, locked) == LOCK_WORD_OFFSET);
+    assert!(offset_of!(RawSpinLock<[u64; 3]>, locked) == LOCK_WORD_OFFSET);
+    assert!(crate::ffi::LOCK_WORD_UNLOCKED == UNLOCKED);
+};
+
+#[cfg(all(test, not(feature = "spin-compat")))]
+#[allow(dead_code)]
 impl<T: ?Sized> RawSpinLock<T> {
     fn is_locked(&self) -> bool {
-        self.locked.load(Ordering::Acquire)
+        self.locked.load(Ordering::Acquire) != UNLOCKED
+    }
+}
+
+impl<T> RawSpinLockGuard<'_, T> {
+    /// Whether the guard really holds the lock, that is, whether its
+    /// [`kind`](Self::kind) is [`GuardKind::Atomic`].
+    pub fn holds_lock(&self) -> bool {
+        self.kind() == GuardKind::Atomic
+    }
+
+    /// How the guard was handed out.
+    pub fn kind(&self) -> GuardKind {
+        #[cfg(all(feature = "hle", target_arch = "x86_64", target_feature = "rtm"))]
+        if self.elided {
+            // The transaction excludes everybody else, like the lock.
+            return GuardKind::Atomic;
+        }
+        if self.bypass {
+            GuardKind::Bypass
+        } else if self.unlock_on_drop {
+            GuardKind::Atomic
+        } else {
+            GuardKind::PreAtomic
+        }
+    }
+
+    /// Lets the guard be dropped in a different context than the one that
+    /// acquired it, for deliberate handoffs. This only disables the
+    /// debug-assertions check; releasing elsewhere is otherwise fine.
+    #[cfg_attr(not(debug_assertions), allow(unused_variables))]
+    pub fn allow_foreign_unlock(guard: &mut Self) {
+        #[cfg(debug_assertions)]
+        {
+            guard.acquirer = None;
+        }
+    }
+
+    #[cfg(debug_assertions)]
+    #[cfg_attr(feature = "tiny", allow(unused_variables))]
+    fn check_releaser(&self) {
+        let Some((acquirer, location)) = self.acquirer else {
+            return;
+        };
+        let Some(me) = crate::owner::current() else {
+            return;
+        };
+        if me != acquirer {
+            #[cfg(feature = "tiny")]
+            panic!("lock released by another context than the one that acquired it");
+            #[cfg(not(feature = "tiny"))]
+            panic!(
+                "lock {:#x} acquired by {:#x} at {location} released by {:#x}",
+                self.lock.locked.as_ptr() as usize,
+                acquirer.get(),
+                me.get()
+            );
+        }
+    }
+}
+
+impl<T> HandoffGuard<'_, T> {
+    /// Releases the lock; the same as dropping the guard, spelled out at
+    /// the completion site.
+    pub fn complete(self) {}
+
+    /// Whether the guard really holds the lock: `false` if it was handed
+    /// out before raw atomics were enabled.
+    pub fn holds_lock(&self) -> bool {
+        self.holds_lock
+    }
+}
+
+impl<T> Drop for HandoffGuard<'_, T> {
+    fn drop(&mut self) {
+        if self.holds_lock {
+            unlock_atomic(&self.lock.locked);
+            #[cfg(feature = "usdt")]
+            crate::usdt::released(
+                self.lock.locked.as_ptr() as usize,
+                self.lock.name().unwrap_or(""),
+            );
+        } else if self.recorded {
+            self.lock.clear_recorded();
+        }
+    }
+}
+
+impl<T> Deref for HandoffGuard<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &Self::Target {
+        // SAFETY: the guard holds the lock, or nothing else runs yet.
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<T> DerefMut for HandoffGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        // SAFETY: as in `deref`.
+        unsafe { &mut *self.lock.data.get() }
     }
 }
 
 impl<T> Drop for RawSpinLockGuard<'_, T> {
     fn drop(&mut self) {
+        // First, so the handler sees the lock and its bookkeeping as they
+        // were when the panic started.
+        #[cfg(feature = "panic-policy")]
+        if !self.bypass {
+            crate::panic_policy::check(|| crate::panic_policy::LockedPanic {
+                lock: self.lock.locked.as_ptr() as usize,
+                name: self.lock.name(),
+                #[cfg(feature = "track-location")]
+                last_acquired_at: self.lock.last_acquired_at(),
+            });
+        }
+        #[cfg(feature = "no-lock-check")]
+        if self.bypass {
+            let bypass = self.lock.bypass.load(Ordering::Relaxed);
+            self.lock.bypass.store(bypass - 1, Ordering::Relaxed);
+        }
+        #[cfg(feature = "checked-guards")]
+        if self.borrowed {
+            self.lock.borrowed.store(false, Ordering::Relaxed);
+        }
+        #[cfg(all(feature = "hle", target_arch = "x86_64", target_feature = "rtm"))]
+        if self.elided {
+            crate::elision::end();
+            preempt::enable();
+        }
+        #[cfg(feature = "tracing")]
+        self.span
+            .with_subscriber(|(id, dispatch)| dispatch.exit(id));
+        #[cfg(feature = "lock-ordering")]
+        if self.ordered {
+            crate::ordering::release(self.lock.locked.as_ptr() as usize);
+        }
+        #[cfg(feature = "lockdep")]
+        if self.lockdep {
+            crate::lockdep::release(self.lock.locked.as_ptr() as usize);
+        }
+        #[cfg(feature = "panic-hook")]
+        if self.held {
+            crate::held::release(self.lock.locked.as_ptr() as usize);
+        }
+        // Still holding the lock, as `Counters` requires.
+        #[cfg(feature = "hold-time")]
+        if let (Some(start), Some(end)) = (self.acquired_at, crate::clock::now()) {
+            self.lock.stats.record_hold(end.saturating_sub(start));
+        }
+        #[cfg(feature = "metrics")]
+        let released = self.metrics.take().map(crate::metrics::Pending::stop);
         if self.unlock_on_drop {
+            #[cfg(feature = "deadlock-detection")]
+            crate::deadlock::released(self.lock.locked.as_ptr() as usize);
+            #[cfg(feature = "owner-tracking")]
+            self.lock.set_owner(None);
             unlock_atomic(&self.lock.locked);
+            #[cfg(feature = "usdt")]
+            crate::usdt::released(
+                self.lock.locked.as_ptr() as usize,
+                self.lock.name().unwrap_or(""),
+            );
+            preempt::enable();
+        } else if self.recorded {
+            self.lock.clear_recorded();
+        }
+        // Reported after the release, so a slow sink does not extend the
+        // critical section.
+        #[cfg(feature = "metrics")]
+        if let Some(released) = released {
+            released.report(self.lock.locked.as_ptr() as usize);
         }
+        // Checked once released, so the panic does not leave the lock held.
+        #[cfg(debug_assertions)]
+        self.check_releaser();
+    }
+}
+
+impl<'a, T> RawSpinLockGuard<'a, T> {
+    /// Narrows the guard to part of the data, like `RefMut::map`. The lock
+    /// stays held until the returned guard is dropped.
+    pub fn map<U: ?Sized>(
+        this: Self,
+        f: impl FnOnce(&mut T) -> &mut U,
+    ) -> MappedRawSpinLockGuard<'a, T, U> {
+        MappedRawSpinLockGuard::new(this, f)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<'a, T> RawSpinLockGuard<'a, T> {
+    /// Splits the guard into guards for two disjoint parts of the data,
+    /// like `RefMut::map_split`. The lock stays held until both are dropped.
+    pub fn map_split<A: ?Sized, B: ?Sized>(
+        this: Self,
+        f: impl FnOnce(&mut T) -> (&mut A, &mut B),
+    ) -> (MappedSpinLockGuard<'a, A>, MappedSpinLockGuard<'a, B>) {
+        MappedSpinLockGuard::split(this, f)
+    }
+}
+
+#[cfg(feature = "spin-compat")]
+impl<'a, T> RawSpinLockGuard<'a, T> {
+    /// Keeps the lock held forever and returns the data, as
+    /// `spin::MutexGuard::leak`. Nothing the drop would undo is undone,
+    /// including the preemption hook's `disable`.
+    pub fn leak(this: Self) -> &'a mut T {
+        let data = this.lock.data.get();
+        core::mem::forget(this);
+        // SAFETY: the guard is never dropped, so the lock stays held and
+        // this is the only reference to the data.
+        unsafe { &mut *data }
     }
 }
 
@@ -208,3 +1759,394 @@ impl<T> DerefMut for RawSpinLockGuard<'_, T> {
         unsafe { &mut *self.lock.data.get() }
     }
 }
+
+#[cfg(feature = "no-lock-check")]
+impl<T> Drop for RawSpinLockReadOnlyGuard<'_, T> {
+    fn drop(&mut self) {
+        let bypass = self.lock.bypass.load(Ordering::Relaxed);
+        self.lock.bypass.store(bypass - 1, Ordering::Relaxed);
+    }
+}
+
+impl<T> Deref for RawSpinLockReadOnlyGuard<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &Self::Target {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl<T: zeroize::Zeroize> RawSpinLock<T> {
+    /// Locks and zeroizes the protected value in place.
+    pub fn zeroize_inner(&self) {
+        self.lock().zeroize();
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl<T: zeroize::Zeroize> zeroize::Zeroize for RawSpinLock<T> {
+    fn zeroize(&mut self) {
+        self.get_mut().zeroize();
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl<T: zeroize::Zeroize> RawSpinLockGuard<'_, T> {
+    /// Zeroizes the protected value in place.
+    pub fn zeroize(&mut self) {
+        zeroize::Zeroize::zeroize(&mut **self);
+    }
+}
+
+/// Reads from the protected reader, so a guard can be passed where a
+/// `Read` is expected.
+#[cfg(feature = "std")]
+impl<T: std::io::Read> std::io::Read for RawSpinLockGuard<'_, T> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        (**self).read(buf)
+    }
+
+    fn read_vectored(&mut self, bufs: &mut [std::io::IoSliceMut<'_>]) -> std::io::Result<usize> {
+        (**self).read_vectored(bufs)
+    }
+
+    fn read_to_end(&mut self, buf: &mut Vec<u8>) -> std::io::Result<usize> {
+        (**self).read_to_end(buf)
+    }
+
+    fn read_to_string(&mut self, buf: &mut String) -> std::io::Result<usize> {
+        (**self).read_to_string(buf)
+    }
+
+    fn read_exact(&mut self, buf: &mut [u8]) -> std::io::Result<()> {
+        (**self).read_exact(buf)
+    }
+}
+
+/// Writes to the protected writer; a `write_all` through
+/// one guard reaches the writer in one piece.
+#[cfg(feature = "std")]
+impl<T: std::io::Write> std::io::Write for RawSpinLockGuard<'_, T> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        (**self).write(buf)
+    }
+
+    fn write_vectored(&mut self, bufs: &[std::io::IoSlice<'_>]) -> std::io::Result<usize> {
+        (**self).write_vectored(bufs)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        (**self).flush()
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> std::io::Result<()> {
+        (**self).write_all(buf)
+    }
+
+    fn write_fmt(&mut self, args: core::fmt::Arguments<'_>) -> std::io::Result<()> {
+        (**self).write_fmt(args)
+    }
+}
+
+/// Formats the value without blocking, or `<locked>` if the lock is held.
+/// With `owner-tracking`, a held lock also shows its holder's id, and with
+/// `track-location` where the lock was last acquired.
+#[cfg(not(feature = "tiny"))]
+impl<T: fmt::Debug> fmt::Debug for RawSpinLock<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut d = f.debug_struct("RawSpinLock");
+        self.peek(|data| match data {
+            Some(data) => {
+                d.field("data", &data);
+            }
+            None => {
+                d.field("data", &format_args!("<locked>"));
+                #[cfg(feature = "owner-tracking")]
+                d.field("owner", &self.owner());
+            }
+        });
+        #[cfg(feature = "track-location")]
+        d.field("last_acquired_at", &self.last_acquired_at());
+        d.finish()
+    }
+}
+
+/// Formats the value without blocking, or `<locked>` if the lock is held.
+#[cfg(feature = "defmt")]
+impl<T: defmt::Format> defmt::Format for RawSpinLock<T> {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        self.peek(|data| match data {
+            Some(data) => defmt::write!(f, "RawSpinLock {{ data: {} }}", data),
+            None => defmt::write!(f, "RawSpinLock {{ data: <locked> }}"),
+        });
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl<T: defmt::Format> defmt::Format for RawSpinLockGuard<'_, T> {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        defmt::Format::format(&**self, f)
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl<T: defmt::Format> defmt::Format for RawSpinLockReadOnlyGuard<'_, T> {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        defmt::Format::format(&**self, f)
+    }
+}
+
+/// Serializes the protected value as if it were not wrapped. The lock is
+/// held while serializing, so this blocks while it is contended, and
+/// deadlocks if the serializing thread already holds it.
+#[cfg(feature = "serde")]
+impl<T: serde::Serialize> serde::Serialize for RawSpinLock<T> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.lock().serialize(serializer)
+    }
+}
+
+/// Deserializes the value into a new, unlocked lock.
+#[cfg(feature = "serde")]
+impl<'de, T: serde::Deserialize<'de>> serde::Deserialize<'de> for RawSpinLock<T> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        T::deserialize(deserializer).map(Self::new)
+    }
+}
+
+/// Clones the value under the lock into a new, unlocked lock, so this
+/// blocks while the lock is contended. The clone is as if made with
+/// [`new`](Self::new): it has no registry name, lockdep class or lock
+/// level, its statistics start at zero, and it uses the global watchdog
+/// threshold.
+impl<T: Clone> Clone for RawSpinLock<T> {
+    #[track_caller]
+    fn clone(&self) -> Self {
+        Self::new(T::clone(&self.lock()))
+    }
+}
+
+/// Compares the protected values with both locks held, taken in address
+/// order so that two threads comparing the same pair cannot deadlock. With
+/// `lock-ordering` the lower level is taken first instead, and comparing
+/// two locks at the same nonzero level panics like any such nesting.
+/// Comparing a lock with itself locks it once.
+impl<T: PartialEq> PartialEq for RawSpinLock<T> {
+    #[track_caller]
+    fn eq(&self, other: &Self) -> bool {
+        if core::ptr::eq(self, other) {
+            let guard = self.lock();
+            // Still compared, as `T` may not be reflexive.
+            #[allow(clippy::eq_op)]
+            return *guard == *guard;
+        }
+        if self.order_key() < other.order_key() {
+            let this = self.lock();
+            *this == *other.lock()
+        } else {
+            let other = other.lock();
+            *self.lock() == *other
+        }
+    }
+}
+
+impl<T: Eq> Eq for RawSpinLock<T> {}
+
+/// Compares the protected value with `other` under the lock.
+impl<T: PartialEq> PartialEq<T> for RawSpinLock<T> {
+    #[track_caller]
+    fn eq(&self, other: &T) -> bool {
+        *self.lock() == *other
+    }
+}
+
+impl<T> Lock<T> for RawSpinLock<T> {
+    type Guard<'a>
+        = RawSpinLockGuard<'a, T>
+    where
+        T: 'a;
+
+    #[track_caller]
+    fn lock(&self) -> Self::Guard<'_> {
+        RawSpinLock::lock(self)
+    }
+
+    #[track_caller]
+    fn try_lock(&self) -> Option<Self::Guard<'_>> {
+        RawSpinLock::try_lock(self)
+    }
+    #[track_caller]
+    fn try_lock_result(&self) -> Result<Self::Guard<'_>, TryLockError> {
+        RawSpinLock::try_lock_result(self)
+    }
+}
+
+/// Declares `static` [`RawSpinLock`]s registered under their own names; see
+/// [`RawSpinLock::registered`]. Each also gets a
+/// [`LockClass`](crate::lockdep::LockClass) of the same name.
+#[macro_export]
+macro_rules! registered_static {
+    ($($(#[$attr:meta])* $vis:vis static $name:ident: $ty:ty = $value:expr;)*) => {
+        $(
+            $(#[$attr])*
+            // SAFETY: statics never move and are never dropped.
+            $vis static $name: $ty = unsafe {
+                <$ty>::builder()
+                    .name(stringify!($name))
+                    .class($crate::lock_class!(stringify!($name)))
+                    .build_static($value)
+            };
+        )*
+    };
+}
+
+/// Declares `static` locks, or arrays of them, from the data they protect.
+///
+/// `static NAME: [L; N] = data;` makes `N` locks of type `L`, each created
+/// from `data`, which must be a constant expression; this works even though
+/// locks are not `Copy`. Any lock with a `const fn new(data)` can be used.
+/// Locks whose type is spelled `RawSpinLock<T>`, with `RawSpinLock` in
+/// scope, are created like [`registered_static!`] does, so with `registry`
+/// and `lockdep` they are named after the static; all locks of an array
+/// share its name.
+///
+/// ```
+/// use mutex::RawSpinLock;
+/// use mutex::RwSpinLock;
+///
+/// mutex::spin_static! {
+///     static FORKS: [RawSpinLock<()>; 5] = ();
+///     static CONSOLE: RawSpinLock<Vec<u8>> = Vec::new();
+///     pub static CONFIG: RwSpinLock<u32> = 115_200;
+/// }
+///
+/// mutex::enable_raw_atomics();
+/// let _left = FORKS[0].lock();
+/// let _right = FORKS[1].lock();
+/// CONSOLE.lock().extend_from_slice(b"eating\n");
+/// assert_eq!(*CONFIG.read(), 115_200);
+/// ```
+#[macro_export]
+macro_rules! spin_static {
+    () => {};
+    (
+        $(#[$attr:meta])* $vis:vis static $name:ident: [RawSpinLock<$data:ty>; $($len:tt)+] = $value:expr;
+        $($rest:tt)*
+    ) => {
+        $(#[$attr])*
+        $vis static $name: [RawSpinLock<$data>; $($len)+] = [const {
+            // SAFETY: statics never move and are never dropped.
+            unsafe {
+                RawSpinLock::<$data>::builder()
+                    .name(stringify!($name))
+                    .class($crate::lock_class!(stringify!($name)))
+                    .build_static($value)
+            }
+        }; $($len)+];
+        $crate::spin_static!($($rest)*);
+    };
+    (
+        $(#[$attr:meta])* $vis:vis static $name:ident: RawSpinLock<$data:ty> = $value:expr;
+        $($rest:tt)*
+    ) => {
+        $crate::registered_static! {
+            $(#[$attr])* $vis static $name: RawSpinLock<$data> = $value;
+        }
+        $crate::spin_static!($($rest)*);
+    };
+    (
+        $(#[$attr:meta])* $vis:vis static $name:ident: [$lock:ty; $($len:tt)+] = $value:expr;
+        $($rest:tt)*
+    ) => {
+        $(#[$attr])*
+        $vis static $name: [$lock; $($len)+] = [const { <$lock>::new($value) }; $($len)+];
+        $crate::spin_static!($($rest)*);
+    };
+    (
+        $(#[$attr:meta])* $vis:vis static $name:ident: $lock:ty = $value:expr;
+        $($rest:tt)*
+    ) => {
+        $(#[$attr])*
+        $vis static $name: $lock = <$lock>::new($value);
+        $crate::spin_static!($($rest)*);
+    };
+}
+
+/// Proof harnesses for the lock-state invariants; run them with
+/// `cargo kani`. Those that do not need raw atomics disabled also run as
+/// unit tests, over every lock word where they take a symbolic one.
+#[cfg(any(kani, test))]
+mod proofs {
+    use super::*;
+    use crate::atomic::Ordering;
+    use crate::raw::LOCKED;
+    use crate::raw::PARKED;
+    use crate::raw::UNLOCKED;
+
+    /// Dropping a guard releases the lock, and only that guard's hold:
+    /// the next acquisition is exclusive again.
+    #[cfg_attr(kani, kani::proof)]
+    #[cfg_attr(test, test)]
+    fn guard_drop_releases_once() {
+        enable_raw_atomics();
+        let lock = RawSpinLock::new(0u8);
+        let guard = lock.try_lock();
+        assert!(guard.is_some());
+        assert_eq!(lock.locked.load(Ordering::Relaxed), LOCKED);
+        drop(guard);
+        assert_eq!(lock.locked.load(Ordering::Relaxed), UNLOCKED);
+        let again = lock.try_lock();
+        assert!(again.is_some());
+        assert!(lock.try_lock().is_none());
+    }
+
+    /// Before raw atomics are enabled a guard marks a free lock word held
+    /// and clears it on drop, and a nested guard leaves the outer mark.
+    /// Not a unit test, since the other tests enable raw atomics.
+    #[cfg(kani)]
+    #[kani::proof]
+    fn permissive_guard_marks_lock_word() {
+        let lock = RawSpinLock::new(0u8);
+        let outer = lock.lock();
+        assert_eq!(lock.locked.load(Ordering::Relaxed), LOCKED);
+        drop(lock.try_lock());
+        assert_eq!(lock.locked.load(Ordering::Relaxed), LOCKED);
+        drop(outer);
+        assert_eq!(lock.locked.load(Ordering::Relaxed), UNLOCKED);
+    }
+
+    /// A `no_lock` guard neither takes nor releases the lock, whatever
+    /// state it is in.
+    fn check_no_lock(before: u8) {
+        let lock = RawSpinLock::new(0u8);
+        lock.locked.store(before, Ordering::Relaxed);
+        // SAFETY: nothing else can reach the lock.
+        drop(unsafe { lock.no_lock() });
+        assert_eq!(lock.locked.load(Ordering::Relaxed), before);
+    }
+
+    /// With `no-lock-check`, `no_lock` on a held lock panics instead.
+    fn no_lock_precondition(before: u8) -> bool {
+        before <= PARKED && (cfg!(not(feature = "no-lock-check")) || before == UNLOCKED)
+    }
+
+    /// [`check_no_lock`], with or without raw atomics enabled.
+    #[cfg(kani)]
+    #[kani::proof]
+    fn no_lock_leaves_state_unchanged() {
+        if kani::any() {
+            enable_raw_atomics();
+        }
+        let before = kani::any();
+        kani::assume(no_lock_precondition(before));
+        check_no_lock(before);
+    }
+
+    #[test]
+    fn no_lock_leaves_every_state_unchanged() {
+        enable_raw_atomics();
+        for before in (0..=u8::MAX).filter(|&before| no_lock_precondition(before)) {
+            check_no_lock(before);
+        }
+    }
+}
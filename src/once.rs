@@ -0,0 +1,105 @@
+//! A lock for statics whose value is only known at run time.
+//!
+//! A [`LateInitSpinLock`] starts out empty in a `static` and is filled once
+//! with [`init`](LateInitSpinLock::init), for instance after probing the
+//! hardware. From then on [`get`](LateInitSpinLock::get) hands out the
+//! plain [`RawSpinLock`] inside, so code running after initialization
+//! neither unwraps an `Option` nor stores one.
+
+#[cfg(not(feature = "tiny"))]
+use core::fmt;
+use core::mem::MaybeUninit;
+
+use crate::atomic::AtomicBool;
+use crate::atomic::Ordering;
+use crate::mutex::RawSpinLock;
+
+/// A [`RawSpinLock`] that is created empty and initialized once later.
+///
+/// Using the lock before [`init`](Self::init) panics, and initializing it
+/// twice fails, so neither can go unnoticed.
+pub struct LateInitSpinLock<T> {
+    /// Set once the value is written, never cleared. A plain store, so it
+    /// can be set before raw atomics are enabled.
+    initialized: AtomicBool,
+    inner: RawSpinLock<MaybeUninit<T>>,
+}
+
+impl<T> LateInitSpinLock<T> {
+    pub const fn new() -> Self {
+        Self {
+            initialized: AtomicBool::new(false),
+            inner: RawSpinLock::new(MaybeUninit::uninit()),
+        }
+    }
+
+    /// Stores `value`, or gives it back if the lock is already initialized.
+    ///
+    /// The value is written with the lock held, so concurrent calls are
+    /// resolved once raw atomics are enabled; before that, as with any
+    /// lock, only a single core may call this.
+    #[track_caller]
+    pub fn init(&self, value: T) -> Result<(), T> {
+        let mut guard = self.inner.lock();
+        if self.initialized.load(Ordering::Relaxed) {
+            return Err(value);
+        }
+        guard.write(value);
+        self.initialized.store(true, Ordering::Release);
+        Ok(())
+    }
+
+    pub fn is_initialized(&self) -> bool {
+        self.initialized.load(Ordering::Acquire)
+    }
+
+    /// The initialized lock, or `None` before [`init`](Self::init).
+    pub fn try_get(&self) -> Option<&RawSpinLock<T>> {
+        if !self.is_initialized() {
+            return None;
+        }
+        // SAFETY: the value is initialized and never uninitialized again,
+        // and `RawSpinLock` is `repr(C)`, so swapping `MaybeUninit<T>` for
+        // `T` keeps the layout.
+        Some(unsafe { &*core::ptr::from_ref(&self.inner).cast::<RawSpinLock<T>>() })
+    }
+
+    /// The initialized lock.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called before [`init`](Self::init).
+    #[track_caller]
+    pub fn get(&self) -> &RawSpinLock<T> {
+        match self.try_get() {
+            Some(lock) => lock,
+            None => panic!("LateInitSpinLock used before it was initialized"),
+        }
+    }
+}
+
+impl<T> Default for LateInitSpinLock<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Drop for LateInitSpinLock<T> {
+    fn drop(&mut self) {
+        if self.initialized.load(Ordering::Relaxed) {
+            // SAFETY: initialized, and dropped only here.
+            unsafe { self.inner.get_mut().assume_init_drop() };
+        }
+    }
+}
+
+/// Formats like the [`RawSpinLock`] inside, or `<uninit>`.
+#[cfg(not(feature = "tiny"))]
+impl<T: fmt::Debug> fmt::Debug for LateInitSpinLock<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.try_get() {
+            Some(lock) => lock.fmt(f),
+            None => f.write_str("LateInitSpinLock(<uninit>)"),
+        }
+    }
+}
@@ -0,0 +1,141 @@
+//! A slot that hands a single value from one core to another.
+//!
+//! A [`Oneshot`] suits handoffs such as the boot core computing a value that
+//! exactly one secondary core picks up. It is a state byte next to the
+//! value: [`send`](Oneshot::send) claims the empty slot, writes the value and
+//! marks it full, and a receiver takes it by moving the state from full to
+//! taken, so however many receivers race, exactly one gets the value.
+//!
+//! Before raw atomics are enabled the state moves with plain stores, which
+//! is enough while a single core runs.
+
+use core::cell::UnsafeCell;
+#[cfg(not(feature = "tiny"))]
+use core::fmt;
+use core::mem::MaybeUninit;
+
+use crate::atomic::AtomicU8;
+use crate::atomic::Ordering;
+use crate::raw::raw_atomics_enabled;
+use crate::relax;
+use crate::relax::Backoff;
+
+const EMPTY: u8 = 0;
+/// Claimed by the sender, which is writing the value.
+const WRITING: u8 = 1;
+const FULL: u8 = 2;
+const TAKEN: u8 = 3;
+
+/// A single-use slot for handing over one value.
+///
+/// The value is sent once and received once; it is dropped with the slot if
+/// it is never received.
+pub struct Oneshot<T> {
+    state: AtomicU8,
+    value: UnsafeCell<MaybeUninit<T>>,
+}
+
+// SAFETY: the value moves from the sender to exactly one receiver, and the
+// state machine gives each of them exclusive access in turn.
+unsafe impl<T: Send> Send for Oneshot<T> {}
+unsafe impl<T: Send> Sync for Oneshot<T> {}
+
+impl<T> Oneshot<T> {
+    pub const fn new() -> Self {
+        Self {
+            state: AtomicU8::new(EMPTY),
+            value: UnsafeCell::new(MaybeUninit::uninit()),
+        }
+    }
+
+    /// Stores `value` for a receiver, or gives it back if a value was
+    /// already sent.
+    pub fn send(&self, value: T) -> Result<(), T> {
+        if raw_atomics_enabled() {
+            if self
+                .state
+                .compare_exchange(EMPTY, WRITING, Ordering::Relaxed, Ordering::Relaxed)
+                .is_err()
+            {
+                return Err(value);
+            }
+        } else {
+            if self.state.load(Ordering::Relaxed) != EMPTY {
+                return Err(value);
+            }
+            self.state.store(WRITING, Ordering::Relaxed);
+        }
+        // SAFETY: `WRITING` gives this call exclusive access to the slot.
+        unsafe { (*self.value.get()).write(value) };
+        self.state.store(FULL, Ordering::Release);
+        relax::notify();
+        Ok(())
+    }
+
+    /// Takes the value if it has been sent and not yet received.
+    ///
+    /// Returns `None` both before the value is sent and after another
+    /// receiver took it.
+    pub fn try_recv(&self) -> Option<T> {
+        if raw_atomics_enabled() {
+            self.state
+                .compare_exchange(FULL, TAKEN, Ordering::Acquire, Ordering::Relaxed)
+                .ok()?;
+        } else {
+            if self.state.load(Ordering::Acquire) != FULL {
+                return None;
+            }
+            self.state.store(TAKEN, Ordering::Relaxed);
+        }
+        // SAFETY: the value was written before `FULL` was published, and
+        // moving the state to `TAKEN` made this call its only reader.
+        Some(unsafe { (*self.value.get()).assume_init_read() })
+    }
+
+    /// Waits for the value and takes it.
+    ///
+    /// A receiver that loses the race to another one spins forever, as the
+    /// value never comes back; with several receivers, all but one should
+    /// use [`try_recv`](Self::try_recv).
+    pub fn recv(&self) -> T {
+        let mut backoff = Backoff::new();
+        loop {
+            if let Some(value) = self.try_recv() {
+                return value;
+            }
+            backoff.wait();
+        }
+    }
+
+    /// Whether a value has been sent, received or not.
+    pub fn is_sent(&self) -> bool {
+        matches!(self.state.load(Ordering::Relaxed), FULL | TAKEN)
+    }
+}
+
+impl<T> Default for Oneshot<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Drop for Oneshot<T> {
+    fn drop(&mut self) {
+        if self.state.load(Ordering::Relaxed) == FULL {
+            // SAFETY: sent and never received, and dropped only here.
+            unsafe { self.value.get_mut().assume_init_drop() };
+        }
+    }
+}
+
+#[cfg(not(feature = "tiny"))]
+impl<T> fmt::Debug for Oneshot<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = match self.state.load(Ordering::Relaxed) {
+            EMPTY | WRITING => "empty",
+            FULL => "full",
+            _ => "taken",
+        };
+        f.debug_struct("Oneshot").field("state", &state).finish()
+    }
+}
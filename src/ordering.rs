@@ -0,0 +1,157 @@
+//! Lock-order validation for [`RawSpinLock`].
+//!
+//! With the `lock-ordering` feature, a lock created with
+//! [`RawSpinLock::with_level`] and a non-zero level takes part in a global
+//! order: [`lock`] panics, naming both call sites, when the lock's level is
+//! not strictly greater than the highest level the current context already
+//! holds. The check runs before spinning, so an inversion is reported on
+//! first occurrence rather than when it finally deadlocks. [`try_lock`]
+//! cannot deadlock and is not checked, but the lock it takes counts as held.
+//!
+//! Each context keeps the levels it holds in a fixed array of [`MAX_DEPTH`]
+//! entries; deeper nesting is not tracked. On `std` the array is
+//! thread-local. Without `std` contexts are told apart through
+//! [`owner::current`] and claim one of [`MAX_CONTEXTS`] slots; nothing is
+//! tracked for a context without an owner id, or beyond the last slot.
+//!
+//! [`RawSpinLock`]: crate::mutex::RawSpinLock
+//! [`lock`]: crate::mutex::RawSpinLock::lock
+//! [`try_lock`]: crate::mutex::RawSpinLock::try_lock
+//! [`RawSpinLock::with_level`]: crate::mutex::RawSpinLock::with_level
+//! [`owner::current`]: crate::owner::current
+
+use core::cell::Cell;
+use core::panic::Location;
+
+/// Locks tracked per context.
+pub const MAX_DEPTH: usize = 16;
+/// Contexts that can be tracked at the same time without `std`.
+pub const MAX_CONTEXTS: usize = 64;
+
+#[derive(Clone, Copy)]
+struct Entry {
+    lock: usize,
+    level: u32,
+    location: &'static Location<'static>,
+}
+
+/// The locks one context holds. Only ever touched by that context.
+struct Held {
+    entries: [Cell<Option<Entry>>; MAX_DEPTH],
+}
+
+impl Held {
+    const fn new() -> Self {
+        Self {
+            entries: [const { Cell::new(None) }; MAX_DEPTH],
+        }
+    }
+
+    fn highest(&self) -> Option<Entry> {
+        self.entries
+            .iter()
+            .filter_map(Cell::get)
+            .max_by_key(|entry| entry.level)
+    }
+}
+
+#[cfg(feature = "std")]
+fn with_held(f: impl FnOnce(&Held)) {
+    std::thread_local!(static HELD: Held = const { Held::new() });
+    let _ = HELD.try_with(f);
+}
+
+#[cfg(not(feature = "std"))]
+fn with_held(f: impl FnOnce(&Held)) {
+    use crate::atomic::AtomicUsize;
+    use crate::atomic::Ordering;
+
+    struct Slot {
+        owner: AtomicUsize,
+        held: Held,
+    }
+
+    // SAFETY: `held` is only accessed by the context whose id is in `owner`.
+    unsafe impl Sync for Slot {}
+
+    static SLOTS: [Slot; MAX_CONTEXTS] = [const {
+        Slot {
+            owner: AtomicUsize::new(0),
+            held: Held::new(),
+        }
+    }; MAX_CONTEXTS];
+
+    let Some(me) = crate::owner::current() else {
+        return;
+    };
+    let me = me.get();
+    let slot = SLOTS
+        .iter()
+        .find(|slot| slot.owner.load(Ordering::Relaxed) == me)
+        .or_else(|| {
+            SLOTS.iter().find(|slot| {
+                slot.owner
+                    .compare_exchange(0, me, Ordering::Acquire, Ordering::Relaxed)
+                    .is_ok()
+            })
+        });
+    if let Some(slot) = slot {
+        f(&slot.held);
+    }
+}
+
+/// Panics if acquiring a lock at `level` would break the order.
+#[track_caller]
+pub(crate) fn check(level: u32) {
+    if level == 0 {
+        return;
+    }
+    // Read outside the closure, which does not inherit `#[track_caller]`.
+    let caller = Location::caller();
+    with_held(|held| {
+        let Some(top) = held.highest() else {
+            return;
+        };
+        if top.level >= level {
+            panic!(
+                "lock order violation: acquiring level {level} at {caller} while holding level {} acquired at {}",
+                top.level, top.location
+            );
+        }
+    });
+}
+
+/// Records that the current context holds `lock`. Returns whether it was
+/// recorded, and so must be removed again with [`release`].
+#[track_caller]
+pub(crate) fn acquire(lock: usize, level: u32) -> bool {
+    if level == 0 {
+        return false;
+    }
+    let location = Location::caller();
+    let mut recorded = false;
+    with_held(|held| {
+        if let Some(free) = held.entries.iter().find(|entry| entry.get().is_none()) {
+            free.set(Some(Entry {
+                lock,
+                level,
+                location,
+            }));
+            recorded = true;
+        }
+    });
+    recorded
+}
+
+/// Forgets the entry [`acquire`] recorded for `lock`.
+pub(crate) fn release(lock: usize) {
+    with_held(|held| {
+        let entry = held
+            .entries
+            .iter()
+            .find(|entry| entry.get().is_some_and(|entry| entry.lock == lock));
+        if let Some(entry) = entry {
+            entry.set(None);
+        }
+    });
+}
@@ -0,0 +1,98 @@
+//! Identifying the thread (or core) that is running.
+//!
+//! Primitives that need to tell one execution context from another ask the
+//! registered [`OwnerIdProvider`] for the current [`OwnerId`]. With `std`
+//! and no provider registered, each thread gets an id derived from its
+//! thread-local storage; without `std` there is no id until a kernel hook is
+//! registered.
+//!
+//! With `owner-tracking`, a waiter on a contended [`RawSpinLock`] also asks
+//! the [`OwnerRunning`] hook every few spins whether the holder is on a CPU.
+//! Once it is not, spinning cannot end soon, so the waiter parks in the
+//! registered [`Parker`] right away, or otherwise jumps to the end of its
+//! backoff, where the default strategy yields.
+//!
+//! [`RawSpinLock`]: crate::mutex::RawSpinLock
+//! [`Parker`]: crate::park::Parker
+
+use core::num::NonZeroUsize;
+
+use crate::hook::HookCell;
+use crate::hook::SetHookError;
+
+/// Identifies an execution context. Ids of live contexts are distinct; an
+/// id may be reused after its context has exited.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(not(feature = "tiny"), derive(Debug))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct OwnerId(NonZeroUsize);
+
+impl OwnerId {
+    pub const fn new(id: NonZeroUsize) -> Self {
+        Self(id)
+    }
+
+    pub const fn get(self) -> usize {
+        self.0.get()
+    }
+}
+
+/// Kernel hook returning the id of the current thread or core.
+pub trait OwnerIdProvider {
+    fn current() -> OwnerId;
+}
+
+static PROVIDER: HookCell<fn() -> OwnerId> = HookCell::new();
+
+/// Registers the owner-id provider. Call once during bring-up.
+pub fn set_owner_id_provider<P: OwnerIdProvider>() -> Result<(), SetHookError> {
+    PROVIDER.set(P::current)
+}
+
+/// Scheduler hook telling whether a context is executing on some CPU.
+pub trait OwnerRunning {
+    /// Returns `false` if `owner` has been preempted or is blocked. Only a
+    /// hint; a stale answer just makes a waiter give up spinning early or
+    /// late.
+    fn is_running(owner: OwnerId) -> bool;
+}
+
+static RUNNING: HookCell<fn(OwnerId) -> bool> = HookCell::new();
+
+/// Registers the owner-running hook. Call once during bring-up.
+///
+/// Until one is registered every owner counts as running, which is all a
+/// `std` build can tell.
+pub fn set_owner_running<R: OwnerRunning>() -> Result<(), SetHookError> {
+    RUNNING.set(R::is_running)
+}
+
+#[cfg(feature = "owner-tracking")]
+#[inline]
+pub(crate) fn is_running(owner: OwnerId) -> bool {
+    RUNNING.get().is_none_or(|is_running| is_running(owner))
+}
+
+/// Returns the id of the current context, or `None` if there is no way to
+/// tell (no provider registered on a `no_std` build).
+#[inline]
+pub fn current() -> Option<OwnerId> {
+    if let Some(current) = PROVIDER.get() {
+        return Some(current());
+    }
+    #[cfg(feature = "std")]
+    {
+        #[cfg(not(any(loom, shuttle)))]
+        std::thread_local!(static ANCHOR: u8 = const { 0 });
+        // The model checkers run all their threads on one real thread.
+        #[cfg(loom)]
+        loom::thread_local!(static ANCHOR: u8 = 0);
+        #[cfg(shuttle)]
+        shuttle::thread_local!(static ANCHOR: u8 = const { 0 });
+        // The address of a thread-local is unique among live threads and
+        // never null.
+        ANCHOR.with(|anchor| NonZeroUsize::new(anchor as *const u8 as usize).map(OwnerId))
+    }
+    #[cfg(not(feature = "std"))]
+    None
+}
@@ -0,0 +1,199 @@
+//! Keeping independent locks off each other's cache lines.
+//!
+//! Locks packed into an array share cache lines, so spinning on one shard
+//! bounces the line holding its neighbours. [`CachePadded`] aligns (and thus
+//! pads) a value to the coherence granule of the target: 128 bytes on x86_64
+//! and AArch64, whose adjacent-line prefetchers pull lines in pairs, and 64
+//! bytes elsewhere.
+//!
+//! [`SplitSpinLock`] is the other half of the problem: a single lock around
+//! a large, hot structure, where waiters polling the lock word would
+//! otherwise keep invalidating the line the owner is writing.
+
+use core::ops::Deref;
+use core::ops::DerefMut;
+
+use crate::mutex::Lock;
+use crate::mutex::RawSpinLock;
+use crate::mutex::RawSpinLockGuard;
+use crate::rwlock::ReadWriteLock;
+
+/// Pads and aligns `T` to a whole cache line.
+///
+/// Dereferences to the wrapped value, so a padded lock keeps the full API of
+/// the lock it wraps, and it implements [`Lock`] and [`ReadWriteLock`]
+/// whenever the wrapped type does.
+#[derive(Default)]
+#[cfg_attr(not(feature = "tiny"), derive(Debug))]
+#[cfg_attr(
+    any(target_arch = "x86_64", target_arch = "aarch64"),
+    repr(C, align(128))
+)]
+#[cfg_attr(
+    not(any(target_arch = "x86_64", target_arch = "aarch64")),
+    repr(C, align(64))
+)]
+pub struct CachePadded<T> {
+    value: T,
+}
+
+/// A [`RawSpinLock`] on a cache line of its own, for per-shard lock arrays.
+pub type PaddedSpinLock<T> = CachePadded<RawSpinLock<T>>;
+
+/// A spinlock whose lock word sits on its own cache line, ahead of the data.
+///
+/// This costs up to a cache line per lock, so only use it when the payload
+/// spans several lines and is written heavily under contention; small
+/// payloads are better served by the compact [`RawSpinLock`].
+pub struct SplitSpinLock<T> {
+    // `RawSpinLock` is `repr(C)` with the lock word first, so aligning the
+    // data pushes it to the start of the next line.
+    inner: RawSpinLock<CachePadded<T>>,
+}
+
+pub struct SplitSpinLockGuard<'a, T> {
+    inner: RawSpinLockGuard<'a, CachePadded<T>>,
+}
+
+const LINE: usize = align_of::<CachePadded<u8>>();
+
+const _: () = {
+    assert!(LINE >= 64);
+    assert!(size_of::<CachePadded<u8>>() == LINE);
+};
+
+const _: () = {
+    crate::mutex::assert_unwind_safe::<SplitSpinLock<core::cell::Cell<u8>>>();
+    crate::mutex::assert_unwind_safe::<SplitSpinLockGuard<'_, &mut u8>>();
+};
+
+// With `stats` the counters take a line of their own, and the model
+// checkers' atomics are larger than real ones.
+#[cfg(not(any(loom, shuttle, feature = "stats")))]
+const _: () = {
+    assert!(size_of::<PaddedSpinLock<()>>() == LINE);
+    // Lock word in the first line, data from the second one on.
+    assert!(size_of::<SplitSpinLock<u8>>() == 2 * LINE);
+    assert!(size_of::<SplitSpinLock<[u8; 256]>>() == LINE + 256);
+};
+
+impl<T> CachePadded<T> {
+    pub const fn new(value: T) -> Self {
+        Self { value }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+}
+
+impl<T> Deref for CachePadded<T> {
+    type Target = T;
+    fn deref(&self) -> &Self::Target {
+        &self.value
+    }
+}
+
+impl<T> DerefMut for CachePadded<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.value
+    }
+}
+
+impl<T: ?Sized, L: Lock<T>> Lock<T> for CachePadded<L> {
+    type Guard<'a>
+        = L::Guard<'a>
+    where
+        Self: 'a;
+
+    fn lock(&self) -> Self::Guard<'_> {
+        self.value.lock()
+    }
+
+    fn try_lock(&self) -> Option<Self::Guard<'_>> {
+        self.value.try_lock()
+    }
+}
+
+impl<T: ?Sized, L: ReadWriteLock<T>> ReadWriteLock<T> for CachePadded<L> {
+    type ReadGuard<'a>
+        = L::ReadGuard<'a>
+    where
+        Self: 'a;
+    type WriteGuard<'a>
+        = L::WriteGuard<'a>
+    where
+        Self: 'a;
+
+    fn read(&self) -> Self::ReadGuard<'_> {
+        self.value.read()
+    }
+
+    fn try_read(&self) -> Option<Self::ReadGuard<'_>> {
+        self.value.try_read()
+    }
+
+    fn write(&self) -> Self::WriteGuard<'_> {
+        self.value.write()
+    }
+
+    fn try_write(&self) -> Option<Self::WriteGuard<'_>> {
+        self.value.try_write()
+    }
+}
+
+impl<T> SplitSpinLock<T> {
+    pub const fn new(data: T) -> Self {
+        Self {
+            inner: RawSpinLock::new(CachePadded::new(data)),
+        }
+    }
+
+    pub fn lock(&self) -> SplitSpinLockGuard<'_, T> {
+        SplitSpinLockGuard {
+            inner: self.inner.lock(),
+        }
+    }
+
+    pub fn try_lock(&self) -> Option<SplitSpinLockGuard<'_, T>> {
+        self.inner
+            .try_lock()
+            .map(|inner| SplitSpinLockGuard { inner })
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner.into_inner().into_inner()
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        self.inner.get_mut()
+    }
+}
+
+impl<T> Deref for SplitSpinLockGuard<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &Self::Target {
+        &self.inner
+    }
+}
+
+impl<T> DerefMut for SplitSpinLockGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.inner
+    }
+}
+
+impl<T> Lock<T> for SplitSpinLock<T> {
+    type Guard<'a>
+        = SplitSpinLockGuard<'a, T>
+    where
+        T: 'a;
+
+    fn lock(&self) -> Self::Guard<'_> {
+        SplitSpinLock::lock(self)
+    }
+
+    fn try_lock(&self) -> Option<Self::Guard<'_>> {
+        SplitSpinLock::try_lock(self)
+    }
+}
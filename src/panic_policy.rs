@@ -0,0 +1,76 @@
+//! What happens when a thread panics while holding a lock.
+//!
+//! Unwinding out of a critical section leaves the data as the panic found
+//! it. [`compat::Mutex`](crate::compat::Mutex) poisons the lock so the next
+//! holder can tell; a kernel that cannot recover from that would rather stop
+//! on the spot. With the `panic-policy` feature, a [`RawSpinLockGuard`]
+//! dropped while its thread panics first calls the handler registered with
+//! [`set_panic_while_locked`], with a [`LockedPanic`] naming the lock,
+//! which it still holds. The handler can [`abort`], dump the
+//! [`registry`](crate::registry), or return, in which case the unwind goes
+//! on, the lock is released and a `compat::Mutex` on top of it is poisoned
+//! as usual, so the two can be combined. Without a handler nothing happens.
+//!
+//! Only guards dropped by unwinding are seen; with `panic = "abort"` no
+//! guard is dropped, and the panic handler is the place to report held
+//! locks, for example through [`held`](crate::held).
+//!
+//! [`RawSpinLockGuard`]: crate::mutex::RawSpinLockGuard
+
+use core::fmt;
+#[cfg(feature = "track-location")]
+use core::panic::Location;
+use std::io::Write;
+
+use crate::hook::HookCell;
+use crate::hook::SetHookError;
+
+/// A lock whose guard was dropped by a panic.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LockedPanic {
+    /// Address of the lock word.
+    pub lock: usize,
+    /// The registry name, or else the lockdep class name, if any.
+    pub name: Option<&'static str>,
+    /// Where the lock was last acquired, which is where the panicking
+    /// thread took it unless the guard came from before raw atomics.
+    #[cfg(feature = "track-location")]
+    pub last_acquired_at: Option<&'static Location<'static>>,
+}
+
+impl fmt::Display for LockedPanic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "panicked while holding lock {:#x}", self.lock)?;
+        if let Some(name) = self.name {
+            write!(f, " ({name})")?;
+        }
+        #[cfg(feature = "track-location")]
+        if let Some(location) = self.last_acquired_at {
+            write!(f, " acquired at {location}")?;
+        }
+        Ok(())
+    }
+}
+
+static HANDLER: HookCell<fn(&LockedPanic)> = HookCell::new();
+
+/// Registers the handler called when a guard is dropped during a panic.
+pub fn set_panic_while_locked(handler: fn(&LockedPanic)) -> Result<(), SetHookError> {
+    HANDLER.set(handler)
+}
+
+/// A handler that writes `info` to standard error and aborts the process.
+pub fn abort(info: &LockedPanic) {
+    let _ = writeln!(std::io::stderr(), "{info}");
+    std::process::abort();
+}
+
+/// Calls the handler if one is registered and the thread is panicking.
+#[inline]
+pub(crate) fn check(info: impl FnOnce() -> LockedPanic) {
+    if let Some(handler) = HANDLER.get() {
+        if std::thread::panicking() {
+            handler(&info());
+        }
+    }
+}
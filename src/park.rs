@@ -0,0 +1,316 @@
+//! Blocking instead of spinning once the scheduler is up.
+//!
+//! With a [`Parker`] registered, a [`RawSpinLock::lock`] that has spun for a
+//! while without success blocks through the hook instead, and unlock wakes a
+//! blocked waiter. The only bookkeeping in the lock itself is a "parked"
+//! state in the lock word; the wait queues belong to the parker and are
+//! keyed by the lock's address. Until a parker is registered locks spin as
+//! before.
+//!
+//! Register the parker before the locks are contended. A waiter blocks only
+//! if the holder's unlock observes the parker too.
+//!
+//! [`RawSpinLock::lock`]: crate::mutex::RawSpinLock::lock
+
+use crate::hook::HookCell;
+use crate::hook::SetHookError;
+
+/// Scheduler hook that blocks and wakes threads waiting on a lock word.
+pub trait Parker {
+    /// Blocks the current thread on `addr` while `validate()` returns `true`.
+    ///
+    /// Implementations must enqueue the thread before calling `validate`, so
+    /// that an [`unpark_one`](Self::unpark_one) racing with it is not lost.
+    /// Spurious wakeups are allowed.
+    fn park(addr: usize, validate: &dyn Fn() -> bool);
+
+    /// Wakes one thread blocked on `addr`, if any.
+    fn unpark_one(addr: usize);
+}
+
+#[derive(Clone, Copy)]
+struct Hooks {
+    park: fn(usize, &dyn Fn() -> bool),
+    unpark_one: fn(usize),
+}
+
+static PARKER: HookCell<Hooks> = HookCell::new();
+
+/// Registers the parker. Call once, before the locks see contention.
+pub fn set_parker<P: Parker>() -> Result<(), SetHookError> {
+    PARKER.set(Hooks {
+        park: P::park,
+        unpark_one: P::unpark_one,
+    })
+}
+
+#[inline(always)]
+pub(crate) fn is_registered() -> bool {
+    PARKER.get().is_some()
+}
+
+pub(crate) fn park(addr: usize, validate: &dyn Fn() -> bool) {
+    if let Some(hooks) = PARKER.get() {
+        (hooks.park)(addr, validate);
+    }
+}
+
+pub(crate) fn unpark_one(addr: usize) {
+    if let Some(hooks) = PARKER.get() {
+        (hooks.unpark_one)(addr);
+    }
+}
+
+/// Reference [`Parker`] built on `std::thread::park`/`unpark`.
+#[cfg(feature = "std")]
+pub struct StdParker;
+
+#[cfg(feature = "std")]
+mod std_parker {
+    use std::sync::Mutex;
+    use std::sync::PoisonError;
+    use std::thread;
+    use std::thread::Thread;
+    use std::vec::Vec;
+
+    use super::Parker;
+    use super::StdParker;
+
+    const BUCKETS: usize = 64;
+
+    static QUEUES: [Mutex<Vec<(usize, Thread)>>; BUCKETS] =
+        [const { Mutex::new(Vec::new()) }; BUCKETS];
+
+    fn queue(addr: usize) -> &'static Mutex<Vec<(usize, Thread)>> {
+        // Locks are at least byte-aligned and usually further apart, so skip
+        // the low bits before picking a bucket.
+        &QUEUES[(addr >> 3) % BUCKETS]
+    }
+
+    impl Parker for StdParker {
+        fn park(addr: usize, validate: &dyn Fn() -> bool) {
+            let queue = queue(addr);
+            let current = thread::current();
+            let id = current.id();
+            queue
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .push((addr, current));
+            if validate() {
+                // A racing `unpark_one` leaves the token set, so this returns.
+                thread::park();
+            }
+            queue
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .retain(|(_, thread)| thread.id() != id);
+        }
+
+        fn unpark_one(addr: usize) {
+            let mut waiters = queue(addr).lock().unwrap_or_else(PoisonError::into_inner);
+            if let Some(index) = waiters.iter().position(|(waiting, _)| *waiting == addr) {
+                waiters.swap_remove(index).1.unpark();
+            }
+        }
+    }
+}
+
+/// [`Parker`] that blocks in the operating system's wait-on-address call:
+/// `futex` on Linux and Android, `WaitOnAddress` on Windows and
+/// `__ulock_wait` on macOS and iOS. On other systems it yields instead of
+/// blocking, so waiters spin through `std::thread::yield_now`.
+///
+/// Those calls compare a 32-bit word, or on Windows a word of any size, but
+/// a lock word is a byte that may share its aligned word with other data.
+/// So waiters instead wait on one of a fixed set of buckets, picked by lock
+/// address, each a waiter count and an epoch to wait on. [`unpark_one`]
+/// bumps the epoch and makes the system call only if the bucket has
+/// waiters, and wakes all of them, since the kernel cannot tell which are
+/// waiting for the lock being released: the others find their lock still
+/// held and block again. Unlike [`StdParker`] it takes no lock of its own.
+///
+/// [`unpark_one`]: Parker::unpark_one
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+pub struct OsParker;
+
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+mod os_parker {
+    // The kernel reads these words itself, so they are `core`'s atomics
+    // even under the model checkers' cfgs.
+    use core::sync::atomic::AtomicU32;
+    use core::sync::atomic::Ordering;
+
+    use super::OsParker;
+    use super::Parker;
+
+    const BUCKETS: usize = 64;
+
+    struct Bucket {
+        waiters: AtomicU32,
+        epoch: AtomicU32,
+    }
+
+    static BUCKETS_BY_ADDR: [Bucket; BUCKETS] = [const {
+        Bucket {
+            waiters: AtomicU32::new(0),
+            epoch: AtomicU32::new(0),
+        }
+    }; BUCKETS];
+
+    fn bucket(addr: usize) -> &'static Bucket {
+        &BUCKETS_BY_ADDR[(addr >> 3) % BUCKETS]
+    }
+
+    impl Parker for OsParker {
+        fn park(addr: usize, validate: &dyn Fn() -> bool) {
+            let bucket = bucket(addr);
+            // Counting ourselves before reading the epoch, both SeqCst, pairs
+            // with `unpark_one` bumping the epoch before reading the count:
+            // either it sees us and wakes the bucket, or we read the new epoch,
+            // after the unlock, and `validate` fails.
+            bucket.waiters.fetch_add(1, Ordering::SeqCst);
+            let epoch = bucket.epoch.load(Ordering::SeqCst);
+            if validate() {
+                // Returns at once if the epoch has moved on since.
+                sys::wait(&bucket.epoch, epoch);
+            }
+            bucket.waiters.fetch_sub(1, Ordering::Relaxed);
+        }
+
+        fn unpark_one(addr: usize) {
+            let bucket = bucket(addr);
+            bucket.epoch.fetch_add(1, Ordering::SeqCst);
+            if bucket.waiters.load(Ordering::SeqCst) != 0 {
+                sys::wake_all(&bucket.epoch);
+            }
+        }
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    mod sys {
+        use core::ptr;
+        use core::sync::atomic::AtomicU32;
+
+        pub(super) fn wait(word: &AtomicU32, expected: u32) {
+            // SAFETY: `word` is a live, aligned 32-bit word, and a null
+            // timeout waits indefinitely. Errors, such as the word having
+            // changed, are spurious wakeups.
+            unsafe {
+                libc::syscall(
+                    libc::SYS_futex,
+                    word.as_ptr(),
+                    libc::FUTEX_WAIT | libc::FUTEX_PRIVATE_FLAG,
+                    expected,
+                    ptr::null::<libc::timespec>(),
+                );
+            }
+        }
+
+        pub(super) fn wake_all(word: &AtomicU32) {
+            // SAFETY: as for `wait`; waking reads nothing through the pointer.
+            unsafe {
+                libc::syscall(
+                    libc::SYS_futex,
+                    word.as_ptr(),
+                    libc::FUTEX_WAKE | libc::FUTEX_PRIVATE_FLAG,
+                    i32::MAX,
+                );
+            }
+        }
+    }
+
+    #[cfg(windows)]
+    mod sys {
+        use core::ffi::c_void;
+        use core::sync::atomic::AtomicU32;
+
+        const INFINITE: u32 = u32::MAX;
+
+        #[link(name = "synchronization")]
+        unsafe extern "system" {
+            fn WaitOnAddress(
+                address: *const c_void,
+                compare: *const c_void,
+                size: usize,
+                milliseconds: u32,
+            ) -> i32;
+            fn WakeByAddressAll(address: *const c_void);
+        }
+
+        pub(super) fn wait(word: &AtomicU32, expected: u32) {
+            // SAFETY: both pointers are to live 4-byte values; failure is a
+            // spurious wakeup.
+            unsafe {
+                WaitOnAddress(
+                    word.as_ptr().cast(),
+                    (&raw const expected).cast(),
+                    4,
+                    INFINITE,
+                );
+            }
+        }
+
+        pub(super) fn wake_all(word: &AtomicU32) {
+            // SAFETY: `word` is live.
+            unsafe { WakeByAddressAll(word.as_ptr().cast()) }
+        }
+    }
+
+    #[cfg(any(target_os = "macos", target_os = "ios"))]
+    mod sys {
+        use core::ffi::c_int;
+        use core::ffi::c_void;
+        use core::sync::atomic::AtomicU32;
+
+        // From the XNU headers; available since macOS 10.12 and iOS 10.
+        const UL_COMPARE_AND_WAIT: u32 = 1;
+        const ULF_WAKE_ALL: u32 = 0x100;
+        const ULF_NO_ERRNO: u32 = 0x0100_0000;
+
+        unsafe extern "C" {
+            fn __ulock_wait(operation: u32, addr: *mut c_void, value: u64, timeout: u32) -> c_int;
+            fn __ulock_wake(operation: u32, addr: *mut c_void, wake_value: u64) -> c_int;
+        }
+
+        pub(super) fn wait(word: &AtomicU32, expected: u32) {
+            // SAFETY: `word` is a live, aligned 32-bit word, and a timeout
+            // of 0 waits indefinitely. Errors are spurious wakeups.
+            unsafe {
+                __ulock_wait(
+                    UL_COMPARE_AND_WAIT | ULF_NO_ERRNO,
+                    word.as_ptr().cast(),
+                    u64::from(expected),
+                    0,
+                );
+            }
+        }
+
+        pub(super) fn wake_all(word: &AtomicU32) {
+            // SAFETY: as for `wait`.
+            unsafe {
+                __ulock_wake(
+                    UL_COMPARE_AND_WAIT | ULF_WAKE_ALL | ULF_NO_ERRNO,
+                    word.as_ptr().cast(),
+                    0,
+                );
+            }
+        }
+    }
+
+    #[cfg(not(any(
+        target_os = "linux",
+        target_os = "android",
+        windows,
+        target_os = "macos",
+        target_os = "ios",
+    )))]
+    mod sys {
+        use core::sync::atomic::AtomicU32;
+
+        pub(super) fn wait(_word: &AtomicU32, _expected: u32) {
+            std::thread::yield_now();
+        }
+
+        pub(super) fn wake_all(_word: &AtomicU32) {}
+    }
+}
@@ -0,0 +1,55 @@
+//! Checks that locks sit in memory where atomic instructions work.
+//!
+//! On some platforms atomic read-modify-write instructions only work on
+//! normal cacheable memory, and a lock placed in a device mapping faults in
+//! ways that look like random corruption. With the `validate-placement`
+//! feature, a registered [`LockableMemory`] hook is asked about each
+//! [`RawSpinLock`](crate::mutex::RawSpinLock) the first time it is
+//! acquired atomically, and a lock it rejects panics with its address.
+//! Without the feature, registering the hook does nothing.
+
+#[cfg(feature = "validate-placement")]
+use crate::hook::HookCell;
+use crate::hook::SetHookError;
+
+/// Kernel hook telling whether atomics work on the memory at an address.
+pub trait LockableMemory {
+    fn is_lockable(addr: usize) -> bool;
+}
+
+#[cfg(feature = "validate-placement")]
+static LOCKABLE: HookCell<fn(usize) -> bool> = HookCell::new();
+
+/// Registers the hook. Call once during bring-up.
+pub fn set_lockable_memory<M: LockableMemory>() -> Result<(), SetHookError> {
+    #[cfg(feature = "validate-placement")]
+    LOCKABLE.set(M::is_lockable)?;
+    Ok(())
+}
+
+/// Checks the lock word at `addr`, returning whether a hook was there to
+/// ask, so that callers only cache the answer once it means something.
+#[cfg(feature = "validate-placement")]
+#[track_caller]
+#[inline(always)]
+pub(crate) fn check(addr: usize) -> bool {
+    match LOCKABLE.get() {
+        Some(is_lockable) => {
+            if !is_lockable(addr) {
+                misplaced(addr);
+            }
+            true
+        }
+        None => false,
+    }
+}
+
+#[cfg(feature = "validate-placement")]
+#[cold]
+#[track_caller]
+fn misplaced(addr: usize) -> ! {
+    panic!(
+        "lock {addr:#x} acquired at {} is in memory that does not support atomics",
+        core::panic::Location::caller()
+    );
+}
@@ -0,0 +1,50 @@
+//! Preemption control while a spinlock is held.
+//!
+//! If the scheduler preempts a lock holder, every other core spins for the
+//! rest of its timeslice. With a [`PreemptionControl`] hook registered, every
+//! real acquisition of a [`RawSpinLock`](crate::mutex::RawSpinLock) or
+//! [`RwSpinLock`](crate::rwlock::RwSpinLock) disables preemption once the lock
+//! is held, and the guard re-enables it after releasing, including when the
+//! guard is dropped during unwinding. Nesting is left to the kernel's own
+//! preemption counter. Guards that do not actually hold the lock (before
+//! raw atomics are enabled, or from `no_lock`) do not call the hook.
+
+use crate::hook::HookCell;
+use crate::hook::SetHookError;
+
+/// Kernel hook that disables and re-enables preemption on the current core.
+pub trait PreemptionControl {
+    fn disable();
+
+    fn enable();
+}
+
+#[derive(Clone, Copy)]
+struct Hooks {
+    disable: fn(),
+    enable: fn(),
+}
+
+static PREEMPTION_CONTROL: HookCell<Hooks> = HookCell::new();
+
+/// Registers the preemption-control hook. Call once during bring-up.
+pub fn set_preemption_control<P: PreemptionControl>() -> Result<(), SetHookError> {
+    PREEMPTION_CONTROL.set(Hooks {
+        disable: P::disable,
+        enable: P::enable,
+    })
+}
+
+#[inline(always)]
+pub(crate) fn disable() {
+    if let Some(hooks) = PREEMPTION_CONTROL.get() {
+        (hooks.disable)();
+    }
+}
+
+#[inline(always)]
+pub(crate) fn enable() {
+    if let Some(hooks) = PREEMPTION_CONTROL.get() {
+        (hooks.enable)();
+    }
+}
@@ -0,0 +1,13 @@
+//! The types most code needs, for a glob import:
+//! `use mutex::prelude::*;`.
+
+pub use crate::mutex::Lock;
+pub use crate::mutex::RawSpinLock;
+pub use crate::mutex::RawSpinLockGuard;
+pub use crate::raw::AtomicsEnabled;
+pub use crate::raw::enable_raw_atomics;
+pub use crate::raw::raw_atomics_enabled;
+pub use crate::rwlock::ReadWriteLock;
+pub use crate::rwlock::RwSpinLock;
+pub use crate::rwlock::RwSpinLockReadGuard;
+pub use crate::rwlock::RwSpinLockWriteGuard;
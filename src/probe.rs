@@ -0,0 +1,143 @@
+//! A runtime check that atomic read-modify-write instructions work.
+//!
+//! Whether they do can depend on configuration the crate cannot see, such
+//! as the memory attributes the MMU assigns or whether the exclusive
+//! monitor is enabled; when they do not, the first lock taken after
+//! [`enable_raw_atomics`] faults or silently loses updates.
+//! [`verify_atomics_supported`] tries the operations the locks use on a
+//! word on the stack and one in a `static` and checks their results, so
+//! boot code can fail at a known point instead, and
+//! [`enable_raw_atomics_verified`] opens the gate only if the probe passes.
+//!
+//! A fault taken by an instruction is only caught if a [`CatchFaults`] hook
+//! is registered; otherwise it surfaces as it would have at a lock.
+//!
+//! [`enable_raw_atomics`]: crate::raw::enable_raw_atomics
+//! [`enable_raw_atomics_verified`]: crate::raw::enable_raw_atomics_verified
+
+#[cfg(not(feature = "tiny"))]
+use core::fmt;
+
+use crate::atomic::Ordering;
+use crate::atomic::global::AtomicU8;
+use crate::atomic::global::AtomicUsize;
+use crate::hook::HookCell;
+use crate::hook::SetHookError;
+
+/// Why [`verify_atomics_supported`] failed.
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(not(feature = "tiny"), derive(Debug))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum AtomicsProbeError {
+    /// A compare-exchange succeeded or failed when it should not have, or
+    /// stored the wrong value.
+    CompareExchange,
+    /// A swap returned or stored the wrong value.
+    Swap,
+    /// A `fetch_add` returned or stored the wrong value.
+    FetchAdd,
+    /// A `fetch_and` returned or stored the wrong value.
+    FetchAnd,
+    /// The [`CatchFaults`] hook caught a fault in the probe.
+    Faulted,
+}
+
+#[cfg(not(feature = "tiny"))]
+impl fmt::Display for AtomicsProbeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::CompareExchange => f.write_str("atomic compare-exchange gave a wrong result"),
+            Self::Swap => f.write_str("atomic swap gave a wrong result"),
+            Self::FetchAdd => f.write_str("atomic fetch_add gave a wrong result"),
+            Self::FetchAnd => f.write_str("atomic fetch_and gave a wrong result"),
+            Self::Faulted => f.write_str("atomic instruction faulted"),
+        }
+    }
+}
+
+#[cfg(not(feature = "tiny"))]
+impl core::error::Error for AtomicsProbeError {}
+
+/// Kernel hook running the probe with synchronous faults caught.
+pub trait CatchFaults {
+    /// Runs `probe` and returns its result, or
+    /// [`AtomicsProbeError::Faulted`] if it took a synchronous fault, such
+    /// as a data abort on an exclusive access.
+    fn catch_faults(probe: fn() -> Result<(), AtomicsProbeError>) -> Result<(), AtomicsProbeError>;
+}
+
+type Catcher = fn(fn() -> Result<(), AtomicsProbeError>) -> Result<(), AtomicsProbeError>;
+
+static CATCH_FAULTS: HookCell<Catcher> = HookCell::new();
+
+/// Registers the fault-catching hook. Call once during bring-up.
+pub fn set_catch_faults<C: CatchFaults>() -> Result<(), SetHookError> {
+    CATCH_FAULTS.set(C::catch_faults)
+}
+
+/// The `static` probe word, back at 0 after every probe.
+static STATIC_WORD: AtomicUsize = AtomicUsize::new(0);
+
+/// A bit pattern that a lost or torn update cannot produce by accident.
+const PATTERN: usize = usize::MAX / 0xff * 0x5a;
+
+/// Runs atomic compare-exchange, swap, `fetch_add` and `fetch_and`
+/// operations on test words and checks their results, through the
+/// [`CatchFaults`] hook if one is registered.
+///
+/// Can be called before or after raw atomics are enabled, although only a
+/// call made before is of any use. It leaves no state behind and does not
+/// open the gate.
+pub fn verify_atomics_supported() -> Result<(), AtomicsProbeError> {
+    match CATCH_FAULTS.get() {
+        Some(catch_faults) => catch_faults(probe),
+        None => probe(),
+    }
+}
+
+fn probe() -> Result<(), AtomicsProbeError> {
+    let stack_word = AtomicUsize::new(0);
+    probe_word(&stack_word)?;
+    let result = probe_word(&STATIC_WORD);
+    STATIC_WORD.store(0, Ordering::Relaxed);
+    result?;
+    // Lock words are bytes, which some targets only update through a
+    // masked access to the surrounding word.
+    probe_byte(&AtomicU8::new(0))
+}
+
+fn probe_word(word: &AtomicUsize) -> Result<(), AtomicsProbeError> {
+    check(
+        word.compare_exchange(0, PATTERN, Ordering::AcqRel, Ordering::Acquire) == Ok(0)
+            && word.compare_exchange(0, 1, Ordering::AcqRel, Ordering::Acquire) == Err(PATTERN)
+            && word.load(Ordering::Acquire) == PATTERN,
+        AtomicsProbeError::CompareExchange,
+    )?;
+    check(
+        word.fetch_add(1, Ordering::AcqRel) == PATTERN
+            && word.load(Ordering::Acquire) == PATTERN + 1,
+        AtomicsProbeError::FetchAdd,
+    )?;
+    check(
+        word.fetch_and(!0xff, Ordering::AcqRel) == PATTERN + 1
+            && word.load(Ordering::Acquire) == PATTERN & !0xff,
+        AtomicsProbeError::FetchAnd,
+    )
+}
+
+fn probe_byte(byte: &AtomicU8) -> Result<(), AtomicsProbeError> {
+    check(
+        byte.compare_exchange(0, 0x5a, Ordering::AcqRel, Ordering::Acquire) == Ok(0)
+            && byte.compare_exchange(0, 1, Ordering::AcqRel, Ordering::Acquire) == Err(0x5a),
+        AtomicsProbeError::CompareExchange,
+    )?;
+    check(
+        byte.swap(0xa5, Ordering::AcqRel) == 0x5a && byte.load(Ordering::Acquire) == 0xa5,
+        AtomicsProbeError::Swap,
+    )
+}
+
+fn check(ok: bool, error: AtomicsProbeError) -> Result<(), AtomicsProbeError> {
+    if ok { Ok(()) } else { Err(error) }
+}
@@ -0,0 +1,243 @@
+//! Guards for one part of a lock's data.
+//!
+//! [`RawSpinLockGuard::map`], [`RwSpinLockReadGuard::map`] and
+//! [`RwSpinLockWriteGuard::map`] narrow a guard to a part of the data, such
+//! as a field, keeping the original guard inside so the lock is released as
+//! usual. Unlike `RawSpinLockGuard::map_split` nothing is shared, so
+//! nothing is allocated.
+//!
+//! [`RawSpinLock::project`] makes the narrowing last: its [`SubLock`] stores
+//! the projection and maps every guard it hands out, so a driver given one
+//! can only ever reach its own field.
+//!
+//! With the `derive` feature, `#[derive(LockProject)]` on a struct generates
+//! `lock_foo`, `read_foo` and `write_foo` accessors for its fields on locks
+//! holding it; see `LockProject` in this module.
+
+#[cfg(not(feature = "tiny"))]
+use core::fmt;
+use core::ops::Deref;
+use core::ops::DerefMut;
+use core::ptr::NonNull;
+
+/// ```
+/// use mutex::RawSpinLock;
+/// use mutex::RwSpinLock;
+/// use mutex::project::LockProject;
+///
+/// #[derive(LockProject)]
+/// struct Device {
+///     interrupts: u64,
+///     #[lock_project(skip)]
+///     model: &'static str,
+/// }
+///
+/// mutex::enable_raw_atomics();
+/// let device = RawSpinLock::new(Device { interrupts: 0, model: "uart16550" });
+/// // Through the generated `DeviceLockProject` trait.
+/// *device.lock_interrupts() += 1;
+/// assert_eq!(device.lock().interrupts, 1);
+///
+/// let shared = RwSpinLock::new(device.into_inner());
+/// // Through `DeviceRwLockProject`.
+/// *shared.write_interrupts() += 1;
+/// assert_eq!(*shared.read_interrupts(), 2);
+/// assert_eq!(shared.read().model, "uart16550");
+/// ```
+#[cfg(feature = "derive")]
+pub use mutex_derive::LockProject;
+
+use crate::mutex::Lock;
+use crate::mutex::RawSpinLock;
+use crate::mutex::RawSpinLockGuard;
+use crate::rwlock::RwSpinLockReadGuard;
+use crate::rwlock::RwSpinLockWriteGuard;
+
+/// A [`RawSpinLockGuard`] narrowed to part of the data by
+/// [`RawSpinLockGuard::map`].
+pub struct MappedRawSpinLockGuard<'a, T, U: ?Sized> {
+    data: NonNull<U>,
+    _guard: RawSpinLockGuard<'a, T>,
+}
+
+/// A [`RwSpinLockReadGuard`] narrowed to part of the data by
+/// [`RwSpinLockReadGuard::map`].
+pub struct MappedRwSpinLockReadGuard<'a, T, U: ?Sized> {
+    data: NonNull<U>,
+    _guard: RwSpinLockReadGuard<'a, T>,
+}
+
+/// A [`RwSpinLockWriteGuard`] narrowed to part of the data by
+/// [`RwSpinLockWriteGuard::map`].
+pub struct MappedRwSpinLockWriteGuard<'a, T, U: ?Sized> {
+    data: NonNull<U>,
+    _guard: RwSpinLockWriteGuard<'a, T>,
+}
+
+/// A handle to part of a [`RawSpinLock`]'s data, from
+/// [`RawSpinLock::project`].
+///
+/// Its guards are [`MappedRawSpinLockGuard`]s over the parent's lock word,
+/// so they exclude the parent's own guards and those of every other
+/// `SubLock` on the same lock, even one over a disjoint field: two drivers
+/// each given a `SubLock` for their own field still take turns. Fields that
+/// should be locked independently need a lock each, such as a
+/// [`PaddedSpinLock`](crate::padded::PaddedSpinLock) per field so their
+/// lock words do not share a cache line either.
+pub struct SubLock<'a, T, U: ?Sized> {
+    parent: &'a RawSpinLock<T>,
+    project: fn(&mut T) -> &mut U,
+}
+
+impl<'a, T, U: ?Sized> SubLock<'a, T, U> {
+    pub(crate) fn new(parent: &'a RawSpinLock<T>, project: fn(&mut T) -> &mut U) -> Self {
+        Self { parent, project }
+    }
+
+    #[track_caller]
+    pub fn lock(&self) -> MappedRawSpinLockGuard<'a, T, U> {
+        RawSpinLockGuard::map(self.parent.lock(), self.project)
+    }
+
+    /// Attempts to acquire the parent lock without spinning.
+    pub fn try_lock(&self) -> Option<MappedRawSpinLockGuard<'a, T, U>> {
+        Some(RawSpinLockGuard::map(self.parent.try_lock()?, self.project))
+    }
+}
+
+impl<T, U: ?Sized> Clone for SubLock<'_, T, U> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T, U: ?Sized> Copy for SubLock<'_, T, U> {}
+
+#[cfg(not(feature = "tiny"))]
+impl<T, U: ?Sized> fmt::Debug for SubLock<'_, T, U> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("SubLock")
+            .field(&core::ptr::from_ref(self.parent))
+            .finish()
+    }
+}
+
+impl<'a, T, U: ?Sized> Lock<U> for SubLock<'a, T, U> {
+    type Guard<'b>
+        = MappedRawSpinLockGuard<'a, T, U>
+    where
+        Self: 'b;
+
+    #[track_caller]
+    fn lock(&self) -> Self::Guard<'_> {
+        SubLock::lock(self)
+    }
+
+    fn try_lock(&self) -> Option<Self::Guard<'_>> {
+        SubLock::try_lock(self)
+    }
+}
+
+// SAFETY: the guard only hands out the part, like a reference to it would.
+unsafe impl<T, U: ?Sized + Sync> Sync for MappedRawSpinLockGuard<'_, T, U> {}
+unsafe impl<T, U: ?Sized + Sync> Sync for MappedRwSpinLockReadGuard<'_, T, U> {}
+unsafe impl<T, U: ?Sized + Sync> Sync for MappedRwSpinLockWriteGuard<'_, T, U> {}
+
+impl<'a, T, U: ?Sized> MappedRawSpinLockGuard<'a, T, U> {
+    pub(crate) fn new(
+        mut guard: RawSpinLockGuard<'a, T>,
+        f: impl FnOnce(&mut T) -> &mut U,
+    ) -> Self {
+        // Points into the lock, not the guard, so moving the guard leaves it
+        // valid.
+        let data = NonNull::from(f(&mut guard));
+        Self {
+            data,
+            _guard: guard,
+        }
+    }
+}
+
+impl<'a, T, U: ?Sized> MappedRwSpinLockReadGuard<'a, T, U> {
+    pub(crate) fn new(guard: RwSpinLockReadGuard<'a, T>, f: impl FnOnce(&T) -> &U) -> Self {
+        // As above.
+        let data = NonNull::from(f(&guard));
+        Self {
+            data,
+            _guard: guard,
+        }
+    }
+}
+
+impl<'a, T, U: ?Sized> MappedRwSpinLockWriteGuard<'a, T, U> {
+    pub(crate) fn new(
+        mut guard: RwSpinLockWriteGuard<'a, T>,
+        f: impl FnOnce(&mut T) -> &mut U,
+    ) -> Self {
+        // As above.
+        let data = NonNull::from(f(&mut guard));
+        Self {
+            data,
+            _guard: guard,
+        }
+    }
+}
+
+impl<T, U: ?Sized> Deref for MappedRawSpinLockGuard<'_, T, U> {
+    type Target = U;
+    fn deref(&self) -> &U {
+        // SAFETY: the guard inside holds the lock.
+        unsafe { self.data.as_ref() }
+    }
+}
+
+impl<T, U: ?Sized> DerefMut for MappedRawSpinLockGuard<'_, T, U> {
+    fn deref_mut(&mut self) -> &mut U {
+        // SAFETY: as for `deref`, and the part was borrowed mutably.
+        unsafe { self.data.as_mut() }
+    }
+}
+
+impl<T, U: ?Sized> Deref for MappedRwSpinLockReadGuard<'_, T, U> {
+    type Target = U;
+    fn deref(&self) -> &U {
+        // SAFETY: the read guard inside keeps writers out.
+        unsafe { self.data.as_ref() }
+    }
+}
+
+impl<T, U: ?Sized> Deref for MappedRwSpinLockWriteGuard<'_, T, U> {
+    type Target = U;
+    fn deref(&self) -> &U {
+        // SAFETY: the write guard inside holds the lock.
+        unsafe { self.data.as_ref() }
+    }
+}
+
+impl<T, U: ?Sized> DerefMut for MappedRwSpinLockWriteGuard<'_, T, U> {
+    fn deref_mut(&mut self) -> &mut U {
+        // SAFETY: as for `deref`, and the part was borrowed mutably.
+        unsafe { self.data.as_mut() }
+    }
+}
+
+#[cfg(not(feature = "tiny"))]
+impl<T, U: ?Sized + fmt::Debug> fmt::Debug for MappedRawSpinLockGuard<'_, T, U> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+#[cfg(not(feature = "tiny"))]
+impl<T, U: ?Sized + fmt::Debug> fmt::Debug for MappedRwSpinLockReadGuard<'_, T, U> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+#[cfg(not(feature = "tiny"))]
+impl<T, U: ?Sized + fmt::Debug> fmt::Debug for MappedRwSpinLockWriteGuard<'_, T, U> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
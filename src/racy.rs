@@ -0,0 +1,151 @@
+//! A cell for data that is deliberately left unsynchronized during bring-up.
+//!
+//! Early boot code often keeps state, such as the boot core's id or a
+//! pointer to the firmware tables, in a `static` that nothing else touches
+//! until secondary cores start. A [`RacyCell`] holds such state and writes
+//! the intended window into the API: its safe [`read`](RacyCell::read) and
+//! [`write`](RacyCell::write) only work while raw atomics are still
+//! disabled, which is when a single core runs, and panic afterwards. A
+//! registered [`SingleCore`] hook is asked as well, so a secondary core
+//! started too early is caught too. Later accesses go through the unsafe
+//! [`get`](RacyCell::get) and [`get_mut`](RacyCell::get_mut), whose callers
+//! provide the synchronization.
+
+use core::cell::UnsafeCell;
+#[cfg(not(feature = "tiny"))]
+use core::fmt;
+use core::ptr;
+
+use crate::hook::HookCell;
+use crate::hook::SetHookError;
+use crate::raw::raw_atomics_enabled;
+
+/// Kernel hook telling whether only one core is running.
+pub trait SingleCore {
+    fn is_single_core() -> bool;
+}
+
+static SINGLE_CORE: HookCell<fn() -> bool> = HookCell::new();
+
+/// Registers the single-core hook. Call once during bring-up.
+///
+/// Until one is registered, the safe accessors of a [`RacyCell`] only check
+/// the raw-atomics gate.
+pub fn set_single_core<S: SingleCore>() -> Result<(), SetHookError> {
+    SINGLE_CORE.set(S::is_single_core)
+}
+
+/// Unsynchronized storage that may be shared between cores.
+///
+/// Unlike an `UnsafeCell`, it can be placed in a `static`. Nothing stops two
+/// cores from touching it at once; see the [module docs](self) for the
+/// window in which the safe accessors can be used.
+pub struct RacyCell<T> {
+    value: UnsafeCell<T>,
+}
+
+// SAFETY: the safe accessors only run while a single core does, and the
+// unsafe ones leave synchronization to their callers.
+unsafe impl<T: Send> Sync for RacyCell<T> {}
+
+impl<T> RacyCell<T> {
+    pub const fn new(value: T) -> Self {
+        Self {
+            value: UnsafeCell::new(value),
+        }
+    }
+
+    /// Returns a shared reference to the value.
+    ///
+    /// # Safety
+    ///
+    /// Nothing may write the value while the reference is alive, on this
+    /// core or any other.
+    pub unsafe fn get(&self) -> &T {
+        // SAFETY: the caller rules out writes.
+        unsafe { &*self.value.get() }
+    }
+
+    /// Returns an exclusive reference to the value.
+    ///
+    /// # Safety
+    ///
+    /// Nothing else may access the value while the reference is alive, on
+    /// this core or any other.
+    #[allow(clippy::mut_from_ref)]
+    pub unsafe fn get_mut(&self) -> &mut T {
+        // SAFETY: the caller rules out other accesses.
+        unsafe { &mut *self.value.get() }
+    }
+
+    /// Returns a copy of the value.
+    ///
+    /// # Panics
+    ///
+    /// Panics once raw atomics are enabled, or if the [`SingleCore`] hook
+    /// reports other cores running.
+    #[track_caller]
+    pub fn read(&self) -> T
+    where
+        T: Copy,
+    {
+        check_single_core("read");
+        // SAFETY: only this core runs, and no reference into the cell can
+        // outlive a safe accessor.
+        unsafe { *self.value.get() }
+    }
+
+    /// Replaces the value, dropping the old one.
+    ///
+    /// # Panics
+    ///
+    /// Panics like [`read`](Self::read).
+    #[track_caller]
+    pub fn write(&self, value: T) {
+        check_single_core("write");
+        // SAFETY: as in `read`. The old value is moved out before it is
+        // dropped, so its destructor may use the cell again.
+        drop(unsafe { ptr::replace(self.value.get(), value) });
+    }
+
+    pub fn into_inner(self) -> T {
+        self.value.into_inner()
+    }
+}
+
+impl<T: Default> Default for RacyCell<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+#[cfg(not(feature = "tiny"))]
+impl<T> fmt::Debug for RacyCell<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Reading the value could race, so it is not shown.
+        f.debug_struct("RacyCell").finish_non_exhaustive()
+    }
+}
+
+#[track_caller]
+#[inline(always)]
+fn check_single_core(access: &str) {
+    if raw_atomics_enabled() || SINGLE_CORE.get().is_some_and(|single| !single()) {
+        racy_access(access);
+    }
+}
+
+#[cold]
+#[track_caller]
+#[cfg_attr(feature = "tiny", allow(unused_variables))]
+fn racy_access(access: &str) -> ! {
+    // The fixed message still carries the caller's location in the
+    // `PanicInfo`.
+    #[cfg(feature = "tiny")]
+    panic!("RacyCell accessed while other cores may be running");
+    #[cfg(not(feature = "tiny"))]
+    panic!(
+        "RacyCell {access} at {} while other cores may be running",
+        core::panic::Location::caller()
+    );
+}
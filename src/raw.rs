@@ -0,0 +1,1082 @@
+//! The raw-atomics gate and the lock-word operations the locks are built on.
+//!
+//! Until [`enable_raw_atomics`] is called, the locks in this crate take no
+//! atomic read-modify-write instructions at all, so they can be used while
+//! the platform still forbids them. The lock-word helpers below are only
+//! called once the gate is open.
+//!
+//! The same algorithms are public for lock words the caller owns:
+//! [`lock`], [`try_lock`] and [`unlock`] on a `RawSpinLock`-style byte, and
+//! [`read_lock`], [`write_lock`] and their siblings on an `RwSpinLock`-style
+//! word, each returning a guard that unlocks on drop.
+
+#[cfg(feature = "track-location")]
+use core::panic::Location;
+
+#[cfg(feature = "track-location")]
+use crate::atomic::AtomicPtr;
+use crate::atomic::AtomicU8;
+use crate::atomic::AtomicUsize;
+use crate::atomic::Ordering;
+use crate::atomic::global;
+use crate::park;
+use crate::relax;
+use crate::relax::Backoff;
+
+// Only ever accessed with plain loads and stores, which are fine before the
+// platform allows atomic RMW instructions.
+static RAW_ATOMICS_ENABLED: global::AtomicBool = global::AtomicBool::new(false);
+
+#[inline]
+pub fn raw_atomics_enabled() -> bool {
+    #[cfg(feature = "static-key")]
+    if crate::static_key::patched() {
+        return true;
+    }
+    // `RAW_ATOMICS_ENABLED` is only written during single-std bring-up, and
+    // secondary stds are started after that point, so Relaxed is enough.
+    RAW_ATOMICS_ENABLED.load(Ordering::Relaxed)
+}
+
+/// Proof that raw atomics are enabled, for APIs that only make sense
+/// afterwards.
+///
+/// The token can only come from [`enable_raw_atomics`] or from
+/// [`get`](Self::get), which checks the gate. Since the gate never closes
+/// again, code that is handed a token can skip checking it, and code that
+/// threads the token through cannot call those APIs too early.
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(not(feature = "tiny"), derive(Debug))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct AtomicsEnabled {
+    _private: (),
+}
+
+impl AtomicsEnabled {
+    /// Returns a token if raw atomics are enabled.
+    #[inline]
+    pub fn get() -> Option<Self> {
+        raw_atomics_enabled().then_some(Self { _private: () })
+    }
+}
+
+/// Enables raw atomic operations globally for this crate.
+///
+/// Returns the [`AtomicsEnabled`] token for the rest of bring-up to pass
+/// along. With the `registry` feature, the registered
+/// [`CacheMaintenance`](crate::cache::CacheMaintenance) hook runs over every
+/// registered lock first. With the `static-key` feature, the registered
+/// `static_key::CodePatcher` then patches the gate checks.
+///
+/// # Invariants
+/// - Call only after paging/caches/memory attributes are enabled. If called too early,
+///   subsequent lock/atomic operations will execute atomic RMW instructions while the
+///   platform still forbids them, which can trap or lead to unpredictable memory behavior.
+/// - Call before secondary stds start and before any concurrent lock usage. If called
+///   concurrently with readers, some operations may remain non-atomic while others become
+///   atomic, leading to data races, lost updates, or aliasing UB.
+/// - This function is intentionally unsynchronized and must only run during single-std
+///   bring-up to avoid races with `raw_atomics_enabled()` readers.
+#[inline]
+pub fn enable_raw_atomics() -> AtomicsEnabled {
+    // Callers must uphold the bring-up sequencing and single-std invariants above.
+    #[cfg(feature = "audit")]
+    crate::audit::seal();
+    #[cfg(feature = "registry")]
+    crate::registry::prepare_for_atomics();
+    RAW_ATOMICS_ENABLED.store(true, Ordering::Relaxed);
+    // After the store, so that a site the patcher leaves alone finds the
+    // flag set.
+    #[cfg(feature = "static-key")]
+    crate::static_key::patch_all();
+    AtomicsEnabled { _private: () }
+}
+
+/// Runs [`verify_atomics_supported`](crate::probe::verify_atomics_supported)
+/// and enables raw atomics only if it passes.
+///
+/// The invariants of [`enable_raw_atomics`] apply unchanged; the probe only
+/// catches a platform that was not ready after all.
+pub fn enable_raw_atomics_verified() -> Result<AtomicsEnabled, crate::probe::AtomicsProbeError> {
+    crate::probe::verify_atomics_supported()?;
+    Ok(enable_raw_atomics())
+}
+
+#[cfg(test)]
+#[allow(dead_code)]
+#[inline]
+fn disable_raw_atomics() {
+    // Used only in tests to restore state.
+    RAW_ATOMICS_ENABLED.store(false, Ordering::Relaxed);
+}
+
+// The lock word encodings are public so that words read out of a memory
+// dump can be interpreted; `crate::state` decodes them.
+
+/// A `RawSpinLock` byte that nobody holds.
+pub const UNLOCKED: u8 = 0;
+/// A `RawSpinLock` byte that is held. Guards from before raw atomics were
+/// enabled mark the byte this way too.
+pub const LOCKED: u8 = 1;
+/// A `RawSpinLock` byte that is held while waiters may be blocked in the
+/// registered `Parker`; it only appears when one is registered.
+pub const PARKED: u8 = 2;
+
+/// Whether the contended lock loops retry with a strong compare-exchange.
+///
+/// On LL/SC targets (32-bit ARM and RISC-V) a weak CAS fails spuriously
+/// whenever the line bounces between the load-linked and the
+/// store-conditional, and under contention waiters spend most of their
+/// attempts on such failures; a strong CAS retries inside the instruction
+/// sequence instead. Elsewhere a CAS is a single instruction and the weak
+/// form costs nothing, so it stays the default. The `strong-cas` feature
+/// picks the strong form on every target, to compare the two with
+/// `cargo bench --bench lock`.
+pub const STRONG_CAS: bool = cfg!(any(
+    feature = "strong-cas",
+    target_arch = "arm",
+    target_arch = "riscv32",
+    target_arch = "riscv64"
+));
+
+/// The strength the contended loops use: [`STRONG_CAS`], except that unit
+/// tests can pick either through [`CAS_STRENGTH`].
+#[cfg(not(test))]
+#[inline(always)]
+const fn strong_cas() -> bool {
+    STRONG_CAS
+}
+
+/// 0 for [`STRONG_CAS`], 1 for the weak form, 2 for the strong form.
+#[cfg(test)]
+static CAS_STRENGTH: global::AtomicU8 = global::AtomicU8::new(0);
+
+#[cfg(test)]
+fn strong_cas() -> bool {
+    match CAS_STRENGTH.load(Ordering::Relaxed) {
+        1 => false,
+        2 => true,
+        _ => STRONG_CAS,
+    }
+}
+
+/// An acquiring compare-exchange of the strength [`STRONG_CAS`] picks, for
+/// the retries in contended loops.
+macro_rules! retry_cas {
+    ($atomic:expr, $current:expr, $new:expr) => {
+        if strong_cas() {
+            $atomic.compare_exchange($current, $new, Ordering::Acquire, Ordering::Relaxed)
+        } else {
+            $atomic.compare_exchange_weak($current, $new, Ordering::Acquire, Ordering::Relaxed)
+        }
+    };
+}
+
+/// Failed attempts a waiter spins for before blocking in the registered `Parker`.
+const SPINS_BEFORE_PARK: usize = 100;
+
+/// Spins between asking the [`OwnerRunning`](crate::owner::OwnerRunning)
+/// hook about the holder.
+#[cfg(feature = "owner-tracking")]
+const OWNER_CHECK_INTERVAL: usize = 8;
+
+/// Backoff rounds between prefetches of the protected data.
+#[cfg(feature = "prefetch")]
+const PREFETCH_INTERVAL: usize = 8;
+
+/// `data` is only used as a prefetch hint while waiting. Returns the number
+/// of spins if the lock was contended.
+#[cfg_attr(feature = "watchdog", track_caller)]
+#[inline(always)]
+pub(crate) fn lock_atomic(
+    locked: &AtomicU8,
+    data: *const u8,
+    #[cfg(feature = "owner-tracking")] owner: &AtomicUsize,
+    #[cfg(feature = "waiter-count")] waiters: &AtomicUsize,
+    #[cfg(feature = "watchdog")] watched: crate::watchdog::Watched<'_>,
+    #[cfg(feature = "usdt")] site: crate::usdt::Site,
+) -> Option<usize> {
+    if locked
+        .compare_exchange(UNLOCKED, LOCKED, Ordering::Acquire, Ordering::Relaxed)
+        .is_err()
+    {
+        #[cfg(feature = "usdt")]
+        crate::usdt::contended(locked.as_ptr() as usize, site);
+        let spins = lock_contended(
+            locked,
+            data,
+            #[cfg(feature = "owner-tracking")]
+            owner,
+            #[cfg(feature = "waiter-count")]
+            waiters,
+            #[cfg(feature = "watchdog")]
+            watched,
+            #[cfg(all(feature = "usdt", feature = "deadlock-detection"))]
+            site,
+        );
+        #[cfg(feature = "chaos")]
+        crate::chaos::delay();
+        return Some(spins);
+    }
+    #[cfg(feature = "chaos")]
+    crate::chaos::delay();
+    None
+}
+
+// Kept out of line so every `lock()` call site inlines only the CAS above.
+#[cold]
+#[inline(never)]
+#[cfg_attr(not(feature = "prefetch"), allow(unused_variables))]
+#[cfg_attr(feature = "watchdog", track_caller)]
+fn lock_contended(
+    locked: &AtomicU8,
+    data: *const u8,
+    #[cfg(feature = "owner-tracking")] owner: &AtomicUsize,
+    #[cfg(feature = "waiter-count")] waiters: &AtomicUsize,
+    #[cfg(feature = "watchdog")] watched: crate::watchdog::Watched<'_>,
+    #[cfg(all(feature = "usdt", feature = "deadlock-detection"))] site: crate::usdt::Site,
+) -> usize {
+    #[cfg(feature = "waiter-count")]
+    let _waiter = Waiter::new(waiters);
+    #[cfg(feature = "deadlock-detection")]
+    let mut waiting = crate::deadlock::Waiting::new(
+        locked.as_ptr() as usize,
+        #[cfg(feature = "usdt")]
+        site,
+    );
+    #[cfg(feature = "watchdog")]
+    let watch = crate::watchdog::Watch::new(watched);
+    let mut spins = 0;
+    let mut backoff = Backoff::new();
+    // Test-and-test-and-set: waiters spin on a shared read of the lock word
+    // and only retry the CAS once it looks free, so the line is not bounced
+    // between waiters in exclusive state.
+    loop {
+        while locked.load(Ordering::Relaxed) != UNLOCKED {
+            spins += 1;
+            if spins >= SPINS_BEFORE_PARK && park::is_registered() {
+                lock_parked(locked);
+                return spins;
+            }
+            // Spinning on a holder that is not running only delays it.
+            #[cfg(feature = "owner-tracking")]
+            if spins % OWNER_CHECK_INTERVAL == 0 && !owner_running(owner) {
+                if park::is_registered() {
+                    lock_parked(locked);
+                    return spins;
+                }
+                backoff.saturate();
+            }
+            #[cfg(feature = "deadlock-detection")]
+            if spins % crate::deadlock::CHECK_INTERVAL == 0 {
+                waiting.check();
+            }
+            #[cfg(feature = "watchdog")]
+            watch.check(locked.as_ptr() as usize, spins);
+            // Pull the data towards this core for writing, so winning the
+            // CAS is not followed by a miss on the line the owner just wrote.
+            #[cfg(feature = "prefetch")]
+            if spins % PREFETCH_INTERVAL == 0 {
+                relax::prefetch_write(data);
+            }
+            backoff.wait_on(locked);
+        }
+        if retry_cas!(locked, UNLOCKED, LOCKED).is_ok() {
+            return spins;
+        }
+    }
+}
+
+/// Whether the holder recorded in `owner` is running; `true` while none is
+/// recorded.
+#[cfg(feature = "owner-tracking")]
+fn owner_running(owner: &AtomicUsize) -> bool {
+    core::num::NonZeroUsize::new(owner.load(Ordering::Relaxed))
+        .is_none_or(|id| crate::owner::is_running(crate::owner::OwnerId::new(id)))
+}
+
+/// Counts the caller as a waiter until dropped, which also covers a
+/// deadlock or watchdog handler panicking out of the loop.
+#[cfg(feature = "waiter-count")]
+struct Waiter<'a>(&'a AtomicUsize);
+
+#[cfg(feature = "waiter-count")]
+impl<'a> Waiter<'a> {
+    fn new(waiters: &'a AtomicUsize) -> Self {
+        waiters.fetch_add(1, Ordering::Relaxed);
+        Self(waiters)
+    }
+}
+
+#[cfg(feature = "waiter-count")]
+impl Drop for Waiter<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+#[cold]
+pub(crate) fn lock_parked(locked: &AtomicU8) {
+    // Acquiring with `PARKED` rather than `LOCKED` is conservative: other
+    // waiters may still be blocked, so our unlock must wake one of them.
+    while locked.swap(PARKED, Ordering::Acquire) != UNLOCKED {
+        park::park(locked.as_ptr() as usize, &|| {
+            locked.load(Ordering::Relaxed) == PARKED
+        });
+    }
+}
+
+#[inline(always)]
+pub(crate) fn try_lock_atomic(locked: &AtomicU8) -> bool {
+    let acquired = locked
+        .compare_exchange(UNLOCKED, LOCKED, Ordering::Acquire, Ordering::Relaxed)
+        .is_ok();
+    #[cfg(feature = "chaos")]
+    if acquired {
+        crate::chaos::delay();
+    }
+    acquired
+}
+
+/// Stores the location of the acquisition that just succeeded.
+#[cfg(feature = "track-location")]
+#[track_caller]
+#[inline(always)]
+pub(crate) fn record_location(last_acquired_at: &AtomicPtr<Location<'static>>) {
+    let location: *const Location<'static> = Location::caller();
+    last_acquired_at.store(location.cast_mut(), Ordering::Relaxed);
+}
+
+#[inline(always)]
+pub(crate) fn unlock_atomic(locked: &AtomicU8) {
+    #[cfg(feature = "chaos")]
+    crate::chaos::delay();
+    if park::is_registered() {
+        if locked.swap(UNLOCKED, Ordering::Release) == PARKED {
+            park::unpark_one(locked.as_ptr() as usize);
+        }
+    } else {
+        locked.store(UNLOCKED, Ordering::Release);
+    }
+    relax::notify();
+}
+
+/// Spins until `idle` returns `true`, or until `budget` spins have passed,
+/// in which case it returns how many there were. `idle` must load with
+/// Acquire, so the caller sees what the last holder wrote.
+#[cold]
+#[inline(never)]
+#[cfg_attr(feature = "watchdog", track_caller)]
+pub(crate) fn wait_until(
+    idle: impl Fn() -> bool,
+    budget: Option<usize>,
+    #[cfg(feature = "watchdog")] lock: usize,
+    #[cfg(feature = "watchdog")] watched: crate::watchdog::Watched<'_>,
+) -> Result<(), usize> {
+    #[cfg(feature = "watchdog")]
+    let watch = crate::watchdog::Watch::new(watched);
+    let mut spins = 0;
+    let mut backoff = Backoff::new();
+    while !idle() {
+        if budget.is_some_and(|budget| spins >= budget) {
+            return Err(spins);
+        }
+        spins += 1;
+        #[cfg(feature = "watchdog")]
+        watch.check(lock, spins);
+        backoff.wait();
+    }
+    Ok(())
+}
+
+/// The top bit of an `RwSpinLock` word, set while a writer holds the lock
+/// or waits for the readers counted in the bits below it to leave.
+pub const WRITE_FLAG: usize = 1 << (usize::BITS - 1);
+
+#[cfg_attr(feature = "watchdog", track_caller)]
+#[inline(always)]
+pub(crate) fn rw_read_lock_atomic(
+    state: &AtomicUsize,
+    max_readers: usize,
+    #[cfg(feature = "watchdog")] watched: crate::watchdog::Watched<'_>,
+) {
+    if !rw_try_read_lock_atomic(state, max_readers) {
+        rw_read_lock_contended(
+            state,
+            max_readers,
+            #[cfg(feature = "watchdog")]
+            watched,
+        );
+    }
+}
+
+#[cold]
+#[inline(never)]
+#[cfg_attr(feature = "watchdog", track_caller)]
+fn rw_read_lock_contended(
+    state: &AtomicUsize,
+    max_readers: usize,
+    #[cfg(feature = "watchdog")] watched: crate::watchdog::Watched<'_>,
+) {
+    #[cfg(feature = "watchdog")]
+    let (watch, mut spins) = (crate::watchdog::Watch::new(watched), 0);
+    let mut backoff = Backoff::new();
+    loop {
+        #[cfg(feature = "watchdog")]
+        {
+            spins += 1;
+            watch.check(state.as_ptr() as usize, spins);
+        }
+        // Wait with plain loads while a writer is in, or the readers are at
+        // their cap, so that waiting readers do not keep bumping the count
+        // a writer drains.
+        let current_state = state.load(Ordering::Relaxed);
+        if current_state & WRITE_FLAG != 0 || current_state >= max_readers {
+            backoff.wait_on(state);
+            continue;
+        }
+        if rw_try_read_lock_atomic(state, max_readers) {
+            break;
+        }
+        backoff.spin();
+    }
+}
+
+/// Readers past this many are turned away. The room left above it takes the
+/// transient increments of readers backing off, so they never carry into
+/// `WRITE_FLAG`.
+pub const MAX_READERS: usize = WRITE_FLAG >> 1;
+
+/// Takes a read lock with one increment, rolled back if a writer holds the
+/// lock or is draining readers, or if `max_readers`, at most
+/// `MAX_READERS`, already hold it.
+///
+/// Unlike a CAS, concurrent readers below the cap never fail each other.
+/// While it lasts, a rolled-back increment looks like a reader, so a
+/// draining writer waits for the decrement, `rw_try_write_lock_atomic` may
+/// fail spuriously, and so may a reader arriving just below the cap.
+#[inline(always)]
+pub(crate) fn rw_try_read_lock_atomic(state: &AtomicUsize, max_readers: usize) -> bool {
+    let previous_state = state.fetch_add(1, Ordering::Acquire);
+    if previous_state & WRITE_FLAG == 0 && previous_state < max_readers {
+        #[cfg(feature = "chaos")]
+        crate::chaos::delay();
+        return true;
+    }
+    // Nothing was read under the increment, so the rollback is Relaxed; as
+    // an RMW it still continues the release sequence of earlier read
+    // unlocks, which the writer's drain loop synchronizes with. It may be
+    // what that loop waits for, so it wakes waiters like an unlock.
+    state.fetch_sub(1, Ordering::Relaxed);
+    relax::notify();
+    #[cfg(all(feature = "async", not(any(loom, shuttle))))]
+    crate::rwlock_async::notify(state);
+    false
+}
+
+/// Takes one more read lock for a caller already holding one, which keeps
+/// writers out, so the increment cannot fail and needs no ordering. A
+/// per-lock reader cap does not apply, since the caller cannot wait for a
+/// reader to leave while it holds the lock itself.
+///
+/// # Panics
+///
+/// Panics if the reader count would pass `MAX_READERS`.
+#[inline(always)]
+pub(crate) fn rw_read_relock_atomic(state: &AtomicUsize) {
+    let previous_state = state.fetch_add(1, Ordering::Relaxed);
+    if previous_state & !WRITE_FLAG >= MAX_READERS {
+        state.fetch_sub(1, Ordering::Relaxed);
+        too_many_readers();
+    }
+}
+
+#[cold]
+fn too_many_readers() -> ! {
+    panic!("RwSpinLock reader count overflow");
+}
+
+#[inline(always)]
+pub(crate) fn rw_read_unlock_atomic(state: &AtomicUsize) {
+    #[cfg(feature = "chaos")]
+    crate::chaos::delay();
+    state.fetch_sub(1, Ordering::Release);
+    relax::notify();
+    #[cfg(all(feature = "async", not(any(loom, shuttle))))]
+    crate::rwlock_async::notify(state);
+}
+
+#[cfg_attr(feature = "watchdog", track_caller)]
+#[inline(always)]
+pub(crate) fn rw_write_lock_atomic(
+    state: &AtomicUsize,
+    #[cfg(feature = "watchdog")] watched: crate::watchdog::Watched<'_>,
+) {
+    if !rw_try_write_lock_atomic(state) {
+        rw_write_lock_contended(
+            state,
+            #[cfg(feature = "watchdog")]
+            watched,
+        );
+    }
+}
+
+#[cold]
+#[inline(never)]
+#[cfg_attr(feature = "watchdog", track_caller)]
+fn rw_write_lock_contended(
+    state: &AtomicUsize,
+    #[cfg(feature = "watchdog")] watched: crate::watchdog::Watched<'_>,
+) {
+    #[cfg(feature = "watchdog")]
+    let (watch, mut spins) = (crate::watchdog::Watch::new(watched), 0);
+    let mut backoff = Backoff::new();
+    loop {
+        #[cfg(feature = "watchdog")]
+        {
+            spins += 1;
+            watch.check(state.as_ptr() as usize, spins);
+        }
+        let current_state = state.load(Ordering::Relaxed);
+        if current_state & WRITE_FLAG != 0 {
+            backoff.wait_on(state);
+            continue;
+        }
+
+        if retry_cas!(state, current_state, current_state | WRITE_FLAG).is_ok() {
+            // Acquire pairs with the release of each reader still inside.
+            while state.load(Ordering::Acquire) & !WRITE_FLAG != 0 {
+                #[cfg(feature = "watchdog")]
+                {
+                    spins += 1;
+                    watch.check(state.as_ptr() as usize, spins);
+                }
+                #[cfg(feature = "chaos")]
+                crate::chaos::delay();
+                backoff.wait_on(state);
+            }
+            #[cfg(feature = "chaos")]
+            crate::chaos::delay();
+            break;
+        }
+        backoff.spin();
+    }
+}
+
+#[inline(always)]
+pub(crate) fn rw_try_write_lock_atomic(state: &AtomicUsize) -> bool {
+    let acquired = state
+        .compare_exchange(0, WRITE_FLAG, Ordering::Acquire, Ordering::Relaxed)
+        .is_ok();
+    #[cfg(feature = "chaos")]
+    if acquired {
+        crate::chaos::delay();
+    }
+    acquired
+}
+
+#[inline(always)]
+pub(crate) fn rw_write_unlock_atomic(state: &AtomicUsize) {
+    #[cfg(feature = "chaos")]
+    crate::chaos::delay();
+    state.fetch_and(!WRITE_FLAG, Ordering::Release);
+    relax::notify();
+    #[cfg(all(feature = "async", not(any(loom, shuttle))))]
+    crate::rwlock_async::notify(state);
+}
+
+/// What a contended wait on a caller-owned word needs in place of a lock's
+/// debugging fields, which such a word does not have.
+#[cfg(not(any(loom, shuttle)))]
+struct Unwatched {
+    #[cfg(feature = "owner-tracking")]
+    owner: AtomicUsize,
+    #[cfg(feature = "waiter-count")]
+    waiters: AtomicUsize,
+    #[cfg(all(feature = "watchdog", feature = "track-location"))]
+    last_acquired_at: AtomicPtr<Location<'static>>,
+}
+
+#[cfg(not(any(loom, shuttle)))]
+impl Unwatched {
+    const fn new() -> Self {
+        Self {
+            #[cfg(feature = "owner-tracking")]
+            owner: AtomicUsize::new(0),
+            #[cfg(feature = "waiter-count")]
+            waiters: AtomicUsize::new(0),
+            #[cfg(all(feature = "watchdog", feature = "track-location"))]
+            last_acquired_at: AtomicPtr::new(core::ptr::null_mut()),
+        }
+    }
+
+    #[cfg(feature = "watchdog")]
+    fn watched(&self) -> crate::watchdog::Watched<'_> {
+        crate::watchdog::Watched {
+            spin_threshold: None,
+            #[cfg(feature = "track-location")]
+            last_acquired_at: &self.last_acquired_at,
+        }
+    }
+}
+
+/// A lock taken on a caller-owned lock word by [`lock`] or [`try_lock`].
+/// Dropping it unlocks the word; `mem::forget` it to hand the lock to
+/// [`unlock`] instead.
+#[cfg(not(any(loom, shuttle)))]
+#[must_use = "dropping the guard unlocks the word immediately"]
+pub struct RawWordGuard<'a> {
+    word: &'a AtomicU8,
+    holds_lock: bool,
+    /// Marked the free word held with a plain store before raw atomics were
+    /// enabled, and clears it on drop.
+    marked: bool,
+}
+
+#[cfg(not(any(loom, shuttle)))]
+impl RawWordGuard<'_> {
+    /// Whether the word was taken atomically: `false` before raw atomics
+    /// were enabled.
+    pub fn holds_lock(&self) -> bool {
+        self.holds_lock
+    }
+}
+
+#[cfg(not(any(loom, shuttle)))]
+impl Drop for RawWordGuard<'_> {
+    fn drop(&mut self) {
+        // A guard that found the word marked by another leaves it alone.
+        if self.holds_lock || self.marked {
+            release_word(self.word);
+        }
+    }
+}
+
+/// Locks a caller-owned lock word with the protocol of
+/// [`RawSpinLock`](crate::mutex::RawSpinLock), for structures that must
+/// keep their own word, such as ones shared with firmware.
+///
+/// The word is a byte: 0 is unlocked, 1 locked and 2 locked with waiters
+/// blocked in a registered [`Parker`](crate::park::Parker). Acquisition is
+/// an Acquire compare-exchange from 0 to 1, with waiters spinning on
+/// Relaxed loads in between, and release is a Release store of 0, or a swap
+/// that wakes parked waiters when a parker is registered. Before raw
+/// atomics are enabled a guard that finds the word 0 only marks it 1 with
+/// a plain store, and excludes nobody; only that guard clears the mark, as
+/// with `RawSpinLock`, so a guard alive across [`enable_raw_atomics`] keeps
+/// the word held until it is dropped. The reader-writer functions leave
+/// their word alone instead, see [`read_lock`].
+///
+/// The watchdog watches the wait; the other debugging features and the
+/// preemption hook belong to the locks and are not applied. The atomics
+/// are `core`'s, or `portable-atomic`'s with that feature. Not available
+/// under the model checkers.
+#[cfg(not(any(loom, shuttle)))]
+#[track_caller]
+pub fn lock(word: &AtomicU8) -> RawWordGuard<'_> {
+    let holds_lock = raw_atomics_enabled();
+    let mut marked = false;
+    if holds_lock {
+        #[cfg_attr(
+            not(any(
+                feature = "owner-tracking",
+                feature = "waiter-count",
+                feature = "watchdog"
+            )),
+            allow(unused_variables)
+        )]
+        let unwatched = Unwatched::new();
+        lock_atomic(
+            word,
+            word.as_ptr(),
+            #[cfg(feature = "owner-tracking")]
+            &unwatched.owner,
+            #[cfg(feature = "waiter-count")]
+            &unwatched.waiters,
+            #[cfg(feature = "watchdog")]
+            unwatched.watched(),
+            #[cfg(feature = "usdt")]
+            crate::usdt::Site::here(""),
+        );
+    } else {
+        marked = mark_word(word);
+    }
+    RawWordGuard {
+        word,
+        holds_lock,
+        marked,
+    }
+}
+
+/// Like [`lock`], but returns `None` if the word is locked instead of
+/// waiting. Before raw atomics are enabled it always succeeds.
+#[cfg(not(any(loom, shuttle)))]
+pub fn try_lock(word: &AtomicU8) -> Option<RawWordGuard<'_>> {
+    let holds_lock = raw_atomics_enabled();
+    let mut marked = false;
+    if holds_lock {
+        if !try_lock_atomic(word) {
+            return None;
+        }
+    } else {
+        marked = mark_word(word);
+    }
+    Some(RawWordGuard {
+        word,
+        holds_lock,
+        marked,
+    })
+}
+
+/// Unlocks a word locked by [`lock`] or [`try_lock`] whose guard was
+/// forgotten.
+///
+/// # Safety
+///
+/// The word must be locked by a guard that was forgotten: one that took
+/// it, or marked it before raw atomics were enabled, rather than one that
+/// found it marked already. Nobody may use what it protects afterwards
+/// without locking it again.
+#[cfg(not(any(loom, shuttle)))]
+pub unsafe fn unlock(word: &AtomicU8) {
+    release_word(word);
+}
+
+/// Marks a free word held before raw atomics are enabled. Returns `false`
+/// if another guard had marked it.
+#[cfg(not(any(loom, shuttle)))]
+fn mark_word(word: &AtomicU8) -> bool {
+    let free = word.load(Ordering::Relaxed) == UNLOCKED;
+    if free {
+        word.store(LOCKED, Ordering::Relaxed);
+    }
+    free
+}
+
+/// Clears a word this guard took or marked: with a real release once raw
+/// atomics are enabled, since other cores may be waiting on it by then.
+#[cfg(not(any(loom, shuttle)))]
+fn release_word(word: &AtomicU8) {
+    if raw_atomics_enabled() {
+        unlock_atomic(word);
+    } else {
+        word.store(UNLOCKED, Ordering::Relaxed);
+    }
+}
+
+/// A read lock taken on a caller-owned word by [`read_lock`] or
+/// [`try_read_lock`]; dropping it unlocks.
+#[cfg(not(any(loom, shuttle)))]
+#[must_use = "dropping the guard unlocks the word immediately"]
+pub struct RawReadGuard<'a> {
+    state: &'a AtomicUsize,
+    holds_lock: bool,
+}
+
+/// The write lock taken on a caller-owned word by [`write_lock`] or
+/// [`try_write_lock`]; dropping it unlocks.
+#[cfg(not(any(loom, shuttle)))]
+#[must_use = "dropping the guard unlocks the word immediately"]
+pub struct RawWriteGuard<'a> {
+    state: &'a AtomicUsize,
+    holds_lock: bool,
+}
+
+#[cfg(not(any(loom, shuttle)))]
+impl RawReadGuard<'_> {
+    /// Whether the word was taken atomically: `false` before raw atomics
+    /// were enabled.
+    pub fn holds_lock(&self) -> bool {
+        self.holds_lock
+    }
+}
+
+#[cfg(not(any(loom, shuttle)))]
+impl RawWriteGuard<'_> {
+    /// Whether the word was taken atomically: `false` before raw atomics
+    /// were enabled.
+    pub fn holds_lock(&self) -> bool {
+        self.holds_lock
+    }
+}
+
+#[cfg(not(any(loom, shuttle)))]
+impl Drop for RawReadGuard<'_> {
+    fn drop(&mut self) {
+        if self.holds_lock {
+            rw_read_unlock_atomic(self.state);
+        }
+    }
+}
+
+#[cfg(not(any(loom, shuttle)))]
+impl Drop for RawWriteGuard<'_> {
+    fn drop(&mut self) {
+        if self.holds_lock {
+            rw_write_unlock_atomic(self.state);
+        }
+    }
+}
+
+/// Read-locks a caller-owned word with the protocol of
+/// [`RwSpinLock`](crate::rwlock::RwSpinLock).
+///
+/// The top bit of the word is the writer flag, set by a writer holding
+/// the lock or waiting for readers to leave, and the rest counts readers.
+/// A reader increments the count with Acquire and takes it back if the
+/// flag was set; a writer sets the flag with an Acquire compare-exchange
+/// and then waits with Acquire loads for the count to drain. Read unlock is
+/// a Release decrement and write unlock a Release clear of the flag.
+/// Before raw atomics are enabled the word is not touched at all and the
+/// guards exclude nobody. Unlike [`lock`], which marks its word as
+/// `RawSpinLock` does, these follow `RwSpinLock`, which counts no readers
+/// then, so the word stays 0 for anything inspecting it.
+///
+/// As with [`lock`], only the watchdog applies, and the function is not
+/// available under the model checkers.
+#[cfg(not(any(loom, shuttle)))]
+#[track_caller]
+pub fn read_lock(state: &AtomicUsize) -> RawReadGuard<'_> {
+    let holds_lock = raw_atomics_enabled();
+    if holds_lock {
+        #[cfg(feature = "watchdog")]
+        let unwatched = Unwatched::new();
+        rw_read_lock_atomic(
+            state,
+            MAX_READERS,
+            #[cfg(feature = "watchdog")]
+            unwatched.watched(),
+        );
+    }
+    RawReadGuard { state, holds_lock }
+}
+
+/// Like [`read_lock`], but returns `None` instead of waiting for a writer.
+#[cfg(not(any(loom, shuttle)))]
+pub fn try_read_lock(state: &AtomicUsize) -> Option<RawReadGuard<'_>> {
+    let holds_lock = raw_atomics_enabled();
+    if holds_lock && !rw_try_read_lock_atomic(state, MAX_READERS) {
+        return None;
+    }
+    Some(RawReadGuard { state, holds_lock })
+}
+
+/// Write-locks a caller-owned word; see [`read_lock`] for the protocol.
+#[cfg(not(any(loom, shuttle)))]
+#[track_caller]
+pub fn write_lock(state: &AtomicUsize) -> RawWriteGuard<'_> {
+    let holds_lock = raw_atomics_enabled();
+    if holds_lock {
+        #[cfg(feature = "watchdog")]
+        let unwatched = Unwatched::new();
+        rw_write_lock_atomic(
+            state,
+            #[cfg(feature = "watchdog")]
+            unwatched.watched(),
+        );
+    }
+    RawWriteGuard { state, holds_lock }
+}
+
+/// Like [`write_lock`], but returns `None` instead of waiting for readers
+/// or a writer.
+#[cfg(not(any(loom, shuttle)))]
+pub fn try_write_lock(state: &AtomicUsize) -> Option<RawWriteGuard<'_>> {
+    let holds_lock = raw_atomics_enabled();
+    if holds_lock && !rw_try_write_lock_atomic(state) {
+        return None;
+    }
+    Some(RawWriteGuard { state, holds_lock })
+}
+
+/// Drops a read lock on a word whose [`RawReadGuard`] was forgotten.
+///
+/// # Safety
+///
+/// The caller must own a read lock on the word, taken while raw atomics
+/// were as they are now, that no guard will release.
+#[cfg(not(any(loom, shuttle)))]
+pub unsafe fn read_unlock(state: &AtomicUsize) {
+    if raw_atomics_enabled() {
+        rw_read_unlock_atomic(state);
+    }
+}
+
+/// Drops the write lock on a word whose [`RawWriteGuard`] was forgotten.
+///
+/// # Safety
+///
+/// As for [`read_unlock`], with the write lock.
+#[cfg(not(any(loom, shuttle)))]
+pub unsafe fn write_unlock(state: &AtomicUsize) {
+    if raw_atomics_enabled() {
+        rw_write_unlock_atomic(state);
+    }
+}
+
+/// Proof harnesses for the lock-state invariants; run them with
+/// `cargo kani`. Each checks a property over symbolic values, and the unit
+/// tests check the same property over the edges of its domain.
+#[cfg(any(kani, test))]
+pub(crate) mod proofs {
+    use super::*;
+
+    /// The lock word never leaves its three states, and `try_lock_atomic`
+    /// only succeeds on a free lock. Unlocking is only taken on a held word.
+    fn check_lock_word(before: u8, lock: bool) {
+        let word = AtomicU8::new(before);
+        if lock {
+            let acquired = try_lock_atomic(&word);
+            assert_eq!(acquired, before == UNLOCKED);
+        } else {
+            unlock_atomic(&word);
+            assert_eq!(word.load(Ordering::Relaxed), UNLOCKED);
+        }
+        assert!(word.load(Ordering::Relaxed) <= PARKED);
+    }
+
+    fn lock_word_precondition(before: u8, lock: bool) -> bool {
+        before <= PARKED && (lock || before != UNLOCKED)
+    }
+
+    /// The rw transitions: try_read, read unlock, try_write, write unlock.
+    const RW_TRANSITIONS: u8 = 4;
+
+    /// Whether transition `op` is one a balanced guard could take from
+    /// `readers` and `writer`.
+    fn rw_precondition(readers: usize, writer: bool, op: u8, max_readers: usize) -> bool {
+        readers < WRITE_FLAG
+            && match op {
+                0 => readers <= MAX_READERS && max_readers > 0 && max_readers <= MAX_READERS,
+                1 => readers > 0,
+                2 => true,
+                _ => writer && readers == 0,
+            }
+    }
+
+    /// Each rw transition moves the reader count by at most one without
+    /// wrapping into `WRITE_FLAG`, and leaves the flag alone unless it is
+    /// the writer's.
+    fn check_rw_transition(readers: usize, writer: bool, op: u8, max_readers: usize) {
+        let before = if writer {
+            readers | WRITE_FLAG
+        } else {
+            readers
+        };
+        let state = AtomicUsize::new(before);
+        match op {
+            0 => {
+                let acquired = rw_try_read_lock_atomic(&state, max_readers);
+                assert_eq!(acquired, !writer && readers < max_readers);
+                let after = state.load(Ordering::Relaxed);
+                assert_eq!(after, if acquired { before + 1 } else { before });
+            }
+            1 => {
+                rw_read_unlock_atomic(&state);
+                assert_eq!(state.load(Ordering::Relaxed), before - 1);
+            }
+            2 => {
+                let acquired = rw_try_write_lock_atomic(&state);
+                assert_eq!(acquired, before == 0);
+                let after = state.load(Ordering::Relaxed);
+                assert_eq!(after, if acquired { WRITE_FLAG } else { before });
+            }
+            _ => {
+                rw_write_unlock_atomic(&state);
+                assert_eq!(state.load(Ordering::Relaxed), 0);
+            }
+        }
+    }
+
+    #[cfg(kani)]
+    #[kani::proof]
+    fn lock_word_stays_valid() {
+        let (before, lock) = (kani::any(), kani::any());
+        kani::assume(lock_word_precondition(before, lock));
+        check_lock_word(before, lock);
+    }
+
+    #[cfg(kani)]
+    #[kani::proof]
+    fn rw_transitions_stay_in_range() {
+        let (readers, writer, max_readers) = (kani::any(), kani::any(), kani::any());
+        let op = kani::any::<u8>() % RW_TRANSITIONS;
+        kani::assume(rw_precondition(readers, writer, op, max_readers));
+        check_rw_transition(readers, writer, op, max_readers);
+    }
+
+    /// Reader counts and limits at the edges of their ranges.
+    #[cfg(test)]
+    fn edge_counts() -> [usize; 8] {
+        [
+            0,
+            1,
+            2,
+            MAX_READERS - 1,
+            MAX_READERS,
+            MAX_READERS + 1,
+            WRITE_FLAG - 1,
+            WRITE_FLAG,
+        ]
+    }
+
+    #[test]
+    fn lock_word_stays_valid_on_every_state() {
+        for before in 0..=u8::MAX {
+            for lock in [false, true] {
+                if lock_word_precondition(before, lock) {
+                    check_lock_word(before, lock);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn rw_transitions_stay_in_range_at_the_edges() {
+        for readers in edge_counts() {
+            for max_readers in edge_counts() {
+                for writer in [false, true] {
+                    for op in 0..RW_TRANSITIONS {
+                        if rw_precondition(readers, writer, op, max_readers) {
+                            check_rw_transition(readers, writer, op, max_readers);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+
+    const THREADS: usize = 4;
+
+    /// Counts under a `RawSpinLock` and an `RwSpinLock` writer from
+    /// `THREADS` threads, with each CAS strength in turn.
+    #[test]
+    fn both_cas_strengths_exclude() {
+        let rounds = if cfg!(miri) { 50 } else { 20_000 };
+        enable_raw_atomics();
+        for strength in [1, 2] {
+            CAS_STRENGTH.store(strength, Ordering::Relaxed);
+            let lock = crate::mutex::RawSpinLock::new(0);
+            let rwlock = crate::rwlock::RwSpinLock::new(0);
+            std::thread::scope(|s| {
+                for _ in 0..THREADS {
+                    s.spawn(|| {
+                        for _ in 0..rounds {
+                            *lock.lock() += 1;
+                            *rwlock.write() += 1;
+                            let _ = *rwlock.read();
+                        }
+                    });
+                }
+            });
+            assert_eq!(lock.into_inner(), THREADS * rounds, "strength {strength}");
+            assert_eq!(rwlock.into_inner(), THREADS * rounds, "strength {strength}");
+        }
+        CAS_STRENGTH.store(0, Ordering::Relaxed);
+    }
+}
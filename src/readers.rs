@@ -0,0 +1,114 @@
+//! Which contexts hold a [`RwSpinLock`]'s read locks, for debugging writer
+//! stalls.
+//!
+//! With the `reader-tracking` feature, every atomic read acquisition claims
+//! one of [`READER_SLOTS`] slots in the lock for its [`OwnerId`] and call
+//! site, and its guard frees the slot again. Readers that find all slots
+//! taken, or that run where [`owner::current`] has no id, are only counted.
+//! [`RwSpinLock::current_readers`] and [`RwSpinLock::untracked_readers`]
+//! report both.
+//!
+//! Claiming and freeing a slot is a compare-exchange and a store on the
+//! slot table, which lives apart from the lock word, so tracking never makes
+//! a reader or writer wait.
+//!
+//! [`RwSpinLock`]: crate::rwlock::RwSpinLock
+//! [`RwSpinLock::current_readers`]: crate::rwlock::RwSpinLock::current_readers
+//! [`RwSpinLock::untracked_readers`]: crate::rwlock::RwSpinLock::untracked_readers
+//! [`owner::current`]: crate::owner::current
+
+use core::num::NonZeroUsize;
+use core::panic::Location;
+use core::ptr;
+
+use crate::atomic::AtomicPtr;
+use crate::atomic::AtomicUsize;
+use crate::atomic::Ordering;
+use crate::owner;
+use crate::owner::OwnerId;
+
+/// How many readers each `RwSpinLock` can name at once.
+pub const READER_SLOTS: usize = 4;
+
+/// The slot recorded in a guard whose reader was only counted.
+pub(crate) const UNTRACKED: usize = usize::MAX;
+
+/// A reader holding a [`RwSpinLock`](crate::rwlock::RwSpinLock).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ReaderInfo {
+    pub owner: OwnerId,
+    /// Where it acquired the lock, or `None` if it has only just claimed
+    /// its slot.
+    pub location: Option<&'static Location<'static>>,
+}
+
+/// The slot table embedded in every `RwSpinLock`.
+pub(crate) struct ReaderSlots {
+    /// Owner ids of the readers in the slots, 0 for a free slot.
+    owners: [AtomicUsize; READER_SLOTS],
+    locations: [AtomicPtr<Location<'static>>; READER_SLOTS],
+    untracked: AtomicUsize,
+}
+
+impl ReaderSlots {
+    pub(crate) const fn new() -> Self {
+        Self {
+            owners: [const { AtomicUsize::new(0) }; READER_SLOTS],
+            locations: [const { AtomicPtr::new(ptr::null_mut()) }; READER_SLOTS],
+            untracked: AtomicUsize::new(0),
+        }
+    }
+
+    /// Records the current context as a reader and returns the slot its
+    /// guard has to free, or [`UNTRACKED`]. Raw atomics must be enabled.
+    #[track_caller]
+    pub(crate) fn claim(&self) -> usize {
+        if let Some(owner) = owner::current() {
+            for (slot, claimed) in self.owners.iter().enumerate() {
+                if claimed
+                    .compare_exchange(0, owner.get(), Ordering::Relaxed, Ordering::Relaxed)
+                    .is_ok()
+                {
+                    let location: *const Location<'static> = Location::caller();
+                    self.locations[slot].store(location.cast_mut(), Ordering::Release);
+                    return slot;
+                }
+            }
+        }
+        self.untracked.fetch_add(1, Ordering::Relaxed);
+        UNTRACKED
+    }
+
+    pub(crate) fn release(&self, slot: usize) {
+        if slot == UNTRACKED {
+            self.untracked.fetch_sub(1, Ordering::Relaxed);
+        } else {
+            self.locations[slot].store(ptr::null_mut(), Ordering::Relaxed);
+            self.owners[slot].store(0, Ordering::Release);
+        }
+    }
+
+    /// The readers in the slots. Each entry is a snapshot taken one slot at
+    /// a time, and may pair a reader that just arrived with the location of
+    /// the one that just left.
+    pub(crate) fn current(&self) -> impl Iterator<Item = ReaderInfo> + '_ {
+        self.owners
+            .iter()
+            .zip(&self.locations)
+            .filter_map(|(owner, location)| {
+                let owner = NonZeroUsize::new(owner.load(Ordering::Acquire))?;
+                // SAFETY: the pointer is null or comes from a
+                // `&'static Location`.
+                let location = unsafe { location.load(Ordering::Acquire).as_ref() };
+                Some(ReaderInfo {
+                    owner: OwnerId::new(owner),
+                    location,
+                })
+            })
+    }
+
+    pub(crate) fn untracked(&self) -> usize {
+        self.untracked.load(Ordering::Relaxed)
+    }
+}
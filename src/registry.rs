@@ -0,0 +1,201 @@
+//! A global list of named locks, for post-mortem debugging.
+//!
+//! With the `registry` feature, a [`RawSpinLock`] created with
+//! [`RawSpinLock::registered`], usually through [`registered_static!`],
+//! links itself into a global list the first time it is locked. The link is
+//! a node inside the lock, so nothing is allocated. [`dump`] walks the list
+//! and writes one line per held lock: its name, address, whether waiters are
+//! parked on it, with `owner-tracking` who holds it, with `track-location`
+//! where it was acquired and, with `stats`, the longest wait and hold seen.
+//!
+//! Nodes are never unlinked, which is why registered locks must stay put
+//! once used; statics, which is what the macro declares, always do.
+//!
+//! [`RawSpinLock`]: crate::mutex::RawSpinLock
+//! [`RawSpinLock::registered`]: crate::mutex::RawSpinLock::registered
+//! [`registered_static!`]: crate::registered_static
+
+use core::fmt;
+use core::ptr;
+
+use crate::atomic::AtomicPtr;
+use crate::atomic::AtomicU8;
+use crate::atomic::Ordering;
+#[cfg(feature = "owner-tracking")]
+use crate::owner::OwnerId;
+use crate::raw::raw_atomics_enabled;
+
+// Node states. Locks created with `new` stay `UNLISTED` forever.
+const UNLISTED: u8 = 0;
+const PENDING: u8 = 1;
+const LINKING: u8 = 2;
+const LINKED: u8 = 3;
+
+/// What [`dump`] reports about one lock.
+pub(crate) struct Snapshot {
+    pub(crate) held: bool,
+    pub(crate) parked: bool,
+    #[cfg(feature = "owner-tracking")]
+    pub(crate) owner: Option<OwnerId>,
+    #[cfg(feature = "track-location")]
+    pub(crate) location: Option<&'static core::panic::Location<'static>>,
+    #[cfg(feature = "stats")]
+    pub(crate) stats: crate::stats::LockStats,
+    #[cfg(feature = "audit")]
+    pub(crate) pre_atomic: usize,
+}
+
+/// The registry link embedded in every lock.
+pub(crate) struct Node {
+    name: &'static str,
+    state: AtomicU8,
+    next: AtomicPtr<Node>,
+    /// The lock containing this node, set when linking.
+    lock: AtomicPtr<()>,
+    /// The size of that lock, for cache maintenance.
+    size: usize,
+    /// Reads the state of the lock `lock` points to.
+    snapshot: unsafe fn(*const ()) -> Snapshot,
+}
+
+static HEAD: AtomicPtr<Node> = AtomicPtr::new(ptr::null_mut());
+
+impl Node {
+    pub(crate) const fn unlisted(snapshot: unsafe fn(*const ()) -> Snapshot) -> Self {
+        Self::new("", UNLISTED, 0, snapshot)
+    }
+
+    /// A node for a lock of `size` bytes, linked in when it is first locked.
+    pub(crate) const fn pending(
+        name: &'static str,
+        size: usize,
+        snapshot: unsafe fn(*const ()) -> Snapshot,
+    ) -> Self {
+        Self::new(name, PENDING, size, snapshot)
+    }
+
+    const fn new(
+        name: &'static str,
+        state: u8,
+        size: usize,
+        snapshot: unsafe fn(*const ()) -> Snapshot,
+    ) -> Self {
+        Self {
+            name,
+            state: AtomicU8::new(state),
+            next: AtomicPtr::new(ptr::null_mut()),
+            lock: AtomicPtr::new(ptr::null_mut()),
+            size,
+            snapshot,
+        }
+    }
+
+    #[cfg_attr(not(feature = "panic-hook"), allow(dead_code))]
+    pub(crate) fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Links the node in if it is still pending.
+    ///
+    /// # Safety
+    ///
+    /// `lock` must contain this node, and neither may move or be dropped
+    /// afterwards.
+    #[inline(always)]
+    pub(crate) unsafe fn register(&self, lock: *const ()) {
+        if self.state.load(Ordering::Relaxed) == PENDING {
+            // SAFETY: forwarded from the caller.
+            unsafe { self.link(lock) };
+        }
+    }
+
+    #[cold]
+    unsafe fn link(&self, lock: *const ()) {
+        if raw_atomics_enabled() {
+            if self
+                .state
+                .compare_exchange(PENDING, LINKING, Ordering::Relaxed, Ordering::Relaxed)
+                .is_err()
+            {
+                return;
+            }
+        } else {
+            self.state.store(LINKING, Ordering::Relaxed);
+        }
+        self.lock.store(lock.cast_mut(), Ordering::Relaxed);
+        let me = ptr::from_ref(self).cast_mut();
+        let mut head = HEAD.load(Ordering::Relaxed);
+        loop {
+            self.next.store(head, Ordering::Relaxed);
+            if !raw_atomics_enabled() {
+                HEAD.store(me, Ordering::Release);
+                break;
+            }
+            // Release publishes `lock` and `next` to `dump`.
+            match HEAD.compare_exchange_weak(head, me, Ordering::Release, Ordering::Relaxed) {
+                Ok(_) => break,
+                Err(current) => head = current,
+            }
+        }
+        self.state.store(LINKED, Ordering::Relaxed);
+    }
+}
+
+/// Calls `f` with the name, address and a snapshot of every registered
+/// lock, stopping at the first error.
+pub(crate) fn for_each(
+    mut f: impl FnMut(&'static str, *const (), Snapshot) -> fmt::Result,
+) -> fmt::Result {
+    let mut node = HEAD.load(Ordering::Acquire);
+    // SAFETY: linked nodes live in locks that never move or get dropped.
+    while let Some(current) = unsafe { node.as_ref() } {
+        let lock = current.lock.load(Ordering::Relaxed);
+        // SAFETY: `lock` is the lock that contains `current`, of the type
+        // `snapshot` was instantiated for.
+        let snapshot = unsafe { (current.snapshot)(lock) };
+        f(current.name, lock, snapshot)?;
+        node = current.next.load(Ordering::Acquire);
+    }
+    Ok(())
+}
+
+/// Runs the cache-maintenance hook over every registered lock; called by
+/// `enable_raw_atomics` just before the gate opens.
+pub(crate) fn prepare_for_atomics() {
+    let mut node = HEAD.load(Ordering::Acquire);
+    // SAFETY: as in `for_each`.
+    while let Some(current) = unsafe { node.as_ref() } {
+        let lock = current.lock.load(Ordering::Relaxed);
+        crate::cache::clean_invalidate(lock as usize, current.size);
+        node = current.next.load(Ordering::Acquire);
+    }
+}
+
+/// Writes one line for every registered lock that is currently held.
+///
+/// The lines are snapshots taken one lock at a time, not an atomic view of
+/// all locks. Locks that have never been locked are not listed yet.
+pub fn dump(writer: &mut dyn fmt::Write) -> fmt::Result {
+    for_each(|name, lock, snapshot| {
+        if !snapshot.held {
+            return Ok(());
+        }
+        write!(writer, "{name} ({lock:p}): held")?;
+        #[cfg(feature = "owner-tracking")]
+        if let Some(owner) = snapshot.owner {
+            write!(writer, " by {:#x}", owner.get())?;
+        }
+        #[cfg(feature = "track-location")]
+        if let Some(location) = snapshot.location {
+            write!(writer, " at {location}")?;
+        }
+        if snapshot.parked {
+            writer.write_str(", waiters parked")?;
+        }
+        #[cfg(feature = "stats")]
+        write!(writer, ", at most {} spins", snapshot.stats.max_spins)?;
+        #[cfg(feature = "hold-time")]
+        write!(writer, ", held at most {} ticks", snapshot.stats.hold_max)?;
+        writer.write_char('\n')
+    })
+}
@@ -0,0 +1,402 @@
+//! How waiters pause between acquisition attempts.
+//!
+//! On AArch64 and ARMv7 a waiter that has seen the lock word held parks the
+//! core with `wfe`, and every unlock helper follows its releasing store with
+//! `dsb ishst; sev` so parked waiters wake promptly. We issue the explicit
+//! `sev` rather than relying on the global exclusive monitor because the
+//! waiters spin with plain loads, which do not arm the monitor. Other targets
+//! use a pure `spin_loop` hint.
+//!
+//! Contended loops pause through a [`Backoff`], which doubles the number of
+//! pause iterations after each failed attempt so the owner can make
+//! progress. Past the longest pause a [`Relax`] strategy takes over:
+//! [`AdaptiveRelax`] with `std`, which yields to the scheduler, and plain
+//! spinning without it. With the `umwait` feature, x86_64 waiters sleep in
+//! [`Umwait`] instead, on CPUs that have it.
+
+use core::marker::PhantomData;
+
+use crate::atomic::spin_loop;
+
+/// Pauses after observing the lock word in a state only an unlock can clear.
+///
+/// Must not be used after a spurious failure (such as a weak CAS failing on a
+/// free lock): on ARM no unlock will follow to wake the core again.
+#[inline(always)]
+pub(crate) fn wait() {
+    #[cfg(all(
+        not(any(loom, shuttle)),
+        any(
+            target_arch = "aarch64",
+            all(target_arch = "arm", target_feature = "v7")
+        )
+    ))]
+    // SAFETY: `wfe` only suspends the core until the next event or interrupt.
+    unsafe {
+        core::arch::asm!("wfe", options(nomem, nostack, preserves_flags));
+    }
+
+    #[cfg(any(
+        loom,
+        shuttle,
+        not(any(
+            target_arch = "aarch64",
+            all(target_arch = "arm", target_feature = "v7")
+        ))
+    ))]
+    spin_loop();
+}
+
+/// Pauses before retrying after a failure that an unlock might not follow.
+#[inline(always)]
+pub(crate) fn spin() {
+    spin_loop();
+}
+
+/// Wakes waiters parked in [`wait`]; called right after every releasing store.
+#[inline(always)]
+pub(crate) fn notify() {
+    #[cfg(all(
+        not(any(loom, shuttle)),
+        any(
+            target_arch = "aarch64",
+            all(target_arch = "arm", target_feature = "v7")
+        )
+    ))]
+    // SAFETY: the barrier makes the preceding release store visible before
+    // the event is signalled; neither instruction touches Rust-visible state.
+    unsafe {
+        core::arch::asm!("dsb ishst", "sev", options(nostack, preserves_flags));
+    }
+}
+
+/// Hints that the current core is about to write the line holding `ptr`.
+///
+/// A no-op where the target has no suitable instruction. x86_64 without
+/// `prfchw` falls back to a read prefetch, which still brings the line in.
+#[cfg(feature = "prefetch")]
+#[inline(always)]
+pub(crate) fn prefetch_write(ptr: *const u8) {
+    #[cfg(all(target_arch = "x86_64", target_feature = "prfchw"))]
+    // SAFETY: prefetches never fault and do not change Rust-visible state.
+    unsafe {
+        core::arch::asm!("prefetchw [{}]", in(reg) ptr, options(nostack, preserves_flags, readonly));
+    }
+
+    #[cfg(all(target_arch = "x86_64", not(target_feature = "prfchw")))]
+    // SAFETY: as above.
+    unsafe {
+        core::arch::x86_64::_mm_prefetch::<{ core::arch::x86_64::_MM_HINT_T0 }>(ptr.cast());
+    }
+
+    #[cfg(target_arch = "aarch64")]
+    // SAFETY: as above.
+    unsafe {
+        core::arch::asm!("prfm pstl1keep, [{}]", in(reg) ptr, options(nostack, preserves_flags, readonly));
+    }
+
+    #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+    let _ = ptr;
+}
+
+/// Log2 of the most pause iterations a single [`Backoff`] step issues.
+#[cfg(not(any(loom, shuttle)))]
+const MAX_BACKOFF_STEP: u32 = 6;
+/// Under a model checker every pause is a yield to the model, and long
+/// backoffs only multiply the interleavings it has to explore.
+#[cfg(any(loom, shuttle))]
+const MAX_BACKOFF_STEP: u32 = 1;
+
+/// What a [`Backoff`] does once its pauses have reached their longest.
+pub trait Relax {
+    /// Called on every backoff past the cap, with the number of such calls
+    /// so far. Returns `false` to fall back to the capped pause loop.
+    fn relax_capped(rounds: u32) -> bool;
+
+    /// Like [`relax_capped`](Self::relax_capped), from
+    /// [`Backoff::wait_on`], for a waiter that saw `word` in a state only a
+    /// store to it can end.
+    #[inline]
+    fn relax_capped_on(word: *const u8, rounds: u32) -> bool {
+        let _ = word;
+        Self::relax_capped(rounds)
+    }
+}
+
+/// Keeps spinning at the capped pause length; the no_std default.
+pub struct Spin;
+
+impl Relax for Spin {
+    #[inline]
+    fn relax_capped(_rounds: u32) -> bool {
+        false
+    }
+}
+
+/// Spins for a while, then yields the timeslice, then sleeps briefly; the
+/// default with `std`.
+///
+/// When threads outnumber cores, the owner may be descheduled and pure
+/// spinning just burns the waiter's timeslice. Yielding lets the owner run.
+#[cfg(feature = "std")]
+pub struct AdaptiveRelax;
+
+#[cfg(feature = "std")]
+impl AdaptiveRelax {
+    /// Capped rounds spun before yielding.
+    const YIELD_AFTER: u32 = 16;
+    /// Capped rounds before yielding turns into sleeping.
+    const SLEEP_AFTER: u32 = 80;
+    const SLEEP: std::time::Duration = std::time::Duration::from_micros(50);
+}
+
+#[cfg(feature = "std")]
+impl Relax for AdaptiveRelax {
+    fn relax_capped(rounds: u32) -> bool {
+        if rounds < Self::YIELD_AFTER {
+            false
+        } else if rounds < Self::SLEEP_AFTER {
+            std::thread::yield_now();
+            true
+        } else {
+            std::thread::sleep(Self::SLEEP);
+            true
+        }
+    }
+}
+
+/// Sleeps in `umwait` until the watched word is written, or in `tpause`
+/// without one, for a bounded number of TSC ticks; x86_64 only.
+///
+/// Both instructions come with WAITPKG (Tremont, Sapphire Rapids and
+/// later), which is detected through CPUID on first use. Without it, and
+/// under Miri, this keeps spinning at the capped pause length like
+/// [`Spin`].
+///
+/// The deadline doubles with each round, up to about 64k ticks, and bounds
+/// the sleep: should the unlock land between the waiter's last load and
+/// arming the monitor, the waiter only oversleeps until then. The core
+/// sleeps in the lighter C0.1 state, which wakes faster, and the OS may cap
+/// the sleep further through `IA32_UMWAIT_CONTROL`.
+#[cfg(all(target_arch = "x86_64", not(any(loom, shuttle))))]
+pub struct Umwait;
+
+#[cfg(all(target_arch = "x86_64", not(any(loom, shuttle))))]
+impl Umwait {
+    /// TSC ticks of the first sleep; later ones double from here.
+    const FIRST_SLEEP: u64 = 1 << 10;
+    const MAX_SHIFT: u32 = 6;
+    /// The C0.1 sleep state, in the control operand.
+    const C0_1: u32 = 1;
+
+    /// Whether this CPU has `umwait` and `tpause`. Detected once, then
+    /// cached.
+    pub fn is_supported() -> bool {
+        use crate::atomic::Ordering;
+        use crate::atomic::global::AtomicU8;
+
+        /// 0 until detected, then 1 without WAITPKG and 2 with it.
+        static WAITPKG: AtomicU8 = AtomicU8::new(0);
+
+        if cfg!(miri) {
+            return false;
+        }
+        match WAITPKG.load(Ordering::Relaxed) {
+            0 => {
+                #[allow(unused_unsafe)]
+                // SAFETY: CPUID leaf 7 exists on every x86_64 CPU with
+                // WAITPKG, and reads as zeroes on older ones.
+                let leaf = unsafe { core::arch::x86_64::__cpuid_count(7, 0) };
+                let supported = leaf.ecx & (1 << 5) != 0;
+                WAITPKG.store(1 + u8::from(supported), Ordering::Relaxed);
+                supported
+            }
+            detected => detected == 2,
+        }
+    }
+
+    fn deadline(rounds: u32) -> u64 {
+        #[allow(unused_unsafe)]
+        // SAFETY: reading the TSC has no side effects.
+        let now = unsafe { core::arch::x86_64::_rdtsc() };
+        now.wrapping_add(Self::FIRST_SLEEP << rounds.min(Self::MAX_SHIFT))
+    }
+}
+
+#[cfg(all(target_arch = "x86_64", not(any(loom, shuttle))))]
+impl Relax for Umwait {
+    fn relax_capped(rounds: u32) -> bool {
+        if !Self::is_supported() {
+            return false;
+        }
+        let deadline = Self::deadline(rounds);
+        // SAFETY: WAITPKG is present, and `tpause` only suspends the core
+        // until the deadline or an interrupt.
+        unsafe {
+            core::arch::asm!(
+                "tpause {control:e}",
+                control = in(reg) Self::C0_1,
+                in("eax") deadline as u32,
+                in("edx") (deadline >> 32) as u32,
+                options(nomem, nostack),
+            );
+        }
+        true
+    }
+
+    fn relax_capped_on(word: *const u8, rounds: u32) -> bool {
+        if !Self::is_supported() {
+            return false;
+        }
+        let deadline = Self::deadline(rounds);
+        // SAFETY: WAITPKG is present. `umonitor` only arms the monitor on
+        // the line holding `word`, which is not dereferenced, and `umwait`
+        // suspends the core until that line is written, the deadline, or
+        // an interrupt.
+        unsafe {
+            core::arch::asm!(
+                "umonitor {word}",
+                "umwait {control:e}",
+                word = in(reg) word,
+                control = in(reg) Self::C0_1,
+                in("eax") deadline as u32,
+                in("edx") (deadline >> 32) as u32,
+                options(readonly, nostack),
+            );
+        }
+        true
+    }
+}
+
+/// The [`Relax`] strategy the crate's locks use. Under a model checker it is
+/// [`Spin`], since the model cannot see real yields and sleeps.
+#[cfg(all(feature = "umwait", target_arch = "x86_64", not(any(loom, shuttle))))]
+pub type DefaultRelax = Umwait;
+#[cfg(all(
+    feature = "std",
+    not(all(feature = "umwait", target_arch = "x86_64")),
+    not(any(loom, shuttle))
+))]
+pub type DefaultRelax = AdaptiveRelax;
+#[cfg(any(
+    all(
+        not(feature = "std"),
+        not(all(feature = "umwait", target_arch = "x86_64"))
+    ),
+    loom,
+    shuttle
+))]
+pub type DefaultRelax = Spin;
+
+/// Bounded exponential backoff for contended spin loops.
+///
+/// Each call pauses for twice as many iterations as the previous one, from
+/// 1 up to 64; after that `R` decides how to wait.
+#[cfg_attr(not(feature = "tiny"), derive(Debug))]
+pub struct Backoff<R: Relax = DefaultRelax> {
+    step: u32,
+    rounds: u32,
+    _relax: PhantomData<R>,
+}
+
+impl Backoff {
+    pub const fn new() -> Self {
+        Self::with_relax()
+    }
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<R: Relax> Backoff<R> {
+    /// Creates a backoff using the strategy `R` instead of [`DefaultRelax`].
+    pub const fn with_relax() -> Self {
+        Self {
+            step: 0,
+            rounds: 0,
+            _relax: PhantomData,
+        }
+    }
+
+    /// Returns `true` if `R` handled this round past the cap.
+    #[inline]
+    fn relax_capped(&mut self, word: Option<*const u8>) -> bool {
+        if self.step < MAX_BACKOFF_STEP {
+            return false;
+        }
+        self.rounds = self.rounds.saturating_add(1);
+        match word {
+            Some(word) => R::relax_capped_on(word, self.rounds - 1),
+            None => R::relax_capped(self.rounds - 1),
+        }
+    }
+
+    /// Backs off after a failed attempt, e.g. a lost CAS race.
+    #[inline]
+    pub fn spin(&mut self) {
+        if self.relax_capped(None) {
+            return;
+        }
+        self.pause();
+    }
+
+    /// The pause loop, one step longer each time up to the cap.
+    #[inline]
+    fn pause(&mut self) {
+        for _ in 0..1u32 << self.step {
+            spin();
+        }
+        if self.step < MAX_BACKOFF_STEP {
+            self.step += 1;
+        }
+    }
+
+    /// Backs off after observing the lock word held, so an unlock is
+    /// guaranteed to follow. On ARM this parks the core with `wfe` instead
+    /// of spinning.
+    #[inline]
+    pub fn wait(&mut self) {
+        self.wait_for(None);
+    }
+
+    /// Like [`wait`](Self::wait), after observing `word` held, so that `R`
+    /// can sleep until it is written; see [`Relax::relax_capped_on`].
+    #[inline]
+    pub fn wait_on<W>(&mut self, word: &W) {
+        self.wait_for(Some(core::ptr::from_ref(word).cast()));
+    }
+
+    #[inline]
+    fn wait_for(&mut self, word: Option<*const u8>) {
+        if self.relax_capped(word) {
+            return;
+        }
+        if cfg!(any(
+            target_arch = "aarch64",
+            all(target_arch = "arm", target_feature = "v7")
+        )) {
+            wait();
+            if self.step < MAX_BACKOFF_STEP {
+                self.step += 1;
+            }
+        } else {
+            self.pause();
+        }
+    }
+
+    /// Skips the remaining pause steps, so the next backoff past this one
+    /// is already up to `R`.
+    #[cfg(feature = "owner-tracking")]
+    pub(crate) fn saturate(&mut self) {
+        self.step = MAX_BACKOFF_STEP;
+    }
+
+    /// Starts over from a single pause iteration.
+    pub fn reset(&mut self) {
+        self.step = 0;
+        self.rounds = 0;
+    }
+}
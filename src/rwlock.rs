@@ -0,0 +1,801 @@
+//! A reader-writer spinlock gated on the same switch as `RawSpinLock`.
+//!
+//! [`RwSpinLock`] packs the reader count and a writer flag into one word.
+//! Before [`enable_raw_atomics`] its guards do not touch that word, so
+//! unlike a `RawSpinLock`'s it does not show bring-up holders.
+//!
+//! [`enable_raw_atomics`]: crate::raw::enable_raw_atomics
+
+use core::cell::UnsafeCell;
+use core::ops::Deref;
+use core::ops::DerefMut;
+#[cfg(feature = "track-location")]
+use core::panic::Location;
+use core::panic::RefUnwindSafe;
+use core::panic::UnwindSafe;
+#[cfg(feature = "track-location")]
+use core::ptr;
+
+#[cfg(feature = "track-location")]
+use crate::atomic::AtomicPtr;
+use crate::atomic::AtomicUsize;
+use crate::atomic::Ordering;
+use crate::error::GateDisabled;
+use crate::error::TryLockError;
+use crate::mutex::GuardKind;
+use crate::preempt;
+use crate::project::MappedRwSpinLockReadGuard;
+use crate::project::MappedRwSpinLockWriteGuard;
+use crate::raw::AtomicsEnabled;
+use crate::raw::MAX_READERS;
+use crate::raw::WRITE_FLAG;
+use crate::raw::raw_atomics_enabled;
+#[cfg(feature = "track-location")]
+use crate::raw::record_location;
+use crate::raw::rw_read_lock_atomic;
+use crate::raw::rw_read_relock_atomic;
+use crate::raw::rw_read_unlock_atomic;
+use crate::raw::rw_try_read_lock_atomic;
+use crate::raw::rw_try_write_lock_atomic;
+use crate::raw::rw_write_lock_atomic;
+use crate::raw::rw_write_unlock_atomic;
+use crate::transaction::TxWriteGuard;
+
+/// A reader-writer primitive from this crate.
+///
+/// Like [`Lock`](crate::mutex::Lock), this uses generic associated types and is not object safe.
+pub trait ReadWriteLock<T: ?Sized> {
+    type ReadGuard<'a>: Deref<Target = T>
+    where
+        Self: 'a;
+    type WriteGuard<'a>: DerefMut<Target = T>
+    where
+        Self: 'a;
+
+    fn read(&self) -> Self::ReadGuard<'_>;
+
+    fn try_read(&self) -> Option<Self::ReadGuard<'_>>;
+
+    fn write(&self) -> Self::WriteGuard<'_>;
+
+    fn try_write(&self) -> Option<Self::WriteGuard<'_>>;
+
+    /// Like [`try_read`](Self::try_read), but says why it failed.
+    #[track_caller]
+    fn try_read_result(&self) -> Result<Self::ReadGuard<'_>, TryLockError> {
+        self.try_read().ok_or(TryLockError::WouldBlock)
+    }
+
+    /// Like [`try_write`](Self::try_write), but says why it failed.
+    #[track_caller]
+    fn try_write_result(&self) -> Result<Self::WriteGuard<'_>, TryLockError> {
+        self.try_write().ok_or(TryLockError::WouldBlock)
+    }
+}
+
+pub struct RwSpinLock<T: ?Sized> {
+    state: AtomicUsize,
+    /// Readers beyond this many wait as if a writer were pending.
+    max_readers: usize,
+    /// Where a reader or the writer last acquired the lock, or null.
+    #[cfg(feature = "track-location")]
+    last_acquired_at: AtomicPtr<Location<'static>>,
+    /// The readers holding the lock, as far as the slot table can tell.
+    #[cfg(feature = "reader-tracking")]
+    readers: crate::readers::ReaderSlots,
+    data: UnsafeCell<T>,
+}
+
+pub struct RwSpinLockReadGuard<'a, T> {
+    lock: &'a RwSpinLock<T>,
+    unlock_on_drop: bool,
+    /// The reader slot to free on drop.
+    #[cfg(feature = "reader-tracking")]
+    slot: usize,
+}
+
+pub struct RwSpinLockWriteGuard<'a, T> {
+    lock: &'a RwSpinLock<T>,
+    unlock_on_drop: bool,
+}
+
+unsafe impl<T: ?Sized + Send> Send for RwSpinLock<T> {}
+unsafe impl<T: ?Sized + Send + Sync> Sync for RwSpinLock<T> {}
+
+// As for `RawSpinLock`: no poisoning, so a writer that panics leaves the
+// data as it was for the next reader.
+impl<T: ?Sized> UnwindSafe for RwSpinLock<T> {}
+impl<T: ?Sized> RefUnwindSafe for RwSpinLock<T> {}
+
+impl<T> RwSpinLock<T> {
+    pub const fn new(data: T) -> Self {
+        Self::with_max_readers(data, MAX_READERS)
+    }
+
+    /// Creates a lock that admits at most `max_readers` readers at a time,
+    /// to bound how long a writer can wait for them to drain, or how many
+    /// readers share what the data guards.
+    ///
+    /// [`read`](Self::read) spins past the cap as it does while a writer is
+    /// pending, and [`try_read`](Self::try_read) fails. Clones of a read
+    /// guard can exceed the cap, since the cloning reader cannot wait for
+    /// another to leave. Before raw atomics are enabled no reader is
+    /// counted, so the cap does not apply.
+    ///
+    /// # Panics
+    ///
+    /// If `max_readers` is 0 or more than [`MAX_READERS`].
+    pub const fn with_max_readers(data: T, max_readers: usize) -> Self {
+        assert!(
+            max_readers != 0 && max_readers <= MAX_READERS,
+            "max_readers must be between 1 and MAX_READERS"
+        );
+        Self {
+            state: AtomicUsize::new(0),
+            max_readers,
+            #[cfg(feature = "track-location")]
+            last_acquired_at: AtomicPtr::new(ptr::null_mut()),
+            #[cfg(feature = "reader-tracking")]
+            readers: crate::readers::ReaderSlots::new(),
+            data: UnsafeCell::new(data),
+        }
+    }
+
+    #[track_caller]
+    pub fn read(&self) -> RwSpinLockReadGuard<'_, T> {
+        match AtomicsEnabled::get() {
+            Some(enabled) => self.read_with(enabled),
+            None => RwSpinLockReadGuard {
+                lock: self,
+                unlock_on_drop: false,
+                #[cfg(feature = "reader-tracking")]
+                slot: crate::readers::UNTRACKED,
+            },
+        }
+    }
+
+    /// Like [`read`](Self::read), for callers that hold the
+    /// [`AtomicsEnabled`] token: the gate is not checked, and the guard
+    /// always holds the lock.
+    #[track_caller]
+    pub fn read_with(&self, _: AtomicsEnabled) -> RwSpinLockReadGuard<'_, T> {
+        rw_read_lock_atomic(
+            &self.state,
+            self.max_readers,
+            #[cfg(feature = "watchdog")]
+            self.watched(),
+        );
+        #[cfg(feature = "track-location")]
+        record_location(&self.last_acquired_at);
+        preempt::disable();
+        RwSpinLockReadGuard {
+            lock: self,
+            unlock_on_drop: true,
+            #[cfg(feature = "reader-tracking")]
+            slot: self.readers.claim(),
+        }
+    }
+
+    /// Attempts to acquire a read guard without spinning.
+    ///
+    /// Fails while a writer holds or is waiting for the lock, or while the
+    /// readers are at the [cap](Self::with_max_readers).
+    ///
+    /// Async-signal-safe: see [`signal`](crate::signal).
+    #[track_caller]
+    pub fn try_read(&self) -> Option<RwSpinLockReadGuard<'_, T>> {
+        self.try_read_result().ok()
+    }
+
+    /// Like [`read`](Self::read), but fails instead of handing out a guard
+    /// that excludes nobody before raw atomics are enabled.
+    #[track_caller]
+    pub fn read_checked(&self) -> Result<RwSpinLockReadGuard<'_, T>, GateDisabled> {
+        if !raw_atomics_enabled() {
+            return Err(GateDisabled::new());
+        }
+        Ok(self.read())
+    }
+
+    /// Like [`try_read_result`](Self::try_read_result), but fails with
+    /// [`TryLockError::GateDisabled`] before raw atomics are enabled.
+    #[track_caller]
+    pub fn try_read_checked(&self) -> Result<RwSpinLockReadGuard<'_, T>, TryLockError> {
+        if !raw_atomics_enabled() {
+            return Err(GateDisabled::new().into());
+        }
+        self.try_read_result()
+    }
+
+    /// Like [`try_read`](Self::try_read), but returns
+    /// [`TryLockError::WouldBlock`] where that returns `None`.
+    #[track_caller]
+    pub fn try_read_result(&self) -> Result<RwSpinLockReadGuard<'_, T>, TryLockError> {
+        #[cfg(feature = "fault-injection")]
+        if crate::fault::inject() {
+            return Err(TryLockError::WouldBlock);
+        }
+        let unlock_on_drop = raw_atomics_enabled();
+        #[cfg(feature = "reader-tracking")]
+        let mut slot = crate::readers::UNTRACKED;
+        if unlock_on_drop {
+            if !rw_try_read_lock_atomic(&self.state, self.max_readers) {
+                return Err(TryLockError::WouldBlock);
+            }
+            #[cfg(feature = "track-location")]
+            record_location(&self.last_acquired_at);
+            preempt::disable();
+            #[cfg(feature = "reader-tracking")]
+            {
+                slot = self.readers.claim();
+            }
+        }
+        Ok(RwSpinLockReadGuard {
+            lock: self,
+            unlock_on_drop,
+            #[cfg(feature = "reader-tracking")]
+            slot,
+        })
+    }
+
+    #[track_caller]
+    pub fn write(&self) -> RwSpinLockWriteGuard<'_, T> {
+        match AtomicsEnabled::get() {
+            Some(enabled) => self.write_with(enabled),
+            None => RwSpinLockWriteGuard {
+                lock: self,
+                unlock_on_drop: false,
+            },
+        }
+    }
+
+    /// Like [`write`](Self::write), for callers that hold the
+    /// [`AtomicsEnabled`] token: the gate is not checked, and the guard
+    /// always holds the lock.
+    #[track_caller]
+    pub fn write_with(&self, _: AtomicsEnabled) -> RwSpinLockWriteGuard<'_, T> {
+        rw_write_lock_atomic(
+            &self.state,
+            #[cfg(feature = "watchdog")]
+            self.watched(),
+        );
+        #[cfg(feature = "track-location")]
+        record_location(&self.last_acquired_at);
+        preempt::disable();
+        RwSpinLockWriteGuard {
+            lock: self,
+            unlock_on_drop: true,
+        }
+    }
+
+    /// Attempts to acquire the write guard without spinning.
+    ///
+    /// Fails while any reader or writer holds the lock.
+    ///
+    /// Async-signal-safe: see [`signal`](crate::signal).
+    #[track_caller]
+    pub fn try_write(&self) -> Option<RwSpinLockWriteGuard<'_, T>> {
+        self.try_write_result().ok()
+    }
+
+    /// Like [`write`](Self::write), but fails instead of handing out a guard
+    /// that excludes nobody before raw atomics are enabled.
+    #[track_caller]
+    pub fn write_checked(&self) -> Result<RwSpinLockWriteGuard<'_, T>, GateDisabled> {
+        if !raw_atomics_enabled() {
+            return Err(GateDisabled::new());
+        }
+        Ok(self.write())
+    }
+
+    /// Like [`try_write_result`](Self::try_write_result), but fails with
+    /// [`TryLockError::GateDisabled`] before raw atomics are enabled.
+    #[track_caller]
+    pub fn try_write_checked(&self) -> Result<RwSpinLockWriteGuard<'_, T>, TryLockError> {
+        if !raw_atomics_enabled() {
+            return Err(GateDisabled::new().into());
+        }
+        self.try_write_result()
+    }
+
+    /// Like [`try_write`](Self::try_write), but returns
+    /// [`TryLockError::WouldBlock`] where that returns `None`.
+    #[track_caller]
+    pub fn try_write_result(&self) -> Result<RwSpinLockWriteGuard<'_, T>, TryLockError> {
+        #[cfg(feature = "fault-injection")]
+        if crate::fault::inject() {
+            return Err(TryLockError::WouldBlock);
+        }
+        let unlock_on_drop = raw_atomics_enabled();
+        if unlock_on_drop {
+            if !rw_try_write_lock_atomic(&self.state) {
+                return Err(TryLockError::WouldBlock);
+            }
+            #[cfg(feature = "track-location")]
+            record_location(&self.last_acquired_at);
+            preempt::disable();
+        }
+        Ok(RwSpinLockWriteGuard {
+            lock: self,
+            unlock_on_drop,
+        })
+    }
+
+    /// Takes the write lock for a change that is published all at once, or
+    /// not at all; see [`transaction`](crate::transaction). The guard
+    /// derefs to a clone of the data.
+    #[track_caller]
+    pub fn write_transaction(&self) -> TxWriteGuard<'_, T>
+    where
+        T: Clone,
+    {
+        let guard = self.write();
+        let working = (*guard).clone();
+        TxWriteGuard::new(guard, working)
+    }
+
+    /// Like [`write_transaction`](Self::write_transaction), with a plain
+    /// copy of the data that never allocates.
+    #[track_caller]
+    pub fn write_transaction_copy(&self) -> TxWriteGuard<'_, T>
+    where
+        T: Copy,
+    {
+        let guard = self.write();
+        let working = *guard;
+        TxWriteGuard::new(guard, working)
+    }
+
+    /// Returns where a reader or the writer last acquired the lock, for
+    /// debugging. With several readers it is the latest of them.
+    #[cfg(feature = "track-location")]
+    pub fn last_acquired_at(&self) -> Option<&'static Location<'static>> {
+        // SAFETY: the pointer is null or comes from a `&'static Location`.
+        unsafe { self.last_acquired_at.load(Ordering::Relaxed).as_ref() }
+    }
+
+    /// The readers holding the lock, for debugging a writer that cannot get
+    /// in. Only a snapshot; readers beyond the
+    /// [`READER_SLOTS`](crate::readers::READER_SLOTS) are counted by
+    /// [`untracked_readers`](Self::untracked_readers) instead. Guards from
+    /// before raw atomics were enabled are never listed.
+    #[cfg(feature = "reader-tracking")]
+    pub fn current_readers(&self) -> impl Iterator<Item = crate::readers::ReaderInfo> + '_ {
+        self.readers.current()
+    }
+
+    /// How many readers hold the lock without a slot, because the slots
+    /// were taken or there was no [`OwnerId`](crate::owner::OwnerId) to
+    /// record.
+    #[cfg(feature = "reader-tracking")]
+    pub fn untracked_readers(&self) -> usize {
+        self.readers.untracked()
+    }
+
+    /// Spins until no writer holds or waits for the lock, without taking
+    /// it. Readers may still hold it.
+    ///
+    /// Like [`RawSpinLock::wait_unlocked`](crate::mutex::RawSpinLock::wait_unlocked),
+    /// this is a momentary observation, synchronizes with the last write
+    /// unlock, is watched by the `watchdog` feature and returns
+    /// at once before raw atomics are enabled.
+    #[track_caller]
+    pub fn wait_no_writer(&self) {
+        let _ = self.wait_until(None, |state| state & WRITE_FLAG == 0);
+    }
+
+    /// Like [`wait_no_writer`](Self::wait_no_writer), but gives up with
+    /// [`TryLockError::TimedOut`] after `spins` spins.
+    #[track_caller]
+    pub fn wait_no_writer_for(&self, spins: usize) -> Result<(), TryLockError> {
+        self.wait_until(Some(spins), |state| state & WRITE_FLAG == 0)
+            .map_err(|spins| TryLockError::TimedOut { spins })
+    }
+
+    /// Spins until neither readers nor a writer hold the lock, without
+    /// taking it; otherwise like [`wait_no_writer`](Self::wait_no_writer).
+    /// Readers that arrive while a writer waits briefly count as holding
+    /// the lock, so this may spin a little longer than needed.
+    #[track_caller]
+    pub fn wait_idle(&self) {
+        let _ = self.wait_until(None, |state| state == 0);
+    }
+
+    /// Like [`wait_idle`](Self::wait_idle), but gives up with
+    /// [`TryLockError::TimedOut`] after `spins` spins.
+    #[track_caller]
+    pub fn wait_idle_for(&self, spins: usize) -> Result<(), TryLockError> {
+        self.wait_until(Some(spins), |state| state == 0)
+            .map_err(|spins| TryLockError::TimedOut { spins })
+    }
+
+    /// Spins until every reader that held the lock when this was called has
+    /// released it, without taking the lock, so new readers and writers
+    /// carry on meanwhile; otherwise like
+    /// [`wait_no_writer`](Self::wait_no_writer).
+    ///
+    /// It returns once it sees no readers at all, with an Acquire load, so
+    /// whatever those earlier readers did while holding the lock happens
+    /// before the return. A writer holding the lock does not delay it.
+    ///
+    /// Readers that start during the wait are not waited for: they may
+    /// still be reading when this returns. But the count only reaches zero
+    /// at a moment when no reader at all holds the lock, so readers that
+    /// keep overlapping one another delay the return for as long as they
+    /// do; use [`wait_for_readers_to_drain_for`](Self::wait_for_readers_to_drain_for)
+    /// to bound the wait. Readers that arrive while a writer waits briefly
+    /// count too, as for [`wait_idle`](Self::wait_idle).
+    #[track_caller]
+    pub fn wait_for_readers_to_drain(&self) {
+        let _ = self.wait_until(None, |state| state & !WRITE_FLAG == 0);
+    }
+
+    /// Like [`wait_for_readers_to_drain`](Self::wait_for_readers_to_drain),
+    /// but gives up with [`TryLockError::TimedOut`] after `spins` spins.
+    #[track_caller]
+    pub fn wait_for_readers_to_drain_for(&self, spins: usize) -> Result<(), TryLockError> {
+        self.wait_until(Some(spins), |state| state & !WRITE_FLAG == 0)
+            .map_err(|spins| TryLockError::TimedOut { spins })
+    }
+
+    #[track_caller]
+    fn wait_until(&self, budget: Option<usize>, idle: fn(usize) -> bool) -> Result<(), usize> {
+        let idle = || idle(self.state.load(Ordering::Acquire));
+        if !raw_atomics_enabled() || idle() {
+            return Ok(());
+        }
+        crate::raw::wait_until(
+            idle,
+            budget,
+            #[cfg(feature = "watchdog")]
+            self.state.as_ptr().addr(),
+            #[cfg(feature = "watchdog")]
+            self.watched(),
+        )
+    }
+
+    #[cfg(feature = "watchdog")]
+    fn watched(&self) -> crate::watchdog::Watched<'_> {
+        crate::watchdog::Watched {
+            spin_threshold: None,
+            #[cfg(feature = "track-location")]
+            last_acquired_at: &self.last_acquired_at,
+        }
+    }
+
+    /// Decodes the lock word; see [`state`](crate::state). Only a
+    /// snapshot, and always unlocked before raw atomics are enabled.
+    pub fn state(&self) -> crate::state::RwState {
+        crate::state::RwState::decode(self.state.load(Ordering::Relaxed))
+    }
+
+    /// The reader cap set by [`with_max_readers`](Self::with_max_readers),
+    /// [`MAX_READERS`] by default.
+    pub fn max_readers(&self) -> usize {
+        self.max_readers
+    }
+
+    /// The number of readers holding the lock, under `spin::RwLock`'s
+    /// name. Only a snapshot, and always 0 before raw atomics are enabled.
+    #[cfg(feature = "spin-compat")]
+    pub fn reader_count(&self) -> usize {
+        self.state.load(Ordering::Relaxed) & !WRITE_FLAG
+    }
+
+    /// 1 if a writer holds the lock and 0 otherwise, under `spin::RwLock`'s
+    /// name. Only a snapshot, and always 0 before raw atomics are enabled.
+    #[cfg(feature = "spin-compat")]
+    pub fn writer_count(&self) -> usize {
+        usize::from(self.state.load(Ordering::Relaxed) & WRITE_FLAG != 0)
+    }
+
+    /// The address async waiters on this lock are queued under.
+    #[cfg(all(feature = "async", not(any(loom, shuttle))))]
+    pub(crate) fn state_key(&self) -> usize {
+        &self.state as *const AtomicUsize as usize
+    }
+
+    /// Consumes the lock and returns the protected value.
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+
+    /// Returns a mutable reference to the data; no locking is needed since
+    /// the borrow is exclusive.
+    pub fn get_mut(&mut self) -> &mut T {
+        self.data.get_mut()
+    }
+}
+
+impl<T> RwSpinLockReadGuard<'_, T> {
+    /// Whether the guard really holds the lock: `false` if it was handed
+    /// out before raw atomics were enabled.
+    pub fn holds_lock(&self) -> bool {
+        self.unlock_on_drop
+    }
+
+    /// How the guard was handed out; never [`GuardKind::Bypass`], as the
+    /// lock has no `no_lock`.
+    pub fn kind(&self) -> GuardKind {
+        if self.unlock_on_drop {
+            GuardKind::Atomic
+        } else {
+            GuardKind::PreAtomic
+        }
+    }
+}
+
+impl<'a, T> RwSpinLockReadGuard<'a, T> {
+    /// Narrows the guard to part of the data, like `Ref::map`. The lock
+    /// stays held until the returned guard is dropped.
+    pub fn map<U: ?Sized>(
+        this: Self,
+        f: impl FnOnce(&T) -> &U,
+    ) -> MappedRwSpinLockReadGuard<'a, T, U> {
+        MappedRwSpinLockReadGuard::new(this, f)
+    }
+}
+
+/// Takes another read lock, as `parking_lot`'s read guards do: the clone
+/// unlocks on its own drop, so the lock stays read-locked until both are
+/// gone. A guard from before raw atomics were enabled clones into another
+/// one that holds nothing.
+///
+/// # Panics
+///
+/// Panics if the reader count would overflow.
+impl<T> Clone for RwSpinLockReadGuard<'_, T> {
+    #[track_caller]
+    fn clone(&self) -> Self {
+        #[cfg(feature = "reader-tracking")]
+        let mut slot = crate::readers::UNTRACKED;
+        if self.unlock_on_drop {
+            rw_read_relock_atomic(&self.lock.state);
+            preempt::disable();
+            #[cfg(feature = "reader-tracking")]
+            {
+                slot = self.lock.readers.claim();
+            }
+        }
+        Self {
+            lock: self.lock,
+            unlock_on_drop: self.unlock_on_drop,
+            #[cfg(feature = "reader-tracking")]
+            slot,
+        }
+    }
+}
+
+impl<T> Drop for RwSpinLockReadGuard<'_, T> {
+    fn drop(&mut self) {
+        if self.unlock_on_drop {
+            // Freed first, so a listed reader still holds the lock.
+            #[cfg(feature = "reader-tracking")]
+            self.lock.readers.release(self.slot);
+            rw_read_unlock_atomic(&self.lock.state);
+            preempt::enable();
+        }
+    }
+}
+
+#[cfg(feature = "spin-compat")]
+impl<'a, T> RwSpinLockReadGuard<'a, T> {
+    /// Keeps the read lock held forever, as `spin::RwLockReadGuard::leak`.
+    pub fn leak(this: Self) -> &'a T {
+        let data = this.lock.data.get();
+        core::mem::forget(this);
+        // SAFETY: the read lock stays held, so no writer can get in.
+        unsafe { &*data }
+    }
+}
+
+impl<T> Deref for RwSpinLockReadGuard<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &Self::Target {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<T> RwSpinLockWriteGuard<'_, T> {
+    /// Whether the guard really holds the lock: `false` if it was handed
+    /// out before raw atomics were enabled.
+    pub fn holds_lock(&self) -> bool {
+        self.unlock_on_drop
+    }
+
+    /// How the guard was handed out; never [`GuardKind::Bypass`], as the
+    /// lock has no `no_lock`.
+    pub fn kind(&self) -> GuardKind {
+        if self.unlock_on_drop {
+            GuardKind::Atomic
+        } else {
+            GuardKind::PreAtomic
+        }
+    }
+}
+
+impl<'a, T> RwSpinLockWriteGuard<'a, T> {
+    /// Narrows the guard to part of the data, like `RefMut::map`. The lock
+    /// stays held until the returned guard is dropped.
+    pub fn map<U: ?Sized>(
+        this: Self,
+        f: impl FnOnce(&mut T) -> &mut U,
+    ) -> MappedRwSpinLockWriteGuard<'a, T, U> {
+        MappedRwSpinLockWriteGuard::new(this, f)
+    }
+}
+
+impl<T> Drop for RwSpinLockWriteGuard<'_, T> {
+    fn drop(&mut self) {
+        if self.unlock_on_drop {
+            rw_write_unlock_atomic(&self.lock.state);
+            preempt::enable();
+        }
+    }
+}
+
+#[cfg(feature = "spin-compat")]
+impl<'a, T> RwSpinLockWriteGuard<'a, T> {
+    /// Keeps the write lock held forever, as `spin::RwLockWriteGuard::leak`.
+    pub fn leak(this: Self) -> &'a mut T {
+        let data = this.lock.data.get();
+        core::mem::forget(this);
+        // SAFETY: the write lock stays held, so this is the only reference.
+        unsafe { &mut *data }
+    }
+}
+
+impl<T> Deref for RwSpinLockWriteGuard<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &Self::Target {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<T> DerefMut for RwSpinLockWriteGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+/// Reads from the protected reader, so a guard can be passed where a
+/// `Read` is expected.
+#[cfg(feature = "std")]
+impl<T: std::io::Read> std::io::Read for RwSpinLockWriteGuard<'_, T> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        (**self).read(buf)
+    }
+
+    fn read_vectored(&mut self, bufs: &mut [std::io::IoSliceMut<'_>]) -> std::io::Result<usize> {
+        (**self).read_vectored(bufs)
+    }
+
+    fn read_to_end(&mut self, buf: &mut Vec<u8>) -> std::io::Result<usize> {
+        (**self).read_to_end(buf)
+    }
+
+    fn read_to_string(&mut self, buf: &mut String) -> std::io::Result<usize> {
+        (**self).read_to_string(buf)
+    }
+
+    fn read_exact(&mut self, buf: &mut [u8]) -> std::io::Result<()> {
+        (**self).read_exact(buf)
+    }
+}
+
+/// Writes to the protected writer; a `write_all` through
+/// one guard reaches the writer in one piece.
+#[cfg(feature = "std")]
+impl<T: std::io::Write> std::io::Write for RwSpinLockWriteGuard<'_, T> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        (**self).write(buf)
+    }
+
+    fn write_vectored(&mut self, bufs: &[std::io::IoSlice<'_>]) -> std::io::Result<usize> {
+        (**self).write_vectored(bufs)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        (**self).flush()
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> std::io::Result<()> {
+        (**self).write_all(buf)
+    }
+
+    fn write_fmt(&mut self, args: core::fmt::Arguments<'_>) -> std::io::Result<()> {
+        (**self).write_fmt(args)
+    }
+}
+
+/// Formats the value without blocking, or `<locked>` if a writer holds the lock.
+#[cfg(feature = "defmt")]
+impl<T: defmt::Format> defmt::Format for RwSpinLock<T> {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        match self.try_read() {
+            Some(guard) => defmt::write!(f, "RwSpinLock {{ data: {} }}", &*guard),
+            None => defmt::write!(f, "RwSpinLock {{ data: <locked> }}"),
+        }
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl<T: defmt::Format> defmt::Format for RwSpinLockReadGuard<'_, T> {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        defmt::Format::format(&**self, f)
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl<T: defmt::Format> defmt::Format for RwSpinLockWriteGuard<'_, T> {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        defmt::Format::format(&**self, f)
+    }
+}
+
+/// Serializes the protected value as if it were not wrapped, under a read
+/// guard, so this blocks while a writer holds or waits for the lock.
+#[cfg(feature = "serde")]
+impl<T: serde::Serialize> serde::Serialize for RwSpinLock<T> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.read().serialize(serializer)
+    }
+}
+
+/// Deserializes the value into a new, unlocked lock.
+#[cfg(feature = "serde")]
+impl<'de, T: serde::Deserialize<'de>> serde::Deserialize<'de> for RwSpinLock<T> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        T::deserialize(deserializer).map(Self::new)
+    }
+}
+
+/// Clones the value under a read guard into a new, unlocked lock, so this
+/// blocks while a writer holds or waits for the lock. The last acquisition
+/// site is not copied.
+impl<T: Clone> Clone for RwSpinLock<T> {
+    #[track_caller]
+    fn clone(&self) -> Self {
+        Self::new(T::clone(&self.read()))
+    }
+}
+
+impl<T> ReadWriteLock<T> for RwSpinLock<T> {
+    type ReadGuard<'a>
+        = RwSpinLockReadGuard<'a, T>
+    where
+        T: 'a;
+    type WriteGuard<'a>
+        = RwSpinLockWriteGuard<'a, T>
+    where
+        T: 'a;
+
+    #[track_caller]
+    fn read(&self) -> Self::ReadGuard<'_> {
+        RwSpinLock::read(self)
+    }
+
+    #[track_caller]
+    fn try_read(&self) -> Option<Self::ReadGuard<'_>> {
+        RwSpinLock::try_read(self)
+    }
+
+    #[track_caller]
+    fn write(&self) -> Self::WriteGuard<'_> {
+        RwSpinLock::write(self)
+    }
+
+    #[track_caller]
+    fn try_write(&self) -> Option<Self::WriteGuard<'_>> {
+        RwSpinLock::try_write(self)
+    }
+    #[track_caller]
+    fn try_read_result(&self) -> Result<Self::ReadGuard<'_>, TryLockError> {
+        RwSpinLock::try_read_result(self)
+    }
+
+    #[track_caller]
+    fn try_write_result(&self) -> Result<Self::WriteGuard<'_>, TryLockError> {
+        RwSpinLock::try_write_result(self)
+    }
+}
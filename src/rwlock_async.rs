@@ -0,0 +1,451 @@
+//! Futures that take an [`RwSpinLock`] without blocking an executor.
+//!
+//! [`RwSpinLock::read_async`] and [`RwSpinLock::write_async`] return futures
+//! that try the lock, spin a little, and then register their waker and
+//! return `Pending`. Releasing a read or write lock checks one global count
+//! of registered futures and, only while it is nonzero, wakes those waiting
+//! on the lock just released. With the `async` feature every release also
+//! issues a full fence before that check; without it nothing changes.
+//!
+//! A waiting future is an intrusive node inside the pinned future itself,
+//! linked into one of a fixed set of queues picked by lock address, each
+//! under a small spinlock of its own, so nothing is allocated. While a write
+//! future is queued on a lock, read futures on that lock queue behind it
+//! instead of taking the lock, and a release wakes the first queued writer
+//! rather than the readers, so a stream of async readers cannot starve an
+//! async writer. Synchronous readers and writers do not take part: they take
+//! the lock whenever its word allows.
+//!
+//! Dropping a future unlinks its node. A writer's node going away wakes the
+//! readers it held back, and a node that had been woken passes the wakeup
+//! on, so cancelling a future never strands the others.
+//!
+//! As with any writer-preferring lock, a task that awaits a read lock while
+//! it holds another read guard of the same lock deadlocks behind a queued
+//! writer. Before raw atomics are enabled every `try_*` succeeds, so the
+//! futures complete on their first poll.
+
+use core::cell::UnsafeCell;
+use core::future::Future;
+use core::marker::PhantomPinned;
+use core::pin::Pin;
+use core::ptr;
+use core::sync::atomic::fence;
+use core::task::Context;
+use core::task::Poll;
+use core::task::Waker;
+
+use crate::atomic::AtomicBool;
+use crate::atomic::AtomicUsize;
+use crate::atomic::Ordering;
+use crate::atomic::spin_loop;
+use crate::raw::raw_atomics_enabled;
+use crate::rwlock::RwSpinLock;
+use crate::rwlock::RwSpinLockReadGuard;
+use crate::rwlock::RwSpinLockWriteGuard;
+
+/// Tries a future makes on its first poll before registering its waker.
+const SPINS_BEFORE_REGISTER: usize = 64;
+
+const QUEUES: usize = 64;
+
+/// Nodes linked into any queue.
+static LINKED: AtomicUsize = AtomicUsize::new(0);
+
+static QUEUES_BY_ADDR: [Queue; QUEUES] = [const { Queue::new() }; QUEUES];
+
+struct Node {
+    /// The address of the lock's state word.
+    key: usize,
+    writer: bool,
+    waker: Option<Waker>,
+    linked: bool,
+    /// Set when a release took the waker, cleared when the future
+    /// registers again.
+    woken: bool,
+    prev: *mut Node,
+    next: *mut Node,
+}
+
+struct Queue {
+    locked: AtomicBool,
+    list: UnsafeCell<List>,
+}
+
+struct List {
+    head: *mut Node,
+    tail: *mut Node,
+}
+
+// SAFETY: `list` and the nodes linked into it are only touched with
+// `locked` held.
+unsafe impl Sync for Queue {}
+
+impl Queue {
+    const fn new() -> Self {
+        Self {
+            locked: AtomicBool::new(false),
+            list: UnsafeCell::new(List {
+                head: ptr::null_mut(),
+                tail: ptr::null_mut(),
+            }),
+        }
+    }
+
+    fn of(key: usize) -> &'static Self {
+        &QUEUES_BY_ADDR[(key >> 3) % QUEUES]
+    }
+
+    /// Only called once raw atomics are enabled, since the futures only
+    /// wait after a `try_*` has failed.
+    fn lock(&self) -> QueueGuard<'_> {
+        while self.locked.swap(true, Ordering::Acquire) {
+            while self.locked.load(Ordering::Relaxed) {
+                spin_loop();
+            }
+        }
+        QueueGuard(self)
+    }
+}
+
+struct QueueGuard<'a>(&'a Queue);
+
+impl QueueGuard<'_> {
+    fn list(&mut self) -> &mut List {
+        // SAFETY: the guard holds the queue's lock.
+        unsafe { &mut *self.0.list.get() }
+    }
+}
+
+impl Drop for QueueGuard<'_> {
+    fn drop(&mut self) {
+        self.0.locked.store(false, Ordering::Release);
+    }
+}
+
+impl List {
+    /// # Safety
+    ///
+    /// `node` must be unlinked and stay put until it is unlinked again.
+    unsafe fn push_back(&mut self, node: *mut Node) {
+        // SAFETY: the caller's promise, and linked nodes are live.
+        unsafe {
+            (*node).prev = self.tail;
+            (*node).next = ptr::null_mut();
+            (*node).linked = true;
+            match self.tail.as_mut() {
+                Some(tail) => tail.next = node,
+                None => self.head = node,
+            }
+        }
+        self.tail = node;
+        LINKED.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// # Safety
+    ///
+    /// `node` must be linked into this list.
+    unsafe fn unlink(&mut self, node: *mut Node) {
+        // SAFETY: linked nodes, and their neighbours, are live.
+        unsafe {
+            let (prev, next) = ((*node).prev, (*node).next);
+            match prev.as_mut() {
+                Some(prev) => prev.next = next,
+                None => self.head = next,
+            }
+            match next.as_mut() {
+                Some(next) => next.prev = prev,
+                None => self.tail = prev,
+            }
+            (*node).linked = false;
+        }
+        LINKED.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    fn nodes(&self) -> impl Iterator<Item = *mut Node> + '_ {
+        let mut node = self.head;
+        core::iter::from_fn(move || {
+            let current = node;
+            // SAFETY: linked nodes are live.
+            node = unsafe { current.as_ref()?.next };
+            Some(current)
+        })
+    }
+
+    fn has_writer(&self, key: usize) -> bool {
+        // SAFETY: linked nodes are live.
+        self.nodes()
+            .any(|node| unsafe { (*node).key == key && (*node).writer })
+    }
+
+    /// Takes the waker of the first writer on `key`, or if there is none,
+    /// of the first reader that has one.
+    fn take_next_waker(&mut self, key: usize) -> Option<Waker> {
+        let writer = self.has_writer(key);
+        let node = self.nodes().find(|&node| {
+            // SAFETY: linked nodes are live.
+            let node = unsafe { &*node };
+            node.key == key && node.writer == writer && node.waker.is_some()
+        })?;
+        // SAFETY: as above; the queue's lock is held. A writer already woken
+        // has no waker, so it keeps its place without being woken again.
+        let node = unsafe { &mut *node };
+        node.woken = true;
+        node.waker.take()
+    }
+
+    fn readers_with_wakers(&self, key: usize) -> usize {
+        self.nodes()
+            .filter(|&node| {
+                // SAFETY: linked nodes are live.
+                let node = unsafe { &*node };
+                node.key == key && !node.writer && node.waker.is_some()
+            })
+            .count()
+    }
+}
+
+/// Called by every release of an `RwSpinLock` word, after the releasing
+/// RMW.
+#[inline(always)]
+pub(crate) fn notify(state: &AtomicUsize) {
+    // Pairs with the fence in `Wait::poll`: either the future sees the
+    // release when it tries again, or this sees it linked.
+    fence(Ordering::SeqCst);
+    if LINKED.load(Ordering::Relaxed) != 0 {
+        wake(state as *const AtomicUsize as usize);
+    }
+}
+
+#[cold]
+#[inline(never)]
+fn wake(key: usize) {
+    let queue = Queue::of(key);
+    let (first, readers) = {
+        let mut guard = queue.lock();
+        let writer = guard.list().has_writer(key);
+        let readers = if writer {
+            0
+        } else {
+            guard.list().readers_with_wakers(key)
+        };
+        (guard.list().take_next_waker(key), readers)
+    };
+    // Wakers run without the queue's lock, since a waker may poll the
+    // future, and the count bounds the passes should woken readers
+    // register again straight away.
+    let Some(first) = first else { return };
+    first.wake();
+    for _ in 1..readers {
+        let next = queue.lock().list().take_next_waker(key);
+        match next {
+            Some(waker) => waker.wake(),
+            None => break,
+        }
+    }
+}
+
+/// The node and bookkeeping both futures share.
+struct Wait {
+    node: UnsafeCell<Node>,
+    /// Whether the node may be linked, so `Drop` must take the queue's lock.
+    registered: bool,
+    polled: bool,
+    _pinned: PhantomPinned,
+}
+
+// SAFETY: the node's pointers are only followed under its queue's lock, and
+// its waker is `Send`.
+unsafe impl Send for Wait {}
+// SAFETY: `&Wait` gives no access to the node.
+unsafe impl Sync for Wait {}
+
+impl Wait {
+    fn new(key: usize, writer: bool) -> Self {
+        Self {
+            node: UnsafeCell::new(Node {
+                key,
+                writer,
+                waker: None,
+                linked: false,
+                woken: false,
+                prev: ptr::null_mut(),
+                next: ptr::null_mut(),
+            }),
+            registered: false,
+            polled: false,
+            _pinned: PhantomPinned,
+        }
+    }
+
+    fn key(&self) -> usize {
+        // SAFETY: `key` is never written after `new`.
+        unsafe { (*self.node.get()).key }
+    }
+
+    fn writer(&self) -> bool {
+        // SAFETY: as for `key`.
+        unsafe { (*self.node.get()).writer }
+    }
+
+    /// Whether a reader must queue behind a writer.
+    fn held_back(&self) -> bool {
+        if self.writer() || LINKED.load(Ordering::Relaxed) == 0 {
+            return false;
+        }
+        Queue::of(self.key()).lock().list().has_writer(self.key())
+    }
+
+    /// # Safety
+    ///
+    /// `self` must be pinned.
+    unsafe fn poll<G>(
+        &mut self,
+        cx: &mut Context<'_>,
+        mut try_acquire: impl FnMut() -> Option<G>,
+    ) -> Poll<G> {
+        let spins = if self.polled {
+            1
+        } else {
+            SPINS_BEFORE_REGISTER
+        };
+        self.polled = true;
+        if !self.held_back() {
+            for _ in 0..spins {
+                if let Some(guard) = try_acquire() {
+                    self.finish();
+                    return Poll::Ready(guard);
+                }
+                spin_loop();
+            }
+        }
+        if !raw_atomics_enabled() {
+            // Only injected faults fail before then; try again later
+            // rather than queue with read-modify-writes.
+            cx.waker().wake_by_ref();
+            return Poll::Pending;
+        }
+        let node = self.node.get();
+        {
+            let mut guard = Queue::of(self.key()).lock();
+            // SAFETY: the queue's lock is held, and the caller's pin keeps
+            // the node in place until `Drop` unlinks it.
+            unsafe {
+                if !(*node).linked {
+                    guard.list().push_back(node);
+                }
+                (*node).woken = false;
+                match &mut (*node).waker {
+                    Some(waker) if waker.will_wake(cx.waker()) => {}
+                    waker => *waker = Some(cx.waker().clone()),
+                }
+            }
+            self.registered = true;
+        }
+        fence(Ordering::SeqCst);
+        if !self.held_back() {
+            if let Some(guard) = try_acquire() {
+                self.finish();
+                return Poll::Ready(guard);
+            }
+        }
+        Poll::Pending
+    }
+
+    /// Unlinks the node once the lock is taken.
+    fn finish(&mut self) {
+        if self.registered {
+            let node = self.node.get();
+            let mut guard = Queue::of(self.key()).lock();
+            // SAFETY: the queue's lock is held.
+            unsafe {
+                if (*node).linked {
+                    guard.list().unlink(node);
+                }
+                (*node).waker = None;
+            }
+            self.registered = false;
+        }
+    }
+}
+
+impl Drop for Wait {
+    fn drop(&mut self) {
+        if !self.registered {
+            return;
+        }
+        let node = self.node.get();
+        let (linked, pass_on) = {
+            let mut guard = Queue::of(self.key()).lock();
+            // SAFETY: the queue's lock is held.
+            unsafe {
+                let linked = (*node).linked;
+                if linked {
+                    guard.list().unlink(node);
+                }
+                (linked, (*node).woken || (*node).writer)
+            }
+        };
+        if linked && pass_on {
+            wake(self.key());
+        }
+    }
+}
+
+/// Future returned by [`RwSpinLock::read_async`].
+#[must_use = "futures do nothing unless polled"]
+pub struct RwSpinLockReadFuture<'a, T> {
+    lock: &'a RwSpinLock<T>,
+    wait: Wait,
+}
+
+/// Future returned by [`RwSpinLock::write_async`].
+#[must_use = "futures do nothing unless polled"]
+pub struct RwSpinLockWriteFuture<'a, T> {
+    lock: &'a RwSpinLock<T>,
+    wait: Wait,
+}
+
+impl<T> RwSpinLock<T> {
+    /// Takes a read lock without blocking the executor; see the
+    /// [module docs](crate::rwlock_async).
+    pub fn read_async(&self) -> RwSpinLockReadFuture<'_, T> {
+        RwSpinLockReadFuture {
+            lock: self,
+            wait: Wait::new(self.state_key(), false),
+        }
+    }
+
+    /// Takes the write lock without blocking the executor; see the
+    /// [module docs](crate::rwlock_async).
+    pub fn write_async(&self) -> RwSpinLockWriteFuture<'_, T> {
+        RwSpinLockWriteFuture {
+            lock: self,
+            wait: Wait::new(self.state_key(), true),
+        }
+    }
+}
+
+impl<'a, T> Future for RwSpinLockReadFuture<'a, T> {
+    type Output = RwSpinLockReadGuard<'a, T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // SAFETY: the node is never moved out of the pinned future.
+        let this = unsafe { self.get_unchecked_mut() };
+        let lock = this.lock;
+        // SAFETY: `this` is pinned.
+        unsafe { this.wait.poll(cx, || lock.try_read()) }
+    }
+}
+
+impl<'a, T> Future for RwSpinLockWriteFuture<'a, T> {
+    type Output = RwSpinLockWriteGuard<'a, T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // SAFETY: as for the read future.
+        let this = unsafe { self.get_unchecked_mut() };
+        let lock = this.lock;
+        // SAFETY: `this` is pinned.
+        unsafe { this.wait.poll(cx, || lock.try_write()) }
+    }
+}
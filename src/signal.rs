@@ -0,0 +1,112 @@
+//! Sharing locked state with Unix signal handlers.
+//!
+//! A handler that calls a blocking `lock()` deadlocks if it interrupted code
+//! holding the same lock, so handlers must stick to the async-signal-safe
+//! subset:
+//!
+//! - [`RawSpinLock::try_lock`], [`RwSpinLock::try_read`] and
+//!   [`RwSpinLock::try_write`];
+//! - dropping the guards they return;
+//! - [`raw_atomics_enabled`](crate::raw::raw_atomics_enabled).
+//!
+//! These paths never allocate, make system calls, or format panic messages;
+//! they consist of atomic operations plus any registered
+//! [`PreemptionControl`](crate::preempt::PreemptionControl) hook, which must
+//! then be signal-safe itself.
+//!
+//! Normal code sharing the lock with a handler uses
+//! [`RawSpinLock::lock_signal_safe`], which masks the handler's signal
+//! through the registered [`SignalMask`] hook before spinning, so the handler
+//! can never interrupt the holder on the same thread.
+//!
+//! [`RwSpinLock::try_read`]: crate::rwlock::RwSpinLock::try_read
+//! [`RwSpinLock::try_write`]: crate::rwlock::RwSpinLock::try_write
+
+use core::mem::ManuallyDrop;
+use core::ops::Deref;
+use core::ops::DerefMut;
+
+use crate::hook::HookCell;
+use crate::hook::SetHookError;
+use crate::mutex::RawSpinLock;
+use crate::mutex::RawSpinLockGuard;
+
+/// Hook that blocks the signals whose handlers share locks with normal code,
+/// typically via `pthread_sigmask`.
+pub trait SignalMask {
+    /// Blocks the relevant signals for the current thread and returns the
+    /// previous mask in an implementation-defined encoding.
+    fn block() -> usize;
+
+    /// Restores a mask returned by [`block`](Self::block).
+    fn restore(saved: usize);
+}
+
+#[derive(Clone, Copy)]
+struct Hooks {
+    block: fn() -> usize,
+    restore: fn(usize),
+}
+
+static SIGNAL_MASK: HookCell<Hooks> = HookCell::new();
+
+/// Registers the signal-mask hook. Call once at startup, before installing
+/// the signal handlers.
+pub fn set_signal_mask<S: SignalMask>() -> Result<(), SetHookError> {
+    SIGNAL_MASK.set(Hooks {
+        block: S::block,
+        restore: S::restore,
+    })
+}
+
+/// Guard returned by [`RawSpinLock::lock_signal_safe`].
+///
+/// Dropping it releases the lock first and then restores the signal mask.
+pub struct RawSpinLockSignalGuard<'a, T> {
+    guard: ManuallyDrop<RawSpinLockGuard<'a, T>>,
+    saved: usize,
+}
+
+impl<T> RawSpinLock<T> {
+    /// Blocks the handler's signal through the [`SignalMask`] hook, then
+    /// acquires the lock. Without a registered hook this is plain `lock()`.
+    ///
+    /// Not async-signal-safe itself; use it from normal code only.
+    pub fn lock_signal_safe(&self) -> RawSpinLockSignalGuard<'_, T> {
+        let saved = SIGNAL_MASK.get().map_or(0, |hooks| (hooks.block)());
+        RawSpinLockSignalGuard {
+            guard: ManuallyDrop::new(self.lock()),
+            saved,
+        }
+    }
+}
+
+impl<T> Drop for RawSpinLockSignalGuard<'_, T> {
+    fn drop(&mut self) {
+        // SAFETY: the inner guard is dropped exactly once, here.
+        unsafe { ManuallyDrop::drop(&mut self.guard) };
+        if let Some(hooks) = SIGNAL_MASK.get() {
+            (hooks.restore)(self.saved);
+        }
+    }
+}
+
+impl<T> Deref for RawSpinLockSignalGuard<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &Self::Target {
+        &self.guard
+    }
+}
+
+impl<T> DerefMut for RawSpinLockSignalGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.guard
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl<T: defmt::Format> defmt::Format for RawSpinLockSignalGuard<'_, T> {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        defmt::Format::format(&**self, f)
+    }
+}
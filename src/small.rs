@@ -0,0 +1,257 @@
+//! A lock for small `Copy` values that packs the value into the lock word.
+//!
+//! For a counter or a flag, a separate lock word is pure overhead:
+//! [`SmallSpinLock`] keeps the value in the low 32 bits of an `AtomicU64`
+//! and the lock state above it, so [`get_cloned`](SmallSpinLock::get_cloned),
+//! [`set`](SmallSpinLock::set) and [`update`](SmallSpinLock::update) are
+//! single atomic operations when nobody holds the lock.
+//! [`lock`](SmallSpinLock::lock) is still available for code written against
+//! [`Lock`]; its guard works on a copy that is written back on release.
+
+use core::marker::PhantomData;
+use core::ops::Deref;
+use core::ops::DerefMut;
+use core::panic::RefUnwindSafe;
+use core::panic::UnwindSafe;
+use core::ptr;
+
+use crate::atomic::AtomicU64;
+use crate::atomic::Ordering;
+use crate::mutex::Lock;
+use crate::preempt;
+use crate::raw::raw_atomics_enabled;
+use crate::relax;
+use crate::relax::Backoff;
+
+const LOCKED: u64 = 1 << 32;
+const VALUE_MASK: u64 = u32::MAX as u64;
+
+/// Types [`SmallSpinLock`] can pack into its lock word.
+///
+/// # Safety
+///
+/// The type must be at most 4 bytes and have no padding or otherwise
+/// uninitialized bytes, since its bytes are reinterpreted as an integer.
+pub unsafe trait Packable: Copy {}
+
+unsafe impl Packable for () {}
+unsafe impl Packable for bool {}
+unsafe impl Packable for char {}
+unsafe impl Packable for u8 {}
+unsafe impl Packable for u16 {}
+unsafe impl Packable for u32 {}
+unsafe impl Packable for i8 {}
+unsafe impl Packable for i16 {}
+unsafe impl Packable for i32 {}
+unsafe impl Packable for f32 {}
+unsafe impl Packable for [u8; 1] {}
+unsafe impl Packable for [u8; 2] {}
+unsafe impl Packable for [u8; 3] {}
+unsafe impl Packable for [u8; 4] {}
+
+const fn pack<T: Packable>(value: T) -> u64 {
+    let mut bits = 0u32;
+    // SAFETY: `Packable` guarantees `T` fits in (and is fully initialized
+    // over) the first `size_of::<T>()` bytes.
+    unsafe {
+        ptr::copy_nonoverlapping(
+            (&raw const value).cast::<u8>(),
+            (&raw mut bits).cast::<u8>(),
+            size_of::<T>(),
+        );
+    }
+    bits as u64
+}
+
+fn unpack<T: Packable>(word: u64) -> T {
+    let bits = (word & VALUE_MASK) as u32;
+    // SAFETY: the value bits were produced by `pack` from a valid `T`.
+    unsafe { ptr::read_unaligned((&raw const bits).cast::<T>()) }
+}
+
+/// A spinlock whose value lives in the lock word itself.
+///
+/// Like [`RawSpinLock`](crate::mutex::RawSpinLock) it uses plain loads and
+/// stores until raw atomics are enabled.
+pub struct SmallSpinLock<T: Packable> {
+    word: AtomicU64,
+    _value: PhantomData<T>,
+}
+
+pub struct SmallSpinLockGuard<'a, T: Packable> {
+    lock: &'a SmallSpinLock<T>,
+    value: T,
+    locked: bool,
+}
+
+unsafe impl<T: Packable + Send> Send for SmallSpinLock<T> {}
+unsafe impl<T: Packable + Send> Sync for SmallSpinLock<T> {}
+
+// Unwind safe like `std::sync::Mutex`, but without poisoning; see
+// `RawSpinLock`. The guard holds a copy of the value, which it writes back
+// even when dropped by unwinding.
+impl<T: Packable> UnwindSafe for SmallSpinLock<T> {}
+impl<T: Packable> RefUnwindSafe for SmallSpinLock<T> {}
+impl<T: Packable> UnwindSafe for SmallSpinLockGuard<'_, T> {}
+impl<T: Packable> RefUnwindSafe for SmallSpinLockGuard<'_, T> {}
+
+const _: () = {
+    crate::mutex::assert_unwind_safe::<SmallSpinLock<u32>>();
+    crate::mutex::assert_unwind_safe::<SmallSpinLockGuard<'_, u32>>();
+};
+
+impl<T: Packable> SmallSpinLock<T> {
+    pub const fn new(value: T) -> Self {
+        Self {
+            word: AtomicU64::new(pack(value)),
+            _value: PhantomData,
+        }
+    }
+
+    /// Returns the value as of the last release.
+    ///
+    /// Does not wait for a current holder; changes it is making through a
+    /// guard are not visible until the guard is dropped.
+    pub fn get_cloned(&self) -> T {
+        unpack(self.word.load(Ordering::Acquire))
+    }
+
+    /// Replaces the value, waiting first if the lock is held.
+    pub fn set(&self, value: T) {
+        self.update(|_| value);
+    }
+
+    /// Replaces the value with `f(old)` and returns the old value, waiting
+    /// first if the lock is held. `f` may run more than once.
+    pub fn update(&self, mut f: impl FnMut(T) -> T) -> T {
+        if !raw_atomics_enabled() {
+            let old = unpack(self.word.load(Ordering::Relaxed));
+            self.word.store(pack(f(old)), Ordering::Relaxed);
+            return old;
+        }
+        let mut backoff = Backoff::new();
+        let mut current = self.word.load(Ordering::Relaxed);
+        loop {
+            if current & LOCKED != 0 {
+                backoff.wait();
+                current = self.word.load(Ordering::Relaxed);
+                continue;
+            }
+            let old = unpack(current);
+            match self.word.compare_exchange_weak(
+                current,
+                pack(f(old)),
+                Ordering::AcqRel,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return old,
+                Err(actual) => current = actual,
+            }
+        }
+    }
+
+    pub fn lock(&self) -> SmallSpinLockGuard<'_, T> {
+        if !raw_atomics_enabled() {
+            return self.guard(self.word.load(Ordering::Relaxed), false);
+        }
+        let mut backoff = Backoff::new();
+        let mut current = self.word.load(Ordering::Relaxed);
+        loop {
+            if current & LOCKED != 0 {
+                backoff.wait();
+                current = self.word.load(Ordering::Relaxed);
+                continue;
+            }
+            match self.word.compare_exchange_weak(
+                current,
+                current | LOCKED,
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => {
+                    preempt::disable();
+                    return self.guard(current, true);
+                }
+                Err(actual) => current = actual,
+            }
+        }
+    }
+
+    /// Attempts to acquire the lock without spinning.
+    ///
+    /// Returns `None` if the lock is currently held. Before raw atomics are
+    /// enabled this always succeeds, mirroring [`lock`](Self::lock).
+    pub fn try_lock(&self) -> Option<SmallSpinLockGuard<'_, T>> {
+        let current = self.word.load(Ordering::Relaxed);
+        if !raw_atomics_enabled() {
+            return Some(self.guard(current, false));
+        }
+        if current & LOCKED != 0 {
+            return None;
+        }
+        self.word
+            .compare_exchange(
+                current,
+                current | LOCKED,
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            )
+            .ok()?;
+        preempt::disable();
+        Some(self.guard(current, true))
+    }
+
+    fn guard(&self, word: u64, locked: bool) -> SmallSpinLockGuard<'_, T> {
+        SmallSpinLockGuard {
+            lock: self,
+            value: unpack(word),
+            locked,
+        }
+    }
+
+    pub fn into_inner(self) -> T {
+        unpack(self.word.into_inner())
+    }
+}
+
+impl<T: Packable> Drop for SmallSpinLockGuard<'_, T> {
+    fn drop(&mut self) {
+        // Writing the value back also clears `LOCKED`.
+        let word = pack(self.value);
+        if self.locked {
+            self.lock.word.store(word, Ordering::Release);
+            relax::notify();
+            preempt::enable();
+        } else {
+            self.lock.word.store(word, Ordering::Relaxed);
+        }
+    }
+}
+
+impl<T: Packable> Deref for SmallSpinLockGuard<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &Self::Target {
+        &self.value
+    }
+}
+
+impl<T: Packable> DerefMut for SmallSpinLockGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.value
+    }
+}
+
+impl<T: Packable> Lock<T> for SmallSpinLock<T> {
+    type Guard<'a>
+        = SmallSpinLockGuard<'a, T>
+    where
+        T: 'a;
+
+    fn lock(&self) -> Self::Guard<'_> {
+        SmallSpinLock::lock(self)
+    }
+
+    fn try_lock(&self) -> Option<Self::Guard<'_>> {
+        SmallSpinLock::try_lock(self)
+    }
+}
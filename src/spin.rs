@@ -0,0 +1,201 @@
+//! The `spin` crate's names over this crate's locks.
+//!
+//! Code written against `spin` switches by replacing `spin::` with
+//! `mutex::spin::` in its imports. [`Mutex`] and [`RwLock`] are aliases of
+//! [`RawSpinLock`] and [`RwSpinLock`], which already share most of `spin`'s
+//! method names; the rest (`is_locked`, `reader_count`, `writer_count` and
+//! the guards' `leak`) exist while this feature is enabled. [`Once`] is a
+//! separate type with `spin::Once`'s methods.
+//!
+//! The semantics differ in a few places:
+//!
+//! - Before [`enable_raw_atomics`] every lock here, `Once` included, is
+//!   permissive: guards exclude nobody, so only a single core may use
+//!   them, and `RwLock`'s counts read as unheld.
+//! - The relax strategy is not a type parameter; `Mutex<T, R>` becomes
+//!   `Mutex<T>`, and waiters pause as described in [`relax`](crate::relax).
+//! - `RwLock` has no upgradeable reads and `Mutex` no ticket or fair
+//!   variants; `spin::Lazy` has no counterpart.
+//! - A `Once` whose initializer panicked is initialized by the next call,
+//!   rather than panicking on every later use.
+//!
+//! [`enable_raw_atomics`]: crate::raw::enable_raw_atomics
+
+use core::cell::UnsafeCell;
+#[cfg(not(feature = "tiny"))]
+use core::fmt;
+use core::mem::MaybeUninit;
+
+use crate::atomic::AtomicBool;
+use crate::atomic::Ordering;
+use crate::atomic::spin_loop;
+use crate::mutex::RawSpinLock;
+use crate::mutex::RawSpinLockGuard;
+use crate::rwlock::RwSpinLock;
+use crate::rwlock::RwSpinLockReadGuard;
+use crate::rwlock::RwSpinLockWriteGuard;
+
+pub type Mutex<T> = RawSpinLock<T>;
+pub type MutexGuard<'a, T> = RawSpinLockGuard<'a, T>;
+pub type RwLock<T> = RwSpinLock<T>;
+pub type RwLockReadGuard<'a, T> = RwSpinLockReadGuard<'a, T>;
+pub type RwLockWriteGuard<'a, T> = RwSpinLockWriteGuard<'a, T>;
+
+/// A value computed once, on first use, like `spin::Once`.
+///
+/// Initializers are serialised by a [`RawSpinLock`], so concurrent calls
+/// run exactly one of them once raw atomics are enabled.
+pub struct Once<T = ()> {
+    /// Set once `data` is written, never cleared. A plain store, so it can
+    /// be set before raw atomics are enabled.
+    completed: AtomicBool,
+    lock: RawSpinLock<()>,
+    data: UnsafeCell<MaybeUninit<T>>,
+}
+
+unsafe impl<T: Send> Send for Once<T> {}
+unsafe impl<T: Send + Sync> Sync for Once<T> {}
+
+impl<T> Once<T> {
+    #[allow(clippy::declare_interior_mutable_const)]
+    pub const INIT: Self = Self::new();
+
+    pub const fn new() -> Self {
+        Self {
+            completed: AtomicBool::new(false),
+            lock: RawSpinLock::new(()),
+            data: UnsafeCell::new(MaybeUninit::uninit()),
+        }
+    }
+
+    /// A `Once` that is already initialized with `data`.
+    pub const fn initialized(data: T) -> Self {
+        Self {
+            completed: AtomicBool::new(true),
+            lock: RawSpinLock::new(()),
+            data: UnsafeCell::new(MaybeUninit::new(data)),
+        }
+    }
+
+    /// Returns the value, running `f` to compute it if nobody has yet.
+    #[track_caller]
+    pub fn call_once<F: FnOnce() -> T>(&self, f: F) -> &T {
+        match self.try_call_once(|| Ok::<T, core::convert::Infallible>(f())) {
+            Ok(value) => value,
+            Err(never) => match never {},
+        }
+    }
+
+    /// Like [`call_once`](Self::call_once), but leaves the `Once`
+    /// uninitialized if `f` fails.
+    #[track_caller]
+    pub fn try_call_once<F: FnOnce() -> Result<T, E>, E>(&self, f: F) -> Result<&T, E> {
+        if let Some(value) = self.get() {
+            return Ok(value);
+        }
+        let _guard = self.lock.lock();
+        if let Some(value) = self.get() {
+            return Ok(value);
+        }
+        let value = f()?;
+        // SAFETY: not completed, so nobody reads `data`, and the lock keeps
+        // other initializers out.
+        unsafe { (*self.data.get()).write(value) };
+        self.completed.store(true, Ordering::Release);
+        // SAFETY: just initialized.
+        Ok(unsafe { self.get_unchecked() })
+    }
+
+    /// The value, or `None` until it is initialized.
+    pub fn get(&self) -> Option<&T> {
+        if self.is_completed() {
+            // SAFETY: completed, and the value is never touched again
+            // except through `&mut self`.
+            Some(unsafe { self.get_unchecked() })
+        } else {
+            None
+        }
+    }
+
+    /// The same as [`get`](Self::get), under `spin`'s other name.
+    pub fn poll(&self) -> Option<&T> {
+        self.get()
+    }
+
+    /// Spins until another context initializes the value.
+    ///
+    /// Before raw atomics are enabled nobody else can, so this only
+    /// returns if the value is already there.
+    pub fn wait(&self) -> &T {
+        loop {
+            if let Some(value) = self.get() {
+                return value;
+            }
+            spin_loop();
+        }
+    }
+
+    pub fn get_mut(&mut self) -> Option<&mut T> {
+        if self.completed.load(Ordering::Relaxed) {
+            // SAFETY: completed, and borrowed exclusively.
+            Some(unsafe { self.data.get_mut().assume_init_mut() })
+        } else {
+            None
+        }
+    }
+
+    pub fn is_completed(&self) -> bool {
+        self.completed.load(Ordering::Acquire)
+    }
+
+    /// Returns the value, or `None` if it was never initialized.
+    pub fn try_into_inner(mut self) -> Option<T> {
+        if !self.completed.load(Ordering::Relaxed) {
+            return None;
+        }
+        self.completed.store(false, Ordering::Relaxed);
+        // SAFETY: was completed, and is now marked uninitialized so the
+        // drop does not drop the value a second time.
+        Some(unsafe { self.data.get_mut().assume_init_read() })
+    }
+
+    /// # Safety
+    ///
+    /// The value must be initialized.
+    unsafe fn get_unchecked(&self) -> &T {
+        // SAFETY: initialized, guaranteed by the caller.
+        unsafe { (*self.data.get()).assume_init_ref() }
+    }
+}
+
+impl<T> Default for Once<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> From<T> for Once<T> {
+    fn from(data: T) -> Self {
+        Self::initialized(data)
+    }
+}
+
+impl<T> Drop for Once<T> {
+    fn drop(&mut self) {
+        if self.completed.load(Ordering::Relaxed) {
+            // SAFETY: initialized, and dropped only here.
+            unsafe { self.data.get_mut().assume_init_drop() };
+        }
+    }
+}
+
+/// Formats the value, or `<uninit>`.
+#[cfg(not(feature = "tiny"))]
+impl<T: fmt::Debug> fmt::Debug for Once<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.get() {
+            Some(value) => f.debug_tuple("Once").field(value).finish(),
+            None => f.write_str("Once(<uninit>)"),
+        }
+    }
+}
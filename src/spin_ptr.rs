@@ -0,0 +1,301 @@
+//! An owning pointer whose lock lives in the pointer's low bit.
+//!
+//! A per-object lock over a boxed node costs a whole [`RawSpinLock`] next
+//! to every pointer. [`SpinPtr`] packs both into one word instead: it holds
+//! an `Option<Box<T>>` and uses bit 0 of the address, which is always clear
+//! for a `T` aligned to at least 2, as the lock flag. A null pointer is the
+//! empty state, so the word is 0 for an unlocked `SpinPtr` holding nothing
+//! and 1 for a locked one.
+//!
+//! Like [`SmallSpinLock`], the guard works on a copy of the contents, here
+//! the `Option<Box<T>>` itself, and writes it back on release, so the box
+//! can be replaced or taken out while the lock is held. Before raw atomics
+//! are enabled the flag is set and cleared with plain stores, and locking a
+//! `SpinPtr` whose guard is still alive panics, since two guards would both
+//! own the box.
+//!
+//! [`RawSpinLock`]: crate::mutex::RawSpinLock
+//! [`SmallSpinLock`]: crate::small::SmallSpinLock
+
+use alloc::boxed::Box;
+#[cfg(not(feature = "tiny"))]
+use core::fmt;
+use core::marker::PhantomData;
+use core::mem;
+use core::ops::Deref;
+use core::ops::DerefMut;
+use core::ptr;
+
+use crate::atomic::AtomicPtr;
+use crate::atomic::Ordering;
+use crate::mutex::Lock;
+use crate::preempt;
+use crate::raw::raw_atomics_enabled;
+use crate::relax;
+use crate::relax::Backoff;
+
+const LOCKED: usize = 1;
+
+/// A lockable `Option<Box<T>>` in a single pointer-sized word.
+///
+/// `T` must be aligned to at least 2 bytes; using a `SpinPtr` of anything
+/// else fails to compile:
+///
+/// ```compile_fail
+/// let _ = mutex::spin_ptr::SpinPtr::new(Box::new(0u8));
+/// ```
+pub struct SpinPtr<T> {
+    word: AtomicPtr<T>,
+    _owns: PhantomData<Box<T>>,
+}
+
+pub struct SpinPtrGuard<'a, T> {
+    ptr: &'a SpinPtr<T>,
+    value: Option<Box<T>>,
+    /// Took the flag atomically, rather than with a plain store before raw
+    /// atomics were enabled.
+    locked: bool,
+}
+
+// SAFETY: the box is only reached through a guard, which excludes others
+// once raw atomics are enabled, like a `RawSpinLock<Option<Box<T>>>`.
+unsafe impl<T: Send> Send for SpinPtr<T> {}
+unsafe impl<T: Send> Sync for SpinPtr<T> {}
+
+#[cfg(not(any(loom, shuttle)))]
+const _: () = assert!(size_of::<SpinPtr<u64>>() == size_of::<usize>());
+
+fn into_word<T>(value: Option<Box<T>>) -> *mut T {
+    match value {
+        Some(value) => Box::into_raw(value),
+        None => ptr::null_mut(),
+    }
+}
+
+/// # Safety
+///
+/// `word`, without the flag, must be null or come from `Box::into_raw`, and
+/// no other box may own it.
+unsafe fn from_word<T>(word: *mut T) -> Option<Box<T>> {
+    let ptr = word.map_addr(|addr| addr & !LOCKED);
+    // SAFETY: guaranteed by the caller.
+    (!ptr.is_null()).then(|| unsafe { Box::from_raw(ptr) })
+}
+
+impl<T> SpinPtr<T> {
+    const ALIGNED: () = assert!(
+        align_of::<T>() >= 2,
+        "SpinPtr needs the low bit of the pointer for its lock"
+    );
+
+    /// Creates a `SpinPtr` holding nothing, for a `static` that gets its
+    /// box later.
+    pub const fn empty() -> Self {
+        Self::from_word(ptr::null_mut())
+    }
+
+    pub fn new(value: Box<T>) -> Self {
+        Self::from_option(Some(value))
+    }
+
+    pub fn from_option(value: Option<Box<T>>) -> Self {
+        Self::from_word(into_word(value))
+    }
+
+    const fn from_word(word: *mut T) -> Self {
+        let () = Self::ALIGNED;
+        Self {
+            word: AtomicPtr::new(word),
+            _owns: PhantomData,
+        }
+    }
+
+    #[track_caller]
+    pub fn lock(&self) -> SpinPtrGuard<'_, T> {
+        if !raw_atomics_enabled() {
+            return self.lock_permissive();
+        }
+        let mut backoff = Backoff::new();
+        let mut current = self.word.load(Ordering::Relaxed);
+        loop {
+            if current.addr() & LOCKED != 0 {
+                backoff.wait();
+                current = self.word.load(Ordering::Relaxed);
+                continue;
+            }
+            match self.word.compare_exchange_weak(
+                current,
+                current.map_addr(|addr| addr | LOCKED),
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => {
+                    preempt::disable();
+                    return self.guard(current, true);
+                }
+                Err(actual) => current = actual,
+            }
+        }
+    }
+
+    /// Attempts to acquire the lock without spinning.
+    ///
+    /// Returns `None` if the lock is currently held, also before raw
+    /// atomics are enabled.
+    pub fn try_lock(&self) -> Option<SpinPtrGuard<'_, T>> {
+        let current = self.word.load(Ordering::Relaxed);
+        if current.addr() & LOCKED != 0 {
+            return None;
+        }
+        if !raw_atomics_enabled() {
+            return Some(self.lock_permissive());
+        }
+        self.word
+            .compare_exchange(
+                current,
+                current.map_addr(|addr| addr | LOCKED),
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            )
+            .ok()?;
+        preempt::disable();
+        Some(self.guard(current, true))
+    }
+
+    #[track_caller]
+    fn lock_permissive(&self) -> SpinPtrGuard<'_, T> {
+        let current = self.word.load(Ordering::Relaxed);
+        if current.addr() & LOCKED != 0 {
+            locked_twice();
+        }
+        self.word
+            .store(current.map_addr(|addr| addr | LOCKED), Ordering::Relaxed);
+        self.guard(current, false)
+    }
+
+    fn guard(&self, word: *mut T, locked: bool) -> SpinPtrGuard<'_, T> {
+        SpinPtrGuard {
+            ptr: self,
+            // SAFETY: setting the flag made this guard the only owner.
+            value: unsafe { from_word(word) },
+            locked,
+        }
+    }
+
+    /// Returns the contents; `&mut self` rules out guards. After a guard
+    /// was forgotten, the `SpinPtr` counts as empty.
+    pub fn get_mut(&mut self) -> Option<&mut T> {
+        let ptr = self.word.load(Ordering::Relaxed);
+        if ptr.addr() & LOCKED != 0 {
+            return None;
+        }
+        // SAFETY: the flag is clear, so the word holds the box `self` owns.
+        unsafe { ptr.as_mut() }
+    }
+
+    pub fn into_inner(self) -> Option<Box<T>> {
+        let mut this = mem::ManuallyDrop::new(self);
+        this.take_owned()
+    }
+
+    /// Moves the box out, leaving the `SpinPtr` empty.
+    fn take_owned(&mut self) -> Option<Box<T>> {
+        let word = self.word.load(Ordering::Relaxed);
+        self.word.store(ptr::null_mut(), Ordering::Relaxed);
+        if word.addr() & LOCKED != 0 {
+            // A guard was forgotten and may have handed the box on; leak
+            // whatever the word points at rather than freeing it twice.
+            return None;
+        }
+        // SAFETY: the flag is clear, so the word is the box `self` owns.
+        unsafe { from_word(word) }
+    }
+}
+
+impl<T> SpinPtrGuard<'_, T> {
+    /// Replaces the box, or empties the `SpinPtr` with `None`, and returns
+    /// the old contents. The new contents are published on release.
+    pub fn replace_locked(&mut self, value: Option<Box<T>>) -> Option<Box<T>> {
+        mem::replace(&mut self.value, value)
+    }
+}
+
+impl<T> Drop for SpinPtrGuard<'_, T> {
+    fn drop(&mut self) {
+        // Writing the pointer back also clears the flag. Still a plain
+        // store, and a release, should raw atomics have been enabled since.
+        let word = into_word(self.value.take());
+        self.ptr.word.store(word, Ordering::Release);
+        if self.locked {
+            relax::notify();
+            preempt::enable();
+        }
+    }
+}
+
+impl<T> Deref for SpinPtrGuard<'_, T> {
+    type Target = Option<Box<T>>;
+    fn deref(&self) -> &Self::Target {
+        &self.value
+    }
+}
+
+impl<T> DerefMut for SpinPtrGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.value
+    }
+}
+
+impl<T> Drop for SpinPtr<T> {
+    fn drop(&mut self) {
+        drop(self.take_owned());
+    }
+}
+
+impl<T> Default for SpinPtr<T> {
+    fn default() -> Self {
+        Self::empty()
+    }
+}
+
+impl<T> From<Box<T>> for SpinPtr<T> {
+    fn from(value: Box<T>) -> Self {
+        Self::new(value)
+    }
+}
+
+/// Formats the contents without blocking, or `<locked>` if the lock is
+/// held.
+#[cfg(not(feature = "tiny"))]
+impl<T: fmt::Debug> fmt::Debug for SpinPtr<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut d = f.debug_tuple("SpinPtr");
+        match self.try_lock() {
+            Some(guard) => d.field(&*guard),
+            None => d.field(&format_args!("<locked>")),
+        };
+        d.finish()
+    }
+}
+
+impl<T> Lock<Option<Box<T>>> for SpinPtr<T> {
+    type Guard<'a>
+        = SpinPtrGuard<'a, T>
+    where
+        T: 'a;
+
+    #[track_caller]
+    fn lock(&self) -> Self::Guard<'_> {
+        SpinPtr::lock(self)
+    }
+
+    fn try_lock(&self) -> Option<Self::Guard<'_>> {
+        SpinPtr::try_lock(self)
+    }
+}
+
+#[cold]
+#[track_caller]
+fn locked_twice() -> ! {
+    panic!("SpinPtr locked again while a guard is alive");
+}
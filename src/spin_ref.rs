@@ -0,0 +1,123 @@
+//! Exclusive references passed where a lock is expected.
+//!
+//! A [`RawSpinLock`] keeps its lock word and debugging fields in front of
+//! the data, so a `&mut T` cannot be reinterpreted as a
+//! `&mut RawSpinLock<T>` the way `Cell::from_mut` does for a `Cell`. Code
+//! written against the [`Lock`] trait can be handed a [`SpinRef`] instead:
+//! it borrows the data in place, so nothing is copied, and its guards only
+//! flip a borrow flag, so nothing is locked.
+//!
+//! [`RawSpinLock`]: crate::mutex::RawSpinLock
+
+use core::cell::Cell;
+#[cfg(not(feature = "tiny"))]
+use core::fmt;
+use core::marker::PhantomData;
+use core::ops::Deref;
+use core::ops::DerefMut;
+use core::ptr::NonNull;
+
+use crate::mutex::Lock;
+
+/// A [`Lock`] over data the caller already borrows exclusively.
+///
+/// It is not `Sync`, so its guards can only be taken on the thread that
+/// made it, where the `&mut T` it was made from already rules out any other
+/// access; the borrow flag only has to catch a second guard taken while the
+/// first is alive, which would alias. [`lock`](Lock::lock) panics in that
+/// case and [`try_lock`](Lock::try_lock) fails.
+pub struct SpinRef<'a, T: ?Sized> {
+    data: NonNull<T>,
+    borrowed: Cell<bool>,
+    _data: PhantomData<&'a mut T>,
+}
+
+// SAFETY: a `SpinRef` is a `&mut T` plus a flag nobody else can see, so it
+// can move to another thread whenever the reference could.
+unsafe impl<T: ?Sized + Send> Send for SpinRef<'_, T> {}
+
+impl<'a, T: ?Sized> SpinRef<'a, T> {
+    pub fn from_mut(data: &'a mut T) -> Self {
+        Self {
+            data: NonNull::from(data),
+            borrowed: Cell::new(false),
+            _data: PhantomData,
+        }
+    }
+
+    /// Gives the exclusive reference back.
+    pub fn into_mut(self) -> &'a mut T {
+        // SAFETY: consuming `self` ends every guard's borrow of it, and the
+        // pointer came from a `&'a mut T`.
+        unsafe { &mut *self.data.as_ptr() }
+    }
+}
+
+impl<'a, T: ?Sized> From<&'a mut T> for SpinRef<'a, T> {
+    fn from(data: &'a mut T) -> Self {
+        Self::from_mut(data)
+    }
+}
+
+#[cfg(not(feature = "tiny"))]
+impl<T: ?Sized> fmt::Debug for SpinRef<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SpinRef")
+            .field("borrowed", &self.borrowed.get())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Guard returned by [`SpinRef`]'s [`Lock`] methods; clears the borrow flag
+/// on drop.
+pub struct SpinRefGuard<'b, T: ?Sized> {
+    data: NonNull<T>,
+    borrowed: &'b Cell<bool>,
+}
+
+impl<T: ?Sized> Deref for SpinRefGuard<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &Self::Target {
+        // SAFETY: the borrow flag makes this the only guard, and the
+        // `SpinRef` it borrows holds the only other path to the data.
+        unsafe { self.data.as_ref() }
+    }
+}
+
+impl<T: ?Sized> DerefMut for SpinRefGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        // SAFETY: as in `deref`.
+        unsafe { self.data.as_mut() }
+    }
+}
+
+impl<T: ?Sized> Drop for SpinRefGuard<'_, T> {
+    fn drop(&mut self) {
+        self.borrowed.set(false);
+    }
+}
+
+impl<T: ?Sized> Lock<T> for SpinRef<'_, T> {
+    type Guard<'b>
+        = SpinRefGuard<'b, T>
+    where
+        Self: 'b;
+
+    #[track_caller]
+    fn lock(&self) -> Self::Guard<'_> {
+        match self.try_lock() {
+            Some(guard) => guard,
+            None => panic!("SpinRef locked again while a guard is alive"),
+        }
+    }
+
+    fn try_lock(&self) -> Option<Self::Guard<'_>> {
+        if self.borrowed.replace(true) {
+            return None;
+        }
+        Some(SpinRefGuard {
+            data: self.data,
+            borrowed: &self.borrowed,
+        })
+    }
+}
@@ -0,0 +1,128 @@
+//! Decoding raw lock words, such as ones read out of a memory dump.
+//!
+//! A `RawSpinLock` keeps its state in a byte holding one of
+//! [`UNLOCKED`], [`LOCKED`] or [`PARKED`]; an `RwSpinLock` in a word whose
+//! top bit is [`WRITE_FLAG`] and whose other bits count readers.
+//! [`MutexState::decode`] and [`RwState::decode`] turn such values into
+//! something readable, and `RawSpinLock::state` and `RwSpinLock::state`
+//! decode a live lock. The encodings are checked against the decoders at
+//! compile time below, and `examples/decode_state.rs` decodes a value given
+//! on the command line.
+//!
+//! A value read out of a dump is only as consistent as the dump: a word
+//! copied while cores were still running may be from the middle of an
+//! acquisition.
+
+#[cfg(not(feature = "tiny"))]
+use core::fmt;
+
+use crate::raw::LOCKED;
+use crate::raw::MAX_READERS;
+use crate::raw::PARKED;
+use crate::raw::UNLOCKED;
+use crate::raw::WRITE_FLAG;
+
+/// The state a `RawSpinLock` byte encodes.
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(not(feature = "tiny"), derive(Debug))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum MutexState {
+    Unlocked,
+    Locked,
+    /// Held, and waiters may be blocked in the registered `Parker`.
+    Parked,
+    /// Not a state the lock ever stores, which points at a corrupted word
+    /// or at something other than a lock.
+    Invalid(u8),
+}
+
+impl MutexState {
+    pub const fn decode(raw: u8) -> Self {
+        match raw {
+            UNLOCKED => Self::Unlocked,
+            LOCKED => Self::Locked,
+            PARKED => Self::Parked,
+            raw => Self::Invalid(raw),
+        }
+    }
+
+    pub const fn is_locked(self) -> bool {
+        matches!(self, Self::Locked | Self::Parked)
+    }
+}
+
+#[cfg(not(feature = "tiny"))]
+impl fmt::Display for MutexState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unlocked => f.write_str("unlocked"),
+            Self::Locked => f.write_str("locked"),
+            Self::Parked => f.write_str("locked, waiters parked"),
+            Self::Invalid(raw) => write!(f, "invalid ({raw:#04x})"),
+        }
+    }
+}
+
+/// The state an `RwSpinLock` word encodes.
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(not(feature = "tiny"), derive(Debug))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct RwState {
+    /// [`WRITE_FLAG`] is set: a writer holds the lock, or has claimed it
+    /// and waits for the readers to leave.
+    pub writer: bool,
+    /// The writer is still waiting for `readers` to leave.
+    pub pending: bool,
+    /// Readers counted in the word. While a writer is in, or with more
+    /// than [`MAX_READERS`], these include readers that have just
+    /// incremented the count and are about to back off again.
+    pub readers: usize,
+}
+
+impl RwState {
+    pub const fn decode(raw: usize) -> Self {
+        let writer = raw & WRITE_FLAG != 0;
+        let readers = raw & !WRITE_FLAG;
+        Self {
+            writer,
+            pending: writer && readers != 0,
+            readers,
+        }
+    }
+
+    /// Whether a writer holds the lock with no reader left.
+    pub const fn is_write_locked(self) -> bool {
+        self.writer && !self.pending
+    }
+}
+
+#[cfg(not(feature = "tiny"))]
+impl fmt::Display for RwState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.writer, self.readers) {
+            (false, 0) => f.write_str("unlocked"),
+            (false, readers) => write!(f, "{readers} readers"),
+            (true, 0) => f.write_str("write-locked"),
+            (true, readers) => write!(f, "writer waiting for {readers} readers"),
+        }
+    }
+}
+
+// The decoders must agree with the encodings the locks store.
+const _: () = {
+    assert!(matches!(MutexState::decode(UNLOCKED), MutexState::Unlocked));
+    assert!(matches!(MutexState::decode(LOCKED), MutexState::Locked));
+    assert!(matches!(MutexState::decode(PARKED), MutexState::Parked));
+    assert!(!MutexState::decode(UNLOCKED).is_locked());
+    assert!(MutexState::decode(LOCKED).is_locked() && MutexState::decode(PARKED).is_locked());
+
+    let idle = RwState::decode(0);
+    assert!(!idle.writer && !idle.pending && idle.readers == 0);
+    let read = RwState::decode(3);
+    assert!(!read.writer && read.readers == 3);
+    assert!(RwState::decode(WRITE_FLAG).is_write_locked());
+    let draining = RwState::decode(WRITE_FLAG | 2);
+    assert!(draining.writer && draining.pending && draining.readers == 2);
+    assert!(RwState::decode(MAX_READERS).readers == MAX_READERS);
+    assert!(!RwState::decode(MAX_READERS).writer);
+};
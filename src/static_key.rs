@@ -0,0 +1,148 @@
+//! Gate checks that bring-up code can patch, like Linux static keys.
+//!
+//! Every lock operation asks [`raw_atomics_enabled`] which path to take.
+//! With the `static-key` feature, on x86_64 ELF targets, each inlined copy
+//! of that check starts with a [`GateSite`]: a 5-byte `mov eax, 2` whose
+//! address is recorded in the `mutex_gate_sites` link section. The
+//! immediate 2 sends the check on to the load of the gate flag, as without
+//! the feature. A kernel that can rewrite its text registers a
+//! [`CodePatcher`] with [`set_code_patcher`], and [`enable_raw_atomics`]
+//! hands it every site in the image once the flag is set. A site rewritten
+//! to [`GateSite::ENABLED_CODE`], `mov eax, 1`, answers without touching
+//! memory from then on.
+//!
+//! The fallback is the flag load. Without a patcher, or for a site the
+//! patcher declines, checks keep loading the flag, which is already set by
+//! the time sites are offered. Nothing is lost if a site is missed either.
+//! On other targets, and under Miri, loom and shuttle, there are no sites
+//! and [`sites`] is empty.
+//!
+//! Patching runs where [`enable_raw_atomics`] runs, single-core bring-up.
+//! The patcher owns everything that makes the write safe: making the text
+//! writable, and serialising the instruction stream afterwards.
+//!
+//! [`raw_atomics_enabled`]: crate::raw::raw_atomics_enabled
+//! [`enable_raw_atomics`]: crate::raw::enable_raw_atomics
+
+use crate::hook::HookCell;
+use crate::hook::SetHookError;
+
+/// One patchable gate check: the address of its `mov eax, imm32`.
+#[repr(transparent)]
+pub struct GateSite {
+    code: *const u8,
+}
+
+// SAFETY: a site is only an address, which the table never changes.
+unsafe impl Sync for GateSite {}
+
+impl GateSite {
+    /// What a patcher writes over the site to make the check answer
+    /// `true`: `mov eax, 1`.
+    pub const ENABLED_CODE: [u8; 5] = [0xb8, 1, 0, 0, 0];
+    /// What the site holds before patching: `mov eax, 2`, for "load the
+    /// flag".
+    pub const UNPATCHED_CODE: [u8; 5] = [0xb8, 2, 0, 0, 0];
+
+    /// The address of the instruction to rewrite.
+    pub fn address(&self) -> usize {
+        self.code.addr()
+    }
+}
+
+/// Kernel hook rewriting a [`GateSite`] in place with
+/// [`GateSite::ENABLED_CODE`].
+pub trait CodePatcher {
+    /// Patches `site`, returning whether it did. A site that is left alone
+    /// keeps loading the gate flag.
+    fn patch_site(site: &GateSite) -> bool;
+}
+
+static PATCHER: HookCell<fn(&GateSite) -> bool> = HookCell::new();
+
+/// Registers the patcher. Call once during bring-up, before
+/// [`enable_raw_atomics`](crate::raw::enable_raw_atomics).
+pub fn set_code_patcher<P: CodePatcher>() -> Result<(), SetHookError> {
+    PATCHER.set(P::patch_site)
+}
+
+/// Offers every site to the registered patcher, if any.
+pub(crate) fn patch_all() {
+    if let Some(patch_site) = PATCHER.get() {
+        for site in sites() {
+            patch_site(site);
+        }
+    }
+}
+
+#[cfg(all(
+    target_arch = "x86_64",
+    any(target_os = "linux", target_os = "none"),
+    not(any(miri, loom, shuttle))
+))]
+mod sites {
+    use super::GateSite;
+
+    unsafe extern "C" {
+        static __start_mutex_gate_sites: GateSite;
+        static __stop_mutex_gate_sites: GateSite;
+    }
+
+    /// Whether this copy of the gate check has been patched to enabled.
+    #[inline(always)]
+    pub(crate) fn patched() -> bool {
+        let value: u32;
+        // SAFETY: the instruction only writes `eax`, and the section only
+        // gains the address of it.
+        unsafe {
+            core::arch::asm!(
+                "2:",
+                ".byte 0xb8",
+                ".long 2",
+                ".pushsection mutex_gate_sites, \"awR\"",
+                ".balign 8",
+                ".quad 2b",
+                ".popsection",
+                out("eax") value,
+                options(nomem, nostack, preserves_flags),
+            );
+        }
+        value == 1
+    }
+
+    /// Every gate site in the image, patched or not.
+    pub fn sites() -> &'static [GateSite] {
+        // A site of its own, so the section exists whenever this is linked.
+        let _ = patched();
+        // SAFETY: the linker places `__start_` and `__stop_` around the
+        // section, which only holds `GateSite` addresses.
+        unsafe {
+            let start = &raw const __start_mutex_gate_sites;
+            let stop = &raw const __stop_mutex_gate_sites;
+            let len = stop.offset_from(start) as usize;
+            core::slice::from_raw_parts(start, len)
+        }
+    }
+}
+
+#[cfg(not(all(
+    target_arch = "x86_64",
+    any(target_os = "linux", target_os = "none"),
+    not(any(miri, loom, shuttle))
+)))]
+mod sites {
+    use super::GateSite;
+
+    #[inline(always)]
+    pub(crate) fn patched() -> bool {
+        false
+    }
+
+    /// Every gate site in the image, patched or not.
+    pub fn sites() -> &'static [GateSite] {
+        &[]
+    }
+}
+
+pub(crate) use sites::patched;
+pub use sites::sites;
@@ -0,0 +1,164 @@
+//! Per-lock acquisition counters, kept with the `stats` feature.
+//!
+//! [`LockStats`] is the snapshot [`RawSpinLock::stats`] returns. With
+//! `fairness` the counters also spread acquisitions over owner slots, and
+//! with `hold-time` they measure how long guards are held.
+
+use crate::atomic::AtomicUsize;
+use crate::atomic::Ordering;
+#[cfg(doc)]
+use crate::mutex::RawSpinLock;
+
+/// Slots of [`RawSpinLock::fairness_histogram`].
+#[cfg(feature = "fairness")]
+pub const FAIRNESS_SLOTS: usize = 16;
+
+/// Counters behind [`RawSpinLock::stats`].
+pub(crate) struct Counters {
+    acquisitions: AtomicUsize,
+    contended_acquisitions: AtomicUsize,
+    spins: AtomicUsize,
+    max_spins: AtomicUsize,
+    #[cfg(feature = "hold-time")]
+    holds: AtomicUsize,
+    #[cfg(feature = "hold-time")]
+    hold_total: AtomicUsize,
+    #[cfg(feature = "hold-time")]
+    hold_max: AtomicUsize,
+    /// Acquisitions per hashed owner id.
+    #[cfg(feature = "fairness")]
+    per_owner: [AtomicUsize; FAIRNESS_SLOTS],
+}
+
+impl Counters {
+    pub(crate) const fn new() -> Self {
+        Self {
+            acquisitions: AtomicUsize::new(0),
+            contended_acquisitions: AtomicUsize::new(0),
+            spins: AtomicUsize::new(0),
+            max_spins: AtomicUsize::new(0),
+            #[cfg(feature = "hold-time")]
+            holds: AtomicUsize::new(0),
+            #[cfg(feature = "hold-time")]
+            hold_total: AtomicUsize::new(0),
+            #[cfg(feature = "hold-time")]
+            hold_max: AtomicUsize::new(0),
+            #[cfg(feature = "fairness")]
+            per_owner: [const { AtomicUsize::new(0) }; FAIRNESS_SLOTS],
+        }
+    }
+
+    // The counters are only updated while holding the lock, so plain
+    // increments cannot lose updates, and they are also fine before raw
+    // atomics are enabled.
+
+    #[inline]
+    pub(crate) fn record(&self, contention: Option<usize>) {
+        bump(&self.acquisitions, 1);
+        #[cfg(feature = "fairness")]
+        if let Some(me) = crate::owner::current() {
+            bump(&self.per_owner[fairness_slot(me)], 1);
+        }
+        if let Some(spins) = contention {
+            bump(&self.contended_acquisitions, 1);
+            bump(&self.spins, spins);
+            if spins > self.max_spins.load(Ordering::Relaxed) {
+                self.max_spins.store(spins, Ordering::Relaxed);
+            }
+        }
+    }
+
+    #[cfg(feature = "hold-time")]
+    #[inline]
+    pub(crate) fn record_hold(&self, ticks: u64) {
+        let ticks = usize::try_from(ticks).unwrap_or(usize::MAX);
+        bump(&self.holds, 1);
+        let total = self
+            .hold_total
+            .load(Ordering::Relaxed)
+            .saturating_add(ticks);
+        self.hold_total.store(total, Ordering::Relaxed);
+        if ticks > self.hold_max.load(Ordering::Relaxed) {
+            self.hold_max.store(ticks, Ordering::Relaxed);
+        }
+    }
+
+    pub(crate) fn snapshot(&self) -> LockStats {
+        LockStats {
+            acquisitions: self.acquisitions.load(Ordering::Relaxed),
+            contended_acquisitions: self.contended_acquisitions.load(Ordering::Relaxed),
+            spins: self.spins.load(Ordering::Relaxed),
+            max_spins: self.max_spins.load(Ordering::Relaxed),
+            #[cfg(feature = "hold-time")]
+            holds: self.holds.load(Ordering::Relaxed),
+            #[cfg(feature = "hold-time")]
+            hold_total: self.hold_total.load(Ordering::Relaxed),
+            #[cfg(feature = "hold-time")]
+            hold_max: self.hold_max.load(Ordering::Relaxed),
+        }
+    }
+
+    #[cfg(feature = "fairness")]
+    pub(crate) fn fairness_histogram(&self) -> [u64; FAIRNESS_SLOTS] {
+        core::array::from_fn(|slot| self.per_owner[slot].load(Ordering::Relaxed) as u64)
+    }
+
+    pub(crate) fn reset(&self) {
+        self.acquisitions.store(0, Ordering::Relaxed);
+        self.contended_acquisitions.store(0, Ordering::Relaxed);
+        self.spins.store(0, Ordering::Relaxed);
+        self.max_spins.store(0, Ordering::Relaxed);
+        #[cfg(feature = "hold-time")]
+        {
+            self.holds.store(0, Ordering::Relaxed);
+            self.hold_total.store(0, Ordering::Relaxed);
+            self.hold_max.store(0, Ordering::Relaxed);
+        }
+        #[cfg(feature = "fairness")]
+        for slot in &self.per_owner {
+            slot.store(0, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Owner ids are often addresses, so their low bits are mostly alignment;
+/// a multiplicative hash spreads them over the slots.
+#[cfg(feature = "fairness")]
+#[inline]
+fn fairness_slot(owner: crate::owner::OwnerId) -> usize {
+    const MULTIPLIER: u64 = 0x9e37_79b9_7f4a_7c15;
+    let hash = (owner.get() as u64).wrapping_mul(MULTIPLIER);
+    (hash >> (u64::BITS - FAIRNESS_SLOTS.trailing_zeros())) as usize
+}
+
+#[inline]
+fn bump(counter: &AtomicUsize, by: usize) {
+    let value = counter.load(Ordering::Relaxed).wrapping_add(by);
+    counter.store(value, Ordering::Relaxed);
+}
+
+/// A snapshot of a lock's counters; see [`RawSpinLock::stats`].
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(not(feature = "tiny"), derive(Debug))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct LockStats {
+    /// Successful `lock` and `try_lock` calls.
+    pub acquisitions: usize,
+    /// Acquisitions that found the lock held and had to wait.
+    pub contended_acquisitions: usize,
+    /// Spin iterations spent waiting, over all contended acquisitions.
+    pub spins: usize,
+    /// Most spin iterations a single acquisition spent waiting.
+    pub max_spins: usize,
+    /// Guards whose hold time was measured; only those dropped while a
+    /// [`Clock`](crate::clock::Clock) was registered count.
+    #[cfg(feature = "hold-time")]
+    pub holds: usize,
+    /// Total time the lock was held over all measured guards, in clock
+    /// ticks, saturating.
+    #[cfg(feature = "hold-time")]
+    pub hold_total: usize,
+    /// Longest measured hold, in clock ticks.
+    #[cfg(feature = "hold-time")]
+    pub hold_max: usize,
+}
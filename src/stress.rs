@@ -0,0 +1,168 @@
+//! A dining-philosophers stress run for any [`Lock`].
+//!
+//! [`run_philosophers`] seats philosophers around a table with one lock per
+//! fork between neighbours. Each one repeatedly locks both of its forks,
+//! lower-numbered first so the table cannot deadlock by itself, and checks
+//! that nobody else is using them. It returns a [`StressReport`] with the
+//! timing, how often each philosopher got its forks, any exclusion
+//! violations and whether the run finished in time, for tests, benchmarks
+//! and `examples/philosophers.rs --stress` alike.
+//!
+//! Enable raw atomics first; before that the locks exclude nobody, and the
+//! report shows it.
+
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::thread;
+use std::time::Duration;
+use std::time::Instant;
+
+use crate::mutex::Lock;
+
+/// How long [`run_philosophers`] waits for the philosophers to finish.
+pub const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// What a [`run_philosophers`] run observed. Plain data, so it can be
+/// logged or, with the `serde` feature, serialized.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StressReport {
+    /// Wall time from the start until every philosopher finished, or until
+    /// the timeout expired.
+    pub elapsed: Duration,
+    /// Fork acquisitions per philosopher, two per meal.
+    pub acquisitions: Vec<usize>,
+    /// Times a philosopher found a fork it had just locked in use.
+    pub violations: usize,
+    /// Some philosophers were still waiting when the timeout expired, which
+    /// points at a deadlock or a lost wakeup in the lock.
+    pub timed_out: bool,
+}
+
+impl StressReport {
+    /// Whether every philosopher finished with the forks excluding each
+    /// other throughout.
+    pub fn passed(&self) -> bool {
+        !self.timed_out && self.violations == 0
+    }
+
+    pub fn total_acquisitions(&self) -> usize {
+        self.acquisitions.iter().sum()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for StressReport {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        let mut report = serializer.serialize_struct("StressReport", 4)?;
+        report.serialize_field("elapsed", &self.elapsed)?;
+        report.serialize_field("acquisitions", self.acquisitions.as_slice())?;
+        report.serialize_field("violations", &self.violations)?;
+        report.serialize_field("timed_out", &self.timed_out)?;
+        report.end()
+    }
+}
+
+/// A fork's lock, and whether a philosopher is eating with it.
+struct Fork<L> {
+    lock: L,
+    in_use: AtomicBool,
+}
+
+struct Table<L> {
+    forks: Vec<Fork<L>>,
+    acquisitions: Vec<AtomicUsize>,
+    violations: AtomicUsize,
+}
+
+impl<L: Lock<()>> Table<L> {
+    fn dine(&self, i: usize, iterations: usize) {
+        let n = self.forks.len();
+        let (left, right) = (i, (i + 1) % n);
+        let (first, second) = (&self.forks[left.min(right)], &self.forks[left.max(right)]);
+        for _ in 0..iterations {
+            let first_guard = first.lock.lock();
+            let second_guard = second.lock.lock();
+            self.acquisitions[i].fetch_add(2, Ordering::Relaxed);
+            let first_busy = first.in_use.swap(true, Ordering::Relaxed);
+            let second_busy = second.in_use.swap(true, Ordering::Relaxed);
+            let clashes = usize::from(first_busy) + usize::from(second_busy);
+            if clashes != 0 {
+                self.violations.fetch_add(clashes, Ordering::Relaxed);
+            }
+            // Give a neighbour that got in anyway a chance to be caught.
+            thread::yield_now();
+            second.in_use.store(false, Ordering::Relaxed);
+            first.in_use.store(false, Ordering::Relaxed);
+            drop(second_guard);
+            drop(first_guard);
+        }
+    }
+}
+
+/// Runs `philosophers` threads for `iterations` meals each, around forks
+/// made by `make_lock`.
+///
+/// Returns once all of them have finished or [`TIMEOUT`] has expired.
+/// Philosophers still stuck at that point are left running, as a spinning
+/// thread cannot be stopped from outside.
+///
+/// # Panics
+///
+/// Panics if there are fewer than two philosophers, or if a philosopher
+/// panics.
+pub fn run_philosophers<L>(
+    philosophers: usize,
+    iterations: usize,
+    make_lock: impl Fn() -> L,
+) -> StressReport
+where
+    L: Lock<()> + Send + Sync + 'static,
+{
+    assert!(philosophers >= 2, "a table needs at least two philosophers");
+    let table = Arc::new(Table {
+        forks: (0..philosophers)
+            .map(|_| Fork {
+                lock: make_lock(),
+                in_use: AtomicBool::new(false),
+            })
+            .collect(),
+        acquisitions: (0..philosophers).map(|_| AtomicUsize::new(0)).collect(),
+        violations: AtomicUsize::new(0),
+    });
+
+    let start = Instant::now();
+    let mut handles: Vec<_> = (0..philosophers)
+        .map(|i| {
+            let table = Arc::clone(&table);
+            thread::spawn(move || table.dine(i, iterations))
+        })
+        .collect();
+    let deadline = start + TIMEOUT;
+    while !handles.iter().all(|handle| handle.is_finished()) && Instant::now() < deadline {
+        thread::sleep(Duration::from_millis(1));
+    }
+    let elapsed = start.elapsed();
+    let timed_out = !handles.iter().all(|handle| handle.is_finished());
+    if !timed_out {
+        for handle in handles.drain(..) {
+            if let Err(panic) = handle.join() {
+                std::panic::resume_unwind(panic);
+            }
+        }
+    }
+
+    StressReport {
+        elapsed,
+        acquisitions: table
+            .acquisitions
+            .iter()
+            .map(|count| count.load(Ordering::Relaxed))
+            .collect(),
+        violations: table.violations.load(Ordering::Relaxed),
+        timed_out,
+    }
+}
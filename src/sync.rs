@@ -0,0 +1,213 @@
+//! Less common wrappers around [`RawSpinLock`]: owned halves sharing one
+//! lock, guards for disjoint parts of the data, and a lock that scrubs its
+//! value on drop.
+
+#[cfg(feature = "alloc")]
+use alloc::rc::Rc;
+#[cfg(feature = "alloc")]
+use alloc::sync::Arc;
+#[cfg(feature = "alloc")]
+#[cfg(not(feature = "tiny"))]
+use core::fmt;
+#[cfg(feature = "alloc")]
+use core::marker::PhantomData;
+#[cfg(any(feature = "alloc", feature = "zeroize"))]
+use core::ops::Deref;
+#[cfg(feature = "alloc")]
+use core::ops::DerefMut;
+#[cfg(feature = "alloc")]
+use core::ptr::NonNull;
+
+#[cfg(any(feature = "alloc", feature = "zeroize"))]
+use crate::mutex::RawSpinLock;
+#[cfg(feature = "alloc")]
+use crate::mutex::RawSpinLockGuard;
+
+/// One of the two owned halves returned by [`RawSpinLock::split_arc`].
+#[cfg(feature = "alloc")]
+pub struct BiLockHalf<T> {
+    inner: Arc<RawSpinLock<T>>,
+}
+
+#[cfg(feature = "alloc")]
+impl<T> BiLockHalf<T> {
+    pub(crate) fn pair(inner: Arc<RawSpinLock<T>>) -> (Self, Self) {
+        (
+            BiLockHalf {
+                inner: inner.clone(),
+            },
+            BiLockHalf { inner },
+        )
+    }
+
+    pub fn lock(&self) -> RawSpinLockGuard<'_, T> {
+        self.inner.lock()
+    }
+
+    pub fn try_lock(&self) -> Option<RawSpinLockGuard<'_, T>> {
+        self.inner.try_lock()
+    }
+
+    /// Returns `true` if both halves came from the same [`RawSpinLock::split_arc`] call.
+    pub fn is_pair_of(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.inner, &other.inner)
+    }
+
+    /// Joins the two halves back together and returns the protected value.
+    ///
+    /// Fails, handing both halves back, if they belong to different locks.
+    pub fn try_reunite(self, other: Self) -> Result<T, ReuniteError<T>> {
+        if !self.is_pair_of(&other) {
+            return Err(ReuniteError(self, other));
+        }
+        drop(other);
+        // The other half was just dropped, so this is the last reference.
+        match self.into_inner() {
+            Ok(value) => Ok(value),
+            Err(_) => unreachable!("BiLockHalf pair had a third owner"),
+        }
+    }
+
+    /// Returns the protected value once the other half has been dropped.
+    ///
+    /// Fails, handing the half back, while the other half is still alive.
+    pub fn into_inner(self) -> Result<T, Self> {
+        match Arc::try_unwrap(self.inner) {
+            Ok(lock) => Ok(lock.into_inner()),
+            Err(inner) => Err(BiLockHalf { inner }),
+        }
+    }
+}
+
+/// Error returned by [`BiLockHalf::try_reunite`] when the halves do not match.
+#[cfg(feature = "alloc")]
+pub struct ReuniteError<T>(pub BiLockHalf<T>, pub BiLockHalf<T>);
+
+#[cfg(all(feature = "alloc", not(feature = "tiny")))]
+impl<T> fmt::Debug for ReuniteError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("ReuniteError").finish_non_exhaustive()
+    }
+}
+
+#[cfg(all(feature = "alloc", not(feature = "tiny")))]
+impl<T> fmt::Display for ReuniteError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("tried to reunite two BiLockHalf values that don't form a pair")
+    }
+}
+
+#[cfg(all(feature = "alloc", not(feature = "tiny")))]
+impl<T> core::error::Error for ReuniteError<T> {}
+
+#[cfg(all(feature = "alloc", feature = "defmt"))]
+impl<T> defmt::Format for ReuniteError<T> {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        defmt::write!(f, "ReuniteError(..)")
+    }
+}
+
+/// One of the two guards returned by [`RawSpinLockGuard::map_split`], for
+/// part of the data.
+///
+/// The two halves share the original guard, so the lock is released when
+/// the second of them is dropped, in either order. Like the guard, a half
+/// stays in the context that acquired the lock.
+#[cfg(feature = "alloc")]
+pub struct MappedSpinLockGuard<'a, T: ?Sized> {
+    data: NonNull<T>,
+    _guard: Rc<dyn Held + 'a>,
+    _marker: PhantomData<&'a mut T>,
+}
+
+/// Erases the data type of the guard the halves share.
+#[cfg(feature = "alloc")]
+trait Held {}
+
+#[cfg(feature = "alloc")]
+impl<T> Held for RawSpinLockGuard<'_, T> {}
+
+#[cfg(feature = "alloc")]
+impl<'a, A: ?Sized> MappedSpinLockGuard<'a, A> {
+    pub(crate) fn split<T, B: ?Sized>(
+        mut guard: RawSpinLockGuard<'a, T>,
+        f: impl FnOnce(&mut T) -> (&mut A, &mut B),
+    ) -> (Self, MappedSpinLockGuard<'a, B>) {
+        let (a, b) = f(&mut guard);
+        let (a, b) = (NonNull::from(a), NonNull::from(b));
+        // The halves point into the lock, not the guard, so moving the
+        // guard leaves them valid.
+        let guard: Rc<dyn Held + 'a> = Rc::new(guard);
+        (
+            MappedSpinLockGuard {
+                data: a,
+                _guard: guard.clone(),
+                _marker: PhantomData,
+            },
+            MappedSpinLockGuard {
+                data: b,
+                _guard: guard,
+                _marker: PhantomData,
+            },
+        )
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T: ?Sized> Deref for MappedSpinLockGuard<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        // SAFETY: the shared guard holds the lock, and `f` returned the two
+        // halves as disjoint mutable borrows.
+        unsafe { self.data.as_ref() }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T: ?Sized> DerefMut for MappedSpinLockGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        // SAFETY: as for `deref`.
+        unsafe { self.data.as_mut() }
+    }
+}
+
+#[cfg(all(feature = "alloc", not(feature = "tiny")))]
+impl<T: ?Sized + fmt::Debug> fmt::Debug for MappedSpinLockGuard<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+/// A [`RawSpinLock`] that zeroizes its value when dropped, for secrets such
+/// as key material.
+///
+/// The scrub uses the `zeroize` crate's volatile writes, so it is not
+/// optimized away. Values moved out with
+/// [`into_inner`](RawSpinLock::into_inner) beforehand are not covered.
+#[cfg(feature = "zeroize")]
+pub struct ZeroizingSpinLock<T: zeroize::Zeroize>(RawSpinLock<T>);
+
+#[cfg(feature = "zeroize")]
+impl<T: zeroize::Zeroize> ZeroizingSpinLock<T> {
+    pub const fn new(data: T) -> Self {
+        Self(RawSpinLock::new(data))
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl<T: zeroize::Zeroize> Deref for ZeroizingSpinLock<T> {
+    type Target = RawSpinLock<T>;
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl<T: zeroize::Zeroize> Drop for ZeroizingSpinLock<T> {
+    fn drop(&mut self) {
+        zeroize::Zeroize::zeroize(&mut self.0);
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl<T: zeroize::Zeroize> zeroize::ZeroizeOnDrop for ZeroizingSpinLock<T> {}
@@ -0,0 +1,35 @@
+//! Where the current thread is running.
+//!
+//! Topology-aware primitives such as [`CohortLock`](crate::cohort::CohortLock)
+//! ask a registered [`Topology`] hook for the NUMA node of the caller.
+//! Without one, everything runs on node 0.
+
+use crate::hook::HookCell;
+use crate::hook::SetHookError;
+
+/// Kernel hook reporting the NUMA node of the caller.
+///
+/// The answers may be stale by the time they are used (the thread can
+/// migrate); callers only rely on them for performance, never correctness.
+pub trait Topology {
+    fn current_node() -> usize;
+}
+
+#[derive(Clone, Copy)]
+struct Hooks {
+    current_node: fn() -> usize,
+}
+
+static TOPOLOGY: HookCell<Hooks> = HookCell::new();
+
+/// Registers the topology hook. Call once during bring-up.
+pub fn set_topology<T: Topology>() -> Result<(), SetHookError> {
+    TOPOLOGY.set(Hooks {
+        current_node: T::current_node,
+    })
+}
+
+#[inline(always)]
+pub(crate) fn current_node() -> usize {
+    TOPOLOGY.get().map_or(0, |hooks| (hooks.current_node)())
+}
@@ -0,0 +1,79 @@
+//! All-or-nothing updates under an [`RwSpinLock`] write lock.
+//!
+//! [`RwSpinLock::write_transaction`] takes the write lock and hands out a
+//! [`TxWriteGuard`] that derefs to a working copy of the data, so a change
+//! can be made in several steps and validated before anyone sees it.
+//! [`commit`](TxWriteGuard::commit) moves the copy into the lock while the
+//! write lock is still held, so readers see either the old data or all of
+//! the new. Anything else, [`rollback`](TxWriteGuard::rollback), an early
+//! return or a panic unwinding past the guard, drops the copy and leaves
+//! the data as it was.
+//!
+//! The copy lives in the guard itself. [`RwSpinLock::write_transaction`]
+//! makes it with `Clone`, which may allocate for `T` that does;
+//! [`RwSpinLock::write_transaction_copy`] requires `Copy` and never does.
+//! Either way the whole `T` is written back on commit, which suits small
+//! configuration structs rather than large tables.
+//!
+//! [`RwSpinLock`]: crate::rwlock::RwSpinLock
+//! [`RwSpinLock::write_transaction`]: crate::rwlock::RwSpinLock::write_transaction
+//! [`RwSpinLock::write_transaction_copy`]: crate::rwlock::RwSpinLock::write_transaction_copy
+
+#[cfg(not(feature = "tiny"))]
+use core::fmt;
+use core::ops::Deref;
+use core::ops::DerefMut;
+
+use crate::rwlock::RwSpinLockWriteGuard;
+
+/// A write lock with a working copy of the data, published only by
+/// [`commit`](Self::commit).
+#[must_use = "dropping the transaction discards its changes"]
+pub struct TxWriteGuard<'a, T> {
+    guard: RwSpinLockWriteGuard<'a, T>,
+    working: T,
+}
+
+impl<'a, T> TxWriteGuard<'a, T> {
+    pub(crate) fn new(guard: RwSpinLockWriteGuard<'a, T>, working: T) -> Self {
+        Self { guard, working }
+    }
+
+    /// The data as it was when the transaction started, for validating the
+    /// working copy against.
+    pub fn original(&self) -> &T {
+        &self.guard
+    }
+
+    /// Publishes the working copy and releases the lock. The old data is
+    /// dropped before the lock is released.
+    pub fn commit(self) {
+        let Self { mut guard, working } = self;
+        *guard = working;
+    }
+
+    /// Discards the working copy and releases the lock, as dropping the
+    /// guard does.
+    pub fn rollback(self) {}
+}
+
+impl<T> Deref for TxWriteGuard<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        &self.working
+    }
+}
+
+impl<T> DerefMut for TxWriteGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.working
+    }
+}
+
+/// Formats the working copy.
+#[cfg(not(feature = "tiny"))]
+impl<T: fmt::Debug> fmt::Debug for TxWriteGuard<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.working, f)
+    }
+}
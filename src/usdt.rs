@@ -0,0 +1,204 @@
+//! Static tracepoints for SystemTap, bpftrace and other USDT consumers.
+//!
+//! With the `usdt` feature, [`RawSpinLock`] marks its lock events with
+//! SystemTap SDT probes under the `mutex` provider: a `nop` at the probe
+//! site, and a `.note.stapsdt` ELF note saying where it is and where its
+//! arguments live. Nothing runs at a site until a tracer attaches, so no
+//! rebuild is needed to start tracing, and a site costs only the moves that
+//! put its arguments in registers. Without the feature the sites are not
+//! compiled at all.
+//!
+//! The probes, with their arguments in order:
+//!
+//! - `lock_contended`: `lock`, `name`, `name_len`, `file`, `file_len`,
+//!   `line`, when an acquisition finds the lock held and starts waiting.
+//! - `lock_acquired`: the same, then `spins`, the failed attempts before
+//!   the acquisition, 0 if it was not contended.
+//! - `lock_released`: `lock`, `name`, `name_len`. A guard's drop has no
+//!   caller location to report.
+//! - `deadlock`: as `lock_contended`, then `cycle_len`, the number of edges
+//!   in the cycle, when `deadlock-detection` has confirmed one, before the
+//!   handler runs.
+//!
+//! `lock` is the address of the lock word. `name` points to the UTF-8
+//! registry name, or else the lockdep class name, and `name_len` is 0 for a
+//! lock with neither; the bytes are not NUL-terminated, so read them with
+//! the length, as in bpftrace's `str(arg1, arg2)`. `file`, `file_len` and
+//! `line` are where the caller asked for the lock. Every argument is an
+//! unsigned 64-bit value.
+//!
+//! Probes are emitted for x86_64 and AArch64 ELF images: Linux programs,
+//! and kernels built for `target_os = "none"`, whose own tooling can read
+//! the notes out of the image. On other targets, and under Miri, loom and
+//! shuttle, the sites are empty.
+//!
+//! Each site's address is also kept in the retained `mutex_usdt_sites`
+//! section. The notes alone do not keep code alive, and the linker would
+//! otherwise leave the notes of code it discarded pointing at addresses
+//! that are not probes, where a tracer must never plant a breakpoint.
+//!
+//! [`RawSpinLock`]: crate::mutex::RawSpinLock
+
+use core::panic::Location;
+
+/// The SDT argument for one register operand: 8 unsigned bytes.
+#[cfg(all(
+    any(target_arch = "x86_64", target_arch = "aarch64"),
+    any(target_os = "linux", target_os = "none"),
+    not(any(miri, loom, shuttle))
+))]
+macro_rules! sdt_arg {
+    ($arg:expr) => {
+        " 8@{}"
+    };
+}
+
+/// Emits a probe named `$name` with the `mutex` provider: a `nop`, and a
+/// note recording its address and where each argument is.
+#[cfg(all(
+    any(target_arch = "x86_64", target_arch = "aarch64"),
+    any(target_os = "linux", target_os = "none"),
+    not(any(miri, loom, shuttle))
+))]
+macro_rules! sdt_probe {
+    ([$($option:ident),*] $name:ident $(, $arg:expr)*) => {
+        // SAFETY: the instruction is a `nop`; the rest only adds the note,
+        // the site's entry in `mutex_usdt_sites` and, once per object, the
+        // base symbol notes are relative to.
+        unsafe {
+            core::arch::asm!(
+                "990: nop",
+                ".pushsection .note.stapsdt, \"\", \"note\"",
+                ".balign 4",
+                ".4byte 992f - 991f, 994f - 993f, 3",
+                "991: .asciz \"stapsdt\"",
+                "992: .balign 4",
+                "993: .8byte 990b",
+                ".8byte _.stapsdt.base",
+                ".8byte 0",
+                ".asciz \"mutex\"",
+                concat!(".asciz \"", stringify!($name), "\""),
+                concat!(".asciz \"", $(sdt_arg!($arg),)* "\""),
+                "994: .balign 4",
+                ".popsection",
+                ".pushsection mutex_usdt_sites, \"awR\"",
+                ".balign 8",
+                ".8byte 990b",
+                ".popsection",
+                ".ifndef _.stapsdt.base",
+                ".pushsection .stapsdt.base, \"aGR\", \"progbits\", .stapsdt.base, comdat",
+                ".weak _.stapsdt.base",
+                ".hidden _.stapsdt.base",
+                "_.stapsdt.base: .space 1",
+                ".size _.stapsdt.base, 1",
+                ".popsection",
+                ".endif",
+                $(in(reg) $arg as usize,)*
+                options(readonly, nostack, preserves_flags $(, $option)*),
+            );
+        }
+    };
+}
+
+/// SDT arguments name x86 registers in AT&T syntax, `%rdi`.
+#[cfg(all(
+    target_arch = "x86_64",
+    any(target_os = "linux", target_os = "none"),
+    not(any(miri, loom, shuttle))
+))]
+macro_rules! probe {
+    ($name:ident $(, $arg:expr)* $(,)?) => {
+        sdt_probe!([att_syntax] $name $(, $arg)*)
+    };
+}
+
+#[cfg(all(
+    target_arch = "aarch64",
+    any(target_os = "linux", target_os = "none"),
+    not(any(miri, loom, shuttle))
+))]
+macro_rules! probe {
+    ($name:ident $(, $arg:expr)* $(,)?) => {
+        sdt_probe!([] $name $(, $arg)*)
+    };
+}
+
+#[cfg(not(all(
+    any(target_arch = "x86_64", target_arch = "aarch64"),
+    any(target_os = "linux", target_os = "none"),
+    not(any(miri, loom, shuttle))
+)))]
+macro_rules! probe {
+    ($name:ident $(, $arg:expr)* $(,)?) => {
+        let _ = ($($arg,)*);
+    };
+}
+
+/// Where a lock event happened, as the probes report it.
+#[derive(Clone, Copy)]
+pub(crate) struct Site {
+    name: &'static str,
+    caller: &'static Location<'static>,
+}
+
+impl Site {
+    /// The caller's site, for the lock called `name`, `""` if unnamed.
+    #[track_caller]
+    #[inline(always)]
+    pub(crate) fn here(name: &'static str) -> Self {
+        Self {
+            name,
+            caller: Location::caller(),
+        }
+    }
+}
+
+#[inline(always)]
+pub(crate) fn contended(lock: usize, site: Site) {
+    let file = site.caller.file();
+    probe!(
+        lock_contended,
+        lock,
+        site.name.as_ptr(),
+        site.name.len(),
+        file.as_ptr(),
+        file.len(),
+        site.caller.line() as usize,
+    );
+}
+
+#[inline(always)]
+pub(crate) fn acquired(lock: usize, site: Site, spins: usize) {
+    let file = site.caller.file();
+    probe!(
+        lock_acquired,
+        lock,
+        site.name.as_ptr(),
+        site.name.len(),
+        file.as_ptr(),
+        file.len(),
+        site.caller.line() as usize,
+        spins,
+    );
+}
+
+#[inline(always)]
+pub(crate) fn released(lock: usize, name: &'static str) {
+    probe!(lock_released, lock, name.as_ptr(), name.len());
+}
+
+#[cfg(feature = "deadlock-detection")]
+#[cold]
+pub(crate) fn deadlock(lock: usize, site: Site, cycle_len: usize) {
+    let file = site.caller.file();
+    probe!(
+        deadlock,
+        lock,
+        site.name.as_ptr(),
+        site.name.len(),
+        file.as_ptr(),
+        file.len(),
+        site.caller.line() as usize,
+        cycle_len,
+    );
+}
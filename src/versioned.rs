@@ -0,0 +1,198 @@
+//! A lock that counts its changes, so readers can tell they missed none.
+//!
+//! A [`VersionedSpinLock`] pairs a [`RawSpinLock`] with a generation
+//! counter that [`generation`](VersionedSpinLock::generation) reads without
+//! taking the lock. Code caching something derived from the data keeps the
+//! generation it derived from and calls
+//! [`lock_if_changed`](VersionedSpinLock::lock_if_changed), which returns
+//! `None` after a single load while nothing changed.
+//!
+//! Whether a guard's `DerefMut` was used cannot be observed, so the count is
+//! conservative: dropping any [`VersionedGuard`] bumps it, as does
+//! [`get_mut`](VersionedSpinLock::get_mut). Only the guards of
+//! [`read`](VersionedSpinLock::read), which cannot write, leave it alone. A
+//! change the guards do not see, such as one through interior mutability
+//! in `T`, is counted with
+//! [`mark_changed`](VersionedSpinLock::mark_changed).
+//!
+//! A guard bumps the generation while it still holds the lock, so whoever
+//! sees the new generation and then locks sees the data it counts. Before
+//! raw atomics are enabled the counter is bumped with plain stores.
+
+#[cfg(not(feature = "tiny"))]
+use core::fmt;
+use core::ops::Deref;
+use core::ops::DerefMut;
+
+use crate::atomic::AtomicUsize;
+use crate::atomic::Ordering;
+use crate::mutex::Lock;
+use crate::mutex::RawSpinLock;
+use crate::mutex::RawSpinLockGuard;
+use crate::raw::raw_atomics_enabled;
+
+/// A [`RawSpinLock`] with a change counter readable without the lock.
+pub struct VersionedSpinLock<T> {
+    generation: AtomicUsize,
+    lock: RawSpinLock<T>,
+}
+
+/// A guard that may write, and so bumps the generation when dropped.
+pub struct VersionedGuard<'a, T> {
+    generation: &'a AtomicUsize,
+    guard: RawSpinLockGuard<'a, T>,
+}
+
+/// A guard that only reads and leaves the generation as it is.
+pub struct VersionedReadGuard<'a, T> {
+    guard: RawSpinLockGuard<'a, T>,
+}
+
+impl<T> VersionedSpinLock<T> {
+    pub const fn new(data: T) -> Self {
+        Self {
+            generation: AtomicUsize::new(0),
+            lock: RawSpinLock::new(data),
+        }
+    }
+
+    /// The number of changes so far, wrapping on overflow. Only a snapshot:
+    /// a writer may be about to bump it.
+    pub fn generation(&self) -> usize {
+        self.generation.load(Ordering::Acquire)
+    }
+
+    /// Counts a change made without a [`VersionedGuard`].
+    pub fn mark_changed(&self) {
+        bump(&self.generation);
+    }
+
+    #[track_caller]
+    pub fn lock(&self) -> VersionedGuard<'_, T> {
+        self.guard(self.lock.lock())
+    }
+
+    /// Attempts to acquire the lock without spinning.
+    pub fn try_lock(&self) -> Option<VersionedGuard<'_, T>> {
+        Some(self.guard(self.lock.try_lock()?))
+    }
+
+    /// Locks for reading only, so the generation is not bumped.
+    #[track_caller]
+    pub fn read(&self) -> VersionedReadGuard<'_, T> {
+        VersionedReadGuard {
+            guard: self.lock.lock(),
+        }
+    }
+
+    /// Locks for reading if the generation moved past `last_seen`, and
+    /// returns the guard with the generation of the data it shows, to pass
+    /// as `last_seen` next time.
+    ///
+    /// Returns `None` without touching the lock while the generation is
+    /// still `last_seen`.
+    #[track_caller]
+    pub fn lock_if_changed(&self, last_seen: usize) -> Option<(VersionedReadGuard<'_, T>, usize)> {
+        if self.generation() == last_seen {
+            return None;
+        }
+        let guard = self.read();
+        // Guards bump the counter with the lock held, so under the lock it
+        // matches the data.
+        let generation = self.generation();
+        Some((guard, generation))
+    }
+
+    /// Returns the data, counting a change, since `&mut self` rules out
+    /// guards but not writes.
+    pub fn get_mut(&mut self) -> &mut T {
+        bump(&self.generation);
+        self.lock.get_mut()
+    }
+
+    pub fn into_inner(self) -> T {
+        self.lock.into_inner()
+    }
+
+    fn guard<'a>(&'a self, guard: RawSpinLockGuard<'a, T>) -> VersionedGuard<'a, T> {
+        VersionedGuard {
+            generation: &self.generation,
+            guard,
+        }
+    }
+}
+
+fn bump(generation: &AtomicUsize) {
+    if raw_atomics_enabled() {
+        generation.fetch_add(1, Ordering::Release);
+    } else {
+        let next = generation.load(Ordering::Relaxed).wrapping_add(1);
+        generation.store(next, Ordering::Release);
+    }
+}
+
+impl<T> Drop for VersionedGuard<'_, T> {
+    fn drop(&mut self) {
+        // Before `guard` is dropped, so the lock is still held.
+        bump(self.generation);
+    }
+}
+
+impl<T> Deref for VersionedGuard<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        &self.guard
+    }
+}
+
+impl<T> DerefMut for VersionedGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.guard
+    }
+}
+
+impl<T> Deref for VersionedReadGuard<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        &self.guard
+    }
+}
+
+impl<T: Default> Default for VersionedSpinLock<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T> From<T> for VersionedSpinLock<T> {
+    fn from(data: T) -> Self {
+        Self::new(data)
+    }
+}
+
+/// Formats the generation and the inner [`RawSpinLock`].
+#[cfg(not(feature = "tiny"))]
+impl<T: fmt::Debug> fmt::Debug for VersionedSpinLock<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("VersionedSpinLock")
+            .field("generation", &self.generation())
+            .field("lock", &self.lock)
+            .finish()
+    }
+}
+
+impl<T> Lock<T> for VersionedSpinLock<T> {
+    type Guard<'a>
+        = VersionedGuard<'a, T>
+    where
+        T: 'a;
+
+    #[track_caller]
+    fn lock(&self) -> Self::Guard<'_> {
+        VersionedSpinLock::lock(self)
+    }
+
+    fn try_lock(&self) -> Option<Self::Guard<'_>> {
+        VersionedSpinLock::try_lock(self)
+    }
+}
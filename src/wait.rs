@@ -0,0 +1,126 @@
+//! Wait queues for blocking until a condition holds.
+//!
+//! A [`WaitQueue`] is a [`SpinList`] of waiters, each a node on the waiting
+//! thread's stack holding a wake flag. A waiter spins on its flag, or with a
+//! [`Parker`](crate::park::Parker) registered blocks in it, keyed by the
+//! node's address. [`wake_one`](WaitQueue::wake_one) and
+//! [`wake_all`](WaitQueue::wake_all) take waiters off the queue and set
+//! their flags.
+//!
+//! The race to close is a waker making the condition true and waking
+//! between a waiter checking it and enqueueing. [`wait_until`] checks the
+//! condition a second time with the queue locked and enqueues under the same
+//! lock, and wakers take that lock to wake, so as long as a waker makes the
+//! condition true before calling `wake_*`, the waiter either sees it or is
+//! already queued for the wakeup.
+//!
+//! Before raw atomics are enabled only an interrupt handler can wake a
+//! waiter, since nothing else runs.
+//!
+//! [`wait_until`]: WaitQueue::wait_until
+
+use core::pin::pin;
+
+use crate::atomic::AtomicBool;
+use crate::atomic::Ordering;
+use crate::list::ListNode;
+use crate::list::SpinList;
+use crate::park;
+use crate::relax;
+use crate::relax::Backoff;
+
+struct Waiter {
+    woken: AtomicBool,
+}
+
+impl Waiter {
+    fn is_woken(&self) -> bool {
+        self.woken.load(Ordering::Acquire)
+    }
+
+    fn addr(&self) -> usize {
+        core::ptr::from_ref(self) as usize
+    }
+
+    fn wait(&self) {
+        let mut backoff = Backoff::new();
+        while !self.is_woken() {
+            if park::is_registered() {
+                park::park(self.addr(), &|| !self.is_woken());
+            } else {
+                backoff.wait();
+            }
+        }
+    }
+
+    /// Only called with the queue locked, which keeps the waiter's node
+    /// alive until this returns.
+    fn wake(&self) {
+        self.woken.store(true, Ordering::Release);
+        if park::is_registered() {
+            park::unpark_one(self.addr());
+        }
+        relax::notify();
+    }
+}
+
+/// A queue of threads waiting for a condition.
+pub struct WaitQueue {
+    waiters: SpinList<Waiter>,
+}
+
+impl WaitQueue {
+    pub const fn new() -> Self {
+        Self {
+            waiters: SpinList::new(),
+        }
+    }
+
+    /// Blocks until `condition` returns `true`.
+    ///
+    /// `condition` runs before each wait, once without and once with the
+    /// queue locked, so it must not use the queue itself.
+    pub fn wait_until(&self, mut condition: impl FnMut() -> bool) {
+        while !condition() {
+            let node = pin!(ListNode::new(Waiter {
+                woken: AtomicBool::new(false),
+            }));
+            {
+                let mut waiters = self.waiters.lock();
+                if condition() {
+                    return;
+                }
+                waiters.push_back(node.as_ref());
+            }
+            node.wait();
+        }
+    }
+
+    /// Wakes the longest-waiting thread, if any. Returns whether one was
+    /// woken.
+    pub fn wake_one(&self) -> bool {
+        let mut waiters = self.waiters.lock();
+        let waiter = waiters.pop_front();
+        if let Some(waiter) = waiter {
+            waiter.wake();
+        }
+        waiter.is_some()
+    }
+
+    /// Wakes every waiting thread and returns how many there were.
+    pub fn wake_all(&self) -> usize {
+        let mut waiters = self.waiters.lock();
+        let mut woken = 0;
+        while let Some(waiter) = waiters.pop_front() {
+            waiter.wake();
+            woken += 1;
+        }
+        woken
+    }
+}
+
+impl Default for WaitQueue {
+    fn default() -> Self {
+        Self::new()
+    }
+}
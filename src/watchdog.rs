@@ -0,0 +1,176 @@
+//! Reporting waiters that have spun for suspiciously long.
+//!
+//! With the `watchdog` feature, a waiter in the contended path of a
+//! [`RawSpinLock`] or [`RwSpinLock`] that has spun for the spin threshold
+//! without getting the lock reports it once: through the handler registered
+//! with [`set_watchdog_handler`], or by panicking if there is none. If the
+//! handler returns, the waiter keeps spinning.
+//!
+//! The threshold is [`DEFAULT_SPIN_THRESHOLD`] until changed with
+//! [`set_spin_threshold`], and can be overridden per lock with
+//! [`RawSpinLock::set_spin_threshold`]. The check compares the waiter's spin
+//! count, which the loop keeps anyway, against the threshold read once on
+//! entry. Waiters that have blocked in a [`Parker`](crate::park::Parker)
+//! no longer spin and are not watched. With `track-location`, the report
+//! also says where the lock was last acquired, which is usually the holder.
+//!
+//! [`RawSpinLock`]: crate::mutex::RawSpinLock
+//! [`RwSpinLock`]: crate::rwlock::RwSpinLock
+//! [`RawSpinLock::set_spin_threshold`]: crate::mutex::RawSpinLock::set_spin_threshold
+
+use core::fmt;
+use core::panic::Location;
+
+#[cfg(feature = "track-location")]
+use crate::atomic::AtomicPtr;
+use crate::atomic::AtomicUsize;
+use crate::atomic::Ordering;
+use crate::hook::HookCell;
+use crate::hook::SetHookError;
+
+/// Spins before a waiter is reported, unless changed.
+pub const DEFAULT_SPIN_THRESHOLD: usize = 1 << 24;
+
+/// Per-lock threshold value meaning "use the global one".
+pub(crate) const INHERIT: usize = 0;
+
+static THRESHOLD: AtomicUsize = AtomicUsize::new(DEFAULT_SPIN_THRESHOLD);
+
+/// Sets the global spin threshold; 0 disables the watchdog for locks that
+/// do not override it.
+pub fn set_spin_threshold(spins: usize) {
+    THRESHOLD.store(spins, Ordering::Relaxed);
+}
+
+/// Converts a per-lock override into its stored form.
+pub(crate) const fn per_lock(spins: Option<usize>) -> usize {
+    match spins {
+        None => INHERIT,
+        // Never reached, since spinning that long takes centuries.
+        Some(0) => usize::MAX,
+        Some(spins) => spins,
+    }
+}
+
+/// The watched lock's fields the slow path needs.
+#[derive(Clone, Copy)]
+pub(crate) struct Watched<'a> {
+    /// The per-lock threshold, for locks that have one.
+    pub(crate) spin_threshold: Option<&'a AtomicUsize>,
+    #[cfg(feature = "track-location")]
+    pub(crate) last_acquired_at: &'a AtomicPtr<Location<'static>>,
+}
+
+/// What a waiter is watched against, set up when it enters the slow path.
+pub(crate) struct Watch<'a> {
+    threshold: usize,
+    location: &'static Location<'static>,
+    #[cfg_attr(not(feature = "track-location"), allow(dead_code))]
+    watched: Watched<'a>,
+}
+
+impl<'a> Watch<'a> {
+    #[track_caller]
+    pub(crate) fn new(watched: Watched<'a>) -> Self {
+        let per_lock = watched
+            .spin_threshold
+            .map_or(INHERIT, |spins| spins.load(Ordering::Relaxed));
+        let threshold = match per_lock {
+            INHERIT => THRESHOLD.load(Ordering::Relaxed),
+            spins => spins,
+        };
+        Self {
+            threshold,
+            location: Location::caller(),
+            watched,
+        }
+    }
+
+    #[inline(always)]
+    pub(crate) fn check(&self, lock: usize, spins: usize) {
+        if spins == self.threshold {
+            self.fire(lock, spins);
+        }
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn fire(&self, lock: usize, spins: usize) {
+        let report = WatchdogReport {
+            lock,
+            location: self.location,
+            spins,
+            // SAFETY: the pointer is null or comes from a `&'static Location`.
+            #[cfg(feature = "track-location")]
+            last_acquired_at: unsafe {
+                self.watched
+                    .last_acquired_at
+                    .load(Ordering::Relaxed)
+                    .as_ref()
+            },
+        };
+        match HANDLER.get() {
+            Some(handler) => handler(&report),
+            None => panic!("{report}"),
+        }
+    }
+}
+
+/// A waiter that has hit the spin threshold.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WatchdogReport {
+    /// Address of the lock.
+    pub lock: usize,
+    /// Where the waiter called `lock`, `read` or `write`.
+    pub location: &'static Location<'static>,
+    pub spins: usize,
+    /// Where the lock was last acquired, if known.
+    #[cfg(feature = "track-location")]
+    pub last_acquired_at: Option<&'static Location<'static>>,
+}
+
+impl fmt::Display for WatchdogReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "spun {} times on lock {:#x} at {}",
+            self.spins, self.lock, self.location
+        )?;
+        #[cfg(feature = "track-location")]
+        if let Some(acquired_at) = self.last_acquired_at {
+            write!(f, ", last acquired at {acquired_at}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for WatchdogReport {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        defmt::write!(
+            f,
+            "spun {} times on lock {:#x} at {}:{}",
+            self.spins,
+            self.lock,
+            self.location.file(),
+            self.location.line()
+        );
+        #[cfg(feature = "track-location")]
+        if let Some(acquired_at) = self.last_acquired_at {
+            defmt::write!(
+                f,
+                ", last acquired at {}:{}",
+                acquired_at.file(),
+                acquired_at.line()
+            );
+        }
+    }
+}
+
+static HANDLER: HookCell<fn(&WatchdogReport)> = HookCell::new();
+
+/// Registers a handler called instead of panicking when a waiter hits the
+/// spin threshold.
+pub fn set_watchdog_handler(handler: fn(&WatchdogReport)) -> Result<(), SetHookError> {
+    HANDLER.set(handler)
+}
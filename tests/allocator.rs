@@ -0,0 +1,147 @@
+//! A bump allocator behind `LockedAllocator` as this binary's global
+//! allocator, used on one thread before raw atomics are enabled and by
+//! several threads after.
+//!
+//! There is no test harness: it would allocate from several threads before
+//! anything could enable raw atomics.
+
+use std::alloc::GlobalAlloc;
+use std::alloc::Layout;
+use std::cell::UnsafeCell;
+use std::thread;
+
+use mutex::allocator::LockedAllocator;
+use mutex::allocator::RawAllocator;
+
+/// Roomy enough for the panic machinery to symbolize a backtrace, since
+/// nothing is ever freed.
+const HEAP_SIZE: usize = if cfg!(miri) { 1 << 20 } else { 64 << 20 };
+const BOXES: u64 = if cfg!(miri) { 50 } else { 1_000 };
+
+#[repr(align(4096))]
+struct Heap(UnsafeCell<[u8; HEAP_SIZE]>);
+
+// SAFETY: the bytes are only handed out by `Bump`, under the lock.
+unsafe impl Sync for Heap {}
+
+static HEAP: Heap = Heap(UnsafeCell::new([0; HEAP_SIZE]));
+static SCRATCH: Heap = Heap(UnsafeCell::new([0; HEAP_SIZE]));
+
+/// Never reuses memory; `dealloc` only counts.
+struct Bump {
+    heap: &'static Heap,
+    next: usize,
+    allocs: usize,
+    allocs_before_atomics: usize,
+    deallocs: usize,
+}
+
+impl Bump {
+    const fn new(heap: &'static Heap) -> Self {
+        Self {
+            heap,
+            next: 0,
+            allocs: 0,
+            allocs_before_atomics: 0,
+            deallocs: 0,
+        }
+    }
+}
+
+unsafe impl RawAllocator for Bump {
+    fn alloc(&mut self, layout: Layout) -> *mut u8 {
+        let base = self.heap.0.get().cast::<u8>();
+        let start = (base as usize + self.next).next_multiple_of(layout.align()) - base as usize;
+        let Some(end) = start
+            .checked_add(layout.size())
+            .filter(|&end| end <= HEAP_SIZE)
+        else {
+            return std::ptr::null_mut();
+        };
+        self.next = end;
+        self.allocs += 1;
+        if !mutex::raw_atomics_enabled() {
+            self.allocs_before_atomics += 1;
+        }
+        // SAFETY: `start..end` is inside the heap and was never handed out.
+        unsafe { base.add(start) }
+    }
+
+    unsafe fn dealloc(&mut self, _ptr: *mut u8, _layout: Layout) {
+        self.deallocs += 1;
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: LockedAllocator<Bump> = LockedAllocator::new(Bump::new(&HEAP));
+
+/// Reads the global allocator's counters without holding its lock across
+/// anything that might allocate, such as a failing assertion.
+fn counters() -> (usize, usize, usize) {
+    let bump = ALLOCATOR.lock();
+    (bump.allocs, bump.allocs_before_atomics, bump.deallocs)
+}
+
+fn main() {
+    serves_one_thread_before_and_many_after_enabling_atomics();
+    realloc_keeps_the_contents_and_alignment();
+    an_exhausted_heap_returns_null();
+}
+
+fn serves_one_thread_before_and_many_after_enabling_atomics() {
+    let early = vec![1, 2, 3];
+    let (_, before_atomics, _) = counters();
+    assert!(before_atomics > 0);
+    mutex::enable_raw_atomics();
+    let (before, _, _) = counters();
+    let threads: Vec<_> = (0..4u64)
+        .map(|id| {
+            thread::spawn(move || {
+                let boxes: Vec<_> = (0..BOXES).map(|i| Box::new([id, i, id, i])).collect();
+                // Growing a vector goes through `realloc`.
+                let mut grown = Vec::new();
+                for i in 0..BOXES {
+                    grown.push(id * BOXES + i);
+                }
+                (boxes, grown)
+            })
+        })
+        .collect();
+    for (id, thread) in (0..4u64).zip(threads) {
+        let (boxes, grown) = thread.join().unwrap();
+        for (i, value) in (0..).zip(boxes) {
+            assert_eq!(*value, [id, i, id, i]);
+        }
+        assert!(grown.iter().copied().eq(id * BOXES..(id + 1) * BOXES));
+    }
+    assert_eq!(early, [1, 2, 3]);
+    let (allocs, after_atomics, deallocs) = counters();
+    assert_eq!(after_atomics, before_atomics);
+    assert!(allocs - before >= 4 * BOXES as usize);
+    assert!(deallocs > 0);
+}
+
+fn realloc_keeps_the_contents_and_alignment() {
+    let allocator = LockedAllocator::new(Bump::new(&SCRATCH));
+    let layout = Layout::from_size_align(8, 64).unwrap();
+    // SAFETY: the blocks are used within their layouts and freed with them.
+    unsafe {
+        let ptr = allocator.alloc(layout);
+        assert_eq!(ptr as usize % 64, 0);
+        ptr.copy_from_nonoverlapping([1, 2, 3, 4, 5, 6, 7, 8].as_ptr(), 8);
+        let grown = allocator.realloc(ptr, layout, 32);
+        assert_eq!(grown as usize % 64, 0);
+        assert_eq!(*grown.cast::<[u8; 8]>(), [1, 2, 3, 4, 5, 6, 7, 8]);
+        allocator.dealloc(grown, Layout::from_size_align(32, 64).unwrap());
+    }
+    let bump = allocator.lock();
+    assert_eq!((bump.allocs, bump.deallocs), (2, 2));
+}
+
+fn an_exhausted_heap_returns_null() {
+    let mut bump = Bump::new(&SCRATCH);
+    bump.next = HEAP_SIZE;
+    let allocator = LockedAllocator::new(bump);
+    // SAFETY: the layout is valid and nothing is allocated.
+    assert!(unsafe { allocator.alloc(Layout::new::<u64>()) }.is_null());
+}
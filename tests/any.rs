@@ -0,0 +1,130 @@
+//! `try_lock_any` and `lock_any` over a set of per-queue locks: which lock
+//! a pass returns, and that rotating the start spreads acquisitions evenly.
+
+use std::thread;
+use std::time::Duration;
+
+use mutex::RawSpinLock;
+use mutex::any;
+
+const LOCKS: usize = 4;
+const ROUNDS: usize = if cfg!(miri) { 40 } else { 4_000 };
+
+fn queues() -> [RawSpinLock<usize>; LOCKS] {
+    std::array::from_fn(|_| RawSpinLock::new(0))
+}
+
+#[test]
+fn a_pass_starts_at_the_rotation_index_and_wraps() {
+    mutex::enable_raw_atomics();
+    let queues = queues();
+    let locks: Vec<_> = queues.iter().collect();
+    for start in 0..2 * LOCKS {
+        let (index, _guard) = any::try_lock_any_from(&locks, start).unwrap();
+        assert_eq!(index, start % LOCKS);
+    }
+    let _second = queues[1].lock();
+    let _third = queues[2].lock();
+    assert_eq!(any::try_lock_any_from(&locks, 1).unwrap().0, 3);
+    assert_eq!(any::try_lock_any_from(&locks, 3).unwrap().0, 3);
+}
+
+#[test]
+fn none_when_every_lock_is_held() {
+    mutex::enable_raw_atomics();
+    let queues = queues();
+    let locks: Vec<_> = queues.iter().collect();
+    let _held: Vec<_> = queues.iter().map(RawSpinLock::lock).collect();
+    for start in 0..LOCKS {
+        assert!(any::try_lock_any_from(&locks, start).is_none());
+    }
+    assert!(any::try_lock_any(&locks).is_none());
+    assert!(any::try_lock_any::<usize, RawSpinLock<usize>>(&[]).is_none());
+}
+
+#[test]
+fn advancing_the_start_spreads_acquisitions_evenly() {
+    mutex::enable_raw_atomics();
+    let queues = queues();
+    let locks: Vec<_> = queues.iter().collect();
+    for start in 0..ROUNDS {
+        let (_, mut guard) = any::try_lock_any_from(&locks, start).unwrap();
+        *guard += 1;
+    }
+    let counts: Vec<_> = queues.iter().map(|queue| *queue.lock()).collect();
+    assert_eq!(counts, [ROUNDS / LOCKS; LOCKS]);
+}
+
+#[test]
+fn a_held_lock_passes_its_share_to_the_next() {
+    mutex::enable_raw_atomics();
+    let queues = queues();
+    let locks: Vec<_> = queues.iter().collect();
+    let held = queues[0].lock();
+    for start in 0..ROUNDS {
+        let (_, mut guard) = any::try_lock_any_from(&locks, start).unwrap();
+        *guard += 1;
+    }
+    drop(held);
+    let counts: Vec<_> = queues.iter().map(|queue| *queue.lock()).collect();
+    let share = ROUNDS / LOCKS;
+    assert_eq!(counts, [0, 2 * share, share, share]);
+}
+
+#[test]
+fn contending_threads_spread_over_the_set() {
+    mutex::enable_raw_atomics();
+    let queues = queues();
+    let locks: Vec<_> = queues.iter().collect();
+    thread::scope(|s| {
+        for id in 0..LOCKS {
+            let locks = &locks;
+            s.spawn(move || {
+                for round in 0..ROUNDS {
+                    let (_, mut guard) = any::lock_any_from(locks, id + round);
+                    *guard += 1;
+                }
+            });
+        }
+    });
+    let counts: Vec<_> = queues.iter().map(|queue| *queue.lock()).collect();
+    assert_eq!(counts.iter().sum::<usize>(), LOCKS * ROUNDS);
+    // A holder preempted mid-pass sends its lock's share to the next one,
+    // so only the uncontended tests above can expect an exact split.
+    assert!(
+        counts.iter().all(|&count| count > 0),
+        "unused lock: {counts:?}"
+    );
+}
+
+#[test]
+fn the_context_start_is_stable_for_a_context() {
+    mutex::enable_raw_atomics();
+    let queues = queues();
+    let locks: Vec<_> = queues.iter().collect();
+    let first = any::try_lock_any(&locks).unwrap().0;
+    for _ in 0..LOCKS {
+        assert_eq!(any::try_lock_any(&locks).unwrap().0, first);
+    }
+}
+
+#[test]
+fn lock_any_waits_for_whichever_lock_is_released() {
+    mutex::enable_raw_atomics();
+    let queues = queues();
+    let locks: Vec<_> = queues.iter().collect();
+    let mut held: Vec<_> = queues.iter().map(|queue| Some(queue.lock())).collect();
+    thread::scope(|s| {
+        let waiter = s.spawn(|| any::lock_any(&locks).0);
+        thread::sleep(Duration::from_millis(20));
+        assert!(!waiter.is_finished());
+        held[2] = None;
+        assert_eq!(waiter.join().unwrap(), 2);
+    });
+}
+
+#[test]
+#[should_panic = "lock_any needs at least one lock"]
+fn lock_any_panics_on_an_empty_set() {
+    any::lock_any::<usize, RawSpinLock<usize>>(&[]);
+}
@@ -0,0 +1,58 @@
+//! `SpinArcSwap` with readers loading while writers swap. Small enough to
+//! run under Miri, which checks that no old `Arc` is freed under a reader
+//! and that the final strong counts add up.
+
+#![cfg(feature = "alloc")]
+
+use std::sync::Arc;
+use std::thread;
+
+use mutex::arc_swap::SpinArcSwap;
+
+const THREADS: usize = if cfg!(miri) { 2 } else { 4 };
+const ROUNDS: usize = if cfg!(miri) { 20 } else { 2_000 };
+
+#[test]
+fn swap_returns_the_old_value() {
+    mutex::enable_raw_atomics();
+    let cell = SpinArcSwap::new(Arc::new(1));
+    let first = cell.load();
+    assert_eq!(Arc::strong_count(&first), 2);
+    let old = cell.swap(Arc::new(2));
+    assert!(Arc::ptr_eq(&old, &first));
+    assert_eq!(*cell.load(), 2);
+    drop(old);
+    assert_eq!(Arc::strong_count(&first), 1);
+    cell.store(Arc::new(3));
+    assert_eq!(*cell.into_inner(), 3);
+}
+
+#[test]
+fn concurrent_load_and_swap() {
+    mutex::enable_raw_atomics();
+    // Every value the cell ever holds, so the counts can be checked after.
+    let values: Vec<_> = (0..=THREADS * ROUNDS).map(Arc::new).collect();
+    let cell = SpinArcSwap::new(Arc::clone(&values[0]));
+    thread::scope(|s| {
+        for id in 0..THREADS {
+            let (cell, values) = (&cell, &values);
+            s.spawn(move || {
+                for round in 0..ROUNDS {
+                    let seen = cell.load();
+                    assert!(*seen <= THREADS * ROUNDS);
+                    let next = 1 + id * ROUNDS + round;
+                    drop(cell.swap(Arc::clone(&values[next])));
+                    assert!(Arc::strong_count(&seen) >= 2);
+                }
+            });
+        }
+    });
+    let last = cell.into_inner();
+    // Only the vector holds the rest, and the last value is in both.
+    for value in &values {
+        let expected = if Arc::ptr_eq(value, &last) { 2 } else { 1 };
+        assert_eq!(Arc::strong_count(value), expected, "value {value}");
+    }
+    drop(last);
+    assert!(values.iter().all(|value| Arc::strong_count(value) == 1));
+}
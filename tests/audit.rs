@@ -0,0 +1,39 @@
+//! The audit report of the locks used before raw atomics were enabled; in
+//! a binary of its own so nothing has enabled raw atomics before it starts.
+
+#![cfg(feature = "audit")]
+
+use mutex::RawSpinLock;
+use mutex::audit;
+
+mutex::registered_static! {
+    static BOOT_CONSOLE: RawSpinLock<u32> = 0;
+    static BOOT_ALLOCATOR: RawSpinLock<u32> = 0;
+    static SCHEDULER: RawSpinLock<u32> = 0;
+}
+
+#[test]
+fn the_report_names_exactly_the_locks_used_during_bring_up() {
+    *BOOT_CONSOLE.lock() += 1;
+    *BOOT_CONSOLE.lock() += 1;
+    drop(BOOT_ALLOCATOR.try_lock().unwrap());
+    assert_eq!(audit::pre_atomic_total(), 3);
+
+    mutex::enable_raw_atomics();
+    *SCHEDULER.lock() += 1;
+    *BOOT_CONSOLE.lock() += 1;
+    assert_eq!(audit::pre_atomic_total(), 3);
+
+    let mut report = String::new();
+    audit::report(&mut report).unwrap();
+    let mut lines: Vec<_> = report.lines().collect();
+    assert_eq!(lines.pop(), Some("total: 3"), "{report}");
+    lines.sort_unstable();
+    let [allocator, console] = lines[..] else {
+        panic!("{report}");
+    };
+    assert!(allocator.starts_with("BOOT_ALLOCATOR ("), "{report}");
+    assert!(allocator.ends_with("): 1 before raw atomics"), "{report}");
+    assert!(console.starts_with("BOOT_CONSOLE ("), "{report}");
+    assert!(console.ends_with("): 2 before raw atomics"), "{report}");
+}
@@ -0,0 +1,143 @@
+//! `Backoff` doubling its pauses up to the cap before handing over to its
+//! `Relax` strategy, contended waiters all getting through in bounded time,
+//! and `AdaptiveRelax` beating pure spinning once threads outnumber cores.
+
+use std::cell::Cell;
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+use std::time::Instant;
+
+use mutex::RawSpinLock;
+use mutex::relax::Backoff;
+use mutex::relax::Relax;
+
+std::thread_local!(static CAPPED: Cell<Vec<u32>> = const { Cell::new(Vec::new()) });
+
+/// Records the rounds it is called with, then spins like `Spin`.
+struct Recording;
+
+impl Relax for Recording {
+    fn relax_capped(rounds: u32) -> bool {
+        CAPPED.with(|capped| {
+            let mut seen = capped.take();
+            seen.push(rounds);
+            capped.set(seen);
+        });
+        false
+    }
+}
+
+fn capped() -> Vec<u32> {
+    CAPPED.with(Cell::take)
+}
+
+#[test]
+fn the_strategy_takes_over_once_the_pauses_are_capped() {
+    let mut backoff = Backoff::<Recording>::with_relax();
+    // Pauses of 1, 2, 4, ... 32 iterations come first; every round after
+    // them is up to the strategy, or else a pause of 64.
+    for _ in 0..6 {
+        backoff.spin();
+    }
+    assert_eq!(capped(), []);
+    for _ in 0..3 {
+        backoff.wait();
+    }
+    assert_eq!(capped(), [0, 1, 2]);
+    backoff.reset();
+    for _ in 0..6 {
+        backoff.spin();
+    }
+    backoff.spin();
+    assert_eq!(capped(), [0]);
+}
+
+#[test]
+fn no_contended_waiter_starves() {
+    mutex::enable_raw_atomics();
+    const THREADS: usize = 4;
+    let rounds = if cfg!(miri) { 10 } else { 2_000 };
+    let lock = RawSpinLock::new(0);
+    let longest = Mutex::new(Duration::ZERO);
+    thread::scope(|s| {
+        for _ in 0..THREADS {
+            s.spawn(|| {
+                let mut mine = Duration::ZERO;
+                for _ in 0..rounds {
+                    let start = Instant::now();
+                    let mut guard = lock.lock();
+                    mine = mine.max(start.elapsed());
+                    *guard += 1;
+                }
+                let mut longest = longest.lock().unwrap();
+                *longest = longest.max(mine);
+            });
+        }
+    });
+    assert_eq!(lock.into_inner(), THREADS * rounds);
+    // The capped backoff keeps every waiter polling, so the longest wait
+    // is a matter of scheduling, nowhere near this.
+    let longest = longest.into_inner().unwrap();
+    assert!(longest < Duration::from_secs(1), "waited {longest:?}");
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn adaptive_relax_escalates_from_spinning_to_yielding_to_sleeping() {
+    use mutex::relax::AdaptiveRelax;
+
+    assert!(!AdaptiveRelax::relax_capped(0));
+    assert!(!AdaptiveRelax::relax_capped(15));
+    assert!(AdaptiveRelax::relax_capped(16));
+    let start = Instant::now();
+    assert!(AdaptiveRelax::relax_capped(80));
+    assert!(start.elapsed() >= Duration::from_micros(50));
+}
+
+/// Time for `threads` threads to take a lock that waits through
+/// `Backoff<R>` `rounds` times each, holding it for half a millisecond.
+#[cfg(feature = "std")]
+fn oversubscribed<R: Relax>(threads: usize, rounds: usize) -> Duration {
+    use std::sync::atomic::AtomicBool;
+    use std::sync::atomic::Ordering;
+
+    let locked = AtomicBool::new(false);
+    let start = Instant::now();
+    thread::scope(|s| {
+        for _ in 0..threads {
+            s.spawn(|| {
+                for _ in 0..rounds {
+                    let mut backoff = Backoff::<R>::with_relax();
+                    while locked.swap(true, Ordering::Acquire) {
+                        while locked.load(Ordering::Relaxed) {
+                            backoff.wait();
+                        }
+                    }
+                    let until = Instant::now() + Duration::from_micros(500);
+                    while Instant::now() < until {
+                        std::hint::spin_loop();
+                    }
+                    locked.store(false, Ordering::Release);
+                }
+            });
+        }
+    });
+    start.elapsed()
+}
+
+#[cfg(feature = "std")]
+#[test]
+#[cfg_attr(miri, ignore)]
+fn adaptive_relax_beats_spinning_when_oversubscribed() {
+    use mutex::relax::AdaptiveRelax;
+    use mutex::relax::Spin;
+
+    // Owners get descheduled mid-section, and spinning waiters then burn
+    // whole timeslices before the owner runs again.
+    let cores = thread::available_parallelism().map_or(1, |n| n.get());
+    let threads = 16 * cores;
+    let spin = oversubscribed::<Spin>(threads, 10);
+    let adaptive = oversubscribed::<AdaptiveRelax>(threads, 10);
+    assert!(adaptive * 3 < spin, "spin {spin:?}, adaptive {adaptive:?}");
+}
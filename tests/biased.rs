@@ -0,0 +1,70 @@
+//! `BiasedSpinLock` with raw atomics enabled.
+
+use std::panic::AssertUnwindSafe;
+use std::panic::catch_unwind;
+use std::thread;
+
+use mutex::biased::BiasedSpinLock;
+
+#[test]
+fn revoke_while_the_owner_is_mid_critical_section_panics() {
+    mutex::enable_raw_atomics();
+    let lock = BiasedSpinLock::new(0);
+    assert!(lock.claim_bias());
+    let guard = lock.lock();
+    let revoked = catch_unwind(AssertUnwindSafe(|| lock.revoke_bias()));
+    assert!(revoked.is_err());
+    assert!(lock.bias().is_some());
+    // The owner's guard still excludes everyone else.
+    thread::scope(|s| {
+        s.spawn(|| assert!(lock.try_lock().is_none()));
+    });
+    drop(guard);
+    lock.revoke_bias();
+    assert!(lock.bias().is_none());
+    thread::scope(|s| {
+        s.spawn(|| assert!(lock.try_lock().is_some()));
+    });
+}
+
+#[test]
+fn the_owner_cannot_lock_twice() {
+    mutex::enable_raw_atomics();
+    let lock = BiasedSpinLock::new(0);
+    assert!(lock.claim_bias());
+    let guard = lock.lock();
+    assert!(lock.try_lock().is_none());
+    let relocked = catch_unwind(AssertUnwindSafe(|| drop(lock.lock())));
+    assert!(relocked.is_err());
+    drop(guard);
+    assert!(lock.try_lock().is_some());
+}
+
+#[test]
+fn repeated_rebias_keeps_the_lock_exclusive() {
+    mutex::enable_raw_atomics();
+    const THREADS: u64 = 4;
+    const ROUNDS: u64 = 50;
+    const LOCKS: u64 = 100;
+    let lock = BiasedSpinLock::new((0u64, 0u64));
+    thread::scope(|s| {
+        for _ in 0..THREADS {
+            s.spawn(|| {
+                for _ in 0..ROUNDS {
+                    assert!(lock.claim_bias());
+                    for _ in 0..LOCKS {
+                        let mut guard = lock.lock();
+                        assert_eq!(guard.0, guard.1);
+                        guard.0 += 1;
+                        guard.1 += 1;
+                    }
+                    lock.revoke_bias();
+                }
+            });
+        }
+    });
+    assert_eq!(
+        lock.into_inner(),
+        (THREADS * ROUNDS * LOCKS, THREADS * ROUNDS * LOCKS)
+    );
+}
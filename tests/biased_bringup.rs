@@ -0,0 +1,28 @@
+//! `BiasedSpinLock` before raw atomics are enabled, in a binary of its own
+//! so no other test has enabled them.
+
+use mutex::biased::BiasedSpinLock;
+
+#[test]
+fn the_bias_is_plain_state_before_raw_atomics() {
+    let lock = BiasedSpinLock::new(0);
+    assert!(lock.claim_bias());
+    assert!(lock.bias().is_some());
+    // Guards are permissive, so none of them is biased and revoking under
+    // them does not panic.
+    let first = lock.lock();
+    let second = lock.try_lock().unwrap();
+    lock.revoke_bias();
+    assert!(lock.bias().is_none());
+    drop((first, second));
+
+    assert!(lock.claim_bias());
+    mutex::enable_raw_atomics();
+    {
+        let mut guard = lock.lock();
+        *guard += 1;
+        assert!(lock.try_lock().is_none());
+    }
+    lock.revoke_bias();
+    assert_eq!(lock.into_inner(), 1);
+}
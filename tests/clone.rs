@@ -0,0 +1,84 @@
+//! Cloning a lock another thread holds: `RawSpinLock` waits for the
+//! holder, `RwSpinLock` clones alongside a reader, and either way the clone
+//! is a new, unlocked lock that shares nothing with the original.
+
+use std::sync::Barrier;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::thread;
+use std::time::Duration;
+
+use mutex::RawSpinLock;
+use mutex::RwSpinLock;
+
+#[test]
+fn a_clone_waits_for_the_holder_and_is_independent() {
+    mutex::enable_raw_atomics();
+    let lock = RawSpinLock::new(vec![1u8]);
+    let held = Barrier::new(2);
+    let released = AtomicBool::new(false);
+    let clone = thread::scope(|s| {
+        s.spawn(|| {
+            let mut guard = lock.lock();
+            held.wait();
+            thread::sleep(Duration::from_millis(20));
+            guard.push(2);
+            released.store(true, Ordering::SeqCst);
+        });
+        held.wait();
+        let clone = lock.clone();
+        assert!(released.load(Ordering::SeqCst), "cloned under the holder");
+        clone
+    });
+    assert_eq!(*clone.lock(), [1, 2]);
+
+    // A new lock: free while the original is held, and neither sees the
+    // other's writes.
+    let mut guard = lock.lock();
+    clone.try_lock().unwrap().push(3);
+    guard.push(4);
+    drop(guard);
+    assert_eq!(lock.into_inner(), [1, 2, 4]);
+    assert_eq!(clone.into_inner(), [1, 2, 3]);
+}
+
+#[cfg(feature = "stats")]
+#[test]
+fn a_clone_starts_with_zeroed_stats() {
+    mutex::enable_raw_atomics();
+    let lock = RawSpinLock::new(0);
+    for _ in 0..3 {
+        *lock.lock() += 1;
+    }
+    let clone = lock.clone();
+    assert!(lock.stats().acquisitions >= 4);
+    assert_eq!(clone.stats().acquisitions, 0);
+}
+
+#[test]
+fn an_rwlock_clones_alongside_a_reader_and_is_independent() {
+    mutex::enable_raw_atomics();
+    let lock = RwSpinLock::new(vec![1u8]);
+    let held = Barrier::new(2);
+    let cloned = Barrier::new(2);
+    let clone = thread::scope(|s| {
+        s.spawn(|| {
+            let guard = lock.read();
+            held.wait();
+            cloned.wait();
+            assert_eq!(*guard, [1]);
+        });
+        held.wait();
+        // The reader keeps its guard until after this returns.
+        let clone = lock.clone();
+        cloned.wait();
+        clone
+    });
+
+    let reader = lock.read();
+    clone.try_write().unwrap().push(2);
+    drop(reader);
+    lock.write().push(3);
+    assert_eq!(lock.into_inner(), [1, 3]);
+    assert_eq!(clone.into_inner(), [1, 2]);
+}
@@ -0,0 +1,99 @@
+//! `CohortLock` with nodes assigned per thread through the topology hook.
+
+use std::cell::Cell;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::thread;
+use std::time::Duration;
+
+use mutex::cohort::CohortLock;
+use mutex::cohort::MAX_LOCAL_HANDOFFS;
+use mutex::topology::Topology;
+
+thread_local! {
+    static NODE: Cell<usize> = const { Cell::new(0) };
+}
+
+struct PerThread;
+
+impl Topology for PerThread {
+    fn current_node() -> usize {
+        NODE.get()
+    }
+}
+
+fn setup() {
+    mutex::enable_raw_atomics();
+    let _ = mutex::topology::set_topology::<PerThread>();
+}
+
+#[test]
+fn another_node_waits_for_at_most_one_round_of_local_handoffs() {
+    setup();
+    static LOCK: CohortLock<Vec<usize>> = CohortLock::new(Vec::new());
+    static STOP: AtomicBool = AtomicBool::new(false);
+    let first = LOCK.lock();
+    // Node 0 threads queue on their cohort's lock behind `first` and keep
+    // coming back, so node 0 always has a local waiter to hand off to.
+    let locals: Vec<_> = (0..2)
+        .map(|_| {
+            thread::spawn(|| {
+                while !STOP.load(Ordering::Relaxed) {
+                    LOCK.lock().push(0);
+                }
+            })
+        })
+        .collect();
+    let remote = thread::spawn(|| {
+        NODE.set(1);
+        LOCK.lock().push(1);
+        STOP.store(true, Ordering::Relaxed);
+    });
+    // Ample time for the remote thread to queue on the global lock.
+    thread::sleep(Duration::from_millis(100));
+    drop(first);
+    remote.join().unwrap();
+    for local in locals {
+        local.join().unwrap();
+    }
+    let order = LOCK.lock();
+    let before_remote = order.iter().position(|&node| node == 1).unwrap();
+    assert!(
+        before_remote <= MAX_LOCAL_HANDOFFS,
+        "node 1 waited for {before_remote} node 0 acquisitions"
+    );
+}
+
+#[test]
+fn node_ids_are_folded_into_the_cohorts() {
+    setup();
+    let lock: CohortLock<u32, 2> = CohortLock::new(0);
+    thread::scope(|s| {
+        for node in [0, 1, 2, 7] {
+            let lock = &lock;
+            s.spawn(move || {
+                NODE.set(node);
+                for _ in 0..100 {
+                    *lock.lock() += 1;
+                }
+            });
+        }
+    });
+    assert_eq!(lock.into_inner(), 400);
+}
+
+#[test]
+fn try_lock_fails_across_nodes() {
+    setup();
+    let lock: CohortLock<u32> = CohortLock::new(0);
+    let guard = lock.lock();
+    thread::scope(|s| {
+        s.spawn(|| {
+            NODE.set(1);
+            assert!(lock.try_lock().is_none());
+        });
+        s.spawn(|| assert!(lock.try_lock().is_none()));
+    });
+    drop(guard);
+    assert!(lock.try_lock().is_some());
+}
@@ -0,0 +1,164 @@
+//! Ordinary `std::sync::Mutex` and `RwLock` code, run against `compat`.
+//!
+//! Everything below is written as it would be for std, mostly after std's
+//! own examples; only the `use` of the lock types changes. Each test also
+//! enables raw atomics first, which std code has no reason to do.
+
+#![cfg(feature = "std")]
+
+use std::collections::HashMap;
+use std::panic;
+use std::sync::Arc;
+use std::sync::mpsc::channel;
+use std::thread;
+
+use mutex::compat::Mutex;
+use mutex::compat::MutexGuard;
+use mutex::compat::PoisonError;
+use mutex::compat::RwLock;
+use mutex::compat::TryLockError;
+
+const N: usize = 10;
+
+#[test]
+fn shared_counter() {
+    mutex::enable_raw_atomics();
+    let data = Arc::new(Mutex::new(0));
+    let (tx, rx) = channel();
+    for _ in 0..N {
+        let (data, tx) = (Arc::clone(&data), tx.clone());
+        thread::spawn(move || {
+            let mut data = data.lock().unwrap();
+            *data += 1;
+            if *data == N {
+                tx.send(()).unwrap();
+            }
+        });
+    }
+    rx.recv().unwrap();
+    assert_eq!(*data.lock().unwrap(), N);
+}
+
+#[test]
+fn recovering_from_a_poisoned_lock() {
+    mutex::enable_raw_atomics();
+    let lock = Arc::new(Mutex::new(0_u32));
+    let lock2 = Arc::clone(&lock);
+    let _ = thread::spawn(move || {
+        let _guard = lock2.lock().unwrap();
+        panic!();
+    })
+    .join();
+    assert!(lock.is_poisoned());
+
+    let mut guard = match lock.lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    *guard += 1;
+    drop(guard);
+
+    let err = lock.lock().unwrap_err();
+    assert_eq!(**err.get_ref(), 1);
+    drop(err);
+    lock.clear_poison();
+    assert!(!lock.is_poisoned());
+    assert_eq!(*lock.lock().unwrap(), 1);
+}
+
+#[test]
+fn try_lock_would_block() {
+    mutex::enable_raw_atomics();
+    let lock = Mutex::new(0);
+    let guard = lock.lock().unwrap();
+    match lock.try_lock() {
+        Err(TryLockError::WouldBlock) => {}
+        Err(TryLockError::Poisoned(_)) => panic!("not poisoned"),
+        Ok(_) => panic!("locked twice"),
+    }
+    drop(guard);
+    *lock.try_lock().unwrap() = 10;
+    assert_eq!(lock.into_inner().unwrap(), 10);
+}
+
+#[test]
+fn into_inner_and_get_mut_of_a_poisoned_lock() {
+    mutex::enable_raw_atomics();
+    let mut lock = Mutex::new(String::from("hello"));
+    let _ = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+        let mut guard = lock.lock().unwrap();
+        guard.push_str(", world");
+        panic!();
+    }));
+    let value: &mut String = lock
+        .get_mut()
+        .unwrap_or_else(|poisoned: PoisonError<&mut String>| poisoned.into_inner());
+    value.push('!');
+    let value = lock.into_inner().unwrap_or_else(PoisonError::into_inner);
+    assert_eq!(value, "hello, world!");
+}
+
+fn insert(map: &Mutex<HashMap<&'static str, u32>>, key: &'static str) -> Result<(), String> {
+    let mut map: MutexGuard<'_, _> = map.lock().map_err(|e| e.to_string())?;
+    *map.entry(key).or_default() += 1;
+    Ok(())
+}
+
+#[test]
+fn propagating_poison_as_an_error() {
+    mutex::enable_raw_atomics();
+    let map = Mutex::new(HashMap::new());
+    insert(&map, "a").unwrap();
+    insert(&map, "a").unwrap();
+    assert_eq!(map.lock().unwrap()["a"], 2);
+}
+
+#[test]
+fn readers_and_a_writer() {
+    mutex::enable_raw_atomics();
+    let lock = Arc::new(RwLock::new(5));
+    {
+        let r1 = lock.read().unwrap();
+        let r2 = lock.read().unwrap();
+        assert_eq!(*r1 + *r2, 10);
+        assert!(matches!(lock.try_write(), Err(TryLockError::WouldBlock)));
+    }
+    {
+        let mut w = lock.write().unwrap();
+        *w += 1;
+        assert!(lock.try_read().is_err());
+    }
+    let handles: Vec<_> = (0..N)
+        .map(|_| {
+            let lock = Arc::clone(&lock);
+            thread::spawn(move || *lock.write().unwrap() += 1)
+        })
+        .collect();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+    assert_eq!(*lock.read().unwrap(), 6 + N);
+
+    let lock2 = Arc::clone(&lock);
+    let _ = thread::spawn(move || {
+        let _guard = lock2.write().unwrap();
+        panic!();
+    })
+    .join();
+    assert!(lock.is_poisoned());
+    assert_eq!(*lock.read().unwrap_err().into_inner(), 6 + N);
+    lock.clear_poison();
+    let mut lock = Arc::into_inner(lock).unwrap();
+    *lock.get_mut().unwrap() = 0;
+    assert_eq!(lock.into_inner().unwrap(), 0);
+}
+
+#[test]
+fn default_from_and_debug() {
+    mutex::enable_raw_atomics();
+    let lock: Mutex<Vec<u8>> = Mutex::default();
+    lock.lock().unwrap().push(1);
+    assert_eq!(format!("{:?}", lock.lock().unwrap()), "[1]");
+    let lock = RwLock::from(7);
+    assert_eq!(lock.read().unwrap().to_string(), "7");
+}
@@ -0,0 +1,66 @@
+//! The wait-for-graph detector on an ABBA deadlock and on plain contention.
+
+#![cfg(feature = "deadlock-detection")]
+
+use std::panic;
+use std::panic::AssertUnwindSafe;
+use std::sync::Barrier;
+use std::thread;
+use std::time::Duration;
+
+use mutex::RawSpinLock;
+use mutex::owner;
+
+#[test]
+fn abba_is_reported_with_both_participants() {
+    mutex::enable_raw_atomics();
+    let a = RawSpinLock::new(0);
+    let b = RawSpinLock::new(0);
+    let barrier = Barrier::new(2);
+    let take = |first: &RawSpinLock<u32>, second: &RawSpinLock<u32>| {
+        let me = owner::current().unwrap().get();
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            let _first = first.lock();
+            barrier.wait();
+            *second.lock() += 1;
+        }));
+        let report = result
+            .err()
+            .map(|payload| *payload.downcast::<String>().unwrap());
+        (me, report)
+    };
+    let ((one, first), (two, second)) = thread::scope(|s| {
+        let one = s.spawn(|| take(&a, &b));
+        let two = s.spawn(|| take(&b, &a));
+        (one.join().unwrap(), two.join().unwrap())
+    });
+    // Whoever found the cycle panicked, which released its lock and let the
+    // other one finish.
+    let reports: Vec<_> = first.into_iter().chain(second).collect();
+    assert!(!reports.is_empty());
+    for report in reports {
+        assert!(report.starts_with("deadlock detected:"), "{report}");
+        for id in [one, two] {
+            assert!(report.contains(&format!("{id:#x}")), "{report}");
+        }
+        for lock in [&a, &b] {
+            let addr = std::ptr::from_ref(lock).addr();
+            assert!(report.contains(&format!("{addr:#x}")), "{report}");
+        }
+    }
+}
+
+#[test]
+fn long_contention_is_not_a_deadlock() {
+    mutex::enable_raw_atomics();
+    let lock = RawSpinLock::new(0);
+    thread::scope(|s| {
+        let guard = lock.lock();
+        let waiter = s.spawn(|| *lock.lock() += 1);
+        // Long enough for the waiter to walk the graph many times.
+        thread::sleep(Duration::from_millis(100));
+        drop(guard);
+        waiter.join().unwrap();
+    });
+    assert_eq!(lock.into_inner(), 1);
+}
@@ -0,0 +1,66 @@
+//! `Debug` for `RawSpinLock` peeks at the data without acquiring the lock
+//! in any way the lock's bookkeeping would notice.
+
+use std::panic;
+use std::panic::AssertUnwindSafe;
+
+use mutex::RawSpinLock;
+use mutex::state::MutexState;
+
+#[test]
+fn shows_the_data_or_locked() {
+    mutex::enable_raw_atomics();
+    let lock = RawSpinLock::new(7);
+    assert!(format!("{lock:?}").contains("data: 7"));
+    let guard = lock.lock();
+    assert!(format!("{lock:?}").contains("data: <locked>"));
+    drop(guard);
+    assert_eq!(lock.state(), MutexState::Unlocked);
+}
+
+#[test]
+fn a_panicking_debug_impl_releases_the_lock() {
+    mutex::enable_raw_atomics();
+    struct Panics;
+    impl std::fmt::Debug for Panics {
+        fn fmt(&self, _: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            panic!("formatting failed");
+        }
+    }
+    let lock = RawSpinLock::new(Panics);
+    assert!(panic::catch_unwind(AssertUnwindSafe(|| format!("{lock:?}"))).is_err());
+    assert_eq!(lock.state(), MutexState::Unlocked);
+    assert!(lock.try_lock().is_some());
+}
+
+#[test]
+#[cfg(feature = "stats")]
+fn is_not_counted_as_an_acquisition() {
+    mutex::enable_raw_atomics();
+    let lock = RawSpinLock::new(0);
+    drop(lock.lock());
+    let _ = format!("{lock:?}");
+    assert_eq!(lock.stats().acquisitions, 1);
+}
+
+#[test]
+#[cfg(feature = "track-location")]
+fn keeps_the_last_acquisition_site() {
+    mutex::enable_raw_atomics();
+    let lock = RawSpinLock::new(0);
+    drop(lock.lock());
+    let acquired = lock.last_acquired_at().unwrap();
+    let _ = format!("{lock:?}");
+    assert_eq!(lock.last_acquired_at(), Some(acquired));
+}
+
+#[test]
+#[cfg(feature = "fault-injection")]
+fn uses_up_no_injected_failures() {
+    mutex::enable_raw_atomics();
+    let lock = RawSpinLock::new(0);
+    mutex::fault::fail_next_try_locks(1);
+    assert!(format!("{lock:?}").contains("data: 0"));
+    assert_eq!(mutex::fault::pending_failures(), 1);
+    mutex::fault::fail_next_try_locks(0);
+}
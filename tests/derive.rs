@@ -0,0 +1,85 @@
+//! What `#[derive(LockProject)]` expands to: one accessor per field, on
+//! both lock types, in traits as visible as the fields.
+
+#![cfg(feature = "derive")]
+
+use mutex::RawSpinLock;
+use mutex::RwSpinLock;
+use mutex::project::LockProject;
+
+#[derive(LockProject, Default)]
+struct Queue<T> {
+    items: Vec<T>,
+    pushed: usize,
+}
+
+mod driver {
+    use mutex::project::LockProject;
+
+    #[derive(LockProject, Default)]
+    pub struct Registers {
+        pub status: u8,
+        pub(crate) control: u8,
+        scratch: u8,
+    }
+
+    impl Registers {
+        pub fn scratch(&self) -> u8 {
+            self.scratch
+        }
+    }
+
+    /// Only this module sees the private field's accessor.
+    pub fn poke_scratch(lock: &mutex::RawSpinLock<Registers>, value: u8) {
+        *lock.lock_scratch() = value;
+    }
+}
+
+use driver::Registers;
+use driver::RegistersLockProject;
+use driver::RegistersLockProjectCrate;
+use driver::RegistersRwLockProject;
+
+#[test]
+fn each_field_gets_a_guard_of_its_own_type() {
+    mutex::enable_raw_atomics();
+    let queue = RawSpinLock::new(Queue::<&str>::default());
+    queue.lock_items().push("a");
+    *queue.lock_pushed() += 1;
+    let queue = queue.into_inner();
+    assert_eq!((queue.items, queue.pushed), (vec!["a"], 1));
+}
+
+#[test]
+fn a_field_guard_holds_the_whole_lock() {
+    mutex::enable_raw_atomics();
+    let queue = RawSpinLock::new(Queue::<u8>::default());
+    let items = queue.lock_items();
+    assert!(queue.try_lock().is_none());
+    drop(items);
+    assert!(queue.try_lock().is_some());
+}
+
+#[test]
+fn rw_accessors_share_reads_and_exclude_writes() {
+    mutex::enable_raw_atomics();
+    let queue = RwSpinLock::new(Queue::<u8>::default());
+    queue.write_items().push(1);
+    let first = queue.read_items();
+    let second = queue.read_pushed();
+    assert_eq!((first.len(), *second), (1, 0));
+    assert!(queue.try_write().is_none());
+}
+
+#[test]
+fn accessors_follow_field_visibility() {
+    mutex::enable_raw_atomics();
+    let registers = RawSpinLock::new(Registers::default());
+    *registers.lock_status() = 1;
+    *registers.lock_control() = 2;
+    driver::poke_scratch(&registers, 3);
+    let registers = RwSpinLock::new(registers.into_inner());
+    assert_eq!(*registers.read_status(), 1);
+    let registers = registers.into_inner();
+    assert_eq!((registers.control, registers.scratch()), (2, 3));
+}
@@ -0,0 +1,71 @@
+//! `DoubleBuffer` frames written whole and published in sequence: the
+//! reader sees only complete frames, never an older one after a newer one.
+
+use std::thread;
+
+use mutex::double_buffer::DoubleBuffer;
+
+const FRAMES: u64 = if cfg!(miri) { 20 } else { 2_000 };
+
+/// Every slot holds the frame's sequence number, so a torn frame shows up
+/// as mixed numbers.
+type Frame = [u64; 8];
+
+fn fill(frame: &mut Frame, seq: u64) {
+    for slot in frame.iter_mut() {
+        *slot = seq;
+    }
+}
+
+fn seq_of(frame: &Frame) -> u64 {
+    assert!(
+        frame.iter().all(|&slot| slot == frame[0]),
+        "torn: {frame:?}"
+    );
+    frame[0]
+}
+
+#[test]
+fn reads_see_the_last_published_frame() {
+    mutex::enable_raw_atomics();
+    let buffer = DoubleBuffer::new([0; 8], [0; 8]);
+    buffer.write(|frame| fill(frame, 1));
+    assert_eq!(buffer.read(seq_of), 0);
+    buffer.publish();
+    assert_eq!(buffer.read(seq_of), 1);
+    buffer.write(|frame| fill(frame, 2));
+    assert_eq!(buffer.read(seq_of), 1);
+    buffer.publish();
+    assert_eq!(buffer.read(seq_of), 2);
+    let (front, back) = buffer.into_inner();
+    assert_eq!((front[0], back[0]), (2, 1));
+}
+
+#[test]
+fn the_reader_sees_complete_frames_in_order() {
+    mutex::enable_raw_atomics();
+    let buffer = DoubleBuffer::new([0; 8], [0; 8]);
+    thread::scope(|s| {
+        s.spawn(|| {
+            for seq in 1..=FRAMES {
+                buffer.write(|frame| {
+                    // Slot by slot, so a reader in the middle would tear it.
+                    for slot in frame.iter_mut() {
+                        *slot = seq;
+                        std::hint::spin_loop();
+                    }
+                });
+                buffer.publish();
+            }
+        });
+        s.spawn(|| {
+            let mut last = 0;
+            while last < FRAMES {
+                let seen = buffer.read(seq_of);
+                assert!(seen >= last, "frame {seen} after {last}");
+                last = seen;
+            }
+        });
+    });
+    assert_eq!(buffer.read(seq_of), FRAMES);
+}
@@ -0,0 +1,94 @@
+//! RTM elision against the plain path: an uncontended `lock` runs as a
+//! transaction, one that finds the lock held for real aborts and spins
+//! instead, and counts from both paths add up. Build with
+//! `-C target-feature=+rtm`; skips on CPUs without RTM.
+
+#![cfg(all(feature = "hle", target_arch = "x86_64", target_feature = "rtm"))]
+
+use std::thread;
+use std::time::Duration;
+
+use mutex::RawSpinLock;
+use mutex::elision;
+
+const THREADS: usize = 4;
+const ROUNDS: usize = 10_000;
+
+fn rtm() -> bool {
+    std::arch::is_x86_feature_detected!("rtm")
+}
+
+#[test]
+fn an_uncontended_lock_is_elided() {
+    if !rtm() {
+        return;
+    }
+    mutex::enable_raw_atomics();
+    let lock = RawSpinLock::new(0);
+    let before = elision::stats();
+    // Nothing inside the section makes a system call, which would abort.
+    for _ in 0..100 {
+        *lock.lock() += 1;
+    }
+    let after = elision::stats();
+    assert_eq!(lock.into_inner(), 100);
+    assert!(after.attempts > before.attempts);
+    // Some transactions may abort for unrelated reasons, not all of them.
+    assert!(
+        after.aborts - before.aborts < after.attempts - before.attempts,
+        "{before:?} -> {after:?}"
+    );
+}
+
+#[test]
+fn a_lock_held_for_real_forces_the_plain_path() {
+    if !rtm() {
+        return;
+    }
+    mutex::enable_raw_atomics();
+    let lock = RawSpinLock::new(0);
+    // `try_lock` never elides, so this holds the lock word.
+    let held = lock.try_lock().unwrap();
+    thread::scope(|s| {
+        let before = elision::stats();
+        let waiter = s.spawn(|| *lock.lock() += 1);
+        thread::sleep(Duration::from_millis(20));
+        assert!(!waiter.is_finished());
+        let during = elision::stats();
+        assert!(during.aborts > before.aborts, "{before:?} -> {during:?}");
+        drop(held);
+        waiter.join().unwrap();
+    });
+    assert_eq!(lock.into_inner(), 1);
+}
+
+#[test]
+fn elided_and_plain_acquisitions_exclude_each_other() {
+    if !rtm() {
+        return;
+    }
+    mutex::enable_raw_atomics();
+    let lock = RawSpinLock::new(0);
+    thread::scope(|s| {
+        for id in 0..THREADS {
+            let lock = &lock;
+            s.spawn(move || {
+                for _ in 0..ROUNDS {
+                    // Half the threads always take the word for real.
+                    if id % 2 == 0 {
+                        *lock.lock() += 1;
+                    } else {
+                        loop {
+                            if let Some(mut guard) = lock.try_lock() {
+                                *guard += 1;
+                                break;
+                            }
+                            std::hint::spin_loop();
+                        }
+                    }
+                }
+            });
+        }
+    });
+    assert_eq!(lock.into_inner(), THREADS * ROUNDS);
+}
@@ -0,0 +1,61 @@
+//! `Exchanger` swapping values between two threads, and the non-blocking
+//! and budgeted forms giving the value back without a partner. Runs under
+//! Miri, which checks each value changes hands exactly once.
+
+use std::thread;
+
+use mutex::exchanger::Exchanger;
+
+const ROUNDS: usize = if cfg!(miri) { 10 } else { 1_000 };
+
+#[test]
+fn two_threads_swap_values() {
+    mutex::enable_raw_atomics();
+    let exchanger = Exchanger::new();
+    thread::scope(|s| {
+        let other = s.spawn(|| exchanger.exchange(String::from("left")));
+        assert_eq!(exchanger.exchange(String::from("right")), "left");
+        assert_eq!(other.join().unwrap(), "right");
+    });
+    assert!(!exchanger.is_waiting());
+}
+
+#[test]
+fn repeated_exchanges_pair_up_in_order() {
+    mutex::enable_raw_atomics();
+    let exchanger = Exchanger::new();
+    thread::scope(|s| {
+        s.spawn(|| {
+            for round in 0..ROUNDS {
+                assert_eq!(exchanger.exchange((1, round)), (2, round));
+            }
+        });
+        for round in 0..ROUNDS {
+            assert_eq!(exchanger.exchange((2, round)), (1, round));
+        }
+    });
+}
+
+#[test]
+fn without_a_partner_the_value_comes_back() {
+    mutex::enable_raw_atomics();
+    let exchanger = Exchanger::new();
+    assert_eq!(exchanger.try_exchange(vec![1]), Err(vec![1]));
+    assert_eq!(exchanger.exchange_for(vec![2], 10), Err(vec![2]));
+    assert!(!exchanger.is_waiting());
+    assert_eq!(format!("{exchanger:?}"), r#"Exchanger { state: "empty" }"#);
+}
+
+#[test]
+fn try_exchange_meets_a_waiting_caller() {
+    mutex::enable_raw_atomics();
+    let exchanger = Exchanger::new();
+    thread::scope(|s| {
+        let waiter = s.spawn(|| exchanger.exchange(1));
+        while !exchanger.is_waiting() {
+            thread::yield_now();
+        }
+        assert_eq!(exchanger.try_exchange(2), Ok(1));
+        assert_eq!(waiter.join().unwrap(), 2);
+    });
+}
@@ -0,0 +1,119 @@
+//! The `extern "C"` entry points called through function pointers, as C
+//! would, on a header laid out by a C-style declaration.
+
+#![cfg(feature = "ffi")]
+
+use std::mem::MaybeUninit;
+use std::thread;
+
+use mutex::RawSpinLock;
+use mutex::ffi::LOCK_WORD_OFFSET;
+use mutex::ffi::LOCK_WORD_UNLOCKED;
+use mutex::ffi::RAWSPINLOCK_ALIGN;
+use mutex::ffi::RAWSPINLOCK_SIZE;
+use mutex::ffi::RawSpinLockFfi;
+
+/// `struct rawspinlock { uint8_t locked; };`
+#[repr(C)]
+struct CRawSpinLock {
+    locked: u8,
+}
+
+/// `struct shared { struct rawspinlock lock; uint64_t count; };`
+#[repr(C)]
+struct CShared {
+    lock: CRawSpinLock,
+    count: u64,
+}
+
+const _: () = assert!(
+    size_of::<CRawSpinLock>() == RAWSPINLOCK_SIZE
+        && align_of::<CRawSpinLock>() == RAWSPINLOCK_ALIGN
+);
+
+type Entry = unsafe extern "C" fn(*mut RawSpinLockFfi);
+type TryEntry = unsafe extern "C" fn(*mut RawSpinLockFfi) -> bool;
+
+static INIT: Entry = mutex::ffi::rawspinlock_init;
+static LOCK: Entry = mutex::ffi::rawspinlock_lock;
+static TRY_LOCK: TryEntry = mutex::ffi::rawspinlock_try_lock;
+static UNLOCK: Entry = mutex::ffi::rawspinlock_unlock;
+
+/// Whether the debugging features leave the header as C declares it.
+fn c_compatible() -> bool {
+    size_of::<RawSpinLockFfi>() == RAWSPINLOCK_SIZE
+        && align_of::<RawSpinLockFfi>() == RAWSPINLOCK_ALIGN
+}
+
+#[test]
+fn the_entry_points_lock_and_unlock_the_header() {
+    mutex::enable_raw_atomics();
+    let mut header = MaybeUninit::<RawSpinLockFfi>::uninit();
+    let ptr = header.as_mut_ptr();
+    let word = || {
+        // SAFETY: `ptr` is live and initialized, and the word is a byte.
+        unsafe { ptr.cast::<u8>().add(LOCK_WORD_OFFSET).read_volatile() }
+    };
+    // SAFETY: `ptr` points to storage for a header, which these calls
+    // initialize and then lock and unlock in pairs.
+    unsafe {
+        INIT(ptr);
+        assert_eq!(word(), LOCK_WORD_UNLOCKED);
+        LOCK(ptr);
+        assert_ne!(word(), LOCK_WORD_UNLOCKED);
+        assert!(!TRY_LOCK(ptr));
+        UNLOCK(ptr);
+        assert_eq!(word(), LOCK_WORD_UNLOCKED);
+        assert!(TRY_LOCK(ptr));
+        // Rust sees the C acquisition too.
+        assert!(RawSpinLock::from_raw(ptr).try_lock().is_none());
+        UNLOCK(ptr);
+        assert!(RawSpinLock::from_raw(ptr).try_lock().is_some());
+    }
+}
+
+struct Shared(*mut CShared);
+
+// SAFETY: only dereferenced under the lock.
+unsafe impl Sync for Shared {}
+
+#[test]
+fn c_and_rust_callers_exclude_each_other() {
+    if !c_compatible() {
+        return;
+    }
+    mutex::enable_raw_atomics();
+    let rounds = if cfg!(miri) { 50 } else { 20_000 };
+    let mut storage = MaybeUninit::<CShared>::uninit();
+    let shared = Shared(storage.as_mut_ptr());
+    // SAFETY: C initializes the header and then its data.
+    unsafe {
+        INIT((&raw mut (*shared.0).lock).cast());
+        (&raw mut (*shared.0).count).write(0);
+    }
+    let shared = &shared;
+    thread::scope(|s| {
+        s.spawn(move || {
+            for _ in 0..rounds {
+                // SAFETY: initialized above; the count is only touched with
+                // the lock held.
+                unsafe {
+                    let lock = (&raw mut (*shared.0).lock).cast();
+                    LOCK(lock);
+                    (*shared.0).count += 1;
+                    UNLOCK(lock);
+                }
+            }
+        });
+        s.spawn(move || {
+            // SAFETY: the header matches C's, so `CShared` is laid out as
+            // this lock.
+            let lock = unsafe { RawSpinLock::<u64>::from_raw(shared.0.cast()) };
+            for _ in 0..rounds {
+                *lock.lock() += 1;
+            }
+        });
+    });
+    // SAFETY: the threads are done.
+    assert_eq!(unsafe { (*shared.0).count }, 2 * rounds);
+}
@@ -0,0 +1,36 @@
+//! Replays the `lock_ops` fuzz target's seed corpus through the same
+//! interpreter, so the corpus and the shadow model stay working without a
+//! nightly toolchain. New findings belong in `fuzz/corpus/lock_ops` too.
+
+#[path = "../fuzz/lock_ops.rs"]
+mod lock_ops;
+
+use std::fs;
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn seed_corpus_replays_cleanly() {
+    let mut inputs: Vec<_> = fs::read_dir("fuzz/corpus/lock_ops")
+        .unwrap()
+        .map(|entry| {
+            let path = entry.unwrap().path();
+            let data = fs::read(&path).unwrap();
+            (path, data)
+        })
+        .collect();
+    // Inputs that never enable raw atomics go first, while permissive mode
+    // can still be exercised; then the rest in name order.
+    inputs.sort_by_key(|(path, data)| {
+        let enables = data
+            .iter()
+            .skip(1)
+            .any(|&byte| byte & 0xf == lock_ops::ENABLE);
+        (enables, path.clone())
+    });
+    assert!(!inputs.is_empty());
+    for (path, data) in &inputs {
+        eprintln!("replaying {}", path.display());
+        lock_ops::run(data);
+    }
+    assert!(mutex::raw_atomics_enabled(), "no seed enables raw atomics");
+}
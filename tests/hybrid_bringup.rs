@@ -0,0 +1,60 @@
+//! `HybridMutex` from permissive bring-up, through the enable with a guard
+//! still alive, to blocking on the std mutex; in a binary of its own so
+//! nothing has enabled raw atomics before it starts.
+
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::thread;
+use std::time::Duration;
+
+use mutex::hybrid::HybridMutex;
+
+const THREADS: usize = if cfg!(miri) { 2 } else { 4 };
+const ROUNDS: usize = if cfg!(miri) { 20 } else { 2_000 };
+
+#[test]
+fn a_guard_from_before_the_enable_excludes_lockers_after_it() {
+    static LOCK: HybridMutex<usize> = HybridMutex::new(0);
+
+    // Permissive: nested guards on one thread, and the inner one leaves
+    // the outer one's mark alone.
+    let mut outer = LOCK.lock();
+    *outer = 1;
+    {
+        let mut inner = LOCK.try_lock().unwrap();
+        *inner += 1;
+    }
+    drop(LOCK.lock());
+    assert_eq!(*outer, 2);
+
+    mutex::enable_raw_atomics();
+    assert!(LOCK.try_lock().is_none());
+    let entered = AtomicBool::new(false);
+    thread::scope(|s| {
+        s.spawn(|| {
+            let mut guard = LOCK.lock();
+            assert_eq!(*guard, 3);
+            *guard = 0;
+            entered.store(true, Ordering::SeqCst);
+        });
+        thread::sleep(Duration::from_millis(20));
+        assert!(!entered.load(Ordering::SeqCst));
+        *outer += 1;
+        drop(outer);
+    });
+    assert!(entered.load(Ordering::SeqCst));
+
+    // Blocking on the std mutex from here on.
+    thread::scope(|s| {
+        for _ in 0..THREADS {
+            s.spawn(|| {
+                for _ in 0..ROUNDS {
+                    *LOCK.lock() += 1;
+                }
+            });
+        }
+    });
+    let held = LOCK.lock();
+    assert_eq!(*held, THREADS * ROUNDS);
+    assert!(LOCK.try_lock().is_none());
+}
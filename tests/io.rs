@@ -0,0 +1,114 @@
+//! `io::Read` and `io::Write` through a `RawSpinLockGuard` and an
+//! `RwSpinLockWriteGuard`: a `Vec<u8>` writer and a `Cursor` reader, each
+//! handed to generic code as the guard itself, and a writer that records
+//! which of its methods the guards reached.
+
+#![cfg(feature = "std")]
+
+use std::io::Cursor;
+use std::io::IoSlice;
+use std::io::IoSliceMut;
+use std::io::Read;
+use std::io::Write;
+
+use mutex::RawSpinLock;
+use mutex::RwSpinLock;
+
+fn write_greeting(mut out: impl Write) {
+    out.write_all(b"hello").unwrap();
+    let written = out
+        .write_vectored(&[IoSlice::new(b", "), IoSlice::new(b"world")])
+        .unwrap();
+    assert_eq!(written, 7);
+    write!(out, " {}", 42).unwrap();
+    out.flush().unwrap();
+}
+
+fn read_greeting(mut input: impl Read) {
+    let mut head = [0; 5];
+    input.read_exact(&mut head).unwrap();
+    assert_eq!(&head, b"hello");
+    let (mut comma, mut space) = ([0; 1], [0; 1]);
+    let read = input
+        .read_vectored(&mut [IoSliceMut::new(&mut comma), IoSliceMut::new(&mut space)])
+        .unwrap();
+    assert_eq!((read, comma, space), (2, *b",", *b" "));
+    let mut word = [0; 5];
+    assert_eq!(input.read(&mut word).unwrap(), 5);
+    assert_eq!(&word, b"world");
+    let mut rest = String::new();
+    input.read_to_string(&mut rest).unwrap();
+    assert_eq!(rest, " 42");
+}
+
+#[test]
+fn a_spin_lock_guard_writes_to_a_vec_and_reads_from_a_cursor() {
+    mutex::enable_raw_atomics();
+    let out = RawSpinLock::new(Vec::new());
+    write_greeting(out.lock());
+    let written = out.into_inner();
+    assert_eq!(written, b"hello, world 42");
+
+    let input = RawSpinLock::new(Cursor::new(written));
+    read_greeting(input.lock());
+    let mut end = Vec::new();
+    assert_eq!(input.lock().read_to_end(&mut end).unwrap(), 0);
+}
+
+#[test]
+fn an_rwlock_write_guard_writes_to_a_vec_and_reads_from_a_cursor() {
+    mutex::enable_raw_atomics();
+    let out = RwSpinLock::new(Vec::new());
+    write_greeting(out.write());
+    let written = out.into_inner();
+    assert_eq!(written, b"hello, world 42");
+
+    let input = RwSpinLock::new(Cursor::new(written));
+    read_greeting(input.write());
+    assert_eq!(input.read().position(), 15);
+}
+
+/// Counts the calls that reach it, by method.
+#[derive(Default)]
+struct Calls {
+    write_vectored: usize,
+    write_all: usize,
+    flush: usize,
+}
+
+impl Write for Calls {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        Ok(buf.len())
+    }
+
+    fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> std::io::Result<usize> {
+        self.write_vectored += 1;
+        Ok(bufs.iter().map(|buf| buf.len()).sum())
+    }
+
+    fn write_all(&mut self, _: &[u8]) -> std::io::Result<()> {
+        self.write_all += 1;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.flush += 1;
+        Ok(())
+    }
+}
+
+#[test]
+fn the_guards_forward_each_method_to_the_inner_writer() {
+    mutex::enable_raw_atomics();
+    let lock = RawSpinLock::new(Calls::default());
+    let rwlock = RwSpinLock::new(Calls::default());
+    write_greeting(lock.lock());
+    write_greeting(rwlock.write());
+    for calls in [lock.into_inner(), rwlock.into_inner()] {
+        // Without forwarding, `write_vectored` and `write_all` would come
+        // through as plain `write`s.
+        assert_eq!(calls.write_vectored, 1);
+        assert!(calls.write_all >= 1);
+        assert_eq!(calls.flush, 1);
+    }
+}
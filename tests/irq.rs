@@ -0,0 +1,180 @@
+//! The irqsave APIs against a recording `InterruptControl` hook.
+
+use std::cell::Cell;
+use std::cell::RefCell;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::thread;
+
+use mutex::RawSpinLock;
+use mutex::irq::InterruptControl;
+use mutex::state::MutexState;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Event {
+    /// Interrupts were disabled; whether the watched lock was held then.
+    Disable { locked: bool },
+    /// The given flags were restored; whether the watched lock was held then.
+    Restore { flags: usize, locked: bool },
+}
+
+thread_local! {
+    static EVENTS: RefCell<Vec<Event>> = const { RefCell::new(Vec::new()) };
+    static WATCHED: Cell<Option<&'static RawSpinLock<u32>>> = const { Cell::new(None) };
+    /// How many `save_and_disable` calls are outstanding on this thread.
+    static DEPTH: Cell<usize> = const { Cell::new(0) };
+    static COUNT_RESTORES: Cell<bool> = const { Cell::new(false) };
+}
+
+static RESTORES: AtomicUsize = AtomicUsize::new(0);
+
+struct Recorder;
+
+fn watched_is_locked() -> bool {
+    WATCHED
+        .get()
+        .is_some_and(|lock| lock.state() != MutexState::Unlocked)
+}
+
+impl InterruptControl for Recorder {
+    fn save_and_disable() -> usize {
+        let locked = watched_is_locked();
+        EVENTS.with_borrow_mut(|events| events.push(Event::Disable { locked }));
+        DEPTH.replace(DEPTH.get() + 1)
+    }
+
+    fn restore(flags: usize) {
+        if COUNT_RESTORES.get() {
+            RESTORES.fetch_add(1, Ordering::SeqCst);
+        }
+        let locked = watched_is_locked();
+        EVENTS.with_borrow_mut(|events| events.push(Event::Restore { flags, locked }));
+        DEPTH.set(flags);
+    }
+}
+
+/// Registers the hook once per binary and starts a fresh recording.
+fn record(lock: Option<&'static RawSpinLock<u32>>) {
+    mutex::enable_raw_atomics();
+    let _ = mutex::irq::set_interrupt_control::<Recorder>();
+    WATCHED.set(lock);
+    EVENTS.take();
+}
+
+fn events() -> Vec<Event> {
+    EVENTS.take()
+}
+
+#[test]
+fn disables_before_locking_and_restores_after_unlocking() {
+    static LOCK: RawSpinLock<u32> = RawSpinLock::new(0);
+    record(Some(&LOCK));
+    let mut guard = LOCK.lock_irqsave();
+    *guard += 1;
+    assert_eq!(events(), [Event::Disable { locked: false }]);
+    drop(guard);
+    assert_eq!(
+        events(),
+        [Event::Restore {
+            flags: 0,
+            locked: false
+        }]
+    );
+    assert_eq!(DEPTH.get(), 0);
+}
+
+#[test]
+fn nested_guards_restore_their_own_flags() {
+    static OUTER: RawSpinLock<u32> = RawSpinLock::new(0);
+    static INNER: RawSpinLock<u32> = RawSpinLock::new(0);
+    record(None);
+    let outer = OUTER.lock_irqsave();
+    let inner = INNER.lock_irqsave();
+    drop(inner);
+    assert_eq!(DEPTH.get(), 1);
+    drop(outer);
+    assert_eq!(DEPTH.get(), 0);
+    let restored: Vec<_> = events()
+        .into_iter()
+        .filter_map(|event| match event {
+            Event::Restore { flags, .. } => Some(flags),
+            Event::Disable { .. } => None,
+        })
+        .collect();
+    assert_eq!(restored, [1, 0]);
+}
+
+#[test]
+fn a_failed_try_lock_irqsave_restores_immediately() {
+    static LOCK: RawSpinLock<u32> = RawSpinLock::new(0);
+    record(Some(&LOCK));
+    let held = LOCK.lock();
+    assert!(LOCK.try_lock_irqsave().is_none());
+    assert_eq!(
+        events(),
+        [
+            Event::Disable { locked: true },
+            Event::Restore {
+                flags: 0,
+                locked: true
+            },
+        ]
+    );
+    drop(held);
+    let guard = LOCK.try_lock_irqsave().unwrap();
+    drop(guard);
+    assert_eq!(
+        events(),
+        [
+            Event::Disable { locked: false },
+            Event::Restore {
+                flags: 0,
+                locked: false
+            },
+        ]
+    );
+}
+
+#[test]
+fn spin_enabled_restores_between_attempts() {
+    static LOCK: RawSpinLock<u32> = RawSpinLock::new(0);
+    mutex::enable_raw_atomics();
+    let held = LOCK.lock();
+    let waiter = thread::spawn(|| {
+        record(Some(&LOCK));
+        COUNT_RESTORES.set(true);
+        drop(LOCK.lock_irqsave_spin_enabled());
+        COUNT_RESTORES.set(false);
+        events()
+    });
+    // Release only once the waiter has failed an attempt and restored the
+    // interrupt state to wait.
+    while RESTORES.load(Ordering::SeqCst) < 1 {
+        thread::yield_now();
+    }
+    drop(held);
+    let events = waiter.join().unwrap();
+    let (last, attempts) = events.split_last().unwrap();
+    assert_eq!(
+        *last,
+        Event::Restore {
+            flags: 0,
+            locked: false
+        }
+    );
+    // Every failed attempt restored the state before waiting.
+    for pair in attempts[..attempts.len() - 1].chunks(2) {
+        assert_eq!(
+            pair,
+            [
+                Event::Disable { locked: true },
+                Event::Restore {
+                    flags: 0,
+                    locked: true
+                },
+            ]
+        );
+    }
+    assert!(matches!(attempts.last(), Some(Event::Disable { .. })));
+    assert!(attempts.len() >= 3);
+}
@@ -0,0 +1,128 @@
+//! `SpinList` with nodes on the test's stack: pushing and popping at both
+//! ends, removing through a cursor, moving nodes with `append`, and a
+//! linked node unlinking itself on drop. Runs under Miri, which checks the
+//! links never dangle.
+
+use std::pin::pin;
+
+use mutex::list::ListNode;
+use mutex::list::SpinList;
+
+fn values(list: &SpinList<u32>) -> Vec<u32> {
+    list.lock().iter().copied().collect()
+}
+
+#[test]
+fn push_and_pop_at_both_ends() {
+    mutex::enable_raw_atomics();
+    let list = SpinList::new();
+    let a = pin!(ListNode::new(1));
+    let b = pin!(ListNode::new(2));
+    let c = pin!(ListNode::new(3));
+    {
+        let mut guard = list.lock();
+        guard.push_back(a.as_ref());
+        guard.push_back(b.as_ref());
+        guard.push_front(c.as_ref());
+        assert_eq!(guard.len(), 3);
+    }
+    assert_eq!(values(&list), [3, 1, 2]);
+    let mut guard = list.lock();
+    assert_eq!(guard.pop_front(), Some(&3));
+    assert_eq!(guard.pop_back(), Some(&2));
+    assert_eq!(guard.pop_back(), Some(&1));
+    assert_eq!(guard.pop_front(), None);
+    assert!(guard.is_empty());
+    // Popped nodes stay assigned to the list and can be pushed again.
+    guard.push_back(b.as_ref());
+    assert_eq!(guard.iter().copied().collect::<Vec<_>>(), [2]);
+    drop(guard);
+    assert!(b.as_ref().unlink());
+    assert!(!a.as_ref().unlink());
+    assert!(list.lock().is_empty());
+}
+
+#[test]
+fn a_cursor_removes_while_walking() {
+    mutex::enable_raw_atomics();
+    let list = SpinList::new();
+    let nodes = [
+        pin!(ListNode::new(1)),
+        pin!(ListNode::new(2)),
+        pin!(ListNode::new(3)),
+        pin!(ListNode::new(4)),
+    ];
+    let mut guard = list.lock();
+    for node in &nodes {
+        guard.push_back(node.as_ref());
+    }
+    let mut removed = Vec::new();
+    let mut cursor = guard.cursor_front();
+    while let Some(&value) = cursor.current() {
+        if value % 2 == 0 {
+            removed.push(*cursor.remove_current().unwrap());
+        } else {
+            cursor.move_next();
+        }
+    }
+    assert_eq!(removed, [2, 4]);
+    assert_eq!(guard.iter().copied().collect::<Vec<_>>(), [1, 3]);
+    assert_eq!(guard.len(), 2);
+}
+
+#[test]
+fn append_moves_nodes_to_the_other_list() {
+    mutex::enable_raw_atomics();
+    let first = SpinList::new();
+    let second = SpinList::new();
+    let a = pin!(ListNode::new(1));
+    let b = pin!(ListNode::new(2));
+    let c = pin!(ListNode::new(3));
+    {
+        let (mut to, mut from) = (first.lock(), second.lock());
+        to.push_back(a.as_ref());
+        from.push_back(b.as_ref());
+        from.push_back(c.as_ref());
+        to.append(&mut from);
+        assert!(from.is_empty());
+        assert_eq!(to.len(), 3);
+    }
+    assert_eq!(values(&first), [1, 2, 3]);
+    // The moved nodes now belong to `first`: `second` cannot remove them,
+    // and unlinking one takes it off `first`.
+    assert!(!second.lock().remove(b.as_ref()));
+    assert!(c.as_ref().unlink());
+    assert_eq!(values(&first), [1, 2]);
+    assert!(first.lock().remove(b.as_ref()));
+    assert_eq!(values(&first), [1]);
+}
+
+#[test]
+fn dropping_a_linked_node_unlinks_it() {
+    mutex::enable_raw_atomics();
+    let list = SpinList::new();
+    let a = pin!(ListNode::new(1));
+    {
+        let b = pin!(ListNode::new(2));
+        let c = pin!(ListNode::new(3));
+        let mut guard = list.lock();
+        guard.push_back(a.as_ref());
+        guard.push_back(b.as_ref());
+        guard.push_back(c.as_ref());
+        drop(guard);
+        assert_eq!(values(&list), [1, 2, 3]);
+    }
+    assert_eq!(values(&list), [1]);
+    assert_eq!(list.lock().len(), 1);
+}
+
+#[test]
+#[should_panic = "node is already linked"]
+fn pushing_a_linked_node_again_panics() {
+    mutex::enable_raw_atomics();
+    let list = SpinList::new();
+    let a = pin!(ListNode::new(1));
+    let mut guard = list.lock();
+    guard.push_back(a.as_ref());
+    guard.push_front(a.as_ref());
+}
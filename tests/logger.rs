@@ -0,0 +1,120 @@
+//! `SpinLogger` writing to a shared byte buffer from several threads.
+
+#![cfg(feature = "log")]
+
+use std::fmt;
+use std::sync::Mutex;
+use std::thread;
+
+use log::Level;
+use log::LevelFilter;
+use log::Log;
+use log::Record;
+use mutex::logger::SpinLogger;
+
+/// Appends every `write_str` chunk separately, so a record written without
+/// the logger's lock would interleave with others mid-line.
+struct Sink(&'static Mutex<Vec<u8>>);
+
+impl fmt::Write for Sink {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for byte in s.bytes() {
+            self.0.lock().unwrap().push(byte);
+        }
+        Ok(())
+    }
+}
+
+fn log(logger: &dyn Log, level: Level, args: fmt::Arguments<'_>) {
+    logger.log(
+        &Record::builder()
+            .level(level)
+            .target("test")
+            .args(args)
+            .build(),
+    );
+}
+
+fn lines(buffer: &Mutex<Vec<u8>>) -> Vec<String> {
+    String::from_utf8(buffer.lock().unwrap().clone())
+        .unwrap()
+        .lines()
+        .map(str::to_owned)
+        .collect()
+}
+
+#[test]
+fn lines_from_several_threads_never_interleave() {
+    mutex::enable_raw_atomics();
+    static BUFFER: Mutex<Vec<u8>> = Mutex::new(Vec::new());
+    static LOGGER: SpinLogger<Sink> = SpinLogger::new(Sink(&BUFFER));
+    const RECORDS: usize = 200;
+    let threads: Vec<_> = (0..4)
+        .map(|id| {
+            thread::spawn(move || {
+                for i in 0..RECORDS {
+                    log(&LOGGER, Level::Info, format_args!("thread {id} record {i}"));
+                }
+            })
+        })
+        .collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    let lines = lines(&BUFFER);
+    for line in &lines {
+        let (id, i) = line
+            .strip_prefix("[INFO] test: thread ")
+            .and_then(|rest| rest.split_once(" record "))
+            .unwrap_or_else(|| panic!("interleaved line {line:?}"));
+        assert!(id.parse::<usize>().unwrap() < 4);
+        assert!(i.parse::<usize>().unwrap() < RECORDS);
+    }
+    // Records that met a busy writer were counted instead of written.
+    assert_eq!(lines.len() + LOGGER.lost_messages(), 4 * RECORDS);
+}
+
+/// Logs again from inside `write_str`, like an interrupt handler that
+/// preempted a log call on the same core.
+struct Reentrant(&'static SpinLogger<Reentrant>, bool);
+
+impl fmt::Write for Reentrant {
+    fn write_str(&mut self, _: &str) -> fmt::Result {
+        if !self.1 {
+            self.1 = true;
+            log(self.0, Level::Warn, format_args!("from the handler"));
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn a_reentrant_record_is_dropped_and_counted() {
+    mutex::enable_raw_atomics();
+    static LOGGER: SpinLogger<Reentrant> = SpinLogger::new(Reentrant(&LOGGER, false));
+    log(&LOGGER, Level::Info, format_args!("outer"));
+    assert_eq!(LOGGER.lost_messages(), 1);
+}
+
+#[test]
+fn records_above_the_max_level_are_filtered() {
+    static BUFFER: Mutex<Vec<u8>> = Mutex::new(Vec::new());
+    static LOGGER: SpinLogger<Sink> =
+        SpinLogger::new(Sink(&BUFFER)).with_max_level(LevelFilter::Warn);
+    log(&LOGGER, Level::Debug, format_args!("hidden"));
+    log(&LOGGER, Level::Error, format_args!("shown"));
+    assert_eq!(lines(&BUFFER), ["[ERROR] test: shown"]);
+    assert_eq!(LOGGER.lost_messages(), 0);
+}
+
+#[test]
+fn set_as_logger_installs_the_level_filter() {
+    static BUFFER: Mutex<Vec<u8>> = Mutex::new(Vec::new());
+    static LOGGER: SpinLogger<Sink> =
+        SpinLogger::new(Sink(&BUFFER)).with_max_level(LevelFilter::Info);
+    LOGGER.set_as_logger().unwrap();
+    assert_eq!(log::max_level(), LevelFilter::Info);
+    log::debug!(target: "test", "hidden");
+    log::info!(target: "test", "through the facade");
+    assert_eq!(lines(&BUFFER), ["[INFO] test: through the facade"]);
+}
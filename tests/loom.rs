@@ -0,0 +1,144 @@
+//! Loom models of the locks' own atomics. Run them with
+//! `RUSTFLAGS="--cfg loom" cargo test --release --test loom`.
+//!
+//! The protected data is a loom `UnsafeCell`, so loom reports any access
+//! that is not ordered after the previous holder's, not just lost updates.
+//! Every lock is created and used once inside the model before it is
+//! shared; see the `atomic` module docs.
+
+#![cfg(loom)]
+
+use loom::cell::UnsafeCell;
+use loom::sync::Arc;
+use loom::thread;
+
+use mutex::RawSpinLock;
+use mutex::RwSpinLock;
+
+/// Two threads increment through a `RawSpinLock`; each access must be
+/// ordered after the previous holder's, and neither update is lost.
+#[test]
+fn raw_spin_lock_excludes() {
+    mutex::enable_raw_atomics();
+    loom::model(|| {
+        let lock = Arc::new(RawSpinLock::new(UnsafeCell::new(0)));
+        drop(lock.lock());
+        let other = {
+            let lock = lock.clone();
+            thread::spawn(move || lock.lock().with_mut(|value| unsafe { *value += 1 }))
+        };
+        lock.lock().with_mut(|value| unsafe { *value += 1 });
+        other.join().unwrap();
+        assert_eq!(lock.lock().with(|value| unsafe { *value }), 2);
+    });
+}
+
+/// Increments under `try_lock`; `false` if the lock was held.
+fn bump(lock: &RawSpinLock<UnsafeCell<usize>>) -> bool {
+    lock.try_lock()
+        .map(|guard| guard.with_mut(|value| unsafe { *value += 1 }))
+        .is_some()
+}
+
+/// `try_lock` either gets the lock, ordered after the last release, or
+/// fails while the other thread holds it.
+#[test]
+fn raw_spin_lock_try_lock_excludes() {
+    mutex::enable_raw_atomics();
+    loom::model(|| {
+        let lock = Arc::new(RawSpinLock::new(UnsafeCell::new(0)));
+        drop(lock.lock());
+        let other = {
+            let lock = lock.clone();
+            thread::spawn(move || bump(&lock))
+        };
+        let mine = bump(&lock);
+        let theirs = other.join().unwrap();
+        let expected = usize::from(mine) + usize::from(theirs);
+        assert!(expected > 0);
+        assert_eq!(lock.lock().with(|value| unsafe { *value }), expected);
+    });
+}
+
+/// What a holder writes before its guard drops is what the next holder
+/// reads, for a guard dropped on another thread than the reader's.
+#[test]
+fn guard_drop_publishes_the_data() {
+    mutex::enable_raw_atomics();
+    loom::model(|| {
+        let lock = Arc::new(RawSpinLock::new(UnsafeCell::new((0, 0))));
+        drop(lock.lock());
+        let writer = {
+            let lock = lock.clone();
+            thread::spawn(move || {
+                let guard = lock.lock();
+                guard.with_mut(|pair| unsafe { *pair = (1, 1) });
+                drop(guard);
+            })
+        };
+        let (a, b) = lock.lock().with(|pair| unsafe { *pair });
+        assert_eq!(a, b);
+        writer.join().unwrap();
+    });
+}
+
+/// Two writers through `RwSpinLock::write` exclude each other.
+#[test]
+fn rw_spin_lock_writers_exclude() {
+    mutex::enable_raw_atomics();
+    loom::model(|| {
+        let lock = Arc::new(RwSpinLock::new(UnsafeCell::new(0)));
+        drop(lock.write());
+        let other = {
+            let lock = lock.clone();
+            thread::spawn(move || lock.write().with_mut(|value| unsafe { *value += 1 }))
+        };
+        lock.write().with_mut(|value| unsafe { *value += 1 });
+        other.join().unwrap();
+        assert_eq!(lock.read().with(|value| unsafe { *value }), 2);
+    });
+}
+
+/// The try variants: a reader and a writer are never inside together.
+#[test]
+fn rw_spin_lock_try_read_and_try_write_exclude() {
+    mutex::enable_raw_atomics();
+    loom::model(|| {
+        let lock = Arc::new(RwSpinLock::new(UnsafeCell::new(0)));
+        drop(lock.write());
+        let reader = {
+            let lock = lock.clone();
+            thread::spawn(move || {
+                let guard = lock.try_read()?;
+                Some(guard.with(|value| unsafe { *value }))
+            })
+        };
+        let wrote = lock
+            .try_write()
+            .map(|guard| guard.with_mut(|value| unsafe { *value = 1 }))
+            .is_some();
+        let seen = reader.join().unwrap();
+        assert!(seen.is_none_or(|seen| seen <= usize::from(wrote)));
+    });
+}
+
+/// One reader inside the lock while a writer sets `WRITE_FLAG` and waits
+/// for it to drain: the writer's access must be ordered after the reader's.
+#[test]
+fn writer_waits_for_the_reader_to_drain() {
+    mutex::enable_raw_atomics();
+    loom::model(|| {
+        let lock = Arc::new(RwSpinLock::new(UnsafeCell::new(0)));
+        drop(lock.read());
+        let reader = {
+            let lock = lock.clone();
+            thread::spawn(move || {
+                let guard = lock.read();
+                guard.with(|value| unsafe { *value })
+            })
+        };
+        lock.write().with_mut(|value| unsafe { *value = 1 });
+        let seen = reader.join().unwrap();
+        assert!(seen == 0 || seen == 1);
+    });
+}
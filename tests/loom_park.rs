@@ -0,0 +1,72 @@
+//! A loom model of a waiter blocking through a registered [`Parker`] and
+//! the holder's unlock waking it. In a binary of its own since the parker
+//! is registered once per process. Run it with
+//! `RUSTFLAGS="--cfg loom" cargo test --release --test loom_park`.
+
+#![cfg(loom)]
+
+use loom::cell::UnsafeCell;
+use loom::sync::Arc;
+use loom::sync::Mutex;
+use loom::thread;
+use loom::thread::Thread;
+
+use mutex::RawSpinLock;
+use mutex::park::Parker;
+
+loom::lazy_static! {
+    /// Created afresh for every execution of the model.
+    static ref WAITERS: Mutex<Vec<(usize, Thread)>> = Mutex::new(Vec::new());
+}
+
+/// A parker on loom's `park` and `unpark`, keeping its queue in the model.
+struct LoomParker;
+
+impl Parker for LoomParker {
+    fn park(addr: usize, validate: &dyn Fn() -> bool) {
+        let me = thread::current();
+        WAITERS.lock().unwrap().push((addr, me.clone()));
+        if validate() {
+            thread::park();
+        }
+        WAITERS
+            .lock()
+            .unwrap()
+            .retain(|(_, waiter)| waiter.id() != me.id());
+    }
+
+    fn unpark_one(addr: usize) {
+        let mut waiters = WAITERS.lock().unwrap();
+        if let Some(index) = waiters.iter().position(|&(waiting, _)| waiting == addr) {
+            waiters.remove(index).1.unpark();
+        }
+    }
+}
+
+/// A waiter that parks behind the holder is woken by its unlock; a lost
+/// wakeup leaves loom with a deadlocked model, which it reports.
+#[test]
+fn unlock_wakes_a_parked_waiter() {
+    mutex::enable_raw_atomics();
+    mutex::park::set_parker::<LoomParker>().unwrap();
+    let mut model = loom::model::Builder::new();
+    model.preemption_bound = Some(2);
+    model.check(|| {
+        let lock = Arc::new(RawSpinLock::new(UnsafeCell::new(0)));
+        drop(lock.lock());
+        let guard = lock.lock();
+        let waiter = {
+            let lock = lock.clone();
+            thread::spawn(move || lock.lock().with_mut(|value| unsafe { *value += 1 }))
+        };
+        guard.with_mut(|value| unsafe { *value += 1 });
+        // Hold the lock until the waiter has spun out and queued itself,
+        // so the unlock races with its `validate` and `park`.
+        while WAITERS.lock().unwrap().is_empty() {
+            thread::yield_now();
+        }
+        drop(guard);
+        waiter.join().unwrap();
+        assert_eq!(lock.lock().with(|value| unsafe { *value }), 2);
+    });
+}
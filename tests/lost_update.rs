@@ -0,0 +1,203 @@
+//! N threads each make M guarded updates through every exclusive lock, and
+//! the result must account for all of them: a plain counter must reach
+//! exactly N * M, and a multi-field record whose fields check each other
+//! must never be seen half-written.
+//!
+//! With the `chaos` feature the same updates run with random delays around
+//! every lock transition: `cargo test-chaos`.
+
+use std::hint::black_box;
+use std::thread;
+
+use mutex::RawSpinLock;
+use mutex::RwSpinLock;
+use mutex::biased::BiasedSpinLock;
+use mutex::cohort::CohortLock;
+use mutex::hybrid::HybridMutex;
+use mutex::mutex::Lock;
+use mutex::padded::SplitSpinLock;
+use mutex::versioned::VersionedSpinLock;
+
+/// Kept small under Miri and in debug builds so the suite stays quick.
+const THREADS: usize = if cfg!(miri) { 2 } else { 8 };
+const UPDATES: usize = if cfg!(miri) {
+    20
+} else if cfg!(debug_assertions) {
+    2_000
+} else {
+    20_000
+};
+
+/// Fields that are only consistent with each other between updates.
+struct Record {
+    count: usize,
+    double: usize,
+    mixed: usize,
+    sum: usize,
+}
+
+impl Record {
+    fn new() -> Self {
+        Self {
+            count: 0,
+            double: 0,
+            mixed: 0x5a5a,
+            sum: 0x5a5a,
+        }
+    }
+
+    fn update(&mut self) {
+        assert!(self.is_consistent(), "saw a torn record");
+        let count = self.count + 1;
+        // Written one field at a time, with the record inconsistent in
+        // between, for another thread to catch.
+        self.count = black_box(count);
+        self.double = black_box(count * 2);
+        self.mixed = black_box(count ^ 0x5a5a);
+        self.sum = count + count * 2 + (count ^ 0x5a5a);
+    }
+
+    fn is_consistent(&self) -> bool {
+        self.double == self.count * 2
+            && self.mixed == self.count ^ 0x5a5a
+            && self.sum == self.count + self.double + self.mixed
+    }
+}
+
+/// Enables raw atomics, and the chaos delays where the feature is on.
+fn start() {
+    mutex::enable_raw_atomics();
+    #[cfg(feature = "chaos")]
+    mutex::chaos::configure(0x5eed, 64);
+}
+
+fn hammer(update: impl Fn() + Sync) {
+    thread::scope(|s| {
+        for _ in 0..THREADS {
+            s.spawn(|| {
+                for _ in 0..UPDATES {
+                    update();
+                }
+            });
+        }
+    });
+}
+
+/// Both checks for a lock built by `new`.
+fn check<L: Lock<usize> + Sync, R: Lock<Record> + Sync>(
+    counter: L,
+    record: R,
+    into_count: impl FnOnce(L) -> usize,
+    into_record: impl FnOnce(R) -> Record,
+) {
+    start();
+    hammer(|| *counter.lock() += 1);
+    assert_eq!(into_count(counter), THREADS * UPDATES);
+    hammer(|| record.lock().update());
+    let record = into_record(record);
+    assert!(record.is_consistent());
+    assert_eq!(record.count, THREADS * UPDATES);
+}
+
+#[test]
+fn raw_spin_lock() {
+    check(
+        RawSpinLock::new(0),
+        RawSpinLock::new(Record::new()),
+        RawSpinLock::into_inner,
+        RawSpinLock::into_inner,
+    );
+}
+
+#[test]
+fn split_spin_lock() {
+    check(
+        SplitSpinLock::new(0),
+        SplitSpinLock::new(Record::new()),
+        SplitSpinLock::into_inner,
+        SplitSpinLock::into_inner,
+    );
+}
+
+#[test]
+fn cohort_lock() {
+    check(
+        CohortLock::<_, 2>::new(0),
+        CohortLock::<_, 2>::new(Record::new()),
+        CohortLock::into_inner,
+        CohortLock::into_inner,
+    );
+}
+
+#[test]
+fn biased_spin_lock() {
+    start();
+    let counter = BiasedSpinLock::new(0);
+    let record = BiasedSpinLock::new(Record::new());
+    // Every thread claims the bias of both locks first, so the others keep
+    // revoking it from each other.
+    thread::scope(|s| {
+        for _ in 0..THREADS {
+            s.spawn(|| {
+                assert!(counter.claim_bias() && record.claim_bias());
+                for _ in 0..UPDATES {
+                    *counter.lock() += 1;
+                    record.lock().update();
+                }
+                counter.revoke_bias();
+                record.revoke_bias();
+            });
+        }
+    });
+    assert_eq!(counter.into_inner(), THREADS * UPDATES);
+    let record = record.into_inner();
+    assert!(record.is_consistent());
+    assert_eq!(record.count, THREADS * UPDATES);
+}
+
+#[test]
+fn hybrid_mutex() {
+    check(
+        HybridMutex::new(0),
+        HybridMutex::new(Record::new()),
+        HybridMutex::into_inner,
+        HybridMutex::into_inner,
+    );
+}
+
+#[test]
+fn versioned_spin_lock() {
+    check(
+        VersionedSpinLock::new(0),
+        VersionedSpinLock::new(Record::new()),
+        VersionedSpinLock::into_inner,
+        VersionedSpinLock::into_inner,
+    );
+}
+
+/// Only the counter, since the packed value must fit beside the lock bit.
+#[cfg(target_has_atomic = "64")]
+#[test]
+fn small_spin_lock() {
+    start();
+    let counter = mutex::small::SmallSpinLock::new(0u32);
+    hammer(|| *counter.lock() += 1);
+    assert_eq!(counter.into_inner() as usize, THREADS * UPDATES);
+}
+
+#[test]
+fn rw_spin_lock_write_path() {
+    start();
+    let counter = RwSpinLock::new(0);
+    hammer(|| *counter.write() += 1);
+    assert_eq!(counter.into_inner(), THREADS * UPDATES);
+    let record = RwSpinLock::new(Record::new());
+    // Readers check the record between writes.
+    hammer(|| {
+        record.write().update();
+        assert!(record.read().is_consistent(), "saw a torn record");
+    });
+    let record = record.into_inner();
+    assert!(record.is_consistent());
+    assert_eq!(record.count, THREADS * UPDATES);
+}
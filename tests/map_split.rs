@@ -0,0 +1,88 @@
+//! `RawSpinLockGuard::map_split` halves used by separate functions.
+
+#![cfg(feature = "alloc")]
+
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::thread;
+use std::time::Duration;
+
+use mutex::RawSpinLock;
+use mutex::RawSpinLockGuard;
+use mutex::state::MutexState;
+use mutex::sync::MappedSpinLockGuard;
+
+struct Rings {
+    tx: Vec<u8>,
+    rx: Vec<u8>,
+}
+
+fn send(tx: &mut Vec<u8>, byte: u8) {
+    tx.push(byte);
+}
+
+fn receive(rx: &mut Vec<u8>) -> Option<u8> {
+    rx.pop()
+}
+
+type Half<'a> = MappedSpinLockGuard<'a, Vec<u8>>;
+
+fn split(lock: &RawSpinLock<Rings>) -> (Half<'_>, Half<'_>) {
+    RawSpinLockGuard::map_split(lock.lock(), |rings| (&mut rings.tx, &mut rings.rx))
+}
+
+fn held(lock: &RawSpinLock<Rings>) -> bool {
+    lock.state() != MutexState::Unlocked
+}
+
+#[test]
+fn the_lock_is_released_after_the_second_half_in_either_order() {
+    mutex::enable_raw_atomics();
+    let lock = RawSpinLock::new(Rings {
+        tx: Vec::new(),
+        rx: vec![7],
+    });
+    for tx_first in [true, false] {
+        let (mut tx, mut rx) = split(&lock);
+        send(&mut tx, 1);
+        assert_eq!(receive(&mut rx), if tx_first { Some(7) } else { None });
+        if tx_first {
+            drop(tx);
+            assert!(held(&lock));
+            assert!(lock.try_lock().is_none());
+            drop(rx);
+        } else {
+            drop(rx);
+            assert!(held(&lock));
+            assert!(lock.try_lock().is_none());
+            drop(tx);
+        }
+        assert!(!held(&lock));
+    }
+    let rings = lock.into_inner();
+    assert_eq!((rings.tx, rings.rx), (vec![1, 1], vec![]));
+}
+
+#[test]
+fn another_thread_waits_for_the_last_half() {
+    mutex::enable_raw_atomics();
+    let lock = RawSpinLock::new(Rings {
+        tx: Vec::new(),
+        rx: Vec::new(),
+    });
+    let entered = AtomicBool::new(false);
+    thread::scope(|s| {
+        let (tx, rx) = split(&lock);
+        let waiter = s.spawn(|| {
+            lock.lock().rx.push(2);
+            entered.store(true, Ordering::SeqCst);
+        });
+        drop(tx);
+        thread::sleep(Duration::from_millis(50));
+        assert!(!entered.load(Ordering::SeqCst));
+        drop(rx);
+        waiter.join().unwrap();
+    });
+    assert!(entered.load(Ordering::SeqCst));
+    assert_eq!(lock.into_inner().rx, [2]);
+}
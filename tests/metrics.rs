@@ -0,0 +1,114 @@
+//! Lock events as a metrics sink sees them, collected into a `Vec` and
+//! filtered to each test's own locks, since the sink is process-wide. The
+//! locks are `static`s, so no two tests share an address.
+
+#![cfg(feature = "metrics")]
+
+use std::sync::Mutex;
+use std::sync::Once;
+use std::thread;
+use std::time::Duration;
+
+use mutex::RawSpinLock;
+use mutex::clock;
+use mutex::clock::StdClock;
+use mutex::metrics;
+use mutex::metrics::LockId;
+use mutex::metrics::MetricsSink;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Event {
+    Acquire { contended: bool, spins: u32 },
+    Release { held_ns: u64 },
+}
+
+struct VecSink(Mutex<Vec<(usize, Event)>>);
+
+impl MetricsSink for VecSink {
+    fn on_acquire(&self, lock: &LockId, contended: bool, spins: u32) {
+        let event = Event::Acquire { contended, spins };
+        self.0.lock().unwrap().push((lock.addr(), event));
+    }
+
+    fn on_release(&self, lock: &LockId, held_ns: u64) {
+        let event = Event::Release { held_ns };
+        self.0.lock().unwrap().push((lock.addr(), event));
+    }
+}
+
+static SINK: VecSink = VecSink(Mutex::new(Vec::new()));
+
+fn setup() {
+    static SETUP: Once = Once::new();
+    SETUP.call_once(|| {
+        mutex::enable_raw_atomics();
+        clock::set_clock::<StdClock>().unwrap();
+        metrics::set_metrics_sink(&SINK).unwrap();
+    });
+}
+
+/// The events reported for `lock`, whose word lies within it.
+fn events_for<T>(lock: &'static RawSpinLock<T>) -> Vec<Event> {
+    let start = std::ptr::from_ref(lock) as usize;
+    let range = start..start + size_of_val(lock);
+    let events = SINK.0.lock().unwrap();
+    events
+        .iter()
+        .filter(|(addr, _)| range.contains(addr))
+        .map(|&(_, event)| event)
+        .collect()
+}
+
+#[test]
+fn each_hold_reports_an_acquire_then_a_release() {
+    setup();
+    static LOCK: RawSpinLock<u32> = RawSpinLock::new(0);
+    let lock = &LOCK;
+    *lock.lock() += 1;
+    {
+        let mut guard = lock.try_lock().unwrap();
+        *guard += 1;
+        thread::sleep(Duration::from_millis(5));
+    }
+    let events = events_for(lock);
+    let [
+        first,
+        Event::Release { .. },
+        second,
+        Event::Release { held_ns },
+    ] = events[..]
+    else {
+        panic!("{events:?}");
+    };
+    let uncontended = Event::Acquire {
+        contended: false,
+        spins: 0,
+    };
+    assert_eq!((first, second), (uncontended, uncontended));
+    assert!(held_ns >= 5_000_000, "held for {held_ns}ns");
+}
+
+#[test]
+fn a_contended_acquisition_reports_its_spins() {
+    setup();
+    static LOCK: RawSpinLock<u32> = RawSpinLock::new(0);
+    let lock = &LOCK;
+    let held = lock.lock();
+    thread::scope(|s| {
+        let waiter = s.spawn(|| *lock.lock() += 1);
+        thread::sleep(Duration::from_millis(20));
+        drop(held);
+        waiter.join().unwrap();
+    });
+    let events = events_for(lock);
+    let [
+        _,
+        _,
+        Event::Acquire { contended, spins },
+        Event::Release { .. },
+    ] = events[..]
+    else {
+        panic!("{events:?}");
+    };
+    assert!(contended && spins > 0, "{events:?}");
+}
@@ -0,0 +1,129 @@
+//! The unsafe paths, in tests small enough for Miri:
+//! `cargo +nightly miri test --test miri --test miri_bringup`. They run
+//! under plain `cargo test` as well.
+//!
+//! `examples/permissive_corruption.rs` races on purpose and is expected to
+//! fail under Miri; it is an example, not a test, so it stays out of these.
+
+use std::mem::MaybeUninit;
+use std::thread;
+
+use mutex::RawSpinLock;
+use mutex::RawSpinLockGuard;
+use mutex::RwSpinLock;
+use mutex::RwSpinLockReadGuard;
+
+#[test]
+fn no_lock_on_one_thread() {
+    mutex::enable_raw_atomics();
+    let lock = RawSpinLock::new(vec![1, 2]);
+    {
+        // SAFETY: nothing else can reach the lock.
+        let mut guard = unsafe { lock.no_lock() };
+        guard.push(3);
+    }
+    // SAFETY: as above.
+    assert_eq!(*unsafe { lock.no_lock_read() }, [1, 2, 3]);
+    assert_eq!(*lock.lock(), [1, 2, 3]);
+}
+
+#[test]
+fn guard_create_and_drop_cycles() {
+    mutex::enable_raw_atomics();
+    let lock = RawSpinLock::new(String::new());
+    let rw = RwSpinLock::new(0);
+    for i in 0..10 {
+        lock.lock().push('x');
+        assert!(lock.try_lock().is_some());
+        *rw.write() += i;
+        let (a, b) = (rw.read(), rw.try_read().unwrap());
+        assert_eq!(*a, *b);
+    }
+    assert_eq!(lock.into_inner().len(), 10);
+    assert_eq!(rw.into_inner(), 45);
+}
+
+#[test]
+fn into_inner_and_get_mut() {
+    let mut lock = RawSpinLock::new(Box::new(1));
+    **lock.get_mut() += 1;
+    assert_eq!(*lock.into_inner(), 2);
+    let mut rw = RwSpinLock::new(Box::new(1));
+    **rw.get_mut() += 1;
+    assert_eq!(*rw.into_inner(), 2);
+}
+
+#[test]
+fn mapped_guards() {
+    mutex::enable_raw_atomics();
+    let lock = RawSpinLock::new((String::from("a"), vec![0]));
+    {
+        let mut name = RawSpinLockGuard::map(lock.lock(), |pair| &mut pair.0);
+        name.push('b');
+        assert!(lock.try_lock().is_none());
+    }
+    {
+        let (mut name, mut items) =
+            RawSpinLockGuard::map_split(lock.lock(), |pair| (&mut pair.0, &mut pair.1));
+        name.push('c');
+        items.push(1);
+        drop(name);
+        assert!(lock.try_lock().is_none());
+    }
+    assert_eq!(*lock.lock(), (String::from("abc"), vec![0, 1]));
+    let rw = RwSpinLock::new((1, String::from("x")));
+    let name = RwSpinLockReadGuard::map(rw.read(), |pair| &pair.1);
+    assert_eq!(*name, "x");
+    assert!(rw.try_write().is_none());
+}
+
+#[test]
+fn init_in_place_and_from_raw() {
+    mutex::enable_raw_atomics();
+    let mut slot = MaybeUninit::<RawSpinLock<Vec<u8>>>::uninit();
+    // SAFETY: `slot` is valid for writes, aligned, and not shared yet.
+    unsafe { RawSpinLock::init_in_place(slot.as_mut_ptr(), vec![7]) };
+    // SAFETY: initialized just above, and `slot` outlives the borrow.
+    let lock = unsafe { RawSpinLock::from_raw(slot.as_ptr()) };
+    thread::scope(|s| {
+        s.spawn(|| lock.lock().push(8));
+        s.spawn(|| lock.lock().push(9));
+    });
+    assert_eq!(lock.lock().len(), 3);
+    // SAFETY: initialized, and the borrows above have ended.
+    drop(unsafe { slot.assume_init() });
+}
+
+#[test]
+fn split_arc_halves_reunite() {
+    mutex::enable_raw_atomics();
+    let (a, b) = RawSpinLock::new(vec![0]).split_arc();
+    let b = thread::spawn(move || {
+        b.lock().push(1);
+        b
+    })
+    .join()
+    .unwrap();
+    a.lock().push(2);
+    let mut value = a.try_reunite(b).unwrap();
+    value.sort_unstable();
+    assert_eq!(value, [0, 1, 2]);
+}
+
+#[test]
+fn debug_races_a_locker() {
+    mutex::enable_raw_atomics();
+    let lock = RawSpinLock::new(0);
+    thread::scope(|s| {
+        s.spawn(|| {
+            for _ in 0..5 {
+                *lock.lock() += 1;
+            }
+        });
+        for _ in 0..5 {
+            let shown = format!("{lock:?}");
+            assert!(shown.starts_with("RawSpinLock"));
+        }
+    });
+    assert_eq!(lock.into_inner(), 5);
+}
@@ -0,0 +1,35 @@
+//! Locking in permissive mode, enabling raw atomics, then locking from two
+//! scoped threads; in a binary of its own so nothing has enabled raw
+//! atomics before it starts. See `tests/miri.rs`.
+
+use std::thread;
+
+use mutex::RawSpinLock;
+use mutex::RwSpinLock;
+
+#[test]
+fn enable_then_lock_across_threads() {
+    let lock = RawSpinLock::new(Vec::new());
+    let rw = RwSpinLock::new(0);
+    lock.lock().push(0);
+    *rw.write() += 1;
+    // SAFETY: nothing else can reach the lock yet.
+    unsafe { lock.no_lock() }.push(1);
+
+    // Thread start orders the permissive writes before the threads' reads.
+    mutex::enable_raw_atomics();
+    thread::scope(|s| {
+        for i in 2..4 {
+            let (lock, rw) = (&lock, &rw);
+            s.spawn(move || {
+                lock.lock().push(i);
+                *rw.write() += 1;
+                assert!(*rw.read() >= 2);
+            });
+        }
+    });
+    let mut values = lock.into_inner();
+    values.sort_unstable();
+    assert_eq!(values, [0, 1, 2, 3]);
+    assert_eq!(rw.into_inner(), 3);
+}
@@ -0,0 +1,88 @@
+//! `try_lock_any` and `lock_any` under contention scripted with `MockLock`,
+//! on one thread.
+
+#![cfg(feature = "testing")]
+
+use mutex::any;
+use mutex::mock::Call;
+use mutex::mock::Event;
+use mutex::mock::MockLock;
+
+fn queues() -> [MockLock<u32>; 3] {
+    std::array::from_fn(|_| MockLock::new(0))
+}
+
+#[test]
+fn a_pass_skips_locks_whose_try_lock_fails() {
+    let queues = queues();
+    let locks: Vec<_> = queues.iter().collect();
+    queues[0].fail_next_try_locks(1);
+    let (index, guard) = any::try_lock_any_from(&locks, 0).unwrap();
+    assert_eq!(index, 1);
+    drop(guard);
+    queues[0].assert_failed_times(1);
+    queues[0].assert_acquired_times(0);
+    queues[1].assert_acquired_times(1);
+    queues[2].assert_acquired_times(0);
+    queues.iter().for_each(MockLock::assert_not_held);
+}
+
+#[test]
+fn a_failed_pass_tries_each_lock_once() {
+    let queues = queues();
+    let locks: Vec<_> = queues.iter().collect();
+    for queue in &queues {
+        queue.fail_next_try_locks(1);
+    }
+    assert!(any::try_lock_any_from(&locks, 2).is_none());
+    for queue in &queues {
+        queue.assert_failed_times(1);
+        queue.assert_acquired_times(0);
+    }
+    // The failures are reported at the call, not inside `any`.
+    let Event::Failed { call, at } = queues[0].history()[0] else {
+        panic!("{:?}", queues[0].history());
+    };
+    assert_eq!(call, Call::TryLock);
+    assert_eq!(at.file(), file!());
+}
+
+#[test]
+fn lock_any_retries_whole_passes_until_one_succeeds() {
+    let queues = queues();
+    let locks: Vec<_> = queues.iter().collect();
+    queues[0].fail_next_try_locks(3);
+    queues[1].fail_next_try_locks(2);
+    queues[2].fail_next_try_locks(3);
+    let (index, _guard) = any::lock_any_from(&locks, 0);
+    // Every pass starts again at the rotation index, so the third one
+    // fails on the first lock before getting the second.
+    assert_eq!(index, 1);
+    queues[0].assert_failed_times(3);
+    queues[1].assert_failed_times(2);
+    queues[1].assert_acquired_times(1);
+    queues[2].assert_failed_times(2);
+}
+
+#[test]
+fn a_delayed_acquisition_moves_a_pass_on() {
+    let queues = queues();
+    let locks: Vec<_> = queues.iter().collect();
+    queues[0].delay_acquisition(2);
+    assert_eq!(any::try_lock_any_from(&locks, 0).unwrap().0, 0);
+    assert_eq!(any::try_lock_any_from(&locks, 0).unwrap().0, 1);
+    assert_eq!(any::try_lock_any_from(&locks, 0).unwrap().0, 0);
+    queues[0].assert_acquired_times(2);
+    queues[0].assert_failed_times(1);
+    assert!(!queues[0].is_delaying());
+}
+
+#[test]
+fn a_held_lock_fails_a_pass_like_a_scripted_one() {
+    let queues = queues();
+    let locks: Vec<_> = queues.iter().collect();
+    let held = any::lock_any_from(&locks, 1);
+    assert_eq!(held.0, 1);
+    assert_eq!(any::lock_any_from(&locks, 1).0, 2);
+    queues[1].assert_failed_times(1);
+}
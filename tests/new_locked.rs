@@ -0,0 +1,43 @@
+//! The boot handoff: a lock created held and released with `force_unlock`.
+
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::thread;
+use std::time::Duration;
+
+use mutex::RawSpinLock;
+use mutex::state::MutexState;
+
+#[test]
+fn starts_held() {
+    let lock = RawSpinLock::new_locked(0);
+    assert_eq!(lock.state(), MutexState::Locked);
+}
+
+#[test]
+fn secondaries_wait_until_force_unlock_then_enter_one_at_a_time() {
+    mutex::enable_raw_atomics();
+    static LOCK: RawSpinLock<Vec<usize>> = RawSpinLock::new_locked(Vec::new());
+    static INSIDE: AtomicUsize = AtomicUsize::new(0);
+    assert!(LOCK.try_lock().is_none());
+    thread::scope(|s| {
+        for id in 0..4 {
+            s.spawn(move || {
+                let mut guard = LOCK.lock();
+                assert_eq!(INSIDE.fetch_add(1, Ordering::SeqCst), 0);
+                guard.push(id);
+                thread::sleep(Duration::from_millis(5));
+                INSIDE.fetch_sub(1, Ordering::SeqCst);
+            });
+        }
+        thread::sleep(Duration::from_millis(50));
+        // SAFETY: nothing holds a guard; the boot side is done with the data.
+        unsafe {
+            assert!(LOCK.no_lock().is_empty());
+            LOCK.force_unlock();
+        }
+    });
+    let mut entered = LOCK.lock().clone();
+    entered.sort_unstable();
+    assert_eq!(entered, [0, 1, 2, 3]);
+}
@@ -0,0 +1,97 @@
+//! `no_lock` guards overlapping real acquisitions under `no-lock-check`.
+
+#![cfg(feature = "no-lock-check")]
+
+use std::panic;
+use std::panic::AssertUnwindSafe;
+use std::thread;
+use std::time::Duration;
+
+use mutex::RawSpinLock;
+
+#[test]
+#[should_panic(expected = "while a no_lock guard is alive")]
+fn locking_during_a_no_lock_guard_panics() {
+    mutex::enable_raw_atomics();
+    let lock = RawSpinLock::new(0);
+    // SAFETY: the overlap is what is being tested; the panic comes first.
+    let _bypass = unsafe { lock.no_lock() };
+    drop(lock.lock());
+}
+
+#[test]
+#[should_panic(expected = "while a no_lock guard is alive")]
+fn try_locking_during_a_no_lock_guard_panics() {
+    mutex::enable_raw_atomics();
+    let lock = RawSpinLock::new(0);
+    // SAFETY: as above.
+    let _bypass = unsafe { lock.no_lock() };
+    drop(lock.try_lock());
+}
+
+#[test]
+#[should_panic(expected = "while it is held")]
+fn no_lock_while_held_panics() {
+    mutex::enable_raw_atomics();
+    let lock = RawSpinLock::new(0);
+    let _guard = lock.lock();
+    // SAFETY: as above.
+    drop(unsafe { lock.no_lock() });
+}
+
+#[test]
+fn sequential_use_is_silent_and_sticky() {
+    mutex::enable_raw_atomics();
+    let lock = RawSpinLock::new(0);
+    assert!(!lock.no_lock_used());
+    // SAFETY: nothing else uses the lock.
+    *unsafe { lock.no_lock() } += 1;
+    *lock.lock() += 1;
+    // SAFETY: as above.
+    *unsafe { lock.no_lock() } += 1;
+    drop(lock.try_lock().unwrap());
+    assert!(lock.no_lock_used());
+    assert_eq!(lock.into_inner(), 3);
+}
+
+#[test]
+#[should_panic(expected = "while a no_lock guard is alive")]
+fn read_only_bypass_guards_count_too() {
+    mutex::enable_raw_atomics();
+    let lock = RawSpinLock::new(0);
+    // SAFETY: as above.
+    let _bypass = unsafe { lock.no_lock_read() };
+    drop(lock.lock());
+}
+
+#[test]
+fn filling_a_new_locked_lock_is_no_overlap() {
+    mutex::enable_raw_atomics();
+    let lock = RawSpinLock::new_locked(Vec::new());
+    thread::scope(|s| {
+        let waiter = s.spawn(|| lock.lock().len());
+        thread::sleep(Duration::from_millis(20));
+        // SAFETY: the initial hold is nobody's, and the waiter is kept out
+        // until `force_unlock`.
+        unsafe { lock.no_lock() }.push(1);
+        // SAFETY: the `no_lock` guard is gone and nobody else holds it.
+        unsafe { lock.force_unlock() };
+        assert_eq!(waiter.join().unwrap(), 1);
+    });
+    // The initial hold is over, so overlaps count again.
+    let _guard = lock.lock();
+    // SAFETY: the overlap is what is being tested; the panic comes first.
+    let overlap = panic::catch_unwind(AssertUnwindSafe(|| drop(unsafe { lock.no_lock() })));
+    assert!(overlap.is_err());
+}
+
+#[test]
+#[should_panic(expected = "force_unlock on lock")]
+fn ending_the_initial_hold_under_a_no_lock_guard_panics() {
+    mutex::enable_raw_atomics();
+    let lock = RawSpinLock::new_locked(0);
+    // SAFETY: the overlap is what is being tested; the panic comes first.
+    let _bypass = unsafe { lock.no_lock() };
+    // SAFETY: as above.
+    unsafe { lock.force_unlock() };
+}
@@ -0,0 +1,63 @@
+//! `Oneshot` handing a value to another thread, and refusing a second send
+//! or a second receipt. Runs under Miri, which checks the value is moved
+//! out and dropped exactly once.
+
+use std::thread;
+
+use mutex::oneshot::Oneshot;
+
+#[test]
+fn send_then_recv_across_threads() {
+    mutex::enable_raw_atomics();
+    let slot = Oneshot::new();
+    thread::scope(|s| {
+        let receiver = s.spawn(|| slot.recv());
+        assert_eq!(slot.send(vec![1, 2, 3]), Ok(()));
+        assert_eq!(receiver.join().unwrap(), [1, 2, 3]);
+    });
+    assert!(slot.is_sent());
+    assert_eq!(slot.try_recv(), None);
+    assert_eq!(format!("{slot:?}"), r#"Oneshot { state: "taken" }"#);
+}
+
+#[test]
+fn a_second_send_is_rejected() {
+    mutex::enable_raw_atomics();
+    let slot = Oneshot::new();
+    assert!(!slot.is_sent());
+    assert_eq!(slot.try_recv(), None);
+    assert_eq!(slot.send(String::from("first")), Ok(()));
+    assert_eq!(
+        slot.send(String::from("second")),
+        Err(String::from("second"))
+    );
+    assert_eq!(slot.try_recv().as_deref(), Some("first"));
+    // Still rejected once the value has been taken.
+    assert_eq!(slot.send(String::from("third")), Err(String::from("third")));
+}
+
+#[test]
+fn racing_receivers_get_the_value_once() {
+    mutex::enable_raw_atomics();
+    let slot = Oneshot::new();
+    let got: Vec<_> = thread::scope(|s| {
+        let receivers: Vec<_> = (0..3).map(|_| s.spawn(|| slot.try_recv())).collect();
+        slot.send(7u32).unwrap();
+        let mut got: Vec<_> = receivers.into_iter().map(|r| r.join().unwrap()).collect();
+        got.push(slot.try_recv());
+        got
+    });
+    assert_eq!(got.into_iter().flatten().collect::<Vec<_>>(), [7]);
+}
+
+#[test]
+fn an_unreceived_value_drops_with_the_slot() {
+    use std::rc::Rc;
+
+    let value = Rc::new(());
+    let slot = Oneshot::new();
+    slot.send(Rc::clone(&value)).unwrap();
+    assert_eq!(Rc::strong_count(&value), 2);
+    drop(slot);
+    assert_eq!(Rc::strong_count(&value), 1);
+}
@@ -0,0 +1,88 @@
+//! Lock levels under the `lock-ordering` feature.
+
+#![cfg(feature = "lock-ordering")]
+
+use std::panic;
+use std::panic::AssertUnwindSafe;
+
+use mutex::RawSpinLock;
+
+static SCHEDULER: RawSpinLock<u32> = RawSpinLock::with_level(0, 1);
+static RUNQUEUE: RawSpinLock<u32> = RawSpinLock::with_level(0, 2);
+static TASK: RawSpinLock<u32> = RawSpinLock::with_level(0, 3);
+
+#[test]
+fn increasing_levels_nest() {
+    mutex::enable_raw_atomics();
+    let scheduler = SCHEDULER.lock();
+    let runqueue = RUNQUEUE.lock();
+    let task = TASK.lock();
+    drop((task, runqueue, scheduler));
+    // Skipping a level is fine too.
+    let scheduler = SCHEDULER.lock();
+    drop(TASK.lock());
+    drop(scheduler);
+}
+
+#[test]
+#[should_panic(expected = "lock order violation: acquiring level 1")]
+fn an_inverted_order_panics() {
+    mutex::enable_raw_atomics();
+    let lock = RawSpinLock::with_level(0, 2);
+    let _held = lock.lock();
+    drop(RawSpinLock::with_level(0, 1).lock());
+}
+
+#[test]
+#[should_panic(expected = "acquiring level 2")]
+fn the_same_level_panics() {
+    mutex::enable_raw_atomics();
+    let first = RawSpinLock::with_level(0, 2);
+    let second = RawSpinLock::with_level(0, 2);
+    let _held = first.lock();
+    drop(second.lock());
+}
+
+#[test]
+fn the_report_names_both_call_sites() {
+    mutex::enable_raw_atomics();
+    let outer = RawSpinLock::with_level(0, 5);
+    let inner = RawSpinLock::with_level(0, 4);
+    let guard = outer.lock();
+    let held_at = line!() - 1;
+    let payload = panic::catch_unwind(AssertUnwindSafe(|| drop(inner.lock()))).unwrap_err();
+    let acquired_at = line!() - 1;
+    let message = payload.downcast::<String>().unwrap();
+    assert!(
+        message.contains(&format!("{}:{acquired_at}:", file!())),
+        "{message}"
+    );
+    assert!(
+        message.contains(&format!("{}:{held_at}:", file!())),
+        "{message}"
+    );
+    drop(guard);
+    // The failed acquisition did not leave anything held.
+    drop(inner.lock());
+}
+
+#[test]
+fn level_zero_and_try_lock_are_unchecked() {
+    mutex::enable_raw_atomics();
+    let high = RawSpinLock::with_level(0, 10);
+    let low = RawSpinLock::with_level(0, 1);
+    let unordered = RawSpinLock::new(0);
+    let _high = high.lock();
+    drop(unordered.lock());
+    drop(low.try_lock().unwrap());
+}
+
+#[test]
+#[should_panic(expected = "while holding level 3")]
+fn a_try_locked_lock_counts_as_held() {
+    mutex::enable_raw_atomics();
+    let first = RawSpinLock::with_level(0, 3);
+    let second = RawSpinLock::with_level(0, 3);
+    let _held = first.try_lock().unwrap();
+    drop(second.lock());
+}
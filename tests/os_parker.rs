@@ -0,0 +1,120 @@
+//! Contended `RawSpinLock`s blocking through `OsParker`, on each system it
+//! has a native wait for.
+
+#![cfg(all(
+    feature = "std",
+    any(
+        target_os = "linux",
+        target_os = "android",
+        windows,
+        target_os = "macos",
+        target_os = "ios",
+    )
+))]
+
+use std::thread;
+use std::time::Duration;
+
+use mutex::RawSpinLock;
+use mutex::park::OsParker;
+
+const THREADS: usize = 4;
+const ROUNDS: usize = if cfg!(miri) { 20 } else { 2_000 };
+
+fn setup() {
+    mutex::enable_raw_atomics();
+    let _ = mutex::park::set_parker::<OsParker>();
+}
+
+/// CPU time the calling thread has used.
+#[cfg(unix)]
+fn thread_cpu_time() -> Duration {
+    let mut time = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    // SAFETY: `time` is a valid out-pointer.
+    assert_eq!(
+        unsafe { libc::clock_gettime(libc::CLOCK_THREAD_CPUTIME_ID, &mut time) },
+        0
+    );
+    Duration::new(time.tv_sec as u64, time.tv_nsec as u32)
+}
+
+/// CPU time the calling thread has used.
+#[cfg(windows)]
+fn thread_cpu_time() -> Duration {
+    use core::ffi::c_void;
+
+    #[repr(C)]
+    #[derive(Default)]
+    struct FileTime {
+        low: u32,
+        high: u32,
+    }
+
+    #[link(name = "kernel32")]
+    unsafe extern "system" {
+        fn GetCurrentThread() -> *mut c_void;
+        fn GetThreadTimes(
+            thread: *mut c_void,
+            creation: *mut FileTime,
+            exit: *mut FileTime,
+            kernel: *mut FileTime,
+            user: *mut FileTime,
+        ) -> i32;
+    }
+
+    let mut times: [FileTime; 4] = Default::default();
+    let [creation, exit, kernel, user] = &mut times;
+    // SAFETY: the pseudo-handle always refers to the calling thread, and
+    // each pointer is a valid out-pointer.
+    assert_ne!(
+        unsafe { GetThreadTimes(GetCurrentThread(), creation, exit, kernel, user) },
+        0
+    );
+    // In units of 100ns.
+    let ticks = |time: &FileTime| u64::from(time.high) << 32 | u64::from(time.low);
+    Duration::from_nanos((ticks(kernel) + ticks(user)) * 100)
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn a_blocked_waiter_uses_little_cpu() {
+    setup();
+    static LOCK: RawSpinLock<u32> = RawSpinLock::new(0);
+    let held = LOCK.lock();
+    let waiter = thread::spawn(|| {
+        let start = thread_cpu_time();
+        *LOCK.lock() += 1;
+        thread_cpu_time() - start
+    });
+    let wait = Duration::from_millis(300);
+    thread::sleep(wait);
+    drop(held);
+    let used = waiter.join().unwrap();
+    // A spinning waiter would burn most of the wait.
+    assert!(used < wait / 4, "waiter used {used:?} of {wait:?}");
+    assert_eq!(*LOCK.lock(), 1);
+}
+
+#[test]
+fn contended_waiters_are_all_woken() {
+    setup();
+    let lock = RawSpinLock::new(0);
+    thread::scope(|s| {
+        for _ in 0..THREADS {
+            s.spawn(|| {
+                for _ in 0..ROUNDS {
+                    let mut guard = lock.lock();
+                    *guard += 1;
+                    // Long enough, now and then, for the others to park.
+                    if *guard % 100 == 0 {
+                        thread::sleep(Duration::from_millis(1));
+                    }
+                }
+            });
+        }
+    });
+    assert_eq!(lock.into_inner(), THREADS * ROUNDS);
+}
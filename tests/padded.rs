@@ -0,0 +1,59 @@
+//! Where `PaddedSpinLock` and `SplitSpinLock` put the lock word and the
+//! data: the word first, and the split lock's data on the next cache line.
+
+use std::sync::atomic::AtomicU8;
+use std::sync::atomic::Ordering;
+
+use mutex::padded::CachePadded;
+use mutex::padded::PaddedSpinLock;
+use mutex::padded::SplitSpinLock;
+
+const LINE: usize = align_of::<CachePadded<u8>>();
+
+/// The byte at the start of `lock`, where the lock word should be.
+fn first_byte<L>(lock: &L) -> u8 {
+    // SAFETY: the lock starts with its `AtomicU8` word, and nothing else
+    // uses the lock while this reads it.
+    unsafe { &*std::ptr::from_ref(lock).cast::<AtomicU8>() }.load(Ordering::Relaxed)
+}
+
+fn offset_in<L, T>(lock: &L, data: &T) -> usize {
+    std::ptr::from_ref(data).addr() - std::ptr::from_ref(lock).addr()
+}
+
+#[test]
+fn a_split_lock_keeps_the_word_and_the_data_a_line_apart() {
+    mutex::enable_raw_atomics();
+    let lock = SplitSpinLock::new([0u8; 256]);
+    assert_eq!(std::ptr::from_ref(&lock).addr() % LINE, 0);
+    assert_eq!(first_byte(&lock), 0);
+    let guard = lock.lock();
+    assert_ne!(first_byte(&lock), 0, "the word is not at offset 0");
+    let offset = offset_in(&lock, &*guard);
+    drop(guard);
+    assert_eq!(first_byte(&lock), 0);
+    if cfg!(feature = "stats") {
+        // The counters' own line sits in between.
+        assert!(offset > LINE && offset % LINE == 0, "data at {offset}");
+    } else {
+        assert_eq!(offset, LINE);
+        assert_eq!(size_of::<SplitSpinLock<[u8; 256]>>(), LINE + 256);
+        assert_eq!(size_of::<SplitSpinLock<u8>>(), 2 * LINE);
+    }
+}
+
+#[test]
+fn a_padded_lock_has_a_line_to_itself() {
+    mutex::enable_raw_atomics();
+    let locks: [PaddedSpinLock<u32>; 2] = [
+        CachePadded::new(mutex::RawSpinLock::new(0)),
+        CachePadded::new(mutex::RawSpinLock::new(0)),
+    ];
+    let [first, second] = &locks;
+    assert_eq!(std::ptr::from_ref(first).addr() % LINE, 0);
+    assert!(offset_in(first, second) >= LINE);
+    let guard = first.lock();
+    assert_ne!(first_byte(first), 0);
+    assert_eq!(first_byte(second), 0);
+    assert!(offset_in(first, &*guard) < LINE || cfg!(feature = "stats"));
+}
@@ -0,0 +1,51 @@
+//! The panic hook's report of the locks a panicking thread holds. The hook
+//! writes to standard error, so the test runs itself again as a child that
+//! panics and reads the report from the child's stderr.
+
+#![cfg(feature = "panic-hook")]
+
+use std::process::Command;
+
+use mutex::RawSpinLock;
+
+mutex::registered_static! {
+    static CONFIG: RawSpinLock<u32> = 0;
+}
+
+const CHILD: &str = "MUTEX_PANIC_HOOK_CHILD";
+const TEST: &str = "the_report_names_the_held_lock";
+
+#[test]
+fn the_report_names_the_held_lock() {
+    if std::env::var_os(CHILD).is_some() {
+        mutex::enable_raw_atomics();
+        mutex::held::install_panic_hook();
+        let _guard = CONFIG.lock();
+        panic!("with the config locked");
+    }
+    let line = line!() - 3;
+    let output = Command::new(std::env::current_exe().unwrap())
+        .args(["--exact", TEST, "--nocapture", "--test-threads=1"])
+        .env(CHILD, "1")
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains(&format!("thread '{TEST}' panicked holding:")),
+        "{stderr}"
+    );
+    let name = if cfg!(any(feature = "registry", feature = "lockdep")) {
+        "CONFIG"
+    } else {
+        "<unnamed>"
+    };
+    let held = stderr
+        .lines()
+        .find(|held| held.starts_with(name))
+        .unwrap_or_else(|| panic!("no line for {name}: {stderr}"));
+    assert!(
+        held.contains(&format!("acquired at {}:{line}:", file!())),
+        "{held}"
+    );
+}
@@ -0,0 +1,109 @@
+//! Contended `RawSpinLock`s blocking through a counting wrapper around
+//! `StdParker`.
+
+#![cfg(all(feature = "std", target_os = "linux"))]
+
+use std::sync::Mutex;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::thread;
+use std::time::Duration;
+
+use mutex::RawSpinLock;
+use mutex::park::Parker;
+use mutex::park::StdParker;
+
+static PARKS: AtomicUsize = AtomicUsize::new(0);
+static UNPARKS: AtomicUsize = AtomicUsize::new(0);
+/// Lock addresses passed to `unpark_one`.
+static UNPARKED: Mutex<Vec<usize>> = Mutex::new(Vec::new());
+
+struct Counting;
+
+impl Parker for Counting {
+    fn park(addr: usize, validate: &dyn Fn() -> bool) {
+        PARKS.fetch_add(1, Ordering::SeqCst);
+        StdParker::park(addr, validate);
+    }
+
+    fn unpark_one(addr: usize) {
+        UNPARKS.fetch_add(1, Ordering::SeqCst);
+        UNPARKED.lock().unwrap().push(addr);
+        StdParker::unpark_one(addr);
+    }
+}
+
+fn setup() {
+    mutex::enable_raw_atomics();
+    let _ = mutex::park::set_parker::<Counting>();
+}
+
+/// CPU time the calling thread has used.
+fn thread_cpu_time() -> Duration {
+    let mut time = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    // SAFETY: `time` is a valid out-pointer.
+    assert_eq!(
+        unsafe { libc::clock_gettime(libc::CLOCK_THREAD_CPUTIME_ID, &mut time) },
+        0
+    );
+    Duration::new(time.tv_sec as u64, time.tv_nsec as u32)
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn a_blocked_waiter_uses_little_cpu() {
+    setup();
+    static LOCK: RawSpinLock<u32> = RawSpinLock::new(0);
+    let held = LOCK.lock();
+    let waiter = thread::spawn(|| {
+        let start = thread_cpu_time();
+        *LOCK.lock() += 1;
+        thread_cpu_time() - start
+    });
+    let wait = Duration::from_millis(300);
+    thread::sleep(wait);
+    drop(held);
+    let used = waiter.join().unwrap();
+    // A spinning waiter would burn most of the wait.
+    assert!(used < wait / 4, "waiter used {used:?} of {wait:?}");
+    assert_eq!(*LOCK.lock(), 1);
+}
+
+#[test]
+fn unlock_wakes_a_parked_waiter() {
+    setup();
+    static LOCK: RawSpinLock<u32> = RawSpinLock::new(0);
+    let held = LOCK.lock();
+    let (parks, unparks) = (PARKS.load(Ordering::SeqCst), UNPARKS.load(Ordering::SeqCst));
+    let waiter = thread::spawn(|| *LOCK.lock() += 1);
+    while PARKS.load(Ordering::SeqCst) == parks {
+        thread::yield_now();
+    }
+    drop(held);
+    waiter.join().unwrap();
+    assert!(UNPARKS.load(Ordering::SeqCst) > unparks);
+    assert_eq!(*LOCK.lock(), 1);
+}
+
+#[test]
+fn uncontended_unlocks_wake_nobody() {
+    setup();
+    let lock = RawSpinLock::new(0);
+    for _ in 0..100 {
+        *lock.lock() += 1;
+    }
+    // Other tests may park concurrently, so only this lock's address counts.
+    let addr = std::ptr::from_ref(&lock).addr();
+    let size = size_of_val(&lock);
+    assert!(
+        !UNPARKED
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|unparked| (addr..addr + size).contains(unparked))
+    );
+    assert_eq!(lock.into_inner(), 100);
+}
@@ -0,0 +1,90 @@
+//! Guards against a counting `PreemptionControl` hook.
+
+use std::cell::Cell;
+use std::panic;
+use std::panic::AssertUnwindSafe;
+
+use mutex::RawSpinLock;
+use mutex::RwSpinLock;
+use mutex::preempt::PreemptionControl;
+
+thread_local! {
+    /// The kernel's preemption counter, per thread.
+    static DISABLED: Cell<usize> = const { Cell::new(0) };
+    static ENABLES: Cell<usize> = const { Cell::new(0) };
+}
+
+struct Counter;
+
+impl PreemptionControl for Counter {
+    fn disable() {
+        DISABLED.set(DISABLED.get() + 1);
+    }
+
+    fn enable() {
+        DISABLED.set(DISABLED.get().checked_sub(1).expect("unbalanced enable"));
+        ENABLES.set(ENABLES.get() + 1);
+    }
+}
+
+fn setup() {
+    mutex::enable_raw_atomics();
+    let _ = mutex::preempt::set_preemption_control::<Counter>();
+}
+
+#[test]
+fn disabled_while_held_and_nested() {
+    setup();
+    let a = RawSpinLock::new(0);
+    let b = RawSpinLock::new(0);
+    let outer = a.lock();
+    assert_eq!(DISABLED.get(), 1);
+    let inner = b.lock();
+    assert_eq!(DISABLED.get(), 2);
+    drop(inner);
+    assert_eq!(DISABLED.get(), 1);
+    drop(outer);
+    assert_eq!(DISABLED.get(), 0);
+}
+
+#[test]
+fn a_failed_try_lock_leaves_preemption_alone() {
+    setup();
+    let lock = RawSpinLock::new(0);
+    let guard = lock.lock();
+    assert!(lock.try_lock().is_none());
+    assert_eq!(DISABLED.get(), 1);
+    drop(guard);
+    assert_eq!(DISABLED.get(), 0);
+}
+
+#[test]
+fn rw_guards_disable_too() {
+    setup();
+    let lock = RwSpinLock::new(0);
+    let first = lock.read();
+    let second = lock.read();
+    assert_eq!(DISABLED.get(), 2);
+    drop((first, second));
+    *lock.write() += 1;
+    assert_eq!(DISABLED.get(), 0);
+}
+
+#[test]
+fn balanced_across_a_panic_in_the_critical_section() {
+    setup();
+    let lock = RawSpinLock::new(0);
+    let rw = RwSpinLock::new(0);
+    let enables = ENABLES.get();
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        let _guard = lock.lock();
+        let _write = rw.write();
+        assert_eq!(DISABLED.get(), 2);
+        panic!("in the critical section");
+    }));
+    assert!(result.is_err());
+    assert_eq!(DISABLED.get(), 0);
+    assert_eq!(ENABLES.get() - enables, 2);
+    assert!(lock.try_lock().is_some());
+    assert!(rw.try_write().is_some());
+}
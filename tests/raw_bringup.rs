@@ -0,0 +1,49 @@
+//! `raw::lock` guards nested on one word before raw atomics are enabled,
+//! with the outer one alive across the enable; in a binary of its own so
+//! nothing has enabled raw atomics before it starts.
+
+// With `portable-atomic` the words are that crate's types.
+#![cfg(not(feature = "portable-atomic"))]
+
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicU8;
+use std::sync::atomic::Ordering;
+use std::thread;
+use std::time::Duration;
+
+use mutex::raw;
+
+#[test]
+fn only_the_marking_guard_clears_the_mark() {
+    static WORD: AtomicU8 = AtomicU8::new(0);
+    let outer = raw::lock(&WORD);
+    let inner = raw::try_lock(&WORD).unwrap();
+    assert!(!outer.holds_lock() && !inner.holds_lock());
+    drop(inner);
+    assert_ne!(
+        WORD.load(Ordering::Relaxed),
+        0,
+        "inner guard cleared the mark"
+    );
+    let forgotten = raw::lock(&WORD);
+    std::mem::forget(forgotten);
+
+    mutex::enable_raw_atomics();
+    // The outer guard still holds the word for real lockers.
+    assert!(raw::try_lock(&WORD).is_none());
+    let entered = AtomicBool::new(false);
+    thread::scope(|s| {
+        s.spawn(|| {
+            let guard = raw::lock(&WORD);
+            assert!(guard.holds_lock());
+            entered.store(true, Ordering::SeqCst);
+        });
+        thread::sleep(Duration::from_millis(20));
+        assert!(!entered.load(Ordering::SeqCst));
+        drop(outer);
+    });
+    assert!(entered.load(Ordering::SeqCst));
+    assert_eq!(WORD.load(Ordering::Relaxed), 0);
+    let last = raw::try_lock(&WORD).unwrap();
+    assert!(last.holds_lock());
+}
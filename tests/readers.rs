@@ -0,0 +1,83 @@
+//! `RwSpinLock` naming the threads that hold its read locks, and counting
+//! the ones beyond its slots.
+
+#![cfg(feature = "reader-tracking")]
+
+use std::sync::Barrier;
+use std::thread;
+
+use mutex::RwSpinLock;
+use mutex::owner;
+use mutex::readers::READER_SLOTS;
+
+#[test]
+fn the_readers_are_the_threads_holding_read_guards() {
+    mutex::enable_raw_atomics();
+    let lock = RwSpinLock::new(0);
+    let holding = Barrier::new(READER_SLOTS + 2);
+    let checked = Barrier::new(READER_SLOTS + 2);
+    let mut owners: Vec<_> = thread::scope(|s| {
+        let readers: Vec<_> = (0..=READER_SLOTS)
+            .map(|i| {
+                let (lock, holding, checked) = (&lock, &holding, &checked);
+                thread::Builder::new()
+                    .name(format!("reader-{i}"))
+                    .spawn_scoped(s, move || {
+                        let guard = lock.read();
+                        let me = owner::current().unwrap();
+                        holding.wait();
+                        checked.wait();
+                        drop(guard);
+                        let name = thread::current().name().unwrap().to_owned();
+                        (me, name)
+                    })
+                    .unwrap()
+            })
+            .collect();
+        holding.wait();
+        // One more reader than slots, so one of them is only counted.
+        let listed: Vec<_> = lock.current_readers().collect();
+        assert_eq!(listed.len(), READER_SLOTS);
+        assert_eq!(lock.untracked_readers(), 1);
+        for reader in &listed {
+            assert_eq!(reader.location.unwrap().file(), file!());
+        }
+        checked.wait();
+        let owners: Vec<_> = readers.into_iter().map(|r| r.join().unwrap()).collect();
+        let mut names: Vec<_> = listed
+            .iter()
+            .map(|reader| {
+                let (_, name) = owners.iter().find(|(me, _)| *me == reader.owner).unwrap();
+                name.as_str()
+            })
+            .collect();
+        names.sort_unstable();
+        names.dedup();
+        assert_eq!(names.len(), READER_SLOTS, "{names:?}");
+        assert!(names.iter().all(|name| name.starts_with("reader-")));
+        owners
+    });
+    owners.sort_by_key(|(me, _)| me.get());
+    owners.dedup_by_key(|(me, _)| *me);
+    assert_eq!(owners.len(), READER_SLOTS + 1, "ids are per thread");
+    assert_eq!(lock.current_readers().count(), 0);
+    assert_eq!(lock.untracked_readers(), 0);
+    assert!(lock.try_write().is_some());
+}
+
+#[test]
+fn a_reader_on_this_thread_is_listed_as_this_thread() {
+    mutex::enable_raw_atomics();
+    let lock = RwSpinLock::new(0);
+    let guard = lock.read();
+    let line = line!() - 1;
+    let listed: Vec<_> = lock.current_readers().collect();
+    let [reader] = listed[..] else {
+        panic!("{listed:?}");
+    };
+    assert_eq!(Some(reader.owner), owner::current());
+    let location = reader.location.unwrap();
+    assert_eq!((location.file(), location.line()), (file!(), line));
+    drop(guard);
+    assert_eq!(lock.current_readers().count(), 0);
+}
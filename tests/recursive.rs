@@ -0,0 +1,50 @@
+//! Recursive `RawSpinLock` acquisition under `owner-tracking`.
+
+#![cfg(feature = "owner-tracking")]
+
+use std::panic;
+use std::panic::AssertUnwindSafe;
+use std::thread;
+use std::time::Duration;
+
+use mutex::RawSpinLock;
+
+#[test]
+#[should_panic(expected = "recursive lock acquisition")]
+fn relocking_from_the_holder_panics() {
+    mutex::enable_raw_atomics();
+    let lock = RawSpinLock::new(0);
+    let _guard = lock.lock();
+    drop(lock.lock());
+}
+
+#[test]
+fn the_panic_names_the_first_acquisition() {
+    mutex::enable_raw_atomics();
+    let lock = RawSpinLock::new(0);
+    let guard = lock.lock();
+    let first = line!() - 1;
+    let payload = panic::catch_unwind(AssertUnwindSafe(|| drop(lock.lock()))).unwrap_err();
+    let message = payload.downcast::<String>().unwrap();
+    assert!(
+        message.contains(&format!("already acquired at {}:{first}:", file!())),
+        "{message}"
+    );
+    drop(guard);
+    // The holder's guard still released the lock.
+    drop(lock.try_lock().unwrap());
+}
+
+#[test]
+fn contention_with_another_thread_waits() {
+    mutex::enable_raw_atomics();
+    let lock = RawSpinLock::new(0);
+    thread::scope(|s| {
+        let guard = lock.lock();
+        let waiter = s.spawn(|| *lock.lock() += 1);
+        thread::sleep(Duration::from_millis(50));
+        drop(guard);
+        waiter.join().unwrap();
+    });
+    assert_eq!(lock.into_inner(), 1);
+}
@@ -0,0 +1,73 @@
+//! The debug-build check that a `RawSpinLockGuard` is released where it was
+//! acquired.
+
+#![cfg(debug_assertions)]
+
+use std::panic;
+use std::thread;
+
+use mutex::RawSpinLock;
+use mutex::RawSpinLockGuard;
+
+/// Smuggles a guard into another thread, as a mistaken `move` closure
+/// around an unsafe `Send` wrapper would.
+struct AssertSend<T>(T);
+
+// SAFETY: only used to provoke the check under test.
+unsafe impl<T> Send for AssertSend<T> {}
+
+static LOCK: RawSpinLock<u32> = RawSpinLock::new(0);
+
+fn drop_elsewhere(guard: RawSpinLockGuard<'static, u32>) -> thread::Result<()> {
+    let guard = AssertSend(guard);
+    thread::spawn(move || {
+        let guard = guard;
+        drop(guard.0);
+    })
+    .join()
+}
+
+#[test]
+fn dropping_a_guard_on_another_thread_panics() {
+    mutex::enable_raw_atomics();
+    let guard = LOCK.lock();
+    let line = line!() - 1;
+    let payload = drop_elsewhere(guard).unwrap_err();
+    let message = payload.downcast::<String>().unwrap();
+    assert!(message.contains("released by"), "{message}");
+    assert!(
+        message.contains(&format!("at {}:{line}:", file!())),
+        "{message}"
+    );
+    // The check runs after the release, so the lock is free again.
+    drop(LOCK.try_lock().unwrap());
+}
+
+#[test]
+#[should_panic(expected = "released by")]
+fn a_foreign_release_is_reported_to_the_test() {
+    mutex::enable_raw_atomics();
+    let lock = Box::leak(Box::new(RawSpinLock::new(0)));
+    if let Err(payload) = drop_elsewhere(lock.lock()) {
+        panic::resume_unwind(payload);
+    }
+}
+
+#[test]
+fn allow_foreign_unlock_opts_out() {
+    mutex::enable_raw_atomics();
+    let lock = Box::leak(Box::new(RawSpinLock::new(0)));
+    let mut guard = lock.lock();
+    RawSpinLockGuard::allow_foreign_unlock(&mut guard);
+    drop_elsewhere(guard).unwrap();
+    drop(lock.try_lock().unwrap());
+}
+
+#[test]
+fn handoff_guards_are_not_checked() {
+    mutex::enable_raw_atomics();
+    let lock = Box::leak(Box::new(RawSpinLock::new(0)));
+    let guard = lock.lock_for_handoff();
+    thread::spawn(move || guard.complete()).join().unwrap();
+    drop(lock.try_lock().unwrap());
+}
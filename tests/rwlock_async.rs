@@ -0,0 +1,133 @@
+//! `read_async` and `write_async` driven by a minimal executor.
+
+#![cfg(feature = "async")]
+
+use std::future::Future;
+use std::pin::pin;
+use std::sync::Arc;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::task::Context;
+use std::task::Poll;
+use std::task::Wake;
+use std::task::Waker;
+use std::thread;
+use std::thread::Thread;
+
+use mutex::RwSpinLock;
+
+struct Unpark(Thread, AtomicUsize);
+
+impl Wake for Unpark {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.1.fetch_add(1, Ordering::SeqCst);
+        self.0.unpark();
+    }
+}
+
+fn block_on<F: Future>(future: F) -> F::Output {
+    let waker = Waker::from(Arc::new(Unpark(thread::current(), AtomicUsize::new(0))));
+    let mut cx = Context::from_waker(&waker);
+    let mut future = pin!(future);
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output;
+        }
+        thread::park();
+    }
+}
+
+fn counting_waker() -> (Waker, Arc<Unpark>) {
+    let unpark = Arc::new(Unpark(thread::current(), AtomicUsize::new(0)));
+    (Waker::from(unpark.clone()), unpark)
+}
+
+#[test]
+fn async_readers_and_writers_exclude_each_other() {
+    mutex::enable_raw_atomics();
+    static LOCK: RwSpinLock<(u64, u64)> = RwSpinLock::new((0, 0));
+    let threads: Vec<_> = (0..8)
+        .map(|i| {
+            thread::spawn(move || {
+                for _ in 0..2_000 {
+                    if i % 2 == 0 {
+                        let mut guard = block_on(LOCK.write_async());
+                        guard.0 += 1;
+                        guard.1 += 1;
+                    } else {
+                        let guard = block_on(LOCK.read_async());
+                        assert_eq!(guard.0, guard.1);
+                    }
+                }
+            })
+        })
+        .collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    assert_eq!(*LOCK.read(), (8_000, 8_000));
+}
+
+#[test]
+fn a_release_wakes_the_waiting_writer() {
+    mutex::enable_raw_atomics();
+    let lock = RwSpinLock::new(0);
+    let (waker, wakes) = counting_waker();
+    let mut cx = Context::from_waker(&waker);
+    let reader = lock.read();
+    let mut write = pin!(lock.write_async());
+    assert!(write.as_mut().poll(&mut cx).is_pending());
+    assert_eq!(wakes.1.load(Ordering::SeqCst), 0);
+    drop(reader);
+    assert_eq!(wakes.1.load(Ordering::SeqCst), 1);
+    let Poll::Ready(mut guard) = write.as_mut().poll(&mut cx) else {
+        panic!("the writer was woken but could not lock");
+    };
+    *guard = 1;
+}
+
+#[test]
+fn a_queued_writer_holds_back_new_async_readers() {
+    mutex::enable_raw_atomics();
+    let lock = RwSpinLock::new(0);
+    let (waker, wakes) = counting_waker();
+    let mut cx = Context::from_waker(&waker);
+    let reader = lock.read();
+    let mut write = pin!(lock.write_async());
+    assert!(write.as_mut().poll(&mut cx).is_pending());
+    let mut read = pin!(lock.read_async());
+    assert!(read.as_mut().poll(&mut cx).is_pending());
+    // Synchronous readers are not held back.
+    drop(lock.try_read().unwrap());
+    assert_eq!(wakes.1.load(Ordering::SeqCst), 1);
+    drop(reader);
+    let Poll::Ready(guard) = write.as_mut().poll(&mut cx) else {
+        panic!("the writer was woken but could not lock");
+    };
+    assert!(read.as_mut().poll(&mut cx).is_pending());
+    drop(guard);
+    assert!(read.as_mut().poll(&mut cx).is_ready());
+}
+
+#[test]
+fn dropping_a_queued_writer_releases_the_readers_it_held_back() {
+    mutex::enable_raw_atomics();
+    let lock = RwSpinLock::new(0);
+    let (waker, wakes) = counting_waker();
+    let mut cx = Context::from_waker(&waker);
+    let writer = lock.write();
+    let mut write = Box::pin(lock.write_async());
+    assert!(write.as_mut().poll(&mut cx).is_pending());
+    drop(writer);
+    let woken = wakes.1.load(Ordering::SeqCst);
+    let mut read = pin!(lock.read_async());
+    // The woken writer has not run yet, so the reader queues behind it.
+    assert!(read.as_mut().poll(&mut cx).is_pending());
+    drop(write);
+    assert!(wakes.1.load(Ordering::SeqCst) > woken);
+    assert!(read.as_mut().poll(&mut cx).is_ready());
+}
@@ -0,0 +1,124 @@
+//! Random sequences of `RwSpinLock` operations, run on one thread against a
+//! reference model of the lock word. After every step the decoded word
+//! must match the model; proptest shrinks a divergence to a minimal
+//! sequence.
+
+use proptest::prelude::*;
+
+use mutex::RwSpinLock;
+use mutex::RwSpinLockReadGuard;
+use mutex::RwSpinLockWriteGuard;
+use mutex::state::RwState;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Model {
+    Unlocked,
+    Readers(usize),
+    Writer,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Op {
+    TryRead,
+    TryWrite,
+    /// `read`, only taken where it cannot block.
+    Read,
+    /// `write`, only taken where it cannot block.
+    Write,
+    /// Drops the read guard at this index, modulo the number held.
+    ReleaseRead(usize),
+    ReleaseWrite,
+}
+
+fn op() -> impl Strategy<Value = Op> {
+    prop_oneof![
+        Just(Op::TryRead),
+        Just(Op::TryWrite),
+        Just(Op::Read),
+        Just(Op::Write),
+        any::<usize>().prop_map(Op::ReleaseRead),
+        Just(Op::ReleaseWrite),
+    ]
+}
+
+struct Held<'a> {
+    readers: Vec<RwSpinLockReadGuard<'a, u32>>,
+    writer: Option<RwSpinLockWriteGuard<'a, u32>>,
+}
+
+impl Held<'_> {
+    fn model(&self) -> Model {
+        match (self.readers.len(), &self.writer) {
+            (0, None) => Model::Unlocked,
+            (0, Some(_)) => Model::Writer,
+            (readers, None) => Model::Readers(readers),
+            (_, Some(_)) => unreachable!("the model never holds both"),
+        }
+    }
+}
+
+fn check(lock: &RwSpinLock<u32>, model: Model) -> Result<(), TestCaseError> {
+    let expected = match model {
+        Model::Unlocked => RwState::decode(0),
+        Model::Readers(readers) => RwState::decode(readers),
+        Model::Writer => RwState::decode(mutex::raw::WRITE_FLAG),
+    };
+    prop_assert_eq!(lock.state(), expected);
+    Ok(())
+}
+
+proptest! {
+    #[test]
+    fn rwlock_matches_the_model(
+        max_readers in 1usize..4,
+        ops in prop::collection::vec(op(), 1..64),
+    ) {
+        mutex::enable_raw_atomics();
+        let lock = RwSpinLock::with_max_readers(0, max_readers);
+        let mut held = Held { readers: Vec::new(), writer: None };
+        for op in ops {
+            let model = held.model();
+            match op {
+                Op::TryRead => {
+                    let guard = lock.try_read();
+                    let admitted = match model {
+                        Model::Unlocked => true,
+                        Model::Readers(readers) => readers < max_readers,
+                        Model::Writer => false,
+                    };
+                    prop_assert_eq!(guard.is_some(), admitted);
+                    held.readers.extend(guard);
+                }
+                Op::TryWrite => {
+                    let guard = lock.try_write();
+                    prop_assert_eq!(guard.is_some(), model == Model::Unlocked);
+                    if guard.is_some() {
+                        held.writer = guard;
+                    }
+                }
+                Op::Read => {
+                    if model == Model::Unlocked
+                        || matches!(model, Model::Readers(readers) if readers < max_readers)
+                    {
+                        held.readers.push(lock.read());
+                    }
+                }
+                Op::Write => {
+                    if model == Model::Unlocked {
+                        let mut guard = lock.write();
+                        *guard += 1;
+                        held.writer = Some(guard);
+                    }
+                }
+                Op::ReleaseRead(index) => {
+                    if !held.readers.is_empty() {
+                        let index = index % held.readers.len();
+                        drop(held.readers.swap_remove(index));
+                    }
+                }
+                Op::ReleaseWrite => drop(held.writer.take()),
+            }
+            check(&lock, held.model())?;
+        }
+    }
+}
@@ -0,0 +1,142 @@
+//! Shuttle runs of bigger scenarios than loom can explore, over random
+//! schedules. Run them with
+//! `RUSTFLAGS="--cfg shuttle" cargo test --release --test shuttle`.
+//!
+//! When a schedule fails, shuttle prints it along with the instructions for
+//! replaying it with `shuttle::replay`.
+
+#![cfg(shuttle)]
+
+use std::collections::VecDeque;
+
+use shuttle::sync::Arc;
+use shuttle::thread;
+
+use mutex::RawSpinLock;
+use mutex::RwSpinLock;
+
+const SCHEDULES: usize = 200;
+
+/// Five philosophers, forks taken lower-numbered first, each counting its
+/// meals on both forks: every meal must land on both.
+#[test]
+fn dining_philosophers() {
+    const PHILOSOPHERS: usize = 5;
+    const MEALS: usize = 3;
+    mutex::enable_raw_atomics();
+    shuttle::check_random(
+        || {
+            let forks: Arc<Vec<RawSpinLock<usize>>> =
+                Arc::new((0..PHILOSOPHERS).map(|_| RawSpinLock::new(0)).collect());
+            let philosophers: Vec<_> = (0..PHILOSOPHERS)
+                .map(|seat| {
+                    let forks = forks.clone();
+                    thread::spawn(move || {
+                        let (left, right) = (seat, (seat + 1) % PHILOSOPHERS);
+                        let (first, second) = (left.min(right), left.max(right));
+                        for _ in 0..MEALS {
+                            let mut first = forks[first].lock();
+                            let mut second = forks[second].lock();
+                            *first += 1;
+                            *second += 1;
+                        }
+                    })
+                })
+                .collect();
+            for philosopher in philosophers {
+                philosopher.join().unwrap();
+            }
+            for fork in forks.iter() {
+                assert_eq!(*fork.lock(), 2 * MEALS);
+            }
+        },
+        SCHEDULES,
+    );
+}
+
+/// Two producers and two consumers over a locked queue: everything sent is
+/// received exactly once.
+#[test]
+fn mpmc_queue_balances() {
+    const PER_PRODUCER: usize = 10;
+    mutex::enable_raw_atomics();
+    shuttle::check_random(
+        || {
+            let queue = Arc::new(RawSpinLock::new(VecDeque::new()));
+            let producers: Vec<_> = (0..2)
+                .map(|producer| {
+                    let queue = queue.clone();
+                    thread::spawn(move || {
+                        for item in 0..PER_PRODUCER {
+                            queue.lock().push_back(producer * PER_PRODUCER + item);
+                        }
+                    })
+                })
+                .collect();
+            let consumers: Vec<_> = (0..2)
+                .map(|_| {
+                    let queue = queue.clone();
+                    thread::spawn(move || {
+                        let mut received = Vec::new();
+                        while received.len() < PER_PRODUCER {
+                            match queue.lock().pop_front() {
+                                Some(item) => received.push(item),
+                                None => thread::yield_now(),
+                            }
+                        }
+                        received
+                    })
+                })
+                .collect();
+            for producer in producers {
+                producer.join().unwrap();
+            }
+            let mut received: Vec<_> = consumers
+                .into_iter()
+                .flat_map(|consumer| consumer.join().unwrap())
+                .collect();
+            received.sort_unstable();
+            assert_eq!(received, (0..2 * PER_PRODUCER).collect::<Vec<_>>());
+            assert!(queue.lock().is_empty());
+        },
+        SCHEDULES,
+    );
+}
+
+/// Writers update a pair whose halves must match, readers check it, and
+/// the writers' updates all count.
+#[test]
+fn rw_spin_lock_readers_and_writers() {
+    const WRITES: usize = 5;
+    mutex::enable_raw_atomics();
+    shuttle::check_random(
+        || {
+            let lock = Arc::new(RwSpinLock::new((0, 0)));
+            let threads: Vec<_> = (0..4)
+                .map(|i| {
+                    let lock = lock.clone();
+                    thread::spawn(move || {
+                        for _ in 0..WRITES {
+                            if i % 2 == 0 {
+                                let mut pair = lock.write();
+                                pair.0 += 1;
+                                thread::yield_now();
+                                pair.1 += 1;
+                            } else if let Some(pair) = lock.try_read() {
+                                assert_eq!(pair.0, pair.1, "saw a torn pair");
+                            } else {
+                                let pair = lock.read();
+                                assert_eq!(pair.0, pair.1, "saw a torn pair");
+                            }
+                        }
+                    })
+                })
+                .collect();
+            for thread in threads {
+                thread.join().unwrap();
+            }
+            assert_eq!(*lock.read(), (2 * WRITES, 2 * WRITES));
+        },
+        SCHEDULES,
+    );
+}
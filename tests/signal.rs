@@ -0,0 +1,118 @@
+//! A SIGUSR1 handler sharing a `RawSpinLock` with normal code through
+//! `try_lock`, and `lock_signal_safe` keeping the signal out of the
+//! critical section through a `pthread_sigmask` hook.
+
+#![cfg(unix)]
+
+use std::mem::MaybeUninit;
+use std::ptr;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+
+use mutex::RawSpinLock;
+use mutex::signal;
+use mutex::signal::SignalMask;
+
+static LOCK: RawSpinLock<usize> = RawSpinLock::new(0);
+/// Handler runs that got the lock, and ones that found it held.
+static TOOK: AtomicUsize = AtomicUsize::new(0);
+static BUSY: AtomicUsize = AtomicUsize::new(0);
+
+extern "C" fn on_sigusr1(_: libc::c_int) {
+    // The signal-safe subset: `try_lock` and dropping its guard.
+    match LOCK.try_lock() {
+        Some(mut guard) => {
+            *guard += 1;
+            TOOK.fetch_add(1, Ordering::SeqCst);
+        }
+        None => {
+            BUSY.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+}
+
+/// Blocks SIGUSR1; the saved mask is whether it was blocked already.
+struct BlockUsr1;
+
+fn set_usr1(how: libc::c_int) -> bool {
+    // SAFETY: the sets are initialized by `sigemptyset` before use.
+    unsafe {
+        let mut set = MaybeUninit::uninit();
+        libc::sigemptyset(set.as_mut_ptr());
+        libc::sigaddset(set.as_mut_ptr(), libc::SIGUSR1);
+        let mut old = MaybeUninit::uninit();
+        libc::sigemptyset(old.as_mut_ptr());
+        assert_eq!(
+            libc::pthread_sigmask(how, set.as_ptr(), old.as_mut_ptr()),
+            0
+        );
+        libc::sigismember(old.as_ptr(), libc::SIGUSR1) == 1
+    }
+}
+
+impl SignalMask for BlockUsr1 {
+    fn block() -> usize {
+        set_usr1(libc::SIG_BLOCK).into()
+    }
+
+    fn restore(saved: usize) {
+        if saved == 0 {
+            set_usr1(libc::SIG_UNBLOCK);
+        }
+    }
+}
+
+fn raise() {
+    // SAFETY: the handler is installed and only touches atomics.
+    assert_eq!(unsafe { libc::raise(libc::SIGUSR1) }, 0);
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn a_handler_shares_the_lock_through_try_lock() {
+    mutex::enable_raw_atomics();
+    signal::set_signal_mask::<BlockUsr1>().unwrap();
+    // SAFETY: a plain `sigaction` install of a handler that only uses the
+    // signal-safe subset.
+    unsafe {
+        let mut action: libc::sigaction = std::mem::zeroed();
+        action.sa_sigaction = on_sigusr1 as extern "C" fn(libc::c_int) as usize;
+        libc::sigemptyset(&mut action.sa_mask);
+        assert_eq!(libc::sigaction(libc::SIGUSR1, &action, ptr::null_mut()), 0);
+    }
+
+    // Free: the handler takes the lock.
+    raise();
+    assert_eq!(
+        (TOOK.load(Ordering::SeqCst), BUSY.load(Ordering::SeqCst)),
+        (1, 0)
+    );
+
+    // Held by the code it interrupted: `try_lock` fails instead of
+    // deadlocking.
+    let guard = LOCK.lock();
+    raise();
+    assert_eq!(
+        (TOOK.load(Ordering::SeqCst), BUSY.load(Ordering::SeqCst)),
+        (1, 1)
+    );
+    drop(guard);
+
+    // Masked while held, so the signal waits for the release and the
+    // handler then finds the lock free.
+    let mut guard = LOCK.lock_signal_safe();
+    raise();
+    *guard += 10;
+    assert_eq!(
+        (TOOK.load(Ordering::SeqCst), BUSY.load(Ordering::SeqCst)),
+        (1, 1)
+    );
+    drop(guard);
+    assert_eq!(
+        (TOOK.load(Ordering::SeqCst), BUSY.load(Ordering::SeqCst)),
+        (2, 1)
+    );
+    assert_eq!(*LOCK.lock(), 12);
+    // And the guard left SIGUSR1 unblocked again.
+    assert!(!set_usr1(libc::SIG_UNBLOCK));
+}
@@ -0,0 +1,42 @@
+//! A `RawSpinLock` guard taken before raw atomics are enabled and kept
+//! across the enable; in a binary of its own so nothing has enabled raw
+//! atomics before it starts.
+
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::thread;
+use std::time::Duration;
+
+use mutex::RawSpinLock;
+
+#[test]
+fn a_guard_held_across_the_enable_keeps_the_lock() {
+    let lock = RawSpinLock::new(0);
+    let mut outer = lock.lock();
+    assert!(!outer.holds_lock());
+    *outer = 1;
+    // A nested permissive guard leaves the outer one's mark.
+    drop(lock.try_lock().unwrap());
+
+    mutex::enable_raw_atomics();
+    assert!(lock.try_lock().is_none());
+    let entered = AtomicBool::new(false);
+    thread::scope(|s| {
+        s.spawn(|| {
+            let mut guard = lock.lock();
+            assert!(guard.holds_lock());
+            assert_eq!(*guard, 2);
+            *guard += 1;
+            entered.store(true, Ordering::SeqCst);
+        });
+        thread::sleep(Duration::from_millis(20));
+        assert!(!entered.load(Ordering::SeqCst));
+        assert!(lock.try_lock().is_none());
+        *outer += 1;
+        drop(outer);
+    });
+    assert!(entered.load(Ordering::SeqCst));
+    let last = lock.try_lock().unwrap();
+    assert!(last.holds_lock());
+    assert_eq!(*last, 3);
+}
@@ -0,0 +1,113 @@
+//! `SpinPtr`'s tagged word, ownership of the box, and contention. Small
+//! enough to run under Miri, which checks the tagging keeps provenance and
+//! every box is freed exactly once.
+
+#![cfg(feature = "alloc")]
+
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::thread;
+
+use mutex::Lock;
+use mutex::spin_ptr::SpinPtr;
+
+const THREADS: usize = if cfg!(miri) { 2 } else { 4 };
+const ROUNDS: usize = if cfg!(miri) { 20 } else { 2_000 };
+
+/// Counts drops, so leaks and double frees both show up.
+#[derive(Debug)]
+struct Node {
+    value: usize,
+    drops: &'static AtomicUsize,
+}
+
+impl Drop for Node {
+    fn drop(&mut self) {
+        self.drops.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+fn node(value: usize, drops: &'static AtomicUsize) -> Box<Node> {
+    Box::new(Node { value, drops })
+}
+
+#[test]
+fn the_lock_bit_does_not_leak_into_the_pointer() {
+    mutex::enable_raw_atomics();
+    let mut ptr = SpinPtr::new(Box::new(41u64));
+    {
+        let mut guard = ptr.lock();
+        **guard.as_mut().unwrap() += 1;
+        assert!(ptr.try_lock().is_none());
+        assert_eq!(format!("{ptr:?}"), "SpinPtr(<locked>)");
+    }
+    assert_eq!(format!("{ptr:?}"), "SpinPtr(Some(42))");
+    assert_eq!(ptr.get_mut(), Some(&mut 42));
+    assert_eq!(ptr.into_inner(), Some(Box::new(42)));
+}
+
+#[test]
+fn empty_is_a_null_word() {
+    mutex::enable_raw_atomics();
+    let mut ptr = SpinPtr::<u64>::empty();
+    assert!(ptr.lock().is_none());
+    assert_eq!(ptr.get_mut(), None);
+    *ptr.lock() = Some(Box::new(1));
+    assert_eq!(ptr.lock().take(), Some(Box::new(1)));
+    assert_eq!(ptr.into_inner(), None);
+    assert!(SpinPtr::<u64>::default().into_inner().is_none());
+}
+
+#[test]
+fn replace_locked_publishes_on_release_and_drops_are_exact() {
+    mutex::enable_raw_atomics();
+    static DROPS: AtomicUsize = AtomicUsize::new(0);
+    let ptr = SpinPtr::from(node(1, &DROPS));
+    let old = ptr.lock().replace_locked(Some(node(2, &DROPS))).unwrap();
+    assert_eq!(old.value, 1);
+    assert_eq!(DROPS.load(Ordering::SeqCst), 0);
+    drop(old);
+    assert_eq!(ptr.lock().as_ref().unwrap().value, 2);
+    drop(ptr);
+    assert_eq!(DROPS.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn concurrent_lock_and_replace() {
+    mutex::enable_raw_atomics();
+    static DROPS: AtomicUsize = AtomicUsize::new(0);
+    let ptr = SpinPtr::new(node(0, &DROPS));
+    thread::scope(|s| {
+        for _ in 0..THREADS {
+            s.spawn(|| {
+                for _ in 0..ROUNDS {
+                    let mut guard = Lock::lock(&ptr);
+                    let next = guard.as_ref().unwrap().value + 1;
+                    drop(guard.replace_locked(Some(node(next, &DROPS))));
+                }
+            });
+        }
+    });
+    let last = ptr.into_inner().unwrap();
+    assert_eq!(last.value, THREADS * ROUNDS);
+    // Every box but the last was replaced and dropped.
+    assert_eq!(DROPS.load(Ordering::SeqCst), THREADS * ROUNDS);
+    drop(last);
+    assert_eq!(DROPS.load(Ordering::SeqCst), THREADS * ROUNDS + 1);
+}
+
+#[test]
+fn a_forgotten_guard_leaks_rather_than_double_frees() {
+    mutex::enable_raw_atomics();
+    static DROPS: AtomicUsize = AtomicUsize::new(0);
+    let mut ptr = SpinPtr::new(node(0, &DROPS));
+    let mut guard = ptr.lock();
+    let owned = guard.take();
+    std::mem::forget(guard);
+    assert!(ptr.get_mut().is_none());
+    assert!(ptr.into_inner().is_none());
+    assert_eq!(DROPS.load(Ordering::SeqCst), 0);
+    // The forgotten guard had handed its box on; that owner frees it once.
+    drop(owned);
+    assert_eq!(DROPS.load(Ordering::SeqCst), 1);
+}
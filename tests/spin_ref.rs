@@ -0,0 +1,70 @@
+//! `SpinRef` standing in for a lock over data the caller already owns.
+//! Small enough to run under Miri, which checks the guards' aliasing.
+
+use std::panic;
+use std::panic::AssertUnwindSafe;
+use std::thread;
+
+use mutex::Lock;
+use mutex::RawSpinLock;
+use mutex::spin_ref::SpinRef;
+
+/// A subsystem written against the lock trait.
+fn fill<L: Lock<[u8]> + ?Sized>(lock: &L, value: u8) -> *const u8 {
+    let mut guard = lock.lock();
+    guard.fill(value);
+    guard.as_ptr()
+}
+
+fn bump<L: Lock<u64>>(lock: &L) {
+    *lock.lock() += 1;
+}
+
+#[test]
+fn borrows_in_place_without_copying() {
+    let mut buffer = [0u8; 4096];
+    let start = buffer.as_ptr();
+    let lock = SpinRef::from_mut(&mut buffer[..]);
+    assert_eq!(fill(&lock, 7), start);
+    let buffer = lock.into_mut();
+    assert!(buffer.iter().all(|&byte| byte == 7));
+}
+
+#[test]
+fn stands_in_for_a_real_lock() {
+    mutex::enable_raw_atomics();
+    let real = RawSpinLock::new(0);
+    bump(&real);
+    let mut value = real.into_inner();
+    bump(&SpinRef::from(&mut value));
+    assert_eq!(value, 2);
+}
+
+#[test]
+fn a_second_guard_is_refused() {
+    let mut value = 0u64;
+    let lock = SpinRef::from_mut(&mut value);
+    let guard = lock.lock();
+    assert!(lock.try_lock().is_none());
+    let relock = panic::catch_unwind(AssertUnwindSafe(|| drop(lock.lock())));
+    assert!(relock.is_err());
+    drop(guard);
+    *lock.try_lock().unwrap() += 1;
+    assert_eq!(*lock.into_mut(), 1);
+}
+
+#[test]
+fn can_move_to_another_thread() {
+    let mut value = 0u64;
+    let lock = SpinRef::from_mut(&mut value);
+    let lock = thread::scope(|s| {
+        s.spawn(move || {
+            bump(&lock);
+            lock
+        })
+        .join()
+        .unwrap()
+    });
+    bump(&lock);
+    assert_eq!(value, 2);
+}
@@ -0,0 +1,76 @@
+//! What `spin_static!` expands to: arrays of locks built from non-`Copy`
+//! data, single locks of several types, and the names they register.
+
+use mutex::RawSpinLock;
+use mutex::RwSpinLock;
+use mutex::small::SmallSpinLock;
+use mutex::state::MutexState;
+
+struct Uart {
+    sent: Vec<u8>,
+}
+
+impl Uart {
+    const fn new() -> Self {
+        Self { sent: Vec::new() }
+    }
+}
+
+mutex::spin_static! {
+    static FORKS: [RawSpinLock<Vec<usize>>; 5] = Vec::new();
+    /// Attributes and visibility are kept.
+    #[allow(dead_code)]
+    pub(crate) static CONSOLE: RawSpinLock<Uart> = Uart::new();
+    static TABLE: RwSpinLock<[u16; 4]> = [0; 4];
+    static FLAGS: [SmallSpinLock<u8>; 2] = 0;
+}
+
+#[test]
+fn array_elements_are_separate_locks() {
+    mutex::enable_raw_atomics();
+    let _first = FORKS[0].lock();
+    assert_eq!(FORKS[0].state(), MutexState::Locked);
+    for fork in &FORKS[1..] {
+        assert_eq!(fork.state(), MutexState::Unlocked);
+        fork.lock().push(1);
+    }
+    assert!(FORKS[0].try_lock().is_none());
+    assert_eq!(
+        FORKS
+            .iter()
+            .skip(1)
+            .map(|fork| fork.lock().len())
+            .sum::<usize>(),
+        4
+    );
+}
+
+#[test]
+fn single_locks_and_other_lock_types() {
+    mutex::enable_raw_atomics();
+    CONSOLE.lock().sent.extend_from_slice(b"ok");
+    assert_eq!(CONSOLE.lock().sent, b"ok");
+    TABLE.write()[1] = 7;
+    assert_eq!(*TABLE.read(), [0, 7, 0, 0]);
+    *FLAGS[1].lock() = 1;
+    assert_eq!(FLAGS[0].get_cloned(), 0);
+    assert_eq!(FLAGS[1].get_cloned(), 1);
+}
+
+#[test]
+#[cfg(feature = "registry")]
+fn raw_spin_locks_are_registered_under_the_static_name() {
+    mutex::enable_raw_atomics();
+    let _fork = FORKS[3].lock();
+    let _console = CONSOLE.lock();
+    let mut dump = String::new();
+    mutex::registry::dump(&mut dump).unwrap();
+    assert!(
+        dump.lines().any(|line| line.starts_with("FORKS (")),
+        "{dump}"
+    );
+    assert!(
+        dump.lines().any(|line| line.starts_with("CONSOLE (")),
+        "{dump}"
+    );
+}
@@ -0,0 +1,104 @@
+//! `RawSpinLock::split_arc` halves driven from two threads.
+
+#![cfg(feature = "alloc")]
+
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::thread;
+
+use mutex::RawSpinLock;
+
+/// Counts how many times the protected value is dropped.
+struct Tracked<'a> {
+    count: u64,
+    drops: &'a AtomicUsize,
+}
+
+impl Drop for Tracked<'_> {
+    fn drop(&mut self) {
+        self.drops.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+#[test]
+fn both_halves_exclude_each_other() {
+    mutex::enable_raw_atomics();
+    let (a, b) = RawSpinLock::new((0u64, 0u64)).split_arc();
+    let other = thread::spawn(move || {
+        for _ in 0..2_000 {
+            let mut guard = b.lock();
+            guard.0 += 1;
+            guard.1 += 1;
+        }
+        b
+    });
+    for _ in 0..2_000 {
+        let mut guard = a.lock();
+        assert_eq!(guard.0, guard.1);
+        guard.0 += 1;
+        guard.1 += 1;
+    }
+    let b = other.join().unwrap();
+    assert_eq!(a.try_reunite(b).unwrap(), (4_000, 4_000));
+}
+
+#[test]
+fn try_lock_fails_while_the_other_half_holds_the_lock() {
+    mutex::enable_raw_atomics();
+    let (a, b) = RawSpinLock::new(0).split_arc();
+    let guard = a.lock();
+    assert!(thread::scope(|s| s
+        .spawn(|| b.try_lock().is_none())
+        .join()
+        .unwrap()));
+    drop(guard);
+    assert!(b.try_lock().is_some());
+}
+
+#[test]
+fn reuniting_returns_the_value_exactly_once() {
+    mutex::enable_raw_atomics();
+    static DROPS: AtomicUsize = AtomicUsize::new(0);
+    let (a, b) = RawSpinLock::new(Tracked {
+        count: 0,
+        drops: &DROPS,
+    })
+    .split_arc();
+    let b = thread::spawn(move || {
+        b.lock().count += 1;
+        b
+    })
+    .join()
+    .unwrap();
+    a.lock().count += 1;
+    let value = a.try_reunite(b).unwrap();
+    assert_eq!(value.count, 2);
+    assert_eq!(DROPS.load(Ordering::SeqCst), 0);
+    drop(value);
+    assert_eq!(DROPS.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn into_inner_waits_for_the_other_half_to_drop() {
+    mutex::enable_raw_atomics();
+    let (a, b) = RawSpinLock::new(vec![1]).split_arc();
+    let a = a.into_inner().unwrap_err();
+    thread::spawn(move || {
+        b.lock().push(2);
+        drop(b);
+    })
+    .join()
+    .unwrap();
+    assert_eq!(a.into_inner().ok().unwrap(), [1, 2]);
+}
+
+#[test]
+fn halves_of_different_locks_do_not_reunite() {
+    mutex::enable_raw_atomics();
+    let (a, b) = RawSpinLock::new(1).split_arc();
+    let (c, d) = RawSpinLock::new(2).split_arc();
+    assert!(!a.is_pair_of(&c));
+    let mutex::mutex::ReuniteError(a, c) = a.try_reunite(c).unwrap_err();
+    assert_eq!(a.try_reunite(b).unwrap(), 1);
+    assert_eq!(c.try_reunite(d).unwrap(), 2);
+}
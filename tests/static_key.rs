@@ -0,0 +1,55 @@
+//! The static-key fallback: with no `CodePatcher` registered, the enable
+//! leaves every gate site as it was and the checks answer from the flag.
+//! In a binary of its own so nothing has enabled raw atomics before it
+//! starts.
+
+#![cfg(feature = "static-key")]
+
+use std::thread;
+
+use mutex::RawSpinLock;
+use mutex::static_key;
+use mutex::static_key::GateSite;
+
+const THREADS: usize = if cfg!(miri) { 2 } else { 4 };
+const ROUNDS: usize = if cfg!(miri) { 20 } else { 20_000 };
+
+fn code(site: &GateSite) -> [u8; 5] {
+    // SAFETY: a site is the address of a 5-byte instruction in the text.
+    unsafe { std::ptr::with_exposed_provenance::<[u8; 5]>(site.address()).read_unaligned() }
+}
+
+#[test]
+fn without_a_patcher_every_check_loads_the_flag() {
+    let sites = static_key::sites();
+    if cfg!(all(target_arch = "x86_64", target_os = "linux", not(miri))) {
+        assert!(!sites.is_empty());
+    }
+    for site in sites {
+        assert_eq!(code(site), GateSite::UNPATCHED_CODE);
+    }
+    assert!(!mutex::raw_atomics_enabled());
+
+    mutex::enable_raw_atomics();
+    assert!(mutex::raw_atomics_enabled());
+    for site in sites {
+        assert_eq!(
+            code(site),
+            GateSite::UNPATCHED_CODE,
+            "patched at {:#x}",
+            site.address()
+        );
+    }
+
+    let lock = RawSpinLock::new(0);
+    thread::scope(|s| {
+        for _ in 0..THREADS {
+            s.spawn(|| {
+                for _ in 0..ROUNDS {
+                    *lock.lock() += 1;
+                }
+            });
+        }
+    });
+    assert_eq!(lock.into_inner(), THREADS * ROUNDS);
+}
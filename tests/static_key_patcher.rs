@@ -0,0 +1,64 @@
+//! The static-key enable with a mock `CodePatcher`: it is offered every
+//! gate site, after the flag is set, while each site still holds the
+//! unpatched code. It declines them all, so the checks keep working from
+//! the flag. In a binary of its own so nothing has enabled raw atomics
+//! before it starts.
+
+#![cfg(feature = "static-key")]
+
+use std::sync::Mutex;
+use std::thread;
+
+use mutex::RawSpinLock;
+use mutex::static_key;
+use mutex::static_key::CodePatcher;
+use mutex::static_key::GateSite;
+
+const THREADS: usize = if cfg!(miri) { 2 } else { 4 };
+const ROUNDS: usize = if cfg!(miri) { 20 } else { 20_000 };
+
+/// Addresses of the sites offered so far.
+static OFFERED: Mutex<Vec<usize>> = Mutex::new(Vec::new());
+
+struct Declining;
+
+impl CodePatcher for Declining {
+    fn patch_site(site: &GateSite) -> bool {
+        assert!(
+            mutex::raw_atomics_enabled(),
+            "offered before the flag was set"
+        );
+        // SAFETY: a site is the address of a 5-byte instruction in the
+        // text.
+        let code = unsafe {
+            std::ptr::with_exposed_provenance::<[u8; 5]>(site.address()).read_unaligned()
+        };
+        assert_eq!(code, GateSite::UNPATCHED_CODE);
+        OFFERED.lock().unwrap().push(site.address());
+        false
+    }
+}
+
+#[test]
+fn the_patcher_is_offered_every_site_and_may_decline() {
+    static_key::set_code_patcher::<Declining>().unwrap();
+    assert!(static_key::set_code_patcher::<Declining>().is_err());
+    assert!(OFFERED.lock().unwrap().is_empty());
+
+    mutex::enable_raw_atomics();
+    let offered = std::mem::take(&mut *OFFERED.lock().unwrap());
+    let sites: Vec<_> = static_key::sites().iter().map(GateSite::address).collect();
+    assert_eq!(offered, sites);
+
+    let lock = RawSpinLock::new(0);
+    thread::scope(|s| {
+        for _ in 0..THREADS {
+            s.spawn(|| {
+                for _ in 0..ROUNDS {
+                    *lock.lock() += 1;
+                }
+            });
+        }
+    });
+    assert_eq!(lock.into_inner(), THREADS * ROUNDS);
+}
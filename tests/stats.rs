@@ -0,0 +1,90 @@
+//! `RawSpinLock` counters over scripted acquisitions, and the lock staying
+//! off its own cache line when the `stats` feature is off.
+
+#[cfg(feature = "stats")]
+use std::thread;
+#[cfg(feature = "stats")]
+use std::time::Duration;
+
+use mutex::RawSpinLock;
+#[cfg(not(feature = "stats"))]
+use mutex::padded::CachePadded;
+#[cfg(feature = "stats")]
+use mutex::stats::LockStats;
+
+/// Holds `lock` on this thread for `hold` while another one waits for it.
+#[cfg(feature = "stats")]
+fn contend(lock: &RawSpinLock<u32>, hold: Duration) {
+    let held = lock.lock();
+    thread::scope(|s| {
+        let waiter = s.spawn(|| *lock.lock() += 1);
+        thread::sleep(hold);
+        drop(held);
+        waiter.join().unwrap();
+    });
+}
+
+#[cfg(feature = "stats")]
+#[test]
+fn uncontended_acquisitions_count_without_spins() {
+    mutex::enable_raw_atomics();
+    let lock = RawSpinLock::new(0);
+    for _ in 0..3 {
+        *lock.lock() += 1;
+    }
+    drop(lock.try_lock().unwrap());
+    let held = lock.lock();
+    assert!(lock.try_lock().is_none());
+    drop(held);
+    assert_eq!(
+        lock.stats(),
+        LockStats {
+            acquisitions: 5,
+            ..LockStats::default()
+        }
+    );
+}
+
+#[cfg(feature = "stats")]
+#[test]
+fn a_contended_acquisition_counts_its_spins() {
+    mutex::enable_raw_atomics();
+    let lock = RawSpinLock::new(0);
+    contend(&lock, Duration::from_millis(20));
+    let stats = lock.stats();
+    assert_eq!(stats.acquisitions, 2);
+    assert_eq!(stats.contended_acquisitions, 1);
+    assert!(stats.spins > 0, "{stats:?}");
+    assert_eq!(stats.max_spins, stats.spins);
+
+    lock.reset_stats();
+    assert_eq!(lock.stats(), LockStats::default());
+    *lock.lock() += 1;
+    assert_eq!(lock.stats().acquisitions, 1);
+}
+
+#[cfg(feature = "stats")]
+#[test]
+fn max_spins_is_the_longest_single_wait() {
+    mutex::enable_raw_atomics();
+    let lock = RawSpinLock::new(0);
+    contend(&lock, Duration::from_millis(50));
+    let long = lock.stats().max_spins;
+    for _ in 0..3 {
+        contend(&lock, Duration::from_millis(1));
+    }
+    let stats = lock.stats();
+    assert_eq!(stats.contended_acquisitions, 4);
+    // The short waits add to the total but leave the maximum to the long
+    // one, which by itself spun more than all of them together.
+    assert_eq!(stats.max_spins, long, "{stats:?}");
+    assert!(stats.spins > long && long > stats.spins - long, "{stats:?}");
+}
+
+#[cfg(not(feature = "stats"))]
+#[test]
+fn without_the_feature_the_counters_take_no_cache_line() {
+    // The counters are `CachePadded`, which would raise the alignment.
+    assert!(align_of::<RawSpinLock<u8>>() < align_of::<CachePadded<u8>>());
+    assert!(size_of::<RawSpinLock<u8>>() < size_of::<CachePadded<u8>>());
+}
@@ -0,0 +1,197 @@
+//! The spans and events `RawSpinLock` emits, as a recording subscriber sees
+//! them. The subscriber is process-wide, so each test looks only at records
+//! naming its own lock.
+
+#![cfg(feature = "tracing")]
+
+use std::fmt;
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::sync::Once;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::thread;
+use std::time::Duration;
+
+use mutex::RawSpinLock;
+use tracing::Event;
+use tracing::Metadata;
+use tracing::Subscriber;
+use tracing::field::Field;
+use tracing::field::Visit;
+use tracing::span;
+
+#[derive(Clone, Debug, PartialEq)]
+enum Record {
+    /// A span's name and fields, under its id.
+    Span(u64, String),
+    Enter(u64),
+    Exit(u64),
+    /// An event's fields, message first.
+    Event(String),
+}
+
+#[derive(Default)]
+struct Fields(String);
+
+impl Visit for Fields {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            self.0.insert_str(0, &format!("{value:?};"));
+        } else {
+            write!(self.0, " {}={value:?}", field.name()).unwrap();
+        }
+    }
+}
+
+struct Recorder {
+    next_id: AtomicU64,
+    records: Mutex<Vec<Record>>,
+}
+
+impl Recorder {
+    fn push(&self, record: Record) {
+        self.records.lock().unwrap().push(record);
+    }
+}
+
+// On the reference, so the global dispatcher can share the `static`.
+impl Subscriber for &'static Recorder {
+    fn enabled(&self, _: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, span: &span::Attributes<'_>) -> span::Id {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let mut fields = Fields(span.metadata().name().to_owned());
+        span.record(&mut fields);
+        self.push(Record::Span(id, fields.0));
+        span::Id::from_u64(id)
+    }
+
+    fn record(&self, _: &span::Id, _: &span::Record<'_>) {}
+
+    fn record_follows_from(&self, _: &span::Id, _: &span::Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let mut fields = Fields::default();
+        event.record(&mut fields);
+        self.push(Record::Event(fields.0));
+    }
+
+    fn enter(&self, span: &span::Id) {
+        self.push(Record::Enter(span.into_u64()));
+    }
+
+    fn exit(&self, span: &span::Id) {
+        self.push(Record::Exit(span.into_u64()));
+    }
+}
+
+static RECORDER: Recorder = Recorder {
+    next_id: AtomicU64::new(1),
+    records: Mutex::new(Vec::new()),
+};
+
+fn setup() {
+    static SETUP: Once = Once::new();
+    SETUP.call_once(|| {
+        mutex::enable_raw_atomics();
+        tracing::subscriber::set_global_default(&RECORDER).unwrap();
+    });
+}
+
+/// The spans opened for `lock`, and the events that name it.
+fn records_for<T>(lock: &'static RawSpinLock<T>) -> (Vec<u64>, Vec<Record>) {
+    let start = std::ptr::from_ref(lock) as usize;
+    let names_lock = |fields: &str| {
+        (start..start + size_of_val(lock)).any(|addr| fields.contains(&format!("lock={addr:#x}")))
+    };
+    let records = RECORDER.records.lock().unwrap();
+    let spans: Vec<_> = records
+        .iter()
+        .filter_map(|record| match record {
+            Record::Span(id, fields) if names_lock(fields) => Some(*id),
+            _ => None,
+        })
+        .collect();
+    let mine = records
+        .iter()
+        .filter(|record| match record {
+            Record::Span(id, _) | Record::Enter(id) | Record::Exit(id) => spans.contains(id),
+            Record::Event(fields) => names_lock(fields),
+        })
+        .cloned()
+        .collect();
+    (spans, mine)
+}
+
+#[test]
+fn a_hold_is_a_span_entered_until_the_guard_drops() {
+    setup();
+    static LOCK: RawSpinLock<u32> = RawSpinLock::new(0);
+    *LOCK.lock() += 1;
+    let line = line!() - 1;
+    let (spans, records) = records_for(&LOCK);
+    let [span] = spans[..] else {
+        panic!("{records:?}");
+    };
+    let [
+        Record::Span(_, fields),
+        Record::Enter(entered),
+        Record::Exit(exited),
+    ] = &records[..]
+    else {
+        panic!("{records:?}");
+    };
+    assert!(fields.starts_with("lock held "), "{fields}");
+    assert!(
+        fields.contains(&format!(" location={}:{line}:", file!())),
+        "{fields}"
+    );
+    assert_eq!((*entered, *exited), (span, span));
+}
+
+#[test]
+fn a_failed_try_lock_is_an_event() {
+    setup();
+    static LOCK: RawSpinLock<u32> = RawSpinLock::new(0);
+    let held = LOCK.lock();
+    assert!(LOCK.try_lock().is_none());
+    drop(held);
+    let (_, records) = records_for(&LOCK);
+    let events: Vec<_> = records
+        .iter()
+        .filter(|record| matches!(record, Record::Event(_)))
+        .collect();
+    let [Record::Event(fields)] = events[..] else {
+        panic!("{records:?}");
+    };
+    assert!(fields.starts_with("try_lock failed;"), "{fields}");
+}
+
+#[test]
+fn a_contended_acquisition_is_an_event_with_its_spins() {
+    setup();
+    static LOCK: RawSpinLock<u32> = RawSpinLock::new(0);
+    let held = LOCK.lock();
+    thread::scope(|s| {
+        let waiter = s.spawn(|| *LOCK.lock() += 1);
+        thread::sleep(Duration::from_millis(20));
+        drop(held);
+        waiter.join().unwrap();
+    });
+    let (spans, records) = records_for(&LOCK);
+    assert_eq!(spans.len(), 2, "{records:?}");
+    let event = records.iter().find_map(|record| match record {
+        Record::Event(fields) => Some(fields),
+        _ => None,
+    });
+    let fields = event.unwrap_or_else(|| panic!("{records:?}"));
+    assert!(fields.starts_with("acquired contended lock;"), "{fields}");
+    let spins: usize = fields
+        .rsplit_once(" spins=")
+        .and_then(|(_, spins)| spins.parse().ok())
+        .unwrap_or_else(|| panic!("{fields}"));
+    assert!(spins > 0);
+}
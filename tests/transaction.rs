@@ -0,0 +1,101 @@
+//! `RwSpinLock` write transactions: a commit publishes every change at
+//! once, and a rollback, a drop or a panic leaves the data as it was.
+
+use std::panic;
+use std::thread;
+
+use mutex::RwSpinLock;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Config {
+    low: u32,
+    high: u32,
+}
+
+const START: Config = Config { low: 1, high: 2 };
+
+#[test]
+fn commit_keeps_the_writes() {
+    mutex::enable_raw_atomics();
+    let lock = RwSpinLock::new(vec![1, 2]);
+    let mut tx = lock.write_transaction();
+    tx.push(3);
+    tx[0] = 0;
+    assert_eq!(*tx.original(), [1, 2]);
+    assert_eq!(format!("{tx:?}"), "[0, 2, 3]");
+    tx.commit();
+    assert_eq!(*lock.read(), [0, 2, 3]);
+}
+
+#[test]
+fn rollback_and_drop_restore_the_original() {
+    mutex::enable_raw_atomics();
+    let lock = RwSpinLock::new(START);
+    let mut tx = lock.write_transaction_copy();
+    tx.low = 10;
+    tx.rollback();
+    assert_eq!(*lock.read(), START);
+    {
+        let mut tx = lock.write_transaction_copy();
+        tx.high = 20;
+    }
+    assert_eq!(*lock.read(), START);
+    let mut tx = lock.write_transaction_copy();
+    tx.low = 10;
+    tx.high = 20;
+    tx.commit();
+    assert_eq!(*lock.read(), Config { low: 10, high: 20 });
+}
+
+#[test]
+fn a_panic_mid_transaction_leaves_the_data_alone() {
+    mutex::enable_raw_atomics();
+    let lock = RwSpinLock::new(START);
+    let result = panic::catch_unwind(|| {
+        let mut tx = lock.write_transaction();
+        tx.low = 10;
+        if tx.low > tx.high {
+            panic!("invalid config");
+        }
+        tx.commit();
+    });
+    assert!(result.is_err());
+    assert_eq!(*lock.read(), START);
+    assert!(lock.try_write().is_some());
+}
+
+#[test]
+fn readers_see_the_old_or_the_whole_new_data() {
+    mutex::enable_raw_atomics();
+    let lock = RwSpinLock::new(Config { low: 0, high: 0 });
+    let rounds = if cfg!(miri) { 20 } else { 2_000 };
+    thread::scope(|s| {
+        s.spawn(|| {
+            for round in 1..=rounds {
+                let mut tx = lock.write_transaction_copy();
+                tx.low = round;
+                thread::yield_now();
+                tx.high = round;
+                if round % 3 == 0 {
+                    tx.rollback();
+                } else {
+                    tx.commit();
+                }
+            }
+        });
+        s.spawn(|| {
+            for _ in 0..rounds {
+                let config = *lock.read();
+                assert_eq!(config.low, config.high, "torn: {config:?}");
+            }
+        });
+    });
+    // The last round is not a multiple of 3, so it committed.
+    assert_eq!(
+        *lock.read(),
+        Config {
+            low: rounds,
+            high: rounds
+        }
+    );
+}
@@ -0,0 +1,81 @@
+//! The locks' happens-before edges as ThreadSanitizer sees them; the target
+//! of `cargo +nightly tsan`, which runs it under `-Zsanitizer=thread`. It
+//! also runs under plain `cargo test`.
+//!
+//! One test, so the steps run in order: data handed over from permissive
+//! mode through thread start, then every lock under contention with raw
+//! atomics enabled and the parking path registered.
+
+use std::thread;
+
+use mutex::RawSpinLock;
+use mutex::RwSpinLock;
+use mutex::biased::BiasedSpinLock;
+use mutex::cohort::CohortLock;
+use mutex::hybrid::HybridMutex;
+use mutex::padded::SplitSpinLock;
+use mutex::park::StdParker;
+
+const THREADS: usize = 4;
+const ROUNDS: usize = 500;
+
+fn contend(work: impl Fn() + Sync) {
+    thread::scope(|s| {
+        for _ in 0..THREADS {
+            s.spawn(|| {
+                for _ in 0..ROUNDS {
+                    work();
+                }
+            });
+        }
+    });
+}
+
+#[test]
+fn atomic_mode_is_clean() {
+    // Written in permissive mode, read by threads started after enabling.
+    let handed_over = RawSpinLock::new(Vec::new());
+    handed_over.lock().push(1);
+    mutex::enable_raw_atomics();
+    mutex::park::set_parker::<StdParker>().unwrap();
+    thread::scope(|s| {
+        s.spawn(|| assert_eq!(*handed_over.lock(), [1]));
+    });
+
+    let raw = RawSpinLock::new([0u64; 4]);
+    contend(|| raw.lock().iter_mut().for_each(|word| *word += 1));
+    assert_eq!(raw.into_inner(), [(THREADS * ROUNDS) as u64; 4]);
+
+    let rw = RwSpinLock::new([0u64; 4]);
+    contend(|| {
+        rw.write().iter_mut().for_each(|word| *word += 1);
+        let seen = rw.read();
+        assert!(seen.iter().all(|&word| word == seen[0]));
+    });
+
+    let biased = BiasedSpinLock::new(0);
+    thread::scope(|s| {
+        for _ in 0..THREADS {
+            s.spawn(|| {
+                assert!(biased.claim_bias());
+                for _ in 0..ROUNDS {
+                    *biased.lock() += 1;
+                }
+                biased.revoke_bias();
+            });
+        }
+    });
+    assert_eq!(biased.into_inner(), THREADS * ROUNDS);
+
+    let cohort = CohortLock::<_, 2>::new(0);
+    contend(|| *cohort.lock() += 1);
+    assert_eq!(cohort.into_inner(), THREADS * ROUNDS);
+
+    let hybrid = HybridMutex::new(0);
+    contend(|| *hybrid.lock() += 1);
+    assert_eq!(hybrid.into_inner(), THREADS * ROUNDS);
+
+    let split = SplitSpinLock::new(0);
+    contend(|| *split.lock() += 1);
+    assert_eq!(split.into_inner(), THREADS * ROUNDS);
+}
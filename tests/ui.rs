@@ -0,0 +1,17 @@
+//! Misuses of the guards and of `#[derive(LockProject)]` that must not
+//! compile. The expected errors are the `.stderr` files next to each case;
+//! regenerate them with `TRYBUILD=overwrite cargo test --test ui` after
+//! checking the new output.
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn guard_misuse_does_not_compile() {
+    trybuild::TestCases::new().compile_fail("tests/ui/*.rs");
+}
+
+#[test]
+#[cfg(feature = "derive")]
+#[cfg_attr(miri, ignore)]
+fn derive_misuse_does_not_compile() {
+    trybuild::TestCases::new().compile_fail("tests/ui/derive/*.rs");
+}
@@ -0,0 +1,18 @@
+mod driver {
+    use mutex::project::LockProject;
+
+    #[derive(LockProject)]
+    pub struct Registers {
+        pub status: u8,
+        scratch: u8,
+    }
+}
+
+use driver::RegistersLockProject;
+
+fn uses(lock: &mutex::RawSpinLock<driver::Registers>) {
+    let _ = lock.lock_status();
+    let _ = lock.lock_scratch();
+}
+
+fn main() {}
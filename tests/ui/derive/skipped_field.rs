@@ -0,0 +1,14 @@
+use mutex::RawSpinLock;
+use mutex::project::LockProject;
+
+#[derive(LockProject)]
+struct Device {
+    interrupts: u64,
+    #[lock_project(skip)]
+    model: &'static str,
+}
+
+fn main() {
+    let device = RawSpinLock::new(Device { interrupts: 0, model: "" });
+    let _ = device.lock_model();
+}
@@ -0,0 +1,6 @@
+use mutex::project::LockProject;
+
+#[derive(LockProject)]
+struct Pair(u8, u8);
+
+fn main() {}
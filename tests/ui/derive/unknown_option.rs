@@ -0,0 +1,9 @@
+use mutex::project::LockProject;
+
+#[derive(LockProject)]
+struct Device {
+    #[lock_project(hide)]
+    model: &'static str,
+}
+
+fn main() {}
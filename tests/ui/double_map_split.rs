@@ -0,0 +1,11 @@
+// `map_split` consumes the guard, so it cannot be split twice.
+use mutex::RawSpinLock;
+use mutex::RawSpinLockGuard;
+
+fn main() {
+    let lock = RawSpinLock::new((0u32, 0u32));
+    let guard = lock.lock();
+    let (a, b) = RawSpinLockGuard::map_split(guard, |pair| (&mut pair.0, &mut pair.1));
+    let (c, d) = RawSpinLockGuard::map_split(guard, |pair| (&mut pair.0, &mut pair.1));
+    drop((a, b, c, d));
+}
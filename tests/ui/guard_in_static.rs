@@ -0,0 +1,14 @@
+// A guard of a local lock cannot be stored where 'static is required.
+use std::sync::OnceLock;
+
+use mutex::RawSpinLock;
+use mutex::RawSpinLockGuard;
+
+static GUARD: OnceLock<std::sync::Mutex<Option<RawSpinLockGuard<'static, u32>>>> =
+    OnceLock::new();
+
+fn main() {
+    let lock = RawSpinLock::new(0);
+    let slot = GUARD.get_or_init(Default::default);
+    *slot.lock().unwrap() = Some(lock.lock());
+}
@@ -0,0 +1,11 @@
+// A guard cannot be returned from the function that owns its lock.
+use mutex::RawSpinLock;
+
+fn guard() -> mutex::RawSpinLockGuard<'static, u32> {
+    let lock = RawSpinLock::new(0);
+    lock.lock()
+}
+
+fn main() {
+    drop(guard());
+}
@@ -0,0 +1,11 @@
+// A guard of a lock around non-Send data cannot move to another thread.
+use std::rc::Rc;
+use std::thread;
+
+use mutex::RawSpinLock;
+
+fn main() {
+    let lock: &'static RawSpinLock<Rc<u32>> = Box::leak(Box::new(RawSpinLock::new(Rc::new(0))));
+    let guard = lock.lock();
+    thread::spawn(move || drop(guard));
+}
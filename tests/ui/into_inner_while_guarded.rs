@@ -0,0 +1,10 @@
+// The lock cannot be consumed while a guard still borrows it.
+use mutex::RawSpinLock;
+
+fn main() {
+    let lock = RawSpinLock::new(0);
+    let guard = lock.lock();
+    let value = lock.into_inner();
+    drop(guard);
+    assert_eq!(value, 0);
+}
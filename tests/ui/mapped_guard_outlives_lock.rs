@@ -0,0 +1,14 @@
+// A half from `map_split` cannot outlive the lock either.
+use mutex::RawSpinLock;
+use mutex::RawSpinLockGuard;
+use mutex::sync::MappedSpinLockGuard;
+
+fn half() -> MappedSpinLockGuard<'static, u32> {
+    let lock = RawSpinLock::new((0, 0));
+    let (a, _b) = RawSpinLockGuard::map_split(lock.lock(), |pair| (&mut pair.0, &mut pair.1));
+    a
+}
+
+fn main() {
+    drop(half());
+}
@@ -0,0 +1,105 @@
+//! `WaitQueue` wakeups counted against queued waiters: `wake_one` lets
+//! exactly one through and `wake_all` every one.
+
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::thread;
+use std::time::Duration;
+
+use mutex::wait::WaitQueue;
+
+const WAITERS: usize = 3;
+
+/// Tickets that waiters take to get through, and how often they checked.
+#[derive(Default)]
+struct Gate {
+    queue: WaitQueue,
+    tickets: AtomicUsize,
+    checks: AtomicUsize,
+    through: AtomicUsize,
+}
+
+impl Gate {
+    fn wait(&self) {
+        self.queue.wait_until(|| {
+            self.checks.fetch_add(1, Ordering::SeqCst);
+            let mut left = self.tickets.load(Ordering::SeqCst);
+            while left > 0 {
+                match self.tickets.compare_exchange(
+                    left,
+                    left - 1,
+                    Ordering::SeqCst,
+                    Ordering::SeqCst,
+                ) {
+                    Ok(_) => return true,
+                    Err(actual) => left = actual,
+                }
+            }
+            false
+        });
+        self.through.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// Waits until every waiter has checked twice, the second time with
+    /// the queue locked just before enqueueing, so a wake that locks the
+    /// queue after this finds them all on it.
+    fn until_all_queued(&self) {
+        while self.checks.load(Ordering::SeqCst) < 2 * WAITERS {
+            thread::yield_now();
+        }
+    }
+
+    fn open(&self, tickets: usize) {
+        self.tickets.fetch_add(tickets, Ordering::SeqCst);
+    }
+}
+
+#[test]
+fn wake_one_wakes_exactly_one_waiter() {
+    mutex::enable_raw_atomics();
+    let gate = Gate::default();
+    thread::scope(|s| {
+        for _ in 0..WAITERS {
+            s.spawn(|| gate.wait());
+        }
+        gate.until_all_queued();
+        gate.open(1);
+        assert!(gate.queue.wake_one());
+        while gate.through.load(Ordering::SeqCst) == 0 {
+            thread::yield_now();
+        }
+        thread::sleep(Duration::from_millis(20));
+        assert_eq!(gate.through.load(Ordering::SeqCst), 1);
+        // The other two are still queued.
+        gate.open(WAITERS - 1);
+        assert_eq!(gate.queue.wake_all(), WAITERS - 1);
+    });
+    assert_eq!(gate.through.load(Ordering::SeqCst), WAITERS);
+    assert!(!gate.queue.wake_one());
+}
+
+#[test]
+fn wake_all_wakes_every_waiter() {
+    mutex::enable_raw_atomics();
+    let gate = Gate::default();
+    thread::scope(|s| {
+        for _ in 0..WAITERS {
+            s.spawn(|| gate.wait());
+        }
+        gate.until_all_queued();
+        gate.open(WAITERS);
+        assert_eq!(gate.queue.wake_all(), WAITERS);
+    });
+    assert_eq!(gate.through.load(Ordering::SeqCst), WAITERS);
+    assert_eq!(gate.queue.wake_all(), 0);
+}
+
+#[test]
+fn a_true_condition_does_not_wait() {
+    mutex::enable_raw_atomics();
+    let gate = Gate::default();
+    gate.open(1);
+    gate.wait();
+    assert_eq!(gate.checks.load(Ordering::SeqCst), 1);
+    assert!(!gate.queue.wake_one());
+}
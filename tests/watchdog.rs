@@ -0,0 +1,57 @@
+//! The watchdog reporting a waiter on a second thread that spins past its
+//! lock's threshold, to the registered handler.
+
+#![cfg(feature = "watchdog")]
+
+use std::ops::Range;
+use std::panic::Location;
+use std::sync::Mutex;
+use std::thread;
+
+use mutex::RawSpinLock;
+use mutex::watchdog;
+use mutex::watchdog::WatchdogReport;
+
+static REPORTS: Mutex<Vec<WatchdogReport>> = Mutex::new(Vec::new());
+
+fn record(report: &WatchdogReport) {
+    REPORTS.lock().unwrap().push(*report);
+}
+
+fn address_range<T>(value: &T) -> Range<usize> {
+    let start = std::ptr::from_ref(value) as usize;
+    start..start + size_of_val(value)
+}
+
+#[test]
+fn a_waiter_past_the_threshold_is_reported_once() {
+    mutex::enable_raw_atomics();
+    watchdog::set_watchdog_handler(record).unwrap();
+    let lock = RawSpinLock::new(0);
+    lock.set_spin_threshold(Some(100));
+    let held = lock.lock();
+    let waiting_at = thread::scope(|s| {
+        let waiter = s.spawn(|| {
+            let at = Location::caller();
+            *lock.lock() += 1;
+            at
+        });
+        while REPORTS.lock().unwrap().is_empty() {
+            thread::yield_now();
+        }
+        drop(held);
+        waiter.join().unwrap()
+    });
+    assert_eq!(*lock.lock(), 1);
+
+    let reports = REPORTS.lock().unwrap();
+    let [report] = reports.as_slice() else {
+        panic!("{reports:?}");
+    };
+    assert_eq!(report.spins, 100);
+    assert!(address_range(&lock).contains(&report.lock), "{report}");
+    assert_eq!(report.location.file(), waiting_at.file());
+    assert_eq!(report.location.line(), waiting_at.line() + 1);
+    #[cfg(feature = "track-location")]
+    assert_eq!(report.last_acquired_at.unwrap().file(), file!());
+}
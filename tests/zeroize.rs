@@ -0,0 +1,53 @@
+//! Zeroizing lock payloads: in place through the lock or a guard, and on
+//! drop for `ZeroizingSpinLock`, whose bytes are read back from the
+//! storage it was dropped in. Runs under Miri, which checks the read-back
+//! stays within that storage.
+
+#![cfg(feature = "zeroize")]
+
+use std::mem::MaybeUninit;
+
+use mutex::RawSpinLock;
+use mutex::sync::ZeroizingSpinLock;
+
+const SECRET: [u8; 32] = [0xa5; 32];
+
+#[test]
+fn zeroize_inner_and_the_guard_scrub_in_place() {
+    mutex::enable_raw_atomics();
+    let lock = RawSpinLock::new(SECRET);
+    lock.zeroize_inner();
+    assert_eq!(*lock.lock(), [0; 32]);
+
+    *lock.lock() = SECRET;
+    let mut guard = lock.lock();
+    guard.zeroize();
+    assert_eq!(*guard, [0; 32]);
+    drop(guard);
+
+    let mut lock = RawSpinLock::new(SECRET);
+    zeroize::Zeroize::zeroize(&mut lock);
+    assert_eq!(lock.into_inner(), [0; 32]);
+}
+
+#[test]
+fn dropping_a_zeroizing_lock_scrubs_its_storage() {
+    mutex::enable_raw_atomics();
+    let mut slot = MaybeUninit::<ZeroizingSpinLock<[u8; 32]>>::uninit();
+    let base = slot.as_mut_ptr();
+    // SAFETY: `base` points at storage for one lock.
+    unsafe { base.write(ZeroizingSpinLock::new(SECRET)) };
+    // SAFETY: initialized just above.
+    let lock = unsafe { &*base };
+    let offset = {
+        let guard = lock.lock();
+        assert_eq!(*guard, SECRET);
+        guard.as_ptr().addr() - base.addr()
+    };
+    // SAFETY: the lock is dropped once, and its storage outlives the drop.
+    unsafe { base.drop_in_place() };
+    // SAFETY: the payload bytes are still in `slot`; `u8` has no invalid
+    // values and the drop has just written every one of them.
+    let scrubbed = unsafe { base.byte_add(offset).cast::<[u8; 32]>().read() };
+    assert_eq!(scrubbed, [0; 32]);
+}